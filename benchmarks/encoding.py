@@ -0,0 +1,78 @@
+"""Benchmark converting k-mers to integer format.
+
+Times `kmer_to_int` and `kmer_to_int_unchecked` on random 21-mers and
+31-mers, which is what reading a k-mer list spends its time on, and
+`canonical_kmer_array` on a long random read, which rolls each base of
+the read into a k-mer the way classifying does.
+
+Run from the root of the repo after installing the package:
+
+    python benchmarks/encoding.py --num-kmers 1000000
+"""
+
+import argparse
+import random
+import time
+
+from trio_binning.kmers import (
+    canonical_kmer_array,
+    kmer_to_int,
+    kmer_to_int_unchecked,
+)
+
+
+def parse_args():
+    """Parse arguments"""
+    parser = argparse.ArgumentParser(
+        description=__doc__,
+        formatter_class=argparse.RawDescriptionHelpFormatter,
+    )
+    parser.add_argument(
+        "-n",
+        "--num-kmers",
+        type=int,
+        default=1000000,
+        help="number of k-mers to convert (default: %(default)s)",
+    )
+    parser.add_argument(
+        "-l",
+        "--read-length",
+        type=int,
+        default=100000000,
+        help="length of the read to find the k-mers of (default: %(default)s)",
+    )
+    parser.add_argument("--seed", type=int, default=1)
+    return parser.parse_args()
+
+
+def main():
+    """Main method of program"""
+    args = parse_args()
+    rng = random.Random(args.seed)
+    read = "".join(rng.choices("ACGT", k=args.read_length))
+
+    for k in [21, 31]:
+        kmer_strings = [
+            "".join(rng.choices("ACGT", k=k)) for _ in range(args.num_kmers)
+        ]
+        for function in [kmer_to_int, kmer_to_int_unchecked]:
+            start = time.perf_counter()
+            for kmer in kmer_strings:
+                function(kmer)
+            elapsed = time.perf_counter() - start
+            print(
+                f"{function.__name__}, k={k}: {args.num_kmers} k-mers in "
+                f"{elapsed:.2f} s, {args.num_kmers / elapsed / 1e6:.2f} M k-mers/s"
+            )
+
+        start = time.perf_counter()
+        canonical_kmer_array(read, k)
+        elapsed = time.perf_counter() - start
+        print(
+            f"canonical_kmer_array, k={k}: {len(read)} bases in {elapsed:.2f} s, "
+            f"{len(read) / elapsed / 1e6:.1f} M bases/s"
+        )
+
+
+if __name__ == "__main__":
+    main()
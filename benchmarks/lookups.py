@@ -0,0 +1,80 @@
+"""Benchmark k-mer lookups in a KmerSet with each hash function.
+
+Builds a set of random k-mers with each `Hasher`, then times counting
+the k-mers of a long random read against it, which is what classifying
+reads spends nearly all its time on. Each k-mer of the read is looked up
+in the set and then in an empty set, as it would be in the second
+haplotype's set, and one in every k k-mers in the first half of the read
+is a hit.
+
+Run from the root of the repo after installing the package:
+
+    python benchmarks/lookups.py --num-lookups 100000000
+"""
+
+import argparse
+import random
+import time
+
+from trio_binning.kmers import Hasher, KmerSet, count_kmers_in_lookups, int_to_kmer
+
+
+def parse_args():
+    """Parse arguments"""
+    parser = argparse.ArgumentParser(
+        description=__doc__,
+        formatter_class=argparse.RawDescriptionHelpFormatter,
+    )
+    parser.add_argument("-k", "--kmer-size", type=int, default=21)
+    parser.add_argument(
+        "-n",
+        "--num-kmers",
+        type=int,
+        default=1000000,
+        help="number of k-mers in each set (default: %(default)s)",
+    )
+    parser.add_argument(
+        "-l",
+        "--num-lookups",
+        type=int,
+        default=100000000,
+        help="number of k-mers to look up (default: %(default)s)",
+    )
+    parser.add_argument("--seed", type=int, default=1)
+    return parser.parse_args()
+
+
+def main():
+    """Main method of program"""
+    args = parse_args()
+    rng = random.Random(args.seed)
+    k = args.kmer_size
+
+    kmer_ints = [rng.getrandbits(2 * k) for _ in range(args.num_kmers)]
+    half = args.num_lookups // 2
+    # the first half of the read is made of k-mers from the set laid end
+    # to end, and the second half is random
+    hits = "".join(
+        int_to_kmer(kmer_ints[i % len(kmer_ints)], k) for i in range(half // k)
+    )
+    misses = "".join(rng.choices("ACGT", k=args.num_lookups - len(hits)))
+    read = hits + misses
+    num_lookups = len(read) - k + 1
+
+    for hasher in Hasher:
+        kmer_set = KmerSet(k, len(kmer_ints), hasher)
+        for kmer_int in kmer_ints:
+            kmer_set.add(kmer_int)
+        empty = KmerSet(k)
+
+        start = time.perf_counter()
+        count_kmers_in_lookups(read, kmer_set, empty)
+        elapsed = time.perf_counter() - start
+        print(
+            f"{hasher.name}: {num_lookups} k-mers in {elapsed:.2f} s, "
+            f"{num_lookups / elapsed / 1e6:.1f} M k-mers/s"
+        )
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,89 @@
+"""Benchmark counting the k-mers of a long contig window by window.
+
+Builds a random contig and sets with one in every 50 of its k-mers, then
+times counting the k-mers of each window by slicing the contig string,
+as `classify_by_window` used to, against packing the contig once with
+`PackedSeq` and counting each window in the packed sequence, as it does
+now.
+
+Run from the root of the repo after installing the package:
+
+    python benchmarks/windows.py --contig-length 100000000
+"""
+
+import argparse
+import random
+import time
+
+from trio_binning.kmers import (
+    KmerSet,
+    PackedSeq,
+    canonicalize,
+    count_kmers_in_lookups,
+    count_kmers_in_packed_lookups,
+    kmer_to_int,
+)
+
+
+def parse_args():
+    """Parse arguments"""
+    parser = argparse.ArgumentParser(
+        description=__doc__,
+        formatter_class=argparse.RawDescriptionHelpFormatter,
+    )
+    parser.add_argument("-k", "--kmer-size", type=int, default=21)
+    parser.add_argument(
+        "-l",
+        "--contig-length",
+        type=int,
+        default=20000000,
+        help="length of the contig (default: %(default)s)",
+    )
+    parser.add_argument(
+        "-w",
+        "--window-size",
+        type=int,
+        default=10000,
+        help="length of each window (default: %(default)s)",
+    )
+    parser.add_argument("--seed", type=int, default=1)
+    return parser.parse_args()
+
+
+def main():
+    """Main method of program"""
+    args = parse_args()
+    rng = random.Random(args.seed)
+    k = args.kmer_size
+    window_size = args.window_size
+
+    contig = "".join(rng.choices("ACGT", k=args.contig_length))
+    hap_a, hap_b = KmerSet(k), KmerSet(k)
+    for i in range(0, len(contig) - k + 1, 50):
+        kmer_int = canonicalize(kmer_to_int(contig[i : i + k]), k)
+        (hap_a if i % 100 else hap_b).add(kmer_int)
+    starts = range(0, len(contig), window_size)
+
+    start_time = time.perf_counter()
+    for start in starts:
+        window = contig[start : start + window_size + k - 1]
+        count_kmers_in_lookups(window, hap_a, hap_b)
+    elapsed = time.perf_counter() - start_time
+    print(
+        f"slicing: {len(starts)} windows in {elapsed:.2f} s, "
+        f"{len(contig) / elapsed / 1e6:.1f} M bases/s"
+    )
+
+    start_time = time.perf_counter()
+    packed = PackedSeq.from_str(contig)
+    for start in starts:
+        count_kmers_in_packed_lookups(packed, hap_a, hap_b, start, start + window_size)
+    elapsed = time.perf_counter() - start_time
+    print(
+        f"packed: {len(starts)} windows in {elapsed:.2f} s, "
+        f"{len(contig) / elapsed / 1e6:.1f} M bases/s"
+    )
+
+
+if __name__ == "__main__":
+    main()
@@ -1,4 +1,5 @@
 #define _GNU_SOURCE
+#include <assert.h>
 #include <stdint.h>
 #include <stdlib.h>
 #include <stdio.h>
@@ -6,6 +7,8 @@
 #include <string.h>
 #include <time.h>
 
+#include "trio_binning.h"
+
 /*
  * Contains a hash set full of k-mers.
  */
@@ -35,11 +38,131 @@ typedef struct {
      * The number of k-mers in the hash set
      */
     int num_kmers;
+
+    /*
+     * Which hash function to use to find k-mers' positions, one of the
+     * HASHER_ constants
+     */
+    unsigned char hasher;
 } hash_set;
 
+/*
+ * The hash functions a hash_set can use. HASHER_MIX is the original
+ * xorshift-multiply hash. HASHER_FIBONACCI is a single multiplication by
+ * 2^64 / the golden ratio, keeping the high bits, which is enough because
+ * packed k-mers are already spread over all 64 bits.
+ */
+#define HASHER_MIX 0
+#define HASHER_FIBONACCI 1
+
+/*
+ * A Bloom filter of k-mers: a compact set that may report k-mers as present
+ * that were never added, but never the reverse.
+ */
+typedef struct {
+    /*
+     * The bit array, num_bits bits long, packed 64 to a word
+     */
+    uint64_t* bits;
+
+    /*
+     * The number of bits in the filter
+     */
+    uint64_t num_bits;
+
+    /*
+     * The number of bits set for each k-mer
+     */
+    unsigned char num_hashes;
+
+    /*
+     * The k-mer size
+     */
+    unsigned char k;
+
+    /*
+     * The number of k-mers added that set at least one new bit, so roughly
+     * the number of distinct k-mers added
+     */
+    uint64_t num_kmers;
+} bloom_filter;
+
+/*
+ * A sorted array of k-mers, searched with binary search. This takes 8 bytes
+ * per k-mer, much less than a hash_set, but can't be added to.
+ */
+typedef struct {
+    /*
+     * The canonical integer representations of the k-mers, sorted and with
+     * no duplicates. Not owned by the struct.
+     */
+    uint64_t* kmers;
+
+    /*
+     * The length of kmers
+     */
+    uint64_t num_kmers;
+
+    /*
+     * The k-mer size
+     */
+    unsigned char k;
+} sorted_kmers;
+
+/*
+ * A sorted array of unique canonical k-mers in a memory-mapped binary
+ * k-mer set file. The k-mers follow the file's header, so they aren't
+ * aligned to 8 bytes, and they're little-endian whatever the host is, so
+ * they are read a byte at a time rather than through a uint64_t pointer.
+ */
+typedef struct {
+    /*
+     * The first byte of the k-mers. Not owned by the struct.
+     */
+    const unsigned char* kmers;
+
+    /*
+     * The number of k-mers
+     */
+    uint64_t num_kmers;
+
+    /*
+     * The k-mer size
+     */
+    unsigned char k;
+} mapped_kmers;
+
+/*
+ * A function that checks if a canonical k-mer is in some set of k-mers,
+ * e.g., a hash_set or bloom_filter, returning 1 if it is and 0 otherwise
+ */
+typedef char (*kmer_lookup)(void* kmers, uint64_t kmer_int);
+
+
+/*
+ * The 2-bit code of each base, with BASE_VALID set for [ACGTacgt] and
+ * BASE_LOWER set for [acgt]. Every other character is 0, so masking with
+ * BASE_CODE_MASK turns it into an A.
+ */
+#define BASE_CODE_MASK 3
+#define BASE_VALID 4
+#define BASE_LOWER 8
+
+static const unsigned char BASE_CODES[256] = {
+    ['A'] = BASE_VALID | 0,
+    ['C'] = BASE_VALID | 1,
+    ['G'] = BASE_VALID | 2,
+    ['T'] = BASE_VALID | 3,
+    ['a'] = BASE_VALID | BASE_LOWER | 0,
+    ['c'] = BASE_VALID | BASE_LOWER | 1,
+    ['g'] = BASE_VALID | BASE_LOWER | 2,
+    ['t'] = BASE_VALID | BASE_LOWER | 3,
+};
 
 /*
- * Convert a kmer string to a 64-bit integer representation
+ * Convert a kmer string to a 64-bit integer representation. Bases may be upper
+ * or lower case. Anything other than [ACGTacgt] is taken to be an A, so this
+ * is only for k-mers that are known to be valid; see kmer_to_int_checked.
  *
  * Args:
  *     kmer: the kmer string to convert
@@ -49,28 +172,47 @@ typedef struct {
  */
 uint64_t kmer_to_int(char* kmer, unsigned char k) {
     uint64_t kmer_int = 0;
+    unsigned char code;
     int i;
 
-    for (i = 0; i < k; i++)
-    {
-        switch (kmer[i]) {
-            case 'A':
-                // no need to waste time doing 'kmer_int |= (0 << (i*2))'
-                break;
-            case 'C':
-                kmer_int |= (UINT64_C(1) << (i*2));
-                break;
-            case 'G':
-                kmer_int |= (UINT64_C(2) << (i*2));
-                break;
-            case 'T':
-                kmer_int |= (UINT64_C(3) << (i*2));
-        }
+    for (i = 0; i < k; i++) {
+        code = BASE_CODES[(unsigned char) kmer[i]];
+        kmer_int |= (uint64_t) (code & BASE_CODE_MASK) << (i * 2);
     }
 
     return kmer_int;
 }
 
+/*
+ * Convert a kmer string to a 64-bit integer representation, checking that
+ * every character in it is a valid base
+ *
+ * Args:
+ *     kmer: the kmer string to convert
+ *     k: the length of the kmer (max 32)
+ *     kmer_int: place to put the integer representation of the k-mer. Not
+ *         modified if the k-mer contains an invalid base.
+ *
+ * Returns: -1 if successful, or the position of the first character that is
+ *     not one of [ACGTacgt] otherwise
+ */
+int kmer_to_int_checked(char* kmer, unsigned char k, uint64_t* kmer_int) {
+    uint64_t checked_int = 0;
+    unsigned char code;
+    int i;
+
+    for (i = 0; i < k; i++) {
+        code = BASE_CODES[(unsigned char) kmer[i]];
+        if (!(code & BASE_VALID)) {
+            return i;
+        }
+        checked_int |= (uint64_t) (code & BASE_CODE_MASK) << (i * 2);
+    }
+
+    *kmer_int = checked_int;
+    return -1;
+}
+
 void reverse_complement(char* kmer_in, char* kmer_out, unsigned char k) {
     int i;
     for (i = 0; i < k; i++)
@@ -88,10 +230,151 @@ void reverse_complement(char* kmer_in, char* kmer_out, unsigned char k) {
             case 'T':
                 kmer_out[k-i-1] = 'A';
                 break;
+            case 'a':
+                kmer_out[k-i-1] = 't';
+                break;
+            case 'c':
+                kmer_out[k-i-1] = 'g';
+                break;
+            case 'g':
+                kmer_out[k-i-1] = 'c';
+                break;
+            case 't':
+                kmer_out[k-i-1] = 'a';
+                break;
         }
     }
 }
 
+/*
+ * Convert a 64-bit integer representation back into a kmer string
+ *
+ * Args:
+ *     kmer_int: the integer representation of the kmer
+ *     kmer_out: buffer of at least k characters to write the kmer to. No
+ *         null terminator is written.
+ *     k: the length of the kmer (max 32)
+ */
+void int_to_kmer(uint64_t kmer_int, char* kmer_out, unsigned char k) {
+    static const char bases[4] = {'A', 'C', 'G', 'T'};
+    int i;
+
+    for (i = 0; i < k; i++)
+    {
+        kmer_out[i] = bases[(kmer_int >> (i*2)) & 3];
+    }
+}
+
+/*
+ * Convert a kmer string of up to 64 bases to a 128-bit integer representation
+ * split across two 64-bit words, checking that every character in it is a
+ * valid base. The first 32 bases go in the low word in the same layout as
+ * kmer_to_int, and the rest go in the high word.
+ *
+ * Args:
+ *     kmer: the kmer string to convert
+ *     k: the length of the kmer (max 64)
+ *     kmer_int_low: place to put the low 64 bits of the integer
+ *     kmer_int_high: place to put the high 64 bits of the integer
+ *
+ * Returns: -1 if successful, or the position of the first character that is
+ *     not one of [ACGT] otherwise
+ */
+int kmer_to_int128_checked(
+    char* kmer,
+    unsigned char k,
+    uint64_t* kmer_int_low,
+    uint64_t* kmer_int_high
+) {
+    int position;
+
+    position = kmer_to_int_checked(kmer, k < 32 ? k : 32, kmer_int_low);
+    if (position >= 0)
+        return position;
+
+    *kmer_int_high = 0;
+    if (k > 32)
+    {
+        position = kmer_to_int_checked(kmer + 32, k - 32, kmer_int_high);
+        if (position >= 0)
+            return position + 32;
+    }
+
+    return -1;
+}
+
+/*
+ * Convert a 128-bit integer representation split across two 64-bit words
+ * back into a kmer string. The inverse of kmer_to_int128_checked.
+ *
+ * Args:
+ *     kmer_int_low: the low 64 bits of the integer representation
+ *     kmer_int_high: the high 64 bits of the integer representation
+ *     kmer_out: buffer of at least k characters to write the kmer to. No
+ *         null terminator is written.
+ *     k: the length of the kmer (max 64)
+ */
+void int128_to_kmer(
+    uint64_t kmer_int_low,
+    uint64_t kmer_int_high,
+    char* kmer_out,
+    unsigned char k
+) {
+    int_to_kmer(kmer_int_low, kmer_out, k < 32 ? k : 32);
+    if (k > 32)
+        int_to_kmer(kmer_int_high, kmer_out + 32, k - 32);
+}
+
+/*
+ * Reverse complement an integer-encoded kmer without decoding it
+ *
+ * Complementing is a bitwise NOT, since A(00) <-> T(11) and C(01) <-> G(10).
+ * The order of the 2-bit bases is then reversed within the whole 64-bit word
+ * by swapping progressively larger groups, which leaves the kmer in the top
+ * 2*k bits, so it is shifted back down at the end.
+ *
+ * Args:
+ *     kmer_int: the integer representation of the kmer
+ *     k: the length of the kmer (min 1, max 32)
+ *
+ * Returns: the integer representation of the reverse complement of the kmer
+ */
+uint64_t reverse_complement_int(uint64_t kmer_int, unsigned char k) {
+    kmer_int = ~kmer_int;
+
+    kmer_int = ((kmer_int >> 2) & UINT64_C(0x3333333333333333))
+        | ((kmer_int & UINT64_C(0x3333333333333333)) << 2);
+    kmer_int = ((kmer_int >> 4) & UINT64_C(0x0F0F0F0F0F0F0F0F))
+        | ((kmer_int & UINT64_C(0x0F0F0F0F0F0F0F0F)) << 4);
+    kmer_int = ((kmer_int >> 8) & UINT64_C(0x00FF00FF00FF00FF))
+        | ((kmer_int & UINT64_C(0x00FF00FF00FF00FF)) << 8);
+    kmer_int = ((kmer_int >> 16) & UINT64_C(0x0000FFFF0000FFFF))
+        | ((kmer_int & UINT64_C(0x0000FFFF0000FFFF)) << 16);
+    kmer_int = (kmer_int >> 32) | (kmer_int << 32);
+
+    return kmer_int >> (64 - 2*k);
+}
+
+/*
+ * Get the canonical form of an integer-encoded kmer, i.e., the lesser of the
+ * kmer and its reverse complement.
+ *
+ * Because the first base of a kmer is stored in the least significant bits,
+ * comparing the integers of a kmer and its reverse complement gives the same
+ * answer as comparing the strings lexicographically, so the int returned here
+ * always decodes to the lexicographically smaller of the two strings.
+ *
+ * Args:
+ *     kmer_int: the integer representation of the kmer
+ *     k: the length of the kmer (max 32)
+ *
+ * Returns: the integer representation of the canonical kmer
+ */
+uint64_t canonicalize_int(uint64_t kmer_int, unsigned char k) {
+    uint64_t kmer_revcomp_int = reverse_complement_int(kmer_int, k);
+    return kmer_int < kmer_revcomp_int ? kmer_int : kmer_revcomp_int;
+}
+
 /*
  * https://stackoverflow.com/questions/664014/
  */
@@ -103,22 +386,153 @@ unsigned int hash_function(uint64_t x) {
 }
 
 /*
- * Add a k-mer to the hash.
+ * Fibonacci hashing: https://en.wikipedia.org/wiki/Hash_function#Fibonacci_hashing
+ */
+unsigned int fibonacci_hash_function(uint64_t x) {
+    return (unsigned int) ((x * UINT64_C(0x9e3779b97f4a7c15)) >> 32);
+}
+
+/*
+ * The position in a hash set's arrays where looking for a k-mer starts
+ */
+static inline unsigned int hash_set_home(hash_set* set, uint64_t kmer_int) {
+    unsigned int hash;
+
+    if (set->hasher == HASHER_FIBONACCI) {
+        hash = fibonacci_hash_function(kmer_int);
+    } else {
+        hash = hash_function(kmer_int);
+    }
+    // maps the hash onto 0..hash_size - 1 without a division:
+    // https://lemire.me/blog/2016/06/27/a-fast-alternative-to-the-modulo-reduction/
+    return (unsigned int) (((uint64_t) hash * set->hash_size) >> 32);
+}
+
+/*
+ * Look up an integer-encoded k-mer in a hash set.
+ *
+ * Args:
+ *     set: hash set in which to look for the k-mer
+ *     kmer_int: integer representation of the k-mer. Must already be
+ *         canonical, since that is how k-mers are stored in the set.
+ *
+ * Returns: the position of the k-mer in the set's arrays if it is in the set,
+ *     or otherwise the position of the empty slot where it would go
+ */
+unsigned int find_in_hash_set(hash_set* set, uint64_t kmer_int) {
+    unsigned int position = hash_set_home(set, kmer_int);
+
+    while (set->full[position] && set->kmers[position] != kmer_int)
+    {
+        if (++position == (unsigned int) set->hash_size) {
+            position = 0;
+        }
+    }
+    return position;
+}
+
+/*
+ * Double the size of a hash set's arrays, rehashing everything in it.
+ *
+ * Args:
+ *     set: the set to grow (modifies)
+ */
+void grow_hash_set(hash_set* set) {
+    int i, old_hash_size = set->hash_size;
+    uint64_t* old_kmers = set->kmers;
+    unsigned char* old_full = set->full;
+    unsigned int position;
+
+    set->hash_size = old_hash_size * 2 + 1;
+    //NOLINTNEXTLINE
+    set->kmers = (uint64_t*) malloc(set->hash_size * sizeof(uint64_t));
+    set->full = (unsigned char*) calloc(set->hash_size, sizeof(unsigned char));
+
+    for (i = 0; i < old_hash_size; i++) {
+        if (old_full[i])
+        {
+            position = find_in_hash_set(set, old_kmers[i]);
+            set->full[position] = 1;
+            set->kmers[position] = old_kmers[i];
+        }
+    }
+
+    free(old_kmers);
+    free(old_full);
+}
+
+/*
+ * Add an integer-encoded k-mer to the hash, canonicalizing it first. The
+ * hash is grown if it gets more than 3/4 full.
  *
  * Args:
  *     set: the set to add the k-mer to (modifies)
- *     kmer: the k-mer string to add to the set
+ *     kmer_int: integer representation of the k-mer to add to the set
+ *
+ * Returns: 1 if the k-mer was added, or 0 if it was already in the set
  */
-void add_to_hash(hash_set* set, char* kmer) {
-    uint64_t kmer_int = kmer_to_int(kmer, set->k);
-    unsigned int position = hash_function(kmer_int) % set->hash_size;
-    while (set->full[position])
+char add_int_to_hash(hash_set* set, uint64_t kmer_int) {
+    unsigned int position;
+
+    if ((uint64_t) (set->num_kmers + 1) * 4 > (uint64_t) set->hash_size * 3)
+    {
+        grow_hash_set(set);
+    }
+
+    kmer_int = canonicalize_int(kmer_int, set->k);
+    position = find_in_hash_set(set, kmer_int);
+    if (set->full[position])
     {
-        position = (position + 1) % set->hash_size;
+        return 0;
     }
 
     set->full[position] = 1;
     set->kmers[position] = kmer_int;
+    set->num_kmers++;
+    return 1;
+}
+
+/*
+ * Add an array of integer-encoded k-mers to the hash.
+ *
+ * Args:
+ *     set: the set to add the k-mers to (modifies)
+ *     kmer_ints: integer representations of the k-mers to add to the set
+ *     num_kmers: the length of kmer_ints
+ */
+void add_ints_to_hash(hash_set* set, uint64_t* kmer_ints, uint64_t num_kmers) {
+    uint64_t i;
+    for (i = 0; i < num_kmers; i++) {
+        add_int_to_hash(set, kmer_ints[i]);
+    }
+}
+
+/*
+ * Copy the k-mers in a hash set to an array, in no particular order.
+ *
+ * Args:
+ *     set: the set to copy the k-mers out of
+ *     kmer_ints: where to copy them to. Must have room for set->num_kmers.
+ */
+void copy_hash_set_kmers(hash_set* set, uint64_t* kmer_ints) {
+    int i;
+    uint64_t num_copied = 0;
+    for (i = 0; i < set->hash_size; i++) {
+        if (set->full[i]) {
+            kmer_ints[num_copied++] = set->kmers[i];
+        }
+    }
+}
+
+/*
+ * Add a k-mer to the hash.
+ *
+ * Args:
+ *     set: the set to add the k-mer to (modifies)
+ *     kmer: the k-mer string to add to the set
+ */
+void add_to_hash(hash_set* set, char* kmer) {
+    add_int_to_hash(set, kmer_to_int(kmer, set->k));
 }
 
 int peek_at_file(char* kmer_file_path, uint64_t* num_kmers) {
@@ -153,32 +567,51 @@ int peek_at_file(char* kmer_file_path, uint64_t* num_kmers) {
  *     k: the k-mer size of the hash
  *     num_kmers: the number of k-mers that are going to be put inside.
  *         This information is needed to figure out how much space to
- *         allocate for it.
+ *         allocate for it. The set grows if more are added, but it is
+ *         faster not to have to.
  *
- * Returns: a new hash set that's ready to start adding stuff to
+ *     hasher: the hash function to use, one of the HASHER_ constants
+ *
+ * Returns: a new, empty hash set that's ready to start adding stuff to
  */
-hash_set* initialize_hash_set(int k, uint64_t num_kmers) {
-    int i;
+hash_set* initialize_hash_set_with_hasher(
+    int k, uint64_t num_kmers, unsigned char hasher
+) {
     hash_set* out_hash_set;
 
     out_hash_set = malloc(sizeof(hash_set));
     out_hash_set->k = k;
-    out_hash_set->num_kmers = num_kmers;
-    out_hash_set->hash_size = num_kmers * 4 / 3;
+    out_hash_set->num_kmers = 0;
+    out_hash_set->hasher = hasher;
+    out_hash_set->hash_size = num_kmers * 4 / 3 + 1;
     //NOLINTNEXTLINE
     out_hash_set->kmers = (uint64_t*) malloc(
         out_hash_set->hash_size * sizeof(uint64_t)
     );
-    out_hash_set->full = (unsigned char*) malloc(
-        out_hash_set->hash_size * sizeof(unsigned char)
+    out_hash_set->full = (unsigned char*) calloc(
+        out_hash_set->hash_size, sizeof(unsigned char)
     );
-    for (i = 0; i < out_hash_set->hash_size; i++) {
-        out_hash_set->full[i] = 0;
-    }
 
     return out_hash_set;
 }
 
+/*
+ * Initialize a new hash_set struct that uses the default hash function. See
+ * initialize_hash_set_with_hasher.
+ */
+hash_set* initialize_hash_set(int k, uint64_t num_kmers) {
+    return initialize_hash_set_with_hasher(k, num_kmers, HASHER_FIBONACCI);
+}
+
+/*
+ * Free a hash set, including the arrays inside of it.
+ */
+void free_hash_set(hash_set* set) {
+    free(set->kmers);
+    free(set->full);
+    free(set);
+}
+
 /*
  * Create a new k-mer hash set from a file full of k-mers, one per line
  */
@@ -230,74 +663,1727 @@ hash_set* create_kmer_hash_set(char* kmer_file_path) {
 
 
 /*
- * Check membership of a k-mer in a hash set.
+ * Check membership of an integer-encoded k-mer in a hash set.
  *
  * Args:
- *     kmer: kmer string to look up
- *     empty_kmer_buffer: preallocated buffer of size sizeof(char)*(k+1) for
- *         putting reverse complements in. Just a temporary place for this
- *         function to put stuff without having to allocate the space every
- *         single time it is run, for speed reasons
+ *     kmer_int: integer representation of the k-mer to look up. Either
+ *         orientation of the k-mer may be given.
  *     set: hash set in which to check for k-mer membership
  *
  * Returns: 1 if k-mer is in set, 0 otherwise
  */
-char kmer_in_hash_set(char* kmer, char* empty_kmer_buffer, hash_set* set) {
-    uint64_t kmer_int, kmer_revcomp_int;
-    unsigned int position;
+char int_in_hash_set(uint64_t kmer_int, hash_set* set) {
+    // look up only the lesser of the kmer and its reverse complement
+    kmer_int = canonicalize_int(kmer_int, set->k);
+    return set->full[find_in_hash_set(set, kmer_int)];
+}
 
-    // calculate the int representations of the kmer and its reverse
-    // complement, looking up only the lesser of the two
-    kmer_int = kmer_to_int(kmer, set->k);
-    reverse_complement(kmer, empty_kmer_buffer, set->k);
-    kmer_revcomp_int = kmer_to_int(empty_kmer_buffer, set->k);
+/*
+ * Make a new hash set of the k-mers in one set that are, or are not, in
+ * another. The k-mers are copied straight into the new set, which starts out
+ * big enough to hold them all.
+ *
+ * Args:
+ *     set: the set to take k-mers from
+ *     other: the set to check the k-mers against. Must have the same k.
+ *     in_other: 1 to keep the k-mers that are in other, 0 to keep the ones
+ *         that aren't
+ *
+ * Returns: a new hash set, to be freed with free_hash_set
+ */
+hash_set* filter_hash_set(hash_set* set, hash_set* other, char in_other) {
+    int i;
+    hash_set* out_hash_set;
+    uint64_t capacity = set->num_kmers;
 
-    kmer_int = kmer_int < kmer_revcomp_int ? kmer_int : kmer_revcomp_int;
+    assert(set->k == other->k);
+    if (in_other && other->num_kmers < set->num_kmers) {
+        capacity = other->num_kmers;
+    }
+    out_hash_set = initialize_hash_set_with_hasher(set->k, capacity, set->hasher);
 
-    position = hash_function(kmer_int) % set->hash_size;
-    while (set->full[position])
-    {
-        if (set->kmers[position] == kmer_int)
-        {
-            return 1;
+    for (i = 0; i < set->hash_size; i++) {
+        if (set->full[i] &&
+                other->full[find_in_hash_set(other, set->kmers[i])] == in_other) {
+            add_int_to_hash(out_hash_set, set->kmers[i]);
         }
-        position = (position + 1) % set->hash_size;
     }
 
-    return 0;
+    return out_hash_set;
 }
 
-void count_kmers_in_read(
-    char* read,
-    hash_set* haplotype_A,
-    hash_set* haplotype_B,
-    int* count_A,
-    int* count_B
-) {
-    int i, j;
-    char* kmer = malloc((haplotype_A->k + 1) * sizeof(char));
-    char* kmer_revcomp = malloc((haplotype_A->k + 1) * sizeof(char));
-    kmer[haplotype_A->k] = '\0';
+/*
+ * Make a new hash set of the k-mers in one set that are not in another.
+ */
+hash_set* hash_set_difference(hash_set* set, hash_set* other) {
+    return filter_hash_set(set, other, 0);
+}
 
+/*
+ * Make a new hash set of the k-mers that are in both of two sets.
+ */
+hash_set* hash_set_intersection(hash_set* set, hash_set* other) {
+    return filter_hash_set(set, other, 1);
+}
+
+/*
+ * Count the k-mers that are in both of two sets without making a new set,
+ * by looking up each k-mer of the smaller set in the bigger one.
+ *
+ * Args:
+ *     set: one set
+ *     other: the other set. Must have the same k.
+ *
+ * Returns: the number of k-mers in both sets
+ */
+uint64_t hash_set_intersection_size(hash_set* set, hash_set* other) {
+    int i;
+    uint64_t num_shared = 0;
+    hash_set* swap;
+
+    assert(set->k == other->k);
+    if (other->num_kmers < set->num_kmers) {
+        swap = set;
+        set = other;
+        other = swap;
+    }
+    for (i = 0; i < set->hash_size; i++) {
+        if (set->full[i] && other->full[find_in_hash_set(other, set->kmers[i])]) {
+            num_shared++;
+        }
+    }
+    return num_shared;
+}
+
+/*
+ * Count the G and C bases in all of the k-mers in a hash set.
+ *
+ * C is 01 and G is 10, so a base is G or C exactly when its two bits differ.
+ *
+ * Returns: the total number of G and C bases
+ */
+uint64_t hash_set_gc_count(hash_set* set) {
+    int i;
+    uint64_t num_gc = 0;
+
+    for (i = 0; i < set->hash_size; i++) {
+        if (set->full[i]) {
+            num_gc += __builtin_popcountll(
+                (set->kmers[i] ^ (set->kmers[i] >> 1)) & 0x5555555555555555ULL);
+        }
+    }
+    return num_gc;
+}
+
+/*
+ * Check membership of a k-mer in a hash set.
+ *
+ * Args:
+ *     kmer: kmer string to look up
+ *     set: hash set in which to check for k-mer membership
+ *
+ * Returns: 1 if k-mer is in set, 0 otherwise
+ */
+char kmer_in_hash_set(char* kmer, hash_set* set) {
+    return int_in_hash_set(kmer_to_int(kmer, set->k), set);
+}
+
+/*
+ * Convert a base to its 2-bit integer representation
+ *
+ * Args:
+ *     base: the base to convert, upper or lower case
+ *     skip_lowercase: 1 to treat lowercase (soft-masked) bases as invalid
+ *
+ * Returns: the integer representation of the base, or -1 if it is not one of
+ *     [ACGTacgt] (or [ACGT] if skip_lowercase is 1)
+ */
+int base_to_int(char base, char skip_lowercase) {
+    unsigned char code = BASE_CODES[(unsigned char) base];
+
+    if (!(code & BASE_VALID) || (skip_lowercase && (code & BASE_LOWER))) {
+        return -1;
+    }
+    return code & BASE_CODE_MASK;
+}
+
+/*
+ * Mix the bits of a 64-bit integer (splitmix64's finalizer)
+ */
+uint64_t mix64(uint64_t x) {
+    x = (x ^ (x >> 30)) * UINT64_C(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)) * UINT64_C(0x94d049bb133111eb);
+    return x ^ (x >> 31);
+}
+
+/*
+ * Initialize an empty Bloom filter.
+ *
+ * Args:
+ *     k: the k-mer size
+ *     num_bits: the number of bits in the filter
+ *     num_hashes: the number of bits to set for each k-mer
+ *
+ * Returns: a new, empty Bloom filter, to be freed with free_bloom_filter
+ */
+bloom_filter* initialize_bloom_filter(
+    int k, uint64_t num_bits, unsigned char num_hashes
+) {
+    bloom_filter* filter = malloc(sizeof(bloom_filter));
+    filter->k = k;
+    filter->num_bits = num_bits > 0 ? num_bits : 1;
+    filter->num_hashes = num_hashes > 0 ? num_hashes : 1;
+    filter->num_kmers = 0;
+    filter->bits = (uint64_t*) calloc(
+        (filter->num_bits + 63) / 64, sizeof(uint64_t)
+    );
+    return filter;
+}
+
+/*
+ * Free a Bloom filter, including its bit array.
+ */
+void free_bloom_filter(bloom_filter* filter) {
+    free(filter->bits);
+    free(filter);
+}
+
+/*
+ * Add a k-mer to a Bloom filter, or check if it is in one.
+ *
+ * The bit positions are generated by double hashing, with each of
+ * h1 + i * h2 mixed again before taking the modulo of the number of bits so
+ * that the positions are still independent in very small filters.
+ *
+ * Args:
+ *     filter: the filter to add the k-mer to or look in
+ *     kmer_int: integer representation of the k-mer. Either orientation may
+ *         be given.
+ *     add: 1 to set the k-mer's bits, 0 to just check them
+ *
+ * Returns: 1 if all of the k-mer's bits were already set, 0 otherwise
+ */
+char bloom_filter_bits(bloom_filter* filter, uint64_t kmer_int, char add) {
+    uint64_t h1, h2, position, mask;
+    char found = 1;
+    int i;
+
+    kmer_int = canonicalize_int(kmer_int, filter->k);
+    h1 = mix64(kmer_int);
+    h2 = mix64(kmer_int ^ UINT64_C(0x9e3779b97f4a7c15)) | 1;
+
+    for (i = 0; i < filter->num_hashes; i++) {
+        position = mix64(h1 + i * h2) % filter->num_bits;
+        mask = UINT64_C(1) << (position % 64);
+        if (!(filter->bits[position / 64] & mask)) {
+            if (!add) {
+                return 0;
+            }
+            found = 0;
+            filter->bits[position / 64] |= mask;
+        }
+    }
+
+    return found;
+}
+
+/*
+ * Add integer-encoded k-mers to a Bloom filter.
+ *
+ * Args:
+ *     filter: the filter to add the k-mers to (modifies)
+ *     kmer_ints: integer representations of the k-mers to add
+ *     num_kmers: the length of kmer_ints
+ */
+void add_ints_to_bloom_filter(
+    bloom_filter* filter, uint64_t* kmer_ints, uint64_t num_kmers
+) {
+    uint64_t i;
+    for (i = 0; i < num_kmers; i++) {
+        if (!bloom_filter_bits(filter, kmer_ints[i], 1)) {
+            filter->num_kmers++;
+        }
+    }
+}
+
+/*
+ * Check if a k-mer is in a Bloom filter. May return 1 for k-mers that were
+ * never added.
+ */
+char int_in_bloom_filter(uint64_t kmer_int, bloom_filter* filter) {
+    return bloom_filter_bits(filter, kmer_int, 0);
+}
+
+/*
+ * The positions of a k-mer's counters in a counting Bloom filter, by double
+ * hashing as in bloom_filter_bits
+ */
+static inline uint64_t counting_bloom_position(
+    uint64_t h1, uint64_t h2, int i, uint64_t num_counters
+) {
+    return mix64(h1 + i * h2) % num_counters;
+}
+
+static inline unsigned char get_counter(
+    const unsigned char* counters, uint64_t position
+) {
+    return (counters[position / 2] >> (4 * (position % 2))) & 15;
+}
+
+/*
+ * The count of a k-mer in a counting Bloom filter: the lowest of its
+ * counters, which is never less than the number of times it was added,
+ * up to 15
+ */
+static unsigned char counting_bloom_count(
+    const unsigned char* counters,
+    uint64_t num_counters,
+    unsigned char num_hashes,
+    uint64_t kmer_int
+) {
+    uint64_t h1 = mix64(kmer_int);
+    uint64_t h2 = mix64(kmer_int ^ UINT64_C(0x9e3779b97f4a7c15)) | 1;
+    unsigned char count = 15, counter;
+    int i;
+
+    for (i = 0; i < num_hashes && count > 0; i++) {
+        counter = get_counter(
+            counters, counting_bloom_position(h1, h2, i, num_counters)
+        );
+        if (counter < count) {
+            count = counter;
+        }
+    }
+    return count;
+}
+
+/*
+ * Add integer-encoded k-mers to a counting Bloom filter of 4-bit counters.
+ *
+ * Each k-mer only increments those of its counters that are at its count,
+ * the lowest of them (conservative update), which keeps the counts of other
+ * k-mers sharing the rest from growing. Counters stop at 15.
+ *
+ * Args:
+ *     counters: the counters, two to a byte with the even ones in the low 4
+ *         bits (modifies)
+ *     num_counters: the number of counters
+ *     num_hashes: the number of counters for each k-mer
+ *     kmer_ints: integer representations of the k-mers to add, in the same
+ *         orientation for every occurrence, e.g., canonical
+ *     num_kmers: the length of kmer_ints
+ */
+void add_ints_to_counting_bloom(
+    unsigned char* counters,
+    uint64_t num_counters,
+    unsigned char num_hashes,
+    uint64_t* kmer_ints,
+    uint64_t num_kmers
+) {
+    uint64_t i, h1, h2, position;
+    unsigned char count;
+    int j;
+
+    for (i = 0; i < num_kmers; i++) {
+        count = counting_bloom_count(
+            counters, num_counters, num_hashes, kmer_ints[i]
+        );
+        if (count == 15) {
+            continue;
+        }
+        h1 = mix64(kmer_ints[i]);
+        h2 = mix64(kmer_ints[i] ^ UINT64_C(0x9e3779b97f4a7c15)) | 1;
+        for (j = 0; j < num_hashes; j++) {
+            position = counting_bloom_position(h1, h2, j, num_counters);
+            if (get_counter(counters, position) == count) {
+                counters[position / 2] += 1 << (4 * (position % 2));
+            }
+        }
+    }
+}
+
+/*
+ * Look up the counts of integer-encoded k-mers in a counting Bloom filter.
+ *
+ * Args:
+ *     counters, num_counters, num_hashes: the filter, as in
+ *         add_ints_to_counting_bloom
+ *     kmer_ints: integer representations of the k-mers to look up
+ *     num_kmers: the length of kmer_ints
+ *     counts: place to put the count of each k-mer, num_kmers long
+ */
+void counting_bloom_counts(
+    const unsigned char* counters,
+    uint64_t num_counters,
+    unsigned char num_hashes,
+    const uint64_t* kmer_ints,
+    uint64_t num_kmers,
+    unsigned char* counts
+) {
+    uint64_t i;
+    for (i = 0; i < num_kmers; i++) {
+        counts[i] = counting_bloom_count(
+            counters, num_counters, num_hashes, kmer_ints[i]
+        );
+    }
+}
+
+/*
+ * Keep only the k-mers with at least a count in a counting Bloom filter.
+ *
+ * Args:
+ *     counters, num_counters, num_hashes: the filter, as in
+ *         add_ints_to_counting_bloom
+ *     kmer_ints: integer representations of the k-mers (modifies)
+ *     num_kmers: the length of kmer_ints
+ *     min_count: the lowest count to keep, up to 15
+ *
+ * Returns: the number of k-mers kept, which are moved to the start of
+ *     kmer_ints in the same order
+ */
+uint64_t keep_ints_in_counting_bloom(
+    const unsigned char* counters,
+    uint64_t num_counters,
+    unsigned char num_hashes,
+    uint64_t* kmer_ints,
+    uint64_t num_kmers,
+    unsigned char min_count
+) {
+    uint64_t i, num_kept = 0;
+    for (i = 0; i < num_kmers; i++) {
+        if (counting_bloom_count(counters, num_counters, num_hashes, kmer_ints[i])
+            >= min_count) {
+            kmer_ints[num_kept++] = kmer_ints[i];
+        }
+    }
+    return num_kept;
+}
+
+/*
+ * The number of counters in a counting Bloom filter that aren't 0
+ */
+uint64_t num_nonzero_counters(const unsigned char* counters, uint64_t num_counters) {
+    uint64_t i, num_nonzero = 0;
+    for (i = 0; i < num_counters; i++) {
+        num_nonzero += get_counter(counters, i) != 0;
+    }
+    return num_nonzero;
+}
+
+/*
+ * Add integer-encoded k-mers to a HyperLogLog sketch of their number of
+ * distinct values.
+ *
+ * Each k-mer is hashed with mix64. The top precision bits of the hash pick
+ * one of the 2^precision registers, which keeps the highest rank seen among
+ * the k-mers it was picked for: the position of the first 1 bit in the rest
+ * of the hash, counting from 1, or 64 - precision + 1 if it is all 0's.
+ * Adding the same k-mer twice changes nothing, and the sketch of two sets
+ * of k-mers is the register-wise maximum of their sketches.
+ *
+ * Args:
+ *     registers: the 2^precision registers (modifies)
+ *     precision: the number of bits that pick a register, from 4 to 18
+ *     kmer_ints: integer representations of the k-mers to add, in the same
+ *         orientation for every occurrence, e.g., canonical
+ *     num_kmers: the length of kmer_ints
+ */
+void add_ints_to_hyperloglog(
+    unsigned char* registers,
+    unsigned char precision,
+    uint64_t* kmer_ints,
+    uint64_t num_kmers
+) {
+    uint64_t i, hash, rest;
+    unsigned char rank, max_rank = 64 - precision + 1;
+
+    for (i = 0; i < num_kmers; i++) {
+        hash = mix64(kmer_ints[i]);
+        rest = hash << precision;
+        rank = rest == 0 ? max_rank : __builtin_clzll(rest) + 1;
+        if (rank > registers[hash >> (64 - precision)]) {
+            registers[hash >> (64 - precision)] = rank;
+        }
+    }
+}
+
+/*
+ * kmer_lookup for hash sets
+ */
+char hash_set_lookup(void* set, uint64_t kmer_int) {
+    return ((hash_set*) set)->full[find_in_hash_set((hash_set*) set, kmer_int)];
+}
+
+/*
+ * kmer_lookup for Bloom filters
+ */
+char bloom_filter_lookup(void* filter, uint64_t kmer_int) {
+    return bloom_filter_bits((bloom_filter*) filter, kmer_int, 0);
+}
+
+int compare_kmer_ints(const void* a, const void* b) {
+    uint64_t x = *(const uint64_t*) a, y = *(const uint64_t*) b;
+    return (x > y) - (x < y);
+}
+
+/*
+ * Canonicalize, sort, and remove duplicates from an array of k-mers, ready
+ * to be searched as a sorted_kmers. Arrays that are already like that, e.g.
+ * from a binary k-mer set file, are left alone without being sorted again.
+ *
+ * Args:
+ *     kmer_ints: integer representations of the k-mers (modifies)
+ *     num_kmers: the length of kmer_ints
+ *     k: the k-mer size
+ *
+ * Returns: the number of k-mers left at the start of kmer_ints
+ */
+uint64_t sort_kmer_ints(uint64_t* kmer_ints, uint64_t num_kmers, unsigned char k) {
+    uint64_t i, num_unique;
+    char sorted = 1;
+
+    for (i = 0; i < num_kmers; i++) {
+        if (kmer_ints[i] != canonicalize_int(kmer_ints[i], k)) {
+            kmer_ints[i] = canonicalize_int(kmer_ints[i], k);
+            sorted = 0;
+        }
+        if (i > 0 && kmer_ints[i] <= kmer_ints[i - 1]) {
+            sorted = 0;
+        }
+    }
+    if (sorted || num_kmers == 0) {
+        return num_kmers;
+    }
+
+    qsort(kmer_ints, num_kmers, sizeof(uint64_t), compare_kmer_ints);
+    num_unique = 1;
+    for (i = 1; i < num_kmers; i++) {
+        if (kmer_ints[i] != kmer_ints[num_unique - 1]) {
+            kmer_ints[num_unique++] = kmer_ints[i];
+        }
+    }
+    return num_unique;
+}
+
+/*
+ * Sort an array of k-mers, e.g., for writing out in order.
+ */
+void sort_uint64s(uint64_t* kmer_ints, uint64_t num_kmers) {
+    /* kmer_ints can be NULL when it is empty, which qsort doesn't allow */
+    if (num_kmers == 0) {
+        return;
+    }
+    qsort(kmer_ints, num_kmers, sizeof(uint64_t), compare_kmer_ints);
+}
+
+/*
+ * Sort an array of k-mers with repeats and count each distinct one.
+ *
+ * Args:
+ *     kmer_ints: integer representations of the k-mers, each repeated once
+ *         for every time it was seen (modifies). They are sorted, and then
+ *         the distinct k-mers are moved to the start, in order.
+ *     num_kmers: the length of kmer_ints
+ *     counts: place to put the number of times each distinct k-mer is in
+ *         kmer_ints. Must have room for num_kmers counts.
+ *
+ * Returns: the number of distinct k-mers, and of counts
+ */
+uint64_t count_kmer_ints(uint64_t* kmer_ints, uint64_t num_kmers, uint64_t* counts) {
+    uint64_t i, num_unique = 0;
+
+    if (num_kmers == 0) {
+        return 0;
+    }
+    qsort(kmer_ints, num_kmers, sizeof(uint64_t), compare_kmer_ints);
+    for (i = 0; i < num_kmers; i++) {
+        if (num_unique > 0 && kmer_ints[i] == kmer_ints[num_unique - 1]) {
+            counts[num_unique - 1]++;
+        } else {
+            kmer_ints[num_unique] = kmer_ints[i];
+            counts[num_unique++] = 1;
+        }
+    }
+    return num_unique;
+}
+
+/*
+ * kmer_lookup for sorted arrays of k-mers
+ */
+char sorted_kmers_lookup(void* kmers, uint64_t kmer_int) {
+    sorted_kmers* sorted = (sorted_kmers*) kmers;
+    uint64_t low = 0, high = sorted->num_kmers, middle;
+
+    // find the first k-mer that is not less than kmer_int
+    while (low < high) {
+        middle = low + (high - low) / 2;
+        if (sorted->kmers[middle] < kmer_int) {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    return low < sorted->num_kmers && sorted->kmers[low] == kmer_int;
+}
+
+/*
+ * Check membership of an integer-encoded k-mer in a sorted array of k-mers.
+ * Either orientation of the k-mer may be given.
+ */
+char int_in_sorted_kmers(uint64_t kmer_int, sorted_kmers* kmers) {
+    return sorted_kmers_lookup(kmers, canonicalize_int(kmer_int, kmers->k));
+}
+
+/*
+ * Read the little-endian uint64_t at some byte, whatever its alignment
+ */
+static inline uint64_t read_le64(const unsigned char* bytes) {
+    uint64_t value = 0;
+    for (int i = 7; i >= 0; i--) {
+        value = (value << 8) | bytes[i];
+    }
+    return value;
+}
+
+/*
+ * kmer_lookup for memory-mapped arrays of k-mers
+ */
+char mapped_kmers_lookup(void* kmers, uint64_t kmer_int) {
+    mapped_kmers* mapped = (mapped_kmers*) kmers;
+    uint64_t low = 0, high = mapped->num_kmers, middle;
+
+    // find the first k-mer that is not less than kmer_int
+    while (low < high) {
+        middle = low + (high - low) / 2;
+        if (read_le64(mapped->kmers + 8 * middle) < kmer_int) {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    return low < mapped->num_kmers
+           && read_le64(mapped->kmers + 8 * low) == kmer_int;
+}
+
+/*
+ * Check membership of an integer-encoded k-mer in a memory-mapped array of
+ * k-mers. Either orientation of the k-mer may be given.
+ */
+char int_in_mapped_kmers(uint64_t kmer_int, mapped_kmers* kmers) {
+    return mapped_kmers_lookup(kmers, canonicalize_int(kmer_int, kmers->k));
+}
+
+/*
+ * Find the canonical k-mers in a read, e.g., to count them.
+ *
+ * K-mers containing a character other than [ACGTacgt], or a lowercase base if
+ * skip_lowercase is 1, are skipped, as in count_kmers_in_read_lookup.
+ *
+ * Args:
+ *     read: the read sequence, null-terminated
+ *     k: the k-mer size
+ *     skip_lowercase: 1 to skip k-mers with soft-masked (lowercase) bases
+ *     kmer_ints: place to put the integer format of each canonical k-mer, in
+ *         order. Must have room for strlen(read) - k + 1 k-mers.
+ *
+ * Returns: the number of k-mers put in kmer_ints
+ */
+size_t canonical_kmers_in_read(
+    char* read, unsigned char k, char skip_lowercase, uint64_t* kmer_ints
+) {
+    int base_int, num_valid_bases = 0;
+    unsigned char shift = 2 * (k - 1);
+    uint64_t kmer_int = 0;
+    size_t i, num_kmers = 0, read_length = strlen(read);
+
+    for (i = 0; i < read_length; i++) {
+        base_int = base_to_int(read[i], skip_lowercase);
+        if (base_int < 0) {
+            num_valid_bases = 0;
+            continue;
+        }
+        kmer_int = kmer_int >> 2 | (uint64_t) base_int << shift;
+        num_valid_bases++;
+        if (num_valid_bases >= k) {
+            kmer_ints[num_kmers++] = canonicalize_int(kmer_int, k);
+        }
+    }
+    return num_kmers;
+}
+
+/*
+ * Count the k-mers in a read that are in each of two sets of k-mers of any
+ * kind.
+ *
+ * Bases may be upper or lower case. Any k-mer containing a character other
+ * than [ACGTacgt] (e.g., an N), or any lowercase base if skip_lowercase is 1,
+ * is skipped rather than looked up, and counted in num_skipped instead. So is
+ * any k-mer containing a base with a quality below min_quality, if the
+ * qualities are given. If a k-mer is in both sets, it is only counted for
+ * haplotype A.
+ *
+ * Args:
+ *     read: the read sequence, null-terminated
+ *     quals: the read's quality string, the same length as read, or NULL to
+ *         not filter by quality
+ *     k: the k-mer size of both sets
+ *     lookup_A: function to look up canonical k-mers in haplotype_A
+ *     haplotype_A: the k-mers in haplotype A
+ *     lookup_B: function to look up canonical k-mers in haplotype_B
+ *     haplotype_B: the k-mers in haplotype B
+ *     count_A: place to put the number of k-mers in haplotype A
+ *     count_B: place to put the number of k-mers in haplotype B
+ *     num_skipped: place to put the number of k-mers skipped
+ *     skip_lowercase: 1 to skip k-mers with soft-masked (lowercase) bases
+ *     min_quality: the lowest quality character (not score) a base can have
+ *         without its k-mers being skipped, e.g., '!' + 20 for a minimum base
+ *         quality of 20 with Phred+33 qualities
+ *     step: only look at the k-mers starting at a multiple of this many bases
+ *         into the read, e.g., 1 for every k-mer. The others are neither looked
+ *         up nor counted in num_skipped.
+ */
+void count_kmers_in_read_lookup(
+    char* read,
+    char* quals,
+    unsigned char k,
+    kmer_lookup lookup_A,
+    void* haplotype_A,
+    kmer_lookup lookup_B,
+    void* haplotype_B,
+    int* count_A,
+    int* count_B,
+    int* num_skipped,
+    char skip_lowercase,
+    char min_quality,
+    unsigned int step
+) {
+    int base_int, num_valid_bases = 0;
+    unsigned char shift = 2 * (k - 1);
+    uint64_t kmer_int = 0, canonical_kmer_int;
+    size_t i, read_length = strlen(read);
+
+    *count_A = 0;
+    *count_B = 0;
+    *num_skipped = 0;
+
+    for (i = 0; i < read_length; i++)
+    {
+        base_int = base_to_int(read[i], skip_lowercase);
+        if (base_int < 0 || (quals != NULL && quals[i] < min_quality)) {
+            num_valid_bases = 0;
+        } else {
+            // roll the new base into the top of the k-mer
+            kmer_int = kmer_int >> 2 | (uint64_t) base_int << shift;
+            num_valid_bases++;
+        }
+
+        if (i + 1 < k || (i + 1 - k) % step != 0) {
+            continue;
+        } else if (num_valid_bases < k) {
+            (*num_skipped)++;
+            continue;
+        }
+
+        canonical_kmer_int = canonicalize_int(kmer_int, k);
+        if (lookup_A(haplotype_A, canonical_kmer_int)) {
+            (*count_A)++;
+        } else if (lookup_B(haplotype_B, canonical_kmer_int)) {
+            (*count_B)++;
+        }
+    }
+}
+
+/*
+ * Pack a sequence into 2 bits per base, 32 bases to a word, with the first
+ * base in the least significant bits of the first word, as in kmer_to_int.
+ * Any character other than [ACGTacgt] (e.g., an N), or any lowercase base if
+ * skip_lowercase is 1, is packed as an A and has its bit set in invalid,
+ * which has one bit per base, 64 to a word.
+ *
+ * Args:
+ *     seq: the sequence, which needn't be null-terminated
+ *     length: the length of seq
+ *     skip_lowercase: 1 to mark soft-masked (lowercase) bases invalid
+ *     words: place to put the packed bases. Must have room for
+ *         (length + 31) / 32 words, all 0.
+ *     invalid: place to put the invalid bases. Must have room for
+ *         (length + 63) / 64 words, all 0.
+ *
+ * Returns: the number of invalid bases
+ */
+size_t pack_seq(
+    char* seq, size_t length, char skip_lowercase, uint64_t* words, uint64_t* invalid
+) {
+    unsigned char code, invalid_flags = skip_lowercase ? BASE_LOWER : 0;
+    size_t i, num_invalid = 0;
+
+    for (i = 0; i < length; i++) {
+        code = BASE_CODES[(unsigned char) seq[i]];
+        if (!(code & BASE_VALID) || (code & invalid_flags)) {
+            invalid[i / 64] |= UINT64_C(1) << (i % 64);
+            num_invalid++;
+        } else {
+            words[i / 32] |= (uint64_t) (code & BASE_CODE_MASK) << (i % 32 * 2);
+        }
+    }
+    return num_invalid;
+}
+
+/*
+ * Get the k-mer starting at a position of a sequence packed by pack_seq,
+ * which must have at least k bases from there
+ */
+static inline uint64_t packed_kmer(
+    const uint64_t* words, size_t position, unsigned char k
+) {
+    size_t word = position / 32;
+    unsigned int offset = position % 32 * 2;
+    uint64_t kmer_int = words[word] >> offset;
+
+    if (offset + 2 * k > 64) {
+        kmer_int |= words[word + 1] << (64 - offset);
+    }
+    return k == 32 ? kmer_int : kmer_int & ((UINT64_C(1) << 2 * k) - 1);
+}
+
+/*
+ * Check whether any of the k bases starting at a position of a sequence
+ * packed by pack_seq is invalid
+ */
+static inline char packed_kmer_invalid(
+    const uint64_t* invalid, size_t position, unsigned char k
+) {
+    size_t word = position / 64;
+    unsigned int offset = position % 64;
+    uint64_t bits = invalid[word] >> offset;
+
+    if (offset + k > 64) {
+        bits |= invalid[word + 1] << (64 - offset);
+    }
+    return (bits & ((UINT64_C(1) << k) - 1)) != 0;
+}
+
+/*
+ * Fetch the slot where a k-mer would be in a hash set into the cache
+ */
+static inline void prefetch_in_hash_set(void* set, uint64_t kmer_int) {
+    unsigned int position = hash_set_home((hash_set*) set, kmer_int);
+
+    __builtin_prefetch(&((hash_set*) set)->full[position]);
+    __builtin_prefetch(&((hash_set*) set)->kmers[position]);
+}
+
+/*
+ * The number of k-mers count_kmers_in_packed_lookup finds before looking
+ * them up
+ */
+#define PACKED_BATCH_SIZE 16
+
+/*
+ * Count the k-mers of a sequence packed by pack_seq that are in each of two
+ * sets of k-mers, like count_kmers_in_read_lookup, but only those starting
+ * between two positions, so that a long sequence can be counted window by
+ * window without packing it again.
+ *
+ * Args:
+ *     words: the packed bases of the sequence
+ *     invalid: the invalid bases of the sequence
+ *     length: the length of the sequence
+ *     start: the position of the first k-mer to count
+ *     end: the position after the last k-mer to count. k-mers running past
+ *         the end of the sequence aren't counted.
+ *     k, lookup_A, haplotype_A, lookup_B, haplotype_B, count_A, count_B,
+ *     num_skipped: as for count_kmers_in_read_lookup, with the k-mers
+ *         covering an invalid base skipped
+ *     step: only look at the k-mers starting at a multiple of this many bases
+ *         after start
+ */
+void count_kmers_in_packed_lookup(
+    uint64_t* words,
+    uint64_t* invalid,
+    size_t length,
+    size_t start,
+    size_t end,
+    unsigned char k,
+    kmer_lookup lookup_A,
+    void* haplotype_A,
+    kmer_lookup lookup_B,
+    void* haplotype_B,
+    int* count_A,
+    int* count_B,
+    int* num_skipped,
+    unsigned int step
+) {
+    uint64_t batch[PACKED_BATCH_SIZE];
+    size_t i = start, j, batch_size;
+
+    *count_A = 0;
+    *count_B = 0;
+    *num_skipped = 0;
+    if (length < k) {
+        return;
+    }
+    if (end > length - k + 1) {
+        end = length - k + 1;
+    }
+
+    while (i < end) {
+        // the k-mers can be taken from anywhere in the packed sequence, so
+        // a batch of them is found first and their slots in hash sets are
+        // fetched into the cache while the rest are found
+        for (batch_size = 0; i < end && batch_size < PACKED_BATCH_SIZE; i += step) {
+            if (packed_kmer_invalid(invalid, i, k)) {
+                (*num_skipped)++;
+                continue;
+            }
+            batch[batch_size] = canonicalize_int(packed_kmer(words, i, k), k);
+            if (lookup_A == hash_set_lookup) {
+                prefetch_in_hash_set(haplotype_A, batch[batch_size]);
+            }
+            if (lookup_B == hash_set_lookup) {
+                prefetch_in_hash_set(haplotype_B, batch[batch_size]);
+            }
+            batch_size++;
+        }
+
+        for (j = 0; j < batch_size; j++) {
+            if (lookup_A(haplotype_A, batch[j])) {
+                (*count_A)++;
+            } else if (lookup_B(haplotype_B, batch[j])) {
+                (*count_B)++;
+            }
+        }
+    }
+}
+
+/*
+ * Check whether any k-mer one substitution away from a k-mer is in a set.
+ *
+ * Each of the k 2-bit bases is changed to each of the other three bases in
+ * turn, so this takes up to 3k lookups, stopping at the first one found.
+ *
+ * Args:
+ *     lookup: function to look up canonical k-mers in kmers
+ *     kmers: the k-mers to look in
+ *     kmer_int: the k-mer, in either orientation
+ *     k: the k-mer size
+ *
+ * Returns: 1 if a neighbor of the k-mer is in the set, 0 otherwise
+ */
+char neighbor_in_lookup(
+    kmer_lookup lookup, void* kmers, uint64_t kmer_int, unsigned char k
+) {
+    unsigned char position;
+    uint64_t substitution;
+
+    for (position = 0; position < k; position++) {
+        for (substitution = 1; substitution < 4; substitution++) {
+            if (lookup(kmers, canonicalize_int(
+                    kmer_int ^ substitution << (2 * position), k))) {
+                return 1;
+            }
+        }
+    }
+    return 0;
+}
+
+/*
+ * Count the k-mers in a read that are in each of two sets of k-mers, or one
+ * substitution away from a k-mer in them, e.g., because of a sequencing
+ * error.
+ *
+ * This is count_kmers_in_read_lookup, but a k-mer that isn't in either set is
+ * then checked for neighbors in both haplotypes with neighbor_in_lookup, and
+ * counted in fuzzy_A or fuzzy_B if it only has them in that one. A k-mer with
+ * neighbors in both, e.g., with an error at a SNP between the haplotypes,
+ * says nothing about which one it's from, so it isn't counted in either.
+ * Only k-mers that aren't exact hits are checked, so this is only much slower
+ * than exact matching for reads with few hits.
+ *
+ * Args:
+ *     fuzzy_A: place to put the number of k-mers with a neighbor in
+ *         haplotype A only, but not in either haplotype themselves
+ *     fuzzy_B: place to put the number of k-mers with a neighbor in
+ *         haplotype B only
+ *     See count_kmers_in_read_lookup for the rest.
+ */
+void count_kmers_in_read_fuzzy(
+    char* read,
+    char* quals,
+    unsigned char k,
+    kmer_lookup lookup_A,
+    void* haplotype_A,
+    kmer_lookup lookup_B,
+    void* haplotype_B,
+    int* count_A,
+    int* count_B,
+    int* fuzzy_A,
+    int* fuzzy_B,
+    int* num_skipped,
+    char skip_lowercase,
+    char min_quality,
+    unsigned int step
+) {
+    int base_int, num_valid_bases = 0;
+    unsigned char shift = 2 * (k - 1);
+    uint64_t kmer_int = 0, canonical_kmer_int;
+    size_t i, read_length = strlen(read);
+    char near_A, near_B;
+
+    *count_A = 0;
+    *count_B = 0;
+    *fuzzy_A = 0;
+    *fuzzy_B = 0;
+    *num_skipped = 0;
+
+    for (i = 0; i < read_length; i++)
+    {
+        base_int = base_to_int(read[i], skip_lowercase);
+        if (base_int < 0 || (quals != NULL && quals[i] < min_quality)) {
+            num_valid_bases = 0;
+        } else {
+            kmer_int = kmer_int >> 2 | (uint64_t) base_int << shift;
+            num_valid_bases++;
+        }
+
+        if (i + 1 < k || (i + 1 - k) % step != 0) {
+            continue;
+        } else if (num_valid_bases < k) {
+            (*num_skipped)++;
+            continue;
+        }
+
+        canonical_kmer_int = canonicalize_int(kmer_int, k);
+        if (lookup_A(haplotype_A, canonical_kmer_int)) {
+            (*count_A)++;
+        } else if (lookup_B(haplotype_B, canonical_kmer_int)) {
+            (*count_B)++;
+        } else {
+            near_A = neighbor_in_lookup(lookup_A, haplotype_A, kmer_int, k);
+            near_B = neighbor_in_lookup(lookup_B, haplotype_B, kmer_int, k);
+            if (near_A && !near_B) {
+                (*fuzzy_A)++;
+            } else if (near_B && !near_A) {
+                (*fuzzy_B)++;
+            }
+        }
+    }
+}
+
+/*
+ * The largest number of k-mers in a minimizer window.
+ */
+#define MAX_MINIMIZER_WINDOW 256
+
+/*
+ * Thomas Wang's invertible 64-bit integer hash, restricted to the low bits
+ * in mask. Because it's invertible, distinct k-mers never collide, and
+ * unlike ordering k-mers by their value, it doesn't favor k-mers like
+ * AAAAA...A as minimizers.
+ */
+uint64_t invertible_hash(uint64_t key, uint64_t mask) {
+    key = (~key + (key << 21)) & mask;
+    key = key ^ key >> 24;
+    key = ((key + (key << 3)) + (key << 8)) & mask;
+    key = key ^ key >> 14;
+    key = ((key + (key << 2)) + (key << 4)) & mask;
+    key = key ^ key >> 28;
+    key = (key + (key << 31)) & mask;
+    return key;
+}
+
+/*
+ * The position of the lowest hash in the window of w hashes ending at
+ * position, taking the last one if there's a tie, for the rolling minimum
+ * of minimizers and syncmers. The hashes are in a ring buffer indexed by
+ * position modulo w.
+ *
+ * Args:
+ *     have_min: whether min_position is the minimum of the window ending at
+ *         position - 1, so that only the new hash can replace it while it's
+ *         still in the window. Otherwise the whole window is searched.
+ */
+static inline size_t window_min_position(
+    const uint64_t* hashes,
+    unsigned int w,
+    size_t position,
+    char have_min,
+    size_t min_position
+) {
+    size_t j;
+
+    if (have_min && min_position + w > position) {
+        return hashes[position % w] <= hashes[min_position % w]
+            ? position
+            : min_position;
+    }
+    min_position = position + 1 - w;
+    for (j = min_position + 1; j <= position; j++) {
+        if (hashes[j % w] <= hashes[min_position % w]) {
+            min_position = j;
+        }
+    }
+    return min_position;
+}
+
+/*
+ * Count the minimizers of a read that are in each of two sets of k-mers.
+ *
+ * The minimizer of each window of w consecutive k-mers is its canonical
+ * k-mer with the lowest invertible_hash, taking the last one if there's a
+ * tie. Consecutive windows usually share a minimizer, which is only looked
+ * up once, so about 2 / (w + 1) of the k-mers are looked up. Windows that
+ * include a skipped k-mer have no minimizer, and the skipped k-mers are
+ * counted in num_skipped like in count_kmers_in_read_lookup.
+ *
+ * Args:
+ *     w: the number of k-mers in each window, from 1 to MAX_MINIMIZER_WINDOW.
+ *         With w = 1 every k-mer is looked up.
+ *     num_minimizers: place to put the number of minimizers looked up
+ *     See count_kmers_in_read_lookup for the rest.
+ */
+void count_minimizers_in_read_lookup(
+    char* read,
+    char* quals,
+    unsigned char k,
+    unsigned int w,
+    kmer_lookup lookup_A,
+    void* haplotype_A,
+    kmer_lookup lookup_B,
+    void* haplotype_B,
+    int* count_A,
+    int* count_B,
+    int* num_minimizers,
+    int* num_skipped,
+    char skip_lowercase,
+    char min_quality
+) {
+    int base_int, num_valid_bases = 0;
+    unsigned char shift = 2 * (k - 1);
+    uint64_t kmer_int = 0;
+    uint64_t mask = k == 32 ? UINT64_MAX : (UINT64_C(1) << 2 * k) - 1;
+    // the canonical k-mers of the current window and their hashes, in a
+    // ring buffer indexed by the k-mer's position modulo w
+    uint64_t canonical[MAX_MINIMIZER_WINDOW], hashes[MAX_MINIMIZER_WINDOW];
+    size_t i, position, new_min_position, min_position = 0;
+    size_t read_length = strlen(read);
+    size_t num_valid_kmers = 0;
+    char have_min = 0;
+
+    assert(w >= 1 && w <= MAX_MINIMIZER_WINDOW);
     *count_A = 0;
     *count_B = 0;
+    *num_minimizers = 0;
+    *num_skipped = 0;
+
+    for (i = 0; i < read_length; i++)
+    {
+        base_int = base_to_int(read[i], skip_lowercase);
+        if (base_int < 0 || (quals != NULL && quals[i] < min_quality)) {
+            num_valid_bases = 0;
+        } else {
+            kmer_int = kmer_int >> 2 | (uint64_t) base_int << shift;
+            num_valid_bases++;
+        }
+
+        if (i + 1 < k) {
+            continue;
+        } else if (num_valid_bases < k) {
+            (*num_skipped)++;
+            num_valid_kmers = 0;
+            have_min = 0;
+            continue;
+        }
+
+        // the position of the k-mer, counting from 0
+        position = i + 1 - k;
+        canonical[position % w] = canonicalize_int(kmer_int, k);
+        hashes[position % w] = invertible_hash(canonical[position % w], mask);
+        num_valid_kmers++;
+        if (num_valid_kmers < w) {
+            continue;
+        }
+
+        new_min_position = window_min_position(
+            hashes, w, position, have_min, min_position
+        );
+        if (have_min && new_min_position == min_position) {
+            // the last minimizer is still the window's, and was looked up
+            continue;
+        }
+        min_position = new_min_position;
+        have_min = 1;
+
+        (*num_minimizers)++;
+        if (lookup_A(haplotype_A, canonical[min_position % w])) {
+            (*count_A)++;
+        } else if (lookup_B(haplotype_B, canonical[min_position % w])) {
+            (*count_B)++;
+        }
+    }
+}
+
+/*
+ * Count the closed syncmers of a read that are in each of two sets of
+ * k-mers.
+ *
+ * A k-mer is a closed syncmer if the lowest invertible_hash of its
+ * canonical s-mers is that of its first or last s-mer. Each k-mer is
+ * picked or not on its own, by its own bases, so reads sharing a k-mer
+ * always agree on it, and about 2 / (k - s + 1) of them are picked, more
+ * evenly spread than minimizers. The s-mers of the reverse complement of a
+ * k-mer are those of the k-mer in reverse order, so either strand picks
+ * the same k-mers. Skipped k-mers are counted in num_skipped like in
+ * count_kmers_in_read_lookup.
+ *
+ * Args:
+ *     s: the s-mer size, from 1 to k - 1
+ *     num_syncmers: place to put the number of syncmers looked up
+ *     See count_kmers_in_read_lookup for the rest.
+ */
+void count_syncmers_in_read_lookup(
+    char* read,
+    char* quals,
+    unsigned char k,
+    unsigned char s,
+    kmer_lookup lookup_A,
+    void* haplotype_A,
+    kmer_lookup lookup_B,
+    void* haplotype_B,
+    int* count_A,
+    int* count_B,
+    int* num_syncmers,
+    int* num_skipped,
+    char skip_lowercase,
+    char min_quality
+) {
+    int base_int, num_valid_bases = 0;
+    unsigned char shift = 2 * (k - 1), smer_shift = 2 * (s - 1);
+    unsigned int w = k - s + 1;
+    uint64_t kmer_int = 0, smer_int = 0, kmer_canonical;
+    uint64_t smer_mask = (UINT64_C(1) << 2 * s) - 1;
+    // the hashes of the canonical s-mers of the current k-mer, in a ring
+    // buffer indexed by the s-mer's position modulo w, which is below 32
+    uint64_t hashes[32];
+    size_t i, position, first, min_position = 0, read_length = strlen(read);
+    char have_min = 0;
 
-    int read_length = strlen(read);
+    assert(s >= 1 && s < k && k <= 32);
+    *count_A = 0;
+    *count_B = 0;
+    *num_syncmers = 0;
+    *num_skipped = 0;
 
-    for (i = 0; i < read_length - haplotype_A->k + 1; i++)
+    for (i = 0; i < read_length; i++)
     {
-        for (j = 0; j < haplotype_A->k; j++)
-            kmer[j] = read[i+j];
-        if (kmer_in_hash_set(kmer, kmer_revcomp, haplotype_A))
+        base_int = base_to_int(read[i], skip_lowercase);
+        if (base_int < 0 || (quals != NULL && quals[i] < min_quality)) {
+            num_valid_bases = 0;
+        } else {
+            kmer_int = kmer_int >> 2 | (uint64_t) base_int << shift;
+            smer_int = smer_int >> 2 | (uint64_t) base_int << smer_shift;
+            num_valid_bases++;
+        }
+
+        if (num_valid_bases >= s) {
+            // the position of the s-mer, counting from 0
+            position = i + 1 - s;
+            hashes[position % w] = invertible_hash(
+                canonicalize_int(smer_int, s), smer_mask
+            );
+        }
+        if (i + 1 < k) {
+            continue;
+        } else if (num_valid_bases < k) {
+            (*num_skipped)++;
+            have_min = 0;
+            continue;
+        }
+
+        // the k-mer's first and last s-mers
+        first = i + 1 - k;
+        position = i + 1 - s;
+        min_position = window_min_position(
+            hashes, w, position, have_min, min_position
+        );
+        have_min = 1;
+        if (hashes[min_position % w] != hashes[first % w]
+                && hashes[min_position % w] != hashes[position % w]) {
+            continue;
+        }
+
+        (*num_syncmers)++;
+        kmer_canonical = canonicalize_int(kmer_int, k);
+        if (lookup_A(haplotype_A, kmer_canonical)) {
+            (*count_A)++;
+        } else if (lookup_B(haplotype_B, kmer_canonical)) {
+            (*count_B)++;
+        }
+    }
+}
+
+/*
+ * The most runs of consecutive care positions a spaced seed can have. Seeds
+ * span at most 32 bases and start and end with a care position, so there can
+ * be at most 16.
+ */
+#define MAX_SEED_RUNS 16
+
+/*
+ * A spaced seed: a pattern of the positions in a window of bases that are
+ * looked at (care positions) and ignored, kept as runs of consecutive care
+ * positions so that they can be taken out of a window a run at a time.
+ */
+typedef struct {
+    /*
+     * The number of bases in the window
+     */
+    unsigned char span;
+
+    /*
+     * The number of care positions, which is the k of the k-mers made
+     */
+    unsigned char weight;
+
+    unsigned char num_runs;
+
+    /*
+     * The position in the window of the first base of each run
+     */
+    unsigned char run_starts[MAX_SEED_RUNS];
+
+    /*
+     * The position in the k-mer made that each run goes to
+     */
+    unsigned char run_offsets[MAX_SEED_RUNS];
+
+    /*
+     * The mask of the bits of each run, once shifted down to the bottom
+     */
+    uint64_t run_masks[MAX_SEED_RUNS];
+} spaced_seed;
+
+/*
+ * Make a spaced seed from its pattern, as a mask with bit i set if position
+ * i of the window is a care position
+ *
+ * Args:
+ *     care: the care positions. Bits 0 and span - 1 must be set.
+ *     span: the number of bases in the window (max 32)
+ *     seed: place to put the seed
+ */
+static void make_spaced_seed(uint32_t care, unsigned char span, spaced_seed* seed) {
+    unsigned char i, run_length;
+
+    seed->span = span;
+    seed->weight = 0;
+    seed->num_runs = 0;
+    i = 0;
+    while (i < span) {
+        if (!(care >> i & 1)) {
+            i++;
+            continue;
+        }
+        seed->run_starts[seed->num_runs] = i;
+        seed->run_offsets[seed->num_runs] = seed->weight;
+        for (run_length = 0; i < span && care >> i & 1; i++) {
+            run_length++;
+        }
+        seed->run_masks[seed->num_runs] = run_length == 32
+            ? UINT64_MAX
+            : (UINT64_C(1) << 2 * run_length) - 1;
+        seed->weight += run_length;
+        seed->num_runs++;
+    }
+}
+
+/*
+ * Take the bases at a spaced seed's care positions out of a window of bases
+ * in integer format and pack them into a k-mer of the seed's weight
+ */
+static inline uint64_t apply_spaced_seed(const spaced_seed* seed, uint64_t window) {
+    uint64_t kmer_int = 0;
+    unsigned char i;
+
+    for (i = 0; i < seed->num_runs; i++) {
+        kmer_int |= (window >> 2 * seed->run_starts[i] & seed->run_masks[i])
+            << 2 * seed->run_offsets[i];
+    }
+    return kmer_int;
+}
+
+/*
+ * The canonical form of the k-mer a spaced seed makes of a window, given the
+ * window and its reverse complement: the lesser of the seed applied to each,
+ * so that either strand gives the same k-mer. k-mer sets store k-mers by
+ * their canonical form, so that is then canonicalized as a k-mer too, which
+ * only changes anything for a pattern that isn't the same backwards. Such a
+ * pattern's don't-care positions are care positions on the other strand, so
+ * only a symmetric one tolerates mismatches whichever k-mer is the lesser.
+ */
+static inline uint64_t canonical_spaced_kmer(
+    const spaced_seed* seed, uint64_t window, uint64_t window_revcomp
+) {
+    uint64_t kmer_int = apply_spaced_seed(seed, window);
+    uint64_t kmer_revcomp_int = apply_spaced_seed(seed, window_revcomp);
+
+    if (kmer_revcomp_int < kmer_int) {
+        kmer_int = kmer_revcomp_int;
+    }
+    return canonicalize_int(kmer_int, seed->weight);
+}
+
+/*
+ * Get the k-mers a spaced seed makes of a read, like canonical_kmers_in_read
+ * does for contiguous k-mers. Windows containing an invalid base at any
+ * position, care or not, are skipped.
+ *
+ * Args:
+ *     read: the read sequence, null-terminated
+ *     care: the care positions of the seed (see make_spaced_seed)
+ *     span: the number of bases the seed spans
+ *     skip_lowercase: 1 to skip windows with soft-masked (lowercase) bases
+ *     kmer_ints: place to put each k-mer, in the canonical form of
+ *         canonical_spaced_kmer, in order. Must have room for
+ *         strlen(read) - span + 1 k-mers.
+ *
+ * Returns: the number of k-mers put in kmer_ints
+ */
+size_t spaced_kmers_in_read(
+    char* read,
+    uint32_t care,
+    unsigned char span,
+    char skip_lowercase,
+    uint64_t* kmer_ints
+) {
+    spaced_seed seed;
+    int base_int, num_valid_bases = 0;
+    unsigned char shift = 2 * (span - 1);
+    uint64_t window = 0, window_revcomp = 0;
+    uint64_t mask = span == 32 ? UINT64_MAX : (UINT64_C(1) << 2 * span) - 1;
+    size_t i, num_kmers = 0, read_length = strlen(read);
+
+    make_spaced_seed(care, span, &seed);
+    for (i = 0; i < read_length; i++) {
+        base_int = base_to_int(read[i], skip_lowercase);
+        if (base_int < 0) {
+            num_valid_bases = 0;
+            continue;
+        }
+        window = window >> 2 | (uint64_t) base_int << shift;
+        window_revcomp = (window_revcomp << 2 | (uint64_t) (3 - base_int)) & mask;
+        num_valid_bases++;
+        if (num_valid_bases >= span) {
+            kmer_ints[num_kmers++] = canonical_spaced_kmer(
+                &seed, window, window_revcomp
+            );
+        }
+    }
+    return num_kmers;
+}
+
+/*
+ * Count the k-mers a spaced seed makes of a read that are in each of two sets
+ * of k-mers, like count_kmers_in_read_lookup does for contiguous k-mers. The
+ * windows are skipped, looked up and stepped over like its k-mers.
+ *
+ * Args:
+ *     care: the care positions of the seed (see make_spaced_seed)
+ *     span: the number of bases the seed spans. The sets must have k-mers
+ *         of the seed's weight.
+ *     See count_kmers_in_read_lookup for the rest.
+ */
+void count_spaced_kmers_in_read_lookup(
+    char* read,
+    char* quals,
+    uint32_t care,
+    unsigned char span,
+    kmer_lookup lookup_A,
+    void* haplotype_A,
+    kmer_lookup lookup_B,
+    void* haplotype_B,
+    int* count_A,
+    int* count_B,
+    int* num_skipped,
+    char skip_lowercase,
+    char min_quality,
+    unsigned int step
+) {
+    spaced_seed seed;
+    int base_int, num_valid_bases = 0;
+    unsigned char shift = 2 * (span - 1);
+    uint64_t window = 0, window_revcomp = 0, kmer_int;
+    uint64_t mask = span == 32 ? UINT64_MAX : (UINT64_C(1) << 2 * span) - 1;
+    size_t i, read_length = strlen(read);
+
+    *count_A = 0;
+    *count_B = 0;
+    *num_skipped = 0;
+
+    make_spaced_seed(care, span, &seed);
+    for (i = 0; i < read_length; i++) {
+        base_int = base_to_int(read[i], skip_lowercase);
+        if (base_int < 0 || (quals != NULL && quals[i] < min_quality)) {
+            num_valid_bases = 0;
+        } else {
+            window = window >> 2 | (uint64_t) base_int << shift;
+            window_revcomp = (window_revcomp << 2 | (uint64_t) (3 - base_int)) & mask;
+            num_valid_bases++;
+        }
+
+        if (i + 1 < span || (i + 1 - span) % step != 0) {
+            continue;
+        } else if (num_valid_bases < span) {
+            (*num_skipped)++;
+            continue;
+        }
+
+        kmer_int = canonical_spaced_kmer(&seed, window, window_revcomp);
+        if (lookup_A(haplotype_A, kmer_int)) {
             (*count_A)++;
-        else if (kmer_in_hash_set(kmer, kmer_revcomp, haplotype_B))
+        } else if (lookup_B(haplotype_B, kmer_int)) {
             (*count_B)++;
+        }
+    }
+}
+
+/*
+ * Count the k-mers in a read that are in each of two hash sets.
+ *
+ * This is count_kmers_in_read_lookup for hash sets.
+ */
+void count_kmers_in_read_skipping(
+    char* read,
+    hash_set* haplotype_A,
+    hash_set* haplotype_B,
+    int* count_A,
+    int* count_B,
+    int* num_skipped,
+    char skip_lowercase
+) {
+    count_kmers_in_read_lookup(
+        read,
+        NULL,
+        haplotype_A->k,
+        hash_set_lookup,
+        haplotype_A,
+        hash_set_lookup,
+        haplotype_B,
+        count_A,
+        count_B,
+        num_skipped,
+        skip_lowercase,
+        0,
+        1
+    );
+}
+
+/*
+ * Count the k-mers in a read that are in each of two sets.
+ *
+ * This is the same as count_kmers_in_read_skipping, without the number of
+ * k-mers skipped, and with lowercase bases treated the same as uppercase.
+ */
+void count_kmers_in_read(
+    char* read,
+    hash_set* haplotype_A,
+    hash_set* haplotype_B,
+    int* count_A,
+    int* count_B
+) {
+    int num_skipped;
+    count_kmers_in_read_skipping(
+        read, haplotype_A, haplotype_B, count_A, count_B, &num_skipped, 0
+    );
+}
+
+/*
+ * The C interface in trio_binning.h. A tb_kmerset wraps a hash_set so that
+ * the header doesn't have to show what's in one.
+ */
+struct tb_kmerset {
+    hash_set* set;
+};
+
+tb_kmerset* tb_kmerset_load(const char* path) {
+    FILE* fp;
+    char* line_buffer = NULL;
+    size_t capacity = 0, length;
+    int k = 0;
+    uint64_t kmer_int;
+    hash_set* set = NULL;
+    tb_kmerset* kmers;
+
+    if (path == NULL) {
+        return NULL;
+    }
+    fp = fopen(path, "r");
+    if (fp == NULL) {
+        return NULL;
+    }
+    while (getline(&line_buffer, &capacity, fp) != -1) {
+        // the k-mer is everything before a tab, like a count, or the newline
+        length = strcspn(line_buffer, "\t\r\n");
+        if (length == 0) {
+            continue;
+        }
+        if (k == 0) {
+            if (length > 32) {
+                goto error;
+            }
+            k = (int) length;
+            set = initialize_hash_set(k, 1024);
+        }
+        if (length != (size_t) k
+                || kmer_to_int_checked(line_buffer, k, &kmer_int) >= 0) {
+            goto error;
+        }
+        add_int_to_hash(set, kmer_int);
+    }
+    if (ferror(fp) || set == NULL) {
+        goto error;
+    }
+    kmers = malloc(sizeof(tb_kmerset));
+    if (kmers == NULL) {
+        goto error;
+    }
+    free(line_buffer);
+    fclose(fp);
+    kmers->set = set;
+    return kmers;
+
+error:
+    free(line_buffer);
+    fclose(fp);
+    if (set != NULL) {
+        free_hash_set(set);
+    }
+    return NULL;
+}
+
+tb_kmerset* tb_kmerset_new(int k) {
+    tb_kmerset* kmers;
+
+    if (k < 1 || k > 32) {
+        return NULL;
+    }
+    kmers = malloc(sizeof(tb_kmerset));
+    if (kmers == NULL) {
+        return NULL;
+    }
+    kmers->set = initialize_hash_set(k, 1024);
+    return kmers;
+}
+
+int tb_kmerset_add(tb_kmerset* kmers, const char* kmer, size_t kmer_len) {
+    char buffer[33];
+    uint64_t kmer_int;
+
+    if (kmers == NULL || kmer == NULL) {
+        return TB_ERROR_NULL;
+    }
+    if (kmer_len != (size_t) kmers->set->k) {
+        return TB_ERROR_INVALID_KMER;
+    }
+    memcpy(buffer, kmer, kmer_len);
+    buffer[kmer_len] = '\0';
+    if (kmer_to_int_checked(buffer, kmer_len, &kmer_int) >= 0) {
+        return TB_ERROR_INVALID_KMER;
+    }
+    add_int_to_hash(kmers->set, kmer_int);
+    return TB_OK;
+}
+
+void tb_kmerset_free(tb_kmerset* kmers) {
+    if (kmers == NULL) {
+        return;
+    }
+    free_hash_set(kmers->set);
+    free(kmers);
+}
+
+int tb_kmerset_k(const tb_kmerset* kmers) {
+    return kmers == NULL ? 0 : kmers->set->k;
+}
+
+size_t tb_kmerset_size(const tb_kmerset* kmers) {
+    return kmers == NULL ? 0 : (size_t) kmers->set->num_kmers;
+}
+
+int tb_classify_seq(
+    const tb_kmerset* hap_a,
+    const tb_kmerset* hap_b,
+    const char* seq,
+    size_t seq_len,
+    tb_result* result
+) {
+    char* read;
+    int count_A, count_B, num_skipped, num_positions;
+    unsigned char k;
+    double max_num_kmers;
+
+    if (hap_a == NULL || hap_b == NULL || seq == NULL || result == NULL) {
+        return TB_ERROR_NULL;
+    }
+    k = hap_a->set->k;
+    if (hap_b->set->k != k) {
+        return TB_ERROR_K_MISMATCH;
     }
+    // the counts are scaled by the size of each set
+    if (hap_a->set->num_kmers == 0 || hap_b->set->num_kmers == 0) {
+        return TB_ERROR_EMPTY;
+    }
+    // count_kmers_in_read_lookup needs a null-terminated read
+    read = malloc(seq_len + 1);
+    if (read == NULL) {
+        return TB_ERROR_MEMORY;
+    }
+    memcpy(read, seq, seq_len);
+    read[seq_len] = '\0';
+    count_kmers_in_read_lookup(
+        read,
+        NULL,
+        k,
+        hash_set_lookup,
+        hap_a->set,
+        hash_set_lookup,
+        hap_b->set,
+        &count_A,
+        &count_B,
+        &num_skipped,
+        0,
+        0,
+        1
+    );
+    num_positions = strlen(read) >= k ? (int) (strlen(read) - k + 1) : 0;
+    free(read);
 
-    free(kmer);
-    free(kmer_revcomp);
+    // scale up the counts of the haplotype with fewer k-mers, as
+    // calculate_scaling_factors does
+    max_num_kmers = hap_a->set->num_kmers > hap_b->set->num_kmers
+        ? hap_a->set->num_kmers : hap_b->set->num_kmers;
+    result->hap_a_count = count_A;
+    result->hap_b_count = count_B;
+    result->hap_a_score = count_A * max_num_kmers / hap_a->set->num_kmers;
+    result->hap_b_score = count_B * max_num_kmers / hap_b->set->num_kmers;
+    result->num_kmers = num_positions - num_skipped;
+    result->num_skipped = num_skipped;
+    if (result->hap_a_score > result->hap_b_score) {
+        result->bin = 'A';
+    } else if (result->hap_b_score > result->hap_a_score) {
+        result->bin = 'B';
+    } else {
+        result->bin = 'U';
+    }
+    return TB_OK;
 }
 
+/*
+ * A demo of reading hapA.txt and hapB.txt and classifying a read, built
+ * with -DKMERS_MAIN. It's left out otherwise so that kmers.c can be
+ * compiled into programs with their own main, for trio_binning.h.
+ */
+#ifdef KMERS_MAIN
 int main() {
     fprintf(stderr, "Reading hapA k-mers into hash...\n");
     hash_set* hapA = create_kmer_hash_set("hapA.txt");
@@ -332,7 +2418,8 @@ int main() {
         count_B
     );
 
-    free(hapA);
-    free(hapB);
+    free_hash_set(hapA);
+    free_hash_set(hapB);
     return 0;
 }
+#endif
@@ -0,0 +1,155 @@
+/*
+ * A C interface to trio_binning's k-mer sets and classification, for
+ * programs in C or C++ that want to classify reads as they load them
+ * without going through Python.
+ *
+ * Build it by compiling kmers.c into the program, or into a library to link
+ * with. Only plain C types cross the interface, and every function returns
+ * an error code or NULL rather than aborting, so a bad input can never take
+ * the calling program down.
+ */
+#ifndef TRIO_BINNING_H
+#define TRIO_BINNING_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * The error codes of tb_classify_seq
+ */
+#define TB_OK 0
+#define TB_ERROR_NULL -1
+#define TB_ERROR_K_MISMATCH -2
+#define TB_ERROR_MEMORY -3
+#define TB_ERROR_EMPTY -4
+#define TB_ERROR_INVALID_KMER -5
+
+/*
+ * A set of k-mers specific to one haplotype, as loaded by
+ * tb_kmerset_load. It's only read by tb_classify_seq, so it can be shared
+ * by any number of threads.
+ */
+typedef struct tb_kmerset tb_kmerset;
+
+/*
+ * The result of classifying a sequence, as in trio_binning's
+ * Classification with the default thresholds
+ */
+typedef struct {
+    /*
+     * The number of k-mers in the sequence found in haplotype A
+     */
+    int hap_a_count;
+
+    /*
+     * The number of k-mers in the sequence found in haplotype B
+     */
+    int hap_b_count;
+
+    /*
+     * hap_a_count, scaled up if haplotype A has fewer k-mers than B
+     */
+    double hap_a_score;
+
+    /*
+     * hap_b_count, scaled up if haplotype B has fewer k-mers than A
+     */
+    double hap_b_score;
+
+    /*
+     * The number of k-mers looked up
+     */
+    int num_kmers;
+
+    /*
+     * The number of k-mers skipped for containing a character other than
+     * [ACGTacgt], e.g., an N
+     */
+    int num_skipped;
+
+    /*
+     * 'A' or 'B' for the haplotype with the higher score, or 'U' for a tie
+     */
+    char bin;
+} tb_result;
+
+/*
+ * Load a list of k-mers, one per line, like the ones find-unique-kmers
+ * writes. Anything after a tab on a line, like a count, is ignored, as are
+ * blank lines.
+ *
+ * Returns: the k-mer set, to be freed with tb_kmerset_free, or NULL if the
+ *     file can't be read, has no k-mers, or has a k-mer that isn't all
+ *     [ACGTacgt], is longer than 32 bases or another length than the first
+ */
+tb_kmerset* tb_kmerset_load(const char* path);
+
+/*
+ * Make an empty set of k-mers of k bases, to add k-mers to with
+ * tb_kmerset_add
+ *
+ * Returns: the k-mer set, to be freed with tb_kmerset_free, or NULL if k
+ *     isn't from 1 to 32 or there's no memory for it
+ */
+tb_kmerset* tb_kmerset_new(int k);
+
+/*
+ * Add a k-mer to a set, in either orientation
+ *
+ * Args:
+ *     kmers: the set to add it to
+ *     kmer: the k-mer, which needn't be null-terminated
+ *     kmer_len: the length of kmer, which must be the k of the set
+ *
+ * Returns: TB_OK, TB_ERROR_NULL if an argument is NULL, or
+ *     TB_ERROR_INVALID_KMER if the k-mer is the wrong length or isn't all
+ *     [ACGTacgt]
+ */
+int tb_kmerset_add(tb_kmerset* kmers, const char* kmer, size_t kmer_len);
+
+/*
+ * Free a k-mer set from tb_kmerset_load or tb_kmerset_new. NULL is ignored.
+ */
+void tb_kmerset_free(tb_kmerset* kmers);
+
+/*
+ * The k-mer size of a set, or 0 for NULL
+ */
+int tb_kmerset_k(const tb_kmerset* kmers);
+
+/*
+ * The number of k-mers in a set, or 0 for NULL
+ */
+size_t tb_kmerset_size(const tb_kmerset* kmers);
+
+/*
+ * Classify a sequence by its k-mers in the two haplotypes
+ *
+ * Args:
+ *     hap_a: the k-mers specific to haplotype A
+ *     hap_b: the k-mers specific to haplotype B, with the same k
+ *     seq: the sequence, which needn't be null-terminated
+ *     seq_len: the length of seq
+ *     result: place to put the result. Not modified on error.
+ *
+ * Returns: TB_OK, or TB_ERROR_NULL if an argument is NULL,
+ *     TB_ERROR_K_MISMATCH if the sets have different k, TB_ERROR_EMPTY if
+ *     either set has no k-mers, so that its counts can't be scaled, or
+ *     TB_ERROR_MEMORY
+ */
+int tb_classify_seq(
+    const tb_kmerset* hap_a,
+    const tb_kmerset* hap_b,
+    const char* seq,
+    size_t seq_len,
+    tb_result* result
+);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -1,3 +1,11 @@
 from setuptools import Extension, setup
 
-setup(ext_modules=[Extension(name="trio_binning.kmers_c", sources=["c/kmers.c"])])
+setup(
+    ext_modules=[
+        Extension(
+            name="trio_binning.kmers_c",
+            sources=["c/kmers.c"],
+            depends=["c/trio_binning.h"],
+        )
+    ]
+)
@@ -0,0 +1,193 @@
+"""Masking leftover adapter sequence in reads before classifying them.
+
+Adapter sequence left at the ends of reads has k-mers from neither
+parent, so it adds k-mers looked up but never hits, dragging the hits per
+kb down. `AdapterMasker` finds the adapters near the ends of each read,
+exactly or with one mismatch, in either orientation, and replaces them
+with N's, so their k-mers are skipped like any others with an N.
+
+Each read is packed with `kmers.PackedSeq` and each adapter compared with
+the read 32 bases at a time, as 64-bit integers, rather than base by base.
+"""
+
+from typing import Iterable, List, Sequence, Tuple
+
+from trio_binning import kmers, seq
+
+DEFAULT_SEARCH_LENGTH = 150
+"""The number of bases at each end of a read adapters are looked for in"""
+
+MIN_ADAPTER_LENGTH = 8
+"""The shortest adapter allowed, since shorter ones, with a mismatch,
+turn up by chance all over the reads"""
+
+_CHUNK = 32
+"""The number of bases of an adapter compared at once"""
+
+_LOW_BITS = 0x5555555555555555
+"""The low bit of each base of a packed 32-mer"""
+
+
+class Adapter:
+    """An adapter sequence packed into chunks of up to 32 bases, to compare
+    with `kmers.PackedSeq.kmer_at`"""
+
+    def __init__(self, sequence: str):
+        self.sequence = sequence
+        self.chunks: List[Tuple[int, int, int]] = [
+            (offset, len(chunk), kmers.kmer_to_int(chunk))
+            for offset in range(0, len(sequence), _CHUNK)
+            for chunk in [sequence[offset : offset + _CHUNK]]
+        ]
+
+    def __len__(self) -> int:
+        return len(self.sequence)
+
+    def mismatches(self, packed: kmers.PackedSeq, position: int, most: int) -> int:
+        """The number of mismatches between the adapter and a read at a
+        position, counting bases of the read like N's as mismatches, or
+        more than `most` once there are more than that"""
+        num_mismatches = 0
+        for offset, length, chunk_int in self.chunks:
+            kmer_int = packed.kmer_at(position + offset, length)
+            if kmer_int is None:
+                # only some of the chunk's bases are invalid, so check them
+                # one at a time
+                for i in range(length):
+                    base = packed.get(position + offset + i)
+                    if base is None or base != chunk_int >> 2 * i & 3:
+                        num_mismatches += 1
+            else:
+                diff = kmer_int ^ chunk_int
+                num_mismatches += bin((diff | diff >> 1) & _LOW_BITS).count("1")
+            if num_mismatches > most:
+                break
+        return num_mismatches
+
+
+class AdapterMasker:
+    """Masks adapters near the ends of reads with N's
+
+    An adapter is found where it overlaps the first or last `search_length`
+    bases of a read, or anywhere with `search_length` 0, with at most
+    `max_mismatches` mismatches, in the orientation given or reverse
+    complemented. Occurrences may overlap each other, e.g., in an adapter
+    dimer, and are all masked.
+
+    A masker is never changed after it's made, so one can be shared by any
+    number of threads.
+    """
+
+    def __init__(
+        self,
+        adapters: Iterable[str],
+        search_length: int = DEFAULT_SEARCH_LENGTH,
+        max_mismatches: int = 1,
+    ):
+        """
+        Args:
+            adapters: the adapter sequences, of [ACGTacgt] only
+            search_length: the number of bases at each end of a read to
+                look in, or 0 for the whole read
+            max_mismatches: the most mismatches an occurrence can have, 0
+                or 1
+
+        Raises:
+            ValueError: if there are no adapters, an adapter is shorter
+                than `MIN_ADAPTER_LENGTH` or has a base other than ACGT,
+                `search_length` is negative or `max_mismatches` isn't 0
+                or 1
+        """
+        if search_length < 0:
+            raise ValueError(
+                f"the search length must not be negative, not {search_length}"
+            )
+        if max_mismatches not in (0, 1):
+            raise ValueError(
+                f"the most mismatches must be 0 or 1, not {max_mismatches}"
+            )
+        sequences = []
+        for adapter in adapters:
+            adapter = adapter.upper()
+            if len(adapter) < MIN_ADAPTER_LENGTH:
+                raise ValueError(
+                    f"adapter {adapter!r} is shorter than {MIN_ADAPTER_LENGTH} bases"
+                )
+            if adapter.strip("ACGT"):
+                raise ValueError(f"adapter {adapter!r} has bases other than ACGT")
+            reverse = seq.Read("adapter", adapter).reverse_complement().seq
+            sequences += [adapter] if reverse == adapter else [adapter, reverse]
+        if not sequences:
+            raise ValueError("there are no adapters to mask")
+        self.adapters = [Adapter(sequence) for sequence in dict.fromkeys(sequences)]
+        self.search_length = search_length
+        self.max_mismatches = max_mismatches
+
+    @classmethod
+    def from_file(
+        cls,
+        path: str,
+        search_length: int = DEFAULT_SEARCH_LENGTH,
+        max_mismatches: int = 1,
+    ) -> "AdapterMasker":
+        """Make a masker with the adapters in a fasta file
+
+        Raises:
+            OSError: if the file can't be read
+            seq.SeqError: if the file isn't fasta/q, or an adapter is
+                invalid, as for `AdapterMasker`
+        """
+        with seq.open_text_read(path) as fp:
+            adapters = [record.seq for record in seq.readfq(fp)]
+        try:
+            return cls(adapters, search_length, max_mismatches)
+        except ValueError as e:
+            raise seq.SeqError(f"{path}: {e}") from None
+
+    def find(self, read_seq: str) -> List[Tuple[int, int]]:
+        """Find the adapters in a sequence
+
+        Returns:
+            the 0-based start and exclusive end of each occurrence, in
+            order of start
+        """
+        packed = kmers.PackedSeq.from_str(read_seq)
+        length = len(read_seq)
+        found = []
+        for adapter in self.adapters:
+            last = length - len(adapter)
+            if last < 0:
+                continue
+            if self.search_length == 0 or 2 * self.search_length >= length:
+                positions: Sequence[int] = range(last + 1)
+            else:
+                # overlapping the first or last search_length bases
+                head = range(min(self.search_length, last + 1))
+                tail_start = max(length - self.search_length - len(adapter) + 1, 0)
+                tail = range(max(tail_start, len(head)), last + 1)
+                positions = [*head, *tail]
+            for position in positions:
+                if adapter.mismatches(packed, position, self.max_mismatches) <= (
+                    self.max_mismatches
+                ):
+                    found.append((position, position + len(adapter)))
+        return sorted(found)
+
+    def mask(self, read_seq: str) -> Tuple[str, int]:
+        """Replace the adapters in a sequence with N's
+
+        Returns:
+            the masked sequence, and the number of bases masked that
+            weren't N's before
+        """
+        found = self.find(read_seq)
+        if not found:
+            return read_seq, 0
+        bases = list(read_seq)
+        num_masked = 0
+        for start, end in found:
+            for i in range(start, end):
+                if bases[i] not in "Nn":
+                    bases[i] = "N"
+                    num_masked += 1
+        return "".join(bases), num_masked
@@ -0,0 +1,183 @@
+"""Writing BGZF, the blocked gzip format of BAM and tabix.
+
+A BGZF file is a series of gzip members, or blocks, each of at most 64
+KiB, with the size of the block in a "BC" extra field of its header,
+and ends with an empty block as an end-of-file marker. Since it's valid
+gzip, any gzip reader, including Python's `gzip` module and so
+`seq.open_text_read`, reads it as is, so only writing needs anything
+special. Each block is compressed on its own, so blocks can be
+compressed by several threads at once, which the `zlib` module lets run
+in parallel without the GIL.
+"""
+
+import io
+import struct
+import zlib
+from collections import deque
+from concurrent.futures import Future, ThreadPoolExecutor
+from typing import BinaryIO, Deque, Optional
+
+BLOCK_SIZE = 0xFF00
+"""The most uncompressed bytes in a block, which is what htslib uses,
+leaving room for deflate to make incompressible data a little bigger
+and still fit in a block of 64 KiB"""
+
+HEADER = struct.Struct("<4BI2BH2BHH")
+"""The header of a block: the gzip magic bytes, the compression method,
+the flags, the modification time, the extra flags, the OS, the length of
+the extra fields, and the BC field with the size of the block minus 1"""
+
+FOOTER = struct.Struct("<II")
+"""The footer of a block: the CRC32 and the length of the uncompressed
+data"""
+
+EOF_BLOCK = bytes.fromhex("1f8b08040000000000ff0600424302001b0003000000000000000000")
+"""The empty block that ends every BGZF file"""
+
+
+class BgzfError(ValueError):
+    """A file that isn't valid BGZF"""
+
+
+def compress_block(data: bytes, compression_level: int = 9) -> bytes:
+    """Compress up to `BLOCK_SIZE` bytes into one BGZF block"""
+    if len(data) > BLOCK_SIZE:
+        raise ValueError(f"a block can hold at most {BLOCK_SIZE} bytes")
+    compressor = zlib.compressobj(compression_level, zlib.DEFLATED, -zlib.MAX_WBITS)
+    compressed = compressor.compress(data) + compressor.flush()
+    block_size = HEADER.size + len(compressed) + FOOTER.size
+    header = HEADER.pack(
+        0x1F, 0x8B, 8, 4, 0, 0, 0xFF, 6, ord("B"), ord("C"), 2, block_size - 1
+    )
+    return header + compressed + FOOTER.pack(zlib.crc32(data), len(data))
+
+
+class BgzfWriter(io.BufferedIOBase):
+    """A binary file written as BGZF
+
+    Data is cut into blocks of `BLOCK_SIZE` bytes as it's written.
+    `flush` writes out any part-filled block too, so that everything
+    written so far is in whole blocks that can be read back, and `close`
+    adds the end-of-file block. With more than one thread, up to two
+    blocks per thread are compressed at once while more data is written,
+    and written out in order as they're done.
+    """
+
+    def __init__(
+        self,
+        filename: str,
+        append: bool = False,
+        compression_level: int = 9,
+        threads: int = 1,
+    ):
+        """
+        Args:
+            filename: the file to write to
+            append: whether to add to the file rather than overwrite it,
+                e.g., after cutting it back to the end of a block
+            compression_level: from 1 (fastest) to 9 (smallest)
+            threads: the number of threads to compress blocks with
+        """
+        super().__init__()
+        if threads < 1:
+            raise ValueError(f"threads must be at least 1, not {threads}")
+        self._file = open(filename, "ab" if append else "wb")
+        self._compression_level = compression_level
+        self._buffer = bytearray()
+        self._threads = threads
+        self._executor: Optional[ThreadPoolExecutor] = None
+        if threads > 1:
+            self._executor = ThreadPoolExecutor(threads)
+        self._pending: Deque[Future] = deque()
+
+    @property
+    def name(self) -> str:
+        return self._file.name
+
+    def writable(self) -> bool:
+        return True
+
+    def fileno(self) -> int:
+        return self._file.fileno()
+
+    def _write_block(self, data: bytes):
+        if self._executor is None:
+            self._file.write(compress_block(data, self._compression_level))
+            return
+        self._pending.append(
+            self._executor.submit(compress_block, data, self._compression_level)
+        )
+        while len(self._pending) > 2 * self._threads:
+            self._file.write(self._pending.popleft().result())
+
+    def write(self, data) -> int:  # type: ignore[override]
+        self._buffer += data
+        num_full = len(self._buffer) // BLOCK_SIZE
+        for start in range(0, num_full * BLOCK_SIZE, BLOCK_SIZE):
+            self._write_block(bytes(self._buffer[start : start + BLOCK_SIZE]))
+        del self._buffer[: num_full * BLOCK_SIZE]
+        return len(data)
+
+    def flush(self):
+        if self._buffer:
+            self._write_block(bytes(self._buffer))
+            self._buffer.clear()
+        while self._pending:
+            self._file.write(self._pending.popleft().result())
+        self._file.flush()
+
+    def close(self):
+        if self.closed:
+            return
+        try:
+            super().close()
+            self._file.write(EOF_BLOCK)
+        finally:
+            if self._executor is not None:
+                self._executor.shutdown()
+            self._file.close()
+
+
+def check(fp: BinaryIO) -> int:
+    """Check that a file is whole BGZF, as `bgzip -t` does
+
+    Every block must have a BC extra field with its size, decompress to
+    its CRC32 and length, and the file must end with the end-of-file
+    block.
+
+    Returns:
+        the number of blocks, including the end-of-file block
+
+    Raises:
+        BgzfError: if it isn't
+    """
+    num_blocks = 0
+    last_block = b""
+    while True:
+        header = fp.read(HEADER.size)
+        if not header:
+            break
+        if len(header) < HEADER.size:
+            raise BgzfError(f"block {num_blocks + 1} is cut off in its header")
+        fields = HEADER.unpack(header)
+        # the magic bytes, deflate and the extra flag, then the BC field
+        if fields[:4] != (0x1F, 0x8B, 8, 4) or fields[7:11] != (6, 66, 67, 2):
+            raise BgzfError(f"block {num_blocks + 1} has no BC field")
+        rest_size = fields[11] + 1 - HEADER.size
+        rest = fp.read(rest_size)
+        if len(rest) < rest_size:
+            raise BgzfError(f"block {num_blocks + 1} is cut off")
+        crc, length = FOOTER.unpack(rest[-FOOTER.size :])
+        try:
+            data = zlib.decompress(rest[: -FOOTER.size], -zlib.MAX_WBITS)
+        except zlib.error as e:
+            raise BgzfError(
+                f"block {num_blocks + 1} can't be decompressed: {e}"
+            ) from None
+        if zlib.crc32(data) != crc or len(data) != length:
+            raise BgzfError(f"block {num_blocks + 1} fails its CRC check")
+        num_blocks += 1
+        last_block = header + rest
+    if last_block != EOF_BLOCK:
+        raise BgzfError("the file doesn't end with the end-of-file block")
+    return num_blocks
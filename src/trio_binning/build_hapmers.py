@@ -0,0 +1,386 @@
+"""Find k-mers unique to each parent by counting them in parental reads.
+
+This does the same job as find-unique-kmers, but counts k-mers itself
+instead of running kmc, so it needs no external programs. It keeps all
+of the counts in memory, so it is best suited to small genomes, unless
+--memory-limit is given to count them in temporary files instead.
+"""
+
+import argparse
+import os
+import sys
+from typing import Iterator, List, Optional, Tuple
+
+from trio_binning import count, kmers, seq
+
+DEFAULT_PREFILTER_BITS = 30
+"""The default --prefilter-bits, for 2 ** 30 counters taking 512 MB for
+each parent"""
+
+PREFILTER_MAX_FALSE_POSITIVE_RATE = 0.01
+"""The false positive rate of a prefilter to warn above, since about as
+many of the other parent's hap-mers are lost"""
+
+
+def parse_args():
+    """Parse arguments"""
+    parser = argparse.ArgumentParser(
+        description=__doc__, formatter_class=argparse.ArgumentDefaultsHelpFormatter
+    )
+    parser.add_argument("-k", "--kmer-size", type=int, required=True)
+    parser.add_argument(
+        "-m",
+        "--min-count",
+        type=int,
+        default=2,
+        help="minimum number of times a k-mer must appear in a parent's reads "
+        "to count as present in that parent",
+    )
+    parser.add_argument(
+        "--ignore-softmasked",
+        action="store_true",
+        default=False,
+        help="skip k-mers with lowercase (soft-masked) bases instead of treating "
+        "lowercase like uppercase",
+    )
+    parser.add_argument(
+        "--compress-homopolymers",
+        action="store_true",
+        default=False,
+        help="count k-mers in homopolymer-compressed reads, for classifying "
+        "nanopore reads with classify-by-kmers --compress-homopolymers. Use with "
+        "--binary so that the compression is recorded in the k-mer files.",
+    )
+    parser.add_argument(
+        "--seed-pattern",
+        metavar="PATTERN",
+        help="make k-mers with a spaced seed like 1110110111, of the bases at the "
+        "1's of each window, so that a mismatch at a 0 doesn't change them. "
+        "Patterns that are the same backwards tolerate mismatches on both "
+        "strands. -k must be the number of 1's. Use with --binary so that the "
+        "pattern is recorded in the k-mer files, and classify with the same "
+        "classify-by-kmers --seed-pattern.",
+    )
+    parser.add_argument(
+        "--binary",
+        action="store_true",
+        default=False,
+        help="write the k-mer lists in binary format, to hapA_only_kmers.kmers and "
+        "hapB_only_kmers.kmers, for classify-by-kmers --load-kmers",
+    )
+    parser.add_argument(
+        "-t",
+        "--threads",
+        type=int,
+        default=1,
+        help="number of threads to count k-mers with. Each thread keeps its own "
+        "counts until they're merged, so this can take up to this many times the "
+        "memory.",
+    )
+    add_disk_counting_arguments(parser)
+    parser.add_argument(
+        "--prefilter-bloom",
+        action="store_true",
+        default=False,
+        help="read each parent's reads twice, first counting k-mers roughly in a "
+        "counting Bloom filter, then only counting exactly those it saw at least "
+        "--min-count times, which takes much less memory than counting the many "
+        "k-mers from sequencing errors. A few hap-mers are lost to the filters' "
+        "false positives.",
+    )
+    parser.add_argument(
+        "--prefilter-bits",
+        metavar="N",
+        type=int,
+        help="with --prefilter-bloom, give each parent's filter 2 ** N 4-bit "
+        f"counters, taking 2 ** (N - 1) bytes. Default is {DEFAULT_PREFILTER_BITS}. "
+        "For a false positive rate under 1%%, that should be at least 13 times the "
+        "number of distinct k-mers, as from kmers estimate.",
+    )
+    parser.add_argument(
+        "-o", "--outpath", default=".", help="directory to write k-mer lists to"
+    )
+    parser.add_argument(
+        "read_files",
+        nargs=2,
+        help="one comma-separated list of file paths for each parent. Files can "
+        "be in fasta or fastq format, and uncompressed or gzipped.",
+    )
+    args = parser.parse_args()
+    check_disk_counting_arguments(parser, args)
+    if args.prefilter_bloom:
+        if args.memory_limit is not None:
+            parser.error("--prefilter-bloom cannot be used with --memory-limit")
+        if any("-" in read_files.split(",") for read_files in args.read_files):
+            parser.error("--prefilter-bloom reads each file twice, so not stdin")
+        if args.prefilter_bits is None:
+            args.prefilter_bits = DEFAULT_PREFILTER_BITS
+        elif not 1 <= args.prefilter_bits <= 40:
+            parser.error(
+                f"--prefilter-bits must be from 1 to 40, not {args.prefilter_bits}"
+            )
+    elif args.prefilter_bits is not None:
+        parser.error("--prefilter-bits is only used with --prefilter-bloom")
+    args.seed = None
+    if args.seed_pattern is not None:
+        try:
+            args.seed = kmers.SpacedSeed(args.seed_pattern)
+        except ValueError as e:
+            parser.error(f"--seed-pattern: {e}")
+        if args.seed.weight != args.kmer_size:
+            parser.error(
+                f"-k must be the number of 1's in --seed-pattern, {args.seed.weight}"
+            )
+    return args
+
+
+def add_disk_counting_arguments(parser: argparse.ArgumentParser):
+    """Add the arguments for counting k-mers on disk"""
+    parser.add_argument(
+        "--memory-limit",
+        metavar="MB",
+        type=int,
+        help="count k-mers in temporary files instead of in memory, for big "
+        "genomes, holding at most this many megabytes of k-mers in memory before "
+        "writing them out. Counting them afterwards takes 16 bytes for each time "
+        "a k-mer in the biggest of the 256 files was seen, and the files take 8 "
+        "bytes for each time any k-mer was seen.",
+    )
+    parser.add_argument(
+        "--tmpdir",
+        help="with --memory-limit, the directory to make the temporary files in. "
+        "Default is $TMPDIR or the system's.",
+    )
+
+
+def check_disk_counting_arguments(
+    parser: argparse.ArgumentParser, args: argparse.Namespace
+):
+    """Check the arguments added by `add_disk_counting_arguments`"""
+    if args.memory_limit is not None:
+        if args.memory_limit < 1:
+            parser.error(
+                f"--memory-limit must be at least 1 MB, not {args.memory_limit}"
+            )
+        if args.threads != 1:
+            parser.error("--threads cannot be used with --memory-limit")
+    elif args.tmpdir is not None:
+        parser.error("--tmpdir is only used with --memory-limit")
+
+
+def _iter_reads_in_files(paths: List[str]) -> Iterator[seq.SeqRecord]:
+    return (read for path in paths for read in seq.open_fastx_read(path))
+
+
+def count_kmers_in_files(
+    paths: List[str],
+    k: int,
+    ignore_softmasked: bool = False,
+    compress_homopolymers: bool = False,
+    threads: int = 1,
+    seed: Optional[kmers.SpacedSeed] = None,
+) -> count.KmerCounts:
+    """Count the k-mers in some read files as if they were one file"""
+    return count.count_kmers(
+        _iter_reads_in_files(paths),
+        k,
+        ignore_softmasked,
+        compress_homopolymers,
+        threads,
+        seed=seed,
+    )
+
+
+def estimate_distinct_kmers_in_files(
+    paths: List[str],
+    k: int,
+    ignore_softmasked: bool = False,
+    compress_homopolymers: bool = False,
+    threads: int = 1,
+    precision: int = kmers.DEFAULT_HLL_PRECISION,
+) -> kmers.CardinalityEstimator:
+    """Estimate the distinct k-mers in some read files as if they were one
+    file"""
+    return count.estimate_distinct_kmers(
+        _iter_reads_in_files(paths),
+        k,
+        ignore_softmasked,
+        compress_homopolymers,
+        threads,
+        precision=precision,
+    )
+
+
+def count_kmers_in_files_on_disk(
+    paths: List[str],
+    k: int,
+    ignore_softmasked: bool = False,
+    compress_homopolymers: bool = False,
+    memory_limit: int = count.DEFAULT_MEMORY_LIMIT,
+    tmpdir: Optional[str] = None,
+    seed: Optional[kmers.SpacedSeed] = None,
+) -> count.DiskKmerCounts:
+    """Count the k-mers in some read files on disk as if they were one file"""
+    return count.DiskKmerCounts(
+        _iter_reads_in_files(paths),
+        k,
+        ignore_softmasked,
+        compress_homopolymers,
+        memory_limit,
+        tmpdir,
+        seed=seed,
+    )
+
+
+def hap_specific_in_memory(
+    args: argparse.Namespace,
+) -> Tuple[kmers.KmerSet, kmers.KmerSet, int]:
+    """Count each parent's k-mers in memory and find the hap-mers"""
+    parent_counts = []
+    for hap_id, read_files in zip(["A", "B"], args.read_files):
+        print(f"Counting k-mers in haplotype {hap_id}...", file=sys.stderr)
+        parent_counts.append(
+            count_kmers_in_files(
+                read_files.split(","),
+                args.kmer_size,
+                args.ignore_softmasked,
+                args.compress_homopolymers,
+                args.threads,
+                args.seed,
+            )
+        )
+        print(
+            f"Found {len(parent_counts[-1])} distinct {args.kmer_size}-mers.",
+            file=sys.stderr,
+        )
+
+    hap_a, hap_b = count.hap_specific(
+        parent_counts[0], parent_counts[1], args.kmer_size, args.min_count
+    )
+    return hap_a, hap_b, count.num_shared(parent_counts[0], parent_counts[1])
+
+
+def hap_specific_prefiltered(
+    args: argparse.Namespace,
+) -> Tuple[kmers.KmerSet, kmers.KmerSet, int]:
+    """Count each parent's k-mers in memory after a counting Bloom filter
+    prefilter and find the hap-mers"""
+    parent_counts = []
+    prefilters = []
+    for hap_id, read_files in zip(["A", "B"], args.read_files):
+        paths = read_files.split(",")
+        print(f"Prefiltering k-mers in haplotype {hap_id}...", file=sys.stderr)
+        prefilter = count.count_kmers_in_counting_bloom(
+            _iter_reads_in_files(paths),
+            args.kmer_size,
+            1 << args.prefilter_bits,
+            args.ignore_softmasked,
+            args.compress_homopolymers,
+            args.seed,
+        )
+        false_positive_percent = 100 * prefilter.false_positive_rate
+        print(
+            f"Prefilter saturation: {100 * prefilter.saturation:.1f}% of counters "
+            f"used, for a false positive rate of about {false_positive_percent:.2g}%.",
+            file=sys.stderr,
+        )
+        if false_positive_percent > 100 * PREFILTER_MAX_FALSE_POSITIVE_RATE:
+            print(
+                f"Warning: the prefilter of haplotype {hap_id} is too full, so about "
+                f"{false_positive_percent:.2g}% of the other haplotype's hap-mers "
+                "will be lost. Raise --prefilter-bits.",
+                file=sys.stderr,
+            )
+        prefilters.append(prefilter)
+
+        print(f"Counting k-mers in haplotype {hap_id}...", file=sys.stderr)
+        parent_counts.append(
+            count.count_kmers(
+                _iter_reads_in_files(paths),
+                args.kmer_size,
+                args.ignore_softmasked,
+                args.compress_homopolymers,
+                args.threads,
+                seed=args.seed,
+                prefilter=prefilter,
+                min_count=args.min_count,
+            )
+        )
+        print(
+            f"Found {len(parent_counts[-1])} distinct {args.kmer_size}-mers that "
+            "passed the prefilter.",
+            file=sys.stderr,
+        )
+
+    return count.hap_specific_prefiltered(
+        parent_counts[0],
+        parent_counts[1],
+        prefilters[0],
+        prefilters[1],
+        args.kmer_size,
+        args.min_count,
+    )
+
+
+def hap_specific_on_disk(
+    args: argparse.Namespace,
+) -> Tuple[kmers.KmerSet, kmers.KmerSet, int]:
+    """Count each parent's k-mers on disk and find the hap-mers"""
+    parent_counts: List[count.DiskKmerCounts] = []
+    try:
+        for hap_id, read_files in zip(["A", "B"], args.read_files):
+            print(f"Counting k-mers in haplotype {hap_id} on disk...", file=sys.stderr)
+            parent_counts.append(
+                count_kmers_in_files_on_disk(
+                    read_files.split(","),
+                    args.kmer_size,
+                    args.ignore_softmasked,
+                    args.compress_homopolymers,
+                    args.memory_limit << 20,
+                    args.tmpdir,
+                    args.seed,
+                )
+            )
+        print("Finding the k-mers unique to each haplotype...", file=sys.stderr)
+        return count.hap_specific_sorted(
+            parent_counts[0], parent_counts[1], args.kmer_size, args.min_count
+        )
+    finally:
+        for disk_counts in parent_counts:
+            disk_counts.close()
+
+
+def main():
+    """Main method of program"""
+    args = parse_args()
+    if args.threads < 1:
+        sys.exit(f"Error: --threads must be at least 1, not {args.threads}")
+
+    if args.prefilter_bloom:
+        hap_a, hap_b, num_shared = hap_specific_prefiltered(args)
+    elif args.memory_limit is None:
+        hap_a, hap_b, num_shared = hap_specific_in_memory(args)
+    else:
+        hap_a, hap_b, num_shared = hap_specific_on_disk(args)
+
+    for kmer_set, hap in [(hap_a, "hapA"), (hap_b, "hapB")]:
+        if args.binary:
+            kmer_set.homopolymer_compressed = args.compress_homopolymers
+            kmer_set.seed = args.seed
+            with open(
+                os.path.join(args.outpath, f"{hap}_only_kmers.kmers"), "wb"
+            ) as kmer_file:
+                kmer_set.write_binary(kmer_file)
+        else:
+            kmer_set.to_file(os.path.join(args.outpath, f"{hap}_only_kmers.txt"))
+
+    print(
+        f"# of k-mers found in both haplotypes (removed): {num_shared}",
+        file=sys.stderr,
+    )
+    print(f"# of unique k-mers in haplotype A: {len(hap_a)}", file=sys.stderr)
+    print(f"# of unique k-mers in haplotype B: {len(hap_b)}", file=sys.stderr)
+
+
+if __name__ == "__main__":
+    main()
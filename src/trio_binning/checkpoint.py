@@ -0,0 +1,260 @@
+"""Checkpoints of a classification run, to resume it after it's stopped.
+
+A run with a checkpoint file saves, every so often, how far it has got
+through its reads files, the length of each of its output files, and
+its running `Summary`. Resuming it cuts each output file back to the
+length it had at the checkpoint, so that no read written after the
+checkpoint is written twice, and skips over the reads it had got through
+before carrying on.
+
+The reads are skipped by counting records rather than by seeking to a
+byte offset, so that gzipped reads, which can't be seeked in, can be
+resumed as well as plain ones. Skipping still has to read and parse the
+records, but doesn't classify them, which is much faster.
+"""
+
+import os
+import pickle
+import re
+import time
+from dataclasses import dataclass
+from itertools import islice
+from typing import Callable, Dict, Iterable, Iterator, List, Optional, TypeVar
+
+from trio_binning import seq
+from trio_binning.classify import Summary
+
+VERSION = 2
+"""The version of the checkpoint format, which is checked on loading"""
+
+DURATION_UNITS = {"": 1, "s": 1, "m": 60, "h": 60 * 60}
+"""The number of seconds in each unit a duration can be given in"""
+
+
+class CheckpointError(ValueError):
+    """A checkpoint that can't be saved, loaded or resumed from"""
+
+
+def parse_duration(duration: str) -> float:
+    """Parse a duration like "10m", "30s" or "1.5h", in seconds
+
+    A number with no unit is in seconds.
+
+    >>> parse_duration("10m")
+    600.0
+
+    Raises:
+        ValueError: if it isn't a positive number with one of the units
+    """
+    match = re.fullmatch(r"(\d+(?:\.\d*)?|\.\d+)([smh]?)", duration.strip())
+    if match is None or float(match.group(1)) <= 0:
+        raise ValueError(
+            f"{duration!r} isn't a positive duration, like 30s, 10m or 2h"
+        )
+    return float(match.group(1)) * DURATION_UNITS[match.group(2)]
+
+
+@dataclass
+class Checkpoint:
+    """How far a run had got at a checkpoint"""
+
+    reads: List[str]
+    """The reads files of the run, to check that a resumed run has the
+    same ones"""
+    file_index: int
+    """The index in `reads` of the file the run was in. Any files before
+    it were done."""
+    records: int
+    """The number of records (or read pairs) done in that file"""
+    outputs: Dict[str, int]
+    """The length in bytes of each output file"""
+    summary: Summary
+    """The summary of the reads done"""
+
+    def save(self, path: str):
+        """Save the checkpoint to a file, replacing any checkpoint already in
+        it only once the new one is written in full"""
+        temp_path = path + ".tmp"
+        with open(temp_path, "wb") as fp:
+            pickle.dump((VERSION, self), fp)
+            fp.flush()
+            os.fsync(fp.fileno())
+        os.replace(temp_path, path)
+
+    @staticmethod
+    def load(path: str) -> "Checkpoint":
+        """Load a checkpoint saved by `save`
+
+        Raises:
+            CheckpointError: if the file isn't a checkpoint of this
+                version
+        """
+        with open(path, "rb") as fp:
+            try:
+                version, checkpoint = pickle.load(fp)
+            except (pickle.UnpicklingError, EOFError, ValueError, TypeError):
+                raise CheckpointError(f"{path} isn't a checkpoint") from None
+        if version != VERSION or not isinstance(checkpoint, Checkpoint):
+            raise CheckpointError(f"{path} is a checkpoint of another version")
+        return checkpoint
+
+    def truncate_outputs(self):
+        """Cut each output file back to its length at the checkpoint
+
+        Raises:
+            CheckpointError: if an output file is missing or shorter than
+                it was at the checkpoint
+        """
+        for output_path, length in self.outputs.items():
+            try:
+                current_length = os.path.getsize(output_path)
+            except OSError:
+                raise CheckpointError(
+                    f"{output_path} was written before the checkpoint but is gone"
+                ) from None
+            if current_length < length:
+                raise CheckpointError(
+                    f"{output_path} is shorter than it was at the checkpoint"
+                )
+            os.truncate(output_path, length)
+
+    def records_done(self, file_index: int) -> Optional[int]:
+        """The number of records done in a reads file, or None if it was
+        done in full"""
+        if file_index < self.file_index:
+            return None
+        if file_index == self.file_index:
+            return self.records
+        return 0
+
+
+T = TypeVar("T")
+
+
+class CountedIterator(Iterator[T]):
+    """An iterator that counts the items taken from it"""
+
+    def __init__(self, items: Iterable[T], count: int = 0):
+        self._items = iter(items)
+        self.count = count
+        """The number of items taken, plus the count it started at"""
+
+    def __next__(self) -> T:
+        item = next(self._items)
+        self.count += 1
+        return item
+
+
+def skip_done(
+    items: Iterator[T],
+    num_done: Optional[int],
+    name: Callable[[T], str],
+    seen: Optional[seq.IdSet],
+    summary: Summary,
+) -> Iterator[T]:
+    """Skip the items done before a checkpoint
+
+    The names of the items skipped are added to `seen`, if it's given,
+    so that duplicates of them are still found after resuming. Malformed
+    records among them aren't counted in `summary` again, since they're
+    already in the summary from the checkpoint.
+
+    Args:
+        items: the reads or pairs of a reads file
+        num_done: the number of them done, or None for all of them
+        name: gets the name of each item
+        seen: the names seen so far with --check-duplicates, or None
+        summary: the summary loaded from the checkpoint
+
+    Returns:
+        the items after those done
+    """
+    if num_done is None and seen is None:
+        return iter([])
+    malformed = summary.malformed
+    for item in islice(items, num_done):
+        if seen is not None:
+            seen.add(name(item))
+    summary.malformed = malformed
+    return items
+
+
+class Checkpointer:
+    """Saves a checkpoint now and then during a run
+
+    Each output file is added with `add_output` when it's opened. The
+    reads are split into segments with `segments`, and a checkpoint is
+    saved with `save` once every read in a segment has been classified
+    and written, which is when no read taken from the reads files is
+    still being classified by a thread, so the outputs are a whole
+    number of reads.
+    """
+
+    def __init__(
+        self,
+        path: str,
+        interval: float,
+        reads: List[str],
+        summary: Summary,
+        clock: Callable[[], float] = time.monotonic,
+    ):
+        """
+        Args:
+            path: the file to save checkpoints to
+            interval: the least number of seconds between checkpoints
+            reads: the reads files of the run
+            summary: the summary of the run, which is saved with each
+                checkpoint
+            clock: what to get the time from, in seconds
+        """
+        self.path = path
+        self.interval = interval
+        self.reads = reads
+        self.summary = summary
+        self._clock = clock
+        self._last_time = clock()
+        self._outputs: Dict[str, seq.TextOrGzip] = {}
+
+    def add_output(self, outfile: seq.TextOrGzip):
+        """Add an output file whose length is saved with each checkpoint"""
+        self._outputs[outfile.name] = outfile
+
+    def due(self) -> bool:
+        """Whether it's time for another checkpoint"""
+        return self._clock() - self._last_time >= self.interval
+
+    def segments(self, items: Iterable[T]) -> Iterator[Iterator[T]]:
+        """Split items into segments that each end once a checkpoint is due
+
+        Each segment has at least one item, except when there are none,
+        so the run gets somewhere even if saving a checkpoint takes longer
+        than the interval. Each segment must be used up before the next
+        one is taken.
+        """
+        items = iter(items)
+        done = False
+
+        def segment() -> Iterator[T]:
+            nonlocal done
+            while True:
+                try:
+                    item = next(items)
+                except StopIteration:
+                    done = True
+                    return
+                yield item
+                if self.due():
+                    return
+
+        while not done:
+            yield segment()
+
+    def save(self, file_index: int, records: int):
+        """Save a checkpoint, after writing the output files out to disk"""
+        outputs = {
+            name: seq.sync_outfile(outfile) for name, outfile in self._outputs.items()
+        }
+        Checkpoint(self.reads, file_index, records, outputs, self.summary).save(
+            self.path
+        )
+        self._last_time = self._clock()
@@ -0,0 +1,1895 @@
+"""Classify sequences into haplotype bins based on k-mers.
+
+This contains the binning logic of trio binning: counting how many
+k-mers in a sequence are specific to each parental haplotype, and
+deciding which haplotype the sequence belongs to based on those counts.
+"""
+import logging
+import sys
+import time
+from collections import deque
+from concurrent.futures import FIRST_COMPLETED, Future, ThreadPoolExecutor, wait
+from dataclasses import MISSING, asdict, dataclass, fields, replace
+from enum import Enum
+from itertools import islice
+from typing import (
+    Any,
+    Callable,
+    Deque,
+    Dict,
+    Iterable,
+    Iterator,
+    List,
+    NamedTuple,
+    Optional,
+    Protocol,
+    Sequence,
+    TextIO,
+    Tuple,
+    TypeVar,
+    get_type_hints,
+)
+
+from trio_binning import kmers
+from trio_binning.adapters import AdapterMasker
+from trio_binning.seq import Read, SeqRecord, check_qualities, open_records
+
+logger = logging.getLogger(__name__)
+
+EARLY_EXIT_CHUNK = 1000
+"""With early exit, the number of k-mer start positions counted between
+checks for whether the bin is certain"""
+
+
+class Bin(Enum):
+    """A haplotype bin that a sequence can be assigned to"""
+
+    HAP_A = "A"
+    HAP_B = "B"
+    UNKNOWN = "U"
+
+
+class ScoreMode(Enum):
+    """How the k-mers found from a haplotype are turned into its score"""
+
+    COUNT = "count"
+    """Each k-mer found adds 1"""
+    WEIGHTED = "weighted"
+    """Each k-mer found adds its count in the parent, up to a cap, so that
+    rare k-mers, which are often sequencing errors, count for less. This
+    needs `kmers.KmerCountMap`s."""
+
+    def __str__(self):
+        return self.value
+
+
+@dataclass
+class Classification:
+    """The result of classifying a sequence"""
+
+    hap_a_count: int
+    """The number of k-mers in the sequence found in haplotype A"""
+    hap_b_count: int
+    """The number of k-mers in the sequence found in haplotype B"""
+    bin: Bin
+    """The haplotype bin the sequence was assigned to"""
+    hap_a_score: float
+    """`hap_a_count`, or with weighted scoring the weight of the hits,
+    multiplied by the haplotype A scaling factor"""
+    hap_b_score: float
+    """`hap_b_count`, or with weighted scoring the weight of the hits,
+    multiplied by the haplotype B scaling factor"""
+    num_kmers: int
+    """The number of k-mers in the sequence that were looked up, which
+    with minimizers or syncmers is only those"""
+    num_skipped: int
+    """The number of k-mers in the sequence that were skipped because
+    they contain a character other than [ACGT] (e.g., an N)"""
+    rescued: bool = False
+    """Whether the sequence was unknown and then assigned to its bin by
+    `rescue` with relaxed thresholds"""
+    early_exit: bool = False
+    """Whether counting stopped partway through the sequence because its
+    bin was already certain, with `ClassifyOptions.early_exit_margin`, so
+    that the counts are only those of the part before that"""
+    masked_bases: int = 0
+    """The number of bases of the sequence masked with N's before its
+    k-mers were counted, e.g., as adapter sequence by
+    `adapters.AdapterMasker`"""
+
+    @property
+    def hap_a_fraction(self) -> float:
+        """`hap_a_count` divided by `num_kmers`, or 0 if there are none"""
+        return self.hap_a_count / self.num_kmers if self.num_kmers else 0.0
+
+    @property
+    def hap_b_fraction(self) -> float:
+        """`hap_b_count` divided by `num_kmers`, or 0 if there are none"""
+        return self.hap_b_count / self.num_kmers if self.num_kmers else 0.0
+
+    @property
+    def hap_a_per_kb(self) -> float:
+        """The number of hits in haplotype A per thousand k-mers looked up,
+        which, unlike `hap_a_count`, doesn't grow with the sequence's
+        length"""
+        return 1000 * self.hap_a_fraction
+
+    @property
+    def hap_b_per_kb(self) -> float:
+        """The number of hits in haplotype B per thousand k-mers looked up"""
+        return 1000 * self.hap_b_fraction
+
+    @property
+    def skipped_fraction(self) -> float:
+        """The fraction of all k-mers in the sequence that were skipped,
+        or 0 if the sequence is shorter than k. With minimizers or
+        syncmers, this is skipped k-mers over them plus the minimizers or
+        syncmers looked up."""
+        total = self.num_kmers + self.num_skipped
+        return self.num_skipped / total if total else 0.0
+
+    def to_dict(self) -> Dict[str, Any]:
+        """Convert the classification to a dict that can be serialized as
+        JSON, with the bin as its one-letter name"""
+        classification = asdict(self)
+        classification["bin"] = self.bin.value
+        return classification
+
+    @staticmethod
+    def from_dict(classification: Dict[str, Any]) -> "Classification":
+        """Make a classification from a dict made by `to_dict`
+
+        Raises:
+            ValueError: if the dict has fields missing or unknown, a value
+                of the wrong type, or a bin that isn't A, B or U
+        """
+        return _dataclass_from_dict(Classification, classification)
+
+
+@dataclass(frozen=True)
+class ClassifyOptions:
+    """Thresholds a sequence must pass to be assigned to a haplotype
+
+    A sequence is assigned to the haplotype with the higher score only
+    if it passes all of these thresholds; otherwise it is unknown. The
+    defaults let every sequence through, so that only ties are unknown.
+    `min_hits` is always a number of k-mers, while `min_margin` is in
+    units of score, so with weighted scoring it is a difference in the
+    weights of the hits, and `min_ratio` is a ratio of scores either way.
+    `min_hits_per_kb` is like `min_hits` but relative to the number of
+    k-mers looked up, so that it means the same for short and long
+    sequences. K-mers skipped for N's or low-quality bases aren't looked
+    up, so raising `min_base_quality` makes it easier to pass.
+    """
+
+    min_hits: int = 0
+    """The minimum total number of k-mers found from either haplotype,
+    before scaling or weighing"""
+    min_margin: float = 0.0
+    """The minimum difference between the haplotype A and B scores"""
+    min_ratio: float = 0.0
+    """The minimum fraction of the sum of the haplotype A and B scores
+    that the higher score must make up, from 0 to 1"""
+    ignore_softmasked: bool = False
+    """Whether to skip k-mers with lowercase (soft-masked) bases, like
+    k-mers with N's, instead of treating them like uppercase"""
+    min_base_quality: int = 0
+    """Skip k-mers with a base of lower quality than this, like k-mers
+    with N's, when the sequence's quality string is given"""
+    compress_homopolymers: bool = False
+    """Whether to compress homopolymers in the sequence before counting
+    k-mers, for k-mer sets made from compressed sequence"""
+    score: ScoreMode = ScoreMode.COUNT
+    """How hits are turned into scores"""
+    weight_cap: int = 10
+    """With weighted scoring, the most that any one k-mer found adds to a
+    score"""
+    min_hits_per_kb: float = 0.0
+    """The minimum total number of k-mers found from either haplotype per
+    thousand k-mers looked up"""
+    fuzzy: bool = False
+    """Whether to count k-mers that aren't in either haplotype but are one
+    substitution away from a k-mer in only one as hits, e.g., for nanopore reads
+    with many single-base errors. This is much slower."""
+    fuzzy_weight: float = 0.5
+    """With `fuzzy`, how much a near hit adds to a score, compared to 1
+    for an exact hit"""
+    minimizer_window: Optional[int] = None
+    """Only look up the sequence's minimizers, one for each window of this
+    many k-mers, rather than all of its k-mers, which is faster. The hits
+    and the number of k-mers looked up are then numbers of minimizers, so
+    `min_hits` should be lowered to match, while `min_hits_per_kb` means
+    about the same as before."""
+    kmer_step: int = 1
+    """Only look up every this-many k-mers, counting from the start of the
+    sequence, which is faster. As with `minimizer_window`, the number of
+    k-mers looked up is then the number at those positions."""
+    early_exit_margin: Optional[int] = None
+    """Stop counting a sequence's k-mers once the sequence would already be
+    assigned to a haplotype, its scaled score is this much more than the
+    other's, and the other has at most `early_exit_max_other` hits, which
+    is faster for long reads. Unknown is never decided early."""
+    early_exit_max_other: int = 10
+    """With `early_exit_margin`, the most hits the other haplotype can have
+    for counting to stop early"""
+    seed_pattern: Optional[str] = None
+    """Make the sequence's k-mers with this spaced seed, like
+    ``1110110111`` (see `kmers.SpacedSeed`), for hap-mers made with the
+    same one, e.g., by build-hapmers --seed-pattern. The k-mer sets' k is
+    then the seed's weight."""
+    syncmer_size: Optional[int] = None
+    """Only look up the sequence's closed syncmers with s-mers of this
+    size (see `kmers.syncmers`), about 2 / (k - s + 1) of its k-mers,
+    rather than all of them. Like `minimizer_window`, the hits and the
+    number of k-mers looked up are then numbers of syncmers. Must be
+    less than the k-mer sets' k."""
+
+    def __post_init__(self):
+        if self.min_hits < 0:
+            raise ValueError(f"min_hits must not be negative, not {self.min_hits}")
+        if self.min_margin < 0:
+            raise ValueError(
+                f"min_margin must not be negative, not {self.min_margin}"
+            )
+        if not 0 <= self.min_ratio <= 1:
+            raise ValueError(
+                f"min_ratio must be between 0 and 1, not {self.min_ratio}"
+            )
+        if not 0 <= self.min_base_quality <= 93:
+            raise ValueError(
+                "min_base_quality must be between 0 and 93, not "
+                f"{self.min_base_quality}"
+            )
+        if self.weight_cap < 1:
+            raise ValueError(f"weight_cap must be at least 1, not {self.weight_cap}")
+        if self.min_hits_per_kb < 0:
+            raise ValueError(
+                f"min_hits_per_kb must not be negative, not {self.min_hits_per_kb}"
+            )
+        if not 0 < self.fuzzy_weight <= 1:
+            raise ValueError(
+                f"fuzzy_weight must be more than 0 and at most 1, not "
+                f"{self.fuzzy_weight}"
+            )
+        if self.fuzzy and self.score == ScoreMode.WEIGHTED:
+            raise ValueError("fuzzy matching can't be combined with weighted scoring")
+        if self.minimizer_window is not None:
+            if not 1 <= self.minimizer_window <= kmers.MAX_MINIMIZER_WINDOW:
+                raise ValueError(
+                    f"minimizer_window must be from 1 to "
+                    f"{kmers.MAX_MINIMIZER_WINDOW}, not {self.minimizer_window}"
+                )
+            if self.fuzzy or self.score == ScoreMode.WEIGHTED:
+                raise ValueError(
+                    "minimizers can't be combined with fuzzy matching or weighted "
+                    "scoring"
+                )
+        if self.kmer_step < 1:
+            raise ValueError(f"kmer_step must be at least 1, not {self.kmer_step}")
+        if self.kmer_step != 1 and self.minimizer_window is not None:
+            raise ValueError("a k-mer step can't be combined with minimizers")
+        if self.early_exit_margin is not None:
+            if self.early_exit_margin < 1:
+                raise ValueError(
+                    "early_exit_margin must be at least 1, not "
+                    f"{self.early_exit_margin}"
+                )
+            if self.minimizer_window is not None:
+                raise ValueError("early exit can't be combined with minimizers")
+        if self.early_exit_max_other < 0:
+            raise ValueError(
+                "early_exit_max_other must not be negative, not "
+                f"{self.early_exit_max_other}"
+            )
+        if self.seed_pattern is not None:
+            # raises a ValueError if the pattern is invalid
+            kmers.SpacedSeed(self.seed_pattern)
+            if (
+                self.fuzzy
+                or self.minimizer_window is not None
+                or self.score == ScoreMode.WEIGHTED
+            ):
+                raise ValueError(
+                    "a seed pattern can't be combined with fuzzy matching, "
+                    "minimizers or weighted scoring"
+                )
+        if self.syncmer_size is not None:
+            if not 1 <= self.syncmer_size < kmers.MAX_K:
+                raise ValueError(
+                    f"syncmer_size must be from 1 to {kmers.MAX_K - 1}, not "
+                    f"{self.syncmer_size}"
+                )
+            if (
+                self.fuzzy
+                or self.minimizer_window is not None
+                or self.score == ScoreMode.WEIGHTED
+                or self.kmer_step != 1
+                or self.early_exit_margin is not None
+                or self.seed_pattern is not None
+            ):
+                raise ValueError(
+                    "syncmers can't be combined with fuzzy matching, minimizers, "
+                    "weighted scoring, a k-mer step, early exit or a seed pattern"
+                )
+
+    @property
+    def seed(self) -> Optional[kmers.SpacedSeed]:
+        """The spaced seed of `seed_pattern`, if there is one"""
+        if self.seed_pattern is None:
+            return None
+        return kmers.SpacedSeed(self.seed_pattern)
+
+    def span(self, k: int) -> int:
+        """The number of bases each k-mer of a sequence is made from: `k`,
+        or the span of the seed pattern"""
+        if self.seed_pattern is None:
+            return k
+        return len(self.seed_pattern)
+
+    def to_dict(self) -> Dict[str, Any]:
+        """Convert the options to a dict that can be serialized as JSON or
+        TOML, with the score mode as its name, e.g., "weighted"
+
+        Options that are None, like `minimizer_window` when it's off, are
+        left out, since TOML has no None.
+        """
+        options = asdict(self)
+        options["score"] = self.score.value
+        return {name: value for name, value in options.items() if value is not None}
+
+    @staticmethod
+    def from_dict(options: Dict[str, Any]) -> "ClassifyOptions":
+        """Make options from a dict like one made by `to_dict`, e.g., read
+        from a JSON or TOML file
+
+        Options left out have their defaults.
+
+        Raises:
+            ValueError: if an option is unknown, has a value of the wrong
+                type, or fails the checks of the options
+        """
+        return _dataclass_from_dict(ClassifyOptions, options)
+
+
+D = TypeVar("D")
+
+
+_OPTIONAL_TYPES = {Optional[int]: int, Optional[str]: str}
+
+
+def _dataclass_from_dict(cls: Callable[..., D], values: Dict[str, Any]) -> D:
+    """Make a dataclass of bools, numbers, enums, and optional numbers and
+    strings from a dict, checking the type of each value
+
+    Ints are taken for floats, as JSON and TOML don't always tell them
+    apart, but bools aren't taken for numbers. Enums are given by value.
+    """
+    name = cls.__name__.lower()  # type: ignore[attr-defined]
+    types = get_type_hints(cls)
+    unknown = set(values) - set(types)
+    if unknown:
+        raise ValueError(f"unknown {name} fields: {', '.join(sorted(unknown))}")
+    missing = [
+        field.name
+        for field in fields(cls)  # type: ignore[arg-type]
+        if field.name not in values
+        and field.default is MISSING
+        and field.default_factory is MISSING
+    ]
+    if missing:
+        raise ValueError(f"missing {name} fields: {', '.join(missing)}")
+
+    converted = {}
+    for field_name, value in values.items():
+        field_type = types[field_name]
+        if field_type in _OPTIONAL_TYPES:
+            if value is None:
+                converted[field_name] = None
+                continue
+            field_type = _OPTIONAL_TYPES[field_type]
+        if isinstance(field_type, type) and issubclass(field_type, Enum):
+            try:
+                converted[field_name] = field_type(value)
+            except ValueError:
+                choices = ", ".join(str(member.value) for member in field_type)
+                raise ValueError(
+                    f"{field_name} must be one of {choices}, not {value!r}"
+                ) from None
+            continue
+        if field_type is float and type(value) is int:
+            value = float(value)
+        if isinstance(value, bool) != (field_type is bool) or not isinstance(
+            value, field_type
+        ):
+            raise ValueError(
+                f"{field_name} must be of type {field_type.__name__}, not {value!r}"
+            )
+        converted[field_name] = value
+    return cls(**converted)
+
+
+def profile(
+    seq: str,
+    hap_a: kmers.KmerLookup,
+    hap_b: kmers.KmerLookup,
+    ignore_softmasked: bool = False,
+) -> List[Tuple[int, Bin]]:
+    """Find where along a sequence its hap-mers are, e.g., to see where
+    the haplotype switches in a chimeric read
+
+    A k-mer in both haplotypes is only counted for haplotype A, as when
+    classifying.
+
+    Args:
+        seq: the sequence to profile
+        hap_a: k-mers specific to haplotype A
+        hap_b: k-mers specific to haplotype B
+        ignore_softmasked: whether to skip k-mers with lowercase bases
+
+    Returns:
+        the 0-based start of each k-mer found in either haplotype, in
+        order, and the haplotype it's from, `Bin.HAP_A` or `Bin.HAP_B`.
+        The k-mer covers the half-open interval [start, start + k).
+
+    Raises:
+        ValueError: if the lookups have different k
+    """
+    kmers.check_same_k(hap_a, hap_b)
+    hits = []
+    for start, kmer_int in kmers.iter_canonical_kmers_with_positions(
+        seq, hap_a.k, ignore_softmasked
+    ):
+        if kmer_int in hap_a:
+            hits.append((start, Bin.HAP_A))
+        elif kmer_int in hap_b:
+            hits.append((start, Bin.HAP_B))
+    return hits
+
+
+def calculate_scaling_factors(
+    hap_a: kmers.KmerLookup, hap_b: kmers.KmerLookup
+) -> Tuple[float, float]:
+    """Calculate the scaling factors for k-mer scores
+
+    These scale counts up for the haplotype with fewer k-mers so that
+    the haplotype with more k-mers does not get an advantage just by
+    having more k-mers to find.
+
+    Args:
+        hap_a: set of k-mers in haplotype A
+        hap_b: set of k-mers in haplotype B
+
+    Returns:
+        scaling_factor_a: scaling factor by which haplotype A counts
+            should be multiplied
+        scaling_factor_b: scaling factor by which haplotype B counts
+            should be multiplied
+
+    Raises:
+        ValueError: if either haplotype has no k-mers, since its counts
+            can't be scaled up to match
+    """
+    for name, hap_kmers in [("A", hap_a), ("B", hap_b)]:
+        if not len(hap_kmers):
+            raise ValueError(f"haplotype {name} has no k-mers")
+    max_num_kmers = max(len(hap_a), len(hap_b))
+    scaling_factor_a = 1.0 * max_num_kmers / len(hap_a)
+    scaling_factor_b = 1.0 * max_num_kmers / len(hap_b)
+    return scaling_factor_a, scaling_factor_b
+
+
+def _classify_counts(
+    hap_a_count: int,
+    hap_b_count: int,
+    num_kmers: int,
+    num_skipped: int,
+    scaling_factors: Tuple[float, float],
+    options: Optional[ClassifyOptions],
+    weights: Optional[Tuple[float, float]] = None,
+) -> Classification:
+    """Assign a bin based on the number of k-mers from each haplotype, or
+    their weights if given, which are scored instead of the counts"""
+    if options is None:
+        options = ClassifyOptions()
+    if weights is None:
+        weights = (hap_a_count, hap_b_count)
+
+    hap_a_score = weights[0] * scaling_factors[0]
+    hap_b_score = weights[1] * scaling_factors[1]
+    return Classification(
+        hap_a_count,
+        hap_b_count,
+        _assign_bin(
+            hap_a_count, hap_b_count, hap_a_score, hap_b_score, num_kmers, options
+        ),
+        hap_a_score,
+        hap_b_score,
+        num_kmers,
+        num_skipped,
+    )
+
+
+def _assign_bin(
+    hap_a_count: int,
+    hap_b_count: int,
+    hap_a_score: float,
+    hap_b_score: float,
+    num_kmers: int,
+    options: ClassifyOptions,
+) -> Bin:
+    total_score = hap_a_score + hap_b_score
+    best_score = max(hap_a_score, hap_b_score)
+    if (
+        hap_a_score == hap_b_score
+        or hap_a_count + hap_b_count < options.min_hits
+        or 1000 * (hap_a_count + hap_b_count) < options.min_hits_per_kb * num_kmers
+        or abs(hap_a_score - hap_b_score) < options.min_margin
+        or best_score < options.min_ratio * total_score
+    ):
+        return Bin.UNKNOWN
+    elif hap_a_score > hap_b_score:
+        return Bin.HAP_A
+    else:
+        return Bin.HAP_B
+
+
+def rescue(classification: Classification, options: ClassifyOptions) -> Classification:
+    """Try to assign an unknown sequence to a haplotype with relaxed
+    thresholds, e.g., `ClassifyOptions(min_hits=1, min_margin=1)`
+
+    Only the thresholds of `options` are used, since the hits are the
+    ones already found, so this is cheap enough to do for every unknown
+    sequence as it comes out of the main pass rather than rereading
+    them afterwards.
+
+    Returns:
+        the classification with the new bin and `rescued` set if it was
+        rescued, or else as it was
+    """
+    if classification.bin != Bin.UNKNOWN:
+        return classification
+    read_bin = _assign_bin(
+        classification.hap_a_count,
+        classification.hap_b_count,
+        classification.hap_a_score,
+        classification.hap_b_score,
+        classification.num_kmers,
+        options,
+    )
+    if read_bin == Bin.UNKNOWN:
+        return classification
+    return replace(classification, bin=read_bin, rescued=True)
+
+
+def classify_seq(
+    seq: str,
+    hap_a: kmers.KmerLookup,
+    hap_b: kmers.KmerLookup,
+    scaling_factors: Tuple[float, float] = (1.0, 1.0),
+    options: Optional[ClassifyOptions] = None,
+    quals: Optional[str] = None,
+) -> Classification:
+    """Classify a sequence into a haplotype bin.
+
+    A sequence with a higher haplotype A score than haplotype B score
+    goes into the haplotype A bin, and vice versa, where the score is
+    the number of k-mers in the sequence specific to a haplotype
+    multiplied by its scaling factor. Sequences with the same score for
+    each haplotype, including those with no k-mers from either (e.g.,
+    because they are shorter than k), are unknown, as are sequences
+    that have fewer than `options.min_hits` k-mers from the two
+    haplotypes put together, or fewer than `options.min_hits_per_kb` per
+    thousand k-mers looked up, a difference in scores of less than
+    `options.min_margin`, or a higher score that is less than
+    `options.min_ratio` of the sum of the scores. Sequences exactly at
+    a threshold pass it. With `options.compress_homopolymers`, the
+    k-mers are counted in the homopolymer-compressed sequence. With
+    weighted `options.score`, each k-mer found adds its count in the
+    parent, up to `options.weight_cap`, to the score rather than 1.
+    With `options.fuzzy`, k-mers one substitution away from a k-mer in
+    only one haplotype count as hits too, adding `options.fuzzy_weight` to its
+    score rather than 1.
+    With `options.minimizer_window`, only the sequence's minimizers are
+    looked up and counted, and with `options.syncmer_size` only its
+    syncmers.
+    With `options.kmer_step`, only the k-mers starting every that many
+    bases are.
+    With `options.early_exit_margin`, counting stops once the bin is
+    certain, and the counts are those of the sequence up to there.
+    With `options.seed_pattern`, the k-mers are made with that spaced
+    seed.
+
+    Args:
+        seq: the sequence to classify
+        hap_a: k-mers specific to haplotype A
+        hap_b: k-mers specific to haplotype B
+        scaling_factors: factors to multiply the haplotype A and B
+            counts by, e.g., from `calculate_scaling_factors`. By
+            default, the counts are compared directly.
+        options: thresholds for assigning the sequence to a haplotype.
+            By default, only ties are unknown.
+        quals: the sequence's quality string, if it has one. k-mers
+            with bases below `options.min_base_quality` are skipped.
+
+    Returns:
+        the k-mer counts for each haplotype and the bin assigned
+    """
+    if options is None:
+        options = ClassifyOptions()
+    if not options.min_base_quality:
+        quals = None
+
+    if options.compress_homopolymers:
+        if quals is None:
+            seq = kmers.compress_homopolymers(seq)
+        else:
+            seq, quals = kmers.compress_homopolymers_with_quality(seq, quals)
+
+    if options.early_exit_margin is None:
+        *counts, weights = _count_kmers(seq, hap_a, hap_b, options, quals)
+        return _classify_counts(*counts, scaling_factors, options, weights)
+
+    # like classify_by_window, each chunk's slice includes the span - 1 bases
+    # after it, and the chunks start at multiples of the k-mer step so that
+    # their k-mers are the ones the whole sequence would have
+    chunk_size = options.kmer_step * -(-EARLY_EXIT_CHUNK // options.kmer_step)
+    span = options.span(hap_a.k)
+    num_positions = max(len(seq) - span + 1, 0)
+    totals = [0, 0, 0, 0]
+    total_weights = (0.0, 0.0)
+    for start in range(0, max(num_positions, 1), chunk_size):
+        end = start + chunk_size + span - 1
+        chunk_quals = None if quals is None else quals[start:end]
+        *counts, weights = _count_kmers(
+            seq[start:end], hap_a, hap_b, options, chunk_quals
+        )
+        totals = [total + count for total, count in zip(totals, counts)]
+        total_weights = (total_weights[0] + weights[0], total_weights[1] + weights[1])
+        classification = _classify_counts(
+            *totals, scaling_factors, options, total_weights
+        )
+        if start + chunk_size < num_positions and _is_certain(classification, options):
+            return replace(classification, early_exit=True)
+    return classification
+
+
+def _is_certain(classification: Classification, options: ClassifyOptions) -> bool:
+    """Whether a partial classification is certain enough to stop counting
+    with `options.early_exit_margin`
+
+    The margin is between the scaled scores, which are what decide the
+    bin, so that a haplotype scaled up for having fewer k-mers can't be
+    left behind by one that only leads on raw hits.
+    """
+    if classification.bin == Bin.UNKNOWN:
+        return False
+    if classification.bin == Bin.HAP_A:
+        leading, other = classification.hap_a_score, classification.hap_b_score
+        other_count = classification.hap_b_count
+    else:
+        leading, other = classification.hap_b_score, classification.hap_a_score
+        other_count = classification.hap_a_count
+    return (
+        leading - other >= options.early_exit_margin
+        and other_count <= options.early_exit_max_other
+    )
+
+
+def _count_kmers(
+    seq: str,
+    hap_a: kmers.KmerLookup,
+    hap_b: kmers.KmerLookup,
+    options: ClassifyOptions,
+    quals: Optional[str],
+) -> Tuple[int, int, int, int, Tuple[float, float]]:
+    """Count the k-mers of a sequence in each haplotype for `classify_seq`
+
+    Returns:
+        the hits in haplotype A and B, the number of k-mers looked up and
+        skipped, and the weights of the hits in A and B to score
+    """
+    weights = None
+    num_kmers = None
+    if options.minimizer_window is not None:
+        counts = kmers.count_minimizers_in_lookups(
+            seq,
+            hap_a,
+            hap_b,
+            options.minimizer_window,
+            options.ignore_softmasked,
+            quals,
+            options.min_base_quality,
+        )
+        hap_a_count, hap_b_count, num_kmers, num_skipped = counts
+    elif options.syncmer_size is not None:
+        counts = kmers.count_syncmers_in_lookups(
+            seq,
+            hap_a,
+            hap_b,
+            options.syncmer_size,
+            options.ignore_softmasked,
+            quals,
+            options.min_base_quality,
+        )
+        hap_a_count, hap_b_count, num_kmers, num_skipped = counts
+    elif options.fuzzy:
+        counts = kmers.count_kmers_in_lookups_fuzzy(
+            seq,
+            hap_a,
+            hap_b,
+            options.ignore_softmasked,
+            quals,
+            options.min_base_quality,
+            options.kmer_step,
+        )
+        hap_a_count = counts.hap_a + counts.fuzzy_a
+        hap_b_count = counts.hap_b + counts.fuzzy_b
+        num_skipped = counts.num_skipped
+        weights = (
+            counts.hap_a + options.fuzzy_weight * counts.fuzzy_a,
+            counts.hap_b + options.fuzzy_weight * counts.fuzzy_b,
+        )
+    elif options.seed is not None:
+        counts = kmers.count_spaced_kmers_in_lookups(
+            seq,
+            hap_a,
+            hap_b,
+            options.seed,
+            options.ignore_softmasked,
+            quals,
+            options.min_base_quality,
+            options.kmer_step,
+        )
+        hap_a_count, hap_b_count, num_skipped = counts
+    else:
+        hap_a_count, hap_b_count, num_skipped = kmers.count_kmers_in_lookups(
+            seq,
+            hap_a,
+            hap_b,
+            options.ignore_softmasked,
+            quals,
+            options.min_base_quality,
+            options.kmer_step,
+        )
+    if num_kmers is None:
+        span = options.span(hap_a.k)
+        num_kmers = _num_kmers(len(seq), span, options.kmer_step) - num_skipped
+    if options.score == ScoreMode.WEIGHTED:
+        weights = kmers.weigh_kmers_in_count_maps(
+            seq,
+            hap_a,
+            hap_b,
+            options.weight_cap,
+            options.ignore_softmasked,
+            quals,
+            options.min_base_quality,
+            options.kmer_step,
+        )
+    if weights is None:
+        weights = (hap_a_count, hap_b_count)
+    return hap_a_count, hap_b_count, num_kmers, num_skipped, weights
+
+
+def _num_kmers(seq_length: int, k: int, step: int) -> int:
+    """The number of k-mers at the positions looked at with a k-mer step,
+    including any skipped"""
+    # k-mers start at 0, step, 2 * step, ... up to seq_length - k
+    return max(seq_length - k + step, 0) // step
+
+
+def classify_record(
+    record: SeqRecord,
+    hap_a: kmers.KmerLookup,
+    hap_b: kmers.KmerLookup,
+    scaling_factors: Tuple[float, float] = (1.0, 1.0),
+    options: Optional[ClassifyOptions] = None,
+) -> Classification:
+    """Classify a read, or any other record, with `classify_seq`
+
+    The record's quality string is used if it has one.
+    """
+    return classify_seq(record.seq, hap_a, hap_b, scaling_factors, options, record.qual)
+
+
+def classify_pair(
+    seq_1: str,
+    seq_2: str,
+    hap_a: kmers.KmerLookup,
+    hap_b: kmers.KmerLookup,
+    scaling_factors: Tuple[float, float] = (1.0, 1.0),
+    options: Optional[ClassifyOptions] = None,
+    quals_1: Optional[str] = None,
+    quals_2: Optional[str] = None,
+) -> Classification:
+    """Classify both mates of a read pair into a single haplotype bin.
+
+    This is the same as `classify_seq`, but the k-mers from each
+    haplotype are counted across both mates, so that the pair ends up
+    in one bin.
+
+    Args:
+        seq_1: the sequence of the first mate
+        seq_2: the sequence of the second mate
+        hap_a: k-mers specific to haplotype A
+        hap_b: k-mers specific to haplotype B
+        scaling_factors: see `classify_seq`
+        options: see `classify_seq`
+        quals_1: the quality string of the first mate, if it has one
+        quals_2: the quality string of the second mate, if it has one
+
+    Returns:
+        the k-mer counts for each haplotype summed across both mates and
+        the bin assigned
+    """
+    # unscaled, so that the scores are the weights of the hits
+    classification_1 = classify_seq(seq_1, hap_a, hap_b, options=options, quals=quals_1)
+    classification_2 = classify_seq(seq_2, hap_a, hap_b, options=options, quals=quals_2)
+    classification = _classify_counts(
+        classification_1.hap_a_count + classification_2.hap_a_count,
+        classification_1.hap_b_count + classification_2.hap_b_count,
+        classification_1.num_kmers + classification_2.num_kmers,
+        classification_1.num_skipped + classification_2.num_skipped,
+        scaling_factors,
+        options,
+        (
+            classification_1.hap_a_score + classification_2.hap_a_score,
+            classification_1.hap_b_score + classification_2.hap_b_score,
+        ),
+    )
+    early_exit = classification_1.early_exit or classification_2.early_exit
+    return replace(classification, early_exit=early_exit)
+
+
+@dataclass
+class WindowedClassification:
+    """The result of classifying a sequence as a whole and by window"""
+
+    overall: Classification
+    """The classification of the whole sequence"""
+    windows: List[Classification]
+    """The classification of each window of the sequence, in order"""
+    window_size: int
+    """The length of each window, except possibly the last"""
+
+    @property
+    def supporting_fraction(self) -> float:
+        """The fraction of windows in the same bin as the whole sequence,
+        or 0 if there are no windows"""
+        if not self.windows:
+            return 0.0
+        num_supporting = sum(
+            1 for window in self.windows if window.bin == self.overall.bin
+        )
+        return num_supporting / len(self.windows)
+
+    def calls(self, min_hits: int = 1) -> List[Bin]:
+        """The bin of each window, or `Bin.UNKNOWN` as no call for those
+        with fewer than `min_hits` hits from both haplotypes together,
+        for `find_switches`"""
+        return [
+            window.bin
+            if window.hap_a_count + window.hap_b_count >= min_hits
+            else Bin.UNKNOWN
+            for window in self.windows
+        ]
+
+
+@dataclass
+class SwitchEvent:
+    """A switch from one haplotype to the other partway along a sequence,
+    as found by `find_switches`"""
+
+    contig: str
+    """The name of the sequence"""
+    start: int
+    """The end of the last window before the switch, 0-based"""
+    end: int
+    """The start of the first window after the switch. The switch is
+    somewhere around `start` to `end`, which are the same unless there
+    are windows with no call between them."""
+    from_bin: Bin
+    """The bin of the windows before the switch"""
+    to_bin: Bin
+    """The bin of the windows after the switch"""
+    windows_before: int
+    """The number of windows called `from_bin` in the run before the
+    switch"""
+    windows_after: int
+    """The number of windows called `to_bin` in the run after the switch"""
+
+
+def find_switches(
+    window_calls: Sequence[Bin], window_size: int, min_run: int = 1, contig: str = ""
+) -> List[SwitchEvent]:
+    """Find where a sequence switches from one haplotype to the other.
+
+    The windows are split into runs of consecutive windows called the
+    same haplotype, and each pair of neighbouring runs of different
+    haplotypes is a switch. Windows with no call, `Bin.UNKNOWN`, don't
+    break a run, and neither do runs of fewer than `min_run` windows,
+    which are left out like windows with no call, so that a stray window
+    doesn't make two switches. So with `min_run` 2, AAnnBB has a switch
+    across the n's, and ABAB has none.
+
+    Args:
+        window_calls: the bin of each window, in order, e.g., from
+            `WindowedClassification.calls`
+        window_size: the length of each window
+        min_run: the fewest windows in a run for it to count
+        contig: the name of the sequence, for the events
+
+    Returns:
+        each switch, in order along the sequence
+
+    Raises:
+        ValueError: if `window_size` or `min_run` is less than 1
+    """
+    if window_size < 1:
+        raise ValueError(f"window_size must be at least 1, not {window_size}")
+    if min_run < 1:
+        raise ValueError(f"min_run must be at least 1, not {min_run}")
+
+    runs: List[_Run] = []
+    for i, call in enumerate(window_calls):
+        if call == Bin.UNKNOWN:
+            continue
+        if runs and runs[-1].bin == call:
+            runs[-1] = runs[-1]._replace(size=runs[-1].size + 1, last=i)
+        else:
+            runs.append(_Run(call, 1, i, i))
+
+    merged: List[_Run] = []
+    for run in runs:
+        if run.size < min_run:
+            continue
+        if merged and merged[-1].bin == run.bin:
+            merged[-1] = merged[-1]._replace(
+                size=merged[-1].size + run.size, last=run.last
+            )
+        else:
+            merged.append(run)
+
+    return [
+        SwitchEvent(
+            contig,
+            (before.last + 1) * window_size,
+            after.first * window_size,
+            before.bin,
+            after.bin,
+            before.size,
+            after.size,
+        )
+        for before, after in zip(merged, merged[1:])
+    ]
+
+
+class _Run(NamedTuple):
+    """A run of windows called the same bin, for `find_switches`"""
+
+    bin: Bin
+    size: int
+    first: int
+    last: int
+
+
+def classify_by_window(
+    seq: str,
+    hap_a: kmers.KmerLookup,
+    hap_b: kmers.KmerLookup,
+    window_size: int,
+    scaling_factors: Tuple[float, float] = (1.0, 1.0),
+    options: Optional[ClassifyOptions] = None,
+) -> WindowedClassification:
+    """Classify a long sequence, such as a contig, as a whole and by window.
+
+    The sequence is split into windows of `window_size` bases, and each
+    k-mer is counted in the window it starts in, so the window counts
+    add up to the counts for the whole sequence. Each window and the
+    whole sequence are classified from their counts like `classify_seq`
+    would. Windows in different bins suggest a chimeric sequence.
+
+    Args:
+        seq: the sequence to classify
+        hap_a: k-mers specific to haplotype A
+        hap_b: k-mers specific to haplotype B
+        window_size: the length of each window. The last window is
+            shorter if the sequence length is not a multiple of this.
+        scaling_factors: see `classify_seq`
+        options: see `classify_seq`
+
+    Returns:
+        the classification of the whole sequence and of each window
+    """
+    if window_size < 1:
+        raise ValueError(f"window_size must be at least 1, not {window_size}")
+    if options is None:
+        options = ClassifyOptions()
+
+    starts = range(0, len(seq), window_size)
+    if _can_count_packed(options):
+        # the sequence is packed once, rather than each window being
+        # encoded on its own, and only exact hits are counted, so the
+        # weights are the counts
+        packed = kmers.PackedSeq.from_str(seq, options.ignore_softmasked)
+        unscaled = []
+        for start in starts:
+            hap_a_count, hap_b_count, num_skipped = kmers.count_kmers_in_packed_lookups(
+                packed, hap_a, hap_b, start, start + window_size, options.kmer_step
+            )
+            window_length = min(window_size + hap_a.k - 1, len(seq) - start)
+            num_kmers = _num_kmers(window_length, hap_a.k, options.kmer_step)
+            unscaled.append(
+                _classify_counts(
+                    hap_a_count,
+                    hap_b_count,
+                    num_kmers - num_skipped,
+                    num_skipped,
+                    (1.0, 1.0),
+                    options,
+                )
+            )
+    else:
+        # each window's slice includes the k - 1 bases after it, so that it
+        # has every k-mer starting in the window. They're classified unscaled
+        # first, so that the scores are the weights of the hits, which are
+        # added up
+        unscaled = [
+            classify_seq(
+                seq[start : start + window_size + options.span(hap_a.k) - 1],
+                hap_a,
+                hap_b,
+                options=options,
+            )
+            for start in starts
+        ]
+    windows = [
+        _classify_counts(
+            window.hap_a_count,
+            window.hap_b_count,
+            window.num_kmers,
+            window.num_skipped,
+            scaling_factors,
+            options,
+            (window.hap_a_score, window.hap_b_score),
+        )
+        for window in unscaled
+    ]
+    overall = _classify_counts(
+        sum(window.hap_a_count for window in windows),
+        sum(window.hap_b_count for window in windows),
+        sum(window.num_kmers for window in windows),
+        sum(window.num_skipped for window in windows),
+        scaling_factors,
+        options,
+        (
+            sum(window.hap_a_score for window in unscaled),
+            sum(window.hap_b_score for window in unscaled),
+        ),
+    )
+    return WindowedClassification(overall, windows, window_size)
+
+
+def _can_count_packed(options: ClassifyOptions) -> bool:
+    """Whether `classify_by_window` can count the k-mers of each window in
+    a packed sequence, which only finds exact, contiguous hits and can't
+    stop early"""
+    return not (
+        options.compress_homopolymers
+        or options.fuzzy
+        or options.minimizer_window is not None
+        or options.score == ScoreMode.WEIGHTED
+        or options.early_exit_margin is not None
+        or options.seed_pattern is not None
+        or options.syncmer_size is not None
+    )
+
+
+SCORE_DIGITS = 3
+"""The significant figures scores are rounded to in a `ScoreHistogram`"""
+
+
+class ScoreHistogram:
+    """The number of sequences with each score, for the mean and median
+
+    Scores are counted rounded to `SCORE_DIGITS` significant figures, so
+    there are at most 900 counts for each power of ten the scores span,
+    however many sequences are added. The mean is exact, but the median is
+    that of the rounded scores, which is within 0.05% of the exact one, and
+    exact for whole-number scores below 1000.
+    """
+
+    def __init__(self):
+        self.count = 0
+        """The number of scores added"""
+        self.total = 0.0
+        """The sum of the scores added"""
+        self.counts: Dict[float, int] = {}
+        """The number of scores added with each rounded score"""
+
+    def add(self, score: float):
+        """Add a score"""
+        self.count += 1
+        self.total += score
+        rounded = float(f"{score:.{SCORE_DIGITS}g}")
+        self.counts[rounded] = self.counts.get(rounded, 0) + 1
+
+    def mean(self) -> Optional[float]:
+        """The mean score, or None if there are none"""
+        return self.total / self.count if self.count else None
+
+    def median(self) -> Optional[float]:
+        """The median rounded score, or None if there are none
+
+        Like `statistics.median`, this is the mean of the middle two when
+        there's an even number of scores.
+        """
+        if not self.count:
+            return None
+        middle = [(self.count - 1) // 2, self.count // 2]
+        values: List[float] = []
+        seen = 0
+        for score in sorted(self.counts):
+            seen += self.counts[score]
+            while len(values) < 2 and middle[len(values)] < seen:
+                values.append(score)
+            if len(values) == 2:
+                break
+        return (values[0] + values[1]) / 2
+
+    def to_dict(self) -> Dict[str, Any]:
+        """Convert the histogram to a dict that can be serialized as JSON,
+        with the counts as [score, count] pairs in order"""
+        return {
+            "total": self.total,
+            "counts": [[score, self.counts[score]] for score in sorted(self.counts)],
+        }
+
+    @staticmethod
+    def from_dict(histogram_dict: Dict[str, Any]) -> "ScoreHistogram":
+        """Make a histogram from a dict made by `to_dict`
+
+        Raises:
+            ValueError: if the dict isn't the right shape
+        """
+        histogram = ScoreHistogram()
+        try:
+            histogram.total = float(histogram_dict["total"])
+            for score, count in histogram_dict["counts"]:
+                histogram.counts[float(score)] = int(count)
+                histogram.count += int(count)
+        except (KeyError, TypeError, ValueError) as e:
+            raise ValueError(f"that isn't a histogram from to_dict: {e!r}") from None
+        return histogram
+
+
+class Summary:
+    """Statistics about the sequences put into each bin
+
+    Add each classified sequence with `add` as it is classified, and
+    each sequence that is left out without being classified with `skip`,
+    e.g., by subsampling, or `filter`, e.g., for being too short.
+    Malformed records that were skipped are counted in `malformed`. The
+    scores of the sequences in each bin are counted in a `ScoreHistogram`
+    for the medians, which doesn't grow with the number of sequences.
+    """
+
+    def __init__(self):
+        self.reads_seen = 0
+        """The number of reads looked at, whether classified or skipped"""
+        self.filtered: Dict[str, int] = {}
+        """The number of reads filtered out before being classified, for
+        each reason they were filtered out for"""
+        self.malformed = 0
+        """The number of malformed records skipped, which aren't seen"""
+        self.reads = {read_bin: 0 for read_bin in Bin}
+        """The number of reads put into each bin"""
+        self.bases = {read_bin: 0 for read_bin in Bin}
+        """The total length of the reads put into each bin"""
+        self.rescued = {read_bin: 0 for read_bin in Bin}
+        """The number of the reads in each bin that were put there by
+        `rescue` rather than by the main pass"""
+        self.duplicates = 0
+        """The number of reads (or, for pairs, mates) with the same name as
+        a read before them, found with --check-duplicates"""
+        self.early_exits = 0
+        """The number of reads whose k-mers were only counted partway
+        through, because their bin was certain before the end"""
+        self.masked_bases = 0
+        """The number of bases masked with N's before counting k-mers, e.g.,
+        as adapter sequence"""
+        self._hap_a_scores = {read_bin: ScoreHistogram() for read_bin in Bin}
+        self._hap_b_scores = {read_bin: ScoreHistogram() for read_bin in Bin}
+
+    def add(self, classification: Classification, num_bases: int, num_reads: int = 1):
+        """Add a classified sequence
+
+        Args:
+            classification: the classification of the sequence
+            num_bases: the length of the sequence
+            num_reads: the number of reads the sequence is made up of,
+                e.g., 2 for a read pair
+        """
+        self.reads_seen += num_reads
+        self.reads[classification.bin] += num_reads
+        self.bases[classification.bin] += num_bases
+        if classification.rescued:
+            self.rescued[classification.bin] += num_reads
+        if classification.early_exit:
+            self.early_exits += num_reads
+        self.masked_bases += classification.masked_bases
+        self._hap_a_scores[classification.bin].add(classification.hap_a_score)
+        self._hap_b_scores[classification.bin].add(classification.hap_b_score)
+
+    def skip(self, num_reads: int = 1):
+        """Count a sequence that was seen but not classified
+
+        Args:
+            num_reads: the number of reads the sequence is made up of
+        """
+        self.reads_seen += num_reads
+
+    def filter(self, reason: str, num_reads: int = 1):
+        """Count a sequence that was filtered out before being classified
+
+        Args:
+            reason: why it was filtered out, e.g., "too_short"
+            num_reads: the number of reads the sequence is made up of
+        """
+        self.reads_seen += num_reads
+        self.filtered[reason] = self.filtered.get(reason, 0) + num_reads
+
+    @property
+    def total_reads(self) -> int:
+        """The number of reads put into any bin"""
+        return sum(self.reads.values())
+
+    @property
+    def total_bases(self) -> int:
+        """The total length of the reads put into any bin"""
+        return sum(self.bases.values())
+
+    @property
+    def unknown_fraction(self) -> float:
+        """The fraction of all reads that are unknown, or 0 if none"""
+        if not self.total_reads:
+            return 0.0
+        return self.reads[Bin.UNKNOWN] / self.total_reads
+
+    def to_dict(self, include_scores: bool = False) -> Dict[str, Any]:
+        """Convert the summary to a dict that can be serialized as JSON
+
+        Mean and median scores are None for bins with no sequences, and
+        the medians are rounded like `ScoreHistogram.median`.
+
+        Args:
+            include_scores: whether to include the `ScoreHistogram` of the
+                scores in each bin, which is bigger than the rest but lets
+                `from_dict` make a summary that more sequences can be added
+                to with the right medians
+        """
+        bins = {}
+        for read_bin in Bin:
+            bin_summary: Dict[str, Any] = {
+                "reads": self.reads[read_bin],
+                "bases": self.bases[read_bin],
+                "primary_reads": self.reads[read_bin] - self.rescued[read_bin],
+                "rescued_reads": self.rescued[read_bin],
+            }
+            for hap, scores in [
+                ("hap_a", self._hap_a_scores[read_bin]),
+                ("hap_b", self._hap_b_scores[read_bin]),
+            ]:
+                bin_summary[f"mean_{hap}_score"] = scores.mean()
+                bin_summary[f"median_{hap}_score"] = scores.median()
+                if include_scores:
+                    bin_summary[f"{hap}_scores"] = scores.to_dict()
+            bins[read_bin.value] = bin_summary
+
+        return {
+            "bins": bins,
+            "reads_seen": self.reads_seen,
+            "filtered": dict(self.filtered),
+            "malformed": self.malformed,
+            "duplicates": self.duplicates,
+            "early_exits": self.early_exits,
+            "masked_bases": self.masked_bases,
+            "total_reads": self.total_reads,
+            "total_bases": self.total_bases,
+            "unknown_fraction": self.unknown_fraction,
+        }
+
+    @staticmethod
+    def from_dict(summary_dict: Dict[str, Any]) -> "Summary":
+        """Make a summary from a dict made by `to_dict`
+
+        The means, medians and totals are worked out again rather than
+        read. Without the scores, from `to_dict(include_scores=True)`,
+        the summary has the counts but no scores, so its means and
+        medians are None.
+
+        Raises:
+            ValueError: if the dict is missing any of the counts, or they
+                aren't the right shape
+        """
+        summary = Summary()
+        try:
+            summary.reads_seen = summary_dict["reads_seen"]
+            summary.filtered = dict(summary_dict["filtered"])
+            summary.malformed = summary_dict["malformed"]
+            summary.duplicates = summary_dict["duplicates"]
+            summary.early_exits = summary_dict["early_exits"]
+            # left out of summaries from before adapters were masked
+            summary.masked_bases = summary_dict.get("masked_bases", 0)
+            for read_bin in Bin:
+                bin_summary = summary_dict["bins"][read_bin.value]
+                summary.reads[read_bin] = bin_summary["reads"]
+                summary.bases[read_bin] = bin_summary["bases"]
+                summary.rescued[read_bin] = bin_summary["rescued_reads"]
+                for hap, scores in [
+                    ("hap_a", summary._hap_a_scores),
+                    ("hap_b", summary._hap_b_scores),
+                ]:
+                    if f"{hap}_scores" in bin_summary:
+                        scores[read_bin] = ScoreHistogram.from_dict(
+                            bin_summary[f"{hap}_scores"]
+                        )
+        except (KeyError, TypeError) as e:
+            raise ValueError(f"that isn't a summary from to_dict: {e!r}") from None
+        return summary
+
+    def print_table(self, file: Optional[TextIO] = None):
+        """Print the number of reads and bases in each bin as a table
+
+        Args:
+            file: the file to print the table to. Default is stderr.
+        """
+        if file is None:
+            file = sys.stderr
+        print("bin\treads\tbases", file=file)
+        for read_bin in Bin:
+            print(
+                f"{read_bin.value}\t{self.reads[read_bin]}\t{self.bases[read_bin]}",
+                file=file,
+            )
+
+
+class Progress:
+    """Prints how many reads have been classified so far, now and then
+
+    Call `update` with the `Summary` of the reads after each one is added
+    to it, and `finish` at the end. A line with the number of reads and
+    bases so far, the throughput since the last line and the reads in each
+    bin is printed at most once every `interval` seconds. Every update comes
+    from the one thread that takes the classified reads, however many
+    threads classify them, so the counts never need locking and only one
+    thread ever prints. If the file is a terminal, each line overwrites the
+    last one.
+    """
+
+    def __init__(
+        self,
+        file: Optional[TextIO] = None,
+        interval: float = 1.0,
+        clock: Callable[[], float] = time.monotonic,
+    ):
+        """
+        Args:
+            file: the file to print progress to. Default is stderr.
+            interval: the least number of seconds between lines
+            clock: what to get the time from, in seconds
+        """
+        self.file = sys.stderr if file is None else file
+        self.interval = interval
+        self._clock = clock
+        self._is_terminal = self.file.isatty()
+        self._start = self._last_time = clock()
+        self._last_bases = 0
+
+    def update(self, summary: Summary):
+        """Print a progress line if it has been long enough since the last"""
+        now = self._clock()
+        if now - self._last_time >= self.interval:
+            self._print(summary, now)
+
+    def finish(self, summary: Summary):
+        """Print a last progress line, with the throughput over the whole run"""
+        self._last_time = self._start
+        self._last_bases = 0
+        self._print(summary, self._clock())
+        if self._is_terminal:
+            print(file=self.file)
+
+    def _print(self, summary: Summary, now: float):
+        elapsed = now - self._last_time
+        bases = summary.total_bases
+        throughput = (bases - self._last_bases) / elapsed / 1e6 if elapsed else 0.0
+        bins = ", ".join(
+            f"{read_bin.value}: {summary.reads[read_bin]}" for read_bin in Bin
+        )
+        message = (
+            f"{summary.total_reads} reads, {bases / 1e6:.1f} Mbp, "
+            f"{throughput:.2f} Mbp/s ({bins})"
+        )
+        if self._is_terminal:
+            # clear the rest of the last line in case it was longer
+            print(f"\r{message}\x1b[K", end="", file=self.file, flush=True)
+        else:
+            print(message, file=self.file, flush=True)
+        self._last_time = now
+        self._last_bases = bases
+
+
+T = TypeVar("T")
+R = TypeVar("R", bound=SeqRecord)
+R_contra = TypeVar("R_contra", bound=SeqRecord, contravariant=True)
+
+
+def _classify_batch(
+    classify: Callable[[T], Classification], batch: List[T]
+) -> List[Tuple[T, Classification]]:
+    """Classify every item in a batch"""
+    start = time.perf_counter()
+    classified = [(item, classify(item)) for item in batch]
+    if logger.isEnabledFor(logging.DEBUG):
+        counts = {read_bin: 0 for read_bin in Bin}
+        for _, classification in classified:
+            counts[classification.bin] += 1
+        logger.debug(
+            "Classified a batch of %d in %.3f s (%s)",
+            len(batch),
+            time.perf_counter() - start,
+            ", ".join(f"{read_bin.value}: {n}" for read_bin, n in counts.items()),
+        )
+    return classified
+
+
+def _classify_all(
+    items: Iterable[T],
+    classify: Callable[[T], Classification],
+    threads: int,
+    keep_order: bool,
+    batch_size: int,
+    queue_depth: Optional[int],
+) -> Iterator[Tuple[T, Classification]]:
+    """Classify items with `classify`, optionally in parallel
+
+    See `classify_reads` for a description of the arguments.
+    """
+    if threads < 1:
+        raise ValueError(f"threads must be at least 1, not {threads}")
+    if batch_size < 1:
+        raise ValueError(f"batch_size must be at least 1, not {batch_size}")
+    if queue_depth is None:
+        queue_depth = 2 * threads
+    elif queue_depth < 1:
+        raise ValueError(f"queue_depth must be at least 1, not {queue_depth}")
+
+    if threads == 1:
+        for item in items:
+            yield item, classify(item)
+        return
+
+    # the batches' futures are kept in the order they were submitted, so
+    # with keep_order the oldest batch is always at the front and is
+    # waited for even if later ones finish first. No more are submitted
+    # while queue_depth are pending, which holds up the workers until the
+    # caller has taken it.
+    items = iter(items)
+    pending: Deque[Future] = deque()
+    with ThreadPoolExecutor(threads) as executor:
+        while True:
+            batch = list(islice(items, batch_size))
+            if batch:
+                pending.append(executor.submit(_classify_batch, classify, batch))
+            if not pending:
+                break
+            if batch and len(pending) < queue_depth:
+                continue
+
+            if keep_order:
+                yield from pending.popleft().result()
+            else:
+                done, _ = wait(pending, return_when=FIRST_COMPLETED)
+                for future in done:
+                    pending.remove(future)
+                    yield from future.result()
+
+
+def classify_reads(
+    reads: Iterable[R],
+    hap_a: kmers.KmerLookup,
+    hap_b: kmers.KmerLookup,
+    scaling_factors: Tuple[float, float] = (1.0, 1.0),
+    options: Optional[ClassifyOptions] = None,
+    threads: int = 1,
+    keep_order: bool = True,
+    batch_size: int = 1000,
+    queue_depth: Optional[int] = None,
+) -> Iterator[Tuple[R, Classification]]:
+    """Classify reads into haplotype bins, optionally in parallel
+
+    With more than one thread, reads are split into batches of
+    `batch_size` that are classified by a pool of worker threads. The
+    k-mer counting is done in C without holding the GIL, so this scales
+    with the number of threads. At most `queue_depth` batches are held
+    in memory at once, counting the one being yielded from, and no more
+    reads are taken from `reads` until the caller has taken the
+    classified reads of a batch, so memory use is bounded however far
+    ahead of the caller (e.g., writing the reads out) the threads get.
+    By default the reads are yielded in the order they were given,
+    whichever thread finished first, so the output is the same with any
+    number of threads.
+
+    Args:
+        reads: the reads to classify, which can be any `SeqRecord`
+        hap_a: k-mers specific to haplotype A
+        hap_b: k-mers specific to haplotype B
+        scaling_factors: see `classify_seq`
+        options: see `classify_seq`
+        threads: number of worker threads to classify reads with
+        keep_order: whether to yield reads in the order they were
+            given. If False, batches are yielded as soon as they are
+            done, which is faster when read lengths vary a lot.
+        batch_size: number of reads per batch with multiple threads
+        queue_depth: the maximum number of batches held at once with
+            multiple threads. Default is two per thread.
+
+    Yields:
+        each read and its classification
+    """
+    return _classify_all(
+        reads,
+        lambda read: classify_record(read, hap_a, hap_b, scaling_factors, options),
+        threads,
+        keep_order,
+        batch_size,
+        queue_depth,
+    )
+
+
+def classify_read_pairs(
+    pairs: Iterable[Tuple[R, R]],
+    hap_a: kmers.KmerLookup,
+    hap_b: kmers.KmerLookup,
+    scaling_factors: Tuple[float, float] = (1.0, 1.0),
+    options: Optional[ClassifyOptions] = None,
+    threads: int = 1,
+    keep_order: bool = True,
+    batch_size: int = 1000,
+    queue_depth: Optional[int] = None,
+) -> Iterator[Tuple[Tuple[R, R], Classification]]:
+    """Classify read pairs into haplotype bins, optionally in parallel
+
+    This is the same as `classify_reads`, but each pair is classified
+    jointly with `classify_pair`.
+
+    Yields:
+        each pair and its classification
+    """
+    return _classify_all(
+        pairs,
+        lambda pair: classify_pair(
+            pair[0].seq,
+            pair[1].seq,
+            hap_a,
+            hap_b,
+            scaling_factors,
+            options,
+            pair[0].qual,
+            pair[1].qual,
+        ),
+        threads,
+        keep_order,
+        batch_size,
+        queue_depth,
+    )
+
+
+class ClassificationSink(Protocol[R_contra]):
+    """Anything that takes classified records, for `Classifier.classify_stream`"""
+
+    def classified(self, record: R_contra, classification: Classification):
+        """Take a record and its classification, e.g., to write it out"""
+
+
+class Classifier:
+    """Everything needed to classify reads, in one object for library use
+
+    This bundles the k-mers of each haplotype, the scaling factors, the
+    thresholds and the threading settings, so a program can set them up
+    once and then classify records without passing them all around. It
+    is what classify-by-kmers classifies with. Make one with the builder:
+
+    >>> hap_a = kmers.KmerSet(5)
+    >>> hap_a.add(kmers.kmer_to_int("AAAAC"))
+    True
+    >>> hap_b = kmers.KmerSet(5)
+    >>> hap_b.add(kmers.kmer_to_int("GGGGT"))
+    True
+    >>> classifier = Classifier.builder().hap_a(hap_a).hap_b(hap_b).build()
+    >>> classifier.classify_seq("GAAAACT").bin
+    <Bin.HAP_A: 'A'>
+
+    A Classifier is never changed after it is built, and the k-mer sets
+    are only read, so one can be shared by any number of threads, e.g.,
+    in a program's own thread pool, as long as nothing adds to the sets.
+    """
+
+    def __init__(
+        self,
+        hap_a: kmers.KmerLookup,
+        hap_b: kmers.KmerLookup,
+        options: Optional[ClassifyOptions] = None,
+        rescue_options: Optional[ClassifyOptions] = None,
+        threads: int = 1,
+        keep_order: bool = True,
+        batch_size: int = 1000,
+        queue_depth: Optional[int] = None,
+        adapter_masker: Optional[AdapterMasker] = None,
+    ):
+        """Set up a classifier. `Classifier.builder` is usually easier.
+
+        Args:
+            hap_a: k-mers specific to haplotype A
+            hap_b: k-mers specific to haplotype B
+            options: see `classify_seq`
+            rescue_options: thresholds to `rescue` unknown records with,
+                or None not to
+            threads: see `classify_reads`
+            keep_order: see `classify_reads`
+            batch_size: see `classify_reads`
+            queue_depth: see `classify_reads`
+            adapter_masker: masks adapters in each record, or each mate of
+                a pair, with N's before its k-mers are counted, or None
+                not to. Windows of `classify_by_window` aren't masked.
+
+        Raises:
+            kmers.KmerSizeMismatchError: if the k-mer sets have different
+                k, which names the files they were read from if known
+            ValueError: if a setting is out of range,
+                `options.syncmer_size` isn't less than k, or either set of
+                k-mers is empty
+        """
+        kmers.check_same_k(hap_a, hap_b)
+        if threads < 1:
+            raise ValueError(f"threads must be at least 1, not {threads}")
+        if batch_size < 1:
+            raise ValueError(f"batch_size must be at least 1, not {batch_size}")
+        if queue_depth is not None and queue_depth < 1:
+            raise ValueError(f"queue_depth must be at least 1, not {queue_depth}")
+        self.hap_a = hap_a
+        self.hap_b = hap_b
+        self.options = options if options is not None else ClassifyOptions()
+        syncmer_size = self.options.syncmer_size
+        if syncmer_size is not None and syncmer_size >= hap_a.k:
+            raise ValueError(
+                f"syncmer_size must be less than k, {hap_a.k}, not {syncmer_size}"
+            )
+        self.rescue_options = rescue_options
+        self.threads = threads
+        self.keep_order = keep_order
+        self.batch_size = batch_size
+        self.queue_depth = queue_depth
+        self.adapter_masker = adapter_masker
+        self.scaling_factors = calculate_scaling_factors(hap_a, hap_b)
+
+    @staticmethod
+    def builder() -> "ClassifierBuilder":
+        """Start building a classifier"""
+        return ClassifierBuilder()
+
+    def _rescue(self, classification: Classification) -> Classification:
+        if self.rescue_options is None:
+            return classification
+        return rescue(classification, self.rescue_options)
+
+    def _mask(self, seq: str) -> Tuple[str, int]:
+        if self.adapter_masker is None:
+            return seq, 0
+        return self.adapter_masker.mask(seq)
+
+    def classify_seq(self, seq: str, quals: Optional[str] = None) -> Classification:
+        """Classify a sequence, like `classify_seq`"""
+        seq, masked_bases = self._mask(seq)
+        classification = classify_seq(
+            seq, self.hap_a, self.hap_b, self.scaling_factors, self.options, quals
+        )
+        if masked_bases:
+            classification = replace(classification, masked_bases=masked_bases)
+        return self._rescue(classification)
+
+    def classify_record(self, record: SeqRecord) -> Classification:
+        """Classify a read or any other record, like `classify_record`"""
+        return self.classify_seq(record.seq, record.qual)
+
+    def classify_pair(self, pair: Tuple[SeqRecord, SeqRecord]) -> Classification:
+        """Classify both mates of a read pair together, like `classify_pair`"""
+        seq_1, masked_1 = self._mask(pair[0].seq)
+        seq_2, masked_2 = self._mask(pair[1].seq)
+        classification = classify_pair(
+            seq_1,
+            seq_2,
+            self.hap_a,
+            self.hap_b,
+            self.scaling_factors,
+            self.options,
+            pair[0].qual,
+            pair[1].qual,
+        )
+        if masked_1 or masked_2:
+            classification = replace(classification, masked_bases=masked_1 + masked_2)
+        return self._rescue(classification)
+
+    def classify_by_window(self, seq: str, window_size: int) -> WindowedClassification:
+        """Classify a sequence as a whole and in windows, like
+        `classify_by_window`. Windows aren't rescued."""
+        windowed = classify_by_window(
+            seq,
+            self.hap_a,
+            self.hap_b,
+            window_size,
+            self.scaling_factors,
+            self.options,
+        )
+        windowed.overall = self._rescue(windowed.overall)
+        return windowed
+
+    def classify_reads(self, reads: Iterable[R]) -> Iterator[Tuple[R, Classification]]:
+        """Classify reads with the classifier's threads, like `classify_reads`"""
+        return _classify_all(
+            reads,
+            self.classify_record,
+            self.threads,
+            self.keep_order,
+            self.batch_size,
+            self.queue_depth,
+        )
+
+    def classify_read_pairs(
+        self, pairs: Iterable[Tuple[R, R]]
+    ) -> Iterator[Tuple[Tuple[R, R], Classification]]:
+        """Classify read pairs with the classifier's threads, like
+        `classify_read_pairs`"""
+        return _classify_all(
+            pairs,
+            self.classify_pair,
+            self.threads,
+            self.keep_order,
+            self.batch_size,
+            self.queue_depth,
+        )
+
+    def classify_file(self, path: str) -> Iterator[Dict[str, Any]]:
+        """Classify every read in a file with the classifier's threads
+
+        The file can be any format `seq.open_records` reads. Each read is
+        yielded as a dict of its name and length and the fields of its
+        `Classification`, from `Classification.to_dict`, e.g., to turn
+        into JSON or a data frame. The file isn't opened until the first
+        read is asked for.
+
+        Raises:
+            seq.SeqError: if the file is malformed, or the reads have no
+                qualities when `ClassifyOptions.min_base_quality` needs
+                them
+        """
+        reads: Iterator[Read] = open_records(path)
+        if self.options.min_base_quality:
+            reads = check_qualities(reads)
+        for read, classification in self.classify_reads(reads):
+            yield {"name": read.name, "length": len(read), **classification.to_dict()}
+
+    def classify_stream(self, reads: Iterable[R], sink: ClassificationSink[R]):
+        """Classify reads and hand each one to `sink.classified` as it is
+        done, in order unless `keep_order` is off"""
+        for read, classification in self.classify_reads(reads):
+            sink.classified(read, classification)
+
+
+class ClassifierBuilder:
+    """Sets up a `Classifier` one setting at a time
+
+    Every setter returns the builder, so they can be chained. Only the
+    two haplotypes' k-mers are required.
+    """
+
+    def __init__(self):
+        self._hap_a: Optional[kmers.KmerLookup] = None
+        self._hap_b: Optional[kmers.KmerLookup] = None
+        self._options = ClassifyOptions()
+        self._rescue_options: Optional[ClassifyOptions] = None
+        self._threads = 1
+        self._keep_order = True
+        self._batch_size = 1000
+        self._queue_depth: Optional[int] = None
+        self._adapter_masker: Optional[AdapterMasker] = None
+
+    def hap_a(self, hap_a: kmers.KmerLookup) -> "ClassifierBuilder":
+        """Set the k-mers specific to haplotype A"""
+        self._hap_a = hap_a
+        return self
+
+    def hap_b(self, hap_b: kmers.KmerLookup) -> "ClassifierBuilder":
+        """Set the k-mers specific to haplotype B"""
+        self._hap_b = hap_b
+        return self
+
+    def options(self, options: ClassifyOptions) -> "ClassifierBuilder":
+        """Set all the thresholds and other options at once"""
+        self._options = options
+        return self
+
+    def min_hits(self, min_hits: int) -> "ClassifierBuilder":
+        """Set `ClassifyOptions.min_hits`"""
+        self._options = replace(self._options, min_hits=min_hits)
+        return self
+
+    def min_hits_per_kb(self, min_hits_per_kb: float) -> "ClassifierBuilder":
+        """Set `ClassifyOptions.min_hits_per_kb`"""
+        self._options = replace(self._options, min_hits_per_kb=min_hits_per_kb)
+        return self
+
+    def min_margin(self, min_margin: float) -> "ClassifierBuilder":
+        """Set `ClassifyOptions.min_margin`"""
+        self._options = replace(self._options, min_margin=min_margin)
+        return self
+
+    def min_ratio(self, min_ratio: float) -> "ClassifierBuilder":
+        """Set `ClassifyOptions.min_ratio`"""
+        self._options = replace(self._options, min_ratio=min_ratio)
+        return self
+
+    def rescue(self, rescue_options: Optional[ClassifyOptions]) -> "ClassifierBuilder":
+        """Set the thresholds to `rescue` unknown records with, or None not
+        to rescue them"""
+        self._rescue_options = rescue_options
+        return self
+
+    def threads(self, threads: int) -> "ClassifierBuilder":
+        """Set the number of worker threads"""
+        self._threads = threads
+        return self
+
+    def keep_order(self, keep_order: bool) -> "ClassifierBuilder":
+        """Set whether reads are yielded in the order they were given"""
+        self._keep_order = keep_order
+        return self
+
+    def batch_size(self, batch_size: int) -> "ClassifierBuilder":
+        """Set the number of reads per batch with multiple threads"""
+        self._batch_size = batch_size
+        return self
+
+    def queue_depth(self, queue_depth: Optional[int]) -> "ClassifierBuilder":
+        """Set the most batches held at once with multiple threads"""
+        self._queue_depth = queue_depth
+        return self
+
+    def adapter_masker(
+        self, adapter_masker: Optional[AdapterMasker]
+    ) -> "ClassifierBuilder":
+        """Set what masks adapters in the records before classifying them,
+        or None not to mask them"""
+        self._adapter_masker = adapter_masker
+        return self
+
+    def build(self) -> Classifier:
+        """Build the classifier
+
+        Raises:
+            ValueError: if a haplotype's k-mers haven't been set, or see
+                `Classifier`
+        """
+        if self._hap_a is None or self._hap_b is None:
+            raise ValueError("the k-mers of both haplotypes are needed")
+        return Classifier(
+            self._hap_a,
+            self._hap_b,
+            self._options,
+            self._rescue_options,
+            self._threads,
+            self._keep_order,
+            self._batch_size,
+            self._queue_depth,
+            self._adapter_masker,
+        )
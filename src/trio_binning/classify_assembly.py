@@ -0,0 +1,271 @@
+"""Classify the contigs of an assembly into bins based on k-mers.
+
+This is a script for assigning the contigs of an assembly that contains
+both haplotypes to parental bins based on the presence of k-mers, like
+classify-by-kmers does for reads. Each contig is also classified in
+fixed-size windows, which can be written to a BED file to find chimeric
+contigs where the haplotype switches partway through, and the switches
+found from them can be written to a table. Scaffolds can be split into
+contigs at their gaps first with reads split-scaffolds, so that no window
+spans a gap, and the windows and switches put back on the scaffolds.
+"""
+
+import argparse
+import sys
+
+from trio_binning import kmers, log, seq
+from trio_binning.classify import Classifier, ClassifyOptions, find_switches
+from trio_binning.classify_by_kmers import load_kmer_set
+
+CONTIG_COLUMNS = [
+    "contig",
+    "length",
+    "bin",
+    "hap_a_hits",
+    "hap_b_hits",
+    "windows",
+    "supporting_fraction",
+]
+"""The columns of the per-contig table. The supporting fraction is the
+fraction of the contig's windows in the same bin as the whole contig."""
+
+SWITCH_COLUMNS = [
+    "contig",
+    "start",
+    "end",
+    "from_bin",
+    "to_bin",
+    "windows_before",
+    "windows_after",
+]
+"""The columns of the --switches table. The switch is between the end of
+the last window before it and the start of the first window after it,
+and the window counts are the sizes of the runs on each side."""
+
+
+def parse_args():
+    """Parse arguments"""
+    parser = argparse.ArgumentParser(
+        description=__doc__, formatter_class=argparse.ArgumentDefaultsHelpFormatter
+    )
+    parser.add_argument(
+        "assembly",
+        help="contigs to classify into bins, in fasta/q format. Can be gzipped.",
+    )
+    parser.add_argument(
+        "haplotype_a_kmers",
+        help="a list of k-mers unique to haplotype A, one per line",
+    )
+    parser.add_argument(
+        "haplotype_b_kmers",
+        help="a list of k-mers unique to haplotype B, one per line",
+    )
+    parser.add_argument(
+        "-k",
+        "--kmer-size",
+        type=int,
+        help=f"k-mer size, from {kmers.MIN_K} to {kmers.MAX_K}. Default is the "
+        "length of the first k-mer in the lists, which must be the same for both.",
+    )
+    parser.add_argument(
+        "-m",
+        "--min-count",
+        type=int,
+        help="skip k-mers with a count less than this in the k-mer lists",
+    )
+    parser.add_argument(
+        "--kmer-format",
+        type=kmers.DumpFormat,
+        choices=list(kmers.DumpFormat),
+        default=kmers.DumpFormat.AUTO,
+        help="format of the k-mer lists",
+    )
+    parser.add_argument(
+        "--load-kmers",
+        action="store_true",
+        default=False,
+        help="the k-mer lists are binary files written with classify-by-kmers "
+        "--save-kmers",
+    )
+    parser.add_argument(
+        "-w",
+        "--window-size",
+        type=int,
+        default=10000,
+        help="length of the windows to classify each contig in",
+    )
+    parser.add_argument(
+        "--windows",
+        metavar="FILE",
+        help="write the k-mer counts and bin of each window to FILE in BED format, "
+        "gzipped if it ends in .gz",
+    )
+    parser.add_argument(
+        "--switches",
+        metavar="FILE",
+        help="write each switch from one haplotype to the other within a contig, "
+        "between runs of windows in each bin, to FILE as a tab-separated table, "
+        "gzipped if it ends in .gz",
+    )
+    parser.add_argument(
+        "--min-switch-run",
+        type=int,
+        default=3,
+        help="with --switches, the fewest windows in a run of the same bin for it "
+        "to count. Shorter runs are ignored, so that a stray window doesn't make "
+        "two switches.",
+    )
+    parser.add_argument(
+        "--min-window-hits",
+        type=int,
+        default=5,
+        help="with --switches, windows with fewer hits than this from both "
+        "haplotypes together are no call, which doesn't break a run",
+    )
+    parser.add_argument(
+        "--scaffold-coordinates",
+        action="store_true",
+        default=False,
+        help="for contigs split from scaffolds by reads split-scaffolds, write the "
+        "--windows and --switches on the scaffolds they came from, using the "
+        "scaffold name and offset in each contig's description. The per-contig "
+        "table still has the contigs.",
+    )
+    parser.add_argument(
+        "--ignore-softmasked",
+        action="store_true",
+        default=False,
+        help="skip k-mers with lowercase (soft-masked) bases, like k-mers with "
+        "N's, instead of treating lowercase like uppercase",
+    )
+    log.add_verbose_argument(parser)
+    args = parser.parse_args()
+    if args.kmer_size is not None and not kmers.MIN_K <= args.kmer_size <= kmers.MAX_K:
+        parser.error(f"-k must be between {kmers.MIN_K} and {kmers.MAX_K}")
+    if args.min_switch_run < 1:
+        parser.error(f"--min-switch-run must be at least 1, not {args.min_switch_run}")
+    if args.min_window_hits < 0:
+        parser.error(
+            f"--min-window-hits must not be negative, not {args.min_window_hits}"
+        )
+    return args
+
+
+@seq.exit_on_broken_pipe
+def main():
+    """Main method of program"""
+    args = parse_args()
+    log.configure(args.verbose)
+    if args.window_size < 1:
+        sys.exit(f"Error: --window-size must be at least 1, not {args.window_size}")
+
+    haplotype_a_kmers = load_kmer_set(
+        args.haplotype_a_kmers,
+        args.kmer_size,
+        args.min_count,
+        args.kmer_format,
+        args.load_kmers,
+    )
+    haplotype_b_kmers = load_kmer_set(
+        args.haplotype_b_kmers,
+        args.kmer_size,
+        args.min_count,
+        args.kmer_format,
+        args.load_kmers,
+    )
+    for kmer_set, kmer_file_path in [
+        (haplotype_a_kmers, args.haplotype_a_kmers),
+        (haplotype_b_kmers, args.haplotype_b_kmers),
+    ]:
+        if kmer_set.homopolymer_compressed:
+            sys.exit(
+                f"Error: the k-mers in {kmer_file_path} are from "
+                "homopolymer-compressed sequence, which contigs can't be "
+                "classified against"
+            )
+        if kmer_set.seed is not None:
+            sys.exit(
+                f"Error: the k-mers in {kmer_file_path} are made with the seed "
+                f"pattern {kmer_set.seed}, which contigs can't be classified "
+                "against"
+            )
+    try:
+        classifier = (
+            Classifier.builder()
+            .hap_a(haplotype_a_kmers)
+            .hap_b(haplotype_b_kmers)
+            .options(ClassifyOptions(ignore_softmasked=args.ignore_softmasked))
+            .build()
+        )
+    except ValueError as e:
+        sys.exit(f"Error: {e}")
+
+    windows_file = None
+    if args.windows is not None:
+        windows_file = seq.open_text_write(args.windows)
+    switches_file = None
+    if args.switches is not None:
+        switches_file = seq.open_text_write(args.switches)
+        print(*SWITCH_COLUMNS, sep="\t", file=switches_file)
+
+    print(*CONTIG_COLUMNS, sep="\t")
+    try:
+        # contigs are read one at a time, so only one is ever in memory
+        for contig in seq.open_fastx_read(args.assembly):
+            classification = classifier.classify_by_window(contig.seq, args.window_size)
+            print(
+                contig.name,
+                len(contig.seq),
+                classification.overall.bin.value,
+                classification.overall.hap_a_count,
+                classification.overall.hap_b_count,
+                len(classification.windows),
+                f"{classification.supporting_fraction:.6g}",
+                sep="\t",
+            )
+
+            # where the windows and switches go, on the contig or its scaffold
+            name, offset = contig.name, 0
+            if args.scaffold_coordinates:
+                name, offset = seq.split_origin(contig) or (name, offset)
+
+            if windows_file is not None:
+                for i, window in enumerate(classification.windows):
+                    start = i * args.window_size
+                    print(
+                        name,
+                        offset + start,
+                        offset + min(start + args.window_size, len(contig.seq)),
+                        window.hap_a_count,
+                        window.hap_b_count,
+                        window.bin.value,
+                        sep="\t",
+                        file=windows_file,
+                    )
+
+            if switches_file is not None:
+                for switch in find_switches(
+                    classification.calls(args.min_window_hits),
+                    args.window_size,
+                    args.min_switch_run,
+                    name,
+                ):
+                    print(
+                        switch.contig,
+                        offset + switch.start,
+                        offset + switch.end,
+                        switch.from_bin.value,
+                        switch.to_bin.value,
+                        switch.windows_before,
+                        switch.windows_after,
+                        sep="\t",
+                        file=switches_file,
+                    )
+    finally:
+        for output_file in [windows_file, switches_file]:
+            if output_file is not None and output_file is not sys.stdout:
+                output_file.close()
+
+
+if __name__ == "__main__":
+    main()
@@ -5,30 +5,584 @@ based on the presence of k-mers.
 """
 
 import argparse
+import functools
+import json
+import logging
+import os
+import shlex
+import sys
+from dataclasses import replace
+from itertools import chain
 from os import path
-from typing import Tuple
+from typing import (
+    Any,
+    Callable,
+    Dict,
+    Iterable,
+    Iterator,
+    List,
+    Optional,
+    Sequence,
+    TextIO,
+    Tuple,
+    TypeVar,
+    Union,
+    cast,
+)
 
-from trio_binning import kmers, seq
+from trio_binning import adapters, checkpoint, config, kmers, log, sam, seq
+from trio_binning.classify import (
+    Bin,
+    Classification,
+    Classifier,
+    ClassifyOptions,
+    Progress,
+    ScoreMode,
+    Summary,
+)
+from trio_binning.pair import PairIter
+
+logger = logging.getLogger(__name__)
+
+DEFAULT_MINIMIZER_WINDOW = 10
+"""The --minimizer-window of --sampling minimizer when it isn't given"""
+
+DEFAULT_SYNCMER_SIZE = 11
+"""The --syncmer-size of --sampling syncmer when it isn't given, which
+looks up about as many of the reads' 21-mers as the default minimizers"""
 
 
 def parse_args():
-    """Parse arguments"""
+    """Parse arguments
+
+    With --config, the options in the config file are the defaults, so
+    options on the command line override them. If the config gives the
+    k-mer lists, every positional argument is a reads file.
+    """
+    # --config has to be known before the parser is made, since it decides
+    # whether the k-mer lists are positional arguments
+    config_parser = argparse.ArgumentParser(add_help=False, allow_abbrev=False)
+    config_parser.add_argument("--config")
+    config_path = config_parser.parse_known_args()[0].config
+    run_config: Dict[str, Any] = {}
+    config_error = None
+    if config_path is not None:
+        try:
+            run_config = config.load_config(config_path)
+        except config.ConfigError as e:
+            config_error = e
+    kmer_lists = {
+        name: run_config.pop(name)
+        for name in ["haplotype_a_kmers", "haplotype_b_kmers"]
+        if name in run_config
+    }
+
     parser = argparse.ArgumentParser(
         description=__doc__, formatter_class=argparse.ArgumentDefaultsHelpFormatter
     )
     parser.add_argument(
         "reads",
-        help="reads to classify into bins, in fasta/q format. Can be gzipped.",
+        nargs="*",
+        help="reads to classify into bins, in fasta/q format, or SAM/BAM, "
+        "where only primary records are used. Can be gzipped, or - for stdin. "
+        "Several files are classified one after another as if they were one. "
+        "Leave out to classify read pairs with --reads-1 and --reads-2 or "
+        "--interleaved instead.",
+    )
+    if not kmer_lists:
+        parser.add_argument(
+            "haplotype_a_kmers",
+            help="a list of k-mers unique to haplotype A, one per line",
+        )
+        parser.add_argument(
+            "haplotype_b_kmers",
+            help="a list of k-mers unique to haplotype B, one per line",
+        )
+    parser.add_argument(
+        "--reads-fofn",
+        metavar="FILE",
+        help="a file of reads files to classify, one path per line, like giving "
+        "them as reads. Blank lines and lines starting with # are skipped.",
+    )
+    parser.add_argument(
+        "-1",
+        "--reads-1",
+        help="first mates of read pairs to classify into bins, in fasta/q "
+        "format. Both mates of a pair are put in the same bin.",
+    )
+    parser.add_argument(
+        "-2",
+        "--reads-2",
+        help="second mates of read pairs to classify, in the same order as "
+        "--reads-1",
+    )
+    parser.add_argument(
+        "--interleaved",
+        help="read pairs to classify into bins, with each first mate followed "
+        "by its second mate",
+    )
+    parser.add_argument(
+        "-k",
+        "--kmer-size",
+        type=int,
+        help=f"k-mer size, from {kmers.MIN_K} to {kmers.MAX_K}. Default is the "
+        "length of the first k-mer in the lists, which must be the same for both.",
+    )
+    parser.add_argument(
+        "-m",
+        "--min-count",
+        type=int,
+        help="skip k-mers with a count less than this in the k-mer lists, which "
+        "must then have a tab-separated count on each line, as in the output of "
+        "`meryl print`",
+    )
+    parser.add_argument(
+        "--kmer-format",
+        type=kmers.DumpFormat,
+        choices=list(kmers.DumpFormat),
+        default=kmers.DumpFormat.AUTO,
+        help="format of the k-mer lists",
+    )
+    parser.add_argument(
+        "--min-hits",
+        type=int,
+        default=0,
+        help="leave reads with fewer k-mers than this from the two haplotypes put "
+        "together unclassified",
+    )
+    parser.add_argument(
+        "--min-hits-per-kb",
+        type=float,
+        default=0.0,
+        help="leave reads with fewer k-mers than this from the two haplotypes put "
+        "together per thousand k-mers looked up unclassified, which means the "
+        "same for short and long reads, e.g., 0.2. K-mers skipped for N's or "
+        "--min-base-quality aren't looked up.",
+    )
+    parser.add_argument(
+        "--min-margin",
+        type=float,
+        default=0.0,
+        help="leave reads whose haplotype A and B scores differ by less than this "
+        "unclassified",
+    )
+    parser.add_argument(
+        "--min-ratio",
+        type=float,
+        default=0.0,
+        help="leave reads whose higher score is less than this fraction of the sum "
+        "of the haplotype A and B scores unclassified, e.g., 0.7",
+    )
+    parser.add_argument(
+        "--fuzzy",
+        action="store_true",
+        default=False,
+        help="for nanopore reads: also count k-mers one substitution away from a "
+        "k-mer in only one list, which single-base errors would otherwise hide, "
+        "as hits. This takes up to 6k more lookups for each k-mer that isn't an "
+        "exact hit, so it's much slower, and isn't worth it for accurate reads "
+        "like HiFi.",
+    )
+    parser.add_argument(
+        "--fuzzy-weight",
+        type=float,
+        default=0.5,
+        help="with --fuzzy, how much a near hit adds to a score, compared to 1 for "
+        "an exact hit",
+    )
+    parser.add_argument(
+        "--sampling",
+        choices=["all", "minimizer", "syncmer"],
+        help="which of the reads' k-mers to look up: all of them, minimizers "
+        "(see --minimizer-window), or closed syncmers (see --syncmer-size). "
+        "Sampling is faster and gives almost the same bins for long reads. The "
+        "k-mer lists are still loaded in full, and --min-hits counts the k-mers "
+        "looked up, so it should be lowered to match. Unless given, it's "
+        "minimizer with --minimizer-window and all otherwise.",
+    )
+    parser.add_argument(
+        "--minimizer-window",
+        metavar="W",
+        type=int,
+        help="with --sampling minimizer, only look up the minimizer of each "
+        "window of W consecutive k-mers in the reads, about 2 / (W + 1) of them. "
+        f"W can be up to {kmers.MAX_MINIMIZER_WINDOW}, and is "
+        f"{DEFAULT_MINIMIZER_WINDOW} unless given.",
+    )
+    parser.add_argument(
+        "--syncmer-size",
+        metavar="S",
+        type=int,
+        help="with --sampling syncmer, only look up the k-mers whose lowest-hashed "
+        "S-mer is their first or last, about 2 / (k - S + 1) of them. Unlike "
+        "minimizers, each k-mer is picked by its own bases alone, so they are "
+        f"spread more evenly. S must be less than k, and is {DEFAULT_SYNCMER_SIZE} "
+        "unless given.",
+    )
+    parser.add_argument(
+        "--kmer-step",
+        metavar="S",
+        type=int,
+        default=1,
+        help="only look up the k-mers starting every S bases in the reads, which "
+        "is about S times faster. --min-hits counts the k-mers looked up, so it "
+        "should be lowered to match. Can't be combined with --sampling minimizer or "
+        "syncmer.",
+    )
+    parser.add_argument(
+        "--early-exit",
+        action="store_true",
+        default=False,
+        help="stop counting a read's k-mers once its bin is certain, which is "
+        "much faster for long reads. The report then has the hits up to there, "
+        "and flags the reads that stopped early.",
+    )
+    parser.add_argument(
+        "--early-exit-margin",
+        type=int,
+        default=50,
+        help="with --early-exit, how much more one haplotype's scaled score needs "
+        "to be than the other's for a read to stop early",
+    )
+    parser.add_argument(
+        "--early-exit-max-other",
+        type=int,
+        default=10,
+        help="with --early-exit, the most hits the other haplotype can have for a "
+        "read to stop early",
+    )
+    parser.add_argument(
+        "--rescue",
+        action="store_true",
+        default=False,
+        help="give reads left unclassified a second chance with the relaxed "
+        "--rescue-* thresholds, and flag the ones rescued in the report",
+    )
+    parser.add_argument(
+        "--rescue-min-hits",
+        type=int,
+        default=1,
+        help="with --rescue, --min-hits for the reads left unclassified",
+    )
+    parser.add_argument(
+        "--rescue-min-margin",
+        type=float,
+        default=1.0,
+        help="with --rescue, --min-margin for the reads left unclassified",
+    )
+    parser.add_argument(
+        "--rescue-min-ratio",
+        type=float,
+        default=0.0,
+        help="with --rescue, --min-ratio for the reads left unclassified",
+    )
+    parser.add_argument(
+        "--score",
+        type=ScoreMode,
+        choices=list(ScoreMode),
+        default=ScoreMode.COUNT,
+        help="how to score each haplotype: by the number of its k-mers found, or "
+        "weighted, where each k-mer found adds its count in the k-mer list, up "
+        "to --weight-cap, so that rare k-mers count for less. Weighted scoring "
+        "needs counted k-mer lists. --min-hits is still a number of k-mers.",
+    )
+    parser.add_argument(
+        "--weight-cap",
+        type=int,
+        default=10,
+        help="with --score weighted, the most that any one k-mer found adds to a "
+        "score",
+    )
+    parser.add_argument(
+        "--min-base-quality",
+        metavar="Q",
+        type=int,
+        default=0,
+        help="skip k-mers containing a base with a quality score below Q, like "
+        "k-mers with N's. The reads must have quality scores, e.g., be fastq, "
+        "read as --quality-encoding says.",
+    )
+    parser.add_argument(
+        "--quality-encoding",
+        type=seq.QualityEncoding,
+        choices=list(seq.QualityEncoding),
+        default=seq.QualityEncoding.AUTO,
+        help="the offset of the quality scores of fastq reads. Phred+64 qualities, "
+        "e.g., from old Illumina GA runs, are converted to Phred+33 as they're "
+        "read, so the quality filters and the output files see only Phred+33. "
+        "With auto, the offset is worked out from the first 1000 reads, taking "
+        "Phred+33 with a warning if they could be either.",
+    )
+    parser.add_argument(
+        "--compress-homopolymers",
+        action="store_true",
+        default=False,
+        help="count k-mers in homopolymer-compressed reads, e.g., for nanopore "
+        "reads. The k-mer lists must be from homopolymer-compressed sequence too, "
+        "e.g., from build-hapmers --compress-homopolymers.",
+    )
+    parser.add_argument(
+        "--rna",
+        action="store_true",
+        default=False,
+        help="read the U's of RNA reads, e.g., transcripts, as T's, so their k-mers "
+        "are looked up in the DNA k-mer lists rather than skipped like k-mers with "
+        "N's. The reads are written out with T's too. Without this, there's a "
+        "warning if the first read of a file looks like RNA.",
+    )
+    parser.add_argument(
+        "--seed-pattern",
+        metavar="PATTERN",
+        help="make the reads' k-mers with a spaced seed like 1110110111, of the "
+        "bases at the 1's of each window, so that a mismatch at a 0 doesn't "
+        "change them. The k-mer lists must be made with the same pattern, e.g., "
+        "by build-hapmers --seed-pattern, and binary lists are checked against "
+        "it. Can't be combined with --fuzzy, --sampling minimizer or --score "
+        "weighted.",
+    )
+    parser.add_argument(
+        "--ignore-softmasked",
+        action="store_true",
+        default=False,
+        help="skip k-mers with lowercase (soft-masked) bases in the reads, like "
+        "k-mers with N's, instead of treating lowercase like uppercase",
+    )
+    parser.add_argument(
+        "--mask-adapters",
+        metavar="FASTA",
+        help="mask the adapters in a fasta file with N's where they're found in "
+        "the reads, exactly or with up to --adapter-mismatches mismatches and in "
+        "either orientation, before counting k-mers, so that leftover adapter "
+        "sequence neither hits nor counts as k-mers looked up. The reads written "
+        "aren't masked.",
+    )
+    parser.add_argument(
+        "--adapter-search-len",
+        type=int,
+        metavar="N",
+        help="with --mask-adapters, only look for adapters overlapping the first "
+        "or last N bases of each read, or with 0, anywhere. Default is "
+        f"{adapters.DEFAULT_SEARCH_LENGTH}.",
+    )
+    parser.add_argument(
+        "--adapter-mismatches",
+        type=int,
+        choices=[0, 1],
+        help="with --mask-adapters, the most mismatches an adapter can be found "
+        "with. Default is 1.",
+    )
+    parser.add_argument(
+        "--subtract-shared",
+        action="store_true",
+        default=False,
+        help="remove k-mers found in both k-mer lists before classifying, e.g., "
+        "if the lists are all the k-mers in each parent rather than only the "
+        "k-mers unique to each",
+    )
+    parser.add_argument(
+        "--lookup",
+        choices=["hash", "sorted"],
+        default="hash",
+        help="how to store the k-mers: in hash sets, or in sorted arrays, which "
+        "take two thirds of the memory but are slower to search",
+    )
+    parser.add_argument(
+        "--bloom",
+        action="store_true",
+        default=False,
+        help="store the k-mers in Bloom filters, which take much less memory than "
+        "exact sets but occasionally find k-mers that aren't there",
+    )
+    parser.add_argument(
+        "--bloom-fpr",
+        type=float,
+        default=0.001,
+        help="false positive rate of the Bloom filters with --bloom",
+    )
+    parser.add_argument(
+        "--save-kmers",
+        metavar="PREFIX",
+        help="save the k-mer lists in binary format to PREFIX.hapA.kmers and "
+        "PREFIX.hapB.kmers, for fast reloading with --load-kmers",
+    )
+    parser.add_argument(
+        "--load-kmers",
+        action="store_true",
+        default=False,
+        help="the k-mer lists are binary files written with --save-kmers",
+    )
+    parser.add_argument(
+        "--mmap",
+        action="store_true",
+        default=False,
+        help="with --load-kmers, memory-map the binary files and search them "
+        "where they are instead of reading them in, which takes no time and "
+        "shares the k-mers' memory between runs on the same machine",
+    )
+    parser.add_argument(
+        "-t",
+        "--threads",
+        type=int,
+        default=1,
+        help="number of threads to classify reads with",
+    )
+    parser.add_argument(
+        "--no-keep-order",
+        action="store_true",
+        default=False,
+        help="with multiple threads, write reads out as soon as they are "
+        "classified instead of in input order",
+    )
+    parser.add_argument(
+        "--batch-size",
+        type=int,
+        default=1000,
+        help="with multiple threads, number of reads (or read pairs) to hand to "
+        "a thread at a time",
+    )
+    parser.add_argument(
+        "--queue-depth",
+        type=int,
+        help="with multiple threads, the most batches to hold in memory at once, "
+        "which bounds memory use when reads are read faster than they're "
+        "written. Default is two per thread.",
+    )
+    parser.add_argument(
+        "--min-length",
+        type=int,
+        help="filter out reads shorter than this before classifying them",
+    )
+    parser.add_argument(
+        "--max-length",
+        type=int,
+        help="filter out reads longer than this before classifying them",
+    )
+    parser.add_argument(
+        "--min-mean-quality",
+        type=float,
+        help="filter out reads with a mean Phred+33 quality below this before "
+        "classifying them. The mean is of the bases' error probabilities, "
+        "not of the quality scores.",
+    )
+    parser.add_argument(
+        "--filtered-out-prefix",
+        default="filtered",
+        help="prefix for the output file of filtered-out reads. A read pair is "
+        "filtered out if either mate is.",
+    )
+    parser.add_argument(
+        "--discard-filtered",
+        action="store_true",
+        default=False,
+        help="only count the filtered-out reads instead of writing them out",
+    )
+    parser.add_argument(
+        "--skip-malformed",
+        action="store_true",
+        default=False,
+        help="skip malformed records in the reads with a warning, instead of "
+        "stopping with an error, and exit with status 3 at the end if any were "
+        "skipped",
+    )
+    seq.add_multiline_fastq_argument(parser)
+    parser.add_argument(
+        "--check-duplicates",
+        action="store_true",
+        default=False,
+        help="check for reads (or read pairs) with the same name as one before "
+        "them, across all the reads files, e.g., from concatenating a file twice. "
+        "This keeps a 64-bit hash of every name, about 70 bytes per read.",
+    )
+    parser.add_argument(
+        "--on-duplicate",
+        choices=["warn", "error", "skip"],
+        default="warn",
+        help="with --check-duplicates, what to do with a duplicate read: warn and "
+        "classify it anyway, stop with an error, or warn and leave it out",
+    )
+    parser.add_argument(
+        "--subsample",
+        metavar="FRACTION",
+        type=float,
+        help="classify only about this fraction of the reads (or read pairs), "
+        "picked by hashing their names, so the same ones are picked every time",
+    )
+    parser.add_argument(
+        "--seed",
+        type=int,
+        default=0,
+        help="with --subsample, pick a different subsample for each seed, any "
+        "64-bit integer",
+    )
+    parser.add_argument(
+        "--report",
+        metavar="FILE",
+        help="write a tab-separated report with the length, k-mer counts, and bin "
+        "of each read to FILE, gzipped if it ends in .gz, or to stdout if it is - "
+        "(replacing the usual output there)",
+    )
+    parser.add_argument(
+        "--summary-json",
+        metavar="FILE",
+        help="write the number of reads and bases and the mean and median scores "
+        "of each bin to FILE in JSON format",
+    )
+    parser.add_argument(
+        "--checkpoint",
+        metavar="FILE",
+        help="save how far the run has got to FILE every --checkpoint-interval, "
+        "so that it can be resumed with --resume if it's stopped. The lines "
+        "printed to stdout for each read can't be taken back on resuming, so use "
+        "--report instead of stdout for those.",
+    )
+    parser.add_argument(
+        "--checkpoint-interval",
+        default="10m",
+        metavar="DURATION",
+        help="how often to save a checkpoint, e.g., 30s, 10m or 2h",
+    )
+    parser.add_argument(
+        "--resume",
+        action="store_true",
+        default=False,
+        help="carry on from the --checkpoint file, if there is one, with the same "
+        "reads and options. The output files are cut back to where they were at "
+        "the checkpoint and added to. The checkpoint file is removed once the run "
+        "is done.",
+    )
+    parser.add_argument(
+        "--progress",
+        action="store_true",
+        default=False,
+        help="print the number of reads classified so far, the throughput and "
+        "the reads in each bin to stderr every second. This is on by default "
+        "when stderr is a terminal, unless --quiet is given.",
+    )
+    parser.add_argument(
+        "-q",
+        "--quiet",
+        action="store_true",
+        default=False,
+        help="don't print the table of reads and bases in each bin to stderr at "
+        "the end",
     )
     parser.add_argument(
-        "haplotype_a_kmers",
-        type=kmers.create_kmer_hash_set,
-        help="a list of k-mers unique to haplotype A, one per line",
+        "--names-only",
+        action="store_true",
+        default=False,
+        help="write only the names of the reads in each bin, one per line, to "
+        "the output prefixes plus .txt (uncompressed), e.g., for samtools view "
+        "-N, instead of writing the reads themselves",
     )
     parser.add_argument(
-        "haplotype_b_kmers",
-        type=kmers.create_kmer_hash_set,
-        help="a list of k-mers unique to haplotype B, one per line",
+        "--tag-output",
+        metavar="FILE",
+        help="instead of splitting the reads into files, copy them from a SAM or "
+        "BAM reads file to FILE with HP:i:1 or HP:i:2 set on those in haplotype "
+        "A or B, and their hap-mer hits in hA:i and hB:i. Reads that aren't "
+        "classified, e.g., filtered out ones, are copied without the tags. FILE "
+        "is SAM if it ends in .sam or is - for stdout, or BAM otherwise, which "
+        "needs pysam.",
     )
     parser.add_argument(
         "--haplotype-a-out-prefix",
@@ -45,76 +599,1273 @@ def parse_args():
         default="unclassified",
         help="prefix for unclassified output file",
     )
+    parser.add_argument(
+        "--output-format",
+        type=seq.OutputFormat,
+        choices=list(seq.OutputFormat),
+        default=seq.OutputFormat.AUTO,
+        help="format to write the reads in. auto writes every output file as "
+        "fastq, with quality strings unchanged, if the first read of any reads "
+        "file has qualities, giving reads without them --fake-quality, or as "
+        "fasta otherwise. fasta drops the qualities, and fastq gives reads "
+        "without qualities --fake-quality.",
+    )
+    parser.add_argument(
+        "--fake-quality",
+        type=int,
+        help="with fastq output, the Phred quality to give each base of "
+        f"reads without qualities. Default is {seq.DEFAULT_FAKE_QUALITY}.",
+    )
     parser.add_argument(
         "--no-gzip-output",
         action="store_true",
         help="don't gzip the output",
         default=False,
     )
-    return parser.parse_args()
+    parser.add_argument(
+        "--compression-level",
+        type=int,
+        default=4,
+        help="gzip compression level of the output, from 1 (fastest) to 9 "
+        "(smallest)",
+    )
+    parser.add_argument(
+        "--bgzf",
+        action="store_true",
+        default=False,
+        help="gzip the output as BGZF, as bgzip does, which samtools faidx and "
+        "other tools can index. A --report ending in .bgz is BGZF either way.",
+    )
+    parser.add_argument(
+        "--compression-threads",
+        type=int,
+        default=1,
+        help="with --bgzf, the number of threads to compress each output file "
+        "with, on top of --threads, for when compressing is slower than "
+        "classifying",
+    )
+    parser.add_argument(
+        "--config",
+        metavar="FILE",
+        help="a TOML file of options, with a key for each option named like it "
+        "with underscores, e.g., min_hits = 3, and haplotype_a_kmers, "
+        "haplotype_b_kmers and reads = [...] for the positional arguments. "
+        "Options given on the command line override it.",
+    )
+    parser.add_argument(
+        "--print-config",
+        action="store_true",
+        help="print every option, after --config and the command line, as a "
+        "config file, and exit without classifying anything",
+    )
+    log.add_verbose_argument(parser)
+
+    if config_path is not None:
+        if config_error is not None:
+            parser.error(f"--config: {config_error}")
+        if len(kmer_lists) == 1:
+            parser.error(
+                "--config must give both haplotype_a_kmers and haplotype_b_kmers, "
+                "or neither"
+            )
+        for name, value in kmer_lists.items():
+            if not isinstance(value, str):
+                parser.error(f"{name} in config must be a string, not {value!r}")
+        try:
+            parser.set_defaults(
+                **config.config_defaults(parser, run_config), **kmer_lists
+            )
+        except config.ConfigError as e:
+            parser.error(f"--config: {e}")
+    args = parser.parse_args()
+    if args.config != config_path:
+        parser.error("give --config in full, not abbreviated")
+    # the arguments as they were parsed, for --print-config
+    parsed_args = argparse.Namespace(**vars(args))
+
+    if not 1 <= args.compression_level <= 9:
+        parser.error("--compression-level must be between 1 and 9")
+    if args.compression_threads < 1:
+        parser.error("--compression-threads must be at least 1")
+    if args.compression_threads > 1 and not args.bgzf:
+        parser.error("--compression-threads needs --bgzf")
+    if args.kmer_size is not None and not kmers.MIN_K <= args.kmer_size <= kmers.MAX_K:
+        parser.error(f"-k must be between {kmers.MIN_K} and {kmers.MAX_K}")
+    if args.seed_pattern is not None:
+        try:
+            seed = kmers.SpacedSeed(args.seed_pattern)
+        except ValueError as e:
+            parser.error(f"--seed-pattern: {e}")
+        if args.kmer_size is not None and args.kmer_size != seed.weight:
+            parser.error(
+                f"-k must be the number of 1's in --seed-pattern, {seed.weight}"
+            )
+    if args.sampling is None:
+        args.sampling = "all" if args.minimizer_window is None else "minimizer"
+    if args.sampling == "minimizer" and args.minimizer_window is None:
+        args.minimizer_window = DEFAULT_MINIMIZER_WINDOW
+    elif args.sampling != "minimizer" and args.minimizer_window is not None:
+        parser.error("--minimizer-window needs --sampling minimizer")
+    if args.sampling == "syncmer" and args.syncmer_size is None:
+        args.syncmer_size = DEFAULT_SYNCMER_SIZE
+    elif args.sampling != "syncmer" and args.syncmer_size is not None:
+        parser.error("--syncmer-size needs --sampling syncmer")
+    if args.bloom:
+        for option, value in [
+            ("--load-kmers", args.load_kmers),
+            ("--save-kmers", args.save_kmers),
+            ("--subtract-shared", args.subtract_shared),
+            ("--lookup sorted", args.lookup == "sorted"),
+        ]:
+            if value:
+                parser.error(f"{option} cannot be used with --bloom")
+        if not 0 < args.bloom_fpr < 1:
+            parser.error("--bloom-fpr must be between 0 and 1")
+    if args.mmap:
+        if not args.load_kmers:
+            parser.error("--mmap needs --load-kmers")
+        if args.subtract_shared:
+            parser.error("--subtract-shared cannot be used with --mmap")
+    if args.score == ScoreMode.WEIGHTED:
+        # the counts are only kept in hash sets read from text lists
+        for option, value in [
+            ("--bloom", args.bloom),
+            ("--load-kmers", args.load_kmers),
+            ("--save-kmers", args.save_kmers),
+            ("--subtract-shared", args.subtract_shared),
+            ("--lookup sorted", args.lookup == "sorted"),
+        ]:
+            if value:
+                parser.error(f"{option} cannot be used with --score weighted")
+
+    if args.reads_fofn is not None:
+        try:
+            args.reads = args.reads + read_fofn(args.reads_fofn)
+        except OSError as e:
+            parser.error(f"could not read --reads-fofn: {e}")
+
+    paired = args.reads_1 is not None or args.reads_2 is not None
+    if (args.reads_1 is None) != (args.reads_2 is None):
+        parser.error("--reads-1 and --reads-2 must be given together")
+    if [bool(args.reads), paired, args.interleaved is not None].count(True) != 1:
+        parser.error(
+            "give exactly one of reads, --reads-1 and --reads-2, or --interleaved"
+        )
+    for option, value in [
+        ("--min-length", args.min_length),
+        ("--max-length", args.max_length),
+        ("--min-mean-quality", args.min_mean_quality),
+    ]:
+        if value is not None and value < 0:
+            parser.error(f"{option} can't be negative")
+    if args.subsample is not None and not 0 < args.subsample <= 1:
+        parser.error("--subsample must be more than 0 and at most 1")
+    if not -(2**63) <= args.seed < 2**63:
+        parser.error(f"--seed must be a 64-bit integer, not {args.seed}")
+
+    if [args.reads_1, args.reads_2, *args.reads].count("-") > 1:
+        parser.error("only one reads file can be read from stdin")
+    if args.tag_output is not None:
+        if len(args.reads) != 1 or args.reads[0] == "-":
+            parser.error("--tag-output needs a single reads file, not stdin")
+        if args.names_only:
+            parser.error("--tag-output cannot be used with --names-only")
+    if args.output_format != seq.OutputFormat.AUTO:
+        for option, value in [
+            ("--names-only", args.names_only),
+            ("--tag-output", args.tag_output is not None),
+        ]:
+            if value:
+                parser.error(f"--output-format cannot be used with {option}")
+    if args.fake_quality is None:
+        args.fake_quality = seq.DEFAULT_FAKE_QUALITY
+    elif args.output_format == seq.OutputFormat.FASTA:
+        parser.error("--fake-quality needs fastq output, not --output-format fasta")
+    elif not 0 <= args.fake_quality <= 93:
+        parser.error(f"--fake-quality must be from 0 to 93, not {args.fake_quality}")
+
+    if args.mask_adapters is None:
+        for option, value in [
+            ("--adapter-search-len", args.adapter_search_len),
+            ("--adapter-mismatches", args.adapter_mismatches),
+        ]:
+            if value is not None:
+                parser.error(f"{option} is only used with --mask-adapters")
+    if args.adapter_search_len is None:
+        args.adapter_search_len = adapters.DEFAULT_SEARCH_LENGTH
+    elif args.adapter_search_len < 0:
+        parser.error("--adapter-search-len can't be negative")
+    if args.adapter_mismatches is None:
+        args.adapter_mismatches = 1
+
+    if args.checkpoint is None:
+        if args.resume:
+            parser.error("--resume needs --checkpoint")
+    else:
+        try:
+            args.checkpoint_interval = checkpoint.parse_duration(
+                args.checkpoint_interval
+            )
+        except ValueError as e:
+            parser.error(f"--checkpoint-interval: {e}")
+        if "-" in [args.reads_1, args.reads_2, args.interleaved, *args.reads]:
+            parser.error("--checkpoint can't be used with reads from stdin")
+        for option, value in [
+            ("--tag-output", args.tag_output is not None),
+            ("--report -", args.report == "-"),
+        ]:
+            if value:
+                parser.error(f"--checkpoint can't be used with {option}")
+
+    if args.print_config:
+        print(config.format_config(parsed_args), end="")
+        sys.exit(0)
+    return args
+
+
+def read_fofn(fofn_path: str) -> List[str]:
+    """Read a file of filenames, skipping blank lines and # comments"""
+    with open(fofn_path) as fofn:
+        lines = (line.strip() for line in fofn)
+        return [line for line in lines if line and not line.startswith("#")]
+
+
+def check_reads_files_exist(args: argparse.Namespace):
+    """Exit with a list of every reads file that doesn't exist, if any
+
+    This is done before anything else so that a missing file doesn't
+    stop the run hours in.
+    """
+    reads_paths = [*args.reads, args.reads_1, args.reads_2, args.interleaved]
+    missing = [
+        reads_path
+        for reads_path in reads_paths
+        if reads_path is not None and reads_path != "-" and not path.isfile(reads_path)
+    ]
+    if missing:
+        sys.exit(
+            "Error: these reads files don't exist:\n"
+            + "\n".join(f"  {reads_path}" for reads_path in missing)
+        )
+
+
+def load_kmer_set(
+    kmer_file_path: str,
+    k: Optional[int],
+    min_count: Optional[int],
+    dump_format: kmers.DumpFormat,
+    binary: bool = False,
+    counts: bool = False,
+) -> kmers.KmerSet:
+    """Load a list of k-mers, exiting with an error message on failure. With
+    `counts`, the list's counts are kept in a `kmers.KmerCountMap`."""
+    logger.info("Reading k-mers in %s", kmer_file_path)
+    try:
+        if binary:
+            with open(kmer_file_path, "rb") as kmer_file:
+                kmer_set = kmers.KmerSet.read_binary(kmer_file)
+            _check_binary_k(kmer_file_path, kmer_set, k)
+        else:
+            kmer_set_class = kmers.KmerCountMap if counts else kmers.KmerSet
+            kmer_set = kmer_set_class.from_file(
+                kmer_file_path, k, min_count, dump_format
+            )
+    except (OSError, kmers.KmerError) as e:
+        sys.exit(f"Error reading k-mers: {e}")
+    kmer_set.path = kmer_file_path
+    logger.info("Loaded %d k-mers with k=%d", len(kmer_set), kmer_set.k)
+    warn_if_k_too_short(kmer_set)
+    return kmer_set
+
+
+def load_sorted_kmer_set(
+    kmer_file_path: str,
+    k: Optional[int],
+    min_count: Optional[int],
+    dump_format: kmers.DumpFormat,
+    binary: bool = False,
+) -> kmers.SortedKmerSet:
+    """Load a list of k-mers into a sorted set, exiting with an error
+    message on failure. Binary files are read straight into the sorted set,
+    and text lists go through a hash set first."""
+    if not binary:
+        kmer_set = load_kmer_set(kmer_file_path, k, min_count, dump_format)
+        sorted_set = kmer_set.into_sorted()
+        sorted_set.path = kmer_file_path
+        return sorted_set
+
+    logger.info("Reading k-mers in %s", kmer_file_path)
+    try:
+        with open(kmer_file_path, "rb") as kmer_file:
+            sorted_set = kmers.SortedKmerSet.read_binary(kmer_file)
+        _check_binary_k(kmer_file_path, sorted_set, k)
+    except (OSError, kmers.KmerError) as e:
+        sys.exit(f"Error reading k-mers: {e}")
+    sorted_set.path = kmer_file_path
+    logger.info("Loaded %d k-mers with k=%d", len(sorted_set), sorted_set.k)
+    warn_if_k_too_short(sorted_set)
+    return sorted_set
+
+
+def load_mmap_kmer_set(
+    kmer_file_path: str, k: Optional[int], *_: Any
+) -> kmers.MmapKmerSet:
+    """Memory-map a binary k-mer set, exiting with an error message on
+    failure. The arguments after k are those of `load_kmer_set`, which
+    don't apply to binary files."""
+    logger.info("Mapping k-mers in %s", kmer_file_path)
+    try:
+        mapped_set = kmers.MmapKmerSet(kmer_file_path)
+        _check_binary_k(kmer_file_path, mapped_set, k)
+    except (OSError, kmers.KmerError) as e:
+        sys.exit(f"Error reading k-mers: {e}")
+    logger.info("Mapped %d k-mers with k=%d", len(mapped_set), mapped_set.k)
+    warn_if_k_too_short(mapped_set)
+    return mapped_set
+
+
+def _check_binary_k(kmer_file_path: str, lookup: kmers.KmerLookup, k: Optional[int]):
+    if k is not None and lookup.k != k:
+        raise kmers.KmerFileError(
+            kmer_file_path, None, f"the k-mers are {lookup.k}-mers, but -k is {k}"
+        )
+
+
+def warn_if_k_too_short(lookup: kmers.KmerLookup):
+    """Warn about k-mer lists too short to classify with reliably, which -k
+    doesn't allow but the lists themselves can still have"""
+    if lookup.k < kmers.MIN_K:
+        logger.warning(
+            "The k-mers in %s are %d-mers, which turn up by chance in too many "
+            "reads to tell the haplotypes apart; k should be at least %d",
+            lookup.path,
+            lookup.k,
+            kmers.MIN_K,
+        )
+
+
+ExactKmerSet = Union[kmers.KmerSet, kmers.SortedKmerSet, kmers.MmapKmerSet]
+
+
+def load_kmer_sets(args: argparse.Namespace) -> Tuple[ExactKmerSet, ExactKmerSet]:
+    """Load the k-mer sets for both haplotypes as given on the command line,
+    subtracting and saving them and making them sorted if asked to"""
+    load: Callable[..., ExactKmerSet] = load_kmer_set
+    if args.mmap:
+        load = load_mmap_kmer_set
+    elif args.lookup == "sorted" and not args.subtract_shared:
+        # subtracting needs hash sets, so they are sorted afterwards instead
+        load = load_sorted_kmer_set
+    elif args.score == ScoreMode.WEIGHTED:
+        load = functools.partial(load_kmer_set, counts=True)
+    haplotype_a_kmers = load(
+        args.haplotype_a_kmers,
+        args.kmer_size,
+        args.min_count,
+        args.kmer_format,
+        args.load_kmers,
+    )
+    haplotype_b_kmers = load(
+        args.haplotype_b_kmers,
+        args.kmer_size,
+        args.min_count,
+        args.kmer_format,
+        args.load_kmers,
+    )
+    try:
+        kmers.check_same_k(haplotype_a_kmers, haplotype_b_kmers)
+    except kmers.KmerSizeMismatchError as e:
+        sys.exit(f"Error: {e}")
+    for kmer_set, kmer_file_path in [
+        (haplotype_a_kmers, args.haplotype_a_kmers),
+        (haplotype_b_kmers, args.haplotype_b_kmers),
+    ]:
+        if not args.load_kmers:
+            # text lists don't say, so take the command line's word for it
+            kmer_set.homopolymer_compressed = args.compress_homopolymers
+        elif kmer_set.homopolymer_compressed and not args.compress_homopolymers:
+            sys.exit(
+                f"Error: the k-mers in {kmer_file_path} are from "
+                "homopolymer-compressed sequence, so --compress-homopolymers is "
+                "needed"
+            )
+        elif args.compress_homopolymers and not kmer_set.homopolymer_compressed:
+            sys.exit(
+                f"Error: the k-mers in {kmer_file_path} are not from "
+                "homopolymer-compressed sequence, so --compress-homopolymers "
+                "can't be used"
+            )
+        check_seed_pattern(kmer_set, kmer_file_path, args)
+
+    if args.subtract_shared:
+        assert isinstance(haplotype_a_kmers, kmers.KmerSet)
+        assert isinstance(haplotype_b_kmers, kmers.KmerSet)
+        haplotype_a_only_kmers = haplotype_a_kmers.difference(haplotype_b_kmers)
+        haplotype_b_only_kmers = haplotype_b_kmers.difference(haplotype_a_kmers)
+        num_shared = len(haplotype_a_kmers) - len(haplotype_a_only_kmers)
+        logger.info("Removed %d k-mers found in both haplotypes", num_shared)
+        for name, only_kmers in [
+            ("A", haplotype_a_only_kmers),
+            ("B", haplotype_b_only_kmers),
+        ]:
+            if not len(only_kmers):
+                sys.exit(
+                    f"Error: no k-mers left in haplotype {name} after removing "
+                    f"the {num_shared} shared ones"
+                )
+        if args.lookup == "sorted":
+            haplotype_a_kmers = haplotype_a_only_kmers.into_sorted()
+            haplotype_b_kmers = haplotype_b_only_kmers.into_sorted()
+        else:
+            haplotype_a_kmers = haplotype_a_only_kmers
+            haplotype_b_kmers = haplotype_b_only_kmers
+
+    if args.save_kmers is not None:
+        for kmer_set, hap in [(haplotype_a_kmers, "hapA"), (haplotype_b_kmers, "hapB")]:
+            with open(f"{args.save_kmers}.{hap}.kmers", "wb") as kmer_file:
+                kmer_set.write_binary(kmer_file)
+
+    return haplotype_a_kmers, haplotype_b_kmers
+
+
+def check_seed_pattern(
+    kmer_set: ExactKmerSet, kmer_file_path: str, args: argparse.Namespace
+):
+    """Check that a k-mer set was made with the spaced seed given by
+    --seed-pattern, if any, exiting with an error message if not. Text
+    lists don't record one, so they're taken to have been."""
+    seed = None if args.seed_pattern is None else kmers.SpacedSeed(args.seed_pattern)
+    if not args.load_kmers:
+        kmer_set.seed = seed
+    elif kmer_set.seed is not None and seed is None:
+        sys.exit(
+            f"Error: the k-mers in {kmer_file_path} are made with the seed "
+            f"pattern {kmer_set.seed}, so --seed-pattern {kmer_set.seed} is needed"
+        )
+    elif kmer_set.seed != seed:
+        made_with = (
+            "contiguous k-mers"
+            if kmer_set.seed is None
+            else f"made with the seed pattern {kmer_set.seed}"
+        )
+        sys.exit(
+            f"Error: the k-mers in {kmer_file_path} are {made_with}, not with "
+            f"--seed-pattern {seed}"
+        )
+    if seed is not None and kmer_set.k != seed.weight:
+        sys.exit(
+            f"Error: the k-mers in {kmer_file_path} are {kmer_set.k}-mers, but "
+            f"--seed-pattern {seed} makes {seed.weight}-mers"
+        )
+
+
+def load_kmer_bloom(
+    kmer_file_path: str,
+    k: Optional[int],
+    min_count: Optional[int],
+    dump_format: kmers.DumpFormat,
+    fpr: float,
+) -> kmers.KmerBloom:
+    """Load a list of k-mers into a Bloom filter, exiting with an error
+    message on failure"""
+    logger.info("Reading k-mers in %s into a Bloom filter", kmer_file_path)
+    try:
+        bloom = kmers.KmerBloom.from_file(
+            kmer_file_path, fpr, k, min_count, dump_format
+        )
+    except (OSError, kmers.KmerError) as e:
+        sys.exit(f"Error reading k-mers: {e}")
+    logger.info(
+        "Loaded about %d k-mers with k=%d into a filter of %d bytes",
+        len(bloom),
+        bloom.k,
+        bloom.num_bits // 8,
+    )
+    warn_if_k_too_short(bloom)
+    return bloom
 
 
-def calculate_scaling_factors(
-    haplotype_a_kmers: kmers.HashSet, haplotype_b_kmers: kmers.HashSet
-) -> Tuple[float, float]:
-    """Calculate the scaling factors for k-mer scores
+def warn_if_rna(reads: Iterator[seq.Read], reads_path: str) -> Iterator[seq.Read]:
+    """Pass reads on, warning if the first one looks like RNA"""
+    first_read = next(reads, None)
+    if first_read is None:
+        return
+    if first_read.is_rna():
+        logger.warning(
+            "The first read in %s looks like RNA, with U's rather than T's, whose "
+            "k-mers will be skipped; use --rna to read them as T's",
+            reads_path,
+        )
+    yield first_read
+    yield from reads
+
+
+def open_reads(
+    reads_path: str,
+    need_qualities: bool,
+    on_error: Optional[Callable[[seq.SeqError], None]] = None,
+    quality_encoding: seq.QualityEncoding = seq.QualityEncoding.AUTO,
+    rna: bool = False,
+    multiline_fastq: bool = False,
+) -> Iterator[seq.Read]:
+    """Open a reads file of any format, converting fastq qualities from
+    `quality_encoding` to Phred+33, checking its qualities if they will be
+    used, and skipping malformed records if `on_error` is given (see
+    `seq.open_records`, which `multiline_fastq` is passed to as well)
+
+    With `rna`, the reads' U's are turned into T's. Otherwise there's a
+    warning if the first read looks like RNA, since its U's would be
+    skipped like N's.
+    """
+    logger.info("Reading reads from %s", reads_path)
+    reads = seq.open_records(reads_path, on_error, quality_encoding, multiline_fastq)
+    if rna:
+        reads = (read.to_dna() for read in reads)
+    else:
+        reads = warn_if_rna(reads, reads_path)
+    if need_qualities:
+        # qualities given as Phred+33 are taken to be, however they look
+        num_reads = 0 if quality_encoding == seq.QualityEncoding.PHRED33 else 1000
+        reads = seq.check_qualities(reads, num_reads)
+    return reads
+
+
+FASTQ_EXTENSIONS = (".fq", ".fastq")
+"""The extensions of fastq files, which are kept for fastq output"""
+
+FASTA_EXTENSIONS = (".fa", ".fasta", ".fna")
+"""The extensions of fasta files, which are kept for fasta output"""
+
+
+def pick_output_format(
+    reads: Iterator[seq.Read], other_paths: Sequence[str], multiline_fastq: bool = False
+) -> Tuple[seq.OutputFormat, Iterator[seq.Read]]:
+    """Pick the one format all the output files are written in for
+    --output-format auto
+
+    This is fastq if the first read of any of the reads files has
+    qualities, giving reads without them --fake-quality, or fasta
+    otherwise, so that fasta and fastq inputs together don't make output
+    files with records of both.
 
     Args:
-        haplotype_a_kmers: set of k-mers in haplotype A
-        haplotype_b_kmers: set of k-mers in haplotype B
+        reads: the reads of the first reads file
+        other_paths: the other reads files, whose first reads are read
+            to see if they have qualities, if the first file's doesn't
+        multiline_fastq: passed to `seq.open_records` for the other files
 
     Returns:
-        scaling_factor_a: scaling factor by which haplotype A counts
-            should be multiplied
-        scaling_factor_b: scaling factor by which haplotype B counts
-            should be multiplied
+        the format, and the reads, including the first one
+
+    Raises:
+        OSError, seq.SeqError: if another reads file can't be read
     """
-    num_kmers_a = kmers.get_number_kmers_in_set(haplotype_a_kmers)
-    num_kmers_b = kmers.get_number_kmers_in_set(haplotype_b_kmers)
-    max_num_kmers = max(num_kmers_a, num_kmers_b)
-    scaling_factor_a = 1.0 * max_num_kmers / num_kmers_a
-    scaling_factor_b = 1.0 * max_num_kmers / num_kmers_b
-    return scaling_factor_a, scaling_factor_b
+    first_read = next(reads, None)
+    if first_read is not None:
+        reads = chain([first_read], reads)
+    if first_read is not None and first_read.qual is not None:
+        return seq.OutputFormat.FASTQ, reads
+    for other_path in other_paths:
+        other_reads = seq.open_records(other_path, multiline_fastq=multiline_fastq)
+        try:
+            other_first = next(other_reads, None)
+        finally:
+            other_reads.close()
+        if other_first is not None and other_first.qual is not None:
+            return seq.OutputFormat.FASTQ, reads
+    return seq.OutputFormat.FASTA, reads
 
 
+def open_bin_outfiles(
+    args: argparse.Namespace,
+    outfile_extension: str,
+    paired_suffix: str = "",
+    gzip_output: Optional[bool] = None,
+    append: bool = False,
+) -> Dict[Bin, seq.TextOrGzip]:
+    """Open an output file for each bin
+
+    Args:
+        args: the parsed command-line arguments
+        outfile_extension: extension for output files (e.g., ".fa")
+        paired_suffix: added to each prefix, e.g., "_R1" for the output
+            files of the first mates of read pairs
+        gzip_output: whether to gzip the files. By default, they are
+            gzipped unless --no-gzip-output was given.
+        append: whether to add to the files, when resuming from a
+            checkpoint
+    """
+    if gzip_output is None:
+        gzip_output = not args.no_gzip_output
+    haplotype_a_outfile, haplotype_b_outfile, unclassified_outfile = seq.open_outfiles(
+        args.haplotype_a_out_prefix + paired_suffix,
+        args.haplotype_b_out_prefix + paired_suffix,
+        args.unclassified_out_prefix + paired_suffix,
+        outfile_extension,
+        gzip_output,
+        args.compression_level,
+        append,
+        args.bgzf,
+        args.compression_threads,
+    )
+    return {
+        Bin.HAP_A: haplotype_a_outfile,
+        Bin.HAP_B: haplotype_b_outfile,
+        Bin.UNKNOWN: unclassified_outfile,
+    }
+
+
+REPORT_COLUMNS = [
+    "read_id",
+    "length",
+    "hap_a_hits",
+    "hap_b_hits",
+    "hap_a_fraction",
+    "hap_b_fraction",
+    "hap_a_per_kb",
+    "hap_b_per_kb",
+    "skipped_fraction",
+    "bin",
+    "rescued",
+    "early_exit",
+    "file",
+]
+"""The columns of the per-read report. The haplotype fractions are the
+number of hits divided by the number of k-mers looked up in the read,
+the per-kb columns are the number of hits per thousand k-mers looked up,
+and the skipped fraction is the fraction of k-mers in the read that
+were skipped because they contain an N or other non-ACGT character, or
+a base below --min-base-quality. Rescued is 1 for reads put into their
+bin by --rescue and 0 otherwise, and early exit is 1 for reads whose
+k-mers --early-exit stopped counting partway through, so that their hits
+and fractions are only those of the part counted. The file is the reads file the read
+came from, or the first mates' file for read pairs."""
+
+EXIT_WITH_WARNINGS = 3
+"""The exit status when the reads were all classified but malformed records
+were skipped with --skip-malformed"""
+
+HAPLOTYPE_TAGS = {Bin.HAP_A: 1, Bin.HAP_B: 2}
+"""The value of the HP tag of the reads in each bin with --tag-output.
+Unclassified reads have no HP tag."""
+
+
+T = TypeVar("T")
+
+
+def subsample(
+    items: Iterable[T],
+    name: Callable[[T], str],
+    num_reads: int,
+    args: argparse.Namespace,
+    summary: Summary,
+) -> Iterator[T]:
+    """Keep only the reads (or pairs) in the subsample, if there is one
+
+    Args:
+        items: the reads or pairs
+        name: gets the name to pick each item by
+        num_reads: the number of reads in each item
+        args: the parsed command-line arguments
+        summary: where the items left out are counted
+    """
+    if args.subsample is None:
+        yield from items
+        return
+    for item in items:
+        if seq.in_subsample(name(item), args.subsample, args.seed):
+            yield item
+        else:
+            summary.skip(num_reads)
+
+
+def check_duplicates(
+    items: Iterable[T],
+    name: Callable[[T], str],
+    num_reads: int,
+    seen: Optional[seq.IdSet],
+    args: argparse.Namespace,
+    summary: Summary,
+) -> Iterator[T]:
+    """Find the reads (or pairs) with a name seen before, with
+    --check-duplicates, and deal with them as --on-duplicate says
+
+    Args:
+        items: the reads or pairs
+        name: gets the name of each item
+        num_reads: the number of reads in each item
+        seen: the names seen so far, shared between the reads files, or
+            None to not check
+        args: the parsed command-line arguments
+        summary: where the duplicates are counted
+
+    Raises:
+        SeqError: for a duplicate with --on-duplicate error
+    """
+    if seen is None:
+        yield from items
+        return
+    for item in items:
+        item_name = name(item)
+        if seen.add(item_name):
+            yield item
+            continue
+        if args.on_duplicate == "error":
+            raise seq.SeqError(f"read {item_name} is in the reads more than once")
+        logger.warning("Read %s is in the reads more than once", item_name)
+        summary.duplicates += num_reads
+        if args.on_duplicate == "skip":
+            summary.skip(num_reads)
+        else:
+            yield item
+
+
+def has_read_filters(args: argparse.Namespace) -> bool:
+    """Whether any of the length and quality filters were given"""
+    return (
+        args.min_length is not None
+        or args.max_length is not None
+        or args.min_mean_quality is not None
+    )
+
+
+def filter_reason(read: seq.Read, args: argparse.Namespace) -> Optional[str]:
+    """Why a read is filtered out, e.g., "too_short", or None if it isn't"""
+    if args.min_length is not None and len(read.seq) < args.min_length:
+        return "too_short"
+    if args.max_length is not None and len(read.seq) > args.max_length:
+        return "too_long"
+    if (
+        args.min_mean_quality is not None
+        and read.qual is not None
+        and seq.mean_quality(read.qual) < args.min_mean_quality
+    ):
+        return "low_quality"
+    return None
+
+
+def filter_reads(
+    items: Iterable[T],
+    name: Callable[[T], str],
+    mates: Callable[[T], Sequence[seq.Read]],
+    args: argparse.Namespace,
+    summary: Summary,
+    write_filtered: Callable[[str, Sequence[seq.Read]], None],
+) -> Iterator[T]:
+    """Leave out the reads (or pairs) that don't pass the read filters
+
+    A pair is left out if either of its mates doesn't pass.
+
+    Args:
+        items: the reads or pairs
+        name: gets the name of each item
+        mates: gets the reads in each item
+        args: the parsed command-line arguments
+        summary: where the items left out are counted
+        write_filtered: called with the name and reads of each item left
+            out
+    """
+    if not has_read_filters(args):
+        yield from items
+        return
+    for item in items:
+        item_mates = mates(item)
+        reasons = (filter_reason(read, args) for read in item_mates)
+        reason = next((reason for reason in reasons if reason is not None), None)
+        if reason is None:
+            yield item
+        else:
+            summary.filter(reason, len(item_mates))
+            write_filtered(name(item), item_mates)
+
+
+def write_report_line(
+    report: TextIO,
+    name: str,
+    length: int,
+    classification: Classification,
+    reads_path: str,
+):
+    """Write the line for one read (or read pair) to the report"""
+    print(
+        name,
+        length,
+        classification.hap_a_count,
+        classification.hap_b_count,
+        f"{classification.hap_a_fraction:.6g}",
+        f"{classification.hap_b_fraction:.6g}",
+        f"{classification.hap_a_per_kb:.6g}",
+        f"{classification.hap_b_per_kb:.6g}",
+        f"{classification.skipped_fraction:.6g}",
+        classification.bin.value,
+        int(classification.rescued),
+        int(classification.early_exit),
+        reads_path,
+        sep="\t",
+        file=report,
+    )
+
+
+def haplotype_tags(classification: Classification) -> Dict[str, Optional[int]]:
+    """The tags to set on a read with --tag-output, with None for no tag"""
+    return {
+        "HP": HAPLOTYPE_TAGS.get(classification.bin),
+        "hA": classification.hap_a_count,
+        "hB": classification.hap_b_count,
+    }
+
+
+def print_summary(args: argparse.Namespace, summary: Summary):
+    """Print the reads and bases in each bin, and how many were left out, to
+    stderr"""
+    summary.print_table()
+    if summary.filtered:
+        print(
+            "Filtered out before classifying: "
+            + ", ".join(
+                f"{num_reads} {reason.replace('_', ' ')}"
+                for reason, num_reads in summary.filtered.items()
+            ),
+            file=sys.stderr,
+        )
+    if args.check_duplicates:
+        print(f"Found {summary.duplicates} duplicate reads.", file=sys.stderr)
+    if args.subsample is not None or summary.filtered:
+        print(
+            f"Classified {summary.total_reads} of the {summary.reads_seen} reads seen.",
+            file=sys.stderr,
+        )
+    if args.rescue:
+        print(
+            f"Rescued {summary.rescued[Bin.HAP_A]} reads into haplotype A and "
+            f"{summary.rescued[Bin.HAP_B]} into haplotype B.",
+            file=sys.stderr,
+        )
+    if args.early_exit:
+        print(
+            f"Stopped counting early for {summary.early_exits} reads.",
+            file=sys.stderr,
+        )
+    if args.mask_adapters is not None:
+        print(f"Masked {summary.masked_bases} adapter bases.", file=sys.stderr)
+    if summary.malformed:
+        print(f"Skipped {summary.malformed} malformed records.", file=sys.stderr)
+
+
+@seq.exit_on_broken_pipe
 def main():
     """Main method of program"""
     args = parse_args()
+    log.configure(args.verbose)
+    check_reads_files_exist(args)
+    if args.threads < 1:
+        sys.exit(f"Error: --threads must be at least 1, not {args.threads}")
+    if args.batch_size < 1:
+        sys.exit(f"Error: --batch-size must be at least 1, not {args.batch_size}")
+    if args.queue_depth is not None and args.queue_depth < 1:
+        sys.exit(f"Error: --queue-depth must be at least 1, not {args.queue_depth}")
+    try:
+        options = ClassifyOptions(
+            args.min_hits,
+            args.min_margin,
+            args.min_ratio,
+            args.ignore_softmasked,
+            args.min_base_quality,
+            args.compress_homopolymers,
+            args.score,
+            args.weight_cap,
+            args.min_hits_per_kb,
+            args.fuzzy,
+            args.fuzzy_weight,
+            args.minimizer_window,
+            args.kmer_step,
+            args.early_exit_margin if args.early_exit else None,
+            args.early_exit_max_other,
+            args.seed_pattern,
+            args.syncmer_size,
+        )
+        rescue_options = None
+        if args.rescue:
+            rescue_options = replace(
+                options,
+                min_hits=args.rescue_min_hits,
+                min_margin=args.rescue_min_margin,
+                min_ratio=args.rescue_min_ratio,
+                min_hits_per_kb=0.0,
+            )
+    except ValueError as e:
+        sys.exit(f"Error: {e}")
 
-    reads = seq.open_fastx_read(args.reads)
+    haplotype_a_kmers: kmers.KmerLookup
+    haplotype_b_kmers: kmers.KmerLookup
+    if args.bloom:
+        haplotype_a_kmers = load_kmer_bloom(
+            args.haplotype_a_kmers,
+            args.kmer_size,
+            args.min_count,
+            args.kmer_format,
+            args.bloom_fpr,
+        )
+        haplotype_b_kmers = load_kmer_bloom(
+            args.haplotype_b_kmers,
+            args.kmer_size,
+            args.min_count,
+            args.kmer_format,
+            args.bloom_fpr,
+        )
+    else:
+        haplotype_a_kmers, haplotype_b_kmers = load_kmer_sets(args)
 
-    haplotype_a_outfile, haplotype_b_outfile, unclassified_outfile = seq.open_outfiles(
-        args.haplotype_a_out_prefix,
-        args.haplotype_b_out_prefix,
-        args.unclassified_out_prefix,
-        path.splitext(args.reads.rstrip(".gz"))[1],
-        not args.no_gzip_output,
-    )
+    adapter_masker = None
+    if args.mask_adapters is not None:
+        try:
+            adapter_masker = adapters.AdapterMasker.from_file(
+                args.mask_adapters, args.adapter_search_len, args.adapter_mismatches
+            )
+        except (OSError, seq.SeqError) as e:
+            sys.exit(f"Error: could not read --mask-adapters: {e}")
 
-    scaling_factor_a, scaling_factor_b = calculate_scaling_factors(
-        args.haplotype_a_kmers,
-        args.haplotype_b_kmers,
-    )
+    try:
+        classifier = (
+            Classifier.builder()
+            .hap_a(haplotype_a_kmers)
+            .hap_b(haplotype_b_kmers)
+            .options(options)
+            .rescue(rescue_options)
+            .threads(args.threads)
+            .keep_order(not args.no_keep_order)
+            .batch_size(args.batch_size)
+            .queue_depth(args.queue_depth)
+            .adapter_masker(adapter_masker)
+            .build()
+        )
+    except ValueError as e:
+        sys.exit(f"Error: {e}")
 
-    for read in reads:
-        hap_a_count, hap_b_count = kmers.count_kmers_in_read(
-            read.seq, args.haplotype_a_kmers, args.haplotype_b_kmers
+    # the first reads file, or the first mates, which the output files take
+    # after
+    reads_path = args.reads[0] if args.reads else args.interleaved or args.reads_1
+    need_qualities = bool(options.min_base_quality or args.min_mean_quality)
+    summary = Summary()
+
+    # every reads file, which a resumed run must have the same of
+    all_reads_paths = [
+        reads_path
+        for reads_path in [*args.reads, args.reads_1, args.reads_2, args.interleaved]
+        if reads_path is not None
+    ]
+    resume_from = None
+    checkpointer = None
+    if args.checkpoint is not None:
+        if args.resume and path.exists(args.checkpoint):
+            try:
+                resume_from = checkpoint.Checkpoint.load(args.checkpoint)
+                if resume_from.reads != all_reads_paths:
+                    raise checkpoint.CheckpointError(
+                        "it's of a run with other reads files"
+                    )
+                resume_from.truncate_outputs()
+            except (OSError, checkpoint.CheckpointError) as e:
+                sys.exit(f"Error: can't resume from {args.checkpoint}: {e}")
+            summary = resume_from.summary
+            logger.info(
+                "Resuming from %s, with %d reads done",
+                args.checkpoint,
+                summary.reads_seen,
+            )
+        checkpointer = checkpoint.Checkpointer(
+            args.checkpoint, args.checkpoint_interval, all_reads_paths, summary
         )
+    resuming = resume_from is not None
+
+    def skip_malformed(reads_path: str) -> Optional[Callable[[seq.SeqError], None]]:
+        if not args.skip_malformed:
+            return None
+
+        def skip(error: seq.SeqError):
+            logger.warning("Skipping a malformed record in %s: %s", reads_path, error)
+            summary.malformed += 1
 
-        hap_a_score = hap_a_count * scaling_factor_a
-        hap_b_score = hap_b_count * scaling_factor_b
+        return skip
+
+    reads = open_reads(
+        reads_path,
+        need_qualities,
+        skip_malformed(reads_path),
+        args.quality_encoding,
+        args.rna,
+        args.multiline_fastq,
+    )
+    tagger = None
+    if args.tag_output is not None:
+        try:
+            if not sam.is_alignment_file(reads_path):
+                sys.exit(f"Error: --tag-output needs SAM or BAM reads: {reads_path}")
+            tagger = sam.open_tagger(
+                reads_path, args.tag_output, "classify-by-kmers", shlex.join(sys.argv)
+            )
+        except seq.SeqError as e:
+            sys.exit(f"Error: {e}")
+        reads = tagger.alignments
+        if need_qualities:
+            reads = seq.check_qualities(reads)
+    output_format = args.output_format
+    if output_format == seq.OutputFormat.AUTO:
+        try:
+            output_format, reads = pick_output_format(
+                reads,
+                [*args.reads[1:], *([args.reads_2] if args.reads_2 else [])],
+                args.multiline_fastq,
+            )
+        except (OSError, seq.SeqError) as e:
+            sys.exit(f"Error reading reads: {e}")
+    # the reads file's extension, if it is one of the output format's, or
+    # else, e.g., for stdin or SAM/BAM, the usual one
+    outfile_extension = path.splitext(seq.strip_compressed_extension(reads_path))[1]
+    if output_format == seq.OutputFormat.FASTQ:
+        if outfile_extension.lower() not in FASTQ_EXTENSIONS:
+            outfile_extension = ".fastq"
+    elif outfile_extension.lower() not in FASTA_EXTENSIONS:
+        outfile_extension = ".fa"
 
-        if hap_a_score > hap_b_score:
-            read_bin = "A"
-            read.print(file=haplotype_a_outfile)
-        elif hap_b_score > hap_a_score:
-            read_bin = "B"
-            read.print(file=haplotype_b_outfile)
+    # where filtered-out reads go: a file per mate, or a file of names
+    filtered_outfiles: List[seq.TextOrGzip] = []
+    if has_read_filters(args) and not args.discard_filtered:
+        if args.names_only:
+            filtered_outfiles = [
+                seq.open_outfile(
+                    args.filtered_out_prefix, ".txt", False, append=resuming
+                )
+            ]
         else:
-            read_bin = "U"
-            read.print(file=unclassified_outfile)
+            filtered_outfiles = [
+                seq.open_outfile(
+                    args.filtered_out_prefix + paired_suffix,
+                    outfile_extension,
+                    not args.no_gzip_output,
+                    args.compression_level,
+                    resuming,
+                    args.bgzf,
+                    args.compression_threads,
+                )
+                for paired_suffix in ([""] if args.reads else ["_R1", "_R2"])
+            ]
+
+    def write_filtered(name: str, mates: Sequence[seq.Read]):
+        if args.names_only and filtered_outfiles:
+            print(name, file=filtered_outfiles[0])
+        else:
+            for outfile, read in zip(filtered_outfiles, mates):
+                seq.write_record(read, outfile, output_format, args.fake_quality)
+
+    seen = seq.IdSet() if args.check_duplicates else None
+
+    def classify_items(
+        file_index: int,
+        items: Iterator[T],
+        name: Callable[[T], str],
+        mates: Callable[[T], Sequence[seq.Read]],
+        num_reads: int,
+        classify: Callable[[Iterable[T]], Iterator[Tuple[T, Classification]]],
+    ) -> Iterator[Tuple[T, Classification]]:
+        # classify the reads or pairs of one reads file, leaving out those
+        # done before the checkpoint resumed from, if any, and those
+        # filtered out, and saving a checkpoint after each segment
+        num_done: Optional[int] = 0
+        if resume_from is not None:
+            num_done = resume_from.records_done(file_index)
+            items = checkpoint.skip_done(items, num_done, name, seen, summary)
+            if num_done is None:
+                return
+        counted = checkpoint.CountedIterator(items, cast(int, num_done))
+        kept = filter_reads(
+            subsample(
+                check_duplicates(counted, name, num_reads, seen, args, summary),
+                name,
+                num_reads,
+                args,
+                summary,
+            ),
+            name,
+            mates,
+            args,
+            summary,
+            write_filtered,
+        )
+        if checkpointer is None:
+            yield from classify(kept)
+            return
+        for segment in checkpointer.segments(kept):
+            yield from classify(segment)
+            checkpointer.save(file_index, counted.count)
+
+    classified: Iterator[Tuple[str, str, Classification, Sequence[seq.Read]]]
+    if args.reads:
+        reads_files = zip(
+            args.reads,
+            chain(
+                [reads],
+                (
+                    open_reads(
+                        other,
+                        need_qualities,
+                        skip_malformed(other),
+                        args.quality_encoding,
+                        args.rna,
+                        args.multiline_fastq,
+                    )
+                    for other in args.reads[1:]
+                ),
+            ),
+        )
+        classified = (
+            (file_path, read.name, classification, [read])
+            for file_index, (file_path, file_reads) in enumerate(reads_files)
+            for read, classification in classify_items(
+                file_index,
+                file_reads,
+                lambda read: read.name,
+                lambda read: [read],
+                1,
+                classifier.classify_reads,
+            )
+        )
+    else:
+        if args.interleaved is not None:
+            pairs = PairIter.interleaved(reads)
+        else:
+            pairs = PairIter(
+                reads,
+                open_reads(
+                    args.reads_2,
+                    need_qualities,
+                    skip_malformed(args.reads_2),
+                    args.quality_encoding,
+                    args.rna,
+                    args.multiline_fastq,
+                ),
+            )
+
+        def pair_name(pair: Tuple[seq.Read, seq.Read]) -> str:
+            return seq.mate_name(pair[0].name)
+
+        classified = (
+            (reads_path, pair_name(pair), classification, pair)
+            for pair, classification in classify_items(
+                0,
+                pairs,
+                pair_name,
+                lambda pair: pair,
+                2,
+                classifier.classify_read_pairs,
+            )
+        )
+
+    # one dict of output files per mate, or just one for unpaired reads, or
+    # none when only the names are written
+    mate_outfiles: List[Dict[Bin, seq.TextOrGzip]]
+    names_outfiles = None
+    if tagger is not None:
+        mate_outfiles = []
+    elif args.names_only:
+        mate_outfiles = []
+        names_outfiles = open_bin_outfiles(
+            args, ".txt", gzip_output=False, append=resuming
+        )
+    elif args.reads:
+        mate_outfiles = [open_bin_outfiles(args, outfile_extension, append=resuming)]
+    else:
+        mate_outfiles = [
+            open_bin_outfiles(args, outfile_extension, "_R1", append=resuming),
+            open_bin_outfiles(args, outfile_extension, "_R2", append=resuming),
+        ]
+
+    progress = None
+    if args.progress or (sys.stderr.isatty() and not args.quiet):
+        progress = Progress()
+
+    report = None
+    if args.report is not None:
+        report = seq.open_text_write(args.report, resuming, args.bgzf)
+        if not resuming:
+            print(*REPORT_COLUMNS, sep="\t", file=report)
+
+    if checkpointer is not None:
+        bin_outfiles = mate_outfiles + ([names_outfiles] if names_outfiles else [])
+        for outfile in chain(
+            filtered_outfiles,
+            [report] if report is not None else [],
+            *(outfiles.values() for outfiles in bin_outfiles),
+        ):
+            checkpointer.add_output(outfile)
+
+    try:
+        for file_path, name, classification, mates in classified:
+            for outfiles, read in zip(mate_outfiles, mates):
+                seq.write_record(
+                    read,
+                    outfiles[classification.bin],
+                    output_format,
+                    args.fake_quality,
+                )
+            if names_outfiles is not None:
+                print(name, file=names_outfiles[classification.bin])
+            if tagger is not None:
+                tags = haplotype_tags(classification)
+                tagger.write(cast(sam.Alignment, mates[0]), tags)
+            length = sum(len(read.seq) for read in mates)
+            summary.add(classification, length, len(mates))
+            if progress is not None:
+                progress.update(summary)
+
+            if report is not None:
+                write_report_line(report, name, length, classification, file_path)
+            if args.report == "-" or args.tag_output == "-":
+                continue
+
+            print(
+                "\t".join(
+                    map(
+                        str,
+                        [
+                            name,
+                            classification.bin.value,
+                            classification.hap_a_score,
+                            classification.hap_b_score,
+                        ],
+                    )
+                )
+            )
+    except seq.SeqError as e:
+        sys.exit(f"Error reading reads: {e}")
+    finally:
+        if names_outfiles is not None:
+            mate_outfiles.append(names_outfiles)
+        for outfiles in mate_outfiles:
+            for outfile in outfiles.values():
+                outfile.close()
+        if report is not None and report is not sys.stdout:
+            report.close()
+        for outfile in filtered_outfiles:
+            outfile.close()
+        if tagger is not None:
+            tagger.close()
 
-        print("\t".join(map(str, [read.name, read_bin, hap_a_score, hap_b_score])))
+    if args.checkpoint is not None and path.exists(args.checkpoint):
+        # the run is done, so there's nothing to resume
+        os.remove(args.checkpoint)
+    if progress is not None:
+        progress.finish(summary)
+    if not args.quiet:
+        print_summary(args, summary)
+    if args.summary_json is not None:
+        summary_file = seq.open_text_write(args.summary_json)
+        json.dump(summary.to_dict(), summary_file, indent=2)
+        print(file=summary_file)
+        if summary_file is not sys.stdout:
+            summary_file.close()
+    if summary.malformed:
+        sys.exit(EXIT_WITH_WARNINGS)
 
 
 if __name__ == "__main__":
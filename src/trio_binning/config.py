@@ -0,0 +1,141 @@
+"""Config files that give the options of a command-line script.
+
+A config file is TOML with one key for each option, named after the
+long option with underscores for dashes, e.g., `min_hits = 3` for
+--min-hits 3, or after a positional argument, e.g., `reads = [...]`.
+Each value goes through the same conversion and checks as it would on
+the command line, but is then used as the default of the option, so
+that options given on the command line override it.
+"""
+
+import argparse
+import json
+import math
+from typing import Any, Dict
+
+try:
+    import tomllib
+except ImportError:  # Python before 3.11
+    import tomli as tomllib  # type: ignore[no-redef]
+
+SKIPPED_OPTIONS = {"help", "config", "print_config"}
+"""Options that are never read from or printed to a config file"""
+
+
+class ConfigError(ValueError):
+    """A config file that can't be read or has an invalid option"""
+
+
+def load_config(path: str) -> Dict[str, Any]:
+    """Read a TOML config file
+
+    Raises:
+        ConfigError: if it can't be read or isn't TOML
+    """
+    try:
+        with open(path, "rb") as fp:
+            return tomllib.load(fp)
+    except OSError as e:
+        raise ConfigError(f"could not read {path}: {e.strerror}") from None
+    except tomllib.TOMLDecodeError as e:
+        raise ConfigError(f"{path} isn't valid TOML: {e}") from None
+
+
+def _convert_value(action: argparse.Action, value: Any) -> Any:
+    """Convert a value from a config file as argparse would an argument"""
+    if isinstance(value, bool) or not isinstance(value, (str, int, float)):
+        raise ConfigError(f"must be a string or number, not {value!r}")
+    converted: Any = None
+    if action.type is None:
+        if not isinstance(value, str):
+            raise ConfigError(f"must be a string, not {value!r}")
+        converted = value
+    else:
+        try:
+            converted = action.type(str(value))  # type: ignore[operator]
+        except (ValueError, TypeError, argparse.ArgumentTypeError):
+            if action.choices is None:
+                raise ConfigError(f"{value!r} isn't a valid value") from None
+    if action.choices is not None and converted not in action.choices:
+        choices = ", ".join(str(choice) for choice in action.choices)
+        raise ConfigError(f"must be one of {choices}, not {value!r}")
+    return converted
+
+
+def _convert_option(action: argparse.Action, value: Any) -> Any:
+    if action.nargs == 0:
+        # a switch, like --fuzzy, or a count, like -v
+        if action.const is None:
+            if isinstance(value, bool) or not isinstance(value, int) or value < 0:
+                raise ConfigError(f"must be a count, not {value!r}")
+        elif not isinstance(value, bool):
+            raise ConfigError(f"must be true or false, not {value!r}")
+        return value
+    if action.nargs in ("*", "+"):
+        if not isinstance(value, list):
+            raise ConfigError(f"must be a list, not {value!r}")
+        return [_convert_value(action, item) for item in value]
+    return _convert_value(action, value)
+
+
+def config_defaults(
+    parser: argparse.ArgumentParser, config: Dict[str, Any]
+) -> Dict[str, Any]:
+    """Convert the options in a config file to defaults for a parser
+
+    Args:
+        parser: the parser of the script's arguments
+        config: the config file, from `load_config`
+
+    Returns:
+        the converted value of each option, to give to
+        `parser.set_defaults`
+
+    Raises:
+        ConfigError: if an option is unknown or has an invalid value
+    """
+    actions = {
+        action.dest: action
+        for action in parser._actions
+        if action.dest not in SKIPPED_OPTIONS
+    }
+    unknown = [key for key in config if key not in actions]
+    if unknown:
+        raise ConfigError(f"unknown options in config: {', '.join(unknown)}")
+    defaults = {}
+    for key, value in config.items():
+        try:
+            defaults[key] = _convert_option(actions[key], value)
+        except ConfigError as e:
+            raise ConfigError(f"{key} in config {e}") from None
+    return defaults
+
+
+def _format_value(value: Any) -> str:
+    if isinstance(value, bool):
+        return str(value).lower()
+    if isinstance(value, (int, float)):
+        # TOML writes infinity and NaN as inf and nan, as str does
+        return repr(value) if math.isfinite(value) else str(value)
+    if isinstance(value, list):
+        return f"[{', '.join(_format_value(item) for item in value)}]"
+    # JSON strings are TOML basic strings, and anything else, like an enum,
+    # is written the way it's given on the command line
+    return json.dumps(str(value))
+
+
+def format_config(args: argparse.Namespace) -> str:
+    """Format parsed arguments as a config file that gives the same ones
+
+    Options that are None aren't written, since TOML has no None and
+    leaving them out leaves them None.
+
+    Args:
+        args: the arguments, before anything has changed them after
+            parsing
+    """
+    return "".join(
+        f"{name} = {_format_value(value)}\n"
+        for name, value in vars(args).items()
+        if name not in SKIPPED_OPTIONS and value is not None
+    )
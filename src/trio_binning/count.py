@@ -0,0 +1,719 @@
+"""Count k-mers in reads.
+
+This contains functions for counting k-mers in the reads of each parent
+and for finding the k-mers specific to each parental haplotype (hap-mers)
+from those counts, so that hap-mer sets can be built without an external
+k-mer counter like kmc.
+
+Counts are kept in a python dict mapping the integer format of each
+canonical k-mer to its count. Expect this to take on the order of 100
+bytes of memory per distinct k-mer, so it is best suited to smaller
+genomes or to reads that have already been filtered. For bigger genomes,
+`DiskKmerCounts` counts k-mers in temporary files instead, in as little
+memory as you like.
+"""
+import heapq
+import os
+import tempfile
+import threading
+from array import array
+from bisect import bisect_left
+from collections import Counter, deque
+from concurrent.futures import Future, ThreadPoolExecutor
+from itertools import groupby, islice
+from operator import itemgetter
+from typing import (
+    Callable,
+    Deque,
+    Dict,
+    Iterable,
+    Iterator,
+    List,
+    Optional,
+    Tuple,
+    TypeVar,
+)
+
+from trio_binning import kmers, seq
+
+KmerCounts = Dict[int, int]
+
+T = TypeVar("T")
+
+DEFAULT_MEMORY_LIMIT = 1 << 30
+"""The default number of bytes of k-mers `DiskKmerCounts` holds in memory
+before writing them out"""
+
+
+def _count_batch(
+    counts: KmerCounts,
+    reads: Iterable[seq.SeqRecord],
+    k: int,
+    ignore_softmasked: bool,
+    compress_homopolymers: bool,
+    seed: Optional[kmers.SpacedSeed],
+    prefilter: Optional[kmers.KmerCountingBloom] = None,
+    min_count: int = 1,
+):
+    """Add the counts of the canonical k-mers in some reads to `counts`,
+    only of those `prefilter` has counted at least `min_count` times if
+    it's given"""
+    for read in reads:
+        read_seq = read.seq
+        if compress_homopolymers:
+            read_seq = kmers.compress_homopolymers(read_seq)
+        read_kmers = _read_kmers(read_seq, k, ignore_softmasked, seed)
+        if prefilter is not None:
+            prefilter.keep_frequent(read_kmers, min_count)
+        counts.update(read_kmers)
+
+
+def _read_kmers(
+    read_seq: str, k: int, ignore_softmasked: bool, seed: Optional[kmers.SpacedSeed]
+) -> array:
+    """The canonical k-mers of a read, or the k-mers `seed` makes of it"""
+    if seed is None:
+        return kmers.canonical_kmer_array(read_seq, k, ignore_softmasked)
+    return kmers.spaced_kmer_array(read_seq, seed, ignore_softmasked)
+
+
+def _check_seed(k: int, seed: Optional[kmers.SpacedSeed]):
+    if seed is not None and seed.weight != k:
+        raise ValueError(
+            f"the seed pattern {seed} makes {seed.weight}-mers, not {k}-mers"
+        )
+
+
+def count_kmers(
+    reads: Iterable[seq.SeqRecord],
+    k: int,
+    ignore_softmasked: bool = False,
+    compress_homopolymers: bool = False,
+    threads: int = 1,
+    batch_size: int = 100,
+    seed: Optional[kmers.SpacedSeed] = None,
+    prefilter: Optional[kmers.KmerCountingBloom] = None,
+    min_count: int = 1,
+) -> KmerCounts:
+    """Count the canonical k-mers in a set of reads.
+
+    With more than one thread, the reads are handed out in batches of
+    `batch_size`, and each thread counts the batches it gets in its own
+    dict, so that no locking is needed until the threads' dicts are
+    merged at the end. Finding the k-mers in a read is done in C without
+    the GIL, but adding them to a dict needs it, so the speedup is well
+    short of the number of threads. Each thread's dict can hold most of
+    the distinct k-mers, so this can take up to `threads` times the
+    memory.
+
+    Args:
+        reads: the reads to count k-mers in, e.g., from
+            `seq.open_fastx_read`, or any other `seq.SeqRecord`s
+        k: the k-mer size
+        ignore_softmasked: if True, skip k-mers with lowercase
+            (soft-masked) bases instead of treating them like uppercase
+        compress_homopolymers: if True, count the k-mers in the
+            homopolymer-compressed reads (see
+            `kmers.iter_homopolymer_compressed`)
+        threads: number of threads to count k-mers with
+        batch_size: number of reads per batch with multiple threads
+        seed: count the k-mers this spaced seed makes of the reads
+            (see `kmers.spaced_kmer_array`) instead, with `k` its weight
+        prefilter: only count the k-mers this filter, from
+            `count_kmers_in_counting_bloom` of the same reads, has
+            counted at least `min_count` times, leaving out most of those
+            seen fewer times
+        min_count: see `prefilter`
+
+    Returns:
+        a dict mapping the integer format of each canonical k-mer found
+        in the reads to the number of times it was found. This is the
+        same however many threads count them.
+
+    Raises:
+        ValueError: if `threads` or `batch_size` is less than 1, or
+            `seed` doesn't make `k`-mers
+    """
+    _check_seed(k, seed)
+    if threads < 1:
+        raise ValueError(f"threads must be at least 1, not {threads}")
+    if batch_size < 1:
+        raise ValueError(f"batch_size must be at least 1, not {batch_size}")
+
+    def count_batch(counts: Counter, batch: Iterable[seq.SeqRecord]):
+        _count_batch(
+            counts,
+            batch,
+            k,
+            ignore_softmasked,
+            compress_homopolymers,
+            seed,
+            prefilter,
+            min_count,
+        )
+
+    if threads == 1:
+        counts: Counter = Counter()
+        count_batch(counts, reads)
+        return counts
+
+    thread_counts = _in_threads(reads, threads, batch_size, Counter, count_batch)
+    if not thread_counts:
+        return Counter()
+    thread_counts.sort(key=len, reverse=True)
+    counts = thread_counts[0]
+    for other_counts in thread_counts[1:]:
+        counts.update(other_counts)
+    return counts
+
+
+def _in_threads(
+    reads: Iterable[seq.SeqRecord],
+    threads: int,
+    batch_size: int,
+    make: Callable[[], T],
+    add_batch: Callable[[T, List[seq.SeqRecord]], None],
+) -> List[T]:
+    """Hand some reads out to threads in batches of `batch_size`
+
+    Each thread adds the batches it gets to its own accumulator from
+    `make`, like a dict of counts, with `add_batch`, so that no locking
+    is needed until the caller merges them.
+
+    Returns:
+        the accumulators of the threads that got a batch
+    """
+    accumulators: List[T] = []
+    local = threading.local()
+
+    def run_batch(batch: List[seq.SeqRecord]):
+        if not hasattr(local, "accumulator"):
+            local.accumulator = make()
+            # list.append is atomic, so this needs no lock
+            accumulators.append(local.accumulator)
+        add_batch(local.accumulator, batch)
+
+    reads = iter(reads)
+    pending: Deque[Future] = deque()
+    with ThreadPoolExecutor(threads) as executor:
+        while True:
+            batch = list(islice(reads, batch_size))
+            if batch:
+                pending.append(executor.submit(run_batch, batch))
+            if not pending:
+                break
+            if batch and len(pending) < 2 * threads:
+                continue
+            # raises any exception from the batch
+            pending.popleft().result()
+    return accumulators
+
+
+def estimate_distinct_kmers(
+    reads: Iterable[seq.SeqRecord],
+    k: int,
+    ignore_softmasked: bool = False,
+    compress_homopolymers: bool = False,
+    threads: int = 1,
+    batch_size: int = 100,
+    precision: int = kmers.DEFAULT_HLL_PRECISION,
+) -> kmers.CardinalityEstimator:
+    """Estimate how many distinct canonical k-mers a set of reads has,
+    without counting them
+
+    This takes the same arguments as `count_kmers`, and the same little
+    memory however many k-mers there are. Each thread keeps its own
+    estimator, and they are merged at the end. Hashing the k-mers is
+    done in C without the GIL, so this speeds up better with threads.
+
+    Args:
+        precision: see `kmers.CardinalityEstimator`
+        See `count_kmers` for the rest.
+
+    Returns:
+        an estimator of every canonical k-mer in the reads, to call
+        `estimate` on
+
+    Raises:
+        ValueError: if `threads` or `batch_size` is less than 1, or the
+            precision is out of range
+    """
+    if threads < 1:
+        raise ValueError(f"threads must be at least 1, not {threads}")
+    if batch_size < 1:
+        raise ValueError(f"batch_size must be at least 1, not {batch_size}")
+    estimator = kmers.CardinalityEstimator(precision)
+
+    def estimate_batch(
+        batch_estimator: kmers.CardinalityEstimator, batch: Iterable[seq.SeqRecord]
+    ):
+        for read in batch:
+            read_seq = read.seq
+            if compress_homopolymers:
+                read_seq = kmers.compress_homopolymers(read_seq)
+            batch_estimator.insert_array(
+                kmers.canonical_kmer_array(read_seq, k, ignore_softmasked)
+            )
+
+    if threads == 1:
+        estimate_batch(estimator, reads)
+        return estimator
+    for thread_estimator in _in_threads(
+        reads,
+        threads,
+        batch_size,
+        lambda: kmers.CardinalityEstimator(precision),
+        estimate_batch,
+    ):
+        estimator.merge(thread_estimator)
+    return estimator
+
+
+def count_kmers_in_counting_bloom(
+    reads: Iterable[seq.SeqRecord],
+    k: int,
+    num_counters: int,
+    ignore_softmasked: bool = False,
+    compress_homopolymers: bool = False,
+    seed: Optional[kmers.SpacedSeed] = None,
+) -> kmers.KmerCountingBloom:
+    """Count the canonical k-mers in a set of reads approximately, in a
+    counting Bloom filter of `num_counters` counters
+
+    This is the first pass of counting with a prefilter: the filter's
+    counts are never too low, so passing it to `count_kmers` of the same
+    reads as `prefilter` counts every k-mer seen at least `min_count`
+    times exactly, and leaves out all but a few of the rest. See
+    `count_kmers` for the other arguments.
+
+    Raises:
+        ValueError: if `seed` doesn't make `k`-mers
+    """
+    _check_seed(k, seed)
+    counts = kmers.KmerCountingBloom(num_counters)
+    for read in reads:
+        read_seq = read.seq
+        if compress_homopolymers:
+            read_seq = kmers.compress_homopolymers(read_seq)
+        counts.add_array(_read_kmers(read_seq, k, ignore_softmasked, seed))
+    return counts
+
+
+def hap_specific(
+    parent_a_counts: KmerCounts, parent_b_counts: KmerCounts, k: int, min_count: int
+) -> Tuple[kmers.KmerSet, kmers.KmerSet]:
+    """Find the k-mers specific to each parent.
+
+    A k-mer is specific to a parent if it is found at least `min_count`
+    times in that parent's reads and not at all in the other parent's.
+
+    Args:
+        parent_a_counts: k-mer counts for parent A, from `count_kmers`
+        parent_b_counts: k-mer counts for parent B, from `count_kmers`
+        k: the k-mer size used to make the counts
+        min_count: the minimum count for a k-mer to be considered
+            present rather than a sequencing error
+
+    Returns:
+        hap_a: a set of k-mers specific to parent A
+        hap_b: a set of k-mers specific to parent B
+    """
+    hap_a = _subtract_counts(parent_a_counts, parent_b_counts, k, min_count)
+    hap_b = _subtract_counts(parent_b_counts, parent_a_counts, k, min_count)
+    return hap_a, hap_b
+
+
+def hap_specific_prefiltered(
+    parent_a_counts: KmerCounts,
+    parent_b_counts: KmerCounts,
+    parent_a_prefilter: kmers.KmerCountingBloom,
+    parent_b_prefilter: kmers.KmerCountingBloom,
+    k: int,
+    min_count: int,
+) -> Tuple[kmers.KmerSet, kmers.KmerSet, int]:
+    """Find the k-mers specific to each parent from prefiltered counts.
+
+    This is `hap_specific` for counts from `count_kmers` with a
+    `prefilter`, which leave out most k-mers seen fewer than `min_count`
+    times. Those still rule a k-mer out of the other parent, so whether
+    a k-mer is in the other parent at all is looked up in that parent's
+    prefilter instead of its counts. The prefilter never misses a k-mer,
+    so every k-mer found is one `hap_specific` would find with exact
+    counts, but a false positive in the other parent's prefilter can
+    leave a real one out, at about that filter's `false_positive_rate`.
+
+    Args:
+        parent_a_counts: prefiltered k-mer counts for parent A
+        parent_b_counts: prefiltered k-mer counts for parent B
+        parent_a_prefilter: the prefilter `parent_a_counts` was made
+            with, from `count_kmers_in_counting_bloom`
+        parent_b_prefilter: the same for parent B
+        See `hap_specific` for the rest.
+
+    Returns:
+        hap_a: a set of k-mers specific to parent A
+        hap_b: a set of k-mers specific to parent B
+        num_shared: the number of k-mers seen at least `min_count` times
+            in one parent that were also in the other, by its prefilter
+    """
+    hap_a, shared_a = _subtract_prefiltered(
+        parent_a_counts, parent_b_prefilter, k, min_count
+    )
+    hap_b, shared_b = _subtract_prefiltered(
+        parent_b_counts, parent_a_prefilter, k, min_count
+    )
+    num_shared_kmers = len(shared_a) + sum(
+        1 for kmer_int in shared_b if kmer_int not in shared_a
+    )
+    return hap_a, hap_b, num_shared_kmers
+
+
+class DiskKmerCounts:
+    """Canonical k-mer counts made in temporary files, for little memory.
+
+    Every k-mer found in the reads is buffered in memory, and whenever the
+    buffer reaches `memory_limit` bytes, it is sorted and each k-mer is
+    appended to one of 2 ** `partition_bits` partition files on disk,
+    picked by the k-mer's top bits. Iterating then reads, sorts and counts
+    one partition at a time, and since the partitions are in order of
+    their top bits, the counts come out in order of k-mer. That takes 16
+    bytes of memory for every time a k-mer in the biggest partition was
+    seen, e.g., about 2 GB for 30x coverage of a 3 Gbp genome with the
+    default 256 partitions; more partitions take less. The disk needs 8
+    bytes for every time any k-mer was seen.
+
+    The files are in a temporary directory in `tmpdir`, which is deleted
+    by `close`, or when leaving a `with` block, including if counting
+    fails.
+
+    >>> with DiskKmerCounts([seq.Read("read1", "AACGTT")], 3) as counts:
+    ...     [(kmers.int_to_kmer(kmer_int, 3), n) for kmer_int, n in counts]
+    [('AAC', 2), ('ACG', 2)]
+    """
+
+    def __init__(
+        self,
+        reads: Iterable[seq.SeqRecord],
+        k: int,
+        ignore_softmasked: bool = False,
+        compress_homopolymers: bool = False,
+        memory_limit: int = DEFAULT_MEMORY_LIMIT,
+        tmpdir: Optional[str] = None,
+        partition_bits: int = 8,
+        seed: Optional[kmers.SpacedSeed] = None,
+    ):
+        """Count the k-mers in some reads, as `count_kmers` does
+
+        Args:
+            memory_limit: the most bytes of k-mers to buffer in memory
+                before writing them to the partition files
+            tmpdir: the directory to make the temporary directory in.
+                Default is the system's, e.g., from $TMPDIR.
+            partition_bits: the number of top bits of each k-mer to pick
+                its partition by, up to 2k
+            See `count_kmers` for the rest.
+
+        Raises:
+            ValueError: if `memory_limit` is less than the 8 bytes of a
+                k-mer, `partition_bits` is less than 0, or `seed` doesn't
+                make `k`-mers
+        """
+        _check_seed(k, seed)
+        if memory_limit < 8:
+            raise ValueError(f"memory_limit must be at least 8, not {memory_limit}")
+        if partition_bits < 0:
+            raise ValueError(
+                f"partition_bits must not be negative, not {partition_bits}"
+            )
+        self.k = k
+        partition_bits = min(partition_bits, 2 * k)
+        self._shift = 2 * k - partition_bits
+        self._temp_dir = tempfile.TemporaryDirectory(prefix="kmer_counts.", dir=tmpdir)
+        self._paths = [
+            os.path.join(self._temp_dir.name, f"{partition}.kmers")
+            for partition in range(1 << partition_bits)
+        ]
+
+        try:
+            buffer = array("Q")
+            for read in reads:
+                read_seq = read.seq
+                if compress_homopolymers:
+                    read_seq = kmers.compress_homopolymers(read_seq)
+                buffer.extend(_read_kmers(read_seq, k, ignore_softmasked, seed))
+                if 8 * len(buffer) >= memory_limit:
+                    self._write_partitions(buffer)
+                    buffer = array("Q")
+            if buffer:
+                self._write_partitions(buffer)
+        except BaseException:
+            self.close()
+            raise
+
+    def _write_partitions(self, buffer: array):
+        """Sort the buffered k-mers and append them to their partitions"""
+        kmers.sort_kmer_array(buffer)
+        start = 0
+        while start < len(buffer):
+            partition = buffer[start] >> self._shift
+            end = bisect_left(buffer, (partition + 1) << self._shift, start)
+            with open(self._paths[partition], "ab") as partition_file:
+                buffer[start:end].tofile(partition_file)
+            start = end
+
+    def __iter__(self) -> Iterator[Tuple[int, int]]:
+        """Iterate over each distinct k-mer and its count, in order of the
+        k-mers' integer format"""
+        for path in self._paths:
+            if not os.path.exists(path):
+                continue
+            partition = array("Q")
+            with open(path, "rb") as partition_file:
+                partition.frombytes(partition_file.read())
+            kmer_ints, counts = kmers.count_kmer_array(partition)
+            yield from zip(kmer_ints, counts)
+
+    def close(self):
+        """Delete the partition files"""
+        self._temp_dir.cleanup()
+
+    def __enter__(self) -> "DiskKmerCounts":
+        return self
+
+    def __exit__(self, *exc_info):
+        self.close()
+
+
+def hap_specific_sorted(
+    parent_a_counts: Iterable[Tuple[int, int]],
+    parent_b_counts: Iterable[Tuple[int, int]],
+    k: int,
+    min_count: int,
+) -> Tuple[kmers.KmerSet, kmers.KmerSet, int]:
+    """Find the k-mers specific to each parent from sorted counts.
+
+    This is `hap_specific` for counts that are streamed in order of
+    k-mer, e.g., from `DiskKmerCounts`, so that neither parent's counts
+    need to fit in memory.
+
+    Args:
+        parent_a_counts: each distinct canonical k-mer in parent A and its
+            count, in order of k-mer
+        parent_b_counts: the same for parent B
+        See `hap_specific` for the rest.
+
+    Returns:
+        hap_a: a set of k-mers specific to parent A
+        hap_b: a set of k-mers specific to parent B
+        num_shared: the number of k-mers found in both, as from
+            `num_shared`
+    """
+    hap_a = kmers.KmerSet(k)
+    hap_b = kmers.KmerSet(k)
+    num_shared_kmers = 0
+    a_iter = iter(parent_a_counts)
+    b_iter = iter(parent_b_counts)
+    a = next(a_iter, None)
+    b = next(b_iter, None)
+    while a is not None or b is not None:
+        if b is None or (a is not None and a[0] < b[0]):
+            if a[1] >= min_count:
+                hap_a.add(a[0])
+            a = next(a_iter, None)
+        elif a is None or b[0] < a[0]:
+            if b[1] >= min_count:
+                hap_b.add(b[0])
+            b = next(b_iter, None)
+        else:
+            num_shared_kmers += 1
+            a = next(a_iter, None)
+            b = next(b_iter, None)
+    return hap_a, hap_b, num_shared_kmers
+
+
+def dump_order(kmer_int: int, k: int) -> int:
+    """A key to sort k-mers in integer format in the order of their strings
+
+    K-mer counters dump their counts in order of the k-mer strings (ACGT
+    order), which isn't the order of the integer format, since that puts
+    the first base in the lowest bits. This reverses the bases, which is
+    the reverse complement, complemented.
+    """
+    return kmers.reverse_complement_int(kmer_int, k) ^ ((1 << 2 * k) - 1)
+
+
+def _check_dump_order(
+    counts: Iterable[Tuple[int, int]], k: int, name: str
+) -> Iterator[Tuple[int, int, int]]:
+    """Add the `dump_order` key to each k-mer, checking that they're sorted"""
+    last_key = -1
+    for kmer_int, kmer_count in counts:
+        key = dump_order(kmer_int, k)
+        if key <= last_key:
+            raise ValueError(
+                f"the k-mers in {name} aren't sorted with each only once: "
+                f"{kmers.int_to_kmer(kmer_int, k)} comes after the same or a "
+                "later k-mer"
+            )
+        last_key = key
+        yield key, kmer_int, kmer_count
+
+
+def merge(
+    dumps: List[Iterable[Tuple[int, int]]], k: int, names: Optional[List[str]] = None
+) -> Iterator[Tuple[int, int]]:
+    """Merge sorted k-mer counts, summing the counts of the same k-mer.
+
+    This is a k-way merge, so it only holds one k-mer from each dump in
+    memory at a time, however big they are. K-mers aren't canonicalized,
+    so a k-mer and its reverse complement are only summed if the dumps
+    are all of canonical k-mers, as they usually are.
+
+    Args:
+        dumps: each dump's k-mers in integer format and their counts, in
+            `dump_order`, e.g., `meryl print` or `kmc_dump` output
+        k: the k-mer size of every dump
+        names: what to call each dump in errors, e.g., its path
+
+    Yields:
+        each distinct k-mer and the sum of its counts, in `dump_order`
+
+    Raises:
+        ValueError: if a dump isn't sorted, or has the same k-mer twice
+    """
+    if names is None:
+        names = [f"dump {i + 1}" for i in range(len(dumps))]
+    merged = heapq.merge(
+        *(_check_dump_order(dump, k, name) for dump, name in zip(dumps, names))
+    )
+    for _, group in groupby(merged, key=itemgetter(0)):
+        _, kmer_int, kmer_count = next(group)
+        yield kmer_int, kmer_count + sum(other_count for _, _, other_count in group)
+
+
+def filter_counts(
+    counts: Iterable[Tuple[int, int]],
+    min_count: Optional[int] = None,
+    max_count: Optional[int] = None,
+) -> Iterator[Tuple[int, int]]:
+    """Drop k-mers with too low or high a count, e.g., errors and repeats
+
+    Yields:
+        each k-mer and its count with a count of at least `min_count` and
+        at most `max_count`, if they're given, in the same order
+    """
+    for kmer_int, kmer_count in counts:
+        if min_count is not None and kmer_count < min_count:
+            continue
+        if max_count is not None and kmer_count > max_count:
+            continue
+        yield kmer_int, kmer_count
+
+
+def top_counts(counts: Iterable[Tuple[int, int]], n: int) -> List[Tuple[int, int]]:
+    """The `n` k-mers with the highest counts, e.g., the hap-mers most
+    reliably present in a parent
+
+    Only a heap of the `n` highest so far is kept, so this takes little
+    memory however many counts there are.
+
+    Returns:
+        up to `n` k-mers and their counts, from the highest count down,
+        with k-mers of the same count in the order they came in
+
+    Raises:
+        ValueError: if `n` is less than 1
+    """
+    if n < 1:
+        raise ValueError(f"n must be at least 1, not {n}")
+    return heapq.nlargest(n, counts, key=itemgetter(1))
+
+
+def num_shared(parent_a_counts: KmerCounts, parent_b_counts: KmerCounts) -> int:
+    """Count the k-mers found in both parents, which `hap_specific` leaves out"""
+    if len(parent_b_counts) < len(parent_a_counts):
+        parent_a_counts, parent_b_counts = parent_b_counts, parent_a_counts
+    return sum(1 for kmer_int in parent_a_counts if kmer_int in parent_b_counts)
+
+
+def histogram(kmer_counts: Iterable[int], max_count: int) -> List[int]:
+    """Make the k-mer spectrum: how many distinct k-mers have each count.
+
+    Args:
+        kmer_counts: the count of each distinct k-mer, e.g., the values
+            from `count_kmers`
+        max_count: the highest count to give its own bin. K-mers with a
+            higher count are put in this bin too, so that the long tail
+            of repeats doesn't make the histogram huge.
+
+    Returns:
+        a list of `max_count + 1` numbers of distinct k-mers, where the
+        number at index i is the number with a count of i, so index 0 is
+        always 0, except that the last is the number with a count of at
+        least `max_count`
+
+    Raises:
+        ValueError: if `max_count` is less than 1
+    """
+    if max_count < 1:
+        raise ValueError(f"max_count must be at least 1, not {max_count}")
+    spectrum = [0] * (max_count + 1)
+    for kmer_count in kmer_counts:
+        spectrum[min(kmer_count, max_count)] += 1
+    return spectrum
+
+
+def suggest_min_count(spectrum: List[int]) -> Optional[int]:
+    """Suggest a minimum count for k-mers to be present in a parent.
+
+    In the k-mer spectrum of a parent's reads, k-mers made by sequencing
+    errors have low counts and falling numbers, while real k-mers make a
+    second peak around the coverage. This finds the valley between them
+    by going up from a count of 1 until the number of k-mers stops
+    falling. It's only a heuristic, so plot the histogram to check.
+
+    Args:
+        spectrum: a histogram of counts, from `histogram`. Its last bin,
+            of the k-mers with the highest counts, is left out, since it
+            isn't a peak even if it's large.
+
+    Returns:
+        the count at the valley, or None if the numbers of k-mers never
+        rise again after it, so there's no coverage peak
+    """
+    spectrum = spectrum[:-1]
+    valley = 1
+    while valley + 1 < len(spectrum) and spectrum[valley + 1] < spectrum[valley]:
+        valley += 1
+    if valley >= len(spectrum) or max(spectrum[valley:]) <= spectrum[valley]:
+        return None
+    return valley
+
+
+def _subtract_counts(
+    counts: KmerCounts, other_counts: KmerCounts, k: int, min_count: int
+) -> kmers.KmerSet:
+    kmer_set = kmers.KmerSet(k)
+    for kmer_int, count in counts.items():
+        if count >= min_count and kmer_int not in other_counts:
+            kmer_set.add(kmer_int)
+    return kmer_set
+
+
+def _subtract_prefiltered(
+    counts: KmerCounts,
+    other_prefilter: kmers.KmerCountingBloom,
+    k: int,
+    min_count: int,
+) -> Tuple[kmers.KmerSet, set]:
+    """The k-mers of `counts` seen at least `min_count` times and not in
+    `other_prefilter`, and the set of those that are in it"""
+    kmer_set = kmers.KmerSet(k)
+    shared = set()
+    frequent = array("Q", (n for n, count in counts.items() if count >= min_count))
+    for kmer_int, other_count in zip(frequent, other_prefilter.counts(frequent)):
+        if other_count:
+            shared.add(kmer_int)
+        else:
+            kmer_set.add(kmer_int)
+    return kmer_set, shared
@@ -0,0 +1,206 @@
+"""Evaluate the bins of classified reads against their true haplotypes.
+
+This compares a classify-by-kmers report with a table of the haplotype
+each read really came from, e.g., `kmers simulate`'s truth table, to
+get a confusion matrix and the precision and recall of each haplotype
+bin, counting reads and bases.
+"""
+
+from typing import Any, Dict, Iterable, Optional, TextIO
+
+from trio_binning.classify import Bin
+
+HAPLOTYPES = [Bin.HAP_A, Bin.HAP_B]
+"""The true haplotypes a read can have. Unknown is only a bin."""
+
+
+class EvaluationError(ValueError):
+    """A report or truth table that can't be read"""
+
+
+def read_truth(lines: Iterable[str]) -> Dict[str, Bin]:
+    """Read a truth table of read ids and haplotypes
+
+    Each line has a read's id and its haplotype, A or B, separated by a
+    tab, and any more columns are ignored, so `kmers simulate`'s truth
+    table can be read as is. A header line starting with "read_id" is
+    skipped. The whole table is held in memory, while the report can be
+    streamed past it.
+
+    Raises:
+        EvaluationError: if a line has too few columns, a haplotype
+            other than A or B, or a read id seen before
+    """
+    truth: Dict[str, Bin] = {}
+    for line_number, line in enumerate(lines, 1):
+        fields = line.rstrip("\n").split("\t")
+        if line_number == 1 and fields[0] == "read_id":
+            continue
+        if len(fields) < 2:
+            raise EvaluationError(
+                f"line {line_number} of the truth table has fewer than 2 columns"
+            )
+        read_id, haplotype = fields[:2]
+        if haplotype not in ("A", "B"):
+            raise EvaluationError(
+                f"line {line_number} of the truth table has haplotype "
+                f"{haplotype!r}, not A or B"
+            )
+        if read_id in truth:
+            raise EvaluationError(f"read {read_id} is in the truth table twice")
+        truth[read_id] = Bin(haplotype)
+    return truth
+
+
+class Evaluation:
+    """Counts of reads and bases by true haplotype and bin"""
+
+    def __init__(self):
+        self.reads = {hap: {read_bin: 0 for read_bin in Bin} for hap in HAPLOTYPES}
+        """The number of reads of each true haplotype put into each bin"""
+        self.bases = {hap: {read_bin: 0 for read_bin in Bin} for hap in HAPLOTYPES}
+        """The total length of the reads of each true haplotype put into
+        each bin"""
+        self.not_in_truth = 0
+        """The number of reads in the report but not in the truth table,
+        which are left out of everything else"""
+        self.not_in_report = 0
+        """The number of reads in the truth table but not in the report,
+        e.g., filtered out before classifying"""
+
+    def add(self, true_hap: Bin, read_bin: Bin, length: int):
+        """Add a classified read with a known haplotype"""
+        self.reads[true_hap][read_bin] += 1
+        self.bases[true_hap][read_bin] += length
+
+    @staticmethod
+    def _rates(counts: Dict[Bin, Dict[Bin, int]]) -> Dict[str, Any]:
+        """The confusion matrix, precision, recall and unknown rate of
+        counts of reads or of bases. A rate with no count to divide by is
+        None."""
+
+        def ratio(numerator: int, denominator: int) -> Optional[float]:
+            return numerator / denominator if denominator else None
+
+        total = sum(sum(row.values()) for row in counts.values())
+        return {
+            "confusion": {
+                hap.value: {read_bin.value: counts[hap][read_bin] for read_bin in Bin}
+                for hap in HAPLOTYPES
+            },
+            "precision": {
+                hap.value: ratio(
+                    counts[hap][hap], sum(counts[true][hap] for true in HAPLOTYPES)
+                )
+                for hap in HAPLOTYPES
+            },
+            "recall": {
+                hap.value: ratio(counts[hap][hap], sum(counts[hap].values()))
+                for hap in HAPLOTYPES
+            },
+            "unknown_rate": ratio(
+                sum(counts[hap][Bin.UNKNOWN] for hap in HAPLOTYPES), total
+            ),
+        }
+
+    def to_dict(self) -> Dict[str, Any]:
+        """Convert the evaluation to a dict that can be serialized as JSON
+
+        The precision of a haplotype is the fraction of the reads put in
+        its bin that are from it, and its recall is the fraction of the
+        reads from it that were put in its bin, so unknown reads lower
+        recall but not precision.
+        """
+        return {
+            "reads": self._rates(self.reads),
+            "bases": self._rates(self.bases),
+            "not_in_truth": self.not_in_truth,
+            "not_in_report": self.not_in_report,
+        }
+
+    def print_table(self, file: TextIO):
+        """Print the confusion matrix and rates as text"""
+        evaluation = self.to_dict()
+
+        def percent(rate: Optional[float]) -> str:
+            return "NA" if rate is None else f"{100 * rate:.2f}%"
+
+        for unit in ["reads", "bases"]:
+            rates = evaluation[unit]
+            print(f"{unit}\t" + "\t".join(f"bin {b.value}" for b in Bin), file=file)
+            for hap in HAPLOTYPES:
+                row = rates["confusion"][hap.value]
+                print(
+                    f"true {hap.value}\t"
+                    + "\t".join(str(row[read_bin.value]) for read_bin in Bin),
+                    file=file,
+                )
+            for hap in HAPLOTYPES:
+                print(
+                    f"Haplotype {hap.value} {unit}: precision "
+                    f"{percent(rates['precision'][hap.value])}, recall "
+                    f"{percent(rates['recall'][hap.value])}",
+                    file=file,
+                )
+            print(f"Unknown {unit}: {percent(rates['unknown_rate'])}", file=file)
+            print(file=file)
+        print(f"Reads in the report but not the truth: {self.not_in_truth}", file=file)
+        print(
+            f"Reads in the truth but not the report: {self.not_in_report}", file=file
+        )
+
+
+def evaluate(report: Iterable[str], truth: Dict[str, Bin]) -> Evaluation:
+    """Evaluate a classify-by-kmers report against the truth
+
+    The report is read one line at a time, finding its read_id, length
+    and bin columns by the header, so only the truth is held in memory.
+
+    Args:
+        report: the lines of the report, with its header
+        truth: the true haplotype of each read, as from `read_truth`
+
+    Raises:
+        EvaluationError: if the report has no header with those
+            columns, a line has too few columns or a bad bin or length,
+            or a read in the truth is in it twice
+    """
+    lines = iter(report)
+    header = next(lines, "").rstrip("\n").split("\t")
+    try:
+        id_column, length_column, bin_column = (
+            header.index(column) for column in ["read_id", "length", "bin"]
+        )
+    except ValueError:
+        raise EvaluationError(
+            "the report's header doesn't have read_id, length and bin columns"
+        ) from None
+    num_columns = max(id_column, length_column, bin_column) + 1
+
+    evaluation = Evaluation()
+    not_in_report = set(truth)
+    for line_number, line in enumerate(lines, 2):
+        fields = line.rstrip("\n").split("\t")
+        if len(fields) < num_columns:
+            raise EvaluationError(
+                f"line {line_number} of the report has fewer than {num_columns} "
+                "columns"
+            )
+        read_id = fields[id_column]
+        true_hap = truth.get(read_id)
+        if true_hap is None:
+            evaluation.not_in_truth += 1
+            continue
+        if read_id not in not_in_report:
+            raise EvaluationError(f"read {read_id} is in the report twice")
+        not_in_report.remove(read_id)
+        try:
+            read_bin = Bin(fields[bin_column])
+            length = int(fields[length_column])
+        except ValueError:
+            raise EvaluationError(
+                f"line {line_number} of the report has a bad bin or length"
+            ) from None
+        evaluation.add(true_hap, read_bin, length)
+    evaluation.not_in_report = len(not_in_report)
+    return evaluation
@@ -0,0 +1,275 @@
+"""Random access to fasta files with a samtools-style .fai index.
+
+The index has one line per sequence with five tab-separated columns:
+the name, the length, the byte offset of the first base, the number of
+bases per line and the number of bytes per line, including the line
+ending. Files written by `build_index` can be used by samtools and vice
+versa. As with samtools, every line of a sequence but the last must be
+the same length, and the fasta file can't be gzipped, since gzip files
+can't be seeked into.
+"""
+import os
+from dataclasses import dataclass
+from typing import BinaryIO, Dict, List, Optional
+
+from trio_binning.seq import GZIP_MAGIC, CoordinateError, Read, SeqError
+
+
+@dataclass
+class FaiEntry:
+    """The index of one sequence in a fasta file"""
+
+    name: str
+    """The name of the sequence, up to the first whitespace in its header"""
+    length: int
+    """The number of bases in the sequence"""
+    offset: int
+    """The byte offset of the first base in the file"""
+    line_bases: int
+    """The number of bases on each line but the last"""
+    line_width: int
+    """The number of bytes on each line but the last, with the line ending"""
+
+    def base_offset(self, position: int) -> int:
+        """The byte offset in the file of the base at a 0-based position"""
+        if self.line_bases == 0:
+            return self.offset
+        line, column = divmod(position, self.line_bases)
+        return self.offset + line * self.line_width + column
+
+
+class FaidxError(SeqError):
+    """An .fai index could not be built or read"""
+
+
+class UnknownSequenceError(SeqError):
+    """A sequence was asked for that isn't in the index
+
+    Attributes:
+        name: the name of the sequence
+    """
+
+    def __init__(self, name: str):
+        self.name = name
+        super().__init__(f"no sequence named {name!r} in the index")
+
+
+def index_path_for(fasta_path: str) -> str:
+    """The path of the index of a fasta file, which is its path plus .fai"""
+    return fasta_path + ".fai"
+
+
+def _check_not_gzipped(fasta_path: str, fp: BinaryIO):
+    """Raise an error if a file is gzipped, then go back to its start"""
+    if fp.read(len(GZIP_MAGIC)) == GZIP_MAGIC:
+        raise FaidxError(f"{fasta_path} is gzipped, so it can't be indexed")
+    fp.seek(0)
+
+
+def build_index(fasta_path: str, index_path: Optional[str] = None) -> List[FaiEntry]:
+    """Index a fasta file, writing the index next to it.
+
+    Args:
+        fasta_path: the fasta file to index
+        index_path: where to write the index. Default is the path of the
+            fasta file plus .fai.
+
+    Returns:
+        the index entry of each sequence in the file, in order
+
+    Raises:
+        FaidxError: if the file is gzipped, has sequence before the first
+            header, a sequence with lines of different lengths, or two
+            sequences with the same name
+    """
+    entries: List[FaiEntry] = []
+    names = set()
+    entry: Optional[FaiEntry] = None
+    # whether the last sequence line was shorter than the ones before it,
+    # which is only allowed for the last line of a sequence
+    short_line = False
+
+    with open(fasta_path, "rb") as fp:
+        _check_not_gzipped(fasta_path, fp)
+        offset = 0
+        for line_number, line in enumerate(fp, start=1):
+            stripped = line.rstrip(b"\r\n")
+            if stripped.startswith(b">"):
+                fields = stripped[1:].split(maxsplit=1)
+                if not fields:
+                    raise FaidxError(f"line {line_number}: header contains no name")
+                name = fields[0].decode("utf-8", "surrogateescape")
+                if name in names:
+                    raise FaidxError(f"line {line_number}: duplicate name {name!r}")
+                names.add(name)
+                entry = FaiEntry(name, 0, offset + len(line), 0, 0)
+                entries.append(entry)
+                short_line = False
+            elif entry is None:
+                if stripped:
+                    raise FaidxError(
+                        f"line {line_number}: sequence before the first header"
+                    )
+            elif stripped:
+                if entry.line_bases == 0:
+                    # the first line of the sequence, maybe after blank lines
+                    entry.offset = offset
+                    entry.line_bases = len(stripped)
+                    entry.line_width = len(line)
+                    short_line = False
+                elif (
+                    short_line
+                    or len(stripped) > entry.line_bases
+                    or len(line) > entry.line_width
+                ):
+                    raise FaidxError(
+                        f"line {line_number}: the lines of {entry.name!r} are "
+                        "different lengths"
+                    )
+                elif len(stripped) < entry.line_bases or len(line) < entry.line_width:
+                    short_line = True
+                entry.length += len(stripped)
+            else:
+                short_line = True
+            offset += len(line)
+
+    if index_path is None:
+        index_path = index_path_for(fasta_path)
+    with open(index_path, "w") as index_file:
+        for entry in entries:
+            print(
+                entry.name,
+                entry.length,
+                entry.offset,
+                entry.line_bases,
+                entry.line_width,
+                sep="\t",
+                file=index_file,
+            )
+    return entries
+
+
+def read_index(index_path: str) -> List[FaiEntry]:
+    """Read an .fai index
+
+    Any columns after the first five, like the quality offset column of
+    the index of a fastq file, are ignored.
+
+    Raises:
+        FaidxError: if a line has fewer than five columns, or a column
+            that should be a number isn't
+    """
+    entries = []
+    with open(index_path) as index_file:
+        for line_number, line in enumerate(index_file, start=1):
+            fields = line.rstrip("\r\n").split("\t")
+            if len(fields) < 5:
+                raise FaidxError(
+                    f"{index_path} line {line_number}: expected 5 columns, "
+                    f"found {len(fields)}"
+                )
+            try:
+                numbers = [int(field) for field in fields[1:5]]
+            except ValueError:
+                raise FaidxError(
+                    f"{index_path} line {line_number}: columns 2-5 must be numbers"
+                ) from None
+            entries.append(FaiEntry(fields[0], *numbers))
+    return entries
+
+
+class IndexedFasta:
+    """A fasta file that sequences can be fetched from by name
+
+    The index is read from `index_path`, or built with `build_index` if
+    it doesn't exist. This can be used as a context manager, closing the
+    file on exit:
+
+    >>> with IndexedFasta("assembly.fa") as fasta:
+    ...     contig = fasta.fetch("contig_1")
+    """
+
+    def __init__(self, fasta_path: str, index_path: Optional[str] = None):
+        """
+        Args:
+            fasta_path: the fasta file, which can't be gzipped
+            index_path: the .fai index of the file. Default is the path
+                of the fasta file plus .fai.
+        """
+        if index_path is None:
+            index_path = index_path_for(fasta_path)
+        if os.path.exists(index_path):
+            entries = read_index(index_path)
+        else:
+            entries = build_index(fasta_path, index_path)
+
+        self.index: Dict[str, FaiEntry] = {entry.name: entry for entry in entries}
+        """The index entry of each sequence, by name, in file order"""
+        self._fp = open(fasta_path, "rb")
+        _check_not_gzipped(fasta_path, self._fp)
+
+    def __len__(self) -> int:
+        return len(self.index)
+
+    def __contains__(self, name: str) -> bool:
+        return name in self.index
+
+    def _entry(self, name: str) -> FaiEntry:
+        try:
+            return self.index[name]
+        except KeyError:
+            raise UnknownSequenceError(name) from None
+
+    def _read(self, entry: FaiEntry, start: int, end: int) -> str:
+        """Read the bases from start to end of a sequence"""
+        if start >= end:
+            return ""
+        self._fp.seek(entry.base_offset(start))
+        data = self._fp.read(entry.base_offset(end - 1) + 1 - entry.base_offset(start))
+        return (
+            data.replace(b"\n", b"")
+            .replace(b"\r", b"")
+            .decode("ascii", "surrogateescape")
+        )
+
+    def fetch(self, name: str) -> Read:
+        """Fetch a whole sequence by name
+
+        Raises:
+            UnknownSequenceError: if there is no sequence with that name
+        """
+        entry = self._entry(name)
+        return Read(name, self._read(entry, 0, entry.length))
+
+    def fetch_range(self, name: str, start: int, end: int) -> Read:
+        """Fetch part of a sequence by name
+
+        Only the part of the file with the bases in the range is read.
+
+        Args:
+            name: the name of the sequence
+            start: the 0-based start of the range
+            end: the 0-based, exclusive end of the range
+
+        Returns:
+            the bases in the range, named `name:start-end`
+
+        Raises:
+            UnknownSequenceError: if there is no sequence with that name
+            CoordinateError: if the range is not within the sequence, or
+                its end is before its start
+        """
+        entry = self._entry(name)
+        if not 0 <= start <= end <= entry.length:
+            raise CoordinateError(name, start, end, entry.length)
+        return Read(f"{name}:{start}-{end}", self._read(entry, start, end))
+
+    def close(self):
+        """Close the fasta file"""
+        self._fp.close()
+
+    def __enter__(self) -> "IndexedFasta":
+        return self
+
+    def __exit__(self, *exc_info):
+        self.close()
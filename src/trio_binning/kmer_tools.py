@@ -0,0 +1,797 @@
+"""Tools for checking and working with lists of hap-mers.
+
+This is a script with a subcommand for each tool:
+
+  stats: the size, k and GC content of the two lists of k-mers, and how
+      many k-mers they share. The lists should share almost none, so a
+      high Jaccard index means something went wrong making them and
+      binning with them will be weak.
+  histogram: the k-mer spectrum of a parent's reads, i.e., how many
+      distinct k-mers are found once, twice, and so on, to choose the
+      --min-count for build-hapmers, with a suggested --min-count at the
+      valley between the error peak and the coverage peak. Like
+      build-hapmers, this keeps every distinct k-mer's count in memory,
+      unless --memory-limit is given to count them on disk.
+  estimate: estimate how many distinct k-mers some reads have, in a few
+      KB of memory, to size a full count before committing to it.
+  merge: sum the counts in several sorted k-mer count dumps, e.g., from
+      each of a parent's sequencing runs, into one sorted dump.
+  filter: drop the k-mers in a count dump with a count outside a range,
+      e.g., sequencing errors and extreme repeats.
+  top: the k-mers in each of some count dumps with the highest counts,
+      as FASTA, e.g., the most reliable hap-mers to design markers from.
+
+  profile: where along some reads each hap-mer hit is, as a BED file,
+      e.g., to find where the haplotype switches in a chimeric read.
+  simulate: write a simulated child's two haplotypes, their hap-mers and
+      reads labeled with the haplotype they came from, to check how well
+      reads like them are binned with some parameters.
+  evaluate: compare the bins in a classify-by-kmers report with the true
+      haplotype of each read, e.g., from simulate, as a confusion matrix
+      and each bin's precision and recall.
+
+merge and filter read and write one k-mer at a time, and top only keeps
+the k-mers it will write, so they take the same little memory however big
+the dumps are.
+"""
+
+import argparse
+import json
+import logging
+import os
+import sys
+from contextlib import ExitStack
+from itertools import chain
+from typing import Any, Dict, Iterable, Iterator, List, Optional, TextIO, Tuple
+
+from trio_binning import classify, count, evaluate, kmers, log, seq, sim
+from trio_binning.build_hapmers import (
+    add_disk_counting_arguments,
+    check_disk_counting_arguments,
+    count_kmers_in_files,
+    count_kmers_in_files_on_disk,
+    estimate_distinct_kmers_in_files,
+)
+from trio_binning.classify_by_kmers import load_kmer_set
+
+logger = logging.getLogger(__name__)
+
+
+def add_kmer_list_arguments(parser: argparse.ArgumentParser):
+    """Add the arguments for reading the two haplotypes' k-mer lists"""
+    parser.add_argument(
+        "haplotype_a_kmers",
+        help="a list of k-mers unique to haplotype A, one per line",
+    )
+    parser.add_argument(
+        "haplotype_b_kmers",
+        help="a list of k-mers unique to haplotype B, one per line",
+    )
+    parser.add_argument(
+        "-k",
+        "--kmer-size",
+        type=int,
+        help="k-mer size. Default is the length of the first k-mer in the lists.",
+    )
+    parser.add_argument(
+        "-m",
+        "--min-count",
+        type=int,
+        help="skip k-mers with a count less than this in the k-mer lists",
+    )
+    parser.add_argument(
+        "--kmer-format",
+        type=kmers.DumpFormat,
+        choices=list(kmers.DumpFormat),
+        default=kmers.DumpFormat.AUTO,
+        help="format of the k-mer lists",
+    )
+    parser.add_argument(
+        "--load-kmers",
+        action="store_true",
+        default=False,
+        help="the k-mer lists are binary files written with classify-by-kmers "
+        "--save-kmers",
+    )
+
+
+def add_count_dump_arguments(parser: argparse.ArgumentParser):
+    """Add the arguments for reading and writing k-mer count dumps"""
+    parser.add_argument(
+        "-k",
+        "--kmer-size",
+        type=int,
+        help="k-mer size. Default is the length of the first k-mer in the dumps.",
+    )
+    parser.add_argument(
+        "--kmer-format",
+        type=kmers.DumpFormat,
+        choices=[
+            dump_format
+            for dump_format in kmers.DumpFormat
+            if dump_format != kmers.DumpFormat.PLAIN
+        ],
+        default=kmers.DumpFormat.AUTO,
+        help="format of the dumps",
+    )
+    parser.add_argument(
+        "-o",
+        "--output",
+        default="-",
+        help="file to write the k-mer counts to, gzipped if it ends in .gz. Default "
+        "is stdout.",
+    )
+    log.add_verbose_argument(parser)
+
+
+def parse_args():
+    """Parse arguments"""
+    parser = argparse.ArgumentParser(
+        description=__doc__, formatter_class=argparse.RawDescriptionHelpFormatter
+    )
+    subparsers = parser.add_subparsers(dest="command", required=True)
+
+    stats_parser = subparsers.add_parser(
+        "stats",
+        help="print statistics about two k-mer lists and how much they overlap",
+        formatter_class=argparse.ArgumentDefaultsHelpFormatter,
+    )
+    add_kmer_list_arguments(stats_parser)
+    stats_parser.add_argument(
+        "--json",
+        action="store_true",
+        default=False,
+        help="print the statistics as JSON instead of as text",
+    )
+    log.add_verbose_argument(stats_parser)
+
+    histogram_parser = subparsers.add_parser(
+        "histogram",
+        help="print the k-mer spectrum of some reads and suggest a --min-count",
+        description="Count the canonical k-mers in some reads, e.g., one parent's, "
+        "and print how many distinct k-mers have each count, as 'count<TAB>number "
+        "of k-mers' lines, leaving out counts that no k-mer has. A suggested "
+        "--min-count for build-hapmers is printed to stderr. Like build-hapmers, "
+        "this keeps every distinct k-mer's count in memory, taking on the order of "
+        "100 bytes each, unless --memory-limit is given.",
+        formatter_class=argparse.ArgumentDefaultsHelpFormatter,
+    )
+    histogram_parser.add_argument(
+        "read_files",
+        nargs="+",
+        help="files of reads to count k-mers in together, in fasta or fastq "
+        "format, uncompressed or gzipped",
+    )
+    histogram_parser.add_argument("-k", "--kmer-size", type=int, required=True)
+    histogram_parser.add_argument(
+        "--max-count",
+        type=int,
+        default=1000,
+        help="the highest count to print on its own line. The k-mers with higher "
+        "counts are added to its line.",
+    )
+    histogram_parser.add_argument(
+        "--ignore-softmasked",
+        action="store_true",
+        default=False,
+        help="skip k-mers with lowercase (soft-masked) bases instead of treating "
+        "lowercase like uppercase",
+    )
+    histogram_parser.add_argument(
+        "--compress-homopolymers",
+        action="store_true",
+        default=False,
+        help="count k-mers in homopolymer-compressed reads, as build-hapmers "
+        "--compress-homopolymers does",
+    )
+    histogram_parser.add_argument(
+        "-t",
+        "--threads",
+        type=int,
+        default=1,
+        help="number of threads to count k-mers with, as with build-hapmers",
+    )
+    add_disk_counting_arguments(histogram_parser)
+    log.add_verbose_argument(histogram_parser)
+
+    estimate_parser = subparsers.add_parser(
+        "estimate",
+        help="estimate how many distinct k-mers some reads have",
+        description="Estimate the number of distinct canonical k-mers in some "
+        "reads with a HyperLogLog sketch, which reads through them once with "
+        "2^precision bytes of memory, e.g., to size a count of a big dataset "
+        "before starting it. Prints the estimate and its expected relative "
+        "standard error, which is about 1.04 / sqrt(2^precision).",
+        formatter_class=argparse.ArgumentDefaultsHelpFormatter,
+    )
+    estimate_parser.add_argument(
+        "read_files",
+        nargs="+",
+        help="files of reads to estimate the k-mers of together, in fasta or "
+        "fastq format, uncompressed or gzipped",
+    )
+    estimate_parser.add_argument("-k", "--kmer-size", type=int, required=True)
+    estimate_parser.add_argument(
+        "-p",
+        "--precision",
+        type=int,
+        default=kmers.DEFAULT_HLL_PRECISION,
+        help=f"the sketch has 2^precision registers, from {kmers.MIN_HLL_PRECISION} "
+        f"to {kmers.MAX_HLL_PRECISION}. Each one more doubles the memory and cuts "
+        "the error by about 1.4 times.",
+    )
+    estimate_parser.add_argument(
+        "--ignore-softmasked",
+        action="store_true",
+        default=False,
+        help="skip k-mers with lowercase (soft-masked) bases instead of treating "
+        "lowercase like uppercase",
+    )
+    estimate_parser.add_argument(
+        "--compress-homopolymers",
+        action="store_true",
+        default=False,
+        help="estimate the k-mers of homopolymer-compressed reads, as "
+        "build-hapmers --compress-homopolymers counts",
+    )
+    estimate_parser.add_argument(
+        "-t",
+        "--threads",
+        type=int,
+        default=1,
+        help="number of threads to hash k-mers with, each keeping its own sketch",
+    )
+    log.add_verbose_argument(estimate_parser)
+
+    merge_parser = subparsers.add_parser(
+        "merge",
+        help="sum the counts in several sorted k-mer count dumps",
+        description="Merge k-mer count dumps, e.g., meryl print or kmc_dump output, "
+        "summing the counts of k-mers in more than one, and write the merged "
+        "counts as 'k-mer<TAB>count' lines. Each dump must be sorted by k-mer, with "
+        "each k-mer only once, as k-mer counters dump them, and the merged dump is "
+        "sorted the same way, so it can be merged again.",
+        formatter_class=argparse.ArgumentDefaultsHelpFormatter,
+    )
+    merge_parser.add_argument("dumps", nargs="+", help="k-mer count dumps to merge")
+    add_count_dump_arguments(merge_parser)
+
+    filter_parser = subparsers.add_parser(
+        "filter",
+        help="drop k-mers with a count outside a range from a k-mer count dump",
+        description="Write the k-mers in a count dump with a count of at least "
+        "--min and at most --max as 'k-mer<TAB>count' lines, in the same order.",
+        formatter_class=argparse.ArgumentDefaultsHelpFormatter,
+    )
+    filter_parser.add_argument("dump", help="k-mer count dump to filter")
+    filter_parser.add_argument(
+        "--min",
+        type=int,
+        dest="min_count",
+        help="drop k-mers with a lower count than this, e.g., sequencing errors",
+    )
+    filter_parser.add_argument(
+        "--max",
+        type=int,
+        dest="max_count",
+        help="drop k-mers with a higher count than this, e.g., extreme repeats",
+    )
+    add_count_dump_arguments(filter_parser)
+
+    top_parser = subparsers.add_parser(
+        "top",
+        help="write the k-mers with the highest counts in count dumps as FASTA",
+        description="Write the --n k-mers with the highest counts in each count "
+        "dump, e.g., hap-mer lists with counts, as FASTA records from the highest "
+        "count down, named after the dump, like '>hapA_only_kmers_1 count=57', so "
+        "that they can go straight into primer design. K-mers of the same count "
+        "are in the dump's order.",
+        formatter_class=argparse.ArgumentDefaultsHelpFormatter,
+    )
+    top_parser.add_argument("dumps", nargs="+", help="k-mer count dumps")
+    top_parser.add_argument(
+        "-n", "--n", type=int, default=1000, help="number of k-mers from each dump"
+    )
+    top_parser.add_argument(
+        "--min-gc",
+        type=float,
+        default=0,
+        help="leave out k-mers with a lower fraction of G's and C's than this",
+    )
+    top_parser.add_argument(
+        "--max-gc",
+        type=float,
+        default=1,
+        help="leave out k-mers with a higher fraction of G's and C's than this",
+    )
+    top_parser.add_argument(
+        "--max-homopolymer",
+        type=int,
+        help="leave out k-mers with a run of the same base longer than this",
+    )
+    add_count_dump_arguments(top_parser)
+
+    profile_parser = subparsers.add_parser(
+        "profile",
+        help="write where the hap-mers in some reads are to a BED file",
+        description="Find every hap-mer in some reads and write where it is as a "
+        "BED interval: the read's name, the 0-based start of the k-mer, its end, "
+        "and the haplotype, A or B. A k-mer in both lists is only written for "
+        "haplotype A, as when classifying.",
+        formatter_class=argparse.ArgumentDefaultsHelpFormatter,
+    )
+    profile_parser.add_argument(
+        "reads",
+        help="reads to profile, in fasta/q, SAM or BAM format. Can be gzipped.",
+    )
+    add_kmer_list_arguments(profile_parser)
+    profile_parser.add_argument(
+        "--read-id",
+        action="append",
+        dest="read_ids",
+        metavar="ID",
+        help="only profile the read with this name. Can be given more than once. "
+        "Default is to profile every read.",
+    )
+    profile_parser.add_argument(
+        "--ignore-softmasked",
+        action="store_true",
+        default=False,
+        help="skip k-mers with lowercase (soft-masked) bases, as classify-by-kmers "
+        "--ignore-softmasked does",
+    )
+    profile_parser.add_argument(
+        "-o",
+        "--output",
+        default="-",
+        help="BED file to write, gzipped if it ends in .gz. Default is stdout.",
+    )
+    log.add_verbose_argument(profile_parser)
+
+    simulate_parser = subparsers.add_parser(
+        "simulate",
+        help="write simulated haplotypes, hap-mers and child reads with known truth",
+        description="Simulate a child's two haplotypes as random sequence, with "
+        "heterozygous substitutions between them, and reads drawn from both, with "
+        "sequencing errors. Writes PREFIX.hapA.fasta and PREFIX.hapB.fasta with the "
+        "haplotypes, PREFIX.hapA.txt and PREFIX.hapB.txt with the k-mers specific "
+        "to each, PREFIX.reads.fastq.gz with the reads, and PREFIX.truth.tsv with "
+        "the haplotype, 0-based start and strand of each read. Classify the reads "
+        "and compare the bins with the truth to see how well some parameters do.",
+        formatter_class=argparse.ArgumentDefaultsHelpFormatter,
+    )
+    simulate_parser.add_argument(
+        "-o", "--output-prefix", default="sim", help="prefix of the files to write"
+    )
+    simulate_parser.add_argument(
+        "--genome-length", type=int, default=1000000, help="length of each haplotype"
+    )
+    simulate_parser.add_argument(
+        "--het-rate",
+        type=float,
+        default=0.001,
+        help="fraction of the sites that differ between the haplotypes",
+    )
+    simulate_parser.add_argument(
+        "--read-length", type=int, default=10000, help="length of each read"
+    )
+    simulate_parser.add_argument(
+        "--coverage",
+        type=float,
+        default=10.0,
+        help="total length of the reads over the genome length",
+    )
+    simulate_parser.add_argument(
+        "--error-rate",
+        type=float,
+        default=0.001,
+        help="probability of a substitution error at each base of the reads",
+    )
+    simulate_parser.add_argument("-k", "--kmer-size", type=int, default=21)
+    simulate_parser.add_argument(
+        "--seed",
+        type=int,
+        help="seed for the random number generator, to simulate the same data "
+        "again. Default is a different seed every time.",
+    )
+    log.add_verbose_argument(simulate_parser)
+
+    evaluate_parser = subparsers.add_parser(
+        "evaluate",
+        help="compare the bins in a classify-by-kmers report with the truth",
+        description="Compare the bins in a classify-by-kmers --report with the true "
+        "haplotype of each read, and print the confusion matrix of true haplotypes "
+        "and bins, the precision and recall of each haplotype's bin and the "
+        "fraction of reads left unknown, for reads and for bases. Reads in only "
+        "one of the files are counted separately and otherwise left out.",
+        formatter_class=argparse.ArgumentDefaultsHelpFormatter,
+    )
+    evaluate_parser.add_argument(
+        "report", help="the per-read report from classify-by-kmers --report"
+    )
+    evaluate_parser.add_argument(
+        "truth",
+        help="a table of read ids and their true haplotypes, A or B, separated by "
+        "tabs, like the truth table from simulate. Other columns are ignored.",
+    )
+    evaluate_parser.add_argument(
+        "--json",
+        action="store_true",
+        default=False,
+        help="print the evaluation as JSON instead of as text",
+    )
+    log.add_verbose_argument(evaluate_parser)
+
+    args = parser.parse_args()
+    if args.command == "filter":
+        if args.min_count is None and args.max_count is None:
+            filter_parser.error("at least one of --min and --max is needed")
+        if (
+            args.min_count is not None
+            and args.max_count is not None
+            and args.min_count > args.max_count
+        ):
+            filter_parser.error(
+                f"--min ({args.min_count}) is more than --max ({args.max_count})"
+            )
+    if args.command == "top":
+        if args.n < 1:
+            parser.error(f"--n must be at least 1, not {args.n}")
+        if not 0 <= args.min_gc <= args.max_gc <= 1:
+            parser.error("--min-gc and --max-gc must be from 0 to 1, in order")
+        if args.max_homopolymer is not None and args.max_homopolymer < 1:
+            parser.error(
+                f"--max-homopolymer must be at least 1, not {args.max_homopolymer}"
+            )
+    if args.command == "histogram":
+        if args.max_count < 1:
+            parser.error(f"--max-count must be at least 1, not {args.max_count}")
+        if args.threads < 1:
+            parser.error(f"--threads must be at least 1, not {args.threads}")
+        check_disk_counting_arguments(histogram_parser, args)
+    return args
+
+
+def kmer_set_stats(hap_a: kmers.KmerSet, hap_b: kmers.KmerSet) -> Dict[str, Any]:
+    """The statistics printed by `stats`, as a dict that can be serialized as
+    JSON"""
+    haplotypes = {}
+    for hap, kmer_set in [("hap_a", hap_a), ("hap_b", hap_b)]:
+        stats = kmer_set.stats()
+        haplotypes[hap] = {
+            "path": kmer_set.path,
+            "kmers": stats.num_kmers,
+            "k": stats.k,
+            "gc_content": stats.gc_content,
+        }
+    return {
+        **haplotypes,
+        "shared_kmers": hap_a.intersection_size(hap_b),
+        "jaccard": hap_a.jaccard(hap_b),
+    }
+
+
+def stats(args: argparse.Namespace):
+    """Print statistics about two k-mer lists"""
+    hap_a = load_kmer_set(
+        args.haplotype_a_kmers,
+        args.kmer_size,
+        args.min_count,
+        args.kmer_format,
+        args.load_kmers,
+    )
+    hap_b = load_kmer_set(
+        args.haplotype_b_kmers,
+        args.kmer_size,
+        args.min_count,
+        args.kmer_format,
+        args.load_kmers,
+    )
+    try:
+        kmers.check_same_k(hap_a, hap_b)
+    except kmers.KmerSizeMismatchError as e:
+        sys.exit(f"Error: {e}")
+
+    set_stats = kmer_set_stats(hap_a, hap_b)
+    if args.json:
+        json.dump(set_stats, sys.stdout, indent=2)
+        print()
+        return
+
+    for hap in ["hap_a", "hap_b"]:
+        hap_stats = set_stats[hap]
+        print(
+            f"{hap_stats['path']}: {hap_stats['kmers']} {hap_stats['k']}-mers, "
+            f"{100 * hap_stats['gc_content']:.1f}% GC"
+        )
+    print(f"Shared k-mers: {set_stats['shared_kmers']}")
+    print(f"Jaccard index: {set_stats['jaccard']:.6g}")
+
+
+def histogram(args: argparse.Namespace):
+    """Print the k-mer spectrum of some reads and a suggested minimum count"""
+    if args.memory_limit is None:
+        kmer_counts = count_kmers_in_files(
+            args.read_files,
+            args.kmer_size,
+            args.ignore_softmasked,
+            args.compress_homopolymers,
+            args.threads,
+        )
+        spectrum = count.histogram(kmer_counts.values(), args.max_count)
+    else:
+        with count_kmers_in_files_on_disk(
+            args.read_files,
+            args.kmer_size,
+            args.ignore_softmasked,
+            args.compress_homopolymers,
+            args.memory_limit << 20,
+            args.tmpdir,
+        ) as disk_counts:
+            spectrum = count.histogram(
+                (kmer_count for _, kmer_count in disk_counts), args.max_count
+            )
+    for kmer_count, num_kmers in enumerate(spectrum):
+        if num_kmers:
+            print(kmer_count, num_kmers, sep="\t")
+
+    min_count = count.suggest_min_count(spectrum)
+    if min_count is None:
+        print(
+            "Found no valley between an error peak and a coverage peak to suggest "
+            "a --min-count from",
+            file=sys.stderr,
+        )
+    else:
+        print(f"Suggested --min-count: {min_count}", file=sys.stderr)
+
+
+def estimate(args: argparse.Namespace):
+    """Print an estimate of the number of distinct k-mers in some reads"""
+    estimator = estimate_distinct_kmers_in_files(
+        args.read_files,
+        args.kmer_size,
+        args.ignore_softmasked,
+        args.compress_homopolymers,
+        args.threads,
+        args.precision,
+    )
+    print(f"Estimated distinct {args.kmer_size}-mers: {estimator.estimate()}")
+    print(f"Relative standard error: {100 * estimator.relative_error:.2g}%")
+
+
+def read_count_dumps(
+    stack: ExitStack,
+    paths: List[str],
+    k: Optional[int],
+    dump_format: kmers.DumpFormat,
+) -> Tuple[int, List[Iterator[Tuple[int, int]]]]:
+    """Open some count dumps, which must all have the same k
+
+    Each dump's first k-mer is read to find k, if it isn't given.
+
+    Returns:
+        k, and each dump's k-mers in integer format and their counts
+
+    Raises:
+        KmerFileError: if a dump can't be parsed, or has a different k
+    """
+    dumps = []
+    for path in paths:
+        fp = stack.enter_context(seq.open_text_read(path))
+        dump = kmers.iter_kmer_dump_counts(fp, k, None, dump_format)
+        first = next(dump, None)
+        if first is None:
+            # only possible if k is given, since k can't be found otherwise
+            dumps.append(iter(()))
+            continue
+        # the later dumps are read with this k, so they must have it too
+        k = first[0]
+        dumps.append(
+            (kmer_int, kmer_count) for _, kmer_int, kmer_count in chain([first], dump)
+        )
+    assert k is not None
+    return k, dumps
+
+
+def write_counts(counts: Iterable[Tuple[int, int]], k: int, fp: TextIO):
+    """Write k-mer counts as 'k-mer<TAB>count' lines"""
+    for kmer_int, kmer_count in counts:
+        print(kmers.int_to_kmer(kmer_int, k), kmer_count, sep="\t", file=fp)
+
+
+def merge(args: argparse.Namespace):
+    """Merge sorted k-mer count dumps"""
+    with ExitStack() as stack:
+        k, dumps = read_count_dumps(stack, args.dumps, args.kmer_size, args.kmer_format)
+        out = seq.open_text_write(args.output)
+        if out is not sys.stdout:
+            stack.enter_context(out)
+        write_counts(count.merge(dumps, k, args.dumps), k, out)
+
+
+def filter_dump(args: argparse.Namespace):
+    """Filter a k-mer count dump by count"""
+    with ExitStack() as stack:
+        k, [dump] = read_count_dumps(
+            stack, [args.dump], args.kmer_size, args.kmer_format
+        )
+        out = seq.open_text_write(args.output)
+        if out is not sys.stdout:
+            stack.enter_context(out)
+        write_counts(count.filter_counts(dump, args.min_count, args.max_count), k, out)
+
+
+def dump_name(path: str) -> str:
+    """What to name the k-mers of a dump in `top`: its file name without
+    extensions, or stdin for -"""
+    if path == "-":
+        return "stdin"
+    return os.path.basename(path).split(".")[0]
+
+
+def top(args: argparse.Namespace):
+    """Write the k-mers with the highest counts in each dump as FASTA"""
+    with ExitStack() as stack:
+        k, dumps = read_count_dumps(stack, args.dumps, args.kmer_size, args.kmer_format)
+        out = seq.open_text_write(args.output)
+        if out is not sys.stdout:
+            stack.enter_context(out)
+        for path, dump in zip(args.dumps, dumps):
+            kept = (
+                (kmer_int, kmer_count)
+                for kmer_int, kmer_count in dump
+                if _keep_top_kmer(kmers.int_to_kmer(kmer_int, k), args)
+            )
+            name = dump_name(path)
+            for rank, (kmer_int, kmer_count) in enumerate(
+                count.top_counts(kept, args.n), 1
+            ):
+                print(
+                    f">{name}_{rank} count={kmer_count}",
+                    kmers.int_to_kmer(kmer_int, k),
+                    sep="\n",
+                    file=out,
+                )
+
+
+def _keep_top_kmer(kmer: str, args: argparse.Namespace) -> bool:
+    if not args.min_gc <= kmers.gc_content(kmer) <= args.max_gc:
+        return False
+    return (
+        args.max_homopolymer is None
+        or kmers.longest_homopolymer(kmer) <= args.max_homopolymer
+    )
+
+
+def profile(args: argparse.Namespace):
+    """Write where the hap-mers in some reads are"""
+    hap_a = load_kmer_set(
+        args.haplotype_a_kmers,
+        args.kmer_size,
+        args.min_count,
+        args.kmer_format,
+        args.load_kmers,
+    )
+    hap_b = load_kmer_set(
+        args.haplotype_b_kmers,
+        args.kmer_size,
+        args.min_count,
+        args.kmer_format,
+        args.load_kmers,
+    )
+    try:
+        kmers.check_same_k(hap_a, hap_b)
+    except kmers.KmerSizeMismatchError as e:
+        sys.exit(f"Error: {e}")
+
+    read_ids = None if args.read_ids is None else set(args.read_ids)
+    out = seq.open_text_write(args.output)
+    try:
+        for read in seq.open_records(args.reads):
+            if read_ids is not None:
+                if read.name not in read_ids:
+                    continue
+                read_ids.remove(read.name)
+            for start, read_bin in classify.profile(
+                read.seq, hap_a, hap_b, args.ignore_softmasked
+            ):
+                end = start + hap_a.k
+                print(read.name, start, end, read_bin.value, sep="\t", file=out)
+            if read_ids is not None and not read_ids:
+                # every read asked for has been found, so stop reading
+                break
+    finally:
+        if out is not sys.stdout:
+            out.close()
+
+    if read_ids:
+        for read_id in sorted(read_ids):
+            logger.warning("Read %s isn't in %s", read_id, args.reads)
+
+
+def simulate(args: argparse.Namespace):
+    """Write simulated trio data"""
+    trio = sim.simulate_trio(
+        args.genome_length,
+        args.het_rate,
+        args.read_length,
+        args.coverage,
+        args.error_rate,
+        args.seed,
+        args.kmer_size,
+    )
+    prefix = args.output_prefix
+    for hap, hap_seq, kmer_set in [
+        ("hapA", trio.hap_a, trio.hap_a_kmers),
+        ("hapB", trio.hap_b, trio.hap_b_kmers),
+    ]:
+        with open(f"{prefix}.{hap}.fasta", "w") as fasta:
+            seq.write_record(seq.Read(hap, hap_seq), fasta)
+        kmer_set.to_file(f"{prefix}.{hap}.txt")
+    with seq.open_text_write(f"{prefix}.reads.fastq.gz") as reads_file:
+        for simulated_read in trio.reads:
+            seq.write_record(simulated_read.read, reads_file)
+    with open(f"{prefix}.truth.tsv", "w") as truth_file:
+        print("read_id", "haplotype", "start", "strand", sep="\t", file=truth_file)
+        for simulated_read in trio.reads:
+            print(
+                simulated_read.read.name,
+                simulated_read.truth.value,
+                simulated_read.start,
+                "-" if simulated_read.reverse else "+",
+                sep="\t",
+                file=truth_file,
+            )
+    logger.info(
+        "Simulated %d reads and %d and %d hap-mers",
+        len(trio.reads),
+        len(trio.hap_a_kmers),
+        len(trio.hap_b_kmers),
+    )
+
+
+def evaluate_report(args: argparse.Namespace):
+    """Print how well the bins in a report match the truth"""
+    with seq.open_text_read(args.truth) as truth_file:
+        truth = evaluate.read_truth(truth_file)
+    with seq.open_text_read(args.report) as report:
+        evaluation = evaluate.evaluate(report, truth)
+    if args.json:
+        json.dump(evaluation.to_dict(), sys.stdout, indent=2)
+        print()
+    else:
+        evaluation.print_table(sys.stdout)
+
+
+@seq.exit_on_broken_pipe
+def main():
+    """Main method of program"""
+    args = parse_args()
+    log.configure(args.verbose)
+    if args.command == "stats":
+        stats(args)
+    elif args.command == "histogram":
+        histogram(args)
+    elif args.command == "profile":
+        profile(args)
+    else:
+        try:
+            if args.command == "estimate":
+                estimate(args)
+            elif args.command == "merge":
+                merge(args)
+            elif args.command == "filter":
+                filter_dump(args)
+            elif args.command == "top":
+                top(args)
+            elif args.command == "simulate":
+                simulate(args)
+            elif args.command == "evaluate":
+                evaluate_report(args)
+        except ValueError as e:
+            sys.exit(f"Error: {e}")
+
+
+if __name__ == "__main__":
+    main()
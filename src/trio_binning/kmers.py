@@ -11,21 +11,57 @@ Found 6 13-mers in file.
 >>> kmers.count_kmers_in_read("GAGGAGATTTAGAGTGTGAGTCGAGCATAGAGATATATA", hapA, hapB)
 (1, 2)
 """
+import logging
+import math
+import mmap
+import os
+import re
+import struct
 import sys
+from abc import ABC, abstractmethod
+from array import array
 from ctypes import (
     POINTER,
     Structure,
+    addressof,
     byref,
+    c_char,
     c_char_p,
     c_int,
+    c_size_t,
     c_ubyte,
+    c_uint,
+    c_uint32,
     c_uint64,
+    c_void_p,
+    cast,
     cdll,
     pointer,
 )
+from dataclasses import dataclass
+from enum import Enum, IntEnum
+from functools import total_ordering
+from glob import glob
 from importlib.machinery import EXTENSION_SUFFIXES
-from os.path import dirname, isfile, join
-from typing import TYPE_CHECKING, Tuple
+from itertools import chain, islice
+from os.path import dirname, isdir, isfile, join
+from typing import (
+    TYPE_CHECKING,
+    BinaryIO,
+    Dict,
+    Iterable,
+    Iterator,
+    List,
+    NamedTuple,
+    Optional,
+    TextIO,
+    Tuple,
+    Union,
+)
+
+from trio_binning.seq import PHRED_OFFSET, TEXT_ENCODING, TEXT_ERRORS, rna_to_dna
+
+logger = logging.getLogger(__name__)
 
 correct_library_file = ""
 for extension in EXTENSION_SUFFIXES:
@@ -56,6 +92,7 @@ class _HashSet(Structure):
         ("hash_size", c_int),
         ("k", c_ubyte),
         ("num_kmers", c_int),
+        ("hasher", c_ubyte),
     ]
 
 
@@ -63,6 +100,44 @@ create_kmer_hash_set_c = lib.create_kmer_hash_set
 create_kmer_hash_set_c.argtypes = [c_char_p]
 create_kmer_hash_set_c.restype = POINTER(_HashSet)
 
+initialize_hash_set_c = lib.initialize_hash_set
+initialize_hash_set_c.argtypes = [c_int, c_uint64]
+initialize_hash_set_c.restype = POINTER(_HashSet)
+
+initialize_hash_set_with_hasher_c = lib.initialize_hash_set_with_hasher
+initialize_hash_set_with_hasher_c.argtypes = [c_int, c_uint64, c_ubyte]
+initialize_hash_set_with_hasher_c.restype = POINTER(_HashSet)
+
+free_hash_set_c = lib.free_hash_set
+free_hash_set_c.argtypes = [POINTER(_HashSet)]
+
+hash_set_difference_c = lib.hash_set_difference
+hash_set_difference_c.argtypes = [POINTER(_HashSet), POINTER(_HashSet)]
+hash_set_difference_c.restype = POINTER(_HashSet)
+
+hash_set_intersection_c = lib.hash_set_intersection
+hash_set_intersection_c.argtypes = [POINTER(_HashSet), POINTER(_HashSet)]
+hash_set_intersection_c.restype = POINTER(_HashSet)
+
+hash_set_intersection_size_c = lib.hash_set_intersection_size
+hash_set_intersection_size_c.argtypes = [POINTER(_HashSet), POINTER(_HashSet)]
+hash_set_intersection_size_c.restype = c_uint64
+
+hash_set_gc_count_c = lib.hash_set_gc_count
+hash_set_gc_count_c.argtypes = [POINTER(_HashSet)]
+hash_set_gc_count_c.restype = c_uint64
+
+add_int_to_hash_c = lib.add_int_to_hash
+add_int_to_hash_c.argtypes = [POINTER(_HashSet), c_uint64]
+add_int_to_hash_c.restype = c_ubyte
+
+add_ints_to_hash_c = lib.add_ints_to_hash
+add_ints_to_hash_c.argtypes = [POINTER(_HashSet), POINTER(c_uint64), c_uint64]
+
+int_in_hash_set_c = lib.int_in_hash_set
+int_in_hash_set_c.argtypes = [c_uint64, POINTER(_HashSet)]
+int_in_hash_set_c.restype = c_ubyte
+
 count_kmers_in_read_c = lib.count_kmers_in_read
 count_kmers_in_read_c.argtypes = [
     c_char_p,
@@ -72,88 +147,3226 @@ count_kmers_in_read_c.argtypes = [
     POINTER(c_int),
 ]
 
+count_kmers_in_read_skipping_c = lib.count_kmers_in_read_skipping
+count_kmers_in_read_skipping_c.argtypes = [
+    c_char_p,
+    POINTER(_HashSet),
+    POINTER(_HashSet),
+    POINTER(c_int),
+    POINTER(c_int),
+    POINTER(c_int),
+    c_char,
+]
+
+count_kmers_in_read_lookup_c = lib.count_kmers_in_read_lookup
+count_kmers_in_read_lookup_c.argtypes = [
+    c_char_p,
+    c_char_p,
+    c_ubyte,
+    c_void_p,
+    c_void_p,
+    c_void_p,
+    c_void_p,
+    POINTER(c_int),
+    POINTER(c_int),
+    POINTER(c_int),
+    c_char,
+    c_char,
+    c_uint,
+]
+
+canonical_kmers_in_read_c = lib.canonical_kmers_in_read
+canonical_kmers_in_read_c.argtypes = [c_char_p, c_ubyte, c_char, POINTER(c_uint64)]
+canonical_kmers_in_read_c.restype = c_size_t
+
+spaced_kmers_in_read_c = lib.spaced_kmers_in_read
+spaced_kmers_in_read_c.argtypes = [
+    c_char_p,
+    c_uint32,
+    c_ubyte,
+    c_char,
+    POINTER(c_uint64),
+]
+spaced_kmers_in_read_c.restype = c_size_t
+
+count_spaced_kmers_in_read_lookup_c = lib.count_spaced_kmers_in_read_lookup
+count_spaced_kmers_in_read_lookup_c.argtypes = [
+    c_char_p,
+    c_char_p,
+    c_uint32,
+    c_ubyte,
+    c_void_p,
+    c_void_p,
+    c_void_p,
+    c_void_p,
+    POINTER(c_int),
+    POINTER(c_int),
+    POINTER(c_int),
+    c_char,
+    c_char,
+    c_uint,
+]
+
+pack_seq_c = lib.pack_seq
+pack_seq_c.argtypes = [
+    c_char_p,
+    c_size_t,
+    c_char,
+    POINTER(c_uint64),
+    POINTER(c_uint64),
+]
+pack_seq_c.restype = c_size_t
+
+count_kmers_in_packed_lookup_c = lib.count_kmers_in_packed_lookup
+count_kmers_in_packed_lookup_c.argtypes = [
+    POINTER(c_uint64),
+    POINTER(c_uint64),
+    c_size_t,
+    c_size_t,
+    c_size_t,
+    c_ubyte,
+    c_void_p,
+    c_void_p,
+    c_void_p,
+    c_void_p,
+    POINTER(c_int),
+    POINTER(c_int),
+    POINTER(c_int),
+    c_uint,
+]
+
+count_minimizers_in_read_lookup_c = lib.count_minimizers_in_read_lookup
+count_minimizers_in_read_lookup_c.argtypes = [
+    c_char_p,
+    c_char_p,
+    c_ubyte,
+    c_uint,
+    c_void_p,
+    c_void_p,
+    c_void_p,
+    c_void_p,
+    POINTER(c_int),
+    POINTER(c_int),
+    POINTER(c_int),
+    POINTER(c_int),
+    c_char,
+    c_char,
+]
+
+count_syncmers_in_read_lookup_c = lib.count_syncmers_in_read_lookup
+count_syncmers_in_read_lookup_c.argtypes = [
+    c_char_p,
+    c_char_p,
+    c_ubyte,
+    c_ubyte,
+    c_void_p,
+    c_void_p,
+    c_void_p,
+    c_void_p,
+    POINTER(c_int),
+    POINTER(c_int),
+    POINTER(c_int),
+    POINTER(c_int),
+    c_char,
+    c_char,
+]
+
+invertible_hash_c = lib.invertible_hash
+invertible_hash_c.argtypes = [c_uint64, c_uint64]
+invertible_hash_c.restype = c_uint64
+
+count_kmers_in_read_fuzzy_c = lib.count_kmers_in_read_fuzzy
+count_kmers_in_read_fuzzy_c.argtypes = [
+    c_char_p,
+    c_char_p,
+    c_ubyte,
+    c_void_p,
+    c_void_p,
+    c_void_p,
+    c_void_p,
+    POINTER(c_int),
+    POINTER(c_int),
+    POINTER(c_int),
+    POINTER(c_int),
+    POINTER(c_int),
+    c_char,
+    c_char,
+    c_uint,
+]
+
+hash_set_lookup_c = cast(lib.hash_set_lookup, c_void_p)
+bloom_filter_lookup_c = cast(lib.bloom_filter_lookup, c_void_p)
+sorted_kmers_lookup_c = cast(lib.sorted_kmers_lookup, c_void_p)
+mapped_kmers_lookup_c = cast(lib.mapped_kmers_lookup, c_void_p)
+
+
+class _BloomFilter(Structure):
+    """Container for a c struct containing a k-mer Bloom filter
+
+    Like `_HashSet`, this is just so that ctypes knows what the C
+    functions that take or return a pointer to the struct are passing
+    around.
+    """
+
+    _fields_: list = [
+        ("bits", POINTER(c_uint64)),
+        ("num_bits", c_uint64),
+        ("num_hashes", c_ubyte),
+        ("k", c_ubyte),
+        ("num_kmers", c_uint64),
+    ]
+
+
+initialize_bloom_filter_c = lib.initialize_bloom_filter
+initialize_bloom_filter_c.argtypes = [c_int, c_uint64, c_ubyte]
+initialize_bloom_filter_c.restype = POINTER(_BloomFilter)
+
+free_bloom_filter_c = lib.free_bloom_filter
+free_bloom_filter_c.argtypes = [POINTER(_BloomFilter)]
+
+add_ints_to_bloom_filter_c = lib.add_ints_to_bloom_filter
+add_ints_to_bloom_filter_c.argtypes = [
+    POINTER(_BloomFilter),
+    POINTER(c_uint64),
+    c_uint64,
+]
+
+int_in_bloom_filter_c = lib.int_in_bloom_filter
+int_in_bloom_filter_c.argtypes = [c_uint64, POINTER(_BloomFilter)]
+int_in_bloom_filter_c.restype = c_ubyte
+
+add_ints_to_hyperloglog_c = lib.add_ints_to_hyperloglog
+add_ints_to_hyperloglog_c.argtypes = [
+    POINTER(c_ubyte),
+    c_ubyte,
+    POINTER(c_uint64),
+    c_uint64,
+]
+
+add_ints_to_counting_bloom_c = lib.add_ints_to_counting_bloom
+add_ints_to_counting_bloom_c.argtypes = [
+    POINTER(c_ubyte),
+    c_uint64,
+    c_ubyte,
+    POINTER(c_uint64),
+    c_uint64,
+]
+
+counting_bloom_counts_c = lib.counting_bloom_counts
+counting_bloom_counts_c.argtypes = [
+    POINTER(c_ubyte),
+    c_uint64,
+    c_ubyte,
+    POINTER(c_uint64),
+    c_uint64,
+    POINTER(c_ubyte),
+]
+
+keep_ints_in_counting_bloom_c = lib.keep_ints_in_counting_bloom
+keep_ints_in_counting_bloom_c.argtypes = [
+    POINTER(c_ubyte),
+    c_uint64,
+    c_ubyte,
+    POINTER(c_uint64),
+    c_uint64,
+    c_ubyte,
+]
+keep_ints_in_counting_bloom_c.restype = c_uint64
+
+num_nonzero_counters_c = lib.num_nonzero_counters
+num_nonzero_counters_c.argtypes = [POINTER(c_ubyte), c_uint64]
+num_nonzero_counters_c.restype = c_uint64
+
+
+class _SortedKmers(Structure):
+    """Container for a c struct pointing to a sorted array of k-mers
+
+    Like `_HashSet`, this is just so that ctypes knows what the C
+    functions that take a pointer to the struct are passing around.
+    """
+
+    _fields_: list = [
+        ("kmers", POINTER(c_uint64)),
+        ("num_kmers", c_uint64),
+        ("k", c_ubyte),
+    ]
+
+
+copy_hash_set_kmers_c = lib.copy_hash_set_kmers
+copy_hash_set_kmers_c.argtypes = [POINTER(_HashSet), POINTER(c_uint64)]
+
+sort_kmer_ints_c = lib.sort_kmer_ints
+sort_kmer_ints_c.argtypes = [POINTER(c_uint64), c_uint64, c_ubyte]
+sort_kmer_ints_c.restype = c_uint64
+
+sort_uint64s_c = lib.sort_uint64s
+sort_uint64s_c.argtypes = [POINTER(c_uint64), c_uint64]
+
+count_kmer_ints_c = lib.count_kmer_ints
+count_kmer_ints_c.argtypes = [POINTER(c_uint64), c_uint64, POINTER(c_uint64)]
+count_kmer_ints_c.restype = c_uint64
+
+int_in_sorted_kmers_c = lib.int_in_sorted_kmers
+int_in_sorted_kmers_c.argtypes = [c_uint64, POINTER(_SortedKmers)]
+int_in_sorted_kmers_c.restype = c_ubyte
+
+
+class _MappedKmers(Structure):
+    """Container for a c struct pointing to the k-mers in a memory-mapped
+    binary k-mer set file"""
+
+    _fields_: list = [
+        ("kmers", c_void_p),
+        ("num_kmers", c_uint64),
+        ("k", c_ubyte),
+    ]
+
+
+int_in_mapped_kmers_c = lib.int_in_mapped_kmers
+int_in_mapped_kmers_c.argtypes = [c_uint64, POINTER(_MappedKmers)]
+int_in_mapped_kmers_c.restype = c_ubyte
+
+MAX_K = 32
+"""The longest k-mer that fits in the 64-bit integer format"""
+
+MAX_K_128 = 64
+"""The longest k-mer that fits in the 128-bit integer format"""
+
+MAX_MINIMIZER_WINDOW = 256
+"""The most k-mers a window can have in `minimizers`"""
+
+MIN_K = 11
+"""The shortest k the scripts accept with -k. Shorter k-mers turn up by
+chance in so many reads that they can't tell the haplotypes apart. The
+library itself takes any k, which is handy for tests."""
+
+
+class KmerError(ValueError):
+    """Base class for errors converting k-mers"""
+
+
+class InvalidBaseError(KmerError):
+    """A k-mer contains a character other than [ACGT]
+
+    Attributes:
+        base: the offending character
+        position: the 0-based position of the character in the k-mer
+    """
+
+    def __init__(self, base: str, position: int):
+        self.base = base
+        self.position = position
+        super().__init__(f"invalid base {base!r} at position {position}")
+
+
+class KmerLengthError(KmerError):
+    """A k-mer is empty or too long to fit in the requested integer format
+
+    Attributes:
+        k: the length of the k-mer
+        max_k: the longest k-mer supported by the integer format
+    """
+
+    def __init__(self, k: int, max_k: int):
+        self.k = k
+        self.max_k = max_k
+        if k < 1:
+            super().__init__(f"k-mers must be at least 1 base long, not {k}")
+        else:
+            super().__init__(
+                f"{k}-mers are too long for this integer format (max k is {max_k})"
+            )
+
+
+class KmerBitsError(KmerError):
+    """An integer that isn't the integer format of any k-mer of a length,
+    because it's negative or has bits set above the lowest 2k
+
+    Attributes:
+        kmer_int: the integer
+        k: the length of the k-mer it was to be decoded as
+    """
+
+    def __init__(self, kmer_int: int, k: int):
+        self.kmer_int = kmer_int
+        self.k = k
+        super().__init__(f"{kmer_int:#x} isn't a {k}-mer in integer format")
+
+
+class KmerSizeMismatchError(KmerError):
+    """The k-mers of the two haplotypes are different lengths
+
+    Attributes:
+        hap_a_k: the k of haplotype A's k-mers
+        hap_b_k: the k of haplotype B's k-mers
+        hap_a_path: the file haplotype A's k-mers were read from, if known
+        hap_b_path: the file haplotype B's k-mers were read from, if known
+    """
+
+    def __init__(
+        self,
+        hap_a_k: int,
+        hap_b_k: int,
+        hap_a_path: Optional[str] = None,
+        hap_b_path: Optional[str] = None,
+    ):
+        self.hap_a_k = hap_a_k
+        self.hap_b_k = hap_b_k
+        self.hap_a_path = hap_a_path
+        self.hap_b_path = hap_b_path
+        in_a = f" in {hap_a_path}" if hap_a_path is not None else ""
+        in_b = f" in {hap_b_path}" if hap_b_path is not None else ""
+        super().__init__(
+            f"the haplotype A k-mers{in_a} are {hap_a_k}-mers, but the haplotype B "
+            f"k-mers{in_b} are {hap_b_k}-mers"
+        )
+
+
+class KmerFileError(KmerError):
+    """A file of k-mers could not be parsed
+
+    Attributes:
+        filename: the name of the file
+        line_number: the 1-based number of the bad line, or None if the
+            problem is with the file as a whole
+    """
+
+    def __init__(self, filename: str, line_number: Optional[int], message: str):
+        self.filename = filename
+        self.line_number = line_number
+        if line_number is None:
+            super().__init__(f"{filename}: {message}")
+        else:
+            super().__init__(f"{filename}, line {line_number}: {message}")
+
+
+def _check_k(k: int, max_k: int = MAX_K):
+    if not 1 <= k <= max_k:
+        raise KmerLengthError(k, max_k)
+
+
+def _check_kmer_int(kmer_int: int, k: int, max_k: int = MAX_K):
+    _check_k(k, max_k)
+    if not 0 <= kmer_int < 1 << 2 * k:
+        raise KmerBitsError(kmer_int, k)
+
+
+kmer_to_int_checked_c = lib.kmer_to_int_checked
+kmer_to_int_checked_c.argtypes = [c_char_p, c_ubyte, POINTER(c_uint64)]
+kmer_to_int_checked_c.restype = c_int
+
+
+Seq = Union[str, bytes]
+"""A sequence to pass to C, either as read by `trio_binning.seq` or as raw
+bytes, which skips encoding it"""
+
+
+def _check_step(step: int):
+    if step < 1:
+        raise ValueError(f"the k-mer step must be at least 1, not {step}")
+
+
+def _encode_text(text: str) -> bytes:
+    """Encode a string to pass to C with one byte per character, so that
+    positions in the bytes are positions in the string
+
+    ASCII characters are themselves, those read with `TEXT_ERRORS` are the
+    bytes they were read from, and anything else is a ?, which is no base
+    either.
+    """
+    try:
+        return text.encode(TEXT_ENCODING, TEXT_ERRORS)
+    except UnicodeEncodeError:
+        return text.encode(TEXT_ENCODING, "replace")
+
+
+def _encode_seq(seq: Seq) -> bytes:
+    """Encode a sequence to pass to C with `_encode_text`, so that C counts
+    as many bases in it as `len` does, unless it's bytes already"""
+    if isinstance(seq, bytes):
+        return seq
+    return _encode_text(seq)
+
+
+def kmer_to_int(kmer: Seq, rna: bool = False) -> int:
+    """Convert a kmer to integer format
+
+    The k-mer may be a string or bytes, and its bases upper or lower case.
+    The first base is in the lowest two bits, as A=0, C=1, G=2 and T=3, so
+    a k-mer's integer is always less than 4**k, and `int_to_kmer` turns it
+    back into the k-mer in uppercase.
+
+    With `rna`, a U is taken to be a T, so ACUG and ACTG are the same
+    k-mer. Otherwise a U is an invalid base like any other, since one in
+    a DNA k-mer list is more likely a mistake than RNA.
+
+    Raises:
+        InvalidBaseError: if the k-mer contains anything other than
+            [ACGTacgt], or [ACGTUacgtu] with `rna`, e.g., an N
+        KmerLengthError: if the k-mer is empty or longer than `MAX_K`. Use
+            `kmer_to_int128` for longer k-mers.
+    """
+    _check_k(len(kmer))
+    if rna:
+        if isinstance(kmer, bytes):
+            kmer = kmer.replace(b"U", b"T").replace(b"u", b"t")
+        else:
+            kmer = rna_to_dna(kmer)
+    kmer_int = c_uint64()
+    kmer_bytes = kmer if isinstance(kmer, bytes) else _encode_text(kmer)
+    position = kmer_to_int_checked_c(kmer_bytes, c_ubyte(len(kmer)), byref(kmer_int))
+    if position >= 0:
+        base = kmer[position : position + 1]
+        if isinstance(base, bytes):
+            base = base.decode(TEXT_ENCODING, TEXT_ERRORS)
+        raise InvalidBaseError(base, position)
+    return kmer_int.value
+
+
 kmer_to_int_c = lib.kmer_to_int
 kmer_to_int_c.argtypes = [c_char_p, c_ubyte]
 kmer_to_int_c.restype = c_uint64
 
 
-def kmer_to_int(kmer: str) -> int:
-    """Convert a kmer to integer format"""
-    return kmer_to_int_c(bytes(kmer, "utf-8"), c_ubyte(len(kmer)))
+def kmer_to_int_unchecked(kmer: str) -> int:
+    """Convert a kmer that's known to be all [ACGTacgt] to integer format
+
+    This skips checking the bases, for k-mers that have already been
+    checked, so anything else is converted as if it were an A rather than
+    being an error. Otherwise it's the same as `kmer_to_int`.
+
+    Raises:
+        KmerLengthError: if the k-mer is empty or longer than `MAX_K`
+    """
+    _check_k(len(kmer))
+    return kmer_to_int_c(_encode_text(kmer), len(kmer))
 
 
 reverse_complement_c = lib.reverse_complement
 reverse_complement_c.argtypes = [c_char_p, c_char_p, c_ubyte]
 
 
-def reverse_complement(kmer: str) -> str:
-    """Reverse complement a k-mer"""
+def reverse_complement(kmer: str, rna: bool = False) -> str:
+    """Reverse complement a k-mer
+
+    With `rna`, a U is complemented like a T, so the reverse complement
+    of an RNA k-mer is DNA, as `int_to_kmer` would give.
+    """
+    if rna:
+        kmer = rna_to_dna(kmer)
     out_kmer = bytes("x" * len(kmer), "utf-8")
     reverse_complement_c(bytes(kmer, "utf-8"), out_kmer, len(kmer))
     return out_kmer.decode("utf-8")
 
 
-# this is ugly as sin, but necessary because mypy is ok with `pointer`
-# as a subscriptable type while python runtime is not
-if TYPE_CHECKING:
-    HashSet = pointer[_HashSet]
-else:
-    HashSet = pointer
+reverse_complement_int_c = lib.reverse_complement_int
+reverse_complement_int_c.argtypes = [c_uint64, c_ubyte]
+reverse_complement_int_c.restype = c_uint64
 
 
-def create_kmer_hash_set(kmer_file_path: str) -> HashSet:
-    """Read a list of k-mers into a set.
+def reverse_complement_int(kmer_int: int, k: int) -> int:
+    """Reverse complement a kmer in integer format.
+
+    This works directly on the integer representation, so it is much
+    faster than going through `int_to_kmer` and `reverse_complement`.
+    """
+    _check_k(k)
+    return reverse_complement_int_c(kmer_int, k)
+
+
+int_to_kmer_c = lib.int_to_kmer
+int_to_kmer_c.argtypes = [c_uint64, c_char_p, c_ubyte]
+
+
+def int_to_kmer(kmer_int: int, k: int) -> str:
+    """Convert a kmer in integer format back to a string, in uppercase
+
+    Raises:
+        KmerLengthError: if k is less than 1 or more than `MAX_K`
+        KmerBitsError: if the integer isn't from 0 to 4**k - 1, so it
+            has bits that aren't part of any k-mer of that length
+    """
+    _check_kmer_int(kmer_int, k)
+    out_kmer = bytes("x" * k, "utf-8")
+    int_to_kmer_c(kmer_int, out_kmer, k)
+    return out_kmer.decode("utf-8")
+
+
+canonicalize_int_c = lib.canonicalize_int
+canonicalize_int_c.argtypes = [c_uint64, c_ubyte]
+canonicalize_int_c.restype = c_uint64
 
-    Reads a list of k-mers into a quickly searchable hash set.
+
+def canonicalize(kmer_int: int, k: int) -> int:
+    """Canonicalize a kmer in integer format.
 
     Args:
-        kmer_file_path: the path to a file containing a set of k-mers,
-            one per line.
+        kmer_int: integer representation of a kmer, e.g., from
+            `kmer_to_int`
+        k: the length of the kmer
 
     Returns:
-        a quickly searchable set of these k-mers that can be passed to
-        `count_kmers_in_read`
+        the integer representation of the lesser of the kmer and its
+        reverse complement. This always decodes (via `int_to_kmer`) to
+        the lexicographically smaller of the two strings.
     """
-    if not isfile(kmer_file_path):
-        raise IOError(f"Specified file {kmer_file_path} does not exist or is not file.")
+    _check_k(k)
+    return canonicalize_int_c(kmer_int, k)
 
-    print(f"Reading k-mers in {kmer_file_path}...", file=sys.stderr)
 
-    return create_kmer_hash_set_c(kmer_file_path.encode("utf-8"))
+def canonical_int(kmer: str) -> int:
+    """Convert a kmer to the integer format of its canonical form"""
+    return canonicalize(kmer_to_int(kmer), len(kmer))
 
 
-def count_kmers_in_read(
-    read: str, kmers_hap_a: HashSet, kmers_hap_b: HashSet
-) -> Tuple[int, int]:
-    """Count k-mers in read and two sets
+kmer_to_int128_checked_c = lib.kmer_to_int128_checked
+kmer_to_int128_checked_c.argtypes = [
+    c_char_p,
+    c_ubyte,
+    POINTER(c_uint64),
+    POINTER(c_uint64),
+]
+kmer_to_int128_checked_c.restype = c_int
 
-    Counts the k-mers in a read, keeping track of how many are in two
-    different sets, respectively.
 
-    Args:
-        read: a string containing a DNA sequence read. May only contain
-            [ACGT].
-        kmers_hap_a: a hash set containing all k-mers in haplotype A
-        kmers_hap_b: a hash set containing all k-mers in haplotype B
+def kmer_to_int128(kmer: str) -> int:
+    """Convert a kmer of up to 64 bases to integer format
 
-    Returns:
-        A tuple of two ints, where the first is the number of k-mers in
-        the read found in the haplotype A set, and the second is the
-        number of k-mers in the read found in the haplotype B set
+    The layout is the same as that of `kmer_to_int`, just with up to
+    128 bits, so k-mers of 32 or fewer bases get the same integer from
+    both functions.
+
+    Raises:
+        InvalidBaseError: if the k-mer contains anything other than
+            [ACGT]
+        KmerLengthError: if the k-mer is empty or longer than `MAX_K_128`
     """
-    count_a, count_b = c_int(), c_int()
+    _check_k(len(kmer), MAX_K_128)
+    kmer_int_low, kmer_int_high = c_uint64(), c_uint64()
+    position = kmer_to_int128_checked_c(
+        _encode_text(kmer),
+        c_ubyte(len(kmer)),
+        byref(kmer_int_low),
+        byref(kmer_int_high),
+    )
+    if position >= 0:
+        raise InvalidBaseError(kmer[position], position)
+    return kmer_int_high.value << 64 | kmer_int_low.value
 
-    count_kmers_in_read_c(
-        read.encode("utf-8"),
-        kmers_hap_a,
-        kmers_hap_b,
-        byref(count_a),
-        byref(count_b),
+
+int128_to_kmer_c = lib.int128_to_kmer
+int128_to_kmer_c.argtypes = [c_uint64, c_uint64, c_char_p, c_ubyte]
+
+
+def int128_to_kmer(kmer_int: int, k: int) -> str:
+    """Convert a kmer in 128-bit integer format back to a string
+
+    Raises:
+        KmerLengthError: if k is less than 1 or more than `MAX_K_128`
+        KmerBitsError: if the integer isn't from 0 to 4**k - 1
+    """
+    _check_kmer_int(kmer_int, k, MAX_K_128)
+    out_kmer = bytes("x" * k, "utf-8")
+    int128_to_kmer_c(
+        kmer_int & 0xFFFFFFFFFFFFFFFF, kmer_int >> 64 & 0xFFFFFFFFFFFFFFFF, out_kmer, k
     )
+    return out_kmer.decode("utf-8")
 
-    return count_a.value, count_b.value
 
+@total_ordering
+@dataclass(frozen=True)
+class Kmer:
+    """A k-mer in integer format along with its length
 
-def get_number_kmers_in_set(kmer_hash_set: HashSet) -> int:
+    k-mers compare equal only if they have the same length and the same
+    bases, so they can be stored in sets and dicts. They are ordered
+    lexicographically, like their strings; trying to order k-mers of
+    different lengths raises a ValueError.
+    """
+
+    bits: int
+    """The integer format of the k-mer, as from `kmer_to_int`"""
+    k: int
+    """The length of the k-mer"""
+
+    @classmethod
+    def from_str(cls, kmer: str) -> "Kmer":
+        """Create a Kmer from a string containing only [ACGT]"""
+        return cls(kmer_to_int(kmer), len(kmer))
+
+    def __str__(self):
+        return int_to_kmer(self.bits, self.k)
+
+    def __lt__(self, other: "Kmer") -> bool:
+        if not isinstance(other, Kmer):
+            return NotImplemented
+        if self.k != other.k:
+            raise ValueError(f"Cannot order a {self.k}-mer and a {other.k}-mer")
+        return self._lexicographic_key() < other._lexicographic_key()
+
+    def _lexicographic_key(self) -> int:
+        # the first base is in the least significant bits, so reverse the
+        # bases to make integer order match string order. Reverse
+        # complementing the complement does exactly that.
+        return reverse_complement_int(~self.bits & ((1 << 2 * self.k) - 1), self.k)
+
+    def revcomp(self) -> "Kmer":
+        """Get the reverse complement of this k-mer"""
+        return Kmer(reverse_complement_int(self.bits, self.k), self.k)
+
+    def canonical(self) -> "Kmer":
+        """Get the lesser of this k-mer and its reverse complement"""
+        return Kmer(canonicalize(self.bits, self.k), self.k)
+
+
+@dataclass(frozen=True)
+class SpacedSeed:
+    """A spaced seed: a pattern of the positions in a window of bases that
+    are looked at and the ones that are ignored, like ``1110110111``
+
+    A k-mer is made of each window from the bases at its care positions
+    (the 1's), so that a mismatch at a don't-care position (a 0) doesn't
+    change it, which makes hap-mers less fragile between divergent
+    parents. The k of the k-mers is the seed's `weight`. The window must
+    start and end with a care position and be at most 32 bases long, and
+    it should be `symmetric` to tolerate mismatches on both strands (see
+    `canonical`).
+
+    >>> seed = SpacedSeed("1101")
+    >>> int_to_kmer(seed.apply(kmer_to_int("ACGT")), seed.weight)
+    'ACT'
+    """
+
+    pattern: str
+    """The pattern, with a 1 for each care position and a 0 for each
+    don't-care position"""
+
+    def __post_init__(self):
+        if not self.pattern or self.pattern.strip("01"):
+            raise ValueError(
+                f"a seed pattern must be made of 1's and 0's, not {self.pattern!r}"
+            )
+        if self.pattern[0] != "1" or self.pattern[-1] != "1":
+            raise ValueError(
+                f"a seed pattern must start and end with a 1, not {self.pattern}"
+            )
+        if len(self.pattern) > MAX_K:
+            raise ValueError(
+                f"a seed pattern can be at most {MAX_K} long, not "
+                f"{len(self.pattern)}"
+            )
+
+    def __str__(self):
+        return self.pattern
+
+    @classmethod
+    def from_mask(cls, care: int, span: int) -> "SpacedSeed":
+        """Make a seed from its `care` mask and span"""
+        return cls("".join("1" if care >> i & 1 else "0" for i in range(span)))
+
+    @property
+    def span(self) -> int:
+        """The number of bases in the window"""
+        return len(self.pattern)
+
+    @property
+    def weight(self) -> int:
+        """The number of care positions, which is the k of the k-mers made"""
+        return self.pattern.count("1")
+
+    @property
+    def care(self) -> int:
+        """A mask with bit i set if position i is a care position"""
+        return sum(1 << i for i, position in enumerate(self.pattern) if position == "1")
+
+    @property
+    def symmetric(self) -> bool:
+        """Whether the pattern is the same backwards, so that applying it to
+        the reverse complement of a window gives the reverse complement of
+        applying it to the window"""
+        return self.pattern == self.pattern[::-1]
+
+    def apply(self, window_int: int) -> int:
+        """Take the bases at the care positions out of a window of `span`
+        bases in integer format, as from `kmer_to_int`, and pack them into
+        a k-mer of `weight` bases in the same format"""
+        kmer_int = 0
+        offset = 0
+        for i, position in enumerate(self.pattern):
+            if position == "1":
+                kmer_int |= (window_int >> 2 * i & 3) << 2 * offset
+                offset += 1
+        return kmer_int
+
+    def canonical(self, window_int: int) -> int:
+        """The canonical k-mer of a window, as it is found in reads and
+        stored in k-mer sets
+
+        This is the lesser of the seed applied to the window and to its
+        reverse complement, so that either strand gives the same k-mer.
+        k-mer sets store k-mers by their canonical form, so it is then
+        canonicalized as a k-mer too, which only changes anything for a
+        pattern that isn't `symmetric`.
+
+        A mismatch at a don't-care position of a symmetric pattern is at
+        one on the other strand too, so it never changes this k-mer. For
+        other patterns it is at a care position on the other strand, so
+        it can, when the other strand's k-mer is the lesser.
+        """
+        kmer_int = min(
+            self.apply(window_int),
+            self.apply(reverse_complement_int(window_int, self.span)),
+        )
+        return canonicalize(kmer_int, self.weight)
+
+
+# iterating over bytes gives the value of each byte, so those are keys too
+_UNMASKED_BASE_TO_INT: Dict[Union[str, int], int] = {
+    **{base: base_int for base_int, base in enumerate("ACGT")},
+    **{ord(base): base_int for base_int, base in enumerate("ACGT")},
+}
+_BASE_TO_INT = {
+    **_UNMASKED_BASE_TO_INT,
+    **{base: base_int for base_int, base in enumerate("acgt")},
+    **{ord(base): base_int for base_int, base in enumerate("acgt")},
+}
+
+
+def _base_to_int_table(ignore_softmasked: bool) -> Dict[Union[str, int], int]:
+    return _UNMASKED_BASE_TO_INT if ignore_softmasked else _BASE_TO_INT
+
+
+def iter_kmers(
+    seq: Union[Iterable[str], bytes],
+    k: int,
+    ignore_softmasked: bool = False,
+    step: int = 1,
+) -> Iterator[int]:
+    """Iterate over the k-mers in a sequence.
+
+    Yields the integer format of every k-mer in a sequence, in order.
+    Each k-mer is computed from the previous one by shifting in the new
+    base rather than by re-encoding the whole window, so this is O(1)
+    per position. Sequences shorter than k yield nothing.
+
+    Bases may be upper or lower case. Any character other than
+    [ACGTacgt] (e.g., an N) is skipped over rather than raising an
+    error: no k-mer containing it is yielded, and iteration picks back
+    up with the first k-mer after it.
+
+    Args:
+        seq: the sequence to get k-mers from, as a string or bytes, or
+            any iterable of its bases, e.g., from
+            `iter_homopolymer_compressed`
+        k: the k-mer size
+        ignore_softmasked: if True, lowercase (soft-masked) bases are
+            skipped over like N's rather than treated like uppercase
+        step: only yield the k-mers starting at a multiple of this many
+            bases into the sequence, e.g., 10 for a tenth of them. The
+            positions count skipped bases, so a k-mer doesn't move off
+            the grid after an N.
+
+    Yields:
+        the integer format of each k-mer, as from `kmer_to_int`. These
+        are not canonicalized.
+
+    Raises:
+        ValueError: if `step` is less than 1
+    """
+    _check_k(k)
+    _check_step(step)
+    base_to_int = _base_to_int_table(ignore_softmasked)
+    shift = 2 * (k - 1)
+    kmer_int = 0
+    num_valid_bases = 0  # number of consecutive valid bases in kmer_int
+
+    for position, base in enumerate(seq):
+        base_int = base_to_int.get(base)
+        if base_int is None:
+            num_valid_bases = 0
+            continue
+
+        kmer_int = kmer_int >> 2 | base_int << shift
+        num_valid_bases += 1
+        if num_valid_bases >= k and (step == 1 or (position + 1 - k) % step == 0):
+            yield kmer_int
+
+
+def iter_kmers_with_quality(
+    seq: str,
+    quals: str,
+    k: int,
+    min_base_quality: int,
+    ignore_softmasked: bool = False,
+) -> Iterator[int]:
+    """Iterate over the k-mers in a sequence with only good-quality bases
+
+    This is the same as `iter_kmers`, except that bases with a quality
+    less than `min_base_quality` are also skipped over like N's, so
+    no k-mer containing one is yielded.
+
+    Args:
+        seq: the sequence to get k-mers from
+        quals: the sequence's Phred+33 quality string, walked in
+            lockstep with it
+        k: the k-mer size
+        min_base_quality: the lowest quality score a base can have
+            without being skipped
+        ignore_softmasked: see `iter_kmers`
+
+    Raises:
+        ValueError: if `quals` is a different length from `seq`
+    """
+    if len(quals) != len(seq):
+        raise ValueError(
+            f"the quality string is {len(quals)} long, but the sequence is "
+            f"{len(seq)}"
+        )
+    masked = _mask_low_quality(seq, quals, min_base_quality)
+    return iter_kmers(masked, k, ignore_softmasked)
+
+
+def _mask_low_quality(seq: str, quals: str, min_base_quality: int) -> str:
+    """Replace bases below a quality with "-", which is never a base, so that
+    it is skipped over like an N"""
+    min_qual = chr(PHRED_OFFSET + min_base_quality)
+    return "".join(base if qual >= min_qual else "-" for base, qual in zip(seq, quals))
+
+
+def iter_homopolymer_compressed(seq: Iterable[str]) -> Iterator[str]:
+    """Iterate over the bases of a sequence with homopolymers compressed
+
+    Each run of the same base is collapsed into its first base, so both
+    AAACGGT and AACGT become ACGT. Nanopore reads often get the lengths
+    of homopolymers wrong, and this makes their k-mers match anyway.
+    Runs are found ignoring case, like the k-mers themselves are.
+
+    This can be passed straight to `iter_kmers` or
+    `iter_canonical_kmers` without building the compressed sequence.
+    """
+    previous = ""
+    for base in seq:
+        upper = base.upper()
+        if upper != previous:
+            previous = upper
+            yield base
+
+
+_HOMOPOLYMER_RUN = re.compile(r"(.)\1+", re.IGNORECASE | re.DOTALL)
+
+
+def compress_homopolymers(seq: str) -> str:
+    """Collapse each run of the same base in a sequence into one base
+
+    This is the same as `iter_homopolymer_compressed`, but faster for
+    whole sequences:
+
+    >>> compress_homopolymers("AAACGGTtt")
+    'ACGT'
+    """
+    return _HOMOPOLYMER_RUN.sub(r"\1", seq)
+
+
+def longest_homopolymer(seq: str) -> int:
+    """The length of the longest run of the same base in a sequence,
+    ignoring case
+
+    >>> longest_homopolymer("ACGGGTtt")
+    3
+    """
+    runs = _HOMOPOLYMER_RUN.finditer(seq)
+    return max((run.end() - run.start() for run in runs), default=min(len(seq), 1))
+
+
+def gc_content(seq: str) -> float:
+    """The fraction of the bases of a sequence that are G or C, ignoring
+    case, or 0 if it's empty"""
+    if not seq:
+        return 0.0
+    upper = seq.upper()
+    return (upper.count("G") + upper.count("C")) / len(seq)
+
+
+def compress_homopolymers_with_quality(seq: str, quals: str) -> Tuple[str, str]:
+    """Compress homopolymers in a sequence and its quality string
+
+    Each collapsed run keeps the lowest quality in it, so that a run
+    with a low-quality base is still treated as low quality.
+
+    Returns:
+        the compressed sequence, as from `compress_homopolymers`, and the
+        compressed quality string
+    """
+    compressed_quals = [
+        min(quals[run.start() : run.end()])
+        for run in re.finditer(r"(.)\1*", seq, re.IGNORECASE | re.DOTALL)
+    ]
+    return compress_homopolymers(seq), "".join(compressed_quals)
+
+
+def count_skipped_kmers(seq: str, k: int, ignore_softmasked: bool = False) -> int:
+    """Count the k-mers that `iter_kmers` skips over in a sequence.
+
+    This is the number of windows of length k in the sequence that
+    contain a character other than [ACGTacgt], or other than [ACGT] if
+    `ignore_softmasked` is True.
+    """
+    _check_k(k)
+    num_windows = max(len(seq) - k + 1, 0)
+    return num_windows - sum(1 for _ in iter_kmers(seq, k, ignore_softmasked))
+
+
+def iter_canonical_kmers(
+    seq: Union[Iterable[str], bytes],
+    k: int,
+    ignore_softmasked: bool = False,
+    step: int = 1,
+) -> Iterator[int]:
+    """Iterate over the canonical k-mers in a sequence.
+
+    This is the same as `iter_kmers`, except that it yields the integer
+    format of the canonical form of each k-mer, as from `canonicalize`.
+    The reverse complement is rolled along with the k-mer, so this is
+    still O(1) per position.
+    """
+    _check_k(k)
+    _check_step(step)
+    base_to_int = _base_to_int_table(ignore_softmasked)
+    shift = 2 * (k - 1)
+    mask = (1 << 2 * k) - 1
+    kmer_int = kmer_revcomp_int = 0
+    num_valid_bases = 0
+
+    for position, base in enumerate(seq):
+        base_int = base_to_int.get(base)
+        if base_int is None:
+            num_valid_bases = 0
+            continue
+
+        kmer_int = kmer_int >> 2 | base_int << shift
+        kmer_revcomp_int = (kmer_revcomp_int << 2 | (3 - base_int)) & mask
+        num_valid_bases += 1
+        if num_valid_bases >= k and (step == 1 or (position + 1 - k) % step == 0):
+            yield kmer_int if kmer_int < kmer_revcomp_int else kmer_revcomp_int
+
+
+def canonical_kmer_array(seq: Seq, k: int, ignore_softmasked: bool = False) -> array:
+    """Get the canonical k-mers in a sequence, like `iter_canonical_kmers`
+
+    This finds them all at once in C, which is much faster, and doesn't
+    hold the GIL, so many threads can do it at once.
+
+    Returns:
+        an array of the integer format of each canonical k-mer, in order
+    """
+    _check_k(k)
+    kmer_ints = array("Q", [0]) * max(len(seq) - k + 1, 0)
+    if not kmer_ints:
+        return kmer_ints
+    address, _ = kmer_ints.buffer_info()
+    num_kmers = canonical_kmers_in_read_c(
+        _encode_seq(seq), k, c_char(ignore_softmasked), cast(address, POINTER(c_uint64))
+    )
+    del kmer_ints[num_kmers:]
+    return kmer_ints
+
+
+def spaced_kmer_array(
+    seq: Seq, seed: SpacedSeed, ignore_softmasked: bool = False
+) -> array:
+    """Get the k-mers a spaced seed makes of a sequence, like
+    `canonical_kmer_array`, in C
+
+    Windows with an N, or a lowercase base with `ignore_softmasked`, are
+    skipped whether it is at a care position or not.
+
+    Returns:
+        an array of `seed.canonical` of each window, in order
+    """
+    kmer_ints = array("Q", [0]) * max(len(seq) - seed.span + 1, 0)
+    if not kmer_ints:
+        return kmer_ints
+    address, _ = kmer_ints.buffer_info()
+    num_kmers = spaced_kmers_in_read_c(
+        _encode_seq(seq),
+        seed.care,
+        seed.span,
+        c_char(ignore_softmasked),
+        cast(address, POINTER(c_uint64)),
+    )
+    del kmer_ints[num_kmers:]
+    return kmer_ints
+
+
+def sort_kmer_array(kmer_ints: array):
+    """Sort an array of k-mers in place, in C"""
+    address, length = kmer_ints.buffer_info()
+    sort_uint64s_c(cast(address, POINTER(c_uint64)), length)
+
+
+def count_kmer_array(kmer_ints: array) -> Tuple[array, array]:
+    """Count the distinct k-mers in an array, in C
+
+    Args:
+        kmer_ints: k-mers in integer format, each repeated once for each
+            time it was seen. This is sorted and reused for the result.
+
+    Returns:
+        kmer_ints: the distinct k-mers, in order
+        counts: the number of times each was in the array
+    """
+    counts = array("Q", [0]) * len(kmer_ints)
+    if not kmer_ints:
+        return kmer_ints, counts
+    address, length = kmer_ints.buffer_info()
+    counts_address, _ = counts.buffer_info()
+    num_unique = count_kmer_ints_c(
+        cast(address, POINTER(c_uint64)),
+        length,
+        cast(counts_address, POINTER(c_uint64)),
+    )
+    del kmer_ints[num_unique:]
+    del counts[num_unique:]
+    return kmer_ints, counts
+
+
+def iter_canonical_kmers_with_positions(
+    seq: Union[Iterable[str], bytes], k: int, ignore_softmasked: bool = False
+) -> Iterator[Tuple[int, int]]:
+    """Iterate over the canonical k-mers in a sequence and where they start.
+
+    This is `iter_canonical_kmers`, but also yields the 0-based position
+    of the first base of each k-mer in the sequence, counting the bases
+    skipped over, e.g., N's, so that the positions are still right after
+    them.
+
+    >>> list(iter_canonical_kmers_with_positions("ACNGTT", 2))
+    [(0, 4), (3, 4), (4, 0)]
+
+    Yields:
+        the position of each k-mer, and its canonical integer format
+    """
+    _check_k(k)
+    base_to_int = _base_to_int_table(ignore_softmasked)
+    shift = 2 * (k - 1)
+    mask = (1 << 2 * k) - 1
+    kmer_int = kmer_revcomp_int = 0
+    num_valid_bases = 0
+
+    for position, base in enumerate(seq):
+        base_int = base_to_int.get(base)
+        if base_int is None:
+            num_valid_bases = 0
+            continue
+
+        kmer_int = kmer_int >> 2 | base_int << shift
+        kmer_revcomp_int = (kmer_revcomp_int << 2 | (3 - base_int)) & mask
+        num_valid_bases += 1
+        if num_valid_bases >= k:
+            yield position - k + 1, min(kmer_int, kmer_revcomp_int)
+
+
+# this is ugly as sin, but necessary because mypy is ok with `pointer`
+# as a subscriptable type while python runtime is not
+if TYPE_CHECKING:
+    HashSet = pointer[_HashSet]
+else:
+    HashSet = pointer
+
+
+def create_kmer_hash_set(kmer_file_path: str) -> HashSet:
+    """Read a list of k-mers into a set.
+
+    Reads a list of k-mers into a quickly searchable hash set.
+
+    Args:
+        kmer_file_path: the path to a file containing a set of k-mers,
+            one per line.
+
+    Returns:
+        a quickly searchable set of these k-mers that can be passed to
+        `count_kmers_in_read`
+    """
+    if not isfile(kmer_file_path):
+        raise IOError(f"Specified file {kmer_file_path} does not exist or is not file.")
+
+    logger.info("Reading k-mers in %s", kmer_file_path)
+
+    return create_kmer_hash_set_c(kmer_file_path.encode("utf-8"))
+
+
+def count_kmers_in_read(
+    read: Seq, kmers_hap_a: HashSet, kmers_hap_b: HashSet
+) -> Tuple[int, int]:
+    """Count k-mers in read and two sets
+
+    Counts the k-mers in a read, keeping track of how many are in two
+    different sets, respectively.
+
+    Args:
+        read: a string containing a DNA sequence read, in upper or
+            lower case, or the same as bytes. k-mers containing anything
+            other than [ACGTacgt] are skipped.
+        kmers_hap_a: a hash set containing all k-mers in haplotype A
+        kmers_hap_b: a hash set containing all k-mers in haplotype B
+
+    Returns:
+        A tuple of two ints, where the first is the number of k-mers in
+        the read found in the haplotype A set, and the second is the
+        number of k-mers in the read found in the haplotype B set
+    """
+    count_a, count_b, _ = count_kmers_in_read_with_skipped(
+        read, kmers_hap_a, kmers_hap_b
+    )
+    return count_a, count_b
+
+
+def count_kmers_in_read_with_skipped(
+    read: Seq,
+    kmers_hap_a: HashSet,
+    kmers_hap_b: HashSet,
+    ignore_softmasked: bool = False,
+) -> Tuple[int, int, int]:
+    """Count k-mers in read and two sets, and the k-mers skipped
+
+    This is the same as `count_kmers_in_read`, but it also returns the
+    number of k-mers that were skipped because they contain a character
+    other than [ACGTacgt] (e.g., an N). If `ignore_softmasked` is True,
+    k-mers with lowercase (soft-masked) bases are skipped as well.
+    """
+    count_a, count_b, num_skipped = c_int(), c_int(), c_int()
+
+    count_kmers_in_read_skipping_c(
+        _encode_seq(read),
+        kmers_hap_a,
+        kmers_hap_b,
+        byref(count_a),
+        byref(count_b),
+        byref(num_skipped),
+        c_char(ignore_softmasked),
+    )
+
+    return count_a.value, count_b.value, num_skipped.value
+
+
+def get_number_kmers_in_set(kmer_hash_set: HashSet) -> int:
     """Look up the number of k-mers in a hash set"""
     return kmer_hash_set.contents.num_kmers
+
+
+class DumpFormat(Enum):
+    """A format for lists of k-mers, as dumped by various k-mer counters"""
+
+    AUTO = "auto"
+    """Detect the format from the first few lines of the file"""
+    PLAIN = "plain"
+    """One k-mer per line, with no counts"""
+    MERYL = "meryl"
+    """`meryl print` output: a k-mer, a tab, and a count on each line"""
+    JELLYFISH = "jellyfish"
+    """`jellyfish dump` output: a fasta file with counts as names"""
+    KMC = "kmc"
+    """`kmc_dump` output: the same as `MERYL`"""
+
+    def __str__(self):
+        return self.value
+
+
+def detect_dump_format(lines: List[str]) -> DumpFormat:
+    """Guess the format of a k-mer list from its first few lines
+
+    meryl and kmc dumps look the same, so both are detected as `MERYL`.
+    """
+    for line in lines:
+        if line.startswith(">"):
+            return DumpFormat.JELLYFISH
+        if "\t" in line:
+            return DumpFormat.MERYL
+        if line.strip():
+            return DumpFormat.PLAIN
+    return DumpFormat.PLAIN
+
+
+# each of these yields (line number, k-mer, count) for each k-mer in a dump,
+# where count is the count as a string, or empty if there isn't one. Only the
+# jellyfish format can be malformed by itself, so only it takes the file name
+# for its errors; the k-mers and counts are checked by the caller
+
+
+def _parse_tabbed_dump(lines: Iterable[str]) -> Iterator[Tuple[int, str, str]]:
+    for line_number, line in enumerate(lines, start=1):
+        kmer, _, count = line.rstrip("\n").partition("\t")
+        if kmer:
+            yield line_number, kmer, count
+
+
+def _parse_jellyfish_dump(
+    lines: Iterable[str], filename: str
+) -> Iterator[Tuple[int, str, str]]:
+    count = None
+    for line_number, line in enumerate(lines, start=1):
+        line = line.rstrip("\n")
+        if line.startswith(">"):
+            if count is not None:
+                raise KmerFileError(
+                    filename, line_number - 1, "expected a k-mer after the count"
+                )
+            count = line[1:]
+        elif line:
+            if count is None:
+                raise KmerFileError(
+                    filename, line_number, "expected a >count line before the k-mer"
+                )
+            yield line_number, line, count
+            count = None
+
+    if count is not None:
+        raise KmerFileError(filename, None, "file ends with a count but no k-mer")
+
+
+def iter_kmer_dump(
+    fp: TextIO,
+    k: Optional[int] = None,
+    min_count: Optional[int] = None,
+    dump_format: DumpFormat = DumpFormat.AUTO,
+) -> Iterator[Tuple[int, int]]:
+    """Read the k-mers in a list of k-mers, one line at a time.
+
+    The list can be in any of the formats in `DumpFormat`. In the plain
+    format, a line may still have a tab and a count after the k-mer,
+    which is ignored. Blank lines are skipped.
+
+    Args:
+        fp: file containing k-mers
+        k: the k-mer size. Every k-mer in the file must be this long. If
+            not given, it is the length of the first k-mer.
+        min_count: if given, skip k-mers with a count less than this.
+            Every k-mer must then have a count, so the list can't be in
+            the plain format. Otherwise, counts are ignored.
+        dump_format: the format of the list
+
+    Yields:
+        k and the integer format of each k-mer
+
+    Raises:
+        KmerFileError: if a line contains a k-mer of the wrong length, a
+            k-mer with a base other than [ACGTacgt], or a missing or
+            invalid count when `min_count` is given, if the file is
+            otherwise not in `dump_format`, if `k` is not given and the
+            file contains no k-mers to get it from, or if it has k-mers
+            but none with a count of at least `min_count`
+    """
+    for k, kmer_int, _ in _iter_kmer_dump(fp, k, min_count, dump_format, False):
+        yield k, kmer_int
+
+
+def iter_kmer_dump_counts(
+    fp: TextIO,
+    k: Optional[int] = None,
+    min_count: Optional[int] = None,
+    dump_format: DumpFormat = DumpFormat.AUTO,
+) -> Iterator[Tuple[int, int, int]]:
+    """Read the k-mers in a list of k-mers along with their counts.
+
+    This is the same as `iter_kmer_dump`, but every k-mer must have a
+    count, so the list can't be in the plain format.
+
+    Yields:
+        k, the integer format of each k-mer, and its count
+
+    Raises:
+        KmerFileError: see `iter_kmer_dump`, and if a k-mer is missing a
+            count
+    """
+    for k, kmer_int, count in _iter_kmer_dump(fp, k, min_count, dump_format, True):
+        assert count is not None
+        yield k, kmer_int, count
+
+
+def _iter_kmer_dump(
+    fp: TextIO,
+    k: Optional[int],
+    min_count: Optional[int],
+    dump_format: DumpFormat,
+    need_counts: bool,
+) -> Iterator[Tuple[int, int, Optional[int]]]:
+    """Read a list of k-mers, and their counts if they are needed to filter
+    by `min_count` or `need_counts` is True, or None otherwise"""
+    filename = getattr(fp, "name", "<k-mer list>")
+
+    lines: Iterable[str] = fp
+    if dump_format == DumpFormat.AUTO:
+        first_lines = list(islice(fp, 10))
+        dump_format = detect_dump_format(first_lines)
+        lines = chain(first_lines, fp)
+    filename = f"{filename} ({dump_format.value} format)"
+
+    parse_counts = need_counts or min_count is not None
+    if parse_counts and dump_format == DumpFormat.PLAIN:
+        purpose = "weigh k-mers" if need_counts else "filter"
+        raise KmerFileError(
+            filename, None, f"cannot {purpose} by count since there are no counts"
+        )
+
+    if dump_format == DumpFormat.JELLYFISH:
+        parsed_lines = _parse_jellyfish_dump(lines, filename)
+    else:
+        parsed_lines = _parse_tabbed_dump(lines)
+
+    k_checked = False
+    num_yielded = num_filtered = 0
+    for line_number, kmer, count in parsed_lines:
+        if k is None:
+            k = len(kmer)
+        if not k_checked:
+            try:
+                _check_k(k)
+            except KmerLengthError as e:
+                raise KmerFileError(filename, line_number, str(e)) from e
+            k_checked = True
+        if len(kmer) != k:
+            raise KmerFileError(
+                filename, line_number, f"expected a {k}-mer but found {kmer!r}"
+            )
+        count_int = None
+        if parse_counts:
+            if not count.isdigit():
+                raise KmerFileError(
+                    filename, line_number, f"expected a count but found {count!r}"
+                )
+            count_int = int(count)
+            if min_count is not None and count_int < min_count:
+                num_filtered += 1
+                continue
+        try:
+            kmer_int = kmer_to_int(kmer)
+        except InvalidBaseError as e:
+            raise KmerFileError(filename, line_number, str(e)) from e
+        yield k, kmer_int, count_int
+        num_yielded += 1
+
+    if k is None:
+        raise KmerFileError(filename, None, "file contains no k-mers")
+    if num_filtered and not num_yielded:
+        raise KmerFileError(
+            filename, None, f"no k-mers have a count of at least {min_count}"
+        )
+
+
+def _check_kmer_file_path(kmer_file_path: str):
+    """Raise a helpful KmerFileError if a path is a directory"""
+    if isdir(kmer_file_path):
+        if glob(join(kmer_file_path, "*.merylIndex")) or isfile(
+            join(kmer_file_path, "merylIndex")
+        ):
+            raise KmerFileError(
+                kmer_file_path,
+                None,
+                "this is a binary meryl database. Dump it to text with "
+                "`meryl print` first.",
+            )
+        raise KmerFileError(kmer_file_path, None, "this is a directory")
+
+
+BINARY_MAGIC = b"TBKMERS\0"
+"""The first bytes of every binary k-mer set file"""
+
+BINARY_VERSION = 1
+"""The version of the binary k-mer set format written by this module"""
+
+# magic, version, k, flags, number of k-mers
+_BINARY_HEADER = struct.Struct("<8sIIIQ")
+
+BINARY_FLAG_HOMOPOLYMER_COMPRESSED = 1
+"""Set in the flags of a binary k-mer set if its k-mers are from
+homopolymer-compressed sequence"""
+
+BINARY_FLAG_SPACED_SEED = 2
+"""Set in the flags of a binary k-mer set if its k-mers are from a
+`SpacedSeed`, whose span and care mask then follow the header"""
+
+_BINARY_FLAGS = BINARY_FLAG_HOMOPOLYMER_COMPRESSED | BINARY_FLAG_SPACED_SEED
+
+# span and care mask of the spaced seed, with BINARY_FLAG_SPACED_SEED
+_BINARY_SEED = struct.Struct("<II")
+
+# number of k-mers to read or write at a time in binary files
+_BINARY_CHUNK_SIZE = 1 << 20
+
+
+def _write_binary(
+    fp: BinaryIO,
+    k: int,
+    flags: int,
+    sorted_kmer_ints: array,
+    seed: Optional[SpacedSeed] = None,
+):
+    """Write a binary k-mer set file (see `KmerSet.write_binary`)"""
+    if seed is not None:
+        flags |= BINARY_FLAG_SPACED_SEED
+    fp.write(
+        _BINARY_HEADER.pack(
+            BINARY_MAGIC, BINARY_VERSION, k, flags, len(sorted_kmer_ints)
+        )
+    )
+    if seed is not None:
+        fp.write(_BINARY_SEED.pack(seed.span, seed.care))
+    if sys.byteorder == "big":
+        for i in range(0, len(sorted_kmer_ints), _BINARY_CHUNK_SIZE):
+            chunk = sorted_kmer_ints[i : i + _BINARY_CHUNK_SIZE]
+            chunk.byteswap()
+            chunk.tofile(fp)
+    else:
+        sorted_kmer_ints.tofile(fp)
+
+
+def _read_binary_header(
+    fp: BinaryIO,
+) -> Tuple[int, int, int, Optional[SpacedSeed]]:
+    """Read and check the header of a binary k-mer set file
+
+    Returns:
+        k: the k-mer size
+        flags: the `BINARY_FLAG_` flags set in the file
+        num_kmers: the number of k-mers in the file
+        seed: the spaced seed the k-mers are from, if any
+
+    Raises:
+        KmerFileError: if the file is not a binary k-mer set or is from
+            an unsupported version of the format or has unknown flags set,
+            or its spaced seed is invalid or doesn't make its k-mers
+    """
+    filename = getattr(fp, "name", "<binary k-mer set>")
+    header = fp.read(_BINARY_HEADER.size)
+    if len(header) < _BINARY_HEADER.size or not header.startswith(BINARY_MAGIC):
+        raise KmerFileError(filename, None, "not a binary k-mer set")
+    _, version, k, flags, num_kmers = _BINARY_HEADER.unpack(header)
+    if version != BINARY_VERSION:
+        raise KmerFileError(
+            filename,
+            None,
+            f"binary k-mer set is version {version}, but only version "
+            f"{BINARY_VERSION} is supported",
+        )
+    if flags & ~_BINARY_FLAGS:
+        raise KmerFileError(
+            filename, None, f"binary k-mer set has unknown flags {flags:#x}"
+        )
+    if not 1 <= k <= MAX_K:
+        raise KmerFileError(
+            filename, None, f"binary k-mer set has k={k}, but the most is {MAX_K}"
+        )
+    if not flags & BINARY_FLAG_SPACED_SEED:
+        return k, flags, num_kmers, None
+
+    seed_bytes = fp.read(_BINARY_SEED.size)
+    if len(seed_bytes) < _BINARY_SEED.size:
+        raise KmerFileError(filename, None, "file is truncated in its seed pattern")
+    span, care = _BINARY_SEED.unpack(seed_bytes)
+    if not 1 <= span <= MAX_K or care >> span:
+        raise KmerFileError(
+            filename,
+            None,
+            f"binary k-mer set has an invalid seed pattern {care:#x} of span {span}",
+        )
+    try:
+        seed = SpacedSeed.from_mask(care, span)
+    except ValueError as e:
+        raise KmerFileError(filename, None, str(e)) from e
+    if seed.weight != k:
+        raise KmerFileError(
+            filename,
+            None,
+            f"binary k-mer set has k={k}, but its seed pattern {seed} makes "
+            f"{seed.weight}-mers",
+        )
+    return k, flags, num_kmers, seed
+
+
+def _read_binary(
+    fp: BinaryIO,
+) -> Tuple[int, int, int, Optional[SpacedSeed], Iterator[array]]:
+    """Read the header of a binary k-mer set file
+
+    Returns:
+        k: the k-mer size
+        flags: the `BINARY_FLAG_` flags set in the file
+        num_kmers: the number of k-mers in the file
+        seed: the spaced seed the k-mers are from, if any
+        chunks: the k-mers in the file, in chunks of up to
+            `_BINARY_CHUNK_SIZE`
+
+    Raises:
+        KmerFileError: see `_read_binary_header`. Iterating over the
+            chunks raises it if the file is truncated.
+    """
+    filename = getattr(fp, "name", "<binary k-mer set>")
+    k, flags, num_kmers, seed = _read_binary_header(fp)
+
+    def chunks() -> Iterator[array]:
+        num_kmers_read = 0
+        while num_kmers_read < num_kmers:
+            chunk_size = min(_BINARY_CHUNK_SIZE, num_kmers - num_kmers_read)
+            chunk = array("Q")
+            try:
+                chunk.fromfile(fp, chunk_size)
+            except EOFError as e:
+                raise KmerFileError(
+                    filename,
+                    None,
+                    f"file is truncated: expected {num_kmers} k-mers but found "
+                    f"{num_kmers_read + len(chunk)}",
+                ) from e
+            if sys.byteorder == "big":
+                chunk.byteswap()
+            num_kmers_read += len(chunk)
+            yield chunk
+
+    return k, flags, num_kmers, seed, chunks()
+
+
+class KmerLookup(ABC):
+    """Something k-mers can be looked up in, such as a `KmerSet`
+
+    Anything that implements this can be classified against with
+    `count_kmers_in_lookups` and the functions in `classify`. Lookups
+    are always by canonical k-mer, so either orientation of a k-mer can
+    be given.
+    """
+
+    path: Optional[str] = None
+    """The file the k-mers were read from, if any, for error messages"""
+
+    @property
+    @abstractmethod
+    def k(self) -> int:
+        """The k-mer size"""
+
+    @abstractmethod
+    def __len__(self) -> int:
+        """The number of k-mers in the lookup"""
+
+    @abstractmethod
+    def __contains__(self, kmer_int: int) -> bool:
+        """Check if a k-mer, in integer format, is in the lookup"""
+
+    @abstractmethod
+    def _c_lookup(self) -> Tuple[c_void_p, c_void_p]:
+        """The C `kmer_lookup` function for this kind of lookup, and the
+        pointer to pass to it"""
+
+
+def check_same_k(hap_a: KmerLookup, hap_b: KmerLookup):
+    """Check that the k-mers of two haplotypes are the same length
+
+    Raises:
+        KmerSizeMismatchError: if they aren't, naming the files they were
+            read from when known
+    """
+    if hap_a.k != hap_b.k:
+        raise KmerSizeMismatchError(hap_a.k, hap_b.k, hap_a.path, hap_b.path)
+
+
+def count_kmers_in_lookups(
+    read: Seq,
+    hap_a: KmerLookup,
+    hap_b: KmerLookup,
+    ignore_softmasked: bool = False,
+    quals: Optional[Seq] = None,
+    min_base_quality: int = 0,
+    step: int = 1,
+) -> Tuple[int, int, int]:
+    """Count k-mers in read and two lookups, and the k-mers skipped
+
+    This is the same as `count_kmers_in_read_with_skipped`, but works
+    with any kind of `KmerLookup` rather than only hash sets, and can
+    also skip k-mers with low-quality bases, like
+    `iter_kmers_with_quality`.
+
+    Args:
+        quals: the read's Phred+33 quality string, to skip k-mers with a
+            base of quality less than `min_base_quality`
+        min_base_quality: see `quals`
+        step: only look at every `step`th k-mer, as by `iter_kmers`. The
+            skipped k-mers are only counted among those.
+
+    Raises:
+        ValueError: if the lookups have different k, `quals` is a
+            different length from `read`, or `step` is less than 1
+    """
+    _check_step(step)
+    if hap_a.k != hap_b.k:
+        raise ValueError(f"cannot compare {hap_a.k}-mers and {hap_b.k}-mers")
+    if quals is not None and len(quals) != len(read):
+        raise ValueError(
+            f"the quality string is {len(quals)} long, but the read is {len(read)}"
+        )
+
+    count_a, count_b, num_skipped = c_int(), c_int(), c_int()
+    count_kmers_in_read_lookup_c(
+        _encode_seq(read),
+        None if quals is None else _encode_seq(quals),
+        hap_a.k,
+        *hap_a._c_lookup(),
+        *hap_b._c_lookup(),
+        byref(count_a),
+        byref(count_b),
+        byref(num_skipped),
+        c_char(ignore_softmasked),
+        c_char(min(PHRED_OFFSET + min_base_quality, 127)),
+        step,
+    )
+    return count_a.value, count_b.value, num_skipped.value
+
+
+def count_spaced_kmers_in_lookups(
+    read: Seq,
+    hap_a: KmerLookup,
+    hap_b: KmerLookup,
+    seed: SpacedSeed,
+    ignore_softmasked: bool = False,
+    quals: Optional[Seq] = None,
+    min_base_quality: int = 0,
+    step: int = 1,
+) -> Tuple[int, int, int]:
+    """Count the k-mers a spaced seed makes of a read in two lookups, and
+    the windows skipped, like `count_kmers_in_lookups`
+
+    The lookups must be of k-mers made with the same seed, e.g., by
+    build-hapmers --seed-pattern, so their k is its weight.
+
+    Raises:
+        ValueError: if the lookups have different k, or a k other than
+            the seed's weight, or see `count_kmers_in_lookups`
+    """
+    _check_step(step)
+    if hap_a.k != hap_b.k:
+        raise ValueError(f"cannot compare {hap_a.k}-mers and {hap_b.k}-mers")
+    if hap_a.k != seed.weight:
+        raise ValueError(
+            f"the seed pattern {seed} makes {seed.weight}-mers, but the k-mers "
+            f"are {hap_a.k}-mers"
+        )
+    if quals is not None and len(quals) != len(read):
+        raise ValueError(
+            f"the quality string is {len(quals)} long, but the read is {len(read)}"
+        )
+
+    count_a, count_b, num_skipped = c_int(), c_int(), c_int()
+    count_spaced_kmers_in_read_lookup_c(
+        _encode_seq(read),
+        None if quals is None else _encode_seq(quals),
+        seed.care,
+        seed.span,
+        *hap_a._c_lookup(),
+        *hap_b._c_lookup(),
+        byref(count_a),
+        byref(count_b),
+        byref(num_skipped),
+        c_char(ignore_softmasked),
+        c_char(min(PHRED_OFFSET + min_base_quality, 127)),
+        step,
+    )
+    return count_a.value, count_b.value, num_skipped.value
+
+
+class PackedSeq:
+    """A sequence packed into 2 bits per base, so that its k-mers can be
+    taken anywhere in it without going back to the string
+
+    The bases are packed 32 to a word, with the first base in the least
+    significant bits, as in `kmer_to_int`. Bases other than [ACGTacgt],
+    like N's, are packed as A's and marked in a bitmask beside the
+    packed bases, so that the k-mers covering them can be skipped.
+    Packing is done in C and doesn't hold the GIL.
+    """
+
+    def __init__(self, words: array, invalid: array, length: int):
+        self._words = words
+        self._invalid = invalid
+        self._length = length
+
+    @classmethod
+    def from_bytes(cls, seq: bytes, ignore_softmasked: bool = False) -> "PackedSeq":
+        """Pack a sequence of ASCII bases
+
+        Args:
+            ignore_softmasked: mark lowercase (soft-masked) bases invalid,
+                like N's, instead of packing them like uppercase
+        """
+        words = array("Q", [0]) * -(-len(seq) // 32)
+        invalid = array("Q", [0]) * -(-len(seq) // 64)
+        if seq:
+            pack_seq_c(
+                seq,
+                len(seq),
+                c_char(ignore_softmasked),
+                cast(words.buffer_info()[0], POINTER(c_uint64)),
+                cast(invalid.buffer_info()[0], POINTER(c_uint64)),
+            )
+        return cls(words, invalid, len(seq))
+
+    @classmethod
+    def from_str(cls, seq: str, ignore_softmasked: bool = False) -> "PackedSeq":
+        """Pack a sequence, like `from_bytes`. Characters that aren't ASCII
+        are invalid, and take up one base each."""
+        return cls.from_bytes(seq.encode("ascii", "replace"), ignore_softmasked)
+
+    def __len__(self) -> int:
+        return self._length
+
+    def _check_range(self, position: int, length: int):
+        if position < 0 or position + length > self._length:
+            raise IndexError(
+                f"{position} to {position + length} is out of range for a "
+                f"sequence of length {self._length}"
+            )
+
+    def get(self, position: int) -> Optional[int]:
+        """Get the 2-bit code of a base, from 0 for A to 3 for T, or None if
+        it's invalid
+
+        Raises:
+            IndexError: if the position is outside the sequence
+        """
+        self._check_range(position, 1)
+        if self._invalid[position // 64] >> position % 64 & 1:
+            return None
+        return self._words[position // 32] >> position % 32 * 2 & 3
+
+    def kmer_at(self, position: int, k: int) -> Optional[int]:
+        """Get the integer format of the k-mer starting at a position, as
+        from `kmer_to_int`, or None if it covers an invalid base
+
+        Raises:
+            IndexError: if the k-mer runs outside the sequence
+            KmerLengthError: if `k` is less than 1 or more than 32
+        """
+        _check_k(k)
+        self._check_range(position, k)
+        word, offset = divmod(position, 64)
+        bits = self._invalid[word] >> offset
+        if offset + k > 64:
+            bits |= self._invalid[word + 1] << 64 - offset
+        if bits & (1 << k) - 1:
+            return None
+
+        word, offset = divmod(2 * position, 64)
+        kmer_int = self._words[word] >> offset
+        if offset + 2 * k > 64:
+            kmer_int |= self._words[word + 1] << 64 - offset
+        return kmer_int & (1 << 2 * k) - 1
+
+    def iter_kmers(self, k: int) -> Iterator[Tuple[int, int]]:
+        """Iterate over the k-mers that don't cover an invalid base
+
+        Yields:
+            the position of each k-mer, and its integer format from
+            `kmer_at`
+        """
+        _check_k(k)
+        for position in range(self._length - k + 1):
+            kmer_int = self.kmer_at(position, k)
+            if kmer_int is not None:
+                yield position, kmer_int
+
+
+def count_kmers_in_packed_lookups(
+    packed: PackedSeq,
+    hap_a: KmerLookup,
+    hap_b: KmerLookup,
+    start: int = 0,
+    end: Optional[int] = None,
+    step: int = 1,
+) -> Tuple[int, int, int]:
+    """Count k-mers of a packed sequence in two lookups, and the k-mers
+    skipped, like `count_kmers_in_lookups`
+
+    Only the k-mers starting from `start` up to `end` are counted, so a
+    long sequence can be packed once and counted window by window. k-mers
+    are skipped if they cover a base that was invalid when the sequence
+    was packed.
+
+    Args:
+        start: the position of the first k-mer to count
+        end: the position after the last k-mer to count, by default the
+            end of the sequence. k-mers running past the end of the
+            sequence aren't counted.
+        step: only look at every `step`th k-mer from `start`
+
+    Raises:
+        ValueError: if the lookups have different k, `start` is negative,
+            or `step` is less than 1
+    """
+    _check_step(step)
+    if hap_a.k != hap_b.k:
+        raise ValueError(f"cannot compare {hap_a.k}-mers and {hap_b.k}-mers")
+    if start < 0:
+        raise ValueError(f"start must not be negative, not {start}")
+    if end is None:
+        end = len(packed)
+    if end <= start or not packed._words:
+        return 0, 0, 0
+
+    count_a, count_b, num_skipped = c_int(), c_int(), c_int()
+    count_kmers_in_packed_lookup_c(
+        cast(packed._words.buffer_info()[0], POINTER(c_uint64)),
+        cast(packed._invalid.buffer_info()[0], POINTER(c_uint64)),
+        len(packed),
+        start,
+        end,
+        hap_a.k,
+        *hap_a._c_lookup(),
+        *hap_b._c_lookup(),
+        byref(count_a),
+        byref(count_b),
+        byref(num_skipped),
+        step,
+    )
+    return count_a.value, count_b.value, num_skipped.value
+
+
+class FuzzyCounts(NamedTuple):
+    """The k-mers of a read found by `count_kmers_in_lookups_fuzzy`"""
+
+    hap_a: int
+    """The number of k-mers in haplotype A"""
+    hap_b: int
+    """The number of k-mers in haplotype B"""
+    fuzzy_a: int
+    """The number of k-mers in neither haplotype, but one substitution away
+    from a k-mer in haplotype A only"""
+    fuzzy_b: int
+    """The number of k-mers one substitution away from a k-mer in haplotype
+    B only"""
+    num_skipped: int
+    """The number of k-mers skipped, as by `count_kmers_in_lookups`"""
+
+
+def count_kmers_in_lookups_fuzzy(
+    read: Seq,
+    hap_a: KmerLookup,
+    hap_b: KmerLookup,
+    ignore_softmasked: bool = False,
+    quals: Optional[Seq] = None,
+    min_base_quality: int = 0,
+    step: int = 1,
+) -> FuzzyCounts:
+    """Count k-mers in a read and two lookups, also counting k-mers with a
+    neighbor in one, e.g., because of a sequencing error
+
+    Each k-mer is looked up exactly first, like `count_kmers_in_lookups`,
+    and only k-mers that aren't in either lookup have their `neighbors`
+    looked up in both haplotypes. A k-mer with neighbors in both, e.g.,
+    from an error at a SNP, isn't counted for either. That's up to 6k
+    more lookups for each k-mer that isn't a hit, so this is much slower
+    than exact matching, and is only worth it for error-prone reads like
+    nanopore reads.
+
+    Raises:
+        ValueError: see `count_kmers_in_lookups`
+    """
+    check_same_k(hap_a, hap_b)
+    _check_step(step)
+    if quals is not None and len(quals) != len(read):
+        raise ValueError(
+            f"the quality string is {len(quals)} long, but the read is {len(read)}"
+        )
+
+    counts = [c_int() for _ in FuzzyCounts._fields]
+    count_a, count_b, fuzzy_a, fuzzy_b, num_skipped = counts
+    count_kmers_in_read_fuzzy_c(
+        _encode_seq(read),
+        None if quals is None else _encode_seq(quals),
+        hap_a.k,
+        *hap_a._c_lookup(),
+        *hap_b._c_lookup(),
+        byref(count_a),
+        byref(count_b),
+        byref(fuzzy_a),
+        byref(fuzzy_b),
+        byref(num_skipped),
+        c_char(ignore_softmasked),
+        c_char(min(PHRED_OFFSET + min_base_quality, 127)),
+        step,
+    )
+    return FuzzyCounts(*(count.value for count in counts))
+
+
+class MinimizerCounts(NamedTuple):
+    """The minimizers of a read found by `count_minimizers_in_lookups`"""
+
+    hap_a: int
+    """The number of minimizers in haplotype A"""
+    hap_b: int
+    """The number of minimizers in haplotype B"""
+    num_minimizers: int
+    """The number of minimizers looked up"""
+    num_skipped: int
+    """The number of k-mers skipped, as by `count_kmers_in_lookups`"""
+
+
+def _check_window(window: int):
+    if not 1 <= window <= MAX_MINIMIZER_WINDOW:
+        raise ValueError(
+            f"the minimizer window must be from 1 to {MAX_MINIMIZER_WINDOW} "
+            f"k-mers, not {window}"
+        )
+
+
+def count_minimizers_in_lookups(
+    read: Seq,
+    hap_a: KmerLookup,
+    hap_b: KmerLookup,
+    window: int,
+    ignore_softmasked: bool = False,
+    quals: Optional[Seq] = None,
+    min_base_quality: int = 0,
+) -> MinimizerCounts:
+    """Count the `minimizers` of a read in two lookups
+
+    This is `count_kmers_in_lookups`, but only looks up the read's
+    minimizers, which are about 2 / (window + 1) of its k-mers. The
+    lookups still need all of their k-mers, since any k-mer can be a
+    minimizer of some read.
+
+    Args:
+        window: the number of k-mers in each window
+        See `count_kmers_in_lookups` for the rest.
+
+    Raises:
+        ValueError: if `window` isn't from 1 to `MAX_MINIMIZER_WINDOW`,
+            or see `count_kmers_in_lookups`
+    """
+    check_same_k(hap_a, hap_b)
+    _check_window(window)
+    if quals is not None and len(quals) != len(read):
+        raise ValueError(
+            f"the quality string is {len(quals)} long, but the read is {len(read)}"
+        )
+
+    counts = [c_int() for _ in MinimizerCounts._fields]
+    count_a, count_b, num_minimizers, num_skipped = counts
+    count_minimizers_in_read_lookup_c(
+        _encode_seq(read),
+        None if quals is None else _encode_seq(quals),
+        hap_a.k,
+        window,
+        *hap_a._c_lookup(),
+        *hap_b._c_lookup(),
+        byref(count_a),
+        byref(count_b),
+        byref(num_minimizers),
+        byref(num_skipped),
+        c_char(ignore_softmasked),
+        c_char(min(PHRED_OFFSET + min_base_quality, 127)),
+    )
+    return MinimizerCounts(*(count.value for count in counts))
+
+
+def minimizers(
+    seq: str, k: int, window: int, ignore_softmasked: bool = False
+) -> Iterator[int]:
+    """Iterate over the minimizers of a sequence.
+
+    The minimizer of each window of `window` consecutive k-mers is its
+    canonical k-mer with the lowest hash, taking the last one if there's
+    a tie. The hash is invertible, so distinct k-mers never tie, and
+    doesn't favor low-complexity k-mers like AAAAA...A the way taking
+    the lowest k-mer would. Consecutive windows usually share a
+    minimizer, which is only yielded once. Because the k-mers are
+    canonical, a sequence and its reverse complement have the same
+    minimizers, in reverse order, as long as no k-mer turns up twice in
+    a window.
+
+    This is the Python counterpart of the C code that
+    `count_minimizers_in_lookups` uses, which is much faster.
+
+    Args:
+        seq: the sequence to get minimizers from
+        k: the k-mer size
+        window: the number of k-mers in each window. Windows including a
+            k-mer skipped for an N, as by `iter_kmers`, have no
+            minimizer.
+        ignore_softmasked: see `iter_kmers`
+
+    Yields:
+        the integer format of each minimizer, canonicalized
+    """
+    _check_k(k)
+    _check_window(window)
+    base_to_int = _base_to_int_table(ignore_softmasked)
+    shift = 2 * (k - 1)
+    mask = (1 << 2 * k) - 1
+    kmer_int = 0
+    num_valid_bases = 0
+    # (position, hash, canonical k-mer) for the current run of valid k-mers
+    kmers_in_run: List[Tuple[int, int, int]] = []
+    last_position = None
+
+    for position, base in enumerate(seq):
+        base_int = base_to_int.get(base)
+        if base_int is None:
+            num_valid_bases = 0
+            kmers_in_run = []
+            continue
+        kmer_int = kmer_int >> 2 | base_int << shift
+        num_valid_bases += 1
+        if num_valid_bases < k:
+            continue
+
+        canonical_kmer_int = canonicalize(kmer_int, k)
+        kmers_in_run.append(
+            (position, invertible_hash_c(canonical_kmer_int, mask), canonical_kmer_int)
+        )
+        if len(kmers_in_run) < window:
+            continue
+        del kmers_in_run[:-window]
+        # the last of the lowest hashes, since max prefers the first
+        min_position, _, minimizer = max(
+            kmers_in_run, key=lambda kmer: (-kmer[1], kmer[0])
+        )
+        if min_position != last_position:
+            last_position = min_position
+            yield minimizer
+
+
+class SyncmerCounts(NamedTuple):
+    """The syncmers of a read found by `count_syncmers_in_lookups`"""
+
+    hap_a: int
+    """The number of syncmers in haplotype A"""
+    hap_b: int
+    """The number of syncmers in haplotype B"""
+    num_syncmers: int
+    """The number of syncmers looked up"""
+    num_skipped: int
+    """The number of k-mers skipped, as by `count_kmers_in_lookups`"""
+
+
+def _check_syncmer_size(k: int, s: int):
+    if not 1 <= s < k:
+        raise ValueError(f"the s-mers of syncmers must be from 1 to {k - 1}, not {s}")
+
+
+def count_syncmers_in_lookups(
+    read: Seq,
+    hap_a: KmerLookup,
+    hap_b: KmerLookup,
+    s: int,
+    ignore_softmasked: bool = False,
+    quals: Optional[Seq] = None,
+    min_base_quality: int = 0,
+) -> SyncmerCounts:
+    """Count the `syncmers` of a read in two lookups
+
+    This is `count_kmers_in_lookups`, but only looks up the read's
+    closed syncmers, which are about 2 / (k - s + 1) of its k-mers. As
+    with `count_minimizers_in_lookups`, the lookups still need all of
+    their k-mers.
+
+    Args:
+        s: the s-mer size, from 1 to k - 1
+        See `count_kmers_in_lookups` for the rest.
+
+    Raises:
+        ValueError: if `s` isn't from 1 to k - 1, or see
+            `count_kmers_in_lookups`
+    """
+    check_same_k(hap_a, hap_b)
+    _check_syncmer_size(hap_a.k, s)
+    if quals is not None and len(quals) != len(read):
+        raise ValueError(
+            f"the quality string is {len(quals)} long, but the read is {len(read)}"
+        )
+
+    counts = [c_int() for _ in SyncmerCounts._fields]
+    count_a, count_b, num_syncmers, num_skipped = counts
+    count_syncmers_in_read_lookup_c(
+        _encode_seq(read),
+        None if quals is None else _encode_seq(quals),
+        hap_a.k,
+        s,
+        *hap_a._c_lookup(),
+        *hap_b._c_lookup(),
+        byref(count_a),
+        byref(count_b),
+        byref(num_syncmers),
+        byref(num_skipped),
+        c_char(ignore_softmasked),
+        c_char(min(PHRED_OFFSET + min_base_quality, 127)),
+    )
+    return SyncmerCounts(*(count.value for count in counts))
+
+
+def syncmers(
+    seq: str, k: int, s: int, ignore_softmasked: bool = False
+) -> Iterator[int]:
+    """Iterate over the closed syncmers of a sequence.
+
+    A k-mer is a closed syncmer if the lowest hash of its canonical
+    s-mers, by the same hash as `minimizers`, is that of its first or
+    last s-mer. Unlike a minimizer, each k-mer is picked or not by its
+    own bases alone, so there are no artifacts at window boundaries,
+    and about 2 / (k - s + 1) of the k-mers are picked. The s-mers of
+    the reverse complement of a k-mer are its s-mers in reverse order,
+    so a sequence and its reverse complement have the same syncmers, in
+    reverse order.
+
+    This is the Python counterpart of the C code that
+    `count_syncmers_in_lookups` uses, which is much faster.
+
+    Args:
+        seq: the sequence to get syncmers from
+        k: the k-mer size
+        s: the s-mer size, from 1 to k - 1
+        ignore_softmasked: see `iter_kmers`
+
+    Yields:
+        the integer format of each syncmer, canonicalized
+    """
+    _check_k(k)
+    _check_syncmer_size(k, s)
+    smer_mask = (1 << 2 * s) - 1
+    for kmer_int in iter_kmers(seq, k, ignore_softmasked):
+        smers = (kmer_int >> 2 * i & smer_mask for i in range(k - s + 1))
+        hashes = [invertible_hash_c(canonicalize(smer, s), smer_mask) for smer in smers]
+        if min(hashes) in (hashes[0], hashes[-1]):
+            yield canonicalize(kmer_int, k)
+
+
+def neighbors(kmer_int: int, k: int) -> Iterator[int]:
+    """Generate the 3k k-mers one substitution away from a k-mer
+
+    Each base is changed to each of the other three bases in turn, by
+    flipping its two bits.
+
+    >>> kmer_ints = neighbors(kmer_to_int("AC"), 2)
+    >>> sorted(int_to_kmer(kmer_int, 2) for kmer_int in kmer_ints)
+    ['AA', 'AG', 'AT', 'CC', 'GC', 'TC']
+    """
+    for position in range(k):
+        for substitution in range(1, 4):
+            yield kmer_int ^ substitution << (2 * position)
+
+
+class Hasher(IntEnum):
+    """A hash function for a `KmerSet`
+
+    The values are the HASHER_ constants in the C library.
+    """
+
+    MIX = 0
+    """The original xorshift-multiply hash"""
+    FIBONACCI = 1
+    """A single multiplication, which is faster and is plenty for packed
+    k-mers, whose bits are already well mixed. This is the default."""
+
+
+def _count_lines(path: str) -> int:
+    """Count the lines in a file quickly, without decoding it"""
+    num_lines = 0
+    with open(path, "rb") as fp:
+        for chunk in iter(lambda: fp.read(1 << 20), b""):
+            num_lines += chunk.count(b"\n")
+    return num_lines
+
+
+class KmerSet(KmerLookup):
+    """A quickly searchable set of canonical k-mers
+
+    k-mers are stored in a hash set in the C library, so a KmerSet can
+    be used with `count_kmers_in_read` via its `hash_set` attribute.
+    Membership can be checked with either orientation of a k-mer:
+
+    >>> kmer_set = KmerSet(3)
+    >>> kmer_set.add(kmer_to_int("AAC"))
+    True
+    >>> kmer_to_int("GTT") in kmer_set
+    True
+
+    Attributes:
+        homopolymer_compressed: whether the k-mers are from
+            homopolymer-compressed sequence (see `compress_homopolymers`).
+            The set doesn't use this itself; it is saved by
+            `write_binary` so that it can be checked against how reads
+            are going to be handled when the set is read back in.
+        seed: the `SpacedSeed` the k-mers were made with, if any, which
+            is saved by `write_binary` the same way
+    """
+
+    def __init__(self, k: int, capacity: int = 0, hasher: Hasher = Hasher.FIBONACCI):
+        """Create an empty set
+
+        Args:
+            k: the k-mer size
+            capacity: the number of k-mers expected to be added. The set
+                grows as needed, but this avoids rehashing.
+            hasher: the hash function to use. Only worth changing for
+                benchmarking.
+        """
+        _check_k(k)
+        self.hash_set: HashSet = initialize_hash_set_with_hasher_c(
+            k, capacity, hasher
+        )
+        self.homopolymer_compressed = False
+        self.seed: Optional[SpacedSeed] = None
+
+    @classmethod
+    def with_capacity(cls, capacity: int, k: int) -> "KmerSet":
+        """Create an empty set with room for `capacity` k-mers"""
+        return cls(k, capacity)
+
+    def __del__(self):
+        if getattr(self, "hash_set", None):
+            free_hash_set_c(self.hash_set)
+
+    @classmethod
+    def from_reader(
+        cls,
+        fp: TextIO,
+        k: Optional[int] = None,
+        min_count: Optional[int] = None,
+        dump_format: DumpFormat = DumpFormat.AUTO,
+        capacity: int = 0,
+    ) -> "KmerSet":
+        """Read a list of k-mers into a set.
+
+        The list is streamed one line at a time, so it never needs to
+        fit in memory. See `iter_kmer_dump` for the formats and the other
+        arguments.
+
+        Args:
+            capacity: the number of k-mers expected, if known, e.g. the
+                number of lines in the list, so the set doesn't need to
+                grow while it is read
+
+        Raises:
+            KmerFileError: if the list can't be parsed
+        """
+        kmer_set = None
+        for k, kmer_int in iter_kmer_dump(fp, k, min_count, dump_format):
+            if kmer_set is None:
+                kmer_set = cls(k, capacity)
+            kmer_set.add(kmer_int)
+
+        if kmer_set is None:
+            # iter_kmer_dump raises for a file with no k-mers unless k is given
+            kmer_set = cls(k)  # type: ignore[arg-type]
+        return kmer_set
+
+    @classmethod
+    def from_file(
+        cls,
+        kmer_file_path: str,
+        k: Optional[int] = None,
+        min_count: Optional[int] = None,
+        dump_format: DumpFormat = DumpFormat.AUTO,
+    ) -> "KmerSet":
+        """Read a file containing a list of k-mers into a set.
+
+        The lines in the file are counted first so that the set can be
+        made big enough for all of them up front, which is much quicker
+        than growing it as it fills. See `iter_kmer_dump` for the formats
+        and arguments.
+
+        Raises:
+            KmerFileError: if the file can't be parsed, including if it is
+                a binary meryl database rather than a text dump of one
+        """
+        _check_kmer_file_path(kmer_file_path)
+        capacity = _count_lines(kmer_file_path)
+        with open(kmer_file_path, "r") as fp:
+            kmer_set = cls.from_reader(fp, k, min_count, dump_format, capacity)
+        kmer_set.path = kmer_file_path
+        return kmer_set
+
+    @property
+    def k(self) -> int:
+        """The k-mer size"""
+        return self.hash_set.contents.k
+
+    def __len__(self) -> int:
+        return self.hash_set.contents.num_kmers
+
+    def add(self, kmer_int: int) -> bool:
+        """Add a k-mer in integer format to the set
+
+        Returns:
+            True if the k-mer was added, False if it was already there
+        """
+        return bool(add_int_to_hash_c(self.hash_set, kmer_int))
+
+    def __contains__(self, kmer_int: int) -> bool:
+        return bool(int_in_hash_set_c(kmer_int, self.hash_set))
+
+    def __iter__(self) -> Iterator[int]:
+        """Iterate over the canonical k-mers in the set, in no order"""
+        hash_set = self.hash_set.contents
+        for i in range(hash_set.hash_size):
+            if hash_set.full[i]:
+                yield hash_set.kmers[i]
+
+    def _c_lookup(self) -> Tuple[c_void_p, c_void_p]:
+        return hash_set_lookup_c, cast(self.hash_set, c_void_p)
+
+    @classmethod
+    def _from_hash_set(
+        cls,
+        hash_set: HashSet,
+        homopolymer_compressed: bool = False,
+        seed: Optional[SpacedSeed] = None,
+    ) -> "KmerSet":
+        """Wrap a hash set made by the C library, taking ownership of it"""
+        kmer_set = cls.__new__(cls)
+        kmer_set.hash_set = hash_set
+        kmer_set.homopolymer_compressed = homopolymer_compressed
+        kmer_set.seed = seed
+        return kmer_set
+
+    def _check_same_k(self, other: "KmerSet"):
+        if self.k != other.k:
+            raise ValueError(
+                f"cannot combine sets of {self.k}-mers and {other.k}-mers"
+            )
+        if self.homopolymer_compressed != other.homopolymer_compressed:
+            raise ValueError(
+                "cannot combine homopolymer-compressed and uncompressed sets"
+            )
+        if self.seed != other.seed:
+            raise ValueError("cannot combine sets made with different seed patterns")
+
+    def difference(self, other: "KmerSet") -> "KmerSet":
+        """Make a new set of the k-mers in this set that are not in `other`
+
+        Raises:
+            ValueError: if the sets have different k, only one is
+                homopolymer-compressed, or they have different seeds
+        """
+        self._check_same_k(other)
+        return KmerSet._from_hash_set(
+            hash_set_difference_c(self.hash_set, other.hash_set),
+            self.homopolymer_compressed,
+            self.seed,
+        )
+
+    def intersection(self, other: "KmerSet") -> "KmerSet":
+        """Make a new set of the k-mers in both this set and `other`
+
+        Raises:
+            ValueError: if the sets have different k, only one is
+                homopolymer-compressed, or they have different seeds
+        """
+        self._check_same_k(other)
+        return KmerSet._from_hash_set(
+            hash_set_intersection_c(self.hash_set, other.hash_set),
+            self.homopolymer_compressed,
+            self.seed,
+        )
+
+    def intersection_size(self, other: "KmerSet") -> int:
+        """Count the k-mers in both this set and `other`, without making a
+        new set like `intersection` does
+
+        Raises:
+            ValueError: if the sets have different k, only one is
+                homopolymer-compressed, or they have different seeds
+        """
+        self._check_same_k(other)
+        return hash_set_intersection_size_c(self.hash_set, other.hash_set)
+
+    def jaccard(self, other: "KmerSet") -> float:
+        """The Jaccard index of this set and `other`: the number of k-mers
+        in both divided by the number in either, or 0 if both are empty
+
+        Raises:
+            ValueError: see `intersection_size`
+        """
+        num_shared = self.intersection_size(other)
+        num_either = len(self) + len(other) - num_shared
+        return num_shared / num_either if num_either else 0.0
+
+    def stats(self) -> "KmerSetStats":
+        """Summarize the set, e.g., to sanity-check hap-mers before using
+        them"""
+        num_bases = len(self) * self.k
+        gc_content = hash_set_gc_count_c(self.hash_set) / num_bases if num_bases else 0
+        return KmerSetStats(len(self), self.k, gc_content, self.homopolymer_compressed)
+
+    def write_binary(self, fp: BinaryIO):
+        """Write the set to a file in a compact binary format
+
+        The format is a header containing `BINARY_MAGIC`, the format
+        version, k, the `BINARY_FLAG_` flags, and the number of k-mers,
+        then the span and care mask of the `seed` if it has one, as
+        little-endian 32-bit unsigned integers, followed by the integer
+        format of every k-mer in the set,
+        sorted, as little-endian 64-bit unsigned integers. This is much
+        faster to read back in with `read_binary` than a text list is.
+
+        The k-mers are written straight from `into_sorted`'s array, so
+        the file can be read back in as either a `KmerSet` or a
+        `SortedKmerSet`.
+
+        Args:
+            fp: a file opened for writing in binary mode
+        """
+        self.into_sorted().write_binary(fp)
+
+    @classmethod
+    def read_binary(cls, fp: BinaryIO) -> "KmerSet":
+        """Read a set written by `write_binary`
+
+        Args:
+            fp: a file opened for reading in binary mode
+
+        Raises:
+            KmerFileError: if the file is not a binary k-mer set, is from
+                an unsupported version of the format or has unknown flags,
+                or is truncated
+        """
+        k, flags, num_kmers, seed, chunks = _read_binary(fp)
+        kmer_set = cls(k, num_kmers)
+        kmer_set.homopolymer_compressed = bool(
+            flags & BINARY_FLAG_HOMOPOLYMER_COMPRESSED
+        )
+        kmer_set.seed = seed
+        for chunk in chunks:
+            address, length = chunk.buffer_info()
+            add_ints_to_hash_c(
+                kmer_set.hash_set, cast(address, POINTER(c_uint64)), length
+            )
+        return kmer_set
+
+    def into_sorted(self) -> "SortedKmerSet":
+        """Make a `SortedKmerSet` of the k-mers in this set
+
+        The sorted set takes about two thirds of the memory, so if only
+        lookups are needed, the hash set can be dropped afterwards.
+        """
+        kmer_ints = array("Q", [0]) * len(self)
+        address, _ = kmer_ints.buffer_info()
+        copy_hash_set_kmers_c(self.hash_set, cast(address, POINTER(c_uint64)))
+        return SortedKmerSet._from_array(
+            self.k, kmer_ints, self.homopolymer_compressed, self.seed
+        )
+
+    def to_file(self, kmer_file_path: str):
+        """Write the k-mers in the set to a file, one per line
+
+        The file can be read back in with `from_file`.
+        """
+        with open(kmer_file_path, "w") as fp:
+            for kmer_int in self:
+                print(int_to_kmer(kmer_int, self.k), file=fp)
+
+
+@dataclass
+class KmerSetStats:
+    """Statistics about a `KmerSet`, from `KmerSet.stats`"""
+
+    num_kmers: int
+    """The number of k-mers in the set"""
+    k: int
+    """The k-mer size"""
+    gc_content: float
+    """The fraction of the bases of all the k-mers that are G or C, which
+    is the same in either orientation, or 0 for an empty set"""
+    homopolymer_compressed: bool
+    """See `KmerSet`"""
+
+
+class KmerCountMap(KmerSet):
+    """A `KmerSet` that also keeps the count of each k-mer
+
+    The counts are from the k-mer counter's dump, so they say how many
+    times each k-mer was seen in the parent's reads, and are used to
+    weigh hits with `weigh_kmers_in_count_maps`. They are kept in a dict
+    alongside the hash set, which takes about another 100 bytes per k-mer,
+    so this is only worth using for that.
+
+    >>> count_map = KmerCountMap(3)
+    >>> count_map.add(kmer_to_int("AAC"), 12)
+    True
+    >>> count_map.count(kmer_to_int("GTT"))
+    12
+    """
+
+    def __init__(self, k: int, capacity: int = 0, hasher: Hasher = Hasher.FIBONACCI):
+        super().__init__(k, capacity, hasher)
+        self.counts: Dict[int, int] = {}
+        """The count of each k-mer, by canonical k-mer"""
+
+    @classmethod
+    def from_reader(
+        cls,
+        fp: TextIO,
+        k: Optional[int] = None,
+        min_count: Optional[int] = None,
+        dump_format: DumpFormat = DumpFormat.AUTO,
+        capacity: int = 0,
+    ) -> "KmerCountMap":
+        """Read a list of k-mers and their counts, like `KmerSet.from_reader`
+
+        Raises:
+            KmerFileError: if the list can't be parsed, has no counts, or
+                has none of at least `min_count`
+        """
+        count_map = None
+        for k, kmer_int, count in iter_kmer_dump_counts(fp, k, min_count, dump_format):
+            if count_map is None:
+                count_map = cls(k, capacity)
+            count_map.add(kmer_int, count)
+
+        if count_map is None:
+            # iter_kmer_dump_counts raises for no k-mers unless k is given
+            count_map = cls(k)  # type: ignore[arg-type]
+        return count_map
+
+    def add(self, kmer_int: int, count: int = 1) -> bool:
+        """Add a k-mer with a count, keeping the higher count if it was
+        already there, e.g., in the other orientation
+
+        Returns:
+            True if the k-mer was added, False if it was already there
+        """
+        canonical = canonicalize(kmer_int, self.k)
+        self.counts[canonical] = max(count, self.counts.get(canonical, 0))
+        return super().add(kmer_int)
+
+    def count(self, kmer_int: int) -> int:
+        """The count of a k-mer in either orientation, or 0 if it isn't in
+        the set"""
+        return self.counts.get(canonicalize(kmer_int, self.k), 0)
+
+
+def weigh_kmers_in_count_maps(
+    read: str,
+    hap_a: KmerLookup,
+    hap_b: KmerLookup,
+    cap: int,
+    ignore_softmasked: bool = False,
+    quals: Optional[str] = None,
+    min_base_quality: int = 0,
+    step: int = 1,
+) -> Tuple[int, int]:
+    """Add up the counts of the k-mers in a read found in two count maps
+
+    Each k-mer found in a haplotype adds its count there, up to `cap`, so
+    k-mers seen only once or twice in the parent, which are often
+    sequencing errors, count for less than solid ones. The same k-mers are
+    skipped as by `count_kmers_in_lookups`. This is done in Python, so it
+    is much slower than just counting hits.
+
+    Args:
+        read: the read's sequence
+        hap_a: the k-mers of haplotype A, which must be a `KmerCountMap`
+        hap_b: the k-mers of haplotype B, which must be a `KmerCountMap`
+        cap: the most that any one k-mer adds
+        ignore_softmasked: see `iter_kmers`
+        quals: see `count_kmers_in_lookups`
+        min_base_quality: see `count_kmers_in_lookups`
+        step: see `count_kmers_in_lookups`
+
+    Returns:
+        the total weight of the k-mers found in haplotype A and in B
+
+    Raises:
+        ValueError: if the lookups aren't both `KmerCountMap`s or have
+            different k
+    """
+    if not isinstance(hap_a, KmerCountMap) or not isinstance(hap_b, KmerCountMap):
+        raise ValueError("weighing k-mers by count needs k-mer sets with counts")
+    if hap_a.k != hap_b.k:
+        raise ValueError(f"cannot compare {hap_a.k}-mers and {hap_b.k}-mers")
+
+    if quals is not None and min_base_quality:
+        read = _mask_low_quality(read, quals, min_base_quality)
+    weight_a = weight_b = 0
+    for kmer_int in iter_canonical_kmers(read, hap_a.k, ignore_softmasked, step):
+        weight_a += min(hap_a.counts.get(kmer_int, 0), cap)
+        weight_b += min(hap_b.counts.get(kmer_int, 0), cap)
+    return weight_a, weight_b
+
+
+class SortedKmerSet(KmerLookup):
+    """A sorted array of canonical k-mers, searched with binary search
+
+    This takes 8 bytes per k-mer, compared to about 12 for a `KmerSet`,
+    and finds the same k-mers, but lookups are slower and it can't be
+    added to. Make one from a `KmerSet` with `KmerSet.into_sorted`, or
+    read one straight from a binary file with `read_binary`.
+
+    >>> sorted_set = SortedKmerSet(3, [kmer_to_int("GTT"), kmer_to_int("AAC")])
+    >>> len(sorted_set)
+    1
+    >>> kmer_to_int("AAC") in sorted_set
+    True
+
+    Attributes:
+        homopolymer_compressed: see `KmerSet`
+        seed: see `KmerSet`
+    """
+
+    def __init__(self, k: int, kmer_ints: Iterable[int] = ()):
+        """Create a set of the given k-mers
+
+        Args:
+            k: the k-mer size
+            kmer_ints: the k-mers, in integer format. They are
+                canonicalized, and duplicates are removed.
+        """
+        _check_k(k)
+        self._set_kmers(k, array("Q", kmer_ints))
+        self.homopolymer_compressed = False
+        self.seed: Optional[SpacedSeed] = None
+
+    @classmethod
+    def _from_array(
+        cls,
+        k: int,
+        kmer_ints: array,
+        homopolymer_compressed: bool = False,
+        seed: Optional[SpacedSeed] = None,
+    ) -> "SortedKmerSet":
+        """Make a set from an array of k-mers, taking ownership of it"""
+        sorted_set = cls.__new__(cls)
+        sorted_set._set_kmers(k, kmer_ints)
+        sorted_set.homopolymer_compressed = homopolymer_compressed
+        sorted_set.seed = seed
+        return sorted_set
+
+    def _set_kmers(self, k: int, kmer_ints: array):
+        address, length = kmer_ints.buffer_info()
+        num_kmers = sort_kmer_ints_c(cast(address, POINTER(c_uint64)), length, k)
+        del kmer_ints[num_kmers:]
+        # the array is never changed after this, so its buffer stays put
+        self._kmer_ints = kmer_ints
+        address, _ = kmer_ints.buffer_info()
+        self._sorted_kmers = _SortedKmers(
+            cast(address, POINTER(c_uint64)), num_kmers, k
+        )
+
+    @classmethod
+    def read_binary(cls, fp: BinaryIO) -> "SortedKmerSet":
+        """Read a set written by `KmerSet.write_binary` or `write_binary`
+
+        The k-mers in the file are already sorted, so they are used as
+        they are.
+
+        Raises:
+            KmerFileError: see `KmerSet.read_binary`
+        """
+        k, flags, _, seed, chunks = _read_binary(fp)
+        kmer_ints = array("Q")
+        for chunk in chunks:
+            kmer_ints.extend(chunk)
+        return cls._from_array(
+            k, kmer_ints, bool(flags & BINARY_FLAG_HOMOPOLYMER_COMPRESSED), seed
+        )
+
+    def write_binary(self, fp: BinaryIO):
+        """Write the set in the format of `KmerSet.write_binary`"""
+        flags = BINARY_FLAG_HOMOPOLYMER_COMPRESSED if self.homopolymer_compressed else 0
+        _write_binary(fp, self.k, flags, self._kmer_ints, self.seed)
+
+    @property
+    def k(self) -> int:
+        """The k-mer size"""
+        return self._sorted_kmers.k
+
+    def __len__(self) -> int:
+        return len(self._kmer_ints)
+
+    def __contains__(self, kmer_int: int) -> bool:
+        return bool(int_in_sorted_kmers_c(kmer_int, byref(self._sorted_kmers)))
+
+    def __iter__(self) -> Iterator[int]:
+        """Iterate over the canonical k-mers in the set, in sorted order"""
+        return iter(self._kmer_ints)
+
+    def _c_lookup(self) -> Tuple[c_void_p, c_void_p]:
+        return sorted_kmers_lookup_c, cast(pointer(self._sorted_kmers), c_void_p)
+
+
+class MmapKmerSet(KmerLookup):
+    """A binary k-mer set file, memory-mapped and searched where it is
+
+    This finds the same k-mers as a `SortedKmerSet` read from the file,
+    but takes no time to load, and the file's pages are read from disk
+    only as lookups need them and are shared by every process on the
+    machine that maps the same file, e.g., several classify-by-kmers
+    runs on one node, rather than each having its own copy.
+
+    The file mustn't be changed while it's mapped. Cutting it short
+    would make lookups crash the process rather than raise an error,
+    since the mapped pages would no longer be there to read.
+    """
+
+    def __init__(self, path: str):
+        """Map a file written by `KmerSet.write_binary`
+
+        Raises:
+            KmerFileError: if the file isn't a binary k-mer set (see
+                `KmerSet.read_binary`), or its length doesn't match the
+                number of k-mers in its header
+        """
+        with open(path, "rb") as fp:
+            k, flags, num_kmers, seed = _read_binary_header(fp)
+            header_size = fp.tell()
+            file_size = os.fstat(fp.fileno()).st_size
+            expected_size = header_size + 8 * num_kmers
+            # checked before mapping, so that every k-mer the C code reads
+            # is inside the file
+            if file_size < expected_size:
+                raise KmerFileError(
+                    path,
+                    None,
+                    f"file is truncated: expected {num_kmers} k-mers but found "
+                    f"{(file_size - header_size) // 8}",
+                )
+            if file_size > expected_size:
+                raise KmerFileError(
+                    path, None, f"file has {file_size - expected_size} extra bytes"
+                )
+            # a private mapping, since ctypes can only take the address of
+            # a writable buffer. Nothing writes to it, so its pages are
+            # never copied and stay shared with the file's page cache.
+            self._map = mmap.mmap(fp.fileno(), 0, access=mmap.ACCESS_COPY)
+        self._buffer = (c_char * file_size).from_buffer(self._map)
+        # the k-mers aren't aligned to 8 bytes, so the C code reads them a
+        # byte at a time (see mapped_kmers in kmers.c)
+        self._mapped_kmers = _MappedKmers(
+            addressof(self._buffer) + header_size, num_kmers, k
+        )
+        self._header_size = header_size
+        self.homopolymer_compressed = bool(flags & BINARY_FLAG_HOMOPOLYMER_COMPRESSED)
+        self.seed = seed
+        self.path = path
+
+    @property
+    def k(self) -> int:
+        """The k-mer size"""
+        return self._mapped_kmers.k
+
+    def __len__(self) -> int:
+        return self._mapped_kmers.num_kmers
+
+    def __contains__(self, kmer_int: int) -> bool:
+        return bool(int_in_mapped_kmers_c(kmer_int, byref(self._mapped_kmers)))
+
+    def __iter__(self) -> Iterator[int]:
+        """Iterate over the canonical k-mers in the set, in sorted order"""
+        kmers_view = memoryview(self._map)[self._header_size :]
+        return (kmer_int for (kmer_int,) in struct.iter_unpack("<Q", kmers_view))
+
+    def write_binary(self, fp: BinaryIO):
+        """Write the set in the format of `KmerSet.write_binary`, which is
+        a copy of the mapped file"""
+        fp.write(self._map)
+
+    def _c_lookup(self) -> Tuple[c_void_p, c_void_p]:
+        return mapped_kmers_lookup_c, cast(pointer(self._mapped_kmers), c_void_p)
+
+
+class KmerBloom(KmerLookup):
+    """A Bloom filter of canonical k-mers
+
+    This takes much less memory than a `KmerSet` (about 14 bits per
+    k-mer at a false positive rate of 0.001, compared to about 100 for
+    a `KmerSet`), at the cost of sometimes saying a k-mer is in it when
+    it isn't. It can't be iterated over or written out.
+
+    When classifying, a false positive makes a k-mer from neither
+    haplotype count as a hit for one of them, and since false positives
+    are equally likely in each haplotype's filter (given filters built
+    with the same false positive rate), this adds a little noise to both
+    counts rather than pushing reads towards either haplotype. A k-mer
+    in haplotype B can also show up as a false positive for haplotype A
+    (which is checked first), so keep the rate low, e.g., 0.001.
+    """
+
+    def __init__(self, k: int, num_bits: int, num_hashes: int):
+        """Create an empty Bloom filter
+
+        Use `for_capacity` to pick the size for a false positive rate.
+
+        Args:
+            k: the k-mer size
+            num_bits: the number of bits in the filter
+            num_hashes: the number of bits set for each k-mer, from 1
+                to 255
+        """
+        _check_k(k)
+        if num_bits < 1:
+            raise ValueError(f"num_bits must be at least 1, not {num_bits}")
+        if not 1 <= num_hashes <= 255:
+            raise ValueError(f"num_hashes must be from 1 to 255, not {num_hashes}")
+        self.bloom_filter = initialize_bloom_filter_c(k, num_bits, num_hashes)
+
+    def __del__(self):
+        if getattr(self, "bloom_filter", None):
+            free_bloom_filter_c(self.bloom_filter)
+
+    @classmethod
+    def for_capacity(cls, k: int, n_expected: int, fpr: float) -> "KmerBloom":
+        """Create an empty Bloom filter sized for a false positive rate
+
+        Args:
+            k: the k-mer size
+            n_expected: the number of distinct k-mers that will be added
+            fpr: the false positive rate to aim for once `n_expected`
+                k-mers have been added, between 0 and 1 (exclusive)
+        """
+        if not 0 < fpr < 1:
+            raise ValueError(f"fpr must be between 0 and 1, not {fpr}")
+        n_expected = max(n_expected, 1)
+        num_bits = math.ceil(-n_expected * math.log(fpr) / math.log(2) ** 2)
+        num_hashes = max(1, min(255, round(num_bits / n_expected * math.log(2))))
+        return cls(k, num_bits, num_hashes)
+
+    @classmethod
+    def from_kmer_iter(
+        cls, kmer_ints: Iterable[int], k: int, n_expected: int, fpr: float
+    ) -> "KmerBloom":
+        """Make a Bloom filter of some k-mers
+
+        Args:
+            kmer_ints: the k-mers to add, in integer format
+            k: the k-mer size
+            n_expected: roughly how many distinct k-mers there are, to
+                size the filter with. See `for_capacity`.
+            fpr: the false positive rate to aim for
+        """
+        bloom = cls.for_capacity(k, n_expected, fpr)
+        kmer_ints = iter(kmer_ints)
+        while True:
+            chunk = array("Q", islice(kmer_ints, _BINARY_CHUNK_SIZE))
+            if not chunk:
+                break
+            bloom._add_array(chunk)
+        return bloom
+
+    @classmethod
+    def from_file(
+        cls,
+        kmer_file_path: str,
+        fpr: float,
+        k: Optional[int] = None,
+        min_count: Optional[int] = None,
+        dump_format: DumpFormat = DumpFormat.AUTO,
+    ) -> "KmerBloom":
+        """Read a file containing a list of k-mers into a Bloom filter.
+
+        The file is read twice: once to count the lines in it, to size
+        the filter, and once to add the k-mers. See `iter_kmer_dump` for
+        the formats and the rest of the arguments.
+
+        Args:
+            kmer_file_path: the path to the list of k-mers
+            fpr: the false positive rate to aim for
+
+        Raises:
+            KmerFileError: if the file can't be parsed
+        """
+        _check_kmer_file_path(kmer_file_path)
+        n_expected = _count_lines(kmer_file_path)
+
+        with open(kmer_file_path, "r") as fp:
+            dump = iter_kmer_dump(fp, k, min_count, dump_format)
+            first = next(dump, None)
+            if first is None:
+                # iter_kmer_dump raises for a file with no k-mers unless k is given
+                bloom = cls.for_capacity(k, 0, fpr)  # type: ignore[arg-type]
+            else:
+                bloom = cls.from_kmer_iter(
+                    chain([first[1]], (kmer_int for _, kmer_int in dump)),
+                    first[0],
+                    n_expected,
+                    fpr,
+                )
+        bloom.path = kmer_file_path
+        return bloom
+
+    def _add_array(self, kmer_ints: array):
+        address, length = kmer_ints.buffer_info()
+        add_ints_to_bloom_filter_c(
+            self.bloom_filter, cast(address, POINTER(c_uint64)), length
+        )
+
+    def add(self, kmer_int: int):
+        """Add a k-mer, in integer format, to the filter"""
+        self._add_array(array("Q", [kmer_int]))
+
+    @property
+    def k(self) -> int:
+        return self.bloom_filter.contents.k
+
+    @property
+    def num_bits(self) -> int:
+        """The number of bits in the filter"""
+        return self.bloom_filter.contents.num_bits
+
+    @property
+    def num_hashes(self) -> int:
+        """The number of bits set for each k-mer"""
+        return self.bloom_filter.contents.num_hashes
+
+    def __len__(self) -> int:
+        """Roughly the number of distinct k-mers added
+
+        This counts the k-mers that set at least one new bit when they
+        were added, so it is slightly low if there were false positives.
+        """
+        return self.bloom_filter.contents.num_kmers
+
+    def __contains__(self, kmer_int: int) -> bool:
+        return bool(int_in_bloom_filter_c(kmer_int, self.bloom_filter))
+
+    def _c_lookup(self) -> Tuple[c_void_p, c_void_p]:
+        return bloom_filter_lookup_c, cast(self.bloom_filter, c_void_p)
+
+
+MIN_HLL_PRECISION = 4
+"""The lowest precision of a `CardinalityEstimator`"""
+
+MAX_HLL_PRECISION = 18
+"""The highest precision of a `CardinalityEstimator`"""
+
+DEFAULT_HLL_PRECISION = 14
+"""The default precision of a `CardinalityEstimator`, which takes 16 KB
+for a relative standard error of 0.8%"""
+
+
+def _hll_sigma(x: float) -> float:
+    """The sigma function of Ertl's improved HyperLogLog estimator, for the
+    fraction of registers that are 0"""
+    if x == 1:
+        return math.inf
+    y = 1.0
+    z = x
+    while True:
+        x *= x
+        z_old = z
+        z += x * y
+        y += y
+        if z == z_old:
+            return z
+
+
+def _hll_tau(x: float) -> float:
+    """The tau function of Ertl's improved HyperLogLog estimator, for the
+    fraction of registers that aren't at the highest rank"""
+    if x == 0 or x == 1:
+        return 0.0
+    y = 1.0
+    z = 1 - x
+    while True:
+        x = math.sqrt(x)
+        z_old = z
+        y *= 0.5
+        z -= (1 - x) ** 2 * y
+        if z == z_old:
+            return z / 3
+
+
+class CardinalityEstimator:
+    """A HyperLogLog sketch estimating how many distinct k-mers there are
+
+    This takes 2 ** `precision` bytes however many k-mers are added, and
+    estimates their number of distinct values to within about
+    `relative_error`, e.g., to size hash sets or `DiskKmerCounts`
+    partitions before counting a big dataset. Estimators can be merged,
+    so each thread can keep its own and merge them at the end, and
+    adding the same k-mers to one estimator or spread over several gives
+    the same estimate.
+
+    The estimate is Ertl's improved raw estimator ("New cardinality
+    estimation algorithms for HyperLogLog sketches", 2017), which needs
+    no bias correction tables and is accurate from a handful of k-mers
+    to 2 ** 64.
+
+    >>> estimator = CardinalityEstimator()
+    >>> for kmer in ["ACGTA", "CCCCG", "ACGTA"]:
+    ...     estimator.insert(kmer_to_int(kmer))
+    >>> estimator.estimate()
+    2
+    """
+
+    def __init__(self, precision: int = DEFAULT_HLL_PRECISION):
+        """Create an empty estimator
+
+        Args:
+            precision: the number of bits of each k-mer's hash that pick
+                its register, from `MIN_HLL_PRECISION` to
+                `MAX_HLL_PRECISION`. Each one more doubles the memory and
+                cuts the error by a factor of about 1.4.
+        """
+        if not MIN_HLL_PRECISION <= precision <= MAX_HLL_PRECISION:
+            raise ValueError(
+                f"the precision must be from {MIN_HLL_PRECISION} to "
+                f"{MAX_HLL_PRECISION}, not {precision}"
+            )
+        self.precision = precision
+        self.registers = bytearray(1 << precision)
+
+    @property
+    def num_registers(self) -> int:
+        """The number of registers, 2 ** `precision`"""
+        return len(self.registers)
+
+    @property
+    def relative_error(self) -> float:
+        """The expected relative standard error of `estimate`"""
+        return 1.04 / math.sqrt(self.num_registers)
+
+    def insert_array(self, kmer_ints: array):
+        """Add an array of k-mers in integer format, as from
+        `canonical_kmer_array`"""
+        if not kmer_ints:
+            return
+        address, length = kmer_ints.buffer_info()
+        add_ints_to_hyperloglog_c(
+            (c_ubyte * self.num_registers).from_buffer(self.registers),
+            self.precision,
+            cast(address, POINTER(c_uint64)),
+            length,
+        )
+
+    def insert(self, kmer_int: int):
+        """Add a k-mer in integer format. The same k-mer must be added in
+        the same orientation each time, e.g., canonicalized."""
+        self.insert_array(array("Q", [kmer_int]))
+
+    def merge(self, other: "CardinalityEstimator"):
+        """Add every k-mer added to another estimator to this one
+
+        Raises:
+            ValueError: if the estimators have different precisions
+        """
+        if other.precision != self.precision:
+            raise ValueError(
+                f"cannot merge estimators of precision {self.precision} and "
+                f"{other.precision}"
+            )
+        self.registers = bytearray(map(max, self.registers, other.registers))
+
+    def estimate(self) -> int:
+        """Estimate the number of distinct k-mers added"""
+        num_registers = self.num_registers
+        max_rank = 64 - self.precision + 1
+        # the number of registers at each rank
+        histogram = [self.registers.count(rank) for rank in range(max_rank + 1)]
+        z = num_registers * _hll_tau(1 - histogram[max_rank] / num_registers)
+        for rank in range(max_rank - 1, 0, -1):
+            z = 0.5 * (z + histogram[rank])
+        z += num_registers * _hll_sigma(histogram[0] / num_registers)
+        return round(num_registers**2 / (2 * math.log(2)) / z)
+
+
+class KmerCountingBloom:
+    """A counting Bloom filter: approximate counts of k-mers, up to 15
+
+    Each k-mer has `num_hashes` 4-bit counters, and its count is the
+    lowest of them, which is never less than the number of times it was
+    added (up to `MAX_COUNT`, where counters stop), but can be more if
+    other k-mers share all of its counters. This takes half a byte per
+    counter however many k-mers are added, so it can count the mostly
+    once-seen k-mers of raw reads in much less memory than a dict, e.g.,
+    to find which k-mers are worth counting exactly.
+
+    >>> counts = KmerCountingBloom(1000)
+    >>> counts.add_array(array("Q", [kmer_to_int(kmer) for kmer in ["ACG", "ACG"]]))
+    >>> counts.count(kmer_to_int("ACG")), counts.count(kmer_to_int("TTT"))
+    (2, 0)
+    """
+
+    MAX_COUNT = 15
+    """The highest count a k-mer can have"""
+
+    def __init__(self, num_counters: int, num_hashes: int = 3):
+        """Create an empty counting Bloom filter
+
+        Args:
+            num_counters: the number of counters, e.g., a few for each
+                distinct k-mer expected
+            num_hashes: the number of counters for each k-mer, from 1 to
+                255
+        """
+        if num_counters < 1:
+            raise ValueError(f"num_counters must be at least 1, not {num_counters}")
+        if not 1 <= num_hashes <= 255:
+            raise ValueError(f"num_hashes must be from 1 to 255, not {num_hashes}")
+        self.num_counters = num_counters
+        self.num_hashes = num_hashes
+        self.counters = bytearray((num_counters + 1) // 2)
+
+    def _c_counters(self):
+        return (c_ubyte * len(self.counters)).from_buffer(self.counters)
+
+    def add_array(self, kmer_ints: array):
+        """Add an array of k-mers in integer format, as from
+        `canonical_kmer_array`. The same k-mer must be added in the same
+        orientation each time, e.g., canonicalized."""
+        if not kmer_ints:
+            return
+        address, length = kmer_ints.buffer_info()
+        add_ints_to_counting_bloom_c(
+            self._c_counters(),
+            self.num_counters,
+            self.num_hashes,
+            cast(address, POINTER(c_uint64)),
+            length,
+        )
+
+    def counts(self, kmer_ints: array) -> array:
+        """The count of each of an array of k-mers in integer format, as
+        an array of unsigned chars"""
+        counts = array("B", bytes(len(kmer_ints)))
+        if kmer_ints:
+            address, length = kmer_ints.buffer_info()
+            counts_address, _ = counts.buffer_info()
+            counting_bloom_counts_c(
+                self._c_counters(),
+                self.num_counters,
+                self.num_hashes,
+                cast(address, POINTER(c_uint64)),
+                length,
+                cast(counts_address, POINTER(c_ubyte)),
+            )
+        return counts
+
+    def count(self, kmer_int: int) -> int:
+        """The count of a k-mer in integer format"""
+        return self.counts(array("Q", [kmer_int]))[0]
+
+    def keep_frequent(self, kmer_ints: array, min_count: int):
+        """Remove the k-mers with a count below `min_count` from an array
+        of k-mers in integer format, keeping the rest in order. A
+        `min_count` above `MAX_COUNT` keeps those at `MAX_COUNT`."""
+        if not kmer_ints:
+            return
+        address, length = kmer_ints.buffer_info()
+        num_kept = keep_ints_in_counting_bloom_c(
+            self._c_counters(),
+            self.num_counters,
+            self.num_hashes,
+            cast(address, POINTER(c_uint64)),
+            length,
+            max(0, min(min_count, self.MAX_COUNT)),
+        )
+        del kmer_ints[num_kept:]
+
+    @property
+    def saturation(self) -> float:
+        """The fraction of counters that aren't 0"""
+        return (
+            num_nonzero_counters_c(self._c_counters(), self.num_counters)
+            / self.num_counters
+        )
+
+    @property
+    def false_positive_rate(self) -> float:
+        """Roughly the chance that a k-mer never added has a count above 0,
+        from `saturation`"""
+        return self.saturation**self.num_hashes
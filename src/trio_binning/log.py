@@ -0,0 +1,47 @@
+"""Logging for the command-line scripts.
+
+The library modules log through `logging.getLogger(__name__)` rather than
+printing, so a program using them can route their messages however it
+likes. The scripts add a -v/--verbose option with `add_verbose_argument`
+and print the messages to stderr with `configure`: warnings always, stage
+transitions like loading the k-mers with -v, and statistics about each
+batch of reads with -vv.
+"""
+
+import argparse
+import logging
+from typing import Optional
+
+LEVELS = [logging.WARNING, logging.INFO, logging.DEBUG]
+"""The level of the messages printed with no -v, -v and -vv"""
+
+_handler: Optional[logging.Handler] = None
+
+
+def add_verbose_argument(parser: argparse.ArgumentParser):
+    """Add a -v/--verbose option, which can be given more than once"""
+    parser.add_argument(
+        "-v",
+        "--verbose",
+        action="count",
+        default=0,
+        help="print what's being done to stderr, or with -vv, statistics about "
+        "each batch of reads too",
+    )
+
+
+def configure(verbosity: int):
+    """Print the messages of this package at a verbosity to stderr
+
+    Args:
+        verbosity: the number of times -v was given. Anything over 2 is
+            the same as 2.
+    """
+    global _handler
+    logger = logging.getLogger("trio_binning")
+    if _handler is not None:
+        logger.removeHandler(_handler)
+    _handler = logging.StreamHandler()
+    _handler.setFormatter(logging.Formatter("%(levelname)s: %(message)s"))
+    logger.addHandler(_handler)
+    logger.setLevel(LEVELS[min(verbosity, len(LEVELS) - 1)])
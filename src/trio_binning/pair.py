@@ -0,0 +1,200 @@
+"""Pairing up the mates of read pairs.
+
+Read pairs come either as two files, one of first mates and one of second
+mates in the same order, or as one interleaved file with each first mate
+directly followed by its second mate. `PairIter` pairs up the mates from
+either, one pair at a time, so any number of pairs takes the same memory,
+and checks by name that the mates of each pair go together, so pairs that
+have gone out of sync are an error rather than silently mispaired.
+
+A mate's name can mark which mate it is in either of the usual ways: a /1
+or /2 suffix, as in `@pair1/1`, or, as Illumina's CASAVA 1.8 and later
+write, a description starting with 1: or 2:, as in `@pair1 1:N:0:ACGT`.
+"""
+
+import logging
+import re
+from typing import Iterable, Iterator, Optional, TextIO, Tuple
+
+from trio_binning import seq
+from trio_binning.seq import Read, SeqError
+
+logger = logging.getLogger(__name__)
+
+_CASAVA_MATE = re.compile(r"([12]):")
+
+
+def mate_number(read: Read) -> Optional[int]:
+    """Which mate a read is, 1 or 2, from a /1 or /2 suffix on its name or
+    a description starting with 1: or 2:, or None if it isn't marked"""
+    if read.name.endswith(("/1", "/2")):
+        return int(read.name[-1])
+    if read.desc is not None:
+        match = _CASAVA_MATE.match(read.desc)
+        if match is not None:
+            return int(match.group(1))
+    return None
+
+
+def pair_name(read: Read) -> str:
+    """The name of the read pair a mate is in, which is the same for both
+    mates"""
+    return seq.mate_name(read.name)
+
+
+class PairIter:
+    """The read pairs in two files of mates or one interleaved file
+
+    Iterating over this yields the first and second mates of each pair,
+    reading one pair at a time.
+
+    Attributes:
+        num_pairs: the number of pairs yielded so far
+
+    Raises:
+        SeqError: when the pair that goes out of sync is reached, if one
+            file of mates has more reads than the other, or an interleaved
+            file an odd number, or the mates of a pair have different pair
+            names or are marked as the wrong mates. The error gives the
+            names of the reads and which records they are.
+    """
+
+    def __init__(self, reads_1: Iterable[Read], reads_2: Iterable[Read]):
+        """Pair up the mates in two files
+
+        Args:
+            reads_1: the first mates of the pairs
+            reads_2: the second mates of the pairs, in the same order
+        """
+        self.num_pairs = 0
+        self._reads_1 = iter(reads_1)
+        self._reads_2 = iter(reads_2)
+        self._interleaved = False
+        self._done = False
+
+    @classmethod
+    def interleaved(cls, reads: Iterable[Read]) -> "PairIter":
+        """Pair up the mates in an interleaved file
+
+        Args:
+            reads: reads in which each first mate is directly followed by
+                its second mate
+        """
+        reads = iter(reads)
+        pairs = cls(reads, reads)
+        pairs._interleaved = True
+        return pairs
+
+    def __iter__(self) -> Iterator[Tuple[Read, Read]]:
+        return self
+
+    def __next__(self) -> Tuple[Read, Read]:
+        if self._done:
+            raise StopIteration
+        read_1 = next(self._reads_1, None)
+        if read_1 is None and self._interleaved:
+            read_2 = None
+        else:
+            read_2 = next(self._reads_2, None)
+        if read_1 is None and read_2 is None:
+            self._done = True
+            raise StopIteration
+
+        pair_index = self.num_pairs + 1
+        if self._interleaved:
+            record_1, record_2 = 2 * pair_index - 1, 2 * pair_index
+        else:
+            record_1 = record_2 = pair_index
+        if read_1 is None or read_2 is None:
+            shorter, read, record = (
+                ("first", read_2, record_2)
+                if read_1 is None
+                else ("second", read_1, record_1)
+            )
+            raise SeqError(
+                f"read pair {pair_index}: the {shorter} mates ran out before the "
+                f"other mates, leaving {read.name!r} ({self._record(record)}) "
+                "unpaired; the mates are out of sync"
+            )
+        if pair_name(read_1) != pair_name(read_2):
+            raise SeqError(
+                f"read pair {pair_index}: mate names {read_1.name!r} "
+                f"({self._record(record_1)}) and {read_2.name!r} "
+                f"({self._record(record_2)}) do not match; the mates are out of "
+                "sync"
+            )
+        for mate, read, record in [(1, read_1, record_1), (2, read_2, record_2)]:
+            number = mate_number(read)
+            if number is not None and number != mate:
+                raise SeqError(
+                    f"read pair {pair_index}: {read.name!r} "
+                    f"({self._record(record)}) is marked as mate {number} but "
+                    f"is in the place of mate {mate}"
+                )
+        self.num_pairs = pair_index
+        return read_1, read_2
+
+    def _record(self, index: int) -> str:
+        """Which record a read is, for an error"""
+        if self._interleaved:
+            return f"record {index}"
+        return f"record {index} of its file"
+
+
+def interleave(
+    reads_1: Iterable[Read],
+    reads_2: Iterable[Read],
+    out: TextIO,
+    output_format: seq.OutputFormat = seq.OutputFormat.AUTO,
+    fake_quality: int = seq.DEFAULT_FAKE_QUALITY,
+) -> int:
+    """Write the mates of read pairs from two files interleaved into one
+
+    Args:
+        reads_1: the first mates of the pairs
+        reads_2: the second mates of the pairs, in the same order
+        out: file to write each first mate followed by its second mate to
+        output_format, fake_quality: as for `seq.write_record`
+
+    Returns:
+        the number of pairs written
+
+    Raises:
+        SeqError: as for `PairIter`
+    """
+    pairs = PairIter(reads_1, reads_2)
+    for read_1, read_2 in pairs:
+        seq.write_record(read_1, out, output_format, fake_quality)
+        seq.write_record(read_2, out, output_format, fake_quality)
+    logger.info("Interleaved %d read pairs", pairs.num_pairs)
+    return pairs.num_pairs
+
+
+def split(
+    reads: Iterable[Read],
+    out_1: TextIO,
+    out_2: TextIO,
+    output_format: seq.OutputFormat = seq.OutputFormat.AUTO,
+    fake_quality: int = seq.DEFAULT_FAKE_QUALITY,
+) -> int:
+    """Write the mates of interleaved read pairs into two files
+
+    Args:
+        reads: reads in which each first mate is directly followed by its
+            second mate
+        out_1: file to write the first mates to
+        out_2: file to write the second mates to, in the same order
+        output_format, fake_quality: as for `seq.write_record`
+
+    Returns:
+        the number of pairs written
+
+    Raises:
+        SeqError: as for `PairIter`
+    """
+    pairs = PairIter.interleaved(reads)
+    for read_1, read_2 in pairs:
+        seq.write_record(read_1, out_1, output_format, fake_quality)
+        seq.write_record(read_2, out_2, output_format, fake_quality)
+    logger.info("Split %d read pairs", pairs.num_pairs)
+    return pairs.num_pairs
@@ -0,0 +1,138 @@
+"""Tools for converting read pairs between two files and one interleaved
+file.
+
+This is a script with a subcommand for each direction:
+
+  interleave: write the mates of read pairs from two files into one file,
+      with each first mate followed by its second mate.
+  split: write the mates of interleaved read pairs into two files.
+
+Both check by name that the mates of each pair go together, taking a /1 or
+/2 suffix or a description starting with 1: or 2: to mark which mate a
+read is, and stop with an error at the first pair that doesn't. The pairs
+are read and written one at a time, so any number of them takes the same
+memory.
+"""
+
+import argparse
+import sys
+
+from trio_binning import log, pair, seq
+
+
+def parse_args():
+    """Parse arguments"""
+    parser = argparse.ArgumentParser(
+        description=__doc__, formatter_class=argparse.RawDescriptionHelpFormatter
+    )
+    subparsers = parser.add_subparsers(dest="command", required=True)
+
+    interleave_parser = subparsers.add_parser(
+        "interleave",
+        help="write read pairs from two files into one interleaved file",
+        description="Write the mates of read pairs in --r1 and --r2 into one file, "
+        "with each first mate followed by its second mate, as fasta or fastq "
+        "like the reads.",
+        formatter_class=argparse.ArgumentDefaultsHelpFormatter,
+    )
+    interleave_parser.add_argument(
+        "--r1",
+        required=True,
+        help="first mates of the read pairs, in fasta/q, SAM or BAM format. Can "
+        "be gzipped, or - for stdin.",
+    )
+    interleave_parser.add_argument(
+        "--r2",
+        required=True,
+        help="second mates of the read pairs, in the same order. Can be gzipped.",
+    )
+    interleave_parser.add_argument(
+        "-o",
+        "--output",
+        default="-",
+        help="file to write the interleaved pairs to, gzipped if it ends in .gz. "
+        "Default is stdout.",
+    )
+    seq.add_multiline_fastq_argument(interleave_parser)
+    log.add_verbose_argument(interleave_parser)
+
+    split_parser = subparsers.add_parser(
+        "split",
+        help="write interleaved read pairs into two files",
+        description="Write the first mates of the interleaved read pairs in "
+        "--interleaved to --r1 and their second mates to --r2, as fasta or fastq "
+        "like the reads.",
+        formatter_class=argparse.ArgumentDefaultsHelpFormatter,
+    )
+    split_parser.add_argument(
+        "--interleaved",
+        required=True,
+        help="read pairs with each first mate followed by its second mate, in "
+        "fasta/q, SAM or BAM format. Can be gzipped, or - for stdin.",
+    )
+    split_parser.add_argument(
+        "--r1",
+        required=True,
+        help="file to write the first mates to, gzipped if it ends in .gz",
+    )
+    split_parser.add_argument(
+        "--r2",
+        required=True,
+        help="file to write the second mates to, gzipped if it ends in .gz",
+    )
+    seq.add_multiline_fastq_argument(split_parser)
+    log.add_verbose_argument(split_parser)
+
+    args = parser.parse_args()
+    if args.command == "interleave":
+        if args.r2 == "-":
+            interleave_parser.error("only --r1 can be - for stdin")
+        if args.output != "-" and args.output in (args.r1, args.r2):
+            interleave_parser.error("--output must not be one of the reads files")
+    elif args.command == "split":
+        if "-" in (args.r1, args.r2):
+            split_parser.error("--r1 and --r2 must be files, not stdout")
+        if args.r1 == args.r2:
+            split_parser.error("--r1 and --r2 must be different files")
+        if args.interleaved in (args.r1, args.r2):
+            split_parser.error("--r1 and --r2 must not be the reads file")
+    return args
+
+
+def interleave(args: argparse.Namespace):
+    """Interleave read pairs from two files"""
+    out = seq.open_text_write(args.output)
+    try:
+        pair.interleave(
+            seq.open_records(args.r1, multiline_fastq=args.multiline_fastq),
+            seq.open_records(args.r2, multiline_fastq=args.multiline_fastq),
+            out,
+        )
+    finally:
+        if out is not sys.stdout:
+            out.close()
+
+
+def split(args: argparse.Namespace):
+    """Split interleaved read pairs into two files"""
+    with seq.open_text_write(args.r1) as out_1, seq.open_text_write(args.r2) as out_2:
+        reads = seq.open_records(args.interleaved, multiline_fastq=args.multiline_fastq)
+        pair.split(reads, out_1, out_2)
+
+
+@seq.exit_on_broken_pipe
+def main():
+    """Main method of program"""
+    args = parse_args()
+    log.configure(args.verbose)
+    try:
+        if args.command == "interleave":
+            interleave(args)
+        elif args.command == "split":
+            split(args)
+    except (OSError, seq.SeqError) as e:
+        sys.exit(f"Error: {e}")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,537 @@
+"""Tools for preparing reads to classify.
+
+This is a script with a subcommand for each tool:
+
+  split: split reads into some number of files with about the same
+      number of reads or bases each, e.g., to classify them on several
+      cluster nodes at once. Both mates of a read pair go into the same
+      shard.
+  extract: pull the reads with some ids out of a reads file, or with
+      --invert, every other read.
+  stats: print the number of reads and bases, the lengths, N50 and GC
+      content of each of some reads files, e.g., before and after binning.
+  split-scaffolds: split the scaffolds of an assembly into contigs at
+      their gaps, e.g., so that classify-assembly's windows don't span
+      them.
+  dedup: remove reads, or read pairs, with the same sequence as an
+      earlier one, e.g., PCR duplicates or a file that was uploaded twice.
+"""
+
+import argparse
+import json
+import sys
+from itertools import chain
+from os import path
+from typing import Iterator, List, Optional, Set, TextIO, Tuple
+
+from trio_binning import log, seq
+from trio_binning.pair import PairIter
+
+FASTX_EXTENSIONS = (".fa", ".fasta", ".fna", ".fq", ".fastq")
+"""The extensions of reads files that are kept for the files written"""
+
+
+def parse_args():
+    """Parse arguments"""
+    parser = argparse.ArgumentParser(
+        description=__doc__, formatter_class=argparse.RawDescriptionHelpFormatter
+    )
+    subparsers = parser.add_subparsers(dest="command", required=True)
+
+    split_parser = subparsers.add_parser(
+        "split",
+        help="split reads into files with about the same number of reads or bases",
+        description="Split reads into --num-shards files named PREFIX.1, PREFIX.2 "
+        "and so on, plus the reads file's extension, gzipped if it is. With "
+        "--by reads, the reads are dealt out to the files in turn; with --by "
+        "bases, each goes to the file with the fewest bases so far. Read pairs "
+        "from --reads-1 and --reads-2 are split into PREFIX.1_R1 and PREFIX.1_R2 "
+        "and so on, keeping the mates of each pair together. The reads and bases "
+        "in each file are printed to stderr.",
+        formatter_class=argparse.ArgumentDefaultsHelpFormatter,
+    )
+    split_parser.add_argument(
+        "reads",
+        nargs="?",
+        help="reads to split, in fasta/q format, or SAM/BAM, which are split into "
+        "fasta/q. Can be gzipped, or - for stdin. Leave out to split read pairs "
+        "with --reads-1 and --reads-2 instead.",
+    )
+    split_parser.add_argument("-1", "--reads-1", help="first mates of read pairs")
+    split_parser.add_argument("-2", "--reads-2", help="second mates of read pairs")
+    split_parser.add_argument(
+        "--interleaved",
+        action="store_true",
+        default=False,
+        help="the reads are read pairs with each first mate followed by its second "
+        "mate, which are kept together and written interleaved too",
+    )
+    split_parser.add_argument(
+        "-n", "--num-shards", type=int, required=True, help="number of files to write"
+    )
+    split_parser.add_argument(
+        "-o", "--output-prefix", required=True, help="prefix of the files to write"
+    )
+    split_parser.add_argument(
+        "--by",
+        type=seq.SplitBy,
+        choices=list(seq.SplitBy),
+        default=seq.SplitBy.READS,
+        help="what to balance between the files",
+    )
+    seq.add_multiline_fastq_argument(split_parser)
+    log.add_verbose_argument(split_parser)
+
+    extract_parser = subparsers.add_parser(
+        "extract",
+        help="write the reads with the ids in a list",
+        description="Write the reads in a file whose ids are in --ids, as fasta or "
+        "fastq, in the order they're in the reads file. A read's id is its name "
+        "up to the first whitespace.",
+        formatter_class=argparse.ArgumentDefaultsHelpFormatter,
+    )
+    extract_parser.add_argument(
+        "reads",
+        help="reads to extract from, in fasta/q, SAM or BAM format. Can be "
+        "gzipped, or - for stdin.",
+    )
+    extract_parser.add_argument(
+        "--ids",
+        required=True,
+        metavar="FILE",
+        help="the ids of the reads to extract, one per line, e.g., one of "
+        "classify-by-kmers' --names-only files",
+    )
+    extract_parser.add_argument(
+        "-o",
+        "--output",
+        default="-",
+        help="file to write the reads to, gzipped if it ends in .gz. Default is "
+        "stdout.",
+    )
+    extract_parser.add_argument(
+        "--invert",
+        action="store_true",
+        default=False,
+        help="write the reads whose ids aren't in --ids instead",
+    )
+    extract_parser.add_argument(
+        "--strict",
+        action="store_true",
+        default=False,
+        help="exit with an error, listing them, if any of the ids aren't in the "
+        "reads",
+    )
+    seq.add_multiline_fastq_argument(extract_parser)
+    log.add_verbose_argument(extract_parser)
+
+    stats_parser = subparsers.add_parser(
+        "stats",
+        help="print the number of reads and bases, lengths, N50 and GC content",
+        description="Print statistics about each reads file as a table: the number "
+        "of reads and bases, the shortest, mean, median and longest lengths, the "
+        "N50 and N90, the GC content of the A's, C's, G's and T's, and the number "
+        "of N's. Each file is read once, keeping only the number of reads of each "
+        "length.",
+        formatter_class=argparse.ArgumentDefaultsHelpFormatter,
+    )
+    stats_parser.add_argument(
+        "reads",
+        nargs="+",
+        help="reads files, in fasta/q, SAM or BAM format. Can be gzipped, or - "
+        "for stdin.",
+    )
+    stats_parser.add_argument(
+        "--json",
+        action="store_true",
+        default=False,
+        help="print the statistics as JSON instead of as a table",
+    )
+    seq.add_multiline_fastq_argument(stats_parser)
+    log.add_verbose_argument(stats_parser)
+
+    split_scaffolds_parser = subparsers.add_parser(
+        "split-scaffolds",
+        help="split scaffolds into contigs at runs of N's",
+        description="Split each scaffold of an assembly wherever there's a run of "
+        "at least --min-gap N's, leaving the N's out, and write the pieces as "
+        "NAME_part1, NAME_part2 and so on. Each piece's description starts with "
+        "scaffold=NAME offset=OFFSET, the 0-based position of the piece in the "
+        "scaffold, which classify-assembly --scaffold-coordinates uses to put "
+        "its windows back on the scaffolds. Gaps at the start or end of a "
+        "scaffold are trimmed off. The number of scaffolds and contigs is "
+        "printed to stderr.",
+        formatter_class=argparse.ArgumentDefaultsHelpFormatter,
+    )
+    split_scaffolds_parser.add_argument(
+        "assembly",
+        help="scaffolds to split, in fasta/q format. Can be gzipped, or - for "
+        "stdin.",
+    )
+    split_scaffolds_parser.add_argument(
+        "-o",
+        "--output",
+        default="-",
+        help="file to write the contigs to, gzipped if it ends in .gz. Default is "
+        "stdout.",
+    )
+    split_scaffolds_parser.add_argument(
+        "--min-gap",
+        type=int,
+        default=seq.DEFAULT_MIN_GAP,
+        help="the fewest N's in a row to split at. Shorter runs are kept in the "
+        "contigs.",
+    )
+    seq.add_multiline_fastq_argument(split_scaffolds_parser)
+    log.add_verbose_argument(split_scaffolds_parser)
+
+    dedup_parser = subparsers.add_parser(
+        "dedup",
+        help="remove reads with the same sequence as an earlier one",
+        description="Write each read whose sequence isn't the same as an earlier "
+        "read's, in order, or with --reads-1 and --reads-2, each read pair whose "
+        "mates aren't both the same as an earlier pair's. Only a 128-bit hash of "
+        "each sequence is kept, so two different sequences are taken to be the "
+        "same with a chance of about 1 in 10**20 for a billion reads; --exact "
+        "reads the reads again to check the duplicates base for base. The number "
+        "of reads and duplicates removed is printed to stderr.",
+        formatter_class=argparse.ArgumentDefaultsHelpFormatter,
+    )
+    dedup_parser.add_argument(
+        "reads",
+        nargs="?",
+        help="reads to deduplicate, in fasta/q, SAM or BAM format. Can be "
+        "gzipped, or - for stdin. Leave out to deduplicate read pairs with "
+        "--reads-1 and --reads-2 instead.",
+    )
+    dedup_parser.add_argument("-1", "--reads-1", help="first mates of read pairs")
+    dedup_parser.add_argument("-2", "--reads-2", help="second mates of read pairs")
+    dedup_parser.add_argument(
+        "-o",
+        "--output",
+        default="-",
+        help="file to write the reads to, gzipped if it ends in .gz. Default is "
+        "stdout.",
+    )
+    dedup_parser.add_argument(
+        "--output-1", help="with --reads-1 and --reads-2, file for the first mates"
+    )
+    dedup_parser.add_argument(
+        "--output-2", help="with --reads-1 and --reads-2, file for the second mates"
+    )
+    dedup_parser.add_argument(
+        "--same-id",
+        action="store_true",
+        default=False,
+        help="only remove duplicates that have the same id as well as the same "
+        "sequence",
+    )
+    dedup_parser.add_argument(
+        "--consider-revcomp",
+        action="store_true",
+        default=False,
+        help="remove reads that are the reverse complement of an earlier read too, "
+        "and read pairs that are an earlier pair with its mates swapped",
+    )
+    dedup_parser.add_argument(
+        "--exact",
+        action="store_true",
+        default=False,
+        help="read the reads twice to check each duplicate found by its hash base "
+        "for base, so no read is removed for a hash collision. The reads can't "
+        "be stdin.",
+    )
+    seq.add_multiline_fastq_argument(dedup_parser)
+    log.add_verbose_argument(dedup_parser)
+
+    args = parser.parse_args()
+    if args.command == "split":
+        paired = args.reads_1 is not None or args.reads_2 is not None
+        if (args.reads_1 is None) != (args.reads_2 is None):
+            split_parser.error("--reads-1 and --reads-2 must be given together")
+        if paired == (args.reads is not None):
+            split_parser.error("give either reads or --reads-1 and --reads-2")
+        if args.interleaved and paired:
+            split_parser.error("--interleaved is for reads, not --reads-1 and -2")
+        if args.num_shards < 1:
+            split_parser.error(
+                f"--num-shards must be at least 1, not {args.num_shards}"
+            )
+    elif args.command == "split-scaffolds":
+        if args.min_gap < 1:
+            split_scaffolds_parser.error(
+                f"--min-gap must be at least 1, not {args.min_gap}"
+            )
+    elif args.command == "dedup":
+        paired = args.reads_1 is not None or args.reads_2 is not None
+        if (args.reads_1 is None) != (args.reads_2 is None):
+            dedup_parser.error("--reads-1 and --reads-2 must be given together")
+        if paired == (args.reads is not None):
+            dedup_parser.error("give either reads or --reads-1 and --reads-2")
+        if paired and (args.output_1 is None or args.output_2 is None):
+            dedup_parser.error("--reads-1 and --reads-2 need --output-1 and --output-2")
+        if not paired and (args.output_1 is not None or args.output_2 is not None):
+            dedup_parser.error("--output-1 and --output-2 are for read pairs")
+        if args.exact and "-" in (args.reads, args.reads_1):
+            dedup_parser.error("--exact reads the reads twice, so they can't be stdin")
+    return args
+
+
+def output_format(
+    reads_path: str, reads: Iterator[seq.Read]
+) -> Tuple[str, bool, Iterator[seq.Read]]:
+    """Pick the extension of the files to write reads to, and whether to
+    gzip them, to match the reads file
+
+    The extension is the reads file's if it is a fasta/q one, or else
+    .fastq if the first read has qualities and .fa if not, e.g., for SAM.
+
+    Returns:
+        the extension, whether the reads file is gzipped, and the reads,
+        including any read looked at
+    """
+    gzipped = False
+    if reads_path != "-":
+        with open(reads_path, "rb") as fp:
+            gzipped = fp.read(len(seq.GZIP_MAGIC)) == seq.GZIP_MAGIC
+    extension = path.splitext(seq.strip_compressed_extension(reads_path))[1]
+    if extension.lower() not in FASTX_EXTENSIONS:
+        first_read = next(reads, None)
+        if first_read is None:
+            return ".fa", gzipped, iter([])
+        extension = ".fa" if first_read.qual is None else ".fastq"
+        reads = chain([first_read], reads)
+    return extension, gzipped, reads
+
+
+def print_shards(shards: List[seq.Shard]):
+    """Print each shard's files and its reads and bases to stderr"""
+    print("file\treads\tbases", file=sys.stderr)
+    for shard in shards:
+        print(
+            ",".join(shard.paths), shard.reads, shard.bases, sep="\t", file=sys.stderr
+        )
+
+
+def split(args: argparse.Namespace):
+    """Split reads into shards"""
+    multiline_fastq = args.multiline_fastq
+    if args.reads is not None:
+        reads = seq.open_records(args.reads, multiline_fastq=multiline_fastq)
+        extension, gzipped, reads = output_format(args.reads, reads)
+        if args.interleaved:
+            shards = seq.split_pairs(
+                PairIter.interleaved(reads),
+                args.num_shards,
+                args.output_prefix,
+                extension,
+                gzipped,
+                args.by,
+                interleave=True,
+            )
+        else:
+            shards = seq.split(
+                reads, args.num_shards, args.output_prefix, extension, gzipped, args.by
+            )
+    else:
+        reads_1 = seq.open_records(args.reads_1, multiline_fastq=multiline_fastq)
+        extension, gzipped, reads_1 = output_format(args.reads_1, reads_1)
+        reads_2 = seq.open_records(args.reads_2, multiline_fastq=multiline_fastq)
+        shards = seq.split_pairs(
+            PairIter(reads_1, reads_2),
+            args.num_shards,
+            args.output_prefix,
+            extension,
+            gzipped,
+            args.by,
+        )
+    print_shards(shards)
+
+
+def extract(args: argparse.Namespace):
+    """Write the reads with some ids"""
+    with seq.open_text_read(args.ids) as ids_file:
+        ids = seq.read_ids(ids_file)
+    found: Set[str] = set()
+    out = seq.open_text_write(args.output)
+    try:
+        reads = seq.open_records(args.reads, multiline_fastq=args.multiline_fastq)
+        for read in seq.filter_by_id(reads, ids, args.invert, found):
+            seq.write_record(read, out)
+    finally:
+        if out is not sys.stdout:
+            out.close()
+
+    missing = ids - found
+    if args.strict and missing:
+        sys.exit(
+            f"Error: {len(missing)} ids weren't found in {args.reads}: "
+            + ", ".join(sorted(missing))
+        )
+
+
+STATS_COLUMNS = [
+    "file",
+    "reads",
+    "bases",
+    "min_len",
+    "mean_len",
+    "median_len",
+    "max_len",
+    "N50",
+    "N90",
+    "GC",
+    "Ns",
+]
+"""The columns of the table printed by `stats`"""
+
+
+def stats_row(reads_path: str, reads_stats: seq.Stats) -> List[str]:
+    """The row of the stats table for a reads file, with NA for the
+    lengths of a file with no reads"""
+
+    def length(value: Optional[float]) -> str:
+        if value is None:
+            return "NA"
+        return f"{value:.1f}" if isinstance(value, float) else str(value)
+
+    return [
+        reads_path,
+        str(reads_stats.num_records),
+        str(reads_stats.num_bases),
+        length(reads_stats.min_length),
+        length(reads_stats.mean_length),
+        length(reads_stats.median_length),
+        length(reads_stats.max_length),
+        length(reads_stats.n50),
+        length(reads_stats.n90),
+        f"{100 * reads_stats.gc_content:.2f}%",
+        str(reads_stats.n_bases),
+    ]
+
+
+def print_aligned(rows: List[List[str]], file: TextIO):
+    """Print a table with its columns lined up, the first aligned left and
+    the rest, which are numbers, right"""
+    widths = [max(len(row[i]) for row in rows) for i in range(len(rows[0]))]
+    for row in rows:
+        cells = [row[0].ljust(widths[0])]
+        cells += [cell.rjust(width) for cell, width in zip(row[1:], widths[1:])]
+        print("  ".join(cells).rstrip(), file=file)
+
+
+def stats(args: argparse.Namespace):
+    """Print statistics about some reads files"""
+    all_stats = [
+        (
+            reads_path,
+            seq.Stats.from_records(
+                seq.open_records(reads_path, multiline_fastq=args.multiline_fastq)
+            ),
+        )
+        for reads_path in args.reads
+    ]
+    if args.json:
+        json.dump(
+            [
+                {"file": reads_path, **reads_stats.to_dict()}
+                for reads_path, reads_stats in all_stats
+            ],
+            sys.stdout,
+            indent=2,
+        )
+        print()
+    else:
+        rows = [stats_row(*file_stats) for file_stats in all_stats]
+        print_aligned([STATS_COLUMNS] + rows, sys.stdout)
+
+
+def split_scaffolds(args: argparse.Namespace):
+    """Split scaffolds into contigs at their gaps"""
+    num_scaffolds = num_contigs = 0
+    out = seq.open_text_write(args.output)
+    try:
+        for scaffold in seq.open_records(
+            args.assembly, multiline_fastq=args.multiline_fastq
+        ):
+            num_scaffolds += 1
+            for contig in seq.split_at_n(scaffold, args.min_gap):
+                seq.write_record(contig, out)
+                num_contigs += 1
+    finally:
+        if out is not sys.stdout:
+            out.close()
+    print(
+        f"Split {num_scaffolds} scaffolds into {num_contigs} contigs", file=sys.stderr
+    )
+
+
+def dedup(args: argparse.Namespace):
+    """Remove duplicate reads or read pairs"""
+    counts = seq.DedupCounts()
+    same_id, consider_revcomp = args.same_id, args.consider_revcomp
+    multiline_fastq = args.multiline_fastq
+    if args.reads is not None:
+
+        def open_reads() -> Iterator[seq.Read]:
+            return seq.open_records(args.reads, multiline_fastq=multiline_fastq)
+
+        if args.exact:
+            reads = seq.dedup_exact(open_reads, same_id, consider_revcomp, counts)
+        else:
+            reads = seq.dedup(open_reads(), same_id, consider_revcomp, counts)
+        out = seq.open_text_write(args.output)
+        try:
+            for read in reads:
+                seq.write_record(read, out)
+        finally:
+            if out is not sys.stdout:
+                out.close()
+        unit = "reads"
+    else:
+
+        def open_pairs() -> PairIter:
+            return PairIter(
+                seq.open_records(args.reads_1, multiline_fastq=multiline_fastq),
+                seq.open_records(args.reads_2, multiline_fastq=multiline_fastq),
+            )
+
+        if args.exact:
+            pairs = seq.dedup_exact(open_pairs, same_id, consider_revcomp, counts)
+        else:
+            pairs = seq.dedup(open_pairs(), same_id, consider_revcomp, counts)
+        with seq.open_text_write(args.output_1) as out_1, seq.open_text_write(
+            args.output_2
+        ) as out_2:
+            for read_1, read_2 in pairs:
+                seq.write_record(read_1, out_1)
+                seq.write_record(read_2, out_2)
+        unit = "read pairs"
+    print(
+        f"Removed {counts.removed} duplicates of {counts.records} {unit}",
+        file=sys.stderr,
+    )
+
+
+@seq.exit_on_broken_pipe
+def main():
+    """Main method of program"""
+    args = parse_args()
+    log.configure(args.verbose)
+    try:
+        if args.command == "split":
+            split(args)
+        elif args.command == "extract":
+            extract(args)
+        elif args.command == "stats":
+            stats(args)
+        elif args.command == "split-scaffolds":
+            split_scaffolds(args)
+        elif args.command == "dedup":
+            dedup(args)
+    except (OSError, seq.SeqError) as e:
+        sys.exit(f"Error: {e}")
+
+
+if __name__ == "__main__":
+    main()
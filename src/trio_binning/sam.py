@@ -0,0 +1,478 @@
+"""Reading reads from SAM and BAM files.
+
+Reads often come off the sequencer as unaligned BAM rather than fastq.
+SAM files are text and are parsed here directly, while BAM files need
+pysam, which is an optional dependency installed with the bam extra
+(pip install trio_binning[bam]), so that nobody who only has fasta/q
+reads has to install it.
+
+Either way, the records are turned into Reads the way `samtools fastq`
+does it: secondary and supplementary alignments are skipped, since they
+repeat a read that is already in the file, and reads aligned to the
+reverse strand are reverse complemented back to how they were sequenced.
+
+The records of a file can also be copied to another one with tags set on
+them, e.g., to mark the haplotype of each read, with `open_tagger`.
+"""
+
+import re
+import sys
+from collections import deque
+from dataclasses import dataclass, field
+from typing import Any, Callable, Deque, Dict, Iterable, Iterator, List, Optional
+
+from trio_binning.seq import (
+    PHRED_OFFSET,
+    Read,
+    SeqError,
+    open_text_read,
+    open_text_write,
+)
+
+REVERSE = 0x10
+"""The flag of a record aligned to the reverse strand"""
+SECONDARY = 0x100
+"""The flag of a secondary alignment"""
+SUPPLEMENTARY = 0x800
+"""The flag of a supplementary alignment"""
+
+BAM_MAGIC = "BAM\x01"
+"""The first bytes of a BAM file once it's decompressed"""
+
+_SAM_HEADER = re.compile(r"@(HD|SQ|RG|PG|CO)\t")
+_SAM_COLUMNS = 11
+
+
+class SamError(SeqError):
+    """A line of a SAM file could not be parsed
+
+    Attributes:
+        line_number: the 1-based number of the bad line
+        message: a description of what is wrong with the line
+    """
+
+    def __init__(self, line_number: int, message: str):
+        self.line_number = line_number
+        self.message = message
+        super().__init__(f"SAM line {line_number}: {message}")
+
+
+def looks_like_sam(line: str) -> bool:
+    """Whether the first line of a file looks like SAM rather than fasta/q
+
+    That is, if it's a SAM header line, or an alignment with at least the
+    11 tab-separated columns every SAM record has.
+    """
+    return bool(_SAM_HEADER.match(line)) or line.count("\t") >= _SAM_COLUMNS - 1
+
+
+def is_primary(flag: int) -> bool:
+    """Whether a record is neither a secondary nor a supplementary alignment"""
+    return not flag & (SECONDARY | SUPPLEMENTARY)
+
+
+def record_to_read(
+    name: str, flag: int, seq: Optional[str], qual: Optional[str]
+) -> Read:
+    """Make a Read from the fields of a SAM or BAM record
+
+    Args:
+        name: the query name
+        flag: the flags of the record
+        seq: the sequence as stored in the record, or None if it has none
+        qual: the qualities as stored in the record, as Phred+33
+            characters, or None if it has none
+
+    Returns:
+        the read as it was sequenced, so reverse complemented if it is
+        aligned to the reverse strand
+    """
+    read = Read(name, seq or "", qual)
+    if flag & REVERSE:
+        reverse = read.reverse_complement()
+        read = Read(name, reverse.seq, reverse.qual)
+    return read
+
+
+class SamRecord:
+    """One alignment line of a SAM file
+
+    The record is kept as its columns, so that it can be written back out
+    as it was, apart from any tags set with `set_tag`.
+    """
+
+    def __init__(self, fields: List[str]):
+        self.fields = fields
+        """The tab-separated columns of the line"""
+
+    @property
+    def flag(self) -> int:
+        return int(self.fields[1])
+
+    def to_read(self) -> Read:
+        """The read in the record, as it was sequenced"""
+        seq = None if self.fields[9] == "*" else self.fields[9]
+        qual = None if self.fields[10] == "*" else self.fields[10]
+        return record_to_read(self.fields[0], self.flag, seq, qual)
+
+    def set_tag(self, tag: str, value: Optional[int]):
+        """Set an integer tag, replacing any tag already with that name, or
+        remove it if the value is None"""
+        self.fields = self.fields[:_SAM_COLUMNS] + [
+            column
+            for column in self.fields[_SAM_COLUMNS:]
+            if not column.startswith(tag + ":")
+        ]
+        if value is not None:
+            self.fields.append(f"{tag}:i:{value}")
+
+    def __str__(self) -> str:
+        return "\t".join(self.fields)
+
+
+def parse_sam_record(line_number: int, line: str) -> SamRecord:
+    """Parse an alignment line of a SAM file
+
+    Raises:
+        SamError: if it has fewer than 11 columns or a flag that isn't a
+            number
+    """
+    fields = line.rstrip("\r\n").split("\t")
+    if len(fields) < _SAM_COLUMNS:
+        raise SamError(
+            line_number,
+            f"expected at least {_SAM_COLUMNS} columns, found {len(fields)}",
+        )
+    if not fields[1].isdigit():
+        raise SamError(line_number, f"flag {fields[1]!r} is not a number")
+    return SamRecord(fields)
+
+
+def read_sam(
+    lines: Iterable[str], on_error: Optional[Callable[[SeqError], None]] = None
+) -> Iterator[Read]:
+    """Read the primary records of a SAM file, skipping the header
+
+    Args:
+        lines: the lines of the file
+        on_error: if given, this is called with each error instead of it
+            being raised, and the bad line is skipped
+
+    Raises:
+        SamError: if a record has fewer than 11 columns or a flag that
+            isn't a number
+    """
+    for line_number, line in enumerate(lines, start=1):
+        if not line.rstrip("\r\n") or line.startswith("@"):
+            continue
+        try:
+            record = parse_sam_record(line_number, line)
+        except SamError as error:
+            if on_error is None:
+                raise
+            on_error(error)
+            continue
+        if is_primary(record.flag):
+            yield record.to_read()
+
+
+def _import_pysam(filename: str) -> Any:
+    """Import pysam, which is needed for BAM files
+
+    Raises:
+        SeqError: if pysam isn't installed
+    """
+    try:
+        import pysam
+    except ImportError:
+        raise SeqError(
+            f"{filename} is a BAM file, and BAM files need pysam, "
+            "which is installed with pip install trio_binning[bam]"
+        ) from None
+    return pysam
+
+
+def _pysam_read(record: Any) -> Read:
+    """The read in a pysam record, as it was sequenced"""
+    qualities = record.query_qualities
+    qual = None
+    if qualities is not None:
+        qual = "".join(chr(q + PHRED_OFFSET) for q in qualities)
+    return record_to_read(
+        record.query_name, record.flag, record.query_sequence, qual
+    )
+
+
+def read_bam(filename: str) -> Iterator[Read]:
+    """Read the primary records of a BAM file, which needs pysam
+
+    Raises:
+        SeqError: if pysam isn't installed
+    """
+    pysam = _import_pysam(filename)
+    with pysam.AlignmentFile(filename, "rb", check_sq=False) as bam:
+        for record in bam.fetch(until_eof=True):
+            if is_primary(record.flag):
+                yield _pysam_read(record)
+
+
+def is_bam(filename: str) -> bool:
+    """Whether a file, gzipped or not, is BAM rather than text"""
+    with open_text_read(filename) as fp:
+        return fp.read(len(BAM_MAGIC)) == BAM_MAGIC
+
+
+def is_alignment_file(filename: str) -> bool:
+    """Whether a file, gzipped or not, is SAM or BAM rather than fasta/q"""
+    with open_text_read(filename) as fp:
+        first = fp.readline()
+    return first.startswith(BAM_MAGIC) or looks_like_sam(first)
+
+
+def is_sam_path(path: str) -> bool:
+    """Whether a file should be written as SAM rather than BAM, which is if
+    it's named .sam or is - for stdout"""
+    return path == "-" or path.endswith(".sam")
+
+
+@dataclass
+class Alignment(Read):
+    """A read from a primary SAM/BAM record, along with the record itself
+
+    This is a Read, so it can be classified and written out like any other.
+    """
+
+    record: Any = None
+    """The record, as a `SamRecord` or a pysam AlignedSegment"""
+    others: List[Any] = field(default_factory=list)
+    """The secondary and supplementary records after this one in the file,
+    up to the next primary record, which are copied along with it"""
+
+
+def _alignments(
+    records: Iterable[Any],
+    to_read: Callable[[Any], Read],
+    write: Callable[[Any], None],
+) -> Iterator[Alignment]:
+    """Group records into Alignments, one per primary record
+
+    Any records before the first primary record are written straight away.
+    """
+    alignment: Optional[Alignment] = None
+    for record in records:
+        if not is_primary(record.flag):
+            if alignment is None:
+                write(record)
+            else:
+                alignment.others.append(record)
+            continue
+        if alignment is not None:
+            yield alignment
+        read = to_read(record)
+        alignment = Alignment(read.name, read.seq, read.qual, read.desc, record)
+    if alignment is not None:
+        yield alignment
+
+
+def program_record(
+    program: str, command_line: str, programs: Iterable[Dict[str, str]]
+) -> Dict[str, str]:
+    """Make the @PG header record of a program that changed a file
+
+    Args:
+        program: the name of the program, which is its ID too, unless the
+            header already has a program with that ID, in which case a
+            number is added to it like samtools does
+        command_line: the command line the program was run with
+        programs: the @PG records already in the header, in order
+
+    Returns:
+        the fields of the record, with the PP (previous program) field set
+        to the ID of the last program in the header if there is one
+    """
+    ids = [record["ID"] for record in programs if "ID" in record]
+    program_id = program
+    suffix = 1
+    while program_id in ids:
+        program_id = f"{program}.{suffix}"
+        suffix += 1
+    record = {"ID": program_id, "PN": program}
+    if ids:
+        record["PP"] = ids[-1]
+    record["CL"] = command_line
+    return record
+
+
+class Tagger:
+    """Copies the records of a SAM/BAM file to another, setting tags on them
+
+    The primary records are read as `Alignment`s from `alignments`, and each
+    one given to `write` is copied to the output file with its tags set,
+    followed by the secondary and supplementary records that came after it.
+    Those read but never given to `write`, e.g., reads that were filtered
+    out, are copied unchanged, along with the records after them, when a
+    later one is written or the tagger is closed, so every record read ends
+    up in the output in its place. This can be used as a context manager,
+    closing both files on exit.
+    """
+
+    alignments: Iterator[Alignment]
+    """The primary records of the input file"""
+
+    def __init__(self, alignments: Iterator[Alignment]):
+        self._pending: Deque[Alignment] = deque()
+        self.alignments = self._read(alignments)
+
+    def _read(self, alignments: Iterator[Alignment]) -> Iterator[Alignment]:
+        for alignment in alignments:
+            self._pending.append(alignment)
+            yield alignment
+
+    def write(self, alignment: Alignment, tags: Dict[str, Optional[int]]):
+        """Copy a record to the output file with integer tags set on it,
+        removing those whose value is None
+
+        Any records read before it that weren't written are copied first.
+        """
+        # the alignment may be a copy, e.g., with its qualities converted, but
+        # its record is the same one
+        while self._pending and self._pending[0].record is not alignment.record:
+            self._write_records(self._pending.popleft())
+        if self._pending:
+            self._pending.popleft()
+        for tag, value in tags.items():
+            self._set_tag(alignment.record, tag, value)
+        self._write_records(alignment)
+
+    def _write_records(self, alignment: Alignment):
+        for record in [alignment.record] + alignment.others:
+            self._write_record(record)
+
+    def _set_tag(self, record: Any, tag: str, value: Optional[int]):
+        raise NotImplementedError
+
+    def _write_record(self, record: Any):
+        raise NotImplementedError
+
+    def close(self):
+        """Copy any records read but not written, and close the input and
+        output files"""
+        while self._pending:
+            self._write_records(self._pending.popleft())
+        self._close()
+
+    def _close(self):
+        raise NotImplementedError
+
+    def __enter__(self) -> "Tagger":
+        return self
+
+    def __exit__(self, *exc_info):
+        self.close()
+
+
+class _SamTagger(Tagger):
+    """A Tagger from SAM to SAM, which doesn't need pysam"""
+
+    def __init__(self, in_path: str, out_path: str, program: str, command_line: str):
+        self._infile = open_text_read(in_path)
+        self._outfile = open_text_write(out_path)
+        lines = enumerate(self._infile, start=1)
+        header: List[str] = []
+        first = None
+        for line_number, line in lines:
+            if line.startswith("@"):
+                header.append(line.rstrip("\r\n"))
+            elif line.rstrip("\r\n"):
+                first = parse_sam_record(line_number, line)
+                break
+        programs = [
+            dict(column.split(":", 1) for column in line.split("\t")[1:])
+            for line in header
+            if line.startswith("@PG\t")
+        ]
+        pg = program_record(program, command_line, programs)
+        pg_columns = [f"{tag}:{value}" for tag, value in pg.items()]
+        header.append("\t".join(["@PG"] + pg_columns))
+        for line in header:
+            print(line, file=self._outfile)
+
+        def records() -> Iterator[SamRecord]:
+            if first is None:
+                return
+            yield first
+            for line_number, line in lines:
+                if line.rstrip("\r\n"):
+                    yield parse_sam_record(line_number, line)
+
+        super().__init__(
+            _alignments(records(), SamRecord.to_read, self._write_record)
+        )
+
+    def _set_tag(self, record: SamRecord, tag: str, value: Optional[int]):
+        record.set_tag(tag, value)
+
+    def _write_record(self, record: SamRecord):
+        print(record, file=self._outfile)
+
+    def _close(self):
+        self._infile.close()
+        if self._outfile is not sys.stdout:
+            self._outfile.close()
+
+
+class _PysamTagger(Tagger):
+    """A Tagger that reads and writes with pysam, for when either file is BAM"""
+
+    def __init__(
+        self,
+        in_path: str,
+        out_path: str,
+        program: str,
+        command_line: str,
+        bam_input: bool,
+    ):
+        pysam = _import_pysam(in_path if bam_input else out_path)
+        self._infile = pysam.AlignmentFile(
+            in_path, "rb" if bam_input else "r", check_sq=False
+        )
+        header = self._infile.header.to_dict()
+        header["PG"] = header.get("PG", []) + [
+            program_record(program, command_line, header.get("PG", []))
+        ]
+        self._outfile = pysam.AlignmentFile(
+            out_path, "w" if is_sam_path(out_path) else "wb", header=header
+        )
+        super().__init__(
+            _alignments(
+                self._infile.fetch(until_eof=True), _pysam_read, self._write_record
+            )
+        )
+
+    def _set_tag(self, record: Any, tag: str, value: Optional[int]):
+        record.set_tag(tag, value, "i")
+
+    def _write_record(self, record: Any):
+        self._outfile.write(record)
+
+    def _close(self):
+        self._infile.close()
+        self._outfile.close()
+
+
+def open_tagger(
+    in_path: str, out_path: str, program: str, command_line: str
+) -> Tagger:
+    """Open a SAM/BAM file to copy to another one with tags set
+
+    The output is SAM if `is_sam_path` says so, and BAM otherwise. Its
+    header is the input's plus a @PG record for the program. SAM to SAM is
+    done here, but anything involving BAM needs pysam.
+
+    Raises:
+        SeqError: if a BAM file is involved and pysam isn't installed
+    """
+    bam_input = is_bam(in_path)
+    if bam_input or not is_sam_path(out_path):
+        return _PysamTagger(in_path, out_path, program, command_line, bam_input)
+    return _SamTagger(in_path, out_path, program, command_line)
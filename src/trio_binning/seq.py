@@ -7,15 +7,321 @@ BioPython) or read-only (e.g., screed).
 This just has a single function for reading fastx files into a Read
 class, which then has a print function. That's all.
 """
+import argparse
+import functools
 import gzip
+import hashlib
+import heapq
+import io
+import logging
+import math
+import os
+import re
 import sys
-from dataclasses import dataclass
-from typing import Iterator, Optional, TextIO, Tuple, Union, cast
+import threading
+from collections import deque
+from contextlib import ExitStack
+from dataclasses import dataclass, replace
+from enum import Enum
+from itertools import chain, islice
+from typing import (
+    Any,
+    BinaryIO,
+    Callable,
+    Deque,
+    Dict,
+    Iterable,
+    Iterator,
+    List,
+    Optional,
+    Protocol,
+    Sequence,
+    Set,
+    TextIO,
+    Tuple,
+    TypeVar,
+    Union,
+    cast,
+)
+
+from trio_binning.bgzf import BgzfWriter
+
+logger = logging.getLogger(__name__)
+
+
+class SeqRecord(Protocol):
+    """Anything with the fields of a fasta or fastq record
+
+    `Read` is one, but so is any class with these attributes, so code
+    that only needs the fields of a record, like `classify_record`, the
+    k-mer counting functions and `write_record`, should take a SeqRecord
+    to work with other libraries' record types too.
+    """
+
+    @property
+    def id(self) -> str:
+        """The name of the record, everything in the header before a space"""
+
+    @property
+    def desc(self) -> Optional[str]:
+        """Everything in the header after the id, or None"""
+
+    @property
+    def seq(self) -> str:
+        """The sequence of the record"""
+
+    @property
+    def qual(self) -> Optional[str]:
+        """The quality string of the record, or None for fasta"""
+
+
+def record_defline(record: SeqRecord) -> str:
+    """The header line of a record, minus the leading > or @"""
+    if record.desc is None:
+        return record.id
+    return f"{record.id} {record.desc}"
+
+
+class OutputFormat(Enum):
+    """The format to write records in"""
+
+    AUTO = "auto"
+    """Fastq for records with a quality string and fasta for the rest, so
+    records are written the way they were read"""
+    FASTA = "fasta"
+    """Fasta, dropping any quality string"""
+    FASTQ = "fastq"
+    """Fastq, with a constant quality for records without a quality
+    string"""
+
+    def __str__(self):
+        return self.value
+
+
+DEFAULT_FAKE_QUALITY = 40
+"""The quality score `OutputFormat.FASTQ` gives the bases of records
+without a quality string"""
+
+
+def format_record(
+    record: SeqRecord,
+    output_format: OutputFormat = OutputFormat.AUTO,
+    fake_quality: int = DEFAULT_FAKE_QUALITY,
+) -> str:
+    """Format a record as text, without a trailing newline.
+
+    By default, records with a quality string are formatted as fastq and
+    the rest as fasta, with the sequence on a single line. The quality
+    string is written as it is, so it round-trips exactly.
+
+    Args:
+        record: the record to format
+        output_format: the format to write the record in
+        fake_quality: with `OutputFormat.FASTQ`, the Phred quality score
+            of every base of a record without a quality string
+    """
+    defline = record_defline(record)
+    if output_format == OutputFormat.FASTA:
+        return f">{defline}\n{record.seq}"
+    if record.qual:
+        return f"@{defline}\n{record.seq}\n+\n{record.qual}"
+    if output_format == OutputFormat.FASTQ:
+        qual = chr(PHRED_OFFSET + fake_quality) * len(record.seq)
+        return f"@{defline}\n{record.seq}\n+\n{qual}"
+    return f">{defline}\n{record.seq}"
+
+
+def write_record(
+    record: SeqRecord,
+    file: TextIO = sys.stdout,
+    output_format: OutputFormat = OutputFormat.AUTO,
+    fake_quality: int = DEFAULT_FAKE_QUALITY,
+):
+    """Write a record to a file, by default as fastq if it has qualities
+    or else fasta. See `format_record` for the other arguments."""
+    print(format_record(record, output_format, fake_quality), file=file)
+
+
+BaseCounts = Tuple[int, int, int, int, int]
+"""The number of A's, C's, G's, T's and other characters in a sequence"""
+
+
+def base_counts(record: SeqRecord) -> BaseCounts:
+    """Count the bases of a record, counting lowercase bases as uppercase
+
+    Returns:
+        the number of A's, C's, G's, T's and anything else, e.g., N's
+    """
+    seq = record.seq.upper()
+    a, c, g, t = (seq.count(base) for base in "ACGT")
+    return a, c, g, t, len(seq) - a - c - g - t
+
+
+def gc_content(record: SeqRecord) -> float:
+    """The fraction of a record's A's, C's, G's and T's that are G or C
+
+    Other characters, like N's, are left out, and lowercase bases are
+    counted as uppercase. A record with no ACGT bases has GC content 0.
+    """
+    a, c, g, t, _ = base_counts(record)
+    num_acgt = a + c + g + t
+    if not num_acgt:
+        return 0.0
+    return (c + g) / num_acgt
+
+
+class Stats:
+    """Statistics about the lengths and bases of some records, like those
+    of seqkit stats
+
+    Add each record with `add`, or get the statistics of all of some
+    records at once with `from_records`. Rather than every length, only
+    the number of records of each length is kept, which is enough to work
+    out the median and N50 exactly, so the memory taken grows with the
+    number of different lengths, at most the longest record's length,
+    however many records there are.
+    """
+
+    def __init__(self):
+        self.num_records = 0
+        """The number of records added"""
+        self.num_bases = 0
+        """The total length of the records"""
+        self.acgt_bases = 0
+        """The number of A's, C's, G's and T's, in either case"""
+        self.gc_bases = 0
+        """The number of G's and C's, in either case"""
+        self.n_bases = 0
+        """The number of N's, in either case"""
+        self.length_counts: Dict[int, int] = {}
+        """The number of records of each length"""
+
+    @classmethod
+    def from_records(cls, records: Iterable[SeqRecord]) -> "Stats":
+        """The statistics of some records, read one at a time"""
+        stats = cls()
+        for record in records:
+            stats.add(record)
+        return stats
+
+    def add(self, record: SeqRecord):
+        """Add a record's length and bases"""
+        seq = record.seq.upper()
+        a, c, g, t, n = (seq.count(base) for base in "ACGTN")
+        self.num_records += 1
+        self.num_bases += len(seq)
+        self.acgt_bases += a + c + g + t
+        self.gc_bases += c + g
+        self.n_bases += n
+        self.length_counts[len(seq)] = self.length_counts.get(len(seq), 0) + 1
+
+    @property
+    def min_length(self) -> Optional[int]:
+        """The length of the shortest record, or None if there are none"""
+        return min(self.length_counts, default=None)
+
+    @property
+    def max_length(self) -> Optional[int]:
+        """The length of the longest record, or None if there are none"""
+        return max(self.length_counts, default=None)
+
+    @property
+    def mean_length(self) -> Optional[float]:
+        """The mean length of the records, or None if there are none"""
+        if not self.num_records:
+            return None
+        return self.num_bases / self.num_records
+
+    @property
+    def median_length(self) -> Optional[float]:
+        """The median length of the records, the mean of the two middle
+        ones if there is an even number, or None if there are none"""
+        if not self.num_records:
+            return None
+        middle = [(self.num_records - 1) // 2, self.num_records // 2]
+        middle_lengths = []
+        num_shorter = 0
+        for length in sorted(self.length_counts):
+            num_shorter += self.length_counts[length]
+            while middle and middle[0] < num_shorter:
+                middle_lengths.append(length)
+                middle.pop(0)
+        return sum(middle_lengths) / 2
+
+    def nx(self, fraction: float) -> Optional[int]:
+        """The Nx length of the records, e.g., the N50 for `fraction` 0.5
+
+        This is the length of the shortest record such that the records
+        at least that long have at least `fraction` of the bases, or None
+        if there are no bases.
+
+        Raises:
+            ValueError: if `fraction` is not more than 0 and at most 1
+        """
+        if not 0 < fraction <= 1:
+            raise ValueError(f"the fraction must be in (0, 1], not {fraction}")
+        if not self.num_bases:
+            return None
+        bases = 0
+        for length in sorted(self.length_counts, reverse=True):
+            bases += length * self.length_counts[length]
+            if bases >= fraction * self.num_bases:
+                return length
+        raise AssertionError("the lengths don't add up to the bases")
+
+    @property
+    def n50(self) -> Optional[int]:
+        """The N50 length of the records"""
+        return self.nx(0.5)
+
+    @property
+    def n90(self) -> Optional[int]:
+        """The N90 length of the records"""
+        return self.nx(0.9)
+
+    @property
+    def gc_content(self) -> float:
+        """The fraction of the A's, C's, G's and T's that are G or C, or 0
+        if there are none, as for `gc_content`"""
+        if not self.acgt_bases:
+            return 0.0
+        return self.gc_bases / self.acgt_bases
+
+    def to_dict(self) -> Dict[str, Any]:
+        """Convert the statistics to a dict that can be serialized as JSON
+
+        The lengths are None if there are no records.
+        """
+        return {
+            "records": self.num_records,
+            "bases": self.num_bases,
+            "min_length": self.min_length,
+            "mean_length": self.mean_length,
+            "median_length": self.median_length,
+            "max_length": self.max_length,
+            "n50": self.n50,
+            "n90": self.n90,
+            "gc_content": self.gc_content,
+            "n_bases": self.n_bases,
+        }
+
+
+_COMPLEMENT = str.maketrans("ACGTUNacgtun", "TGCAANtgcaan")
+
+_RNA_TO_DNA = str.maketrans("Uu", "Tt")
+
+
+def rna_to_dna(sequence: str) -> str:
+    """Turn the U's of an RNA sequence into T's, keeping their case"""
+    return sequence.translate(_RNA_TO_DNA)
 
 
 @dataclass
 class Read:
-    """A fastx read"""
+    """A fastx read
+
+    This is a `SeqRecord` whose id is its name.
+    """
 
     name: str
     """The name of the read"""
@@ -23,12 +329,124 @@ class Read:
     """The sequence of the read"""
     qual: Optional[str] = None
     """The quality score string of the read"""
+    desc: Optional[str] = None
+    """Everything in the header line after the name and the space
+    following it, or None if there is nothing after the name"""
+
+    @property
+    def defline(self) -> str:
+        """The header line of the read, minus the leading > or @"""
+        return record_defline(self)
+
+    @property
+    def id(self) -> str:
+        """The name of the read, to make it a `SeqRecord`"""
+        return self.name
+
+    def subseq(self, start: int, end: int) -> "Read":
+        """Get part of the read
+
+        Args:
+            start: the 0-based start of the part
+            end: the 0-based, exclusive end of the part
+
+        Returns:
+            the part of the sequence and quality string from start to
+            end, named `name:start-end` and with the same description
+
+        Raises:
+            CoordinateError: if the part is not within the read, or its
+                end is before its start
+        """
+        if not 0 <= start <= end <= len(self.seq):
+            raise CoordinateError(self.name, start, end, len(self.seq))
+        return Read(
+            f"{self.name}:{start}-{end}",
+            self.seq[start:end],
+            None if self.qual is None else self.qual[start:end],
+            self.desc,
+        )
+
+    def reverse_complement(self) -> "Read":
+        """Reverse complement the read
+
+        A, C, G, T and N are complemented, keeping their case, and any
+        other characters are left as they are. U is complemented to A,
+        so the reverse complement of RNA is DNA. The quality string is
+        reversed.
+
+        Returns:
+            the reverse complement, named `name_rc` and with the same
+            description
+        """
+        return Read(
+            f"{self.name}_rc",
+            self.seq.translate(_COMPLEMENT)[::-1],
+            None if self.qual is None else self.qual[::-1],
+            self.desc,
+        )
+
+    def is_rna(self) -> bool:
+        """Whether the read looks like RNA: it has a U in it and no T, in
+        either case"""
+        return any(base in self.seq for base in "Uu") and not any(
+            base in self.seq for base in "Tt"
+        )
+
+    def to_dna(self) -> "Read":
+        """The read with the U's of its sequence turned into T's, keeping
+        its name, qualities and description"""
+        return replace(self, seq=rna_to_dna(self.seq))
+
+    def __len__(self) -> int:
+        """The length of the read's sequence
+
+        This means a read with an empty sequence is falsy, so check for
+        a missing read with `is None`.
+        """
+        return len(self.seq)
+
+    def to_dict(self) -> Dict[str, Any]:
+        """Convert the read to a dict that can be serialized as JSON
+
+        A missing quality string or description is left out rather than
+        being None.
+        """
+        read = {"name": self.name, "seq": self.seq}
+        if self.qual is not None:
+            read["qual"] = self.qual
+        if self.desc is not None:
+            read["desc"] = self.desc
+        return read
+
+    @staticmethod
+    def from_dict(read: Dict[str, Any]) -> "Read":
+        """Make a read from a dict made by `to_dict`
+
+        Raises:
+            ValueError: if the dict is missing the name or sequence, has
+                other keys, or a value that isn't a string
+        """
+        unknown = set(read) - {"name", "seq", "qual", "desc"}
+        if unknown:
+            raise ValueError(f"unknown read fields: {', '.join(sorted(unknown))}")
+        for field in ["name", "seq"]:
+            if field not in read:
+                raise ValueError(f"a read needs a {field}")
+        for field, value in read.items():
+            if not isinstance(value, str):
+                raise ValueError(f"the {field} of a read must be a string")
+        return Read(read["name"], read["seq"], read.get("qual"), read.get("desc"))
 
     def __str__(self):
-        if self.qual:
-            return f"@{self.name}\n{self.seq}\n+\n{self.qual}"
-        else:
-            return f">{self.name}\n{self.seq}"
+        """Format the read as text, without a trailing newline.
+
+        The text is rebuilt from the read's fields rather than kept from
+        the input, so it is normalized: the sequence and quality string
+        are always on a single line each, however they were wrapped in
+        the input. Use `FastaWriter` to wrap sequences.
+        """
+        return format_record(self)
 
     def print(self, file: TextIO = sys.stdout):
         """Print the read.
@@ -42,6 +460,132 @@ class Read:
         print(self, file=file)
 
 
+DEFAULT_MIN_GAP = 10
+"""The fewest N's in a row that `split_at_n` splits a scaffold at"""
+
+_SPLIT_ORIGIN = re.compile(r"^scaffold=(\S+) offset=(\d+)(?: |$)")
+"""Matches the start of the description `split_at_n` gives each piece"""
+
+
+def split_at_n(record: Read, min_gap: int = DEFAULT_MIN_GAP) -> List[Read]:
+    """Split a scaffold into contigs at its gaps
+
+    The scaffold is split wherever there's a run of at least `min_gap`
+    N's, in either case, which is left out. Runs like that at the start
+    or end are trimmed off rather than making empty pieces, and shorter
+    runs stay in the pieces. The pieces are named `name_part1`,
+    `name_part2` and so on, even if there's only one, and each one's
+    description starts with the scaffold's name and the 0-based offset
+    of the piece in it, as ``scaffold=name offset=1234``, followed by the
+    scaffold's description, which `split_origin` reads back.
+
+    Returns:
+        the pieces, in order, with their parts of the quality string,
+        or none if the scaffold is all gap
+
+    Raises:
+        ValueError: if `min_gap` is less than 1
+    """
+    if min_gap < 1:
+        raise ValueError(f"min_gap must be at least 1, not {min_gap}")
+    bounds = []
+    start = 0
+    for gap in re.finditer(f"[Nn]{{{min_gap},}}", record.seq):
+        bounds.append((start, gap.start()))
+        start = gap.end()
+    bounds.append((start, len(record.seq)))
+
+    pieces = []
+    for start, end in bounds:
+        if start == end:
+            continue
+        desc = f"scaffold={record.name} offset={start}"
+        if record.desc is not None:
+            desc += f" {record.desc}"
+        pieces.append(
+            Read(
+                f"{record.name}_part{len(pieces) + 1}",
+                record.seq[start:end],
+                None if record.qual is None else record.qual[start:end],
+                desc,
+            )
+        )
+    return pieces
+
+
+def split_origin(record: SeqRecord) -> Optional[Tuple[str, int]]:
+    """Get the scaffold a piece from `split_at_n` was split from
+
+    Returns:
+        the scaffold's name and the 0-based offset of the piece in it,
+        or None if the record's description doesn't say
+    """
+    match = _SPLIT_ORIGIN.match(record.desc or "")
+    if match is None:
+        return None
+    return match.group(1), int(match.group(2))
+
+
+_WHITESPACE = re.compile(r"\s")
+
+
+def split_defline(defline: str) -> Tuple[str, Optional[str]]:
+    """Split a header line into a name and description.
+
+    Args:
+        defline: the header line, minus the leading > or @
+
+    Returns:
+        name: everything up to the first whitespace, e.g., a space or a
+            tab
+        desc: everything after the first whitespace, verbatim, or None
+            if there is nothing after it
+    """
+    parts = _WHITESPACE.split(defline, maxsplit=1)
+    return parts[0], parts[1] if len(parts) > 1 and parts[1] else None
+
+
+class FastaWriter:
+    """Writes reads to a file in fasta format.
+
+    Unlike `Read.print`, this wraps sequences to a fixed line width and
+    always writes fasta, even for reads with quality scores. It can be
+    used as a context manager, flushing the file on exit:
+
+    >>> with FastaWriter(gzip.open("out.fa.gz", "wt"), line_width=80) as writer:
+    ...     writer.write(read)
+    """
+
+    def __init__(self, file: TextIO, line_width: int = 60):
+        """
+        Args:
+            file: the file to write to
+            line_width: the maximum number of bases per sequence line,
+                or 0 to write each sequence on a single line
+        """
+        self.file = file
+        self.line_width = line_width
+
+    def write(self, read: SeqRecord):
+        """Write a read, or any other record, in fasta format"""
+        print(f">{record_defline(read)}", file=self.file)
+        if self.line_width > 0 and read.seq:
+            for i in range(0, len(read.seq), self.line_width):
+                print(read.seq[i : i + self.line_width], file=self.file)
+        else:
+            print(read.seq, file=self.file)
+
+    def flush(self):
+        """Flush the underlying file"""
+        self.file.flush()
+
+    def __enter__(self) -> "FastaWriter":
+        return self
+
+    def __exit__(self, *exc_info):
+        self.flush()
+
+
 def readfq(fp: TextIO) -> Iterator[Read]:
     """Read a fastx file.
 
@@ -54,46 +598,1193 @@ def readfq(fp: TextIO) -> Iterator[Read]:
         if not last:  # the first record or a record following a fastq
             for line in fp:  # search for the start of the next record
                 if line[0] in ">@":  # fasta/q header line
-                    last = line[:-1]  # save this line
+                    last = line.rstrip("\r\n")  # save this line
                     break
         if not last:
             break
-        name, seqs, last = last[1:].partition(" ")[0], [], None
+        name, desc = split_defline(last[1:])
+        seqs, last = [], None
         for line in fp:  # read the sequence
             if line[0] in "@+>":
-                last = line[:-1]
+                last = line.rstrip("\r\n")
                 break
-            seqs.append(line[:-1])
+            if line[0] != ";":  # skip fasta comment lines
+                seqs.append(line.rstrip("\r\n"))
         if not last or last[0] != "+":  # this is a fasta record
-            yield Read(name, "".join(seqs), None)  # yield a fasta record
+            yield Read(name, "".join(seqs), None, desc)  # yield a fasta record
             if not last:
                 break
         else:  # this is a fastq record
             seq, leng, seqs = "".join(seqs), 0, []
             for line in fp:  # read the quality
-                seqs.append(line[:-1])
-                leng += len(line) - 1
+                line = line.rstrip("\r\n")
+                seqs.append(line)
+                leng += len(line)
                 if leng >= len(seq):  # have read enough quality
                     last = None
-                    yield Read(name, seq, "".join(seqs))
+                    yield Read(name, seq, "".join(seqs), desc)
                     # yield a fastq record
                     break
             if last:  # reach EOF before reading enough quality
-                yield Read(name, seq, None)  # yield a fasta record instead
+                yield Read(name, seq, None, desc)  # yield a fasta record instead
                 break
 
 
-def open_fastx_read(filename: str) -> Iterator[Read]:
-    """Open a fasta/q(.gz) file for reading."""
-    if filename.endswith(".gz"):
-        reads = readfq(cast(TextIO, gzip.open(filename, "rt")))
-    else:
-        reads = readfq(open(filename, "r"))
-    return reads
+class SeqError(ValueError):
+    """Base class for errors parsing sequence files"""
+
+
+class FastaError(SeqError):
+    """A line of a fasta file could not be parsed
+
+    Attributes:
+        line_number: the 1-based number of the bad line
+        line: the contents of the bad line
+        message: a description of what is wrong with the line
+    """
+
+    def __init__(self, line_number: int, line: str, message: str):
+        self.line_number = line_number
+        self.line = line
+        self.message = message
+        super().__init__(f"line {line_number}: {message}: {line!r}")
+
+
+class FastqError(SeqError):
+    """A fastq record could not be parsed
+
+    Attributes:
+        record_index: the 0-based index of the bad record in the file
+        message: a description of what is wrong with the record
+    """
+
+    def __init__(self, record_index: int, message: str):
+        self.record_index = record_index
+        self.message = message
+        super().__init__(f"fastq record {record_index}: {message}")
+
+
+class CoordinateError(SeqError):
+    """A range was asked for that isn't within a sequence
+
+    Attributes:
+        name: the name of the sequence
+        start: the 0-based start of the range
+        end: the 0-based, exclusive end of the range
+        length: the length of the sequence
+    """
+
+    def __init__(self, name: str, start: int, end: int, length: int):
+        self.name = name
+        self.start = start
+        self.end = end
+        self.length = length
+        super().__init__(
+            f"range {start}-{end} is not within {name!r}, which is {length} long"
+        )
+
+
+def read_fasta(
+    fp: TextIO, on_error: Optional[Callable[[SeqError], None]] = None
+) -> Iterator[Read]:
+    """Read a fasta file, checking that it is well-formed.
+
+    Read a fasta file, yielding a Read instance for each entry. Unlike
+    `readfq`, this checks each line as it goes and raises an error if
+    anything is wrong with it. Windows (CRLF) line endings are accepted,
+    and blank lines and comment lines starting with ; are skipped.
+
+    Args:
+        fp: the file to read
+        on_error: if given, this is called with each error instead of it
+            being raised, and everything up to the next header line is
+            skipped
+
+    Raises:
+        FastaError: if a header line has no name, or there is sequence
+            before the first header line
+    """
+    name: Optional[str] = None
+    desc: Optional[str] = None
+    seqs: List[str] = []
+    # whether the lines up to the next header are being skipped after an error
+    skipping = False
+    for line_number, line in enumerate(fp, start=1):
+        line = line.rstrip("\r\n")
+        if not line or line.startswith(";"):
+            continue
+        if line.startswith(">"):
+            if name is not None:
+                yield Read(name, "".join(seqs), None, desc)
+            name, desc = split_defline(line[1:])
+            seqs = []
+            skipping = False
+            if not name:
+                error = FastaError(line_number, line, "defline contains no ID")
+                if on_error is None:
+                    raise error
+                on_error(error)
+                name = None
+                skipping = True
+        elif skipping:
+            continue
+        elif name is None:
+            error = FastaError(line_number, line, "sequence before first defline")
+            if on_error is None:
+                raise error
+            on_error(error)
+            skipping = True
+        else:
+            seqs.append(line)
+
+    if name is not None:
+        yield Read(name, "".join(seqs), None, desc)
+
+
+def _parse_fastq_record(record_index: int, lines: List[str]) -> Read:
+    """Parse the (up to) four lines of a fastq record
+
+    Raises:
+        FastqError: if the record is malformed or cut short
+    """
+    header = lines[0]
+    if not header.startswith("@"):
+        raise FastqError(record_index, f"header {header!r} does not start with @")
+    name, desc = split_defline(header[1:])
+
+    if len(lines) < 3:
+        raise FastqError(record_index, "file ends before the + line")
+    if not lines[2].startswith("+"):
+        raise FastqError(record_index, f"separator {lines[2]!r} does not start with +")
+    if len(lines) < 4:
+        raise FastqError(record_index, "file ends before the quality line")
+
+    seq, qual = lines[1], lines[3]
+    if len(qual) != len(seq):
+        raise FastqError(
+            record_index,
+            f"quality length {len(qual)} does not match sequence length {len(seq)}",
+        )
+    return Read(name, seq, qual, desc)
+
+
+def _find_fastq_record(buffer: Deque[str], lines: Iterator[str]):
+    """Drop lines from the front of a buffer, refilling it from `lines`, until
+    it starts with what looks like the start of a fastq record"""
+    while True:
+        while len(buffer) < 3:
+            line = next(lines, None)
+            if line is None:
+                break
+            buffer.append(line)
+        if not buffer:
+            return
+        if buffer[0].startswith("@") and (len(buffer) < 3 or buffer[2].startswith("+")):
+            return
+        buffer.popleft()
+
+
+def _read_multiline_fastq(
+    lines: Iterator[str], on_error: Optional[Callable[[SeqError], None]] = None
+) -> Iterator[Read]:
+    """Read fastq records with their sequence and quality wrapped across
+    any number of lines
+
+    The sequence lines of a record run up to the first line starting with
+    +, and its quality lines from there until they're as long as the
+    sequence, which is where the next record's header is. Quality lines
+    can start with @, so the length is all there is to go by: a quality
+    that runs longer than the sequence is an error, and so is a quality
+    line with a space in it, which no quality character is, but the
+    header of the next record after a quality cut short likely has. A
+    quality cut short by just the length of the next header takes in the
+    header, so when a quality line starts with @, the line after the
+    quality must start with @ too, or else the record is an error rather
+    than merged with the next one.
+
+    With `on_error`, a bad record is skipped up to the next line that
+    starts with @, which could be a quality line, a chance the four-line
+    format doesn't have to take.
+
+    Raises:
+        FastqError: if the file ends partway through a record, a header
+            line doesn't start with @, a line starting with @ comes
+            before the + line, the quality ends up longer than the
+            sequence or has a space in it, or a quality line starts with
+            @ and the line after the quality doesn't
+    """
+    # a line that was read ahead, which is the next one to look at
+    pending: Deque[str] = deque()
+
+    def next_line() -> Optional[str]:
+        return pending.popleft() if pending else next(lines, None)
+
+    def parse(record_index: int, header: str) -> Read:
+        if not header.startswith("@"):
+            raise FastqError(record_index, f"header {header!r} does not start with @")
+        name, desc = split_defline(header[1:])
+        seqs: List[str] = []
+        while True:
+            line = next_line()
+            if line is None:
+                raise FastqError(record_index, "file ends before the + line")
+            if line.startswith("+"):
+                break
+            if line.startswith("@"):
+                pending.append(line)
+                raise FastqError(record_index, f"header {line!r} before the + line")
+            seqs.append(line)
+        seq = "".join(seqs)
+
+        quals: List[str] = []
+        qual_length = 0
+        while qual_length < len(seq):
+            line = next_line()
+            if line is None:
+                raise FastqError(
+                    record_index,
+                    f"file ends after {qual_length} of {len(seq)} quality characters",
+                )
+            if " " in line:
+                pending.append(line)
+                raise FastqError(
+                    record_index,
+                    f"quality line {line!r} has a space in it, so the quality is "
+                    f"likely cut short at {qual_length} of {len(seq)} characters",
+                )
+            quals.append(line)
+            qual_length += len(line)
+        if qual_length > len(seq):
+            raise FastqError(
+                record_index,
+                f"quality length {qual_length} does not match sequence length "
+                f"{len(seq)}",
+            )
+        if not any(qual.startswith("@") for qual in quals):
+            return Read(name, seq, "".join(quals), desc)
+        line = next_line()
+        while line == "":
+            line = next_line()
+        if line is not None:
+            pending.append(line)
+            if not line.startswith("@"):
+                raise FastqError(
+                    record_index,
+                    f"line {line!r} after the quality is not a header, so the "
+                    "quality likely ran into the next record's header",
+                )
+        return Read(name, seq, "".join(quals), desc)
+
+    record_index = 0
+    while True:
+        header = next_line()
+        if header is None:
+            return
+        if not header:
+            continue
+        try:
+            read = parse(record_index, header)
+        except FastqError as error:
+            if on_error is None:
+                raise
+            on_error(error)
+            while True:
+                line = next_line()
+                if line is None or line.startswith("@"):
+                    break
+            if line is not None:
+                pending.append(line)
+            record_index += 1
+            continue
+
+        yield read
+        record_index += 1
+
+
+def read_fastq(
+    fp: TextIO,
+    on_error: Optional[Callable[[SeqError], None]] = None,
+    multiline: bool = False,
+) -> Iterator[Read]:
+    """Read a fastq file, checking that it is well-formed.
+
+    Read a fastq file in the standard four-line format, yielding a Read
+    instance for each entry. Unlike `readfq`, this checks each record as
+    it goes and raises an error if anything is wrong with it. Windows
+    (CRLF) line endings are accepted, and so are blank lines between
+    records.
+
+    With `multiline`, records may have their sequence and quality
+    wrapped across any number of lines, as some old files do, which is
+    slower. See `_read_multiline_fastq`.
+
+    Args:
+        fp: the file to read
+        on_error: if given, this is called with each error instead of it
+            being raised, and the bad record is skipped by looking for the
+            next line that starts with @ and has a line starting with +
+            two lines after it, which is where the next record most
+            likely starts, since quality lines can start with @ too
+        multiline: whether to accept wrapped sequence and quality lines
+
+    Raises:
+        FastqError: if the file ends partway through a record, a header
+            line doesn't start with @, the separator line doesn't start
+            with +, or the quality string is not the same length as the
+            sequence
+    """
+    lines = (line.rstrip("\r\n") for line in fp)
+    if multiline:
+        yield from _read_multiline_fastq(lines, on_error)
+        return
+    # lines that were read ahead while looking for the next record
+    pending: Deque[str] = deque()
+
+    def next_line() -> Optional[str]:
+        return pending.popleft() if pending else next(lines, None)
+
+    record_index = 0
+    while True:
+        header = next_line()
+        if header is None:
+            return
+        if not header:
+            continue
+        record_lines = [header]
+        while len(record_lines) < 4:
+            line = next_line()
+            if line is None:
+                break
+            record_lines.append(line)
+
+        try:
+            read = _parse_fastq_record(record_index, record_lines)
+        except FastqError as error:
+            if on_error is None:
+                raise
+            on_error(error)
+            # look for the next record after the bad header
+            buffer = deque(record_lines[1:])
+            buffer.extend(pending)
+            pending.clear()
+            _find_fastq_record(buffer, lines)
+            pending.extend(buffer)
+            record_index += 1
+            continue
+
+        yield read
+        record_index += 1
+
+
+def mate_name(name: str) -> str:
+    """Get the name of a read pair from the name of one of its mates
+
+    This strips the /1 or /2 suffix that some tools add to the names of
+    the first and second mates.
+    """
+    if name.endswith(("/1", "/2")):
+        return name[:-2]
+    return name
+
+
+def pair_reads(
+    reads_1: Iterable[Read], reads_2: Iterable[Read]
+) -> Iterator[Tuple[Read, Read]]:
+    """Pair up the first and second mates of read pairs
+
+    This is `trio_binning.pair.PairIter`, which checks the mates' names.
+
+    Args:
+        reads_1: the first mates of the pairs
+        reads_2: the second mates of the pairs, in the same order
+
+    Yields:
+        the first and second mates of each pair
+
+    Raises:
+        SeqError: if there are more reads in one file than the other, or
+            the mates of a pair have different names (ignoring any /1 and
+            /2 suffixes)
+    """
+    from trio_binning.pair import PairIter
+
+    return PairIter(reads_1, reads_2)
 
 
+def deinterleave(reads: Iterable[Read]) -> Iterator[Tuple[Read, Read]]:
+    """Pair up the mates of read pairs from interleaved reads
+
+    This is `trio_binning.pair.PairIter.interleaved`.
+
+    Args:
+        reads: reads in which each first mate is directly followed by its
+            second mate
+
+    Yields:
+        the first and second mates of each pair
+
+    Raises:
+        SeqError: if there is an odd number of reads or two consecutive
+            mates have different names (ignoring any /1 and /2 suffixes)
+    """
+    from trio_binning.pair import PairIter
+
+    return PairIter.interleaved(reads)
+
+
+class IdSet:
+    """A set of read ids, to find reads seen more than once
+
+    Only a 64-bit BLAKE2b hash of each id is kept, so the set takes the
+    same memory, about 70 bytes per id, however long the ids are. Two
+    different ids have the same hash with probability 2 ** -64, so the
+    chance of a false duplicate among n ids is about n ** 2 / 2 ** 65:
+    3e-4 for 100 million reads, and 3% for a billion.
+
+    The hashes are split between `num_shards` sets by their lowest bits,
+    each with its own lock, so threads can add ids at the same time and
+    only wait for each other when they land in the same shard.
+    """
+
+    def __init__(self, num_shards: int = 16):
+        if num_shards < 1:
+            raise ValueError(f"num_shards must be at least 1, not {num_shards}")
+        self._shards: List[Set[int]] = [set() for _ in range(num_shards)]
+        self._locks = [threading.Lock() for _ in range(num_shards)]
+
+    @staticmethod
+    def _hash(read_id: str) -> int:
+        # names read with surrogateescape can have non-ASCII bytes in them
+        encoded = read_id.encode(TEXT_ENCODING, TEXT_ERRORS)
+        digest = hashlib.blake2b(encoded, digest_size=8).digest()
+        return int.from_bytes(digest, "little")
+
+    def add(self, read_id: str) -> bool:
+        """Add an id, and return whether it wasn't in the set already"""
+        id_hash = self._hash(read_id)
+        shard_index = id_hash % len(self._shards)
+        shard = self._shards[shard_index]
+        with self._locks[shard_index]:
+            if id_hash in shard:
+                return False
+            shard.add(id_hash)
+            return True
+
+    def __contains__(self, read_id: str) -> bool:
+        id_hash = self._hash(read_id)
+        return id_hash in self._shards[id_hash % len(self._shards)]
+
+    def __len__(self) -> int:
+        return sum(len(shard) for shard in self._shards)
+
+
+R = TypeVar("R", bound=SeqRecord)
+
+
+def read_ids(lines: Iterable[str]) -> Set[str]:
+    """Read a list of read ids, one per line
+
+    Anything after whitespace on a line is ignored, like in a fasta/q
+    header, as are blank lines.
+    """
+    return {fields[0] for fields in (line.split() for line in lines) if fields}
+
+
+def filter_by_id(
+    records: Iterable[R],
+    ids: Set[str],
+    invert: bool = False,
+    found: Optional[Set[str]] = None,
+) -> Iterator[R]:
+    """Keep the records with one of some ids, or with `invert`, the rest
+
+    A record's id is its name up to the first whitespace, so a read
+    named "read1 length=100" in a fasta file matches the id "read1".
+
+    Args:
+        records: the records to filter
+        ids: the ids of the records to keep or, with `invert`, to drop
+        invert: keep the records whose ids aren't in `ids` instead
+        found: if given, each id in `ids` that a record has is added to it
+            as the records are read, e.g., to find the ids that no record
+            had once they all have been
+
+    Yields:
+        the records kept, in order
+    """
+    for record in records:
+        record_id = record.id
+        if record_id in ids:
+            if found is not None:
+                found.add(record_id)
+            if not invert:
+                yield record
+        elif invert:
+            yield record
+
+
+DEDUP_DIGEST_SIZE = 16
+"""The size in bytes of the hash `dedup` keeps of each record, 128 bits.
+Two different sequences have the same hash with probability 2**-128, so
+among n distinct records the chance of any two sharing a hash, which
+would drop one as a duplicate of the other, is about n**2 / 2**129: less
+than 10**-20 for a billion reads. `dedup_exact` rules even that out."""
+
+D = TypeVar("D", bound=Union[SeqRecord, Tuple[SeqRecord, SeqRecord]])
+
+
+@dataclass
+class DedupCounts:
+    """How many records `dedup` or `dedup_exact` looked at and removed"""
+
+    records: int = 0
+    """The number of records, or read pairs, read"""
+    removed: int = 0
+    """The number of those that were duplicates of an earlier one"""
+    collisions: int = 0
+    """With `dedup_exact`, the number that had the same hash as an earlier
+    record but not the same sequence, which are kept"""
+
+
+def _dedup_key(item: D, same_id: bool, consider_revcomp: bool) -> bytes:
+    """What two records, or read pairs, must share to be duplicates
+
+    The mates of a pair are joined with a NUL between them, which no
+    sequence has, so moving bases from one mate to the other makes a
+    different key. With `consider_revcomp`, a record and its reverse
+    complement have the same key, and so do a pair and the same pair with
+    its mates swapped, which is the same fragment read from the other
+    strand.
+    """
+    mates: Sequence[SeqRecord] = item if isinstance(item, tuple) else (item,)
+    seqs = [mate.seq for mate in mates]
+    if consider_revcomp:
+        if len(seqs) == 1:
+            seqs = [min(seqs[0], seqs[0].translate(_COMPLEMENT)[::-1])]
+        else:
+            seqs = min(seqs, seqs[::-1])
+    parts = seqs
+    if same_id:
+        parts = [mate.id for mate in mates] + seqs
+    return "\0".join(parts).encode(TEXT_ENCODING, TEXT_ERRORS)
+
+
+def _dedup_digest(key: bytes) -> bytes:
+    return hashlib.blake2b(key, digest_size=DEDUP_DIGEST_SIZE).digest()
+
+
+def dedup(
+    items: Iterable[D],
+    same_id: bool = False,
+    consider_revcomp: bool = False,
+    counts: Optional[DedupCounts] = None,
+) -> Iterator[D]:
+    """Remove records with the same sequence as an earlier one
+
+    The records can be single records, or read pairs as tuples of their
+    mates, which are duplicates if both mates' sequences are the same.
+    Only a 128-bit hash of each distinct sequence is kept, not the
+    sequence, so memory stays bounded however long the reads are, at the
+    cost of a tiny chance of a collision (see `DEDUP_DIGEST_SIZE`).
+
+    Args:
+        items: the records or read pairs, which are read once
+        same_id: whether duplicates must have the same id too, e.g., to
+            remove the reads of a file that was uploaded twice
+        consider_revcomp: whether a record's reverse complement, or a
+            pair with its mates swapped, is a duplicate too
+        counts: if given, the records read and removed are added to it
+
+    Yields:
+        the first record with each sequence, in order
+    """
+    seen: Set[bytes] = set()
+    for item in items:
+        if counts is not None:
+            counts.records += 1
+        digest = _dedup_digest(_dedup_key(item, same_id, consider_revcomp))
+        if digest in seen:
+            if counts is not None:
+                counts.removed += 1
+            continue
+        seen.add(digest)
+        yield item
+
+
+def dedup_exact(
+    open_items: Callable[[], Iterable[D]],
+    same_id: bool = False,
+    consider_revcomp: bool = False,
+    counts: Optional[DedupCounts] = None,
+) -> Iterator[D]:
+    """Like `dedup`, but checking every duplicate byte for byte
+
+    The records are read twice. The first pass finds, from their hashes,
+    the records that may be duplicates and the earlier records they may
+    be duplicates of. The second keeps the sequences of just those
+    earlier records, and drops each candidate only if its sequence is
+    the same as its earlier record's. So memory grows with the number of
+    distinct sequences that have duplicates, rather than all of them.
+
+    Args:
+        open_items: opens the records or read pairs, which is called
+            twice and must give the same ones each time
+        same_id: as for `dedup`
+        consider_revcomp: as for `dedup`
+        counts: as for `dedup`, counting from the second pass
+
+    Yields:
+        the first record with each sequence, in order
+    """
+    first_indices: Dict[bytes, int] = {}
+    # the index of the earlier record with the same hash of each candidate
+    candidates: Dict[int, int] = {}
+    for index, item in enumerate(open_items()):
+        digest = _dedup_digest(_dedup_key(item, same_id, consider_revcomp))
+        first_index = first_indices.setdefault(digest, index)
+        if first_index != index:
+            candidates[index] = first_index
+    del first_indices
+
+    needed = set(candidates.values())
+    originals: Dict[int, bytes] = {}
+    for index, item in enumerate(open_items()):
+        if counts is not None:
+            counts.records += 1
+        if index in needed or index in candidates:
+            key = _dedup_key(item, same_id, consider_revcomp)
+            if index in needed:
+                originals[index] = key
+            if index in candidates:
+                if originals[candidates[index]] == key:
+                    if counts is not None:
+                        counts.removed += 1
+                    continue
+                logger.warning(
+                    "Record %d has the same hash as record %d but a different "
+                    "sequence, so it's kept",
+                    index + 1,
+                    candidates[index] + 1,
+                )
+                if counts is not None:
+                    counts.collisions += 1
+        yield item
+
+
+def in_subsample(name: str, fraction: float, seed: int = 0) -> bool:
+    """Whether a read is in a random subsample of about `fraction` of reads
+
+    Whether a read is in the subsample depends only on its name and the
+    seed, which are hashed together, so the same reads are picked every
+    time, whatever order they come in. Give mates the name of their pair,
+    from `mate_name`, to keep or leave out both. The seed must be a
+    signed 64-bit integer.
+    """
+    digest = hashlib.blake2b(
+        name.encode("utf-8", TEXT_ERRORS),
+        digest_size=8,
+        key=seed.to_bytes(8, "little", signed=True),
+    ).digest()
+    return int.from_bytes(digest, "little") < fraction * 2**64
+
+
+PHRED_OFFSET = 33
+"""The offset of quality characters from quality scores, e.g., "+" is
+quality 10. Phred+64 qualities are converted to these with
+`convert_qualities` when they're read."""
+
+
+_ERROR_PROBABILITIES = [10 ** (-quality / 10) for quality in range(94)]
+"""The probability of an error for each Phred quality score"""
+
+
+def mean_quality(qual: str) -> float:
+    """The mean Phred+33 quality of a quality string
+
+    The mean is of the error probabilities of the bases, converted back
+    to a quality score, rather than of the quality scores themselves, so
+    a few bad bases bring it down a lot: the mean of 40 and 10 is about
+    13, not 25. An empty quality string has mean quality 0.
+    """
+    if not qual:
+        return 0.0
+    total = sum(
+        _ERROR_PROBABILITIES[min(max(ord(char) - PHRED_OFFSET, 0), 93)]
+        for char in qual
+    )
+    return -10 * math.log10(total / len(qual))
+
+
+class QualityEncoding(Enum):
+    """The offset of the quality characters in a fastq file"""
+
+    AUTO = "auto"
+    """Detected from the qualities of the first reads, with
+    `detect_quality_encoding`"""
+    PHRED33 = "phred33"
+    """Quality 0 is "!", as in all recent data"""
+    PHRED64 = "phred64"
+    """Quality 0 is "@", as in Illumina GA and HiSeq data from before
+    Illumina 1.8"""
+
+    def __str__(self):
+        return self.value
+
+
+PHRED64_OFFSET = 64
+"""The offset of Phred+64 quality characters from quality scores"""
+
+_LOWEST_PHRED64 = chr(PHRED64_OFFSET)
+"""The lowest Phred+64 character, "@" for quality 0. Anything below it
+is Phred+33."""
+
+_HIGHEST_PHRED33_ILLUMINA = "J"
+"""The highest Illumina Phred+33 character, quality 41. Anything above it
+up to `_HIGHEST_PHRED64` is taken to be Phred+64."""
+
+_HIGHEST_PHRED64 = "i"
+"""The highest Phred+64 character Illumina ever wrote, quality 41.
+Anything above it is Phred+33, e.g., PacBio HiFi's "~"."""
+
+_PHRED64_TO_PHRED33 = str.maketrans(
+    {
+        chr(code): chr(max(code - PHRED64_OFFSET, 0) + PHRED_OFFSET)
+        for code in range(PHRED_OFFSET, 127)
+    }
+)
+"""Converts Phred+64 quality characters to Phred+33 ones, with anything
+below "@", which Phred+64 doesn't have, becoming quality 0"""
+
+
+def detect_quality_encoding(quals: Iterable[str]) -> Optional[QualityEncoding]:
+    """Work out whether quality strings are Phred+33 or Phred+64
+
+    A character below "@" can only be Phred+33, and so can one above "i",
+    the highest Phred+64 character. Otherwise, a character above "J", the
+    highest Illumina Phred+33 character, is Phred+64. If they're all from
+    "@" to "J", they could be either. Solexa qualities go down to ";", so
+    they're taken to be Phred+33, and need `QualityEncoding.PHRED64`
+    given to be converted.
+
+    Returns:
+        `QualityEncoding.PHRED33` or `QualityEncoding.PHRED64`, or None
+        if there are no qualities or they could be either
+    """
+    quals = [qual for qual in quals if qual]
+    if not quals:
+        return None
+    lowest = min(min(qual) for qual in quals)
+    highest = max(max(qual) for qual in quals)
+    if lowest < _LOWEST_PHRED64 or highest > _HIGHEST_PHRED64:
+        return QualityEncoding.PHRED33
+    if highest > _HIGHEST_PHRED33_ILLUMINA:
+        return QualityEncoding.PHRED64
+    return None
+
+
+def convert_qualities(
+    reads: Iterable[Read],
+    encoding: QualityEncoding = QualityEncoding.AUTO,
+    num_reads: int = 1000,
+) -> Iterator[Read]:
+    """Convert the quality strings of reads to Phred+33 if they're Phred+64
+
+    With `QualityEncoding.AUTO`, the encoding is detected from the
+    qualities of the first `num_reads` reads, which are looked at before
+    any are passed on. If they could be either, they're taken to be
+    Phred+33, with a warning. Reads without qualities pass through as
+    they are.
+
+    Args:
+        reads: the reads to convert
+        encoding: the encoding of the reads' qualities
+        num_reads: how many reads to detect the encoding from
+
+    Yields:
+        the reads, with Phred+33 quality strings
+    """
+    reads = iter(reads)
+    if encoding == QualityEncoding.AUTO:
+        first_reads = list(islice(reads, num_reads))
+        quals = [read.qual for read in first_reads if read.qual]
+        detected = detect_quality_encoding(quals)
+        if detected is None:
+            if quals:
+                logger.warning(
+                    "Can't tell whether the quality scores are Phred+33 or Phred+64 "
+                    "(they range from %r to %r), so taking them to be Phred+33",
+                    min(min(qual) for qual in quals),
+                    max(max(qual) for qual in quals),
+                )
+            detected = QualityEncoding.PHRED33
+        logger.info("Quality scores look like %s", detected)
+        encoding = detected
+        reads = chain(first_reads, reads)
+
+    if encoding == QualityEncoding.PHRED33:
+        yield from reads
+        return
+    for read in reads:
+        if read.qual is not None:
+            read = replace(read, qual=read.qual.translate(_PHRED64_TO_PHRED33))
+        yield read
+
+
+def check_qualities(reads: Iterable[Read], num_reads: int = 1000) -> Iterator[Read]:
+    """Check that reads have Phred+33 quality strings
+
+    The qualities of the first `num_reads` reads are looked at before
+    any are passed on, and are an error if `detect_quality_encoding` says
+    they're Phred+64. Read fastq files with `convert_qualities`, e.g.,
+    through `open_records`, to take Phred+64 qualities instead.
+
+    Args:
+        reads: the reads to check
+        num_reads: how many reads to look at for Phred+64 qualities
+
+    Yields:
+        the reads, unchanged
+
+    Raises:
+        SeqError: if a read has no quality string, e.g., because it is
+            from a fasta file, or the qualities look like Phred+64
+    """
+    reads = iter(reads)
+    first_reads = list(islice(reads, num_reads))
+    quals = [read.qual for read in first_reads if read.qual]
+    if detect_quality_encoding(quals) == QualityEncoding.PHRED64:
+        raise SeqError(
+            f"quality scores look like Phred+64 (they range from "
+            f"{min(min(qual) for qual in quals)!r} to "
+            f"{max(max(qual) for qual in quals)!r}), but only Phred+{PHRED_OFFSET} "
+            "is supported"
+        )
+
+    for read in chain(first_reads, reads):
+        if read.qual is None:
+            raise SeqError(f"read {read.name!r} has no quality scores")
+        yield read
+
+
+GZIP_MAGIC = b"\x1f\x8b"
+"""The first two bytes of every gzip file"""
+
+TEXT_ENCODING = "ascii"
+"""The encoding sequence files are read and written in"""
+
+TEXT_ERRORS = "surrogateescape"
+"""How bytes that aren't ASCII are handled. Each one is read as a lone
+surrogate character and written back out as the same byte, so a file
+with, e.g., Latin-1 in its descriptions passes through unchanged rather
+than failing to decode."""
+
 TextOrGzip = Union[TextIO, gzip.GzipFile]
 
+COMPRESSED_EXTENSIONS = (".gz", ".bgz")
+"""The extensions of files that are written gzipped, with .bgz for BGZF"""
+
+
+def open_text_read(filename: str) -> TextIO:
+    """Open a possibly gzipped text file for reading.
+
+    Whether the file is gzipped is detected by looking for the gzip
+    magic bytes at the start of the file rather than by its extension,
+    so this works even if a gzipped file isn't named .gz or vice versa.
+
+    A filename of "-" means standard input, which can be gzipped too.
+    Its first bytes are peeked at rather than read, so nothing is lost
+    if it isn't.
+    """
+    if filename == "-":
+        stdin = cast(io.BufferedReader, sys.stdin.buffer)
+        binary: BinaryIO = stdin
+        if stdin.peek(len(GZIP_MAGIC))[: len(GZIP_MAGIC)] == GZIP_MAGIC:
+            binary = cast(BinaryIO, gzip.GzipFile(fileobj=stdin))
+        return io.TextIOWrapper(binary, encoding=TEXT_ENCODING, errors=TEXT_ERRORS)
+
+    with open(filename, "rb") as fp:
+        is_gzipped = fp.read(len(GZIP_MAGIC)) == GZIP_MAGIC
+
+    if is_gzipped:
+        return cast(
+            TextIO,
+            gzip.open(filename, "rt", encoding=TEXT_ENCODING, errors=TEXT_ERRORS),
+        )
+    else:
+        return open(filename, "r", encoding=TEXT_ENCODING, errors=TEXT_ERRORS)
+
+
+def open_text_write(filename: str, append: bool = False, bgzf: bool = False) -> TextIO:
+    """Open a text file for writing, gzipped if its name ends in .gz, and
+    BGZF if it ends in .bgz, or in .gz with `bgzf`.
+
+    A filename of "-" means standard output, which is returned as is,
+    so the caller should not close it, except that any bytes that weren't
+    ASCII in the input are written back out as they were (see
+    `TEXT_ERRORS`). With `append`, the file is added to rather than
+    overwritten.
+    """
+    if filename == "-":
+        if isinstance(sys.stdout, io.TextIOWrapper):
+            sys.stdout.reconfigure(errors=TEXT_ERRORS)
+        return sys.stdout
+    if filename.endswith(COMPRESSED_EXTENSIONS):
+        return cast(TextIO, _open_gzip_write(filename, append=append, bgzf=bgzf))
+    return _open_plain_write(filename, append)
+
+
+def strip_compressed_extension(filename: str) -> str:
+    """Take the .gz or .bgz off the end of a filename, if it has one"""
+    for extension in COMPRESSED_EXTENSIONS:
+        if filename.endswith(extension):
+            return filename[: -len(extension)]
+    return filename
+
+
+def _open_plain_write(filename: str, append: bool = False) -> TextIO:
+    """Open an uncompressed sequence file for writing"""
+    return open(
+        filename, "a" if append else "w", encoding=TEXT_ENCODING, errors=TEXT_ERRORS
+    )
+
+
+class _GzipMembers(io.BufferedIOBase):
+    """A binary file that is written as a series of gzip members
+
+    The file is one member until `end_member` is called, which finishes
+    the member so far and starts another, so that the file can be cut
+    back to the end of that member and still be a whole gzip file.
+    Readers of gzip, including Python's and zcat, read the members one
+    after another as if they were one.
+    """
+
+    def __init__(self, filename: str, append: bool, compression_level: int):
+        super().__init__()
+        self._file = open(filename, "ab" if append else "wb")
+        self._compression_level = compression_level
+        self._member = self._start_member()
+
+    def _start_member(self) -> gzip.GzipFile:
+        # the modification time is 0 rather than the time of writing, so
+        # the same reads always give byte-identical files that can be
+        # checked with checksums
+        return gzip.GzipFile(
+            fileobj=self._file,
+            mode="wb",
+            compresslevel=self._compression_level,
+            mtime=0,
+        )
+
+    @property
+    def name(self) -> str:
+        return self._file.name
+
+    def writable(self) -> bool:
+        return True
+
+    def write(self, data) -> int:  # type: ignore[override]
+        return self._member.write(data)
+
+    def flush(self):
+        self._member.flush()
+        self._file.flush()
+
+    def fileno(self) -> int:
+        return self._file.fileno()
+
+    def end_member(self):
+        """Finish the member written so far and start another"""
+        self._member.close()
+        self._file.flush()
+        self._member = self._start_member()
+
+    def close(self):
+        if self.closed:
+            return
+        try:
+            super().close()
+            self._member.close()
+        finally:
+            self._file.close()
+
+
+def _open_gzip_write(
+    filename: str,
+    compression_level: int = 9,
+    append: bool = False,
+    bgzf: bool = False,
+    threads: int = 1,
+) -> TextOrGzip:
+    """Open a gzipped sequence file for writing, or for adding to with
+    `append`
+
+    The file is BGZF if its name ends in .bgz or `bgzf` is given, with
+    its blocks compressed by `threads` threads, and plain gzip
+    otherwise.
+    """
+    binary: io.BufferedIOBase
+    if bgzf or filename.endswith(".bgz"):
+        binary = BgzfWriter(filename, append, compression_level, threads)
+    else:
+        binary = _GzipMembers(filename, append, compression_level)
+    return io.TextIOWrapper(
+        cast(BinaryIO, binary), encoding=TEXT_ENCODING, errors=TEXT_ERRORS
+    )
+
+
+def sync_outfile(outfile: TextOrGzip) -> int:
+    """Write everything written to a file opened by `open_outfile` or
+    `open_text_write` out to disk, and get its length
+
+    A gzipped file has its gzip member finished, and a BGZF file its
+    block, so the file can be cut back to the length returned and still
+    be read in full.
+
+    Returns:
+        the length of the file on disk, in bytes
+    """
+    outfile.flush()
+    binary = getattr(outfile, "buffer", None)
+    if isinstance(binary, _GzipMembers):
+        binary.end_member()
+    os.fsync(outfile.fileno())
+    return os.fstat(outfile.fileno()).st_size
+
+
+F = TypeVar("F", bound=Callable[..., Any])
+
+
+def exit_on_broken_pipe(main: F) -> F:
+    """Make a main function exit quietly if its output pipe is closed
+
+    This is for when the output is piped into something that stops
+    reading early, like `head`, which otherwise makes Python print a
+    BrokenPipeError traceback.
+    """
+
+    @functools.wraps(main)
+    def wrapper(*args, **kwargs):
+        try:
+            return main(*args, **kwargs)
+        except BrokenPipeError:
+            # Python flushes stdout again on exit, which would fail the same
+            # way, so send whatever is left to /dev/null instead
+            devnull = os.open(os.devnull, os.O_WRONLY)
+            os.dup2(devnull, sys.stdout.fileno())
+            sys.exit(1)
+
+    return cast(F, wrapper)
+
+
+def open_fastx_read(filename: str) -> Iterator[Read]:
+    """Open a fasta/q file, gzipped or not, for reading."""
+    return readfq(open_text_read(filename))
+
+
+def open_records(
+    filename: str,
+    on_error: Optional[Callable[[SeqError], None]] = None,
+    quality_encoding: Optional[QualityEncoding] = None,
+    multiline_fastq: bool = False,
+) -> Iterator[Read]:
+    """Open a fasta, fastq, SAM or BAM file, gzipped or not, detecting its format.
+
+    The format is detected from the start of the file after decompressing
+    it: > (or a ; comment) for fasta, @ for fastq, a SAM header line or
+    tab-separated alignment for SAM, or the BAM magic bytes for BAM. The
+    file is then read with `read_fasta`, `read_fastq`, `sam.read_sam` or
+    `sam.read_bam`, which check it as they go, so a malformed file is an
+    error rather than being misread. Either way, the records are Reads,
+    with qual set only if the file has qualities.
+
+    Nothing is read until the first record is asked for, so errors are
+    raised by iterating rather than by calling this.
+
+    Args:
+        filename: the file to read, or - for stdin
+        on_error: if given, malformed records are skipped, and this is
+            called with the error for each one instead of it being raised.
+            See `read_fastq` for how the next record is found. This
+            isn't used for BAM files, which pysam reads.
+        quality_encoding: if given, the qualities of a fastq file are
+            converted to Phred+33 from this encoding with
+            `convert_qualities`. SAM and BAM qualities are always
+            Phred+33.
+        multiline_fastq: whether a fastq file may have its sequences and
+            qualities wrapped across more than one line each, passed to
+            `read_fastq`
+
+    Raises:
+        SeqError: if the file is empty or doesn't look like any of those
+            formats, including if it starts with whitespace, or is
+            malformed, or is BAM and pysam isn't installed or it is
+            standard input
+    """
+    from trio_binning import sam
+
+    with open_text_read(filename) as fp:
+        first = fp.readline()
+        if not first:
+            raise SeqError(f"{filename} is empty")
+        if first.startswith(sam.BAM_MAGIC):
+            if filename == "-":
+                raise SeqError("BAM files can't be read from standard input")
+            fp.close()
+            yield from sam.read_bam(filename)
+            return
+        if sam.looks_like_sam(first):
+            yield from sam.read_sam(chain([first], fp), on_error)
+            return
+        if first[0].isspace():
+            raise SeqError(
+                f"{filename} starts with whitespace rather than a > or @ header line"
+            )
+        if first[0] not in ">@;":
+            raise SeqError(
+                f"{filename} doesn't look like fasta or fastq: it starts with "
+                f"{first[0]!r} rather than > or @"
+            )
+
+        if first[0] != "@":
+            yield from read_fasta(chain([first], fp), on_error)
+            return
+        reads = read_fastq(chain([first], fp), on_error, multiline_fastq)
+        if quality_encoding is not None:
+            reads = convert_qualities(reads, quality_encoding)
+        yield from reads
+
+
+def add_multiline_fastq_argument(parser: argparse.ArgumentParser):
+    """Add a --multiline-fastq option, for `open_records`'
+    `multiline_fastq`"""
+    parser.add_argument(
+        "--multiline-fastq",
+        action="store_true",
+        default=False,
+        help="let fastq reads have their sequences and qualities wrapped across "
+        "more than one line each, which is slower to read. Without it, each fastq "
+        "record must be four lines.",
+    )
+
+
+def open_outfile(
+    prefix: str,
+    outfile_extension: str,
+    gzip_output: bool,
+    compression_level: int = 9,
+    append: bool = False,
+    bgzf: bool = False,
+    compression_threads: int = 1,
+) -> TextOrGzip:
+    """Open an output file named `prefix` plus `outfile_extension`, plus .gz
+    if gzipped. See `open_outfiles` for the other arguments."""
+    outfile_name = prefix + outfile_extension
+    if not gzip_output:
+        return _open_plain_write(outfile_name, append)
+    return _open_gzip_write(
+        outfile_name + ".gz", compression_level, append, bgzf, compression_threads
+    )
+
 
 def open_outfiles(
     haplotype_a_prefix: str,
@@ -101,6 +1792,10 @@ def open_outfiles(
     unclassified_prefix: str,
     outfile_extension: str,
     gzip_output: bool,
+    compression_level: int = 9,
+    append: bool = False,
+    bgzf: bool = False,
+    compression_threads: int = 1,
 ) -> Tuple[TextOrGzip, TextOrGzip, TextOrGzip]:
     """Open output files based on given options.
 
@@ -110,27 +1805,180 @@ def open_outfiles(
         unclassified_prefix: path prefix for unclassified output file
         outfile_extension: extension for output file (e.g., ".fa")
         gzip_output: True to gzip output files, False otherwise
+        compression_level: gzip compression level, from 1 (fastest) to 9
+            (smallest)
+        append: True to add to the files rather than overwrite them
+        bgzf: True to gzip the files as BGZF, which can be indexed
+        compression_threads: the number of threads to compress each
+            BGZF file with
 
     Returns:
         haplotype_a_outfile: writeable outfile for haplotype A
         haplotype_b_outfile: writeable outfile for haplotype B
         unclassified_outfile: writeable outfile for unclassified reads
     """
-    haplotype_a_outfile_name = haplotype_a_prefix + outfile_extension
-    haplotype_b_outfile_name = haplotype_b_prefix + outfile_extension
-    unclassified_outfile_name = unclassified_prefix + outfile_extension
+    haplotype_a_outfile, haplotype_b_outfile, unclassified_outfile = (
+        open_outfile(
+            prefix,
+            outfile_extension,
+            gzip_output,
+            compression_level,
+            append,
+            bgzf,
+            compression_threads,
+        )
+        for prefix in [haplotype_a_prefix, haplotype_b_prefix, unclassified_prefix]
+    )
+    return haplotype_a_outfile, haplotype_b_outfile, unclassified_outfile
 
-    haplotype_a_outfile: TextOrGzip
-    haplotype_b_outfile: TextOrGzip
-    unclassified_outfile: TextOrGzip
 
-    if not gzip_output:
-        haplotype_a_outfile = open(haplotype_a_outfile_name, "w")
-        haplotype_b_outfile = open(haplotype_a_outfile_name, "w")
-        unclassified_outfile = open(unclassified_outfile_name, "w")
-    else:
-        haplotype_a_outfile = gzip.open(haplotype_a_outfile_name + ".gz", "wt")
-        haplotype_b_outfile = gzip.open(haplotype_b_outfile_name + ".gz", "wt")
-        unclassified_outfile = gzip.open(unclassified_outfile_name + ".gz", "wt")
+class SplitBy(Enum):
+    """What `split` balances between the shards"""
 
-    return haplotype_a_outfile, haplotype_b_outfile, unclassified_outfile
+    READS = "reads"
+    BASES = "bases"
+
+    def __str__(self):
+        return self.value
+
+
+@dataclass
+class Shard:
+    """One of the files, or pairs of files, written by `split`"""
+
+    paths: List[str]
+    """The file the shard's reads are in, or the first and second mates'
+    files"""
+    reads: int = 0
+    """The number of reads in the shard, counting each mate of a pair"""
+    bases: int = 0
+    """The total length of the reads in the shard"""
+
+
+def split(
+    records: Iterable[SeqRecord],
+    n: int,
+    prefix: str,
+    outfile_extension: str = ".fa",
+    gzip_output: bool = False,
+    by: SplitBy = SplitBy.READS,
+    compression_level: int = 9,
+) -> List[Shard]:
+    """Split records between `n` files, e.g., to classify them in parallel
+
+    The files are named `prefix`, a dot, the shard's number from 1, then
+    `outfile_extension` and .gz if gzipped, e.g., reads.1.fastq.gz. The
+    numbers are zero-padded to the same width, so the files sort in
+    order. By reads, the records are dealt out to the shards in turn; by
+    bases, each record goes to the shard with the fewest bases so far,
+    so the shards' bases differ by at most the length of the longest
+    record. Either way, this streams the records, holding none in
+    memory.
+
+    Returns:
+        each shard's files and how many reads and bases went into it
+
+    Raises:
+        ValueError: if `n` is less than 1
+    """
+    return _split(
+        ((record,) for record in records),
+        n,
+        prefix,
+        [""],
+        outfile_extension,
+        gzip_output,
+        by,
+        compression_level,
+    )
+
+
+def split_pairs(
+    pairs: Iterable[Tuple[SeqRecord, SeqRecord]],
+    n: int,
+    prefix: str,
+    outfile_extension: str = ".fa",
+    gzip_output: bool = False,
+    by: SplitBy = SplitBy.READS,
+    compression_level: int = 9,
+    interleave: bool = False,
+) -> List[Shard]:
+    """Split read pairs between `n` shards, keeping both mates of a pair in
+    the same one
+
+    This is the same as `split`, but each shard is two files, one for
+    each mate, named with _R1 and _R2 after the shard's number, e.g.,
+    reads.1_R1.fastq, or with `interleave`, one file with each first mate
+    followed by its second mate. By bases, a pair's bases are both mates'.
+    """
+    suffixes = [""] if interleave else ["_R1", "_R2"]
+    return _split(
+        pairs,
+        n,
+        prefix,
+        suffixes,
+        outfile_extension,
+        gzip_output,
+        by,
+        compression_level,
+    )
+
+
+def _split(
+    items: Iterable[Sequence[SeqRecord]],
+    n: int,
+    prefix: str,
+    suffixes: List[str],
+    outfile_extension: str,
+    gzip_output: bool,
+    by: SplitBy,
+    compression_level: int,
+) -> List[Shard]:
+    """Split items of one or more mates between `n` shards, with each mate
+    in the file with its suffix, or all in one file if there is only one
+    suffix"""
+    if n < 1:
+        raise ValueError(f"the number of shards must be at least 1, not {n}")
+    width = len(str(n))
+    shard_prefixes = [
+        [f"{prefix}.{i:0{width}d}{suffix}" for suffix in suffixes]
+        for i in range(1, n + 1)
+    ]
+    shards = [
+        Shard(
+            [
+                shard_prefix + outfile_extension + (".gz" if gzip_output else "")
+                for shard_prefix in prefixes
+            ]
+        )
+        for prefixes in shard_prefixes
+    ]
+    with ExitStack() as stack:
+        outfiles = [
+            [
+                stack.enter_context(
+                    open_outfile(
+                        shard_prefix, outfile_extension, gzip_output, compression_level
+                    )
+                )
+                for shard_prefix in prefixes
+            ]
+            for prefixes in shard_prefixes
+        ]
+        # the shards by their bases so far, to take the one with the fewest
+        heap = [(0, i) for i in range(n)]
+        for item_index, item in enumerate(items):
+            if by == SplitBy.READS:
+                shard_index = item_index % n
+            else:
+                _, shard_index = heap[0]
+            shard = shards[shard_index]
+            num_bases = sum(len(record.seq) for record in item)
+            for mate_index, record in enumerate(item):
+                files = outfiles[shard_index]
+                write_record(record, files[mate_index % len(files)])
+            shard.reads += len(item)
+            shard.bases += num_bases
+            if by == SplitBy.BASES:
+                heapq.heapreplace(heap, (shard.bases, shard_index))
+    return shards
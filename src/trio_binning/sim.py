@@ -0,0 +1,163 @@
+"""Simulate trio data with a known truth.
+
+`simulate_trio` makes two parental haplotypes, the hap-mers specific to
+each, and reads of the child drawn from the two haplotypes with
+sequencing errors, labeled with the haplotype each came from. This is
+for checking classification against the truth, in tests or, with the
+`kmers simulate` tool, to try out parameters before binning real data.
+
+The haplotypes are random sequence, so the hap-mers are only those
+around the heterozygous sites, with none of the repeats or copy number
+differences of a real genome. Accuracy on simulated data is a best case.
+"""
+
+import math
+import random
+from dataclasses import dataclass, replace
+from typing import List, Optional
+
+from trio_binning import count, kmers, seq
+from trio_binning.classify import Bin
+
+BASES = "ACGT"
+
+
+@dataclass
+class SimulatedRead:
+    """A read of the child with the truth about where it came from"""
+
+    read: seq.Read
+    """The read, with its truth also in its description"""
+    truth: Bin
+    """The haplotype the read was drawn from"""
+    start: int
+    """The 0-based start of the read in its haplotype"""
+    reverse: bool
+    """Whether the read is from the reverse strand"""
+
+
+@dataclass
+class SimulatedTrio:
+    """The output of `simulate_trio`"""
+
+    hap_a: str
+    """The sequence of haplotype A"""
+    hap_b: str
+    """The sequence of haplotype B, which is haplotype A with a
+    substitution at each heterozygous site"""
+    hap_a_kmers: kmers.KmerSet
+    """The canonical k-mers in haplotype A and not in B"""
+    hap_b_kmers: kmers.KmerSet
+    """The canonical k-mers in haplotype B and not in A"""
+    reads: List[SimulatedRead]
+    """The child's reads, in the order they were drawn"""
+
+
+def _substitute(rng: random.Random, bases: str, rate: float) -> str:
+    """Substitute a different random base for each base with probability
+    `rate`
+
+    The gaps between substitutions are drawn from a geometric
+    distribution, so this takes time in the number of substitutions
+    rather than the number of bases.
+    """
+    if rate <= 0:
+        return bases
+    mutated = list(bases)
+    log_no_substitution = math.log1p(-rate) if rate < 1 else -math.inf
+    i = -1
+    while True:
+        # 1 - random() is in (0, 1], so its log is finite
+        i += 1 + int(math.log(1 - rng.random()) / log_no_substitution)
+        if i >= len(mutated):
+            return "".join(mutated)
+        mutated[i] = rng.choice([base for base in BASES if base != mutated[i]])
+
+
+def simulate_trio(
+    genome_len: int,
+    het_rate: float,
+    read_len: int,
+    coverage: float,
+    error_rate: float,
+    seed: Optional[int] = None,
+    k: int = 21,
+) -> SimulatedTrio:
+    """Simulate a child's two haplotypes, their hap-mers, and reads of them
+
+    Each read is drawn from either haplotype with equal probability, at
+    a uniformly random start and strand, and then has a substitution at
+    each base with probability `error_rate`. The reads are fastq with
+    every base's quality at the error rate. Each read's description has
+    its truth, e.g., "hap=A start=1200 strand=-".
+
+    >>> trio = simulate_trio(1000, 0.01, 100, 2, 0.0, seed=1)
+    >>> len(trio.hap_a), len(trio.reads), len(trio.reads[0].read)
+    (1000, 20, 100)
+
+    Args:
+        genome_len: the length of each haplotype
+        het_rate: the probability of a heterozygous site, where the two
+            haplotypes have different bases, at each position
+        read_len: the length of each read
+        coverage: the total length of the reads over `genome_len`
+        error_rate: the probability of a sequencing error at each base
+        seed: a seed for the random number generator, to simulate the
+            same data every time
+        k: the size of the hap-mers
+
+    Returns:
+        the haplotypes, their hap-mers and the reads, with their truth
+
+    Raises:
+        ValueError: if k is out of range, a rate isn't between 0 and 1,
+            or the reads are longer than the haplotypes or shorter than k
+    """
+    if not kmers.MIN_K <= k <= kmers.MAX_K:
+        raise ValueError(f"k must be from {kmers.MIN_K} to {kmers.MAX_K}, not {k}")
+    for name, rate in [("het_rate", het_rate), ("error_rate", error_rate)]:
+        if not 0 <= rate <= 1:
+            raise ValueError(f"{name} must be between 0 and 1, not {rate}")
+    if not k <= read_len <= genome_len:
+        raise ValueError(
+            f"read_len must be from k ({k}) to genome_len ({genome_len}), not "
+            f"{read_len}"
+        )
+    if coverage < 0:
+        raise ValueError(f"coverage must not be negative, not {coverage}")
+
+    rng = random.Random(seed)
+    hap_a = "".join(rng.choices(BASES, k=genome_len))
+    hap_b = _substitute(rng, hap_a, het_rate)
+    hap_a_kmers, hap_b_kmers = count.hap_specific(
+        count.count_kmers([seq.Read("hapA", hap_a)], k),
+        count.count_kmers([seq.Read("hapB", hap_b)], k),
+        k,
+        1,
+    )
+
+    haplotypes = {Bin.HAP_A: hap_a, Bin.HAP_B: hap_b}
+    quality = chr(seq.PHRED_OFFSET + _phred(error_rate)) * read_len
+    reads = []
+    for i in range(round(genome_len * coverage / read_len)):
+        truth = rng.choice([Bin.HAP_A, Bin.HAP_B])
+        start = rng.randint(0, genome_len - read_len)
+        reverse = rng.random() < 0.5
+        read = seq.Read(
+            f"read{i + 1}",
+            haplotypes[truth][start : start + read_len],
+            quality,
+            f"hap={truth.value} start={start} strand={'-' if reverse else '+'}",
+        )
+        if reverse:
+            read = replace(read.reverse_complement(), name=read.name)
+        read.seq = _substitute(rng, read.seq, error_rate)
+        reads.append(SimulatedRead(read, truth, start, reverse))
+    return SimulatedTrio(hap_a, hap_b, hap_a_kmers, hap_b_kmers, reads)
+
+
+def _phred(error_rate: float) -> int:
+    """The Phred quality score of an error rate, from 0 to 93"""
+    if error_rate <= 0:
+        return 93
+    return max(0, min(93, round(-10 * math.log10(error_rate))))
@@ -0,0 +1,67 @@
+/*
+ * Classifies sequences through trio_binning.h, for test_ffi.py to check
+ * that a program can link against the C interface.
+ *
+ * Usage: ffi_classify HAP_A_KMERS HAP_B_KMERS SEQ...
+ *
+ * Prints the bin, counts and scores of each sequence, tab-separated. A - for
+ * HAP_A_KMERS is an empty set, with the k of HAP_B_KMERS.
+ */
+#include <stdio.h>
+#include <string.h>
+
+#include "trio_binning.h"
+
+int main(int argc, char** argv) {
+    tb_kmerset* hap_a;
+    tb_kmerset* hap_b;
+    tb_result result;
+    int i, status;
+
+    if (argc < 3) {
+        fprintf(stderr, "usage: %s HAP_A_KMERS HAP_B_KMERS SEQ...\n", argv[0]);
+        return 2;
+    }
+    hap_b = tb_kmerset_load(argv[2]);
+    if (strcmp(argv[1], "-") == 0) {
+        hap_a = tb_kmerset_new(tb_kmerset_k(hap_b));
+    } else {
+        hap_a = tb_kmerset_load(argv[1]);
+    }
+    if (hap_a == NULL || hap_b == NULL) {
+        fprintf(stderr, "could not load the k-mers\n");
+        return 1;
+    }
+    if (tb_classify_seq(hap_a, NULL, "ACGT", 4, &result) != TB_ERROR_NULL) {
+        fprintf(stderr, "a NULL k-mer set wasn't an error\n");
+        return 1;
+    }
+    if (tb_kmerset_add(hap_a, "ACGN", 4) != TB_ERROR_INVALID_KMER) {
+        fprintf(stderr, "an invalid k-mer wasn't an error\n");
+        return 1;
+    }
+    printf("k\t%d\t%zu\t%zu\n", tb_kmerset_k(hap_a), tb_kmerset_size(hap_a),
+           tb_kmerset_size(hap_b));
+
+    for (i = 3; i < argc; i++) {
+        status = tb_classify_seq(hap_a, hap_b, argv[i], strlen(argv[i]), &result);
+        if (status != TB_OK) {
+            fprintf(stderr, "could not classify %s: %d\n", argv[i], status);
+            return 1;
+        }
+        printf(
+            "%c\t%d\t%d\t%d\t%d\t%.17g\t%.17g\n",
+            result.bin,
+            result.hap_a_count,
+            result.hap_b_count,
+            result.num_kmers,
+            result.num_skipped,
+            result.hap_a_score,
+            result.hap_b_score
+        );
+    }
+
+    tb_kmerset_free(hap_a);
+    tb_kmerset_free(hap_b);
+    return 0;
+}
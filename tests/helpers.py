@@ -0,0 +1,12 @@
+"""Things shared by more than one test file"""
+
+from typing import NamedTuple, Optional
+
+
+class OtherRecord(NamedTuple):
+    """A record type from some other library"""
+
+    id: str
+    desc: Optional[str]
+    seq: str
+    qual: Optional[str]
@@ -0,0 +1,112 @@
+import os
+import random
+
+import pytest
+
+from trio_binning import seq
+from trio_binning.adapters import AdapterMasker
+
+ADAPTER = "AGATCGGAAGAGCACACGTCTGAACTCCAGTCAC"
+"""The TruSeq read 1 adapter, longer than one 32-base chunk"""
+
+
+def random_seq(rng, length):
+    return "".join(rng.choices("ACGT", k=length))
+
+
+def reverse_complement(sequence):
+    return seq.Read("read", sequence).reverse_complement().seq
+
+
+def test_find_adapters():
+    rng = random.Random(1)
+    insert = random_seq(rng, 400)
+    masker = AdapterMasker([ADAPTER])
+    assert masker.find(insert) == []
+    # at the start, the end, reverse complemented, and with a mismatch
+    assert masker.find(ADAPTER + insert) == [(0, len(ADAPTER))]
+    end = len(insert) + len(ADAPTER)
+    assert masker.find(insert + ADAPTER) == [(len(insert), end)]
+    assert masker.find(insert + reverse_complement(ADAPTER)) == [(len(insert), end)]
+    mismatched = ADAPTER[:20] + ("A" if ADAPTER[20] != "A" else "C") + ADAPTER[21:]
+    assert masker.find(mismatched + insert) == [(0, len(ADAPTER))]
+    assert AdapterMasker([ADAPTER], max_mismatches=0).find(mismatched + insert) == []
+    # an N is a mismatch
+    assert masker.find(ADAPTER[:32] + "N" + ADAPTER[33:] + insert) == [
+        (0, len(ADAPTER))
+    ]
+    assert masker.find("N" + ADAPTER[1:-1] + "N" + insert) == []
+    # two mismatches
+    twice = ADAPTER[:3] + "C" + ADAPTER[4:30] + "A" + ADAPTER[31:]
+    assert masker.find(twice + insert) == []
+
+
+def test_find_adapters_search_length():
+    rng = random.Random(2)
+    read = random_seq(rng, 200) + ADAPTER + random_seq(rng, 200)
+    start = 200
+    assert AdapterMasker([ADAPTER]).find(read) == []
+    assert AdapterMasker([ADAPTER], search_length=0).find(read) == [
+        (start, start + len(ADAPTER))
+    ]
+    # found if it overlaps the first or last search length bases at all
+    assert AdapterMasker([ADAPTER], search_length=201).find(read) == [
+        (start, start + len(ADAPTER))
+    ]
+    assert AdapterMasker([ADAPTER], search_length=200).find(read) == []
+    tail_overlap = len(read) - start - len(ADAPTER) + 1
+    assert AdapterMasker([ADAPTER], search_length=tail_overlap).find(read) == [
+        (start, start + len(ADAPTER))
+    ]
+    assert AdapterMasker([ADAPTER], search_length=tail_overlap - 1).find(read) == []
+    # reads shorter than the adapter have none
+    assert AdapterMasker([ADAPTER]).find(ADAPTER[:-1]) == []
+
+
+def test_mask_adapters():
+    masker = AdapterMasker(["ACGTACGT", "GGATCCTA"])
+    # ACGTACGT is its own reverse complement, so it's only looked for once
+    assert [adapter.sequence for adapter in masker.adapters] == [
+        "ACGTACGT",
+        "GGATCCTA",
+        "TAGGATCC",
+    ]
+    masked, num_masked = masker.mask("ccACGTACGTtaggatcccGN")
+    assert masked == "cc" + "N" * 16 + "cGN"
+    assert num_masked == 16
+    # overlapping occurrences, and N's already there, aren't counted twice
+    masked, num_masked = masker.mask("ACGTACGTGGATCCTANNN")
+    assert masked == "N" * 19
+    assert num_masked == 16
+    assert masker.mask("TTTTTTTTTTTT") == ("TTTTTTTTTTTT", 0)
+
+
+@pytest.mark.parametrize(
+    "adapters,search_length,max_mismatches,message",
+    [
+        ([], 150, 1, "no adapters"),
+        (["ACGTACG"], 150, 1, "shorter than 8"),
+        (["ACGTNACGT"], 150, 1, "other than ACGT"),
+        ([ADAPTER], -1, 1, "search length must not be negative"),
+        ([ADAPTER], 150, 2, "must be 0 or 1"),
+    ],
+)
+def test_adapter_masker_invalid(adapters, search_length, max_mismatches, message):
+    with pytest.raises(ValueError, match=message):
+        AdapterMasker(adapters, search_length, max_mismatches)
+
+
+def test_adapter_masker_from_file(tmpdir):
+    adapters_path = os.path.join(tmpdir, "adapters.fa")
+    with open(adapters_path, "w") as fp:
+        fp.write(f">truseq\n{ADAPTER.lower()}\n>short\nACGTAC\n")
+    with pytest.raises(seq.SeqError, match="adapters.fa: adapter 'ACGTAC' is short"):
+        AdapterMasker.from_file(adapters_path)
+    with open(adapters_path, "w") as fp:
+        fp.write(f">truseq\n{ADAPTER[:20]}\n{ADAPTER[20:]}\n")
+    masker = AdapterMasker.from_file(adapters_path, 50, 0)
+    assert [adapter.sequence for adapter in masker.adapters] == [
+        ADAPTER,
+        reverse_complement(ADAPTER),
+    ]
+    assert (masker.search_length, masker.max_mismatches) == (50, 0)
@@ -0,0 +1,65 @@
+import gzip
+import io
+import os
+import random
+
+import pytest
+
+from trio_binning import bgzf, seq
+
+
+def test_compress_block():
+    block = bgzf.compress_block(b"ACGT" * 100)
+    # the BC extra field, with the size of the block minus 1
+    assert block[12:16] == b"BC\x02\x00"
+    assert int.from_bytes(block[16:18], "little") == len(block) - 1
+    assert gzip.decompress(block) == b"ACGT" * 100
+    assert bgzf.compress_block(b"") == bgzf.EOF_BLOCK
+
+    with pytest.raises(ValueError):
+        bgzf.compress_block(bytes(bgzf.BLOCK_SIZE + 1))
+
+
+@pytest.mark.parametrize("threads", [1, 3])
+def test_bgzf_writer(tmpdir, threads):
+    rng = random.Random(76)
+    # random bases, so the blocks don't compress to almost nothing, in
+    # writes of all sizes
+    data = "".join(rng.choices("ACGT", k=5 * bgzf.BLOCK_SIZE + 123)).encode()
+    out_path = os.path.join(tmpdir, "out.bgz")
+    with bgzf.BgzfWriter(out_path, threads=threads) as writer:
+        start = 0
+        while start < len(data):
+            end = start + rng.randint(1, 2 * bgzf.BLOCK_SIZE)
+            writer.write(data[start:end])
+            start = end
+
+    with open(out_path, "rb") as fp:
+        assert bgzf.check(fp) == 7
+    with gzip.open(out_path, "rb") as fp:
+        assert fp.read() == data
+
+
+def test_bgzf_writer_flush_and_append(tmpdir):
+    out_path = os.path.join(tmpdir, "out.fa.bgz")
+    with seq.open_text_write(out_path) as fp:
+        seq.Read("read1", "ACGT").print(file=fp)
+        length = seq.sync_outfile(fp)
+        seq.Read("read2", "ACGT").print(file=fp)
+
+    os.truncate(out_path, length)
+    with seq.open_text_write(out_path, append=True) as fp:
+        seq.Read("read3", "ACGT").print(file=fp)
+    with open(out_path, "rb") as fp:
+        assert bgzf.check(fp) == 3
+    with seq.open_text_read(out_path) as fp:
+        assert [read.name for read in seq.readfq(fp)] == ["read1", "read3"]
+
+
+def test_check_invalid():
+    with pytest.raises(bgzf.BgzfError, match="no BC field"):
+        bgzf.check(io.BytesIO(gzip.compress(b"ACGT")))
+    with pytest.raises(bgzf.BgzfError, match="end-of-file block"):
+        bgzf.check(io.BytesIO(bgzf.compress_block(b"ACGT")))
+    with pytest.raises(bgzf.BgzfError, match="cut off"):
+        bgzf.check(io.BytesIO(bgzf.compress_block(b"ACGT")[:-1]))
@@ -0,0 +1,137 @@
+import gzip
+import os
+
+import pytest
+
+from trio_binning import seq
+from trio_binning.checkpoint import (
+    Checkpoint,
+    CheckpointError,
+    Checkpointer,
+    CountedIterator,
+    parse_duration,
+    skip_done,
+)
+from trio_binning.classify import Summary
+
+
+@pytest.mark.parametrize(
+    "duration,seconds", [("30", 30), ("30s", 30), ("10m", 600), ("1.5h", 5400)]
+)
+def test_parse_duration(duration, seconds):
+    assert parse_duration(duration) == seconds
+
+
+@pytest.mark.parametrize("duration", ["", "0m", "-1s", "10d", "m"])
+def test_parse_duration_invalid(duration):
+    with pytest.raises(ValueError, match="isn't a positive duration"):
+        parse_duration(duration)
+
+
+def test_checkpoint_save_load(tmpdir):
+    summary = Summary()
+    summary.skip(3)
+    checkpoint_path = os.path.join(tmpdir, "checkpoint")
+    Checkpoint(["reads.fq"], 0, 3, {"out.fa": 10}, summary).save(checkpoint_path)
+    checkpoint = Checkpoint.load(checkpoint_path)
+    assert checkpoint.reads == ["reads.fq"]
+    assert (checkpoint.file_index, checkpoint.records) == (0, 3)
+    assert checkpoint.outputs == {"out.fa": 10}
+    assert checkpoint.summary.to_dict() == summary.to_dict()
+    assert not os.path.exists(checkpoint_path + ".tmp")
+
+
+def test_checkpoint_load_invalid(tmpdir):
+    checkpoint_path = os.path.join(tmpdir, "checkpoint")
+    with open(checkpoint_path, "w") as fp:
+        fp.write("not a checkpoint\n")
+    with pytest.raises(CheckpointError, match="isn't a checkpoint"):
+        Checkpoint.load(checkpoint_path)
+
+
+def test_checkpoint_truncate_outputs(tmpdir):
+    out_path = os.path.join(tmpdir, "out.fa")
+    with open(out_path, "w") as fp:
+        fp.write(">read1\nACGT\n>read2\nAC")
+    checkpoint = Checkpoint([], 0, 1, {out_path: 12}, Summary())
+    checkpoint.truncate_outputs()
+    with open(out_path) as fp:
+        assert fp.read() == ">read1\nACGT\n"
+
+    checkpoint.outputs = {out_path: 20}
+    with pytest.raises(CheckpointError, match="shorter"):
+        checkpoint.truncate_outputs()
+    os.remove(out_path)
+    with pytest.raises(CheckpointError, match="is gone"):
+        checkpoint.truncate_outputs()
+
+
+def test_checkpoint_records_done():
+    checkpoint = Checkpoint(["1.fq", "2.fq", "3.fq"], 1, 5, {}, Summary())
+    assert [checkpoint.records_done(i) for i in range(3)] == [None, 5, 0]
+
+
+def test_skip_done():
+    summary = Summary()
+    summary.malformed = 2
+    seen = seq.IdSet()
+    reads = iter([seq.Read(f"read{i}", "ACGT") for i in range(5)])
+    rest = skip_done(reads, 3, lambda read: read.name, seen, summary)
+    assert [read.name for read in rest] == ["read3", "read4"]
+    assert len(seen) == 3 and "read2" in seen
+    assert summary.malformed == 2
+
+    reads = iter([seq.Read("read1", "ACGT")])
+    assert list(skip_done(reads, None, lambda read: read.name, None, summary)) == []
+
+
+def test_counted_iterator():
+    counted = CountedIterator("abc", 10)
+    assert next(counted) == "a"
+    assert counted.count == 11
+    assert list(counted) == ["b", "c"]
+    assert counted.count == 13
+
+
+def test_checkpointer_segments():
+    now = 0.0
+    checkpointer = Checkpointer("checkpoint", 10, [], Summary(), lambda: now)
+    segments = checkpointer.segments(range(7))
+    taken = []
+    for segment in segments:
+        for item in segment:
+            taken.append(item)
+            # a checkpoint is due after every third item
+            if item % 3 == 2:
+                now += 10
+        taken.append("checkpoint")
+        checkpointer._last_time = now
+    assert taken == [0, 1, 2, "checkpoint", 3, 4, 5, "checkpoint", 6, "checkpoint"]
+
+
+def test_checkpointer_segments_take_one_item():
+    # even if a checkpoint is always due, each segment still gets somewhere
+    checkpointer = Checkpointer("checkpoint", 1, [], Summary(), lambda: 0)
+    checkpointer.interval = -1
+    assert [list(segment) for segment in checkpointer.segments("ab")] == [
+        ["a"],
+        ["b"],
+        [],
+    ]
+
+
+def test_checkpointer_save(tmpdir):
+    checkpoint_path = os.path.join(tmpdir, "checkpoint")
+    out_path = os.path.join(tmpdir, "out.fa.gz")
+    checkpointer = Checkpointer(checkpoint_path, 60, ["reads.fq"], Summary())
+    with seq.open_text_write(out_path) as out:
+        checkpointer.add_output(out)
+        print(">read1\nACGT", file=out)
+        checkpointer.save(0, 1)
+        print(">read2\nACGT", file=out)
+
+    checkpoint = Checkpoint.load(checkpoint_path)
+    assert checkpoint.records == 1
+    checkpoint.truncate_outputs()
+    with gzip.open(out_path, "rt") as fp:
+        assert fp.read() == ">read1\nACGT\n"
@@ -0,0 +1,1035 @@
+import io
+import json
+import os
+import random
+from concurrent.futures import ThreadPoolExecutor
+from itertools import islice
+
+import pytest
+from helpers import OtherRecord
+
+from trio_binning import kmers
+from trio_binning.adapters import AdapterMasker
+from trio_binning.classify import (
+    Bin,
+    Classification,
+    Classifier,
+    ClassifyOptions,
+    Progress,
+    ScoreHistogram,
+    ScoreMode,
+    Summary,
+    SwitchEvent,
+    calculate_scaling_factors,
+    classify_by_window,
+    classify_pair,
+    classify_reads,
+    classify_seq,
+    find_switches,
+    profile,
+    rescue,
+)
+from trio_binning.seq import Read
+
+
+def make_kmer_set(k, kmer_strings):
+    kmer_set = kmers.KmerSet(k)
+    for kmer in kmer_strings:
+        kmer_set.add(kmers.kmer_to_int(kmer))
+    return kmer_set
+
+
+@pytest.fixture
+def hap_sets():
+    return (
+        make_kmer_set(5, ["AAAAC", "CCCCG"]),
+        make_kmer_set(5, ["GGGGT", "TTTTA"]),
+    )
+
+
+@pytest.mark.parametrize(
+    "seq,hap_a_count,hap_b_count,read_bin",
+    [
+        ("AAAACGTCCCCG", 2, 0, Bin.HAP_A),
+        # reverse complement of GGGGT
+        ("ACCCC", 0, 1, Bin.HAP_B),
+        ("AAAACTTTTA", 1, 1, Bin.UNKNOWN),
+        ("AAAACTTTTAGGGGT", 1, 2, Bin.HAP_B),
+        ("ACGTACGT", 0, 0, Bin.UNKNOWN),
+        ("AAAA", 0, 0, Bin.UNKNOWN),
+        ("NNNNNNNNNNNN", 0, 0, Bin.UNKNOWN),
+        ("AAAACNGTCCCCGN", 2, 0, Bin.HAP_A),
+        ("", 0, 0, Bin.UNKNOWN),
+    ],
+)
+def test_classify_seq(hap_sets, seq, hap_a_count, hap_b_count, read_bin):
+    classification = classify_seq(seq, *hap_sets)
+    assert classification.hap_a_count == hap_a_count
+    assert classification.hap_b_count == hap_b_count
+    assert classification.bin == read_bin
+
+
+@pytest.mark.parametrize(
+    "options,seq,read_bin",
+    [
+        (ClassifyOptions(min_hits=2), "AAAAC", Bin.UNKNOWN),
+        (ClassifyOptions(min_hits=2), "AAAACGCCCCG", Bin.HAP_A),
+        (ClassifyOptions(min_margin=2), "AAAACGCCCCGATTTTA", Bin.UNKNOWN),
+        (ClassifyOptions(min_margin=2), "AAAACGCCCCG", Bin.HAP_A),
+        (ClassifyOptions(min_ratio=0.7), "AAAACGCCCCGATTTTA", Bin.UNKNOWN),
+        (ClassifyOptions(min_ratio=0.7), "AAAACGCCCCGAAAACTTTTA", Bin.HAP_A),
+        (ClassifyOptions(min_ratio=0.7), "ACGTACGT", Bin.UNKNOWN),
+        (ClassifyOptions(), "AAAACGCCCCGATTTTA", Bin.HAP_A),
+        (ClassifyOptions(), "aaaacgccccgatttta", Bin.HAP_A),
+        (ClassifyOptions(ignore_softmasked=True), "aaaacgccccgatttta", Bin.UNKNOWN),
+        (ClassifyOptions(ignore_softmasked=True), "aaaacgccccgaTTTTA", Bin.HAP_B),
+    ],
+)
+def test_classify_seq_options(hap_sets, options, seq, read_bin):
+    assert classify_seq(seq, *hap_sets, options=options).bin == read_bin
+
+
+@pytest.mark.parametrize(
+    "kwargs,message",
+    [
+        ({"min_hits": -1}, "min_hits"),
+        ({"min_margin": -0.5}, "min_margin"),
+        ({"min_ratio": 1.5}, "min_ratio"),
+        ({"min_base_quality": 94}, "min_base_quality"),
+        ({"weight_cap": 0}, "weight_cap"),
+        ({"min_hits_per_kb": -0.1}, "min_hits_per_kb"),
+        ({"fuzzy_weight": 0}, "fuzzy_weight"),
+        ({"fuzzy_weight": 1.5}, "fuzzy_weight"),
+        ({"fuzzy": True, "score": ScoreMode.WEIGHTED}, "weighted"),
+        ({"minimizer_window": 0}, "minimizer_window"),
+        ({"minimizer_window": 5, "fuzzy": True}, "minimizers"),
+        ({"seed_pattern": "1100"}, "start and end with a 1"),
+        ({"seed_pattern": "101", "fuzzy": True}, "seed pattern can't be combined"),
+        ({"seed_pattern": "101", "minimizer_window": 5}, "seed pattern"),
+        ({"seed_pattern": "101", "score": ScoreMode.WEIGHTED}, "seed pattern"),
+        ({"syncmer_size": 0}, "syncmer_size must be from 1 to 31, not 0"),
+        ({"syncmer_size": 5, "minimizer_window": 5}, "syncmers can't be combined"),
+        ({"syncmer_size": 5, "kmer_step": 2}, "syncmers can't be combined"),
+        ({"syncmer_size": 5, "early_exit_margin": 5}, "syncmers can't be combined"),
+    ],
+)
+def test_classify_options_invalid(kwargs, message):
+    with pytest.raises(ValueError, match=message):
+        ClassifyOptions(**kwargs)
+
+
+@pytest.mark.parametrize(
+    "options",
+    [
+        ClassifyOptions(),
+        ClassifyOptions(min_hits=3, min_ratio=0.7, score=ScoreMode.WEIGHTED),
+        ClassifyOptions(minimizer_window=10, early_exit_max_other=2),
+        ClassifyOptions(seed_pattern="1110110111"),
+        ClassifyOptions(syncmer_size=11),
+    ],
+)
+def test_classify_options_round_trip(options):
+    options_dict = json.loads(json.dumps(options.to_dict()))
+    assert ClassifyOptions.from_dict(options_dict) == options
+
+
+def test_classify_options_from_dict():
+    options = ClassifyOptions.from_dict({"min_margin": 2, "score": "weighted"})
+    assert options == ClassifyOptions(min_margin=2.0, score=ScoreMode.WEIGHTED)
+    assert isinstance(options.min_margin, float)
+    assert "minimizer_window" not in options.to_dict()
+
+
+@pytest.mark.parametrize(
+    "options_dict,message",
+    [
+        ({"min_hit": 2}, "unknown classifyoptions fields: min_hit"),
+        ({"min_hits": "2"}, "min_hits must be of type int"),
+        ({"min_hits": True}, "min_hits must be of type int"),
+        ({"min_margin": False}, "min_margin must be of type float"),
+        ({"fuzzy": 1}, "fuzzy must be of type bool"),
+        ({"score": "max"}, "score must be one of count, weighted"),
+        ({"min_ratio": 2}, "min_ratio must be between 0 and 1"),
+        ({"seed_pattern": 101}, "seed_pattern must be of type str"),
+    ],
+)
+def test_classify_options_from_dict_invalid(options_dict, message):
+    with pytest.raises(ValueError, match=message):
+        ClassifyOptions.from_dict(options_dict)
+
+
+def test_classification_round_trip():
+    classification = Classification(3, 1, Bin.HAP_A, 3.0, 1.5, 10, 2, True)
+    classification_dict = json.loads(json.dumps(classification.to_dict()))
+    assert classification_dict["bin"] == "A"
+    assert Classification.from_dict(classification_dict) == classification
+
+    del classification_dict["num_kmers"]
+    with pytest.raises(ValueError, match="missing classification fields"):
+        Classification.from_dict(classification_dict)
+
+
+def test_classify_seq_skipped(hap_sets):
+    classification = classify_seq("AAAACNGTCCCCGN", *hap_sets)
+    assert classification.num_kmers == 4
+    assert classification.num_skipped == 6
+    assert classification.skipped_fraction == pytest.approx(0.6)
+
+    classification = classify_seq("NNNNNNNN", *hap_sets)
+    assert classification.num_kmers == 0
+    assert classification.skipped_fraction == 1
+
+    # a character that's more than one byte in UTF-8 is still one base
+    classification = classify_seq("AAAAC\u20acGTCCCCG\u20ac", *hap_sets)
+    assert (classification.num_kmers, classification.num_skipped) == (4, 6)
+
+
+def test_classify_seq_fuzzy():
+    hap_a = make_kmer_set(11, ["ACGGATCCTAG", "CTTAGCAGGCA"])
+    hap_b = make_kmer_set(11, ["GTCACTGAATC"])
+    # each hap-mer has one error, so none of them is an exact hit
+    seq = "ACGGAACCTAG" + "CTTAGCTGGCA" + "GTCACAGAATC"
+    assert classify_seq(seq, hap_a, hap_b).bin == Bin.UNKNOWN
+
+    options = ClassifyOptions(fuzzy=True)
+    classification = classify_seq(seq, hap_a, hap_b, options=options)
+    assert classification.bin == Bin.HAP_A
+    assert classification.hap_a_count == 2
+    assert classification.hap_b_count == 1
+    assert classification.hap_a_score == pytest.approx(2 * 0.5)
+    assert classification.hap_b_score == pytest.approx(0.5)
+
+    # exact hits aren't down-weighted
+    options = ClassifyOptions(fuzzy=True, fuzzy_weight=0.25)
+    classification = classify_seq("ACGGATCCTAG" + seq, hap_a, hap_b, options=options)
+    assert classification.hap_a_count == 3
+    assert classification.hap_a_score == pytest.approx(1 + 2 * 0.25)
+
+
+def test_classify_seq_minimizers():
+    rng = random.Random(43)
+    hap_a_seq = "".join(rng.choices("ACGT", k=5000))
+    hap_b_seq = "".join(rng.choices("ACGT", k=5000))
+    hap_a = make_kmer_set(15, (hap_a_seq[i : i + 15] for i in range(0, 4986, 3)))
+    hap_b = make_kmer_set(15, (hap_b_seq[i : i + 15] for i in range(0, 4986, 3)))
+
+    classification = classify_seq(hap_a_seq, hap_a, hap_b)
+    options = ClassifyOptions(minimizer_window=10)
+    minimizer_classification = classify_seq(hap_a_seq, hap_a, hap_b, options=options)
+    assert minimizer_classification.bin == classification.bin == Bin.HAP_A
+    assert minimizer_classification.num_kmers == len(
+        list(kmers.minimizers(hap_a_seq, 15, 10))
+    )
+    assert minimizer_classification.num_kmers < classification.num_kmers / 4
+    # a third of the k-mers are hap-mers, whether they're minimizers or not
+    assert minimizer_classification.hap_a_fraction == pytest.approx(1 / 3, abs=0.1)
+
+
+def test_classify_seq_kmer_step(hap_sets):
+    seq = "AAAAC" + "N" + "GGGGT" + "A" + "CCCCG"
+    classification = classify_seq(seq, *hap_sets)
+    assert classification.num_kmers == 8
+    assert classification.num_skipped == 5
+    # the k-mers at 0, 3, 6, 9 and 12, less ACNGG at 3
+    options = ClassifyOptions(kmer_step=3)
+    classification = classify_seq(seq, *hap_sets, options=options)
+    assert (classification.hap_a_count, classification.hap_b_count) == (2, 1)
+    assert classification.num_kmers == 4
+    assert classification.num_skipped == 1
+    assert classification.bin == Bin.HAP_A
+    # a read too short for any k-mers has none, whatever the step
+    classification = classify_seq("AAAA", *hap_sets, options=options)
+    assert classification.num_kmers == 0
+    assert classify_seq("AAAAC", *hap_sets, options=options).num_kmers == 1
+
+
+@pytest.mark.parametrize(
+    "kwargs,message",
+    [
+        ({"kmer_step": 0}, "kmer_step must be at least 1"),
+        ({"kmer_step": 2, "minimizer_window": 5}, "can't be combined"),
+    ],
+)
+def test_classify_options_kmer_step_invalid(kwargs, message):
+    with pytest.raises(ValueError, match=message):
+        ClassifyOptions(**kwargs)
+
+
+def mutate(rng, seq, error_rate):
+    return "".join(
+        rng.choice("ACGT") if rng.random() < error_rate else base for base in seq
+    )
+
+
+def test_classify_seq_early_exit():
+    rng = random.Random(68)
+    haplotypes = {
+        Bin.HAP_A: "".join(rng.choices("ACGT", k=50000)),
+        Bin.HAP_B: "".join(rng.choices("ACGT", k=50000)),
+    }
+    # one in ten k-mers of each haplotype is a hap-mer
+    hap_a, hap_b = (
+        make_kmer_set(21, (seq[i : i + 21] for i in range(0, len(seq) - 20, 10)))
+        for seq in haplotypes.values()
+    )
+    options = ClassifyOptions(min_hits=5)
+    early_exit_options = ClassifyOptions(min_hits=5, early_exit_margin=50)
+
+    num_early_exits = 0
+    for _ in range(200):
+        read_bin = rng.choice([Bin.HAP_A, Bin.HAP_B])
+        length = rng.randint(100, 20000)
+        start = rng.randint(0, 50000 - length)
+        read = mutate(rng, haplotypes[read_bin][start : start + length], 0.01)
+        full = classify_seq(read, hap_a, hap_b, options=options)
+        early = classify_seq(read, hap_a, hap_b, options=early_exit_options)
+        assert early.bin == full.bin == read_bin
+        assert early.hap_a_count <= full.hap_a_count
+        assert early.hap_b_count <= full.hap_b_count
+        if early.early_exit:
+            num_early_exits += 1
+            assert early.num_kmers < full.num_kmers
+        else:
+            assert early == full
+    assert num_early_exits > 100
+
+
+def test_classify_seq_seed_pattern():
+    rng = random.Random(86)
+    seed = kmers.SpacedSeed("1111011110111101111")
+    haplotypes = {
+        read_bin: "".join(rng.choices("ACGT", k=5000))
+        for read_bin in [Bin.HAP_A, Bin.HAP_B]
+    }
+    hap_a, hap_b = kmers.KmerSet(16), kmers.KmerSet(16)
+    for kmer_set, seq in zip([hap_a, hap_b], haplotypes.values()):
+        for kmer_int in kmers.spaced_kmer_array(seq, seed)[::10]:
+            kmer_set.add(kmer_int)
+    options = ClassifyOptions(seed_pattern=str(seed))
+    early_exit_options = ClassifyOptions(seed_pattern=str(seed), early_exit_margin=5)
+
+    for read_bin, seq in haplotypes.items():
+        read = mutate(rng, seq[1000:3000], 0.02)
+        classification = classify_seq(read, hap_a, hap_b, options=options)
+        assert classification.bin == read_bin
+        assert classification.num_kmers == len(read) - seed.span + 1
+        early = classify_seq(read, hap_a, hap_b, options=early_exit_options)
+        assert early.bin == read_bin
+        assert early.early_exit
+
+    # the sets' k must be the seed's weight
+    with pytest.raises(ValueError, match="makes 16-mers, but the k-mers are 5-mers"):
+        classify_seq(
+            "ACGTACGTACGT", make_kmer_set(5, []), make_kmer_set(5, []), options=options
+        )
+
+
+def test_classify_seq_syncmers():
+    rng = random.Random(87)
+    haplotypes = {
+        read_bin: "".join(rng.choices("ACGT", k=5000))
+        for read_bin in [Bin.HAP_A, Bin.HAP_B]
+    }
+    hap_a, hap_b = (
+        make_kmer_set(21, (seq[i : i + 21] for i in range(0, len(seq) - 20, 3)))
+        for seq in haplotypes.values()
+    )
+    options = ClassifyOptions(syncmer_size=11)
+    for read_bin, seq in haplotypes.items():
+        read = seq[1000:3000]
+        classification = classify_seq(read, hap_a, hap_b, options=options)
+        assert classification.bin == read_bin
+        # about 2 / 11 of the k-mers are looked up, and a third of those hit
+        assert classification.num_kmers == len(list(kmers.syncmers(read, 21, 11)))
+        assert 0.15 < classification.num_kmers / (len(read) - 20) < 0.22
+        assert classification.hap_a_count + classification.hap_b_count == pytest.approx(
+            classification.num_kmers / 3, rel=0.3
+        )
+
+    with pytest.raises(ValueError, match="less than k, 21, not 21"):
+        Classifier(hap_a, hap_b, ClassifyOptions(syncmer_size=21))
+
+
+def test_classify_seq_early_exit_unknown(hap_sets):
+    # both haplotypes have 120 hits, with their reverse complements, so the
+    # read is never certain
+    seq = ("AAAAC" + "GGGGT") * 60 + "A" * 2000
+    options = ClassifyOptions(early_exit_margin=50)
+    classification = classify_seq(seq, *hap_sets, options=options)
+    assert classification.bin == Bin.UNKNOWN
+    assert not classification.early_exit
+    assert classification == classify_seq(seq, *hap_sets)
+
+    # only haplotype A has hits, but the first mate stops early
+    seq = "AAAAC" * 300
+    classification = classify_pair(seq, "CCCCG", *hap_sets, options=options)
+    assert classification.bin == Bin.HAP_A
+    assert classification.early_exit
+    assert classification.hap_a_count == 201
+
+
+@pytest.mark.parametrize(
+    "scaling_factors,early_exit,read_bin",
+    [
+        ((1.0, 1.0), True, Bin.HAP_A),
+        # B's 4 hits are scaled up to 120 against A's 150, too close to stop
+        ((1.0, 30.0), False, Bin.HAP_A),
+        # or to 180, which puts the read in B even though A has more hits
+        ((1.0, 45.0), False, Bin.HAP_B),
+    ],
+)
+def test_classify_seq_early_exit_scaled(
+    hap_sets, scaling_factors, early_exit, read_bin
+):
+    seq = "GGGGT" * 3 + "AAAAC" * 150 + "A" * 3000
+    options = ClassifyOptions(early_exit_margin=50)
+    classification = classify_seq(seq, *hap_sets, scaling_factors, options)
+    assert classification.early_exit == early_exit
+    assert classification.bin == read_bin
+    assert (classification.hap_a_count, classification.hap_b_count) == (150, 4)
+
+
+@pytest.mark.parametrize(
+    "kwargs,message",
+    [
+        ({"early_exit_margin": 0}, "early_exit_margin must be at least 1"),
+        ({"early_exit_max_other": -1}, "early_exit_max_other must not be negative"),
+        ({"early_exit_margin": 50, "minimizer_window": 5}, "can't be combined"),
+    ],
+)
+def test_classify_options_early_exit_invalid(kwargs, message):
+    with pytest.raises(ValueError, match=message):
+        ClassifyOptions(**kwargs)
+
+
+def test_profile(hap_sets):
+    # 0         1
+    # 012345678901234567
+    # AAAACNNGGGGTNCCCCG
+    seq = "AAAAC" + "NN" + "GGGGT" + "N" + "CCCCG"
+    assert profile(seq, *hap_sets) == [(0, Bin.HAP_A), (7, Bin.HAP_B), (13, Bin.HAP_A)]
+    # GTTTT is the reverse complement of AAAAC
+    assert profile("GTTTTA", *hap_sets) == [(0, Bin.HAP_A), (1, Bin.HAP_B)]
+    assert profile(seq.lower(), *hap_sets, ignore_softmasked=True) == []
+    assert profile("AAAA", *hap_sets) == []
+
+
+def test_classify_seq_min_hits_per_kb(hap_sets):
+    # the long read has the same density of hits as the short one, so only
+    # an absolute threshold treats them differently
+    short = "AAAAC" + "ACGT" * 5 + "G"
+    long = short * 4
+    short_classification = classify_seq(short, *hap_sets)
+    long_classification = classify_seq(long, *hap_sets)
+    assert short_classification.hap_a_per_kb == pytest.approx(1000 / 22)
+    assert long_classification.hap_a_per_kb == 40
+    assert long_classification.hap_b_per_kb == 0
+
+    options = ClassifyOptions(min_hits=2)
+    assert classify_seq(short, *hap_sets, options=options).bin == Bin.UNKNOWN
+    assert classify_seq(long, *hap_sets, options=options).bin == Bin.HAP_A
+    for min_hits_per_kb, read_bin in [(40, Bin.HAP_A), (41, Bin.UNKNOWN)]:
+        options = ClassifyOptions(min_hits_per_kb=min_hits_per_kb)
+        assert classify_seq(long, *hap_sets, options=options).bin == read_bin
+
+    # k-mers skipped for low-quality bases aren't counted in the denominator
+    quals = "I" * 10 + "#" * 11 + "I" * 83
+    options = ClassifyOptions(min_hits_per_kb=41, min_base_quality=20)
+    classification = classify_seq(long, *hap_sets, options=options, quals=quals)
+    assert classification.num_kmers == 85
+    assert classification.bin == Bin.HAP_A
+
+
+def test_rescue(hap_sets):
+    options = ClassifyOptions(min_hits=3)
+    rescue_options = ClassifyOptions(min_hits=1, min_margin=1)
+    for seq, read_bin, rescued in [
+        ("AAAACGCCCCG", Bin.HAP_A, True),
+        # one hit each, so even the relaxed thresholds can't split them
+        ("AAAACTTTTA", Bin.UNKNOWN, False),
+        ("ACGTACGT", Bin.UNKNOWN, False),
+    ]:
+        classification = classify_seq(seq, *hap_sets, options=options)
+        assert classification.bin == Bin.UNKNOWN
+        rescued_classification = rescue(classification, rescue_options)
+        assert rescued_classification.bin == read_bin
+        assert rescued_classification.rescued == rescued
+        assert rescued_classification.hap_a_count == classification.hap_a_count
+
+    # reads classified in the main pass are left alone
+    classification = classify_seq("ACCCC", *hap_sets)
+    assert rescue(classification, ClassifyOptions(min_hits=5)) is classification
+
+
+def test_classify_seq_min_base_quality():
+    # the read is GATTACAGGCT with a low-quality sequencing error at the
+    # eighth base, which makes two haplotype B k-mers
+    read = "GATTACATGCT"
+    quals = "IIIIIII#III"
+    hap_a = make_kmer_set(5, ["GATTA"])
+    hap_b = make_kmer_set(5, ["ACATG", "CATGC"])
+    assert classify_seq(read, hap_a, hap_b, quals=quals).bin == Bin.HAP_B
+
+    options = ClassifyOptions(min_base_quality=20)
+    classification = classify_seq(read, hap_a, hap_b, options=options, quals=quals)
+    assert classification.hap_a_count == 1
+    assert classification.hap_b_count == 0
+    assert classification.num_skipped == 4
+    assert classification.bin == Bin.HAP_A
+
+    # without qualities, nothing is filtered
+    assert classify_seq(read, hap_a, hap_b, options=options).bin == Bin.HAP_B
+
+
+def test_classify_seq_compress_homopolymers():
+    hap_a = make_kmer_set(5, ["GATAC", "ATACA"])
+    hap_b = make_kmer_set(5, ["GAGTC"])
+    # GATACA with homopolymer length errors
+    read = "GGATTTACAA"
+    assert classify_seq(read, hap_a, hap_b).bin == Bin.UNKNOWN
+
+    options = ClassifyOptions(compress_homopolymers=True)
+    classification = classify_seq(read, hap_a, hap_b, options=options)
+    assert classification.hap_a_count == 2
+    assert classification.num_kmers == 2
+    assert classification.bin == Bin.HAP_A
+
+
+def make_kmer_count_map(k, kmer_counts):
+    count_map = kmers.KmerCountMap(k)
+    for kmer, count in kmer_counts.items():
+        count_map.add(kmers.kmer_to_int(kmer), count)
+    return count_map
+
+
+def test_classify_seq_weighted():
+    # the only hap-A hits are k-mers seen once, which are probably errors
+    hap_a = make_kmer_count_map(5, {"AAAAC": 1, "CCCCG": 1, "ACACA": 1})
+    hap_b = make_kmer_count_map(5, {"GGGGT": 20, "TTTTA": 20})
+    seq = "AAAACGTCCCCGAGACACAGAGGGGTGATTTTA"
+
+    counted = classify_seq(seq, hap_a, hap_b)
+    assert (counted.hap_a_count, counted.hap_b_count) == (3, 2)
+    assert counted.bin == Bin.HAP_A
+
+    options = ClassifyOptions(score=ScoreMode.WEIGHTED)
+    weighted = classify_seq(seq, hap_a, hap_b, options=options)
+    assert (weighted.hap_a_count, weighted.hap_b_count) == (3, 2)
+    # each hap-B k-mer is capped at 10
+    assert (weighted.hap_a_score, weighted.hap_b_score) == (3.0, 20.0)
+    assert weighted.bin == Bin.HAP_B
+    options = ClassifyOptions(score=ScoreMode.WEIGHTED, weight_cap=1)
+    assert classify_seq(seq, hap_a, hap_b, options=options).bin == Bin.HAP_A
+
+    # min_hits is still in k-mers, and min_margin in weight
+    options = ClassifyOptions(min_hits=6, score=ScoreMode.WEIGHTED)
+    assert classify_seq(seq, hap_a, hap_b, options=options).bin == Bin.UNKNOWN
+    options = ClassifyOptions(min_margin=17, score=ScoreMode.WEIGHTED)
+    assert classify_seq(seq, hap_a, hap_b, options=options).bin == Bin.HAP_B
+    options = ClassifyOptions(min_margin=18, score=ScoreMode.WEIGHTED)
+    assert classify_seq(seq, hap_a, hap_b, options=options).bin == Bin.UNKNOWN
+
+    # scaling applies to the weights
+    options = ClassifyOptions(score=ScoreMode.WEIGHTED)
+    scaled = classify_seq(seq, hap_a, hap_b, (10.0, 1.0), options)
+    assert (scaled.hap_a_score, scaled.hap_b_score) == (30.0, 20.0)
+    assert scaled.bin == Bin.HAP_A
+
+    pair = classify_pair(seq, seq, hap_a, hap_b, options=options)
+    assert (pair.hap_a_score, pair.hap_b_score) == (6.0, 40.0)
+    windowed = classify_by_window(seq, hap_a, hap_b, 10, options=options)
+    assert windowed.overall.hap_b_score == 20.0
+    assert sum(window.hap_a_score for window in windowed.windows) == 3.0
+
+    with pytest.raises(ValueError, match="with counts"):
+        classify_seq(seq, make_kmer_set(5, ["AAAAC"]), hap_b, options=options)
+
+
+def test_classify_pair(hap_sets):
+    # neither mate has a majority on its own, but the pair does
+    classification = classify_pair("AAAACTTTTA", "CCCCG", *hap_sets)
+    assert classification.hap_a_count == 2
+    assert classification.hap_b_count == 1
+    assert classification.bin == Bin.HAP_A
+
+
+def test_classify_seq_scaling_factors():
+    hap_a = make_kmer_set(5, ["AAAAC", "CCCCG"])
+    hap_b = make_kmer_set(5, ["GGGGT", "TTTTA", "AATTC", "CAATT"])
+    scaling_factors = calculate_scaling_factors(hap_a, hap_b)
+    assert scaling_factors == (2.0, 1.0)
+
+    classification = classify_seq("AAAACAATTC", hap_a, hap_b, scaling_factors)
+    assert classification.hap_a_count == 1
+    assert classification.hap_b_count == 2
+    assert classification.hap_a_score == classification.hap_b_score == 2.0
+    assert classification.bin == Bin.UNKNOWN
+
+
+@pytest.mark.parametrize("threads,keep_order", [(1, True), (3, True), (3, False)])
+def test_classify_reads(hap_sets, threads, keep_order):
+    rng = random.Random(21)
+    reads = [
+        Read(f"read{i}", "".join(rng.choices("ACGT", k=rng.randint(0, 30))))
+        for i in range(500)
+    ]
+    results = list(
+        classify_reads(
+            reads, *hap_sets, threads=threads, keep_order=keep_order, batch_size=7
+        )
+    )
+    if not keep_order:
+        results.sort(key=lambda result: int(result[0].name[4:]))
+
+    assert [read for read, _ in results] == reads
+    assert [classification for _, classification in results] == [
+        classify_seq(read.seq, *hap_sets) for read in reads
+    ]
+
+
+@pytest.mark.parametrize(
+    "threads,keep_order,batch_size,queue_depth",
+    [(1, True, 10, None), (3, True, 10, None), (3, False, 7, 2), (2, True, 1, 1)],
+)
+def test_classify_reads_bounded(hap_sets, threads, keep_order, batch_size, queue_depth):
+    # an endless stream of reads, as from a huge file, that counts how many
+    # reads have been taken from it
+    taken = 0
+
+    def endless_reads():
+        nonlocal taken
+        rng = random.Random(44)
+        while True:
+            taken += 1
+            yield Read(f"read{taken}", "".join(rng.choices("ACGT", k=30)))
+
+    max_batches = 2 * threads if queue_depth is None else queue_depth
+    results = classify_reads(
+        endless_reads(),
+        *hap_sets,
+        threads=threads,
+        keep_order=keep_order,
+        batch_size=batch_size,
+        queue_depth=queue_depth,
+    )
+    for num_yielded, _ in enumerate(islice(results, 2000), start=1):
+        assert taken - num_yielded < max_batches * batch_size
+    results.close()
+
+
+@pytest.mark.parametrize("batch_size,queue_depth", [(0, None), (1, 0)])
+def test_classify_reads_bad_batches(hap_sets, batch_size, queue_depth):
+    with pytest.raises(ValueError, match="must be at least 1"):
+        list(
+            classify_reads(
+                [], *hap_sets, batch_size=batch_size, queue_depth=queue_depth
+            )
+        )
+
+
+def test_classify_reads_other_records(hap_sets):
+    records = [
+        OtherRecord("read1", None, "AAAACGTCCCCG", None),
+        OtherRecord("read2", "desc", "GATTACATGCT", "IIIIIII#III"),
+    ]
+    options = ClassifyOptions(min_base_quality=10)
+    results = list(classify_reads(records, *hap_sets, options=options))
+
+    assert [record for record, _ in results] == records
+    assert [classification for _, classification in results] == [
+        classify_seq(record.seq, *hap_sets, options=options, quals=record.qual)
+        for record in records
+    ]
+
+
+def test_classify_reads_bad_threads(hap_sets):
+    with pytest.raises(ValueError, match="threads"):
+        list(classify_reads([], *hap_sets, threads=0))
+
+
+def test_classify_by_window(hap_sets):
+    seq = "AAAACGCCCCGTTTTAACACACACGATTTTAG"
+    classification = classify_by_window(seq, *hap_sets, 8)
+    assert [window.bin for window in classification.windows] == [
+        Bin.HAP_A,
+        Bin.UNKNOWN,
+        Bin.UNKNOWN,
+        Bin.HAP_B,
+    ]
+    assert [window.num_kmers for window in classification.windows] == [8, 8, 8, 4]
+    assert classification.overall.bin == Bin.HAP_A
+    assert classification.supporting_fraction == 0.25
+
+
+def window_calls(calls: str):
+    return [Bin.UNKNOWN if call == "n" else Bin(call) for call in calls]
+
+
+@pytest.mark.parametrize(
+    "calls, min_run, expected",
+    [
+        ("AAABBB", 1, [(300, 300, "A", "B", 3, 3)]),
+        ("AAABBB", 3, [(300, 300, "A", "B", 3, 3)]),
+        ("AAABBB", 4, []),
+        # no calls don't break a run, and the switch is somewhere across them
+        ("AAnnBB", 2, [(200, 400, "A", "B", 2, 2)]),
+        ("nAnnAAnBnBBnn", 2, [(600, 700, "A", "B", 3, 3)]),
+        (
+            "ABAB",
+            1,
+            [
+                (100, 100, "A", "B", 1, 1),
+                (200, 200, "B", "A", 1, 1),
+                (300, 300, "A", "B", 1, 1),
+            ],
+        ),
+        ("ABAB", 2, []),
+        # the stray B is ignored, so the A's on each side are one run
+        ("AAABAAnBBB", 2, [(600, 700, "A", "B", 5, 3)]),
+        ("BBBnnnAAABBB", 3, [(300, 600, "B", "A", 3, 3), (900, 900, "A", "B", 3, 3)]),
+        ("AAAAAA", 1, []),
+        ("nnnn", 1, []),
+        ("", 1, []),
+    ],
+)
+def test_find_switches(calls, min_run, expected):
+    switches = find_switches(window_calls(calls), 100, min_run, "contig1")
+    assert switches == [
+        SwitchEvent("contig1", start, end, Bin(before), Bin(after), *run_sizes)
+        for start, end, before, after, *run_sizes in expected
+    ]
+
+
+def test_find_switches_in_windows(hap_sets):
+    seq = "AAAACGCCCCGTTTTAACACACACGATTTTAG"
+    classification = classify_by_window(seq, *hap_sets, 8)
+    # the second window's hits are a tie, and the last has too few
+    assert classification.calls() == window_calls("AnnB")
+    assert classification.calls(2) == window_calls("Annn")
+    assert classification.calls(0) == window_calls("AnnB")
+    assert find_switches(classification.calls(), 8) == [
+        SwitchEvent("", 8, 24, Bin.HAP_A, Bin.HAP_B, 1, 1)
+    ]
+
+    with pytest.raises(ValueError, match="min_run must be at least 1, not 0"):
+        find_switches(classification.calls(), 8, 0)
+    with pytest.raises(ValueError, match="window_size must be at least 1, not 0"):
+        find_switches(classification.calls(), 0)
+
+
+@pytest.mark.parametrize("window_size", [1, 5, 7, 100])
+def test_classify_by_window_adds_up(hap_sets, window_size):
+    rng = random.Random(window_size)
+    for _ in range(20):
+        seq = "".join(rng.choices("ACGTN", k=rng.randint(0, 60)))
+        classification = classify_by_window(seq, *hap_sets, window_size)
+        assert classification.overall == classify_seq(seq, *hap_sets)
+        assert len(classification.windows) == -(-len(seq) // window_size)
+
+
+@pytest.mark.parametrize(
+    "options",
+    [
+        ClassifyOptions(ignore_softmasked=True),
+        ClassifyOptions(kmer_step=3),
+        ClassifyOptions(min_hits=2),
+        ClassifyOptions(seed_pattern="1101011"),
+        ClassifyOptions(syncmer_size=3),
+    ],
+)
+def test_classify_by_window_options(hap_sets, options):
+    rng = random.Random(options.kmer_step)
+    for _ in range(20):
+        seq = "".join(rng.choices("ACGTNacgt", k=rng.randint(0, 60)))
+        classification = classify_by_window(seq, *hap_sets, 7, (2.0, 1.0), options)
+        # each window has the k-mers starting in it
+        assert classification.windows == [
+            classify_seq(
+                seq[start : start + 6 + options.span(5)], *hap_sets, (2.0, 1.0), options
+            )
+            for start in range(0, len(seq), 7)
+        ]
+
+
+def test_classifier(hap_sets):
+    hap_a, _ = hap_sets
+    hap_b = make_kmer_set(5, ["GGGGT", "TTTTA", "AATTC", "CAATT"])
+    classifier = (
+        Classifier.builder()
+        .hap_a(hap_a)
+        .hap_b(hap_b)
+        .min_hits(2)
+        .min_margin(0.5)
+        .threads(3)
+        .batch_size(4)
+        .build()
+    )
+    options = ClassifyOptions(min_hits=2, min_margin=0.5)
+    assert classifier.options == options
+    assert classifier.scaling_factors == (2.0, 1.0)
+
+    rng = random.Random(57)
+    reads = [
+        Read(f"read{i}", "".join(rng.choices("ACGT", k=rng.randint(0, 30))))
+        for i in range(100)
+    ]
+    expected = [
+        classify_seq(read.seq, hap_a, hap_b, (2.0, 1.0), options) for read in reads
+    ]
+    assert [classifier.classify_record(read) for read in reads] == expected
+    assert [c for _, c in classifier.classify_reads(reads)] == expected
+
+    class Sink:
+        def __init__(self):
+            self.classified_reads = []
+
+        def classified(self, read, classification):
+            self.classified_reads.append((read, classification))
+
+    sink = Sink()
+    classifier.classify_stream(reads, sink)
+    assert sink.classified_reads == list(zip(reads, expected))
+
+    pairs = list(zip(reads[::2], reads[1::2]))
+    assert [c for _, c in classifier.classify_read_pairs(pairs)] == [
+        classify_pair(pair[0].seq, pair[1].seq, hap_a, hap_b, (2.0, 1.0), options)
+        for pair in pairs
+    ]
+
+
+def test_classifier_shared_between_threads(hap_sets):
+    classifier = Classifier.builder().hap_a(hap_sets[0]).hap_b(hap_sets[1]).build()
+    seqs = ["AAAACGCCCCG", "ACCCC", "AAAACTTTTA"] * 200
+    with ThreadPoolExecutor(4) as executor:
+        bins = list(executor.map(lambda seq: classifier.classify_seq(seq).bin, seqs))
+    assert bins == [Bin.HAP_A, Bin.HAP_B, Bin.UNKNOWN] * 200
+
+
+@pytest.mark.parametrize("threads", [1, 2])
+def test_classifier_classify_file(hap_sets, tmpdir, threads):
+    reads_path = os.path.join(tmpdir, "reads.fa")
+    with open(reads_path, "w") as fp:
+        fp.write(">read1 desc\nAAAACGCCCCG\n>read2\nACCCC\n>read3\nAAAACTTTTA\n")
+    classifier = (
+        Classifier.builder()
+        .hap_a(hap_sets[0])
+        .hap_b(hap_sets[1])
+        .threads(threads)
+        .batch_size(1)
+        .build()
+    )
+    classified = list(classifier.classify_file(reads_path))
+    assert [(read["name"], read["length"], read["bin"]) for read in classified] == [
+        ("read1", 11, "A"),
+        ("read2", 5, "B"),
+        ("read3", 10, "U"),
+    ]
+    assert classified[0]["hap_a_count"] == 2
+    assert json.loads(json.dumps(classified)) == classified
+
+
+def test_classifier_rescue(hap_sets):
+    classifier = (
+        Classifier.builder()
+        .hap_a(hap_sets[0])
+        .hap_b(hap_sets[1])
+        .min_hits(3)
+        .rescue(ClassifyOptions(min_hits=1))
+        .build()
+    )
+    classification = classifier.classify_seq("AAAACGCCCCG")
+    assert (classification.bin, classification.rescued) == (Bin.HAP_A, True)
+    classification = classifier.classify_pair(
+        (Read("read1", "AAAAC"), Read("read2", "CCCCG"))
+    )
+    assert (classification.bin, classification.rescued) == (Bin.HAP_A, True)
+
+
+def test_classifier_mask_adapters(hap_sets):
+    # the adapter ends in AAAA, so the haplotype A k-mer AAAAC spans its end
+    adapter = "GATCGGAAGAGCAAAA"
+    read = Read("read1", adapter + "CTTTTA")
+    classifier = Classifier.builder().hap_a(hap_sets[0]).hap_b(hap_sets[1])
+    unmasked = classifier.build().classify_record(read)
+    assert (unmasked.hap_a_count, unmasked.hap_b_count) == (1, 1)
+    assert (unmasked.bin, unmasked.masked_bases) == (Bin.UNKNOWN, 0)
+
+    classifier = classifier.adapter_masker(AdapterMasker([adapter])).build()
+    masked = classifier.classify_record(read)
+    assert (masked.hap_a_count, masked.hap_b_count) == (0, 1)
+    assert masked.bin == Bin.HAP_B
+    # the adapter's k-mers are skipped rather than looked up
+    assert (masked.num_kmers, masked.num_skipped) == (2, 16)
+    assert masked.masked_bases == len(adapter)
+    assert read.seq == adapter + "CTTTTA"
+
+    pair = classifier.classify_pair((read, Read("read1", "TAAAAG" + adapter)))
+    assert pair.masked_bases == 2 * len(adapter)
+    summary = Summary()
+    summary.add(masked, len(read))
+    summary.add(pair, 2 * len(read), 2)
+    assert summary.to_dict()["masked_bases"] == 3 * len(adapter)
+
+
+@pytest.mark.parametrize(
+    "builder,message",
+    [
+        (Classifier.builder(), "both haplotypes"),
+        (
+            Classifier.builder()
+            .hap_a(make_kmer_set(5, ["AAAAC"]))
+            .hap_b(make_kmer_set(7, ["AAAAACC"])),
+            "are 5-mers, but the haplotype B k-mers are 7-mers",
+        ),
+        (
+            Classifier.builder()
+            .hap_a(make_kmer_set(5, ["AAAAC"]))
+            .hap_b(make_kmer_set(5, ["GGGGT"]))
+            .threads(0),
+            "threads",
+        ),
+        (
+            Classifier.builder()
+            .hap_a(make_kmer_set(5, ["AAAAC"]))
+            .hap_b(make_kmer_set(5, [])),
+            "haplotype B has no k-mers",
+        ),
+    ],
+)
+def test_classifier_invalid(builder, message):
+    with pytest.raises(ValueError, match=message):
+        builder.build()
+
+
+def test_summary():
+    summary = Summary()
+    assert summary.unknown_fraction == 0
+    assert summary.to_dict()["bins"]["A"]["mean_hap_a_score"] is None
+
+    summary.add(Classification(3, 1, Bin.HAP_A, 3.0, 1.0, 10, 0), 14)
+    summary.add(Classification(5, 0, Bin.HAP_A, 5.0, 0.0, 10, 0), 14)
+    summary.add(Classification(7, 1, Bin.HAP_A, 7.0, 1.0, 10, 0, True), 14)
+    summary.add(Classification(0, 0, Bin.UNKNOWN, 0.0, 0.0, 10, 0), 20, 2)
+
+    summary.skip(2)
+
+    assert summary.total_reads == 5
+    assert summary.reads_seen == 7
+    assert summary.to_dict()["reads_seen"] == 7
+    assert summary.total_bases == 62
+    assert summary.unknown_fraction == pytest.approx(0.4)
+    hap_a_summary = summary.to_dict()["bins"]["A"]
+    assert hap_a_summary == {
+        "reads": 3,
+        "bases": 42,
+        "primary_reads": 2,
+        "rescued_reads": 1,
+        "mean_hap_a_score": 5.0,
+        "median_hap_a_score": 5.0,
+        "mean_hap_b_score": pytest.approx(2 / 3),
+        "median_hap_b_score": 1.0,
+    }
+
+
+def test_score_histogram():
+    histogram = ScoreHistogram()
+    assert (histogram.mean(), histogram.median()) == (None, None)
+
+    # whole numbers below 1000 are counted exactly
+    for score in [1, 2, 2, 999]:
+        histogram.add(score)
+    assert histogram.mean() == 251
+    assert histogram.median() == 2
+    histogram.add(3)
+    assert histogram.median() == 2
+
+    # others are rounded to 3 significant figures, but not for the mean
+    histogram = ScoreHistogram()
+    for score in range(100_000):
+        histogram.add(score / 7)
+    assert len(histogram.counts) < 3000
+    assert histogram.mean() == pytest.approx(99_999 / 14)
+    assert histogram.median() == pytest.approx(99_999 / 14, rel=0.0005)
+
+    loaded = ScoreHistogram.from_dict(json.loads(json.dumps(histogram.to_dict())))
+    assert (loaded.count, loaded.total, loaded.counts) == (
+        histogram.count,
+        histogram.total,
+        histogram.counts,
+    )
+    with pytest.raises(ValueError, match="isn't a histogram"):
+        ScoreHistogram.from_dict({"total": 1.0, "counts": [1.0]})
+
+
+@pytest.mark.parametrize("include_scores", [False, True])
+def test_summary_round_trip(include_scores):
+    summary = Summary()
+    summary.add(Classification(3, 1, Bin.HAP_A, 3.0, 1.0, 10, 0), 14)
+    summary.add(Classification(7, 1, Bin.HAP_A, 7.0, 1.0, 10, 0, True, True), 14)
+    summary.add(Classification(0, 2, Bin.HAP_B, 0.0, 2.0, 10, 0), 20, 2)
+    summary.filter("too_short", 3)
+    summary.malformed = 1
+    summary.duplicates = 2
+
+    summary_dict = json.loads(json.dumps(summary.to_dict(include_scores)))
+    assert ("hap_a_scores" in summary_dict["bins"]["A"]) == include_scores
+    loaded = Summary.from_dict(summary_dict)
+    if include_scores:
+        assert loaded.to_dict() == summary.to_dict()
+    else:
+        # the counts are all there, but not the scores
+        assert loaded.to_dict()["bins"]["A"]["median_hap_a_score"] is None
+        loaded_dict, summary_dict = loaded.to_dict(), summary.to_dict()
+        for bin_dict in [*loaded_dict["bins"].values(), *summary_dict["bins"].values()]:
+            for key in [key for key in bin_dict if key.endswith("_score")]:
+                del bin_dict[key]
+        assert loaded_dict == summary_dict
+
+
+def test_summary_from_dict_invalid():
+    with pytest.raises(ValueError, match="isn't a summary"):
+        Summary.from_dict({"reads_seen": 3})
+
+
+class TerminalIO(io.StringIO):
+    def isatty(self):
+        return True
+
+
+def test_progress():
+    times = iter([0.0, 0.5, 2.0, 2.5, 4.0])
+    out = io.StringIO()
+    progress = Progress(out, clock=lambda: next(times))
+    summary = Summary()
+
+    summary.add(Classification(3, 1, Bin.HAP_A, 3.0, 1.0, 10, 0), 1_000_000)
+    # too soon after starting to print anything
+    progress.update(summary)
+    assert out.getvalue() == ""
+    summary.add(Classification(0, 2, Bin.HAP_B, 0.0, 2.0, 10, 0), 3_000_000)
+    progress.update(summary)
+    summary.add(Classification(0, 0, Bin.UNKNOWN, 0.0, 0.0, 10, 0), 1_000_000)
+    progress.update(summary)
+    progress.finish(summary)
+
+    assert out.getvalue().splitlines() == [
+        "2 reads, 4.0 Mbp, 2.00 Mbp/s (A: 1, B: 1, U: 0)",
+        # the last line has the throughput over the whole run
+        "3 reads, 5.0 Mbp, 1.25 Mbp/s (A: 1, B: 1, U: 1)",
+    ]
+
+
+def test_progress_terminal():
+    times = iter([0.0, 1.0, 2.0])
+    out = TerminalIO()
+    progress = Progress(out, clock=lambda: next(times))
+    summary = Summary()
+    summary.add(Classification(3, 1, Bin.HAP_A, 3.0, 1.0, 10, 0), 1_000_000)
+    progress.update(summary)
+    progress.finish(summary)
+
+    # each line overwrites the last, and the last one is ended
+    assert out.getvalue() == (
+        "\r1 reads, 1.0 Mbp, 1.00 Mbp/s (A: 1, B: 0, U: 0)\x1b[K"
+        "\r1 reads, 1.0 Mbp, 0.50 Mbp/s (A: 1, B: 0, U: 0)\x1b[K\n"
+    )
@@ -0,0 +1,159 @@
+import gzip
+from os.path import join
+from unittest.mock import patch
+
+import pytest
+
+from trio_binning.classify_assembly import main
+from trio_binning.read_tools import main as read_tools_main
+
+
+def test_classify_assembly(capsys, tmpdir):
+    with open(join(tmpdir, "hapA.txt"), "w") as hap_a_file:
+        print("AAAAC\nCCCCG", file=hap_a_file)
+    with open(join(tmpdir, "hapB.txt"), "w") as hap_b_file:
+        print("GGGGT\nTTTTA", file=hap_b_file)
+    with open(join(tmpdir, "assembly.fasta"), "w") as assembly_file:
+        # contig1 switches from haplotype A to haplotype B partway through
+        print(">contig1\nAAAACGCCCCGTTTTAACACACACGATTTTAG", file=assembly_file)
+        print(">contig2\nGGGGTA", file=assembly_file)
+
+    with patch(
+        "sys.argv",
+        [
+            "classify-assembly",
+            join(tmpdir, "assembly.fasta"),
+            join(tmpdir, "hapA.txt"),
+            join(tmpdir, "hapB.txt"),
+            "--window-size",
+            "8",
+            "--windows",
+            join(tmpdir, "windows.bed"),
+            "--switches",
+            join(tmpdir, "switches.tsv.gz"),
+            "--min-switch-run",
+            "1",
+            "--min-window-hits",
+            "1",
+        ],
+    ):
+        main()
+
+    out, _ = capsys.readouterr()
+    assert [line.split("\t") for line in out.splitlines()] == [
+        [
+            "contig",
+            "length",
+            "bin",
+            "hap_a_hits",
+            "hap_b_hits",
+            "windows",
+            "supporting_fraction",
+        ],
+        ["contig1", "32", "A", "3", "2", "4", "0.25"],
+        ["contig2", "6", "B", "0", "1", "1", "1"],
+    ]
+    with open(join(tmpdir, "windows.bed")) as windows_file:
+        assert windows_file.read() == (
+            "contig1\t0\t8\t2\t0\tA\n"
+            "contig1\t8\t16\t1\t1\tU\n"
+            "contig1\t16\t24\t0\t0\tU\n"
+            "contig1\t24\t32\t0\t1\tB\n"
+            "contig2\t0\t6\t0\t1\tB\n"
+        )
+    with gzip.open(join(tmpdir, "switches.tsv.gz"), "rt") as switches_file:
+        assert switches_file.read() == (
+            "contig\tstart\tend\tfrom_bin\tto_bin\twindows_before\twindows_after\n"
+            "contig1\t8\t24\tA\tB\t1\t1\n"
+        )
+
+
+def test_classify_assembly_scaffold_coordinates(capsys, tmpdir):
+    with open(join(tmpdir, "hapA.txt"), "w") as hap_a_file:
+        print("AAAAC\nCCCCG", file=hap_a_file)
+    with open(join(tmpdir, "hapB.txt"), "w") as hap_b_file:
+        print("GGGGT\nTTTTA", file=hap_b_file)
+    with open(join(tmpdir, "scaffolds.fa"), "w") as scaffolds_file:
+        scaffold = "AAAACGCCCCGTTTTAACACACACGATTTTAG" + "N" * 10 + "GGGGTA"
+        print(f">scaffold1\n{scaffold}\n>contig3\nGGGGTA", file=scaffolds_file)
+    with patch(
+        "sys.argv",
+        [
+            "reads",
+            "split-scaffolds",
+            join(tmpdir, "scaffolds.fa"),
+            "-o",
+            join(tmpdir, "contigs.fa"),
+        ],
+    ):
+        read_tools_main()
+    capsys.readouterr()
+
+    with patch(
+        "sys.argv",
+        [
+            "classify-assembly",
+            join(tmpdir, "contigs.fa"),
+            join(tmpdir, "hapA.txt"),
+            join(tmpdir, "hapB.txt"),
+            "--window-size",
+            "8",
+            "--windows",
+            join(tmpdir, "windows.bed"),
+            "--switches",
+            join(tmpdir, "switches.tsv"),
+            "--min-switch-run",
+            "1",
+            "--min-window-hits",
+            "1",
+            "--scaffold-coordinates",
+        ],
+    ):
+        main()
+
+    out, _ = capsys.readouterr()
+    assert [line.split("\t")[:3] for line in out.splitlines()[1:]] == [
+        ["scaffold1_part1", "32", "A"],
+        ["scaffold1_part2", "6", "B"],
+        ["contig3_part1", "6", "B"],
+    ]
+    # the second piece's windows are from 42 on the scaffold
+    with open(join(tmpdir, "windows.bed")) as windows_file:
+        assert windows_file.read() == (
+            "scaffold1\t0\t8\t2\t0\tA\n"
+            "scaffold1\t8\t16\t1\t1\tU\n"
+            "scaffold1\t16\t24\t0\t0\tU\n"
+            "scaffold1\t24\t32\t0\t1\tB\n"
+            "scaffold1\t42\t48\t0\t1\tB\n"
+            "contig3\t0\t6\t0\t1\tB\n"
+        )
+    with open(join(tmpdir, "switches.tsv")) as switches_file:
+        assert switches_file.read().splitlines()[1:] == [
+            "scaffold1\t8\t24\tA\tB\t1\t1"
+        ]
+
+
+def test_classify_assembly_bad_window_size(tmpdir):
+    with patch(
+        "sys.argv",
+        ["classify-assembly", "a.fa", "hapA.txt", "hapB.txt", "-w", "0"],
+    ):
+        with pytest.raises(SystemExit) as excinfo:
+            main()
+
+    assert "--window-size" in str(excinfo.value.code)
+
+
+@pytest.mark.parametrize(
+    "args, message",
+    [
+        (["--min-switch-run", "0"], "--min-switch-run must be at least 1, not 0"),
+        (["--min-window-hits", "-1"], "--min-window-hits must not be negative"),
+    ],
+)
+def test_classify_assembly_bad_switch_options(capsys, args, message):
+    argv = ["classify-assembly", "a.fa", "hapA.txt", "hapB.txt", *args]
+    with patch("sys.argv", argv), pytest.raises(SystemExit):
+        main()
+    _, err = capsys.readouterr()
+    assert message in err
@@ -1,10 +1,47 @@
-from os.path import dirname, join
+import gzip
+import io
+import json
+import random
+from os import listdir, makedirs
+from os.path import dirname, exists, join
 from unittest.mock import patch
 
 import pytest
 
+from trio_binning import bgzf, kmers, seq
+from trio_binning.checkpoint import Checkpointer
 from trio_binning.classify_by_kmers import main
-from trio_binning.seq import readfq
+from trio_binning.config import tomllib
+from trio_binning.seq import Read, readfq
+
+CCS_READS = join(dirname(__file__), "data", "test.ccs.fastq.gz")
+HAP_A_KMERS = join(dirname(__file__), "data", "hapA.txt")
+HAP_B_KMERS = join(dirname(__file__), "data", "hapB.txt")
+
+
+def run_main(tmpdir, *args, reads=CCS_READS, hap_a=HAP_A_KMERS, hap_b=HAP_B_KMERS):
+    """Run classify-by-kmers with some options, writing the bins to hapA,
+    hapB and hapU in tmpdir
+
+    `reads` is a reads file, or a list of the arguments that go before the
+    k-mer lists, e.g., several reads files or --interleaved and a file.
+    """
+    reads_args = [reads] if isinstance(reads, str) else reads
+    argv = [
+        "classify-by-kmers",
+        *reads_args,
+        hap_a,
+        hap_b,
+        "--haplotype-a-out-prefix",
+        join(tmpdir, "hapA"),
+        "--haplotype-b-out-prefix",
+        join(tmpdir, "hapB"),
+        "--unclassified-out-prefix",
+        join(tmpdir, "hapU"),
+        *args,
+    ]
+    with patch("sys.argv", argv):
+        main()
 
 
 def test_classify_by_kmers_help(capsys):
@@ -45,13 +82,1874 @@ def test_classify_by_kmers(capsys, tmpdir):
             assert splits[1] == "B"
             assert float(splits[3]) == 2
 
-    hap_a_out = readfq(open(join(tmpdir, "hapA.fastq"), "r"))
-    correct_out = readfq(open(join(dirname(__file__), "data", "hapA.fastq"), "r"))
+    hap_b_out = readfq(open(join(tmpdir, "hapB.fastq"), "r"))
+    correct_out = readfq(open(join(dirname(__file__), "data", "hapB.fastq"), "r"))
     num_reads = 0
-    for seq1, seq2 in zip(hap_a_out, correct_out):
+    for seq1, seq2 in zip(hap_b_out, correct_out):
         num_reads += 1
         assert seq1.name == seq2.name
         assert seq1.seq == seq2.seq
         assert seq1.qual == seq2.qual
 
     assert num_reads == 1
+
+
+def test_classify_by_kmers_summary(capsys, tmpdir):
+    with patch(
+        "sys.argv",
+        [
+            "classify-by-kmers",
+            join(dirname(__file__), "data", "test.ccs.fastq.gz"),
+            join(dirname(__file__), "data", "hapA.txt"),
+            join(dirname(__file__), "data", "hapB.txt"),
+            "-k",
+            "21",
+            "--haplotype-a-out-prefix",
+            join(tmpdir, "hapA"),
+            "--haplotype-b-out-prefix",
+            join(tmpdir, "hapB"),
+            "--unclassified-out-prefix",
+            join(tmpdir, "hapU"),
+        ],
+    ):
+        main()
+
+    _, err = capsys.readouterr()
+    assert "A\t1\t20288\n" in err
+    assert "B\t1\t9808\n" in err
+    assert "U\t1\t14017\n" in err
+    for prefix in ["hapA", "hapB", "hapU"]:
+        assert len(list(readfq(gzip.open(join(tmpdir, prefix + ".fastq.gz"), "rt"))))
+
+
+@pytest.mark.parametrize("gzipped", [False, True])
+def test_classify_by_kmers_stdin(capsys, tmpdir, gzipped):
+    with gzip.open(join(dirname(__file__), "data", "test.ccs.fastq.gz"), "rb") as fp:
+        reads = fp.read()
+    if gzipped:
+        reads = gzip.compress(reads)
+    stdin = io.TextIOWrapper(io.BufferedReader(io.BytesIO(reads)))
+
+    with patch("sys.stdin", stdin), patch(
+        "sys.argv",
+        [
+            "classify-by-kmers",
+            "-",
+            join(dirname(__file__), "data", "hapA.txt"),
+            join(dirname(__file__), "data", "hapB.txt"),
+            "--haplotype-a-out-prefix",
+            join(tmpdir, "hapA"),
+            "--haplotype-b-out-prefix",
+            join(tmpdir, "hapB"),
+            "--unclassified-out-prefix",
+            join(tmpdir, "hapU"),
+            "--no-gzip-output",
+        ],
+    ):
+        main()
+
+    _, err = capsys.readouterr()
+    assert "B\t1\t9808\n" in err
+    # the output files are named after the format of the reads
+    assert sorted(listdir(tmpdir)) == ["hapA.fastq", "hapB.fastq", "hapU.fastq"]
+
+
+def test_classify_by_kmers_wrong_k(tmpdir):
+    with patch(
+        "sys.argv",
+        [
+            "classify-by-kmers",
+            join(dirname(__file__), "data", "test.ccs.fastq.gz"),
+            join(dirname(__file__), "data", "hapA.txt"),
+            join(dirname(__file__), "data", "hapB.txt"),
+            "-k",
+            "25",
+        ],
+    ):
+        with pytest.raises(SystemExit) as excinfo:
+            main()
+
+    assert "hapA.txt" in str(excinfo.value.code)
+
+
+def test_classify_by_kmers_mismatched_k(tmpdir):
+    hap_b_path = join(tmpdir, "hapB.txt")
+    with open(hap_b_path, "w") as hap_b_file:
+        print("ACGTACGTACGTACGTACGTACGTA", file=hap_b_file)
+
+    for extra_args in [[], ["--bloom"], ["--lookup", "sorted"]]:
+        with patch(
+            "sys.argv",
+            [
+                "classify-by-kmers",
+                join(dirname(__file__), "data", "test.ccs.fastq.gz"),
+                join(dirname(__file__), "data", "hapA.txt"),
+                hap_b_path,
+                *extra_args,
+            ],
+        ):
+            with pytest.raises(SystemExit) as excinfo:
+                main()
+
+        assert str(excinfo.value.code) == (
+            "Error: the haplotype A k-mers in "
+            f"{join(dirname(__file__), 'data', 'hapA.txt')} are 21-mers, but the "
+            f"haplotype B k-mers in {hap_b_path} are 25-mers"
+        )
+
+
+@pytest.mark.parametrize("k", ["5", "10", "33"])
+def test_classify_by_kmers_k_out_of_range(capsys, k):
+    with patch(
+        "sys.argv",
+        [
+            "classify-by-kmers",
+            join(dirname(__file__), "data", "test.ccs.fastq.gz"),
+            join(dirname(__file__), "data", "hapA.txt"),
+            join(dirname(__file__), "data", "hapB.txt"),
+            "-k",
+            k,
+        ],
+    ):
+        with pytest.raises(SystemExit):
+            main()
+
+    _, err = capsys.readouterr()
+    assert "-k must be between 11 and 32" in err
+
+
+def test_classify_by_kmers_binary_wrong_k(tmpdir):
+    with patch(
+        "sys.argv",
+        [
+            "classify-by-kmers",
+            join(dirname(__file__), "data", "test.ccs.fastq.gz"),
+            join(dirname(__file__), "data", "hapA.txt"),
+            join(dirname(__file__), "data", "hapB.txt"),
+            "--save-kmers",
+            join(tmpdir, "saved"),
+            "--haplotype-a-out-prefix",
+            join(tmpdir, "hapA"),
+            "--haplotype-b-out-prefix",
+            join(tmpdir, "hapB"),
+            "--unclassified-out-prefix",
+            join(tmpdir, "hapU"),
+        ],
+    ):
+        main()
+
+    with patch(
+        "sys.argv",
+        [
+            "classify-by-kmers",
+            join(dirname(__file__), "data", "test.ccs.fastq.gz"),
+            join(tmpdir, "saved.hapA.kmers"),
+            join(tmpdir, "saved.hapB.kmers"),
+            "--load-kmers",
+            "-k",
+            "25",
+        ],
+    ):
+        with pytest.raises(SystemExit) as excinfo:
+            main()
+
+    assert str(excinfo.value.code) == (
+        f"Error reading k-mers: {join(tmpdir, 'saved.hapA.kmers')}: the k-mers are "
+        "21-mers, but -k is 25"
+    )
+
+
+def test_classify_by_kmers_save_load_kmers(capsys, tmpdir):
+    def run(hap_a_kmers, hap_b_kmers, *extra_args):
+        with patch(
+            "sys.argv",
+            [
+                "classify-by-kmers",
+                join(dirname(__file__), "data", "test.ccs.fastq.gz"),
+                hap_a_kmers,
+                hap_b_kmers,
+                "--haplotype-a-out-prefix",
+                join(tmpdir, "hapA"),
+                "--haplotype-b-out-prefix",
+                join(tmpdir, "hapB"),
+                "--unclassified-out-prefix",
+                join(tmpdir, "hapU"),
+                *extra_args,
+            ],
+        ):
+            main()
+        return capsys.readouterr()[0]
+
+    text_out = run(
+        join(dirname(__file__), "data", "hapA.txt"),
+        join(dirname(__file__), "data", "hapB.txt"),
+        "--save-kmers",
+        join(tmpdir, "saved"),
+    )
+    binary_out = run(
+        join(tmpdir, "saved.hapA.kmers"),
+        join(tmpdir, "saved.hapB.kmers"),
+        "--load-kmers",
+    )
+    assert binary_out == text_out
+
+    # sorted sets give the same results, whether read from text or binary
+    text_lists = [
+        join(dirname(__file__), "data", "hapA.txt"),
+        join(dirname(__file__), "data", "hapB.txt"),
+    ]
+    binary_lists = [join(tmpdir, "saved.hapA.kmers"), join(tmpdir, "saved.hapB.kmers")]
+    for extra_args in [
+        [*text_lists],
+        [*text_lists, "--subtract-shared"],
+        [*binary_lists, "--load-kmers"],
+    ]:
+        assert run(*extra_args, "--lookup", "sorted") == text_out
+
+    # and so do memory-mapped binary files
+    assert run(*binary_lists, "--load-kmers", "--mmap") == text_out
+
+
+def test_classify_by_kmers_mmap_needs_load_kmers(capsys):
+    with patch(
+        "sys.argv",
+        [
+            "classify-by-kmers",
+            join(dirname(__file__), "data", "test.ccs.fastq.gz"),
+            join(dirname(__file__), "data", "hapA.txt"),
+            join(dirname(__file__), "data", "hapB.txt"),
+            "--mmap",
+        ],
+    ):
+        with pytest.raises(SystemExit):
+            main()
+
+    _, err = capsys.readouterr()
+    assert "--mmap needs --load-kmers" in err
+
+
+def test_classify_by_kmers_compress_homopolymers_mismatch(tmpdir):
+    def run(hap_a_kmers, hap_b_kmers, *extra_args):
+        with patch(
+            "sys.argv",
+            [
+                "classify-by-kmers",
+                join(dirname(__file__), "data", "test.ccs.fastq.gz"),
+                hap_a_kmers,
+                hap_b_kmers,
+                "--haplotype-a-out-prefix",
+                join(tmpdir, "hapA"),
+                "--haplotype-b-out-prefix",
+                join(tmpdir, "hapB"),
+                "--unclassified-out-prefix",
+                join(tmpdir, "hapU"),
+                *extra_args,
+            ],
+        ):
+            main()
+
+    run(
+        join(dirname(__file__), "data", "hapA.txt"),
+        join(dirname(__file__), "data", "hapB.txt"),
+        "--compress-homopolymers",
+        "--save-kmers",
+        join(tmpdir, "compressed"),
+    )
+    with pytest.raises(SystemExit) as excinfo:
+        run(
+            join(tmpdir, "compressed.hapA.kmers"),
+            join(tmpdir, "compressed.hapB.kmers"),
+            "--load-kmers",
+        )
+    assert "--compress-homopolymers is needed" in str(excinfo.value.code)
+
+
+def test_classify_by_kmers_seed_pattern(tmpdir):
+    def run(hap_a_kmers, hap_b_kmers, *extra_args):
+        with patch(
+            "sys.argv",
+            [
+                "classify-by-kmers",
+                join(dirname(__file__), "data", "test.ccs.fastq.gz"),
+                hap_a_kmers,
+                hap_b_kmers,
+                "--haplotype-a-out-prefix",
+                join(tmpdir, "hapA"),
+                "--haplotype-b-out-prefix",
+                join(tmpdir, "hapB"),
+                "--unclassified-out-prefix",
+                join(tmpdir, "hapU"),
+                *extra_args,
+            ],
+        ):
+            main()
+
+    rng = random.Random(86)
+    for hap in ["hapA", "hapB"]:
+        with open(join(tmpdir, f"{hap}.txt"), "w") as fp:
+            for _ in range(10):
+                print("".join(rng.choices("ACGT", k=12)), file=fp)
+    text_lists = join(tmpdir, "hapA.txt"), join(tmpdir, "hapB.txt")
+    seed = "1111110111111"
+    run(*text_lists, "--seed-pattern", seed, "--save-kmers", join(tmpdir, "spaced"))
+    run(*text_lists, "--save-kmers", join(tmpdir, "contiguous"))
+    spaced = join(tmpdir, "spaced.hapA.kmers"), join(tmpdir, "spaced.hapB.kmers")
+    with open(spaced[0], "rb") as fp:
+        assert kmers.KmerSet.read_binary(fp).seed == kmers.SpacedSeed(seed)
+    run(*spaced, "--load-kmers", "--seed-pattern", seed)
+
+    contiguous = (
+        join(tmpdir, "contiguous.hapA.kmers"),
+        join(tmpdir, "contiguous.hapB.kmers"),
+    )
+    for lists, extra_args, message in [
+        (spaced, ["--load-kmers"], f"--seed-pattern {seed} is needed"),
+        (
+            spaced,
+            ["--load-kmers", "--seed-pattern", "1111101111111"],
+            f"made with the seed pattern {seed}, not with --seed-pattern 11111011",
+        ),
+        (
+            contiguous,
+            ["--load-kmers", "--seed-pattern", seed],
+            "are contiguous k-mers, not with --seed-pattern",
+        ),
+        (text_lists, ["--seed-pattern", "10111"], "are 12-mers, but --seed-pattern"),
+    ]:
+        with pytest.raises(SystemExit) as excinfo:
+            run(*lists, *extra_args)
+        assert message in str(excinfo.value.code)
+
+
+@pytest.mark.parametrize(
+    "args, message",
+    [
+        (["--seed-pattern", "11x1"], "--seed-pattern: a seed pattern must be made"),
+        (["--seed-pattern", "1" * 12, "-k", "13"], "-k must be the number of 1's"),
+    ],
+)
+def test_classify_by_kmers_seed_pattern_invalid(capsys, args, message):
+    with patch(
+        "sys.argv",
+        [
+            "classify-by-kmers",
+            join(dirname(__file__), "data", "test.ccs.fastq.gz"),
+            join(dirname(__file__), "data", "hapA.txt"),
+            join(dirname(__file__), "data", "hapB.txt"),
+            *args,
+        ],
+    ):
+        with pytest.raises(SystemExit):
+            main()
+
+    _, err = capsys.readouterr()
+    assert message in err
+
+
+def test_classify_by_kmers_threads(capsys, tmpdir):
+    rng = random.Random(21)
+    with open(join(tmpdir, "hapA.txt"), "w") as hap_a_file:
+        print("AAAAC\nCCCCG", file=hap_a_file)
+    with open(join(tmpdir, "hapB.txt"), "w") as hap_b_file:
+        print("GGGGT\nTTTTA", file=hap_b_file)
+    with open(join(tmpdir, "reads.fasta"), "w") as reads_file:
+        for i in range(3000):
+            seq = "".join(rng.choices("ACGT", k=rng.randint(1, 60)))
+            print(f">read{i}\n{seq}", file=reads_file)
+
+    outputs = []
+    for threads in ["1", "4"]:
+        with patch(
+            "sys.argv",
+            [
+                "classify-by-kmers",
+                join(tmpdir, "reads.fasta"),
+                join(tmpdir, "hapA.txt"),
+                join(tmpdir, "hapB.txt"),
+                "--threads",
+                threads,
+                "--haplotype-a-out-prefix",
+                join(tmpdir, f"hapA.{threads}"),
+                "--haplotype-b-out-prefix",
+                join(tmpdir, f"hapB.{threads}"),
+                "--unclassified-out-prefix",
+                join(tmpdir, f"hapU.{threads}"),
+                "--no-gzip-output",
+            ],
+        ):
+            main()
+
+        out, _ = capsys.readouterr()
+        binned = [
+            open(join(tmpdir, f"{prefix}.{threads}.fasta"), "rb").read()
+            for prefix in ["hapA", "hapB", "hapU"]
+        ]
+        assert all(binned)
+        outputs.append((out, binned))
+
+    assert outputs[0] == outputs[1]
+
+
+def test_classify_by_kmers_threads_identical(capsys, tmpdir):
+    # reads of very different lengths, in small batches, so later batches
+    # often finish before earlier ones
+    rng = random.Random(74)
+    with open(join(tmpdir, "hapA.txt"), "w") as hap_a_file:
+        print("AAAAC\nCCCCG", file=hap_a_file)
+    with open(join(tmpdir, "hapB.txt"), "w") as hap_b_file:
+        print("GGGGT\nTTTTA", file=hap_b_file)
+    with open(join(tmpdir, "reads.fasta"), "w") as reads_file:
+        for i in range(2000):
+            seq = "".join(rng.choices("ACGT", k=rng.choice([5, 50, 1000])))
+            print(f">read{i}\n{seq}", file=reads_file)
+
+    outputs = []
+    for threads in ["1", "8"]:
+        out_dir = join(tmpdir, threads)
+        makedirs(out_dir)
+        with patch(
+            "sys.argv",
+            [
+                "classify-by-kmers",
+                join(tmpdir, "reads.fasta"),
+                join(tmpdir, "hapA.txt"),
+                join(tmpdir, "hapB.txt"),
+                "--threads",
+                threads,
+                "--batch-size",
+                "3",
+                "--haplotype-a-out-prefix",
+                join(out_dir, "hapA"),
+                "--haplotype-b-out-prefix",
+                join(out_dir, "hapB"),
+                "--unclassified-out-prefix",
+                join(out_dir, "hapU"),
+                "--report",
+                join(out_dir, "report.tsv"),
+            ],
+        ):
+            main()
+
+        out, _ = capsys.readouterr()
+        outputs.append(
+            (
+                out,
+                [
+                    open(join(out_dir, name), "rb").read()
+                    for name in [
+                        "hapA.fasta.gz",
+                        "hapB.fasta.gz",
+                        "hapU.fasta.gz",
+                        "report.tsv",
+                    ]
+                ],
+            )
+        )
+
+    assert outputs[0] == outputs[1]
+
+
+def test_classify_by_kmers_paired(capsys, tmpdir):
+    with open(join(tmpdir, "hapA.txt"), "w") as hap_a_file:
+        print("AAAAC\nCCCCG", file=hap_a_file)
+    with open(join(tmpdir, "hapB.txt"), "w") as hap_b_file:
+        print("GGGGT\nTTTTA", file=hap_b_file)
+    pairs = [
+        ("pairA", "AAAACTTTTA", "CCCCG"),
+        ("pairB", "GGGGT", "ACGTACGT"),
+        ("pairU", "ACGTACGT", "ACGT"),
+    ]
+    with open(join(tmpdir, "reads_1.fasta"), "w") as reads_1, open(
+        join(tmpdir, "reads_2.fasta"), "w"
+    ) as reads_2, open(join(tmpdir, "interleaved.fasta"), "w") as interleaved:
+        for name, seq_1, seq_2 in pairs:
+            print(f">{name}/1\n{seq_1}", file=reads_1)
+            print(f">{name}/2\n{seq_2}", file=reads_2)
+            print(f">{name}/1\n{seq_1}\n>{name}/2\n{seq_2}", file=interleaved)
+
+    for reads_args in [
+        ["-1", join(tmpdir, "reads_1.fasta"), "-2", join(tmpdir, "reads_2.fasta")],
+        ["--interleaved", join(tmpdir, "interleaved.fasta")],
+    ]:
+        with patch(
+            "sys.argv",
+            [
+                "classify-by-kmers",
+                *reads_args,
+                join(tmpdir, "hapA.txt"),
+                join(tmpdir, "hapB.txt"),
+                "--haplotype-a-out-prefix",
+                join(tmpdir, "hapA"),
+                "--haplotype-b-out-prefix",
+                join(tmpdir, "hapB"),
+                "--unclassified-out-prefix",
+                join(tmpdir, "hapU"),
+                "--no-gzip-output",
+            ],
+        ):
+            main()
+
+        out, err = capsys.readouterr()
+        assert [line.split("\t")[:2] for line in out.splitlines()] == [
+            ["pairA", "A"],
+            ["pairB", "B"],
+            ["pairU", "U"],
+        ]
+        assert "A\t2\t15\n" in err
+        for (name, seq_1, seq_2), prefix in zip(pairs, ["hapA", "hapB", "hapU"]):
+            for mate, mate_seq in [("1", seq_1), ("2", seq_2)]:
+                with open(join(tmpdir, f"{prefix}_R{mate}.fasta")) as outfile:
+                    assert outfile.read() == f">{name}/{mate}\n{mate_seq}\n"
+
+
+def test_classify_by_kmers_paired_out_of_sync(tmpdir):
+    with open(join(tmpdir, "reads_1.fasta"), "w") as reads_1:
+        print(">read1/1\nACGT\n>read2/1\nACGT", file=reads_1)
+    with open(join(tmpdir, "reads_2.fasta"), "w") as reads_2:
+        print(">read1/2\nACGT", file=reads_2)
+
+    with patch(
+        "sys.argv",
+        [
+            "classify-by-kmers",
+            "-1",
+            join(tmpdir, "reads_1.fasta"),
+            "-2",
+            join(tmpdir, "reads_2.fasta"),
+            join(dirname(__file__), "data", "hapA.txt"),
+            join(dirname(__file__), "data", "hapB.txt"),
+            "--haplotype-a-out-prefix",
+            join(tmpdir, "hapA"),
+            "--haplotype-b-out-prefix",
+            join(tmpdir, "hapB"),
+            "--unclassified-out-prefix",
+            join(tmpdir, "hapU"),
+        ],
+    ):
+        with pytest.raises(SystemExit) as excinfo:
+            main()
+
+    assert "out of sync" in str(excinfo.value.code)
+
+
+def test_classify_by_kmers_no_reads():
+    with patch(
+        "sys.argv",
+        [
+            "classify-by-kmers",
+            join(dirname(__file__), "data", "hapA.txt"),
+            join(dirname(__file__), "data", "hapB.txt"),
+        ],
+    ):
+        with pytest.raises(SystemExit) as excinfo:
+            main()
+
+    assert excinfo.value.code == 2
+
+
+def test_classify_by_kmers_min_base_quality(capsys, tmpdir):
+    def run(reads):
+        with patch(
+            "sys.argv",
+            [
+                "classify-by-kmers",
+                join(dirname(__file__), "data", reads),
+                join(dirname(__file__), "data", "hapA.txt"),
+                join(dirname(__file__), "data", "hapB.txt"),
+                "--min-base-quality",
+                "40",
+                "--report",
+                "-",
+                "--haplotype-a-out-prefix",
+                join(tmpdir, "hapA"),
+                "--haplotype-b-out-prefix",
+                join(tmpdir, "hapB"),
+                "--unclassified-out-prefix",
+                join(tmpdir, "hapU"),
+            ],
+        ):
+            main()
+
+    run("test.ccs.fastq.gz")
+    lines = [line.split("\t") for line in capsys.readouterr()[0].splitlines()[1:]]
+    # every read has some bases below Q40, and the k-mers found in the first
+    # read without --min-base-quality overlap them
+    assert [line[2:4] for line in lines] == [["0", "0"], ["0", "2"], ["0", "0"]]
+    assert [line[9] for line in lines] == ["U", "B", "U"]
+    assert all(float(line[8]) > 0 for line in lines)
+
+    with pytest.raises(SystemExit) as excinfo:
+        run("test.fa")
+    assert "no quality scores" in str(excinfo.value.code)
+
+
+def test_classify_by_kmers_quality_encoding(capsys, tmpdir):
+    with gzip.open(join(dirname(__file__), "data", "test.ccs.fastq.gz"), "rt") as fp:
+        reads = list(readfq(fp))
+    # the same qualities, capped at 40 to fit in Phred+64, at each offset
+    capped = {
+        offset: [
+            Read(
+                read.name,
+                read.seq,
+                "".join(chr(min(ord(char) - 33, 40) + offset) for char in read.qual),
+                read.desc,
+            )
+            for read in reads
+        ]
+        for offset in [33, 64]
+    }
+    for offset, offset_reads in capped.items():
+        with open(join(tmpdir, f"reads{offset}.fastq"), "w") as fp:
+            for read in offset_reads:
+                read.print(file=fp)
+
+    def run(offset, *extra_args):
+        argv = [
+            "classify-by-kmers",
+            join(tmpdir, f"reads{offset}.fastq"),
+            join(dirname(__file__), "data", "hapA.txt"),
+            join(dirname(__file__), "data", "hapB.txt"),
+            "--min-base-quality",
+            "30",
+            "--report",
+            join(tmpdir, "report.tsv"),
+            "--haplotype-a-out-prefix",
+            join(tmpdir, "hapA"),
+            "--haplotype-b-out-prefix",
+            join(tmpdir, "hapB"),
+            "--unclassified-out-prefix",
+            join(tmpdir, "hapU"),
+            "--no-gzip-output",
+            *extra_args,
+        ]
+        with patch("sys.argv", argv):
+            main()
+        capsys.readouterr()
+        written = []
+        for hap in ["hapA", "hapB", "hapU"]:
+            with open(join(tmpdir, f"{hap}.fastq")) as fp:
+                written += list(readfq(fp))
+        with open(join(tmpdir, "report.tsv")) as fp:
+            report = fp.read().replace(f"reads{offset}.fastq", "reads.fastq")
+        return report, sorted(written, key=lambda read: read.name)
+
+    expected = run(33)
+    assert expected[1] == sorted(capped[33], key=lambda read: read.name)
+    # Phred+64 is detected or given, and written out as Phred+33
+    assert run(64) == expected
+    assert run(64, "--quality-encoding", "phred64") == expected
+    # given as Phred+33, the qualities are taken as they are
+    report, written = run(64, "--quality-encoding", "phred33")
+    assert written == sorted(capped[64], key=lambda read: read.name)
+    assert report != expected[0]
+
+
+def test_classify_by_kmers_rna(capsys, tmpdir):
+    with gzip.open(join(dirname(__file__), "data", "test.ccs.fastq.gz"), "rt") as fp:
+        reads = list(readfq(fp))
+    with open(join(tmpdir, "dna.fa"), "w") as fp:
+        for read in reads:
+            Read(read.name, read.seq, None, read.desc).print(file=fp)
+    with open(join(tmpdir, "rna.fa"), "w") as fp:
+        for read in reads:
+            Read(read.name, read.seq.replace("T", "U"), None, read.desc).print(file=fp)
+
+    def run(reads_name, *extra_args):
+        argv = [
+            "classify-by-kmers",
+            join(tmpdir, reads_name),
+            join(dirname(__file__), "data", "hapA.txt"),
+            join(dirname(__file__), "data", "hapB.txt"),
+            "--report",
+            join(tmpdir, "report.tsv"),
+            "--haplotype-a-out-prefix",
+            join(tmpdir, "hapA"),
+            "--haplotype-b-out-prefix",
+            join(tmpdir, "hapB"),
+            "--unclassified-out-prefix",
+            join(tmpdir, "hapU"),
+            "--no-gzip-output",
+            *extra_args,
+        ]
+        with patch("sys.argv", argv):
+            main()
+        _, err = capsys.readouterr()
+        with open(join(tmpdir, "report.tsv")) as fp:
+            report = fp.read().replace(reads_name, "reads.fa")
+        return err, report
+
+    dna_err, dna_report = run("dna.fa")
+    assert "looks like RNA" not in dna_err
+    # the U's are skipped like N's, so nothing is classified
+    rna_err, rna_report = run("rna.fa")
+    assert "looks like RNA" in rna_err
+    assert [line.split("\t")[9] for line in rna_report.splitlines()[1:]] == ["U"] * 3
+    # with --rna, the reads are classified as if they were DNA, and written so
+    rna_err, rna_report = run("rna.fa", "--rna")
+    assert "looks like RNA" not in rna_err
+    assert rna_report == dna_report
+    with open(join(tmpdir, "hapB.fa")) as fp:
+        assert [read.seq for read in readfq(fp)] == [reads[1].seq]
+
+
+def test_classify_by_kmers_compression_level(tmpdir):
+    def run(out_dir, *extra_args):
+        with patch(
+            "sys.argv",
+            [
+                "classify-by-kmers",
+                join(dirname(__file__), "data", "test.ccs.fastq.gz"),
+                join(dirname(__file__), "data", "hapA.txt"),
+                join(dirname(__file__), "data", "hapB.txt"),
+                "--haplotype-a-out-prefix",
+                join(tmpdir, f"{out_dir}_hapA"),
+                "--haplotype-b-out-prefix",
+                join(tmpdir, f"{out_dir}_hapB"),
+                "--unclassified-out-prefix",
+                join(tmpdir, f"{out_dir}_hapU"),
+                *extra_args,
+            ],
+        ):
+            main()
+
+    run("plain", "--no-gzip-output")
+    for level in ["1", "9"]:
+        run(level, "--compression-level", level)
+        for hap in ["hapA", "hapB", "hapU"]:
+            with gzip.open(join(tmpdir, f"{level}_{hap}.fastq.gz"), "rt") as fp:
+                compressed = fp.read()
+            with open(join(tmpdir, f"plain_{hap}.fastq")) as fp:
+                assert compressed == fp.read()
+
+    with pytest.raises(SystemExit):
+        run("bad", "--compression-level", "10")
+
+
+def test_classify_by_kmers_names_only(tmpdir):
+    with patch(
+        "sys.argv",
+        [
+            "classify-by-kmers",
+            join(dirname(__file__), "data", "test.ccs.fastq.gz"),
+            join(dirname(__file__), "data", "hapA.txt"),
+            join(dirname(__file__), "data", "hapB.txt"),
+            "--names-only",
+            "--report",
+            join(tmpdir, "report.tsv"),
+            "--haplotype-a-out-prefix",
+            join(tmpdir, "hapA"),
+            "--haplotype-b-out-prefix",
+            join(tmpdir, "hapB"),
+            "--unclassified-out-prefix",
+            join(tmpdir, "hapU"),
+        ],
+    ):
+        main()
+
+    # no reads are written, only their names
+    assert sorted(listdir(tmpdir)) == ["hapA.txt", "hapB.txt", "hapU.txt", "report.tsv"]
+    names = {
+        hap: open(join(tmpdir, f"{hap}.txt")).read().splitlines()
+        for hap in ["hapA", "hapB", "hapU"]
+    }
+    assert names == {
+        "hapA": ["m64234e_220609_193909/2/ccs"],
+        "hapB": ["m64234e_220609_193909/3/ccs"],
+        "hapU": ["m64234e_220609_193909/6/ccs"],
+    }
+    assert len(open(join(tmpdir, "report.tsv")).read().splitlines()) == 4
+
+
+@pytest.mark.parametrize("input_format", ["fastq", "fa"])
+@pytest.mark.parametrize("output_format", ["auto", "fasta", "fastq"])
+@pytest.mark.parametrize("gzipped", [False, True])
+def test_classify_by_kmers_output_format(
+    capsys, tmpdir, input_format, output_format, gzipped
+):
+    with gzip.open(join(dirname(__file__), "data", "test.ccs.fastq.gz"), "rt") as fp:
+        reads = list(readfq(fp))
+    reads_path = join(tmpdir, f"reads.{input_format}" + (".gz" if gzipped else ""))
+    with (gzip.open if gzipped else open)(reads_path, "wt") as fp:
+        for read in reads:
+            if input_format == "fa":
+                read = Read(read.name, read.seq, None, read.desc)
+            read.print(file=fp)
+
+    argv = [
+        "classify-by-kmers",
+        reads_path,
+        join(dirname(__file__), "data", "hapA.txt"),
+        join(dirname(__file__), "data", "hapB.txt"),
+        "--output-format",
+        output_format,
+        "--haplotype-a-out-prefix",
+        join(tmpdir, "hapA"),
+        "--haplotype-b-out-prefix",
+        join(tmpdir, "hapB"),
+        "--unclassified-out-prefix",
+        join(tmpdir, "hapU"),
+    ]
+    if not gzipped:
+        argv.append("--no-gzip-output")
+    if output_format == "fastq" and input_format == "fa":
+        argv += ["--fake-quality", "20"]
+    with patch("sys.argv", argv):
+        main()
+    capsys.readouterr()
+
+    extension = {"auto": input_format, "fasta": "fa", "fastq": "fastq"}[output_format]
+    written = []
+    for hap in ["hapA", "hapB", "hapU"]:
+        out_path = join(tmpdir, f"{hap}.{extension}" + (".gz" if gzipped else ""))
+        with seq.open_text_read(out_path) as fp:
+            written += list(readfq(fp))
+    assert sorted(read.name for read in written) == sorted(read.name for read in reads)
+
+    by_name = {read.name: read for read in reads}
+    for read in written:
+        original = by_name[read.name]
+        assert (read.seq, read.desc) == (original.seq, original.desc)
+        if output_format == "fasta" or (output_format, input_format) == ("auto", "fa"):
+            assert read.qual is None
+        elif input_format == "fa":
+            # quality 20 is 5 in Phred+33
+            assert read.qual == "5" * len(read.seq)
+        else:
+            # quality strings are copied exactly
+            assert read.qual == original.qual
+
+
+@pytest.mark.parametrize(
+    "args, message",
+    [
+        (
+            ["--output-format", "fasta", "--fake-quality", "20"],
+            "--fake-quality needs fastq output",
+        ),
+        (["--output-format", "fastq", "--fake-quality", "94"], "from 0 to 93, not 94"),
+        (["--output-format", "fasta", "--names-only"], "cannot be used with --names"),
+    ],
+)
+def test_classify_by_kmers_output_format_invalid(capsys, args, message):
+    with patch("sys.argv", ["classify-by-kmers", "reads.fq", "a.txt", "b.txt", *args]):
+        with pytest.raises(SystemExit):
+            main()
+    _, err = capsys.readouterr()
+    assert message in err
+
+
+def test_classify_by_kmers_mask_adapters(capsys, tmpdir):
+    rng = random.Random(3)
+    adapter = "AGATCGGAAGAGCACACGTCTGAACTCCAGTCAC"
+    read = Read("read1", "".join(rng.choices("ACGT", k=200)) + adapter, None)
+    # a haplotype A k-mer spanning the start of the adapter, and a haplotype
+    # B one in the rest of the read
+    for hap, kmer in [("hapA", read.seq[195:206]), ("hapB", read.seq[50:61])]:
+        with open(join(tmpdir, f"{hap}.txt"), "w") as fp:
+            print(kmer, file=fp)
+    with open(join(tmpdir, "reads.fa"), "w") as fp:
+        read.print(file=fp)
+    with open(join(tmpdir, "adapters.fa"), "w") as fp:
+        print(f">truseq\n{adapter}", file=fp)
+
+    outputs = []
+    for extra_args in [[], ["--mask-adapters", join(tmpdir, "adapters.fa")]]:
+        argv = [
+            "classify-by-kmers",
+            join(tmpdir, "reads.fa"),
+            join(tmpdir, "hapA.txt"),
+            join(tmpdir, "hapB.txt"),
+            "--report",
+            join(tmpdir, "report.tsv"),
+            "--haplotype-a-out-prefix",
+            join(tmpdir, "hapA"),
+            "--haplotype-b-out-prefix",
+            join(tmpdir, "hapB"),
+            "--unclassified-out-prefix",
+            join(tmpdir, "hapU"),
+            "--no-gzip-output",
+            *extra_args,
+        ]
+        with patch("sys.argv", argv):
+            main()
+        _, err = capsys.readouterr()
+        with open(join(tmpdir, "report.tsv")) as report:
+            lines = [line.rstrip("\n").split("\t") for line in report]
+        outputs.append((err, lines[1]))
+
+    (unmasked_err, unmasked), (masked_err, masked) = outputs
+    assert unmasked[2:4] + unmasked[9:10] == ["1", "1", "U"]
+    assert "adapter bases" not in unmasked_err
+    assert masked[2:4] + masked[9:10] == ["0", "1", "B"]
+    assert float(masked[8]) > float(unmasked[8])
+    assert f"Masked {len(adapter)} adapter bases." in masked_err
+    # the read written has its adapter
+    with open(join(tmpdir, "hapB.fa")) as fp:
+        assert list(readfq(fp)) == [read]
+
+
+@pytest.mark.parametrize(
+    "args,message",
+    [
+        (["--adapter-search-len", "50"], "only used with --mask-adapters"),
+        (["--adapter-mismatches", "0"], "only used with --mask-adapters"),
+        (["--mask-adapters", "a.fa", "--adapter-search-len=-1"], "negative"),
+        (["--mask-adapters", "a.fa", "--adapter-mismatches", "2"], "invalid choice"),
+    ],
+)
+def test_classify_by_kmers_mask_adapters_invalid(capsys, args, message):
+    with patch("sys.argv", ["classify-by-kmers", "reads.fq", "a.txt", "b.txt", *args]):
+        with pytest.raises(SystemExit):
+            main()
+    _, err = capsys.readouterr()
+    assert message in err
+
+
+@pytest.mark.parametrize("report_name", ["report.tsv", "report.tsv.gz", "-"])
+def test_classify_by_kmers_report(capsys, tmpdir, report_name):
+    report_path = report_name if report_name == "-" else join(tmpdir, report_name)
+    reads_path = join(dirname(__file__), "data", "test.ccs.fastq.gz")
+    with patch(
+        "sys.argv",
+        [
+            "classify-by-kmers",
+            reads_path,
+            join(dirname(__file__), "data", "hapA.txt"),
+            join(dirname(__file__), "data", "hapB.txt"),
+            "--report",
+            report_path,
+            "--haplotype-a-out-prefix",
+            join(tmpdir, "hapA"),
+            "--haplotype-b-out-prefix",
+            join(tmpdir, "hapB"),
+            "--unclassified-out-prefix",
+            join(tmpdir, "hapU"),
+        ],
+    ):
+        main()
+
+    out, _ = capsys.readouterr()
+    if report_name == "-":
+        report = out
+    elif report_name.endswith(".gz"):
+        report = gzip.open(report_path, "rt").read()
+    else:
+        report = open(report_path).read()
+
+    lines = [line.split("\t") for line in report.splitlines()]
+    assert lines[0] == [
+        "read_id",
+        "length",
+        "hap_a_hits",
+        "hap_b_hits",
+        "hap_a_fraction",
+        "hap_b_fraction",
+        "hap_a_per_kb",
+        "hap_b_per_kb",
+        "skipped_fraction",
+        "bin",
+        "rescued",
+        "early_exit",
+        "file",
+    ]
+    assert len(lines) == 4
+    assert lines[1:] == [
+        [
+            "m64234e_220609_193909/2/ccs",
+            "20288",
+            "4",
+            "1",
+            "0.000197355",
+            "4.93389e-05",
+            "0.197355",
+            "0.0493389",
+            "0",
+            "A",
+            "0",
+            "0",
+            reads_path,
+        ],
+        [
+            "m64234e_220609_193909/3/ccs",
+            "9808",
+            "0",
+            "2",
+            "0",
+            "0.000204332",
+            "0",
+            "0.204332",
+            "0",
+            "B",
+            "0",
+            "0",
+            reads_path,
+        ],
+        [
+            "m64234e_220609_193909/6/ccs",
+            "14017",
+            "0",
+            "0",
+            "0",
+            "0",
+            "0",
+            "0",
+            "0",
+            "U",
+            "0",
+            "0",
+            reads_path,
+        ],
+    ]
+
+
+def test_classify_by_kmers_summary_json(capsys, tmpdir):
+    run_main(tmpdir, "--summary-json", join(tmpdir, "summary.json"))
+
+    with open(join(tmpdir, "summary.json")) as summary_file:
+        summary = json.load(summary_file)
+
+    assert summary["total_reads"] == 3
+    assert summary["total_bases"] == 20288 + 9808 + 14017
+    assert summary["unknown_fraction"] == pytest.approx(1 / 3)
+    assert summary["bins"]["B"]["reads"] == 1
+    assert summary["bins"]["B"]["bases"] == 9808
+    assert summary["bins"]["U"]["mean_hap_a_score"] == 0
+    assert summary["bins"]["U"]["median_hap_b_score"] == 0
+    assert summary["bins"]["A"]["mean_hap_a_score"] > 0
+
+    # the stderr table is made from the same numbers
+    _, err = capsys.readouterr()
+    for bin_name, bin_summary in summary["bins"].items():
+        assert f"{bin_name}\t{bin_summary['reads']}\t{bin_summary['bases']}\n" in err
+
+
+def test_classify_by_kmers_subtract_shared(capsys, tmpdir):
+    with open(join(tmpdir, "hapA.txt"), "w") as hap_a_file:
+        print("AAAAC\nCCCCG\nGATTA", file=hap_a_file)
+    with open(join(tmpdir, "hapB.txt"), "w") as hap_b_file:
+        print("GGGGT\nTTTTA\nGATTA", file=hap_b_file)
+    with open(join(tmpdir, "reads.fasta"), "w") as reads_file:
+        print(">read1\nCCCCGGATTA\n>read2\nGGGGTGATTA", file=reads_file)
+
+    # without subtracting, shared k-mers are counted for haplotype A
+    for args, bins in [([], ["A", "U"]), (["--subtract-shared", "-v"], ["A", "B"])]:
+        run_main(
+            tmpdir,
+            *args,
+            reads=join(tmpdir, "reads.fasta"),
+            hap_a=join(tmpdir, "hapA.txt"),
+            hap_b=join(tmpdir, "hapB.txt"),
+        )
+
+        out, err = capsys.readouterr()
+        assert [line.split("\t")[1] for line in out.splitlines()] == bins
+
+    assert "Removed 1 k-mers found in both haplotypes" in err
+    assert (
+        f"WARNING: The k-mers in {join(tmpdir, 'hapA.txt')} are 5-mers, which turn up "
+        "by chance in too many reads" in err
+    )
+
+
+def test_classify_by_kmers_subtract_shared_all(tmpdir):
+    hap_a_path = join(dirname(__file__), "data", "hapA.txt")
+    with pytest.raises(SystemExit) as excinfo:
+        run_main(tmpdir, "--subtract-shared", hap_a=hap_a_path, hap_b=hap_a_path)
+    num_kmers = len(kmers.KmerSet.from_file(hap_a_path))
+    assert excinfo.value.code == (
+        f"Error: no k-mers left in haplotype A after removing the {num_kmers} "
+        "shared ones"
+    )
+
+
+def test_classify_by_kmers_rescue(capsys, tmpdir):
+    run_main(
+        tmpdir,
+        "--report",
+        join(tmpdir, "report.tsv"),
+        "--summary-json",
+        join(tmpdir, "summary.json"),
+        "--min-hits",
+        "3",
+        "--rescue",
+    )
+
+    out, err = capsys.readouterr()
+    # the second read only has 2 hits, so it takes the rescue to classify
+    assert [line.split("\t")[1] for line in out.splitlines()] == ["A", "B", "U"]
+    assert "Rescued 0 reads into haplotype A and 1 into haplotype B." in err
+    lines = [line.rstrip("\n").split("\t") for line in open(join(tmpdir, "report.tsv"))]
+    assert [line[10] for line in lines] == ["rescued", "0", "1", "0"]
+    with gzip.open(join(tmpdir, "hapB.fastq.gz"), "rt") as hap_b_file:
+        assert [read.name for read in readfq(hap_b_file)] == [
+            "m64234e_220609_193909/3/ccs"
+        ]
+    with open(join(tmpdir, "summary.json")) as summary_file:
+        bins = json.load(summary_file)["bins"]
+    assert (bins["A"]["primary_reads"], bins["A"]["rescued_reads"]) == (1, 0)
+    assert (bins["B"]["primary_reads"], bins["B"]["rescued_reads"]) == (0, 1)
+
+
+def test_classify_by_kmers_early_exit(capsys, tmpdir):
+    outputs = []
+    for extra_args in [[], ["--early-exit", "--early-exit-margin", "2"]]:
+        run_main(tmpdir, "--report", join(tmpdir, "report.tsv"), *extra_args)
+        out, err = capsys.readouterr()
+        with open(join(tmpdir, "report.tsv")) as report:
+            lines = [line.rstrip("\n").split("\t") for line in report]
+        outputs.append((out, lines))
+
+    (full_out, full_lines), (early_out, early_lines) = outputs
+    assert [line.split("\t")[1] for line in early_out.splitlines()] == [
+        line.split("\t")[1] for line in full_out.splitlines()
+    ]
+    assert [line[9] for line in early_lines] == [line[9] for line in full_lines]
+    assert [line[11] for line in full_lines] == ["early_exit", "0", "0", "0"]
+    num_early_exits = sum(line[11] == "1" for line in early_lines[1:])
+    assert f"Stopped counting early for {num_early_exits} reads." in err
+    for full_line, early_line in zip(full_lines[1:], early_lines[1:]):
+        if early_line[11] == "0":
+            assert early_line == full_line
+        else:
+            # the hits are only those up to where it stopped
+            assert int(early_line[2]) <= int(full_line[2])
+            assert int(early_line[3]) <= int(full_line[3])
+
+
+def test_classify_by_kmers_bloom(capsys, tmpdir):
+    outputs = []
+    for extra_args in [[], ["--bloom", "--bloom-fpr", "1e-9"]]:
+        run_main(tmpdir, *extra_args)
+        outputs.append(capsys.readouterr()[0])
+
+    assert outputs[0] == outputs[1]
+
+
+def test_classify_by_kmers_bloom_subtract_shared(capsys, tmpdir):
+    with pytest.raises(SystemExit):
+        run_main(tmpdir, "--bloom", "--subtract-shared")
+
+    _, err = capsys.readouterr()
+    assert "--subtract-shared cannot be used with --bloom" in err
+
+
+def test_classify_by_kmers_weighted(capsys, tmpdir):
+    with open(join(tmpdir, "hapA.tsv"), "w") as hap_a_file:
+        print("AAAAC\t1\nCCCCG\t1\nACACA\t1", file=hap_a_file)
+    with open(join(tmpdir, "hapB.tsv"), "w") as hap_b_file:
+        print("GGGGT\t20\nTTTTA\t20\nCATGC\t20", file=hap_b_file)
+    with open(join(tmpdir, "reads.fasta"), "w") as reads_file:
+        print(">read1\nAAAACGTCCCCGAGACACAGAGGGGTGATTTTA", file=reads_file)
+
+    # the hap-A k-mers were only seen once, so they're outweighed
+    for args, read_bin in [([], "A"), (["--score", "weighted"], "B")]:
+        run_main(
+            tmpdir,
+            *args,
+            reads=join(tmpdir, "reads.fasta"),
+            hap_a=join(tmpdir, "hapA.tsv"),
+            hap_b=join(tmpdir, "hapB.tsv"),
+        )
+
+        out, _ = capsys.readouterr()
+        assert out.split("\t")[1] == read_bin
+
+    with pytest.raises(SystemExit, match="cannot weigh k-mers by count"):
+        run_main(tmpdir, "--score", "weighted", reads=join(tmpdir, "reads.fasta"))
+
+
+def test_classify_by_kmers_fuzzy(capsys, tmpdir):
+    with open(join(tmpdir, "hapA.txt"), "w") as hap_a_file:
+        print("ACGGATCCTAG\nCTTAGCAGGCA", file=hap_a_file)
+    with open(join(tmpdir, "hapB.txt"), "w") as hap_b_file:
+        print("GTCACTGAATC\nTTGCAAGCCGT", file=hap_b_file)
+    with open(join(tmpdir, "reads.fasta"), "w") as reads_file:
+        print(">read1\nACGGAACCTAGCTTAGCTGGCAGTCACAGAATC", file=reads_file)
+
+    for args, read_bin in [([], "U"), (["--fuzzy"], "A")]:
+        run_main(
+            tmpdir,
+            *args,
+            reads=join(tmpdir, "reads.fasta"),
+            hap_a=join(tmpdir, "hapA.txt"),
+            hap_b=join(tmpdir, "hapB.txt"),
+        )
+
+        out, _ = capsys.readouterr()
+        assert out.split("\t")[1] == read_bin
+
+
+def test_classify_by_kmers_kmer_step(capsys, tmpdir):
+    outputs = []
+    for args in [[], ["--kmer-step", "1"], ["--kmer-step", "4"]]:
+        run_main(tmpdir, *args)
+        out, _ = capsys.readouterr()
+        outputs.append(out)
+    assert outputs[0] == outputs[1]
+    assert outputs[2] != outputs[0]
+
+    with pytest.raises(SystemExit, match="kmer_step must be at least 1"):
+        run_main(tmpdir, "--kmer-step", "0")
+
+
+def test_classify_by_kmers_minimizer_window(capsys, tmpdir):
+    outputs = []
+    # with a window of 1, every k-mer is a minimizer
+    for args in [[], ["--minimizer-window", "1"]]:
+        run_main(tmpdir, *args)
+        out, _ = capsys.readouterr()
+        outputs.append(out)
+    assert outputs[0] == outputs[1]
+
+    with pytest.raises(SystemExit, match="minimizers can't be combined"):
+        run_main(tmpdir, "--minimizer-window", "10", "--fuzzy")
+
+
+def test_classify_by_kmers_sampling(capsys, tmpdir):
+    def run(*args):
+        run_main(tmpdir, *args)
+        out, _ = capsys.readouterr()
+        return out
+
+    # every 21-mer is a syncmer of 20-mers, as with a minimizer window of 1
+    outputs = [
+        run(*args)
+        for args in [
+            ["--sampling", "all"],
+            ["--sampling", "minimizer", "--minimizer-window", "1"],
+            ["--sampling", "syncmer", "--syncmer-size", "20"],
+        ]
+    ]
+    assert outputs[0] == outputs[1] == outputs[2]
+    run("--sampling", "syncmer")
+
+    for args, message in [
+        (["--minimizer-window", "5", "--sampling", "all"], "--sampling minimizer"),
+        (["--syncmer-size", "5"], "--syncmer-size needs --sampling syncmer"),
+    ]:
+        with pytest.raises(SystemExit):
+            run(*args)
+        _, err = capsys.readouterr()
+        assert message in err
+    with pytest.raises(SystemExit, match="syncmer_size must be less than k, 21"):
+        run("--sampling", "syncmer", "--syncmer-size", "21")
+
+
+def test_classify_by_kmers_weighted_bloom(capsys, tmpdir):
+    with pytest.raises(SystemExit):
+        run_main(tmpdir, "--bloom", "--score", "weighted")
+
+    _, err = capsys.readouterr()
+    assert "--bloom cannot be used with --score weighted" in err
+
+
+def test_classify_by_kmers_multiple_files(capsys, tmpdir):
+    fastq_path = CCS_READS
+    # the same reads as fasta, renamed so they can be told apart
+    fasta_path = join(tmpdir, "reads.fa")
+    with open(fasta_path, "w") as fp:
+        for read in readfq(gzip.open(fastq_path, "rt")):
+            print(f">fasta_{read.name}\n{read.seq}", file=fp)
+    fofn_path = join(tmpdir, "reads.fofn")
+    with open(fofn_path, "w") as fp:
+        print("# more reads", fasta_path, "", sep="\n", file=fp)
+
+    run_main(
+        tmpdir,
+        "--reads-fofn",
+        fofn_path,
+        "--report",
+        join(tmpdir, "report.tsv"),
+        reads=fastq_path,
+    )
+
+    _, err = capsys.readouterr()
+    assert "A\t2\t40576\n" in err
+    lines = [line.split("\t") for line in open(join(tmpdir, "report.tsv"))]
+    sources = [(line[0].startswith("fasta_"), line[-1].rstrip()) for line in lines]
+    assert sources[1:] == [(False, fastq_path)] * 3 + [(True, fasta_path)] * 3
+    assert [line[9] for line in lines[1:]] == ["A", "B", "U"] * 2
+
+
+@pytest.mark.parametrize("fastq_first", [False, True])
+def test_classify_by_kmers_mixed_formats(capsys, tmpdir, fastq_first):
+    fastq_path = CCS_READS
+    fasta_path = join(tmpdir, "reads.fa")
+    with open(fasta_path, "w") as fp:
+        for read in readfq(gzip.open(fastq_path, "rt")):
+            print(f">fasta_{read.name}\n{read.seq}", file=fp)
+    reads_paths = [fastq_path, fasta_path] if fastq_first else [fasta_path, fastq_path]
+
+    options = ["--no-gzip-output", "--fake-quality", "20"]
+    run_main(tmpdir, *options, reads=reads_paths)
+    capsys.readouterr()
+
+    # every output file is all fastq, with the fasta reads given qualities
+    written = []
+    for hap in ["hapA", "hapB", "hapU"]:
+        assert not exists(join(tmpdir, f"{hap}.fa"))
+        with open(join(tmpdir, f"{hap}.fastq")) as fp:
+            written += list(seq.read_fastq(fp))
+    assert len(written) == 6
+    for read in written:
+        if read.name.startswith("fasta_"):
+            assert read.qual == "5" * len(read.seq)
+
+    # fasta reads alone are written as fasta
+    run_main(tmpdir, *options, reads=fasta_path)
+    for hap in ["hapA", "hapB", "hapU"]:
+        with open(join(tmpdir, f"{hap}.fa")) as fp:
+            assert all(read.qual is None for read in seq.read_fasta(fp))
+
+
+def test_classify_by_kmers_missing_files(tmpdir):
+    missing = [join(tmpdir, "missing_1.fq"), join(tmpdir, "missing_2.fq")]
+    with pytest.raises(SystemExit) as excinfo:
+        run_main(tmpdir, reads=[missing[0], CCS_READS, missing[1]])
+
+    assert str(excinfo.value) == (
+        f"Error: these reads files don't exist:\n  {missing[0]}\n  {missing[1]}"
+    )
+
+
+@pytest.mark.parametrize("paired", [False, True])
+def test_classify_by_kmers_subsample(capsys, tmpdir, paired):
+    reads_path = join(dirname(__file__), "data", "test.ccs.fastq.gz")
+    # enough reads that some are picked and some aren't
+    many_reads_path = join(tmpdir, "reads.fq")
+    reads = list(readfq(gzip.open(reads_path, "rt")))
+    with open(many_reads_path, "w") as fp:
+        for i in range(150):
+            read = reads[i % 3]
+            # interleaved mates, for paired mode
+            read.name = f"read{i // 2}/{1 + i % 2}"
+            read.print(file=fp)
+
+    reads_args = ["--interleaved", many_reads_path] if paired else [many_reads_path]
+    picked = []
+    for threads in ["1", "3"]:
+        run_main(
+            tmpdir,
+            "--subsample",
+            "0.3",
+            "--seed",
+            "7",
+            "--threads",
+            threads,
+            "--batch-size",
+            "4",
+            "--summary-json",
+            join(tmpdir, "summary.json"),
+            "--names-only",
+            reads=reads_args,
+        )
+
+        names = []
+        for prefix in ["hapA", "hapB", "hapU"]:
+            names += open(join(tmpdir, f"{prefix}.txt")).read().split()
+        picked.append(sorted(names))
+        summary = json.load(open(join(tmpdir, "summary.json")))
+        assert summary["reads_seen"] == 150
+        assert summary["total_reads"] == len(names) * (2 if paired else 1)
+        _, err = capsys.readouterr()
+        assert f"Classified {summary['total_reads']} of the 150 reads seen" in err
+
+    assert picked[0] == picked[1]
+    assert 0 < len(picked[0]) < (75 if paired else 150)
+
+
+@pytest.mark.parametrize(
+    "args, message",
+    [
+        (["--subsample", "0"], "--subsample must be more than 0"),
+        (["--seed", str(2**63)], f"--seed must be a 64-bit integer, not {2**63}"),
+        ([f"--seed={-(2**63) - 1}"], "--seed must be a 64-bit integer"),
+    ],
+)
+def test_classify_by_kmers_subsample_invalid(capsys, tmpdir, args, message):
+    with pytest.raises(SystemExit):
+        run_main(tmpdir, *args, reads="reads.fq", hap_a="a.txt", hap_b="b.txt")
+    _, err = capsys.readouterr()
+    assert message in err
+
+
+@pytest.mark.parametrize("discard", [False, True])
+def test_classify_by_kmers_filters(capsys, tmpdir, discard):
+    run_main(
+        tmpdir,
+        "--min-length",
+        "9000",
+        "--max-length",
+        "20000",
+        "--min-mean-quality",
+        "30",
+        *(["--discard-filtered"] if discard else []),
+        "--summary-json",
+        join(tmpdir, "summary.json"),
+        "--no-gzip-output",
+        "--filtered-out-prefix",
+        join(tmpdir, "filtered"),
+    )
+
+    summary = json.load(open(join(tmpdir, "summary.json")))
+    assert summary["reads_seen"] == 3
+    assert summary["total_reads"] == 1
+    assert summary["filtered"] == {"too_long": 1, "low_quality": 1}
+    _, err = capsys.readouterr()
+    assert "1 too long, 1 low quality" in err
+    if discard:
+        assert "filtered.fastq" not in listdir(tmpdir)
+    else:
+        filtered = list(readfq(open(join(tmpdir, "filtered.fastq"))))
+        assert [read.name for read in filtered] == [
+            "m64234e_220609_193909/2/ccs",
+            "m64234e_220609_193909/6/ccs",
+        ]
+
+
+def test_classify_by_kmers_filters_pairs(tmpdir):
+    reads_path = join(dirname(__file__), "data", "test.ccs.fastq.gz")
+    reads = list(readfq(gzip.open(reads_path, "rt")))
+    interleaved_path = join(tmpdir, "reads.fq")
+    with open(interleaved_path, "w") as fp:
+        # only the second mate of the second pair is too short
+        for i, (mate_1, mate_2) in enumerate([(0, 2), (2, 1)]):
+            for mate, read_index in enumerate([mate_1, mate_2], start=1):
+                read = reads[read_index]
+                print(f"@pair{i}/{mate}\n{read.seq}\n+\n{read.qual}", file=fp)
+
+    run_main(
+        tmpdir,
+        "--min-length",
+        "10000",
+        "--summary-json",
+        join(tmpdir, "summary.json"),
+        "--no-gzip-output",
+        "--filtered-out-prefix",
+        join(tmpdir, "filtered"),
+        reads=["--interleaved", interleaved_path],
+    )
+
+    summary = json.load(open(join(tmpdir, "summary.json")))
+    assert summary["filtered"] == {"too_short": 2}
+    assert summary["total_reads"] == 2
+    for suffix in ["_R1", "_R2"]:
+        filtered = list(readfq(open(join(tmpdir, f"filtered{suffix}.fq"))))
+        assert [read.name for read in filtered] == [f"pair1/{suffix[-1]}"]
+
+
+def write_test_sam(tmpdir):
+    """Write the test reads as unaligned SAM, with a secondary alignment after
+    the first read that should be skipped, returning the reads and the path"""
+    reads_path = join(dirname(__file__), "data", "test.ccs.fastq.gz")
+    reads = list(readfq(gzip.open(reads_path, "rt")))
+    sam_path = join(tmpdir, "reads.sam")
+    with open(sam_path, "w") as fp:
+        print("@HD\tVN:1.6\tSO:unknown", file=fp)
+        for i, read in enumerate(reads):
+            fields = [read.name, 4, "*", 0, 0, "*", "*", 0, 0, read.seq, read.qual]
+            print(*fields, sep="\t", file=fp)
+            if i == 0:
+                fields = [read.name, 256, "*", 0, 0, "*", "*", 0, 0, "*", "*"]
+                print(*fields, sep="\t", file=fp)
+    return reads, sam_path
+
+
+def test_classify_by_kmers_sam(capsys, tmpdir):
+    reads, sam_path = write_test_sam(tmpdir)
+
+    run_main(tmpdir, "--no-gzip-output", reads=sam_path)
+
+    _, err = capsys.readouterr()
+    assert "A\t1\t20288\n" in err
+    # the reads are written out as fastq, qualities and all
+    binned = []
+    for prefix in ["hapA", "hapB", "hapU"]:
+        binned.extend(readfq(open(join(tmpdir, prefix + ".fastq"))))
+    assert sorted((read.name, read.seq, read.qual) for read in binned) == sorted(
+        (read.name, read.seq, read.qual) for read in reads
+    )
+
+
+@pytest.mark.parametrize("out_name", ["tagged.sam", "tagged.bam"])
+def test_classify_by_kmers_tag_output(capsys, tmpdir, out_name):
+    if out_name.endswith(".bam"):
+        pysam = pytest.importorskip("pysam")
+    reads, sam_path = write_test_sam(tmpdir)
+    out_path = join(tmpdir, out_name)
+    run_main(tmpdir, "--tag-output", out_path, reads=sam_path)
+
+    out, err = capsys.readouterr()
+    assert "A\t1\t20288\n" in err
+    # the reads aren't split into files
+    assert sorted(listdir(tmpdir)) == sorted(["reads.sam", out_name])
+
+    # the name, HP, hA and hB of each record, with None for no tag
+    if out_name.endswith(".sam"):
+        with open(out_path) as fp:
+            lines = fp.read().splitlines()
+        header = [line for line in lines if line.startswith("@")]
+        records = [line.split("\t") for line in lines if not line.startswith("@")]
+        tags = [
+            (
+                fields[0],
+                *(
+                    next((int(f[5:]) for f in fields[11:] if f[:2] == tag), None)
+                    for tag in ["HP", "hA", "hB"]
+                ),
+            )
+            for fields in records
+        ]
+    else:
+        with pysam.AlignmentFile(out_path, "rb", check_sq=False) as bam:
+            header = [str(bam.header)]
+            tags = [
+                (
+                    record.query_name,
+                    *(
+                        record.get_tag(tag) if record.has_tag(tag) else None
+                        for tag in ["HP", "hA", "hB"]
+                    ),
+                )
+                for record in bam.fetch(until_eof=True)
+            ]
+    assert any("\tID:classify-by-kmers\t" in line for line in header)
+    assert tags == [
+        ("m64234e_220609_193909/2/ccs", 1, 4, 1),
+        # the secondary alignment is copied as it was
+        ("m64234e_220609_193909/2/ccs", None, None, None),
+        ("m64234e_220609_193909/3/ccs", 2, 0, 2),
+        ("m64234e_220609_193909/6/ccs", None, 0, 0),
+    ]
+
+
+@pytest.mark.parametrize("options", [["--min-length", "10"], ["--subsample", "0.001"]])
+def test_classify_by_kmers_tag_output_skipped(capsys, tmpdir, options):
+    sam_path = join(dirname(__file__), "data", "test.sam")
+    out_path = join(tmpdir, "tagged.sam")
+    run_main(
+        tmpdir, "--tag-output", out_path, "--discard-filtered", *options, reads=sam_path
+    )
+    capsys.readouterr()
+
+    # the reads left out are copied without tags, secondary records and all
+    with open(sam_path) as fp:
+        original = [line.rstrip("\n") for line in fp if not line.startswith("@")]
+    with open(out_path) as fp:
+        records = [line.rstrip("\n") for line in fp if not line.startswith("@")]
+    assert len(records) == len(original) == 5
+    assert [line.split("\t")[:11] for line in records] == [
+        line.split("\t")[:11] for line in original
+    ]
+    assert records[1:] == original[1:]
+
+
+@pytest.mark.parametrize("quiet", [False, True])
+def test_classify_by_kmers_progress_quiet(capsys, tmpdir, quiet):
+    run_main(tmpdir, "--progress", *(["--quiet"] if quiet else []))
+
+    _, err = capsys.readouterr()
+    # stderr isn't a terminal, so the progress is a line at the end
+    assert "3 reads, 0.0 Mbp" in err
+    assert "(A: 1, B: 1, U: 1)\n" in err
+    assert ("bin\treads\tbases" in err) != quiet
+
+
+@pytest.mark.parametrize("verbosity", [0, 1, 2])
+def test_classify_by_kmers_verbose(capsys, tmpdir, verbosity):
+    run_main(tmpdir, "--threads", "2", "--batch-size", "2", *["-v"] * verbosity)
+
+    _, err = capsys.readouterr()
+    assert ("INFO: Loaded 4 k-mers with k=21" in err) == (verbosity >= 1)
+    assert ("INFO: Reading reads from" in err) == (verbosity >= 1)
+    batches = [line for line in err.splitlines() if "Classified a batch" in line]
+    assert len(batches) == (2 if verbosity >= 2 else 0)
+
+
+def test_classify_by_kmers_skip_malformed(capsys, tmpdir):
+    with gzip.open(join(dirname(__file__), "data", "test.ccs.fastq.gz"), "rt") as fp:
+        lines = fp.readlines()
+    # cut the quality line off the second read
+    reads_path = join(tmpdir, "reads.fastq")
+    with open(reads_path, "w") as fp:
+        fp.writelines(lines[:7] + lines[8:])
+
+    summary_args = ["--summary-json", join(tmpdir, "summary.json")]
+    # by default, the first malformed record is an error
+    with pytest.raises(SystemExit, match="quality length"):
+        run_main(tmpdir, *summary_args, reads=reads_path)
+
+    with pytest.raises(SystemExit) as excinfo:
+        run_main(tmpdir, *summary_args, "--skip-malformed", reads=reads_path)
+    assert excinfo.value.code == 3
+
+    _, err = capsys.readouterr()
+    assert f"WARNING: Skipping a malformed record in {reads_path}" in err
+    assert "Skipped 1 malformed records." in err
+    assert "A\t1\t20288\n" in err
+    assert "U\t1\t14017\n" in err
+    with open(join(tmpdir, "summary.json")) as fp:
+        assert json.load(fp)["malformed"] == 1
+
+
+def test_classify_by_kmers_multiline_fastq(capsys, tmpdir):
+    fastq_path = CCS_READS
+    wrapped_path = join(tmpdir, "wrapped.fastq")
+    with open(wrapped_path, "w") as fp:
+        for read in readfq(gzip.open(fastq_path, "rt")):
+            seq_lines = [read.seq[i : i + 60] for i in range(0, len(read.seq), 60)]
+            qual_lines = [read.qual[i : i + 60] for i in range(0, len(read.qual), 60)]
+            header = f"@{seq.record_defline(read)}"
+            print(header, *seq_lines, "+", *qual_lines, sep="\n", file=fp)
+
+    with pytest.raises(SystemExit, match="fastq record 0"):
+        run_main(tmpdir, "--no-gzip-output", reads=wrapped_path)
+
+    makedirs(join(tmpdir, "wrapped"))
+    makedirs(join(tmpdir, "unwrapped"))
+    wrapped_args = ["--no-gzip-output", "--multiline-fastq"]
+    run_main(join(tmpdir, "wrapped"), *wrapped_args, reads=wrapped_path)
+    run_main(join(tmpdir, "unwrapped"), "--no-gzip-output", reads=fastq_path)
+    capsys.readouterr()
+    # the wrapped reads are binned the same as the four-line ones
+    for hap in ["hapA", "hapB", "hapU"]:
+        with open(join(tmpdir, "wrapped", f"{hap}.fastq")) as fp:
+            wrapped = fp.read()
+        with open(join(tmpdir, "unwrapped", f"{hap}.fastq")) as fp:
+            assert fp.read() == wrapped
+
+
+@pytest.mark.parametrize("policy", ["warn", "skip", "error"])
+def test_classify_by_kmers_check_duplicates(capsys, tmpdir, policy):
+    with gzip.open(join(dirname(__file__), "data", "test.ccs.fastq.gz"), "rt") as fq:
+        lines = fq.readlines()
+    # the first read again at the end
+    reads_path = join(tmpdir, "reads.fastq")
+    with open(reads_path, "w") as reads_file:
+        reads_file.writelines(lines + lines[:4])
+    args = ["--check-duplicates", "--on-duplicate", policy]
+    if policy == "error":
+        with pytest.raises(SystemExit) as exc_info:
+            run_main(tmpdir, *args, reads=reads_path)
+        assert "more than once" in str(exc_info.value)
+        return
+    run_main(tmpdir, *args, reads=reads_path)
+
+    out, err = capsys.readouterr()
+    names = [line.split("\t")[0] for line in out.splitlines()]
+    first_name = lines[0][1:].split()[0]
+    assert names.count(first_name) == (2 if policy == "warn" else 1)
+    assert len(names) == (4 if policy == "warn" else 3)
+    assert "Found 1 duplicate reads." in err
+
+
+def test_classify_by_kmers_check_duplicates_non_ascii(capsys, tmpdir):
+    reads_path = join(tmpdir, "reads.fastq")
+    with open(reads_path, "wb") as reads_file:
+        reads_file.write(b"@r\xe91\nACGTA\n+\nIIIII\n" * 2)
+    run_main(
+        tmpdir,
+        "--no-gzip-output",
+        "--check-duplicates",
+        "--on-duplicate",
+        "skip",
+        reads=reads_path,
+    )
+
+    _, err = capsys.readouterr()
+    assert "Found 1 duplicate reads." in err
+    with open(join(tmpdir, "hapU.fastq"), "rb") as fp:
+        assert fp.read() == b"@r\xe91\nACGTA\n+\nIIIII\n"
+
+
+class Preempted(Exception):
+    """The node a run was on going away"""
+
+
+@pytest.mark.parametrize("threads", ["1", "3"])
+def test_classify_by_kmers_checkpoint_resume(capsys, tmpdir, threads):
+    rng = random.Random(75)
+    with open(join(tmpdir, "hapA.txt"), "w") as hap_a_file:
+        print("AAAAC\nCCCCG", file=hap_a_file)
+    with open(join(tmpdir, "hapB.txt"), "w") as hap_b_file:
+        print("GGGGT\nTTTTA", file=hap_b_file)
+    reads_paths = [join(tmpdir, f"reads{i}.fastq.gz") for i in range(2)]
+    for reads_path in reads_paths:
+        with gzip.open(reads_path, "wt") as reads_file:
+            for i in range(300):
+                seq = "".join(rng.choices("ACGT", k=rng.randint(1, 60)))
+                print(f"@read{i}\n{seq}\n+\n{'I' * len(seq)}", file=reads_file)
+
+    def run(out_dir, *extra_args):
+        run_main(
+            out_dir,
+            "--threads",
+            threads,
+            "--batch-size",
+            "10",
+            "--min-length",
+            "10",
+            "--filtered-out-prefix",
+            join(out_dir, "filtered"),
+            "--report",
+            join(out_dir, "report.tsv"),
+            "--summary-json",
+            join(out_dir, "summary.json"),
+            *extra_args,
+            reads=reads_paths,
+            hap_a=join(tmpdir, "hapA.txt"),
+            hap_b=join(tmpdir, "hapB.txt"),
+        )
+        capsys.readouterr()
+
+    def outputs(out_dir):
+        contents = {}
+        for name in listdir(out_dir):
+            opener = gzip.open if name.endswith(".gz") else open
+            with opener(join(out_dir, name), "rt") as fp:
+                contents[name] = fp.read()
+        return contents
+
+    makedirs(join(tmpdir, "full"))
+    run(join(tmpdir, "full"))
+
+    # stop the run partway through the second file, after the reads of the
+    # segment since the last checkpoint were written
+    out_dir = join(tmpdir, "resumed")
+    makedirs(out_dir)
+    checkpoint_path = join(tmpdir, "checkpoint")
+    checkpoint_args = [
+        "--checkpoint",
+        checkpoint_path,
+        "--checkpoint-interval",
+        "0.0001s",
+        "--resume",
+    ]
+    save = Checkpointer.save
+
+    def save_until_preempted(self, file_index, records):
+        if file_index == 1 and records > 100:
+            raise Preempted()
+        save(self, file_index, records)
+
+    with patch.object(Checkpointer, "save", save_until_preempted):
+        with pytest.raises(Preempted):
+            run(out_dir, *checkpoint_args)
+    assert exists(checkpoint_path)
+    # the reads after the checkpoint were written too, which resuming takes
+    # back
+    assert outputs(out_dir)["report.tsv"].count("\n") > 300
+
+    run(out_dir, *checkpoint_args)
+    assert not exists(checkpoint_path)
+    resumed = outputs(out_dir)
+    full = outputs(join(tmpdir, "full"))
+    assert sorted(resumed) == sorted(full)
+    for name in full:
+        assert resumed[name] == full[name], name
+
+
+def test_classify_by_kmers_checkpoint_stdin(tmpdir):
+    with pytest.raises(SystemExit):
+        run_main(tmpdir, "--checkpoint", join(tmpdir, "checkpoint"), reads="-")
+
+
+def test_classify_by_kmers_bgzf(capsys, tmpdir):
+    run_main(
+        tmpdir,
+        "--report",
+        join(tmpdir, "report.tsv.gz"),
+        "--bgzf",
+        "--compression-threads",
+        "2",
+    )
+    capsys.readouterr()
+
+    num_reads = 0
+    for name in ["hapA.fastq.gz", "hapB.fastq.gz", "hapU.fastq.gz", "report.tsv.gz"]:
+        with open(join(tmpdir, name), "rb") as fp:
+            bgzf.check(fp)
+        if name != "report.tsv.gz":
+            with gzip.open(join(tmpdir, name), "rt") as fp:
+                num_reads += sum(1 for _ in readfq(fp))
+    assert num_reads == 3
+
+
+def test_classify_by_kmers_compression_threads_without_bgzf(tmpdir):
+    with pytest.raises(SystemExit):
+        run_main(tmpdir, "--compression-threads", "2")
+
+
+def test_classify_by_kmers_config(capsys, tmpdir):
+    data_dir = join(dirname(__file__), "data")
+    config_path = join(tmpdir, "run.toml")
+    with open(config_path, "w") as fp:
+        fp.write(
+            f"reads = [{json.dumps(join(data_dir, 'test.ccs.fastq.gz'))}]\n"
+            f"haplotype_a_kmers = {json.dumps(join(data_dir, 'hapA.txt'))}\n"
+            f"haplotype_b_kmers = {json.dumps(join(data_dir, 'hapB.txt'))}\n"
+            f"haplotype_a_out_prefix = {json.dumps(join(tmpdir, 'hapA'))}\n"
+            f"haplotype_b_out_prefix = {json.dumps(join(tmpdir, 'hapB'))}\n"
+            f"unclassified_out_prefix = {json.dumps(join(tmpdir, 'hapU'))}\n"
+            "min_hits = 2\n"
+            "score = 'count'\n"
+            "no_gzip_output = true\n"
+            "threads = 2\n"
+        )
+
+    # the command line overrides the config
+    argv = ["classify-by-kmers", "--config", config_path, "--min-hits", "3"]
+    with patch("sys.argv", [*argv, "--print-config"]):
+        with pytest.raises(SystemExit) as exit_info:
+            main()
+    assert exit_info.value.code == 0
+    printed, _ = capsys.readouterr()
+    resolved = tomllib.loads(printed)
+    assert resolved["min_hits"] == 3
+    assert resolved["threads"] == 2
+    assert resolved["score"] == "count"
+    assert resolved["reads"] == [join(data_dir, "test.ccs.fastq.gz")]
+    assert resolved["haplotype_a_kmers"] == join(data_dir, "hapA.txt")
+    assert "config" not in resolved and "print_config" not in resolved
+
+    # and the printed config runs the same as the command line
+    printed_path = join(tmpdir, "resolved.toml")
+    with open(printed_path, "w") as fp:
+        fp.write(printed)
+    with patch("sys.argv", argv):
+        main()
+    expected = {}
+    for name in ["hapA.fastq", "hapB.fastq", "hapU.fastq"]:
+        with open(join(tmpdir, name)) as fp:
+            expected[name] = fp.read()
+    with patch("sys.argv", ["classify-by-kmers", "--config", printed_path]):
+        main()
+    for name, contents in expected.items():
+        with open(join(tmpdir, name)) as fp:
+            assert fp.read() == contents
+    capsys.readouterr()
+
+
+@pytest.mark.parametrize(
+    "contents,message",
+    [
+        ("min_hit = 2\n", "unknown options in config: min_hit"),
+        ("min_hits = 'two'\n", "min_hits in config 'two' isn't a valid value"),
+        ("min_hits = true\n", "min_hits in config must be a string or number"),
+        ("fuzzy = 1\n", "fuzzy in config must be true or false"),
+        ("score = 'max'\n", "score in config must be one of count, weighted"),
+        ("reads = 'reads.fq'\n", "reads in config must be a list"),
+        ("haplotype_a_kmers = 'hapA.txt'\n", "both haplotype_a_kmers and"),
+        ("min_hits = \n", "isn't valid TOML"),
+    ],
+)
+def test_classify_by_kmers_config_invalid(capsys, tmpdir, contents, message):
+    config_path = join(tmpdir, "run.toml")
+    with open(config_path, "w") as fp:
+        fp.write(contents)
+    with pytest.raises(SystemExit):
+        run_main(tmpdir, "--config", config_path)
+
+    _, err = capsys.readouterr()
+    assert message in err
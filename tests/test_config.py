@@ -0,0 +1,97 @@
+import argparse
+import enum
+import os
+
+import pytest
+
+from trio_binning import config
+from trio_binning.config import tomllib
+
+
+class Color(enum.Enum):
+    RED = "red"
+    BLUE = "blue"
+
+    def __str__(self):
+        return self.value
+
+
+def make_parser():
+    parser = argparse.ArgumentParser()
+    parser.add_argument("files", nargs="*")
+    parser.add_argument("--size", type=int, default=1)
+    parser.add_argument("--fraction", type=float, default=0.5)
+    parser.add_argument("--name")
+    parser.add_argument("--color", type=Color, choices=list(Color), default=Color.RED)
+    parser.add_argument("--fast", action="store_true")
+    parser.add_argument("--no-order", dest="order", action="store_false")
+    parser.add_argument("-v", "--verbose", action="count", default=0)
+    return parser
+
+
+def test_config_defaults():
+    parser = make_parser()
+    defaults = config.config_defaults(
+        parser,
+        {
+            "files": ["a.fq", "b.fq"],
+            "size": 3,
+            "fraction": 1,
+            "color": "blue",
+            "fast": True,
+            "order": False,
+            "verbose": 2,
+        },
+    )
+    assert defaults == {
+        "files": ["a.fq", "b.fq"],
+        "size": 3,
+        "fraction": 1.0,
+        "color": Color.BLUE,
+        "fast": True,
+        "order": False,
+        "verbose": 2,
+    }
+    parser.set_defaults(**defaults)
+    args = parser.parse_args(["--size", "4", "c.fq"])
+    assert (args.files, args.size, args.color) == (["c.fq"], 4, Color.BLUE)
+
+
+@pytest.mark.parametrize(
+    "options,message",
+    [
+        ({"sizes": 3}, "unknown options in config: sizes"),
+        ({"size": 2.5}, "size in config 2.5 isn't a valid value"),
+        ({"name": 3}, "name in config must be a string"),
+        ({"verbose": True}, "verbose in config must be a count"),
+        ({"files": [["a.fq"]]}, "files in config must be a string or number"),
+        ({"help": True}, "unknown options in config: help"),
+    ],
+)
+def test_config_defaults_invalid(options, message):
+    with pytest.raises(config.ConfigError, match=message):
+        config.config_defaults(make_parser(), options)
+
+
+def test_format_config():
+    args = make_parser().parse_args(["a \"quoted\" file.fq", "--fraction", "inf"])
+    formatted = config.format_config(args)
+    assert "name" not in formatted
+    assert tomllib.loads(formatted) == {
+        "files": ['a "quoted" file.fq'],
+        "size": 1,
+        "fraction": float("inf"),
+        "color": "red",
+        "fast": False,
+        "order": True,
+        "verbose": 0,
+    }
+
+
+def test_load_config(tmpdir):
+    config_path = os.path.join(tmpdir, "run.toml")
+    with open(config_path, "w") as fp:
+        fp.write("size = 3\n")
+    assert config.load_config(config_path) == {"size": 3}
+    with pytest.raises(config.ConfigError, match="could not read"):
+        config.load_config(os.path.join(tmpdir, "missing.toml"))
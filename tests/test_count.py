@@ -0,0 +1,461 @@
+import os
+import random
+from os.path import join
+from unittest.mock import patch
+
+import pytest
+
+from trio_binning import count, kmers
+from trio_binning.build_hapmers import main, parse_args
+from trio_binning.seq import Read
+
+
+def canonical_counts(counts):
+    return {kmers.int_to_kmer(kmer_int, 3): n for kmer_int, n in counts.items()}
+
+
+def random_reads(seed):
+    rng = random.Random(seed)
+    return [
+        Read(f"read{i}", "".join(rng.choices("ACGTNacgt", k=rng.randint(0, 300))))
+        for i in range(200)
+    ]
+
+
+def test_count_kmers():
+    counts = count.count_kmers([Read("read1", "AACGTT"), Read("read2", "GTTNAAC")], 3)
+    assert canonical_counts(counts) == {"AAC": 4, "ACG": 2}
+
+
+def test_count_kmers_compress_homopolymers():
+    reads = [Read("read1", "AAACGGT"), Read("read2", "AACGT")]
+    counts = count.count_kmers(reads, 3, compress_homopolymers=True)
+    assert canonical_counts(counts) == {"ACG": 4}
+
+
+def test_count_kmers_spaced_seed():
+    seed = kmers.SpacedSeed("101")
+    # ACG and ATG differ at the don't-care position, and the windows with the
+    # N are skipped
+    reads = [Read("read1", "ACGA"), Read("read2", "ATGNAC")]
+    counts = count.count_kmers(reads, 2, seed=seed)
+    assert {kmers.int_to_kmer(i, 2): n for i, n in counts.items()} == {
+        "AG": 2,
+        "CA": 1,
+    }
+    # threads count the same
+    assert count.count_kmers(reads, 2, threads=2, batch_size=1, seed=seed) == counts
+    with pytest.raises(ValueError, match="makes 2-mers, not 3-mers"):
+        count.count_kmers(reads, 3, seed=seed)
+
+
+@pytest.mark.parametrize("threads,batch_size", [(2, 1), (3, 7), (4, 100)])
+@pytest.mark.parametrize("ignore_softmasked", [False, True])
+def test_count_kmers_threads(threads, batch_size, ignore_softmasked):
+    reads = random_reads(53)
+    expected = {}
+    for read in reads:
+        for kmer_int in kmers.iter_canonical_kmers(read.seq, 11, ignore_softmasked):
+            expected[kmer_int] = expected.get(kmer_int, 0) + 1
+
+    assert count.count_kmers(reads, 11, ignore_softmasked) == expected
+    counts = count.count_kmers(
+        reads, 11, ignore_softmasked, threads=threads, batch_size=batch_size
+    )
+    assert counts == expected
+    assert count.count_kmers([], 11, threads=threads) == {}
+
+
+@pytest.mark.parametrize("threads", [1, 3])
+def test_estimate_distinct_kmers(threads):
+    reads = random_reads(88)
+    exact = len(count.count_kmers(reads, 11))
+    estimator = count.estimate_distinct_kmers(reads, 11, threads=threads)
+    relative_error = estimator.relative_error
+    assert estimator.estimate() == pytest.approx(exact, rel=3 * relative_error)
+    # the threads' estimators merge into the same sketch as one
+    assert estimator.registers == count.estimate_distinct_kmers(reads, 11).registers
+
+    compressed = count.estimate_distinct_kmers(
+        reads, 11, compress_homopolymers=True, precision=10
+    )
+    assert compressed.estimate() == pytest.approx(
+        len(count.count_kmers(reads, 11, compress_homopolymers=True)),
+        rel=3 * compressed.relative_error,
+    )
+
+
+def test_count_kmers_bad_threads():
+    with pytest.raises(ValueError, match="threads"):
+        count.count_kmers([], 3, threads=0)
+    with pytest.raises(ValueError, match="batch_size"):
+        count.count_kmers([], 3, threads=2, batch_size=0)
+
+
+@pytest.mark.parametrize(
+    "memory_limit,partition_bits", [(8, 8), (800, 0), (800, 5), (1 << 20, 30)]
+)
+def test_disk_kmer_counts(tmpdir, memory_limit, partition_bits):
+    reads = random_reads(61)
+    expected = count.count_kmers(reads, 11)
+    with count.DiskKmerCounts(
+        reads,
+        11,
+        memory_limit=memory_limit,
+        tmpdir=str(tmpdir),
+        partition_bits=partition_bits,
+    ) as disk_counts:
+        assert len(os.listdir(tmpdir)) == 1
+        counts = list(disk_counts)
+        # can be read again
+        assert list(disk_counts) == counts
+    assert counts == sorted(expected.items())
+    assert os.listdir(tmpdir) == []
+
+
+def test_disk_kmer_counts_options(tmpdir):
+    reads = [Read("read1", "AAACGGTacgt"), Read("read2", "AACGT")]
+    for kwargs in [
+        {"ignore_softmasked": True},
+        {"compress_homopolymers": True},
+        {"seed": kmers.SpacedSeed("1101")},
+    ]:
+        with count.DiskKmerCounts(reads, 3, tmpdir=str(tmpdir), **kwargs) as counts:
+            assert dict(counts) == count.count_kmers(reads, 3, **kwargs)
+    with count.DiskKmerCounts([], 3, tmpdir=str(tmpdir)) as counts:
+        assert list(counts) == []
+    with pytest.raises(ValueError, match="memory_limit"):
+        count.DiskKmerCounts(reads, 3, memory_limit=4)
+
+
+def test_disk_kmer_counts_cleanup(tmpdir):
+    def failing_reads():
+        yield Read("read1", "ACGTACGTAC")
+        raise OSError("truncated file")
+
+    with pytest.raises(OSError, match="truncated"):
+        count.DiskKmerCounts(failing_reads(), 3, memory_limit=8, tmpdir=str(tmpdir))
+    assert os.listdir(tmpdir) == []
+
+
+def test_hap_specific_sorted():
+    counts_a = count.count_kmers(random_reads(67), 7)
+    counts_b = count.count_kmers(random_reads(71), 7)
+    hap_a, hap_b, num_shared = count.hap_specific_sorted(
+        sorted(counts_a.items()), sorted(counts_b.items()), 7, 2
+    )
+    expected_a, expected_b = count.hap_specific(counts_a, counts_b, 7, 2)
+    assert sorted(hap_a) == sorted(expected_a)
+    assert sorted(hap_b) == sorted(expected_b)
+    assert num_shared == count.num_shared(counts_a, counts_b)
+
+
+def test_dump_order():
+    rng = random.Random(79)
+    kmer_strings = ["".join(rng.choices("ACGT", k=7)) for _ in range(100)]
+    kmer_ints = [kmers.kmer_to_int(kmer) for kmer in kmer_strings]
+    kmer_ints.sort(key=lambda kmer_int: count.dump_order(kmer_int, 7))
+    assert [kmers.int_to_kmer(kmer_int, 7) for kmer_int in kmer_ints] == sorted(
+        kmer_strings
+    )
+
+
+def test_merge():
+    def dump(*pairs):
+        return [(kmers.kmer_to_int(kmer), kmer_count) for kmer, kmer_count in pairs]
+
+    dumps = [
+        dump(("AAC", 1), ("ACG", 2), ("CCA", 5)),
+        dump(("AAA", 3), ("ACG", 4)),
+        dump(("ACG", 1), ("CCA", 1), ("GTA", 7)),
+    ]
+    merged = [(kmers.int_to_kmer(i, 3), n) for i, n in count.merge(dumps, 3)]
+    assert merged == [("AAA", 3), ("AAC", 1), ("ACG", 7), ("CCA", 6), ("GTA", 7)]
+    assert list(count.merge([], 3)) == []
+
+    with pytest.raises(ValueError, match="the k-mers in b.txt aren't sorted"):
+        list(count.merge([dumps[0], dump(("CCA", 1), ("ACG", 1))], 3, ["a", "b.txt"]))
+    with pytest.raises(ValueError, match="dump 1 aren't sorted"):
+        list(count.merge([dump(("ACG", 1), ("ACG", 1))], 3))
+
+
+def test_filter_counts():
+    counts = [(1, 1), (2, 5), (3, 100), (4, 2)]
+    assert list(count.filter_counts(counts, min_count=2)) == [(2, 5), (3, 100), (4, 2)]
+    assert list(count.filter_counts(counts, max_count=5)) == [(1, 1), (2, 5), (4, 2)]
+    assert list(count.filter_counts(counts, 2, 5)) == [(2, 5), (4, 2)]
+
+
+def test_top_counts():
+    rng = random.Random(90)
+    counts = [(kmer_int, rng.randint(1, 500)) for kmer_int in range(100000)]
+    expected = sorted(counts, key=lambda kmer_count: -kmer_count[1])[:1000]
+    assert count.top_counts(iter(counts), 1000) == expected
+    assert count.top_counts(counts[:3], 10) == sorted(counts[:3], key=lambda x: -x[1])
+    assert count.top_counts([], 1) == []
+    with pytest.raises(ValueError, match="n must be at least 1, not 0"):
+        count.top_counts(counts, 0)
+
+
+def test_hap_specific():
+    counts_a = count.count_kmers([Read("a1", "AAAAAC"), Read("a2", "AAACC")], 3)
+    counts_b = count.count_kmers([Read("b1", "AACCT"), Read("b2", "CCTAA")], 3)
+    hap_a, hap_b = count.hap_specific(counts_a, counts_b, 3, min_count=2)
+
+    assert sorted(kmers.int_to_kmer(i, 3) for i in hap_a) == ["AAA"]
+    assert sorted(kmers.int_to_kmer(i, 3) for i in hap_b) == ["AGG"]
+    assert count.num_shared(counts_a, counts_b) == 2
+
+
+def simulated_parent_reads(rng, genome, coverage, read_length, error_rate):
+    reads = []
+    for i in range(len(genome) * coverage // read_length):
+        start = rng.randrange(len(genome) - read_length)
+        read = list(genome[start : start + read_length])
+        for position in range(read_length):
+            if rng.random() < error_rate:
+                read[position] = rng.choice("ACGT")
+        reads.append(Read(f"read{i}", "".join(read)))
+    return reads
+
+
+@pytest.mark.parametrize("num_counters, max_lost", [(1 << 20, 0.01), (1 << 18, 0.1)])
+def test_hap_specific_prefiltered(num_counters, max_lost):
+    rng = random.Random(89)
+    genome_a = "".join(rng.choices("ACGT", k=20000))
+    # parent B differs at about one base in 100
+    genome_b = "".join(
+        rng.choice("ACGT") if rng.random() < 0.01 else base for base in genome_a
+    )
+    reads_a = simulated_parent_reads(rng, genome_a, 10, 500, 0.01)
+    reads_b = simulated_parent_reads(rng, genome_b, 10, 500, 0.01)
+    k = 21
+
+    exact_a, exact_b = count.count_kmers(reads_a, k), count.count_kmers(reads_b, k)
+    prefilter_a = count.count_kmers_in_counting_bloom(reads_a, k, num_counters)
+    prefilter_b = count.count_kmers_in_counting_bloom(reads_b, k, num_counters)
+    counts_a = count.count_kmers(reads_a, k, prefilter=prefilter_a, min_count=3)
+    counts_b = count.count_kmers(reads_b, k, prefilter=prefilter_b, min_count=3)
+    # the prefiltered counts are exact, and drop most errors
+    assert all(exact_a[kmer_int] == n for kmer_int, n in counts_a.items())
+    assert {x for x, n in exact_a.items() if n >= 3} <= set(counts_a)
+    assert len(counts_a) < 0.5 * len(exact_a)
+
+    hap_a, hap_b = count.hap_specific(exact_a, exact_b, k, 3)
+    prefiltered_a, prefiltered_b, num_shared = count.hap_specific_prefiltered(
+        counts_a, counts_b, prefilter_a, prefilter_b, k, 3
+    )
+    assert len(hap_a) > 1000
+    for exact, prefiltered, other_prefilter in [
+        (hap_a, prefiltered_a, prefilter_b),
+        (hap_b, prefiltered_b, prefilter_a),
+    ]:
+        # only the hap-mers the other parent's prefilter falsely has are lost
+        assert set(prefiltered) <= set(exact)
+        lost = set(exact) - set(prefiltered)
+        assert all(other_prefilter.count(kmer_int) > 0 for kmer_int in lost)
+        assert len(lost) <= max_lost * len(exact)
+        assert len(lost) / len(exact) == pytest.approx(
+            other_prefilter.false_positive_rate, abs=0.01
+        )
+    frequent_a = {x for x, n in exact_a.items() if n >= 3}
+    frequent_b = {x for x, n in exact_b.items() if n >= 3}
+    expected_shared = {x for x in frequent_a if x in exact_b} | {
+        x for x in frequent_b if x in exact_a
+    }
+    assert len(expected_shared) <= num_shared
+    assert num_shared <= len(expected_shared) + len(hap_a) + len(hap_b)
+
+
+def test_histogram():
+    kmer_counts = [1, 1, 2, 5, 12, 2]
+    assert count.histogram(kmer_counts, 6) == [0, 2, 2, 0, 0, 1, 1]
+    assert count.histogram(kmer_counts, 3) == [0, 2, 2, 2]
+    assert count.histogram([], 2) == [0, 0, 0]
+    with pytest.raises(ValueError, match="max_count"):
+        count.histogram(kmer_counts, 0)
+
+
+@pytest.mark.parametrize(
+    "spectrum,min_count",
+    [
+        # an error peak at 1, a valley at 4 and a coverage peak at 7
+        ([0, 1000, 300, 40, 10, 25, 60, 80, 50, 20, 5], 4),
+        # a flat valley is cut at its start
+        ([0, 500, 0, 0, 0, 60, 10], 2),
+        # no errors, so nothing needs filtering
+        ([0, 5, 30, 80, 40, 2], 1),
+        # only errors and a big tail of repeats, which isn't a peak
+        ([0, 1000, 300, 40, 10, 200], None),
+        ([0, 0], None),
+    ],
+)
+def test_suggest_min_count(spectrum, min_count):
+    assert count.suggest_min_count(spectrum) == min_count
+
+
+@pytest.mark.parametrize(
+    "args",
+    [
+        [],
+        ["--threads", "2"],
+        ["--memory-limit", "1"],
+        ["--prefilter-bloom", "--prefilter-bits", "12"],
+        ["--prefilter-bloom", "--threads", "2"],
+    ],
+)
+def test_build_hapmers(tmpdir, args):
+    reads_a = join(tmpdir, "a.fa")
+    reads_b = join(tmpdir, "b.fa")
+    with open(reads_a, "w") as fp:
+        print(">a1\nGATTACAGATTACA\n>a2\nTTTGATTACAGG", file=fp)
+    with open(reads_b, "w") as fp:
+        print(">b1\nGATTACCGATTACC", file=fp)
+
+    with patch(
+        "sys.argv",
+        [
+            "build-hapmers",
+            "-k",
+            "5",
+            "-o",
+            str(tmpdir),
+            *args,
+            reads_a,
+            reads_b,
+        ],
+    ):
+        main()
+
+    hap_a = kmers.KmerSet.from_file(join(tmpdir, "hapA_only_kmers.txt"))
+    hap_b = kmers.KmerSet.from_file(join(tmpdir, "hapB_only_kmers.txt"))
+    assert sorted(kmers.int_to_kmer(i, 5) for i in hap_a) == ["CTGTA", "TGTAA"]
+    assert sorted(kmers.int_to_kmer(i, 5) for i in hap_b) == ["GGTAA"]
+
+
+def test_build_hapmers_compress_homopolymers(tmpdir):
+    reads_a = join(tmpdir, "a.fa")
+    reads_b = join(tmpdir, "b.fa")
+    with open(reads_a, "w") as fp:
+        print(">a1\nGGATTTACAA\n>a2\nGATTACCA", file=fp)
+    with open(reads_b, "w") as fp:
+        print(">b1\nGAGTCA", file=fp)
+
+    with patch(
+        "sys.argv",
+        [
+            "build-hapmers",
+            "-k",
+            "5",
+            "--compress-homopolymers",
+            "--binary",
+            "-o",
+            str(tmpdir),
+            reads_a,
+            reads_b,
+        ],
+    ):
+        main()
+
+    with open(join(tmpdir, "hapA_only_kmers.kmers"), "rb") as fp:
+        hap_a = kmers.KmerSet.read_binary(fp)
+    assert hap_a.homopolymer_compressed
+    # both reads are GATACA once compressed
+    assert sorted(kmers.int_to_kmer(i, 5) for i in hap_a) == ["ATACA", "GATAC"]
+
+
+def test_build_hapmers_seed_pattern(tmpdir):
+    reads_a = join(tmpdir, "a.fa")
+    reads_b = join(tmpdir, "b.fa")
+    with open(reads_a, "w") as fp:
+        print(">a1\nGATTACA\n>a2\nGATTACA", file=fp)
+    with open(reads_b, "w") as fp:
+        print(">b1\nGACTAGA\n>b2\nGACTAGA", file=fp)
+
+    with patch(
+        "sys.argv",
+        [
+            "build-hapmers",
+            "-k",
+            "4",
+            "--seed-pattern",
+            "11011",
+            "--binary",
+            "-o",
+            str(tmpdir),
+            reads_a,
+            reads_b,
+        ],
+    ):
+        main()
+
+    with open(join(tmpdir, "hapA_only_kmers.kmers"), "rb") as fp:
+        hap_a = kmers.KmerSet.read_binary(fp)
+    assert hap_a.seed == kmers.SpacedSeed("11011")
+    seed = hap_a.seed
+    windows = ["GATTA", "ATTAC", "TTACA"]
+    expected = {seed.canonical(kmers.kmer_to_int(window)) for window in windows}
+    # GACTAGA makes GATA from GACTA, the same as GATTA does
+    expected.discard(seed.canonical(kmers.kmer_to_int("GACTA")))
+    assert set(hap_a) == expected
+
+
+@pytest.mark.parametrize(
+    "args, message",
+    [
+        (["-k", "3", "--seed-pattern", "1101"], None),
+        (["-k", "4", "--seed-pattern", "1101"], "-k must be the number of 1's"),
+        (["-k", "3", "--seed-pattern", "0111"], "--seed-pattern: a seed pattern"),
+    ],
+)
+def test_build_hapmers_seed_pattern_args(args, message, capsys):
+    with patch("sys.argv", ["build-hapmers", *args, "a.fa", "b.fa"]):
+        if message is None:
+            assert parse_args().seed == kmers.SpacedSeed("1101")
+            return
+        with pytest.raises(SystemExit):
+            parse_args()
+    _, err = capsys.readouterr()
+    assert message in err
+
+
+def test_build_hapmers_prefilter_warning(tmpdir, capsys):
+    reads_a, reads_b = join(tmpdir, "a.fa"), join(tmpdir, "b.fa")
+    for path in [reads_a, reads_b]:
+        with open(path, "w") as fp:
+            print(f">read\n{''.join(random.choices('ACGT', k=100))}", file=fp)
+    argv = ["build-hapmers", "-k", "5", "-o", str(tmpdir), reads_a, reads_b]
+
+    with patch("sys.argv", [*argv, "--prefilter-bloom", "--prefilter-bits", "16"]):
+        main()
+    _, err = capsys.readouterr()
+    assert "Prefilter saturation: 0.4% of counters used" in err
+    assert "Warning" not in err
+    # 16 counters are full after 100 k-mers
+    with patch("sys.argv", [*argv, "--prefilter-bloom", "--prefilter-bits", "4"]):
+        main()
+    _, err = capsys.readouterr()
+    assert "Prefilter saturation: 100.0% of counters used" in err
+    assert "Warning: the prefilter of haplotype A is too full" in err
+
+
+@pytest.mark.parametrize(
+    "args, message",
+    [
+        (["--prefilter-bits", "20"], "only used with --prefilter-bloom"),
+        (["--prefilter-bloom", "--prefilter-bits", "0"], "from 1 to 40, not 0"),
+        (["--prefilter-bloom", "--memory-limit", "10"], "cannot be used with"),
+    ],
+)
+def test_build_hapmers_prefilter_args(args, message, capsys):
+    with patch("sys.argv", ["build-hapmers", "-k", "5", *args, "a.fa", "b.fa"]):
+        with pytest.raises(SystemExit):
+            parse_args()
+    _, err = capsys.readouterr()
+    assert message in err
+
+    with patch("sys.argv", ["build-hapmers", "-k", "5", "--prefilter-bloom", "a", "-"]):
+        with pytest.raises(SystemExit):
+            parse_args()
+    _, err = capsys.readouterr()
+    assert "reads each file twice, so not stdin" in err
@@ -0,0 +1,82 @@
+import pytest
+
+from trio_binning.classify import Bin
+from trio_binning.evaluate import EvaluationError, evaluate, read_truth
+
+REPORT_HEADER = "read_id\tlength\thap_a_hits\thap_b_hits\tbin\n"
+
+
+def test_read_truth():
+    lines = ["read_id\thaplotype\tstart\n", "read1\tA\t0\n", "read2\tB\n"]
+    assert read_truth(lines) == {"read1": Bin.HAP_A, "read2": Bin.HAP_B}
+    assert read_truth(["read1\tA\n"]) == {"read1": Bin.HAP_A}
+
+
+@pytest.mark.parametrize(
+    "lines,message",
+    [
+        (["read1\n"], "fewer than 2 columns"),
+        (["read1\tU\n"], "haplotype 'U', not A or B"),
+        (["read1\tA\n", "read1\tB\n"], "read1 is in the truth table twice"),
+    ],
+)
+def test_read_truth_invalid(lines, message):
+    with pytest.raises(EvaluationError, match=message):
+        read_truth(lines)
+
+
+def test_evaluate():
+    truth = {
+        "read1": Bin.HAP_A,
+        "read2": Bin.HAP_A,
+        "read3": Bin.HAP_A,
+        "read4": Bin.HAP_B,
+        "read5": Bin.HAP_B,
+    }
+    report = [
+        REPORT_HEADER,
+        "read1\t100\t5\t0\tA\n",
+        "read2\t200\t0\t5\tB\n",
+        "read3\t300\t0\t0\tU\n",
+        "read4\t400\t0\t5\tB\n",
+        "read6\t500\t0\t5\tB\n",
+    ]
+    evaluation = evaluate(report, truth).to_dict()
+    assert evaluation["reads"] == {
+        "confusion": {"A": {"A": 1, "B": 1, "U": 1}, "B": {"A": 0, "B": 1, "U": 0}},
+        "precision": {"A": 1.0, "B": 0.5},
+        "recall": {"A": pytest.approx(1 / 3), "B": 1.0},
+        "unknown_rate": 0.25,
+    }
+    assert evaluation["bases"] == {
+        "confusion": {
+            "A": {"A": 100, "B": 200, "U": 300},
+            "B": {"A": 0, "B": 400, "U": 0},
+        },
+        "precision": {"A": 1.0, "B": pytest.approx(2 / 3)},
+        "recall": {"A": pytest.approx(1 / 6), "B": 1.0},
+        "unknown_rate": 0.3,
+    }
+    assert (evaluation["not_in_truth"], evaluation["not_in_report"]) == (1, 1)
+
+
+def test_evaluate_empty():
+    evaluation = evaluate([REPORT_HEADER], {"read1": Bin.HAP_A}).to_dict()
+    assert evaluation["reads"]["precision"] == {"A": None, "B": None}
+    assert evaluation["reads"]["unknown_rate"] is None
+    assert evaluation["not_in_report"] == 1
+
+
+@pytest.mark.parametrize(
+    "report,message",
+    [
+        ([], "doesn't have read_id, length and bin columns"),
+        (["read_id\tbin\n"], "doesn't have read_id, length and bin columns"),
+        ([REPORT_HEADER, "read1\t100\n"], "line 2 of the report has fewer than 5"),
+        ([REPORT_HEADER, "read1\t100\t1\t0\tX\n"], "line 2 .* bad bin or length"),
+        ([REPORT_HEADER, *["read1\t100\t1\t0\tA\n"] * 2], "read1 is in the report"),
+    ],
+)
+def test_evaluate_invalid(report, message):
+    with pytest.raises(EvaluationError, match=message):
+        evaluate(report, {"read1": Bin.HAP_A})
@@ -0,0 +1,97 @@
+import os
+import random
+import shutil
+
+import pytest
+
+from trio_binning import faidx, seq
+
+FASTA_PATH = os.path.join(os.path.dirname(__file__), "data", "test.fa")
+
+
+@pytest.fixture
+def fasta_path(tmpdir):
+    path = os.path.join(tmpdir, "test.fa")
+    shutil.copy(FASTA_PATH, path)
+    return path
+
+
+def test_build_index(fasta_path):
+    entries = faidx.build_index(fasta_path)
+
+    # what samtools faidx writes for test.fa
+    with open(fasta_path + ".fai") as index_file:
+        assert index_file.read() == (
+            "read1\t33\t7\t33\t34\n"
+            "read2\t66\t48\t33\t34\n"
+            "read3\t37\t123\t37\t38\n"
+        )
+    assert faidx.read_index(fasta_path + ".fai") == entries
+
+
+@pytest.mark.parametrize("line_ending", ["\n", "\r\n"])
+def test_fetch(tmpdir, line_ending):
+    rng = random.Random(41)
+    reads = [
+        seq.Read(f"contig{i}", "".join(rng.choices("ACGTN", k=rng.randint(0, 100))))
+        for i in range(20)
+    ]
+    fasta_path = os.path.join(tmpdir, "contigs.fa")
+    with open(fasta_path, "w", newline=line_ending) as fp:
+        writer = seq.FastaWriter(fp, line_width=7)
+        for read in reads:
+            writer.write(read)
+
+    with faidx.IndexedFasta(fasta_path) as fasta:
+        assert len(fasta) == len(reads)
+        for read in reads:
+            assert fasta.fetch(read.name) == read
+            for _ in range(10):
+                start = rng.randint(0, len(read.seq))
+                end = rng.randint(start, len(read.seq))
+                assert fasta.fetch_range(read.name, start, end) == seq.Read(
+                    f"{read.name}:{start}-{end}", read.seq[start:end]
+                )
+    assert os.path.exists(fasta_path + ".fai")
+
+
+def test_fetch_errors(fasta_path):
+    with faidx.IndexedFasta(fasta_path) as fasta:
+        assert "read1" in fasta
+        with pytest.raises(faidx.UnknownSequenceError):
+            fasta.fetch("read4")
+        with pytest.raises(faidx.UnknownSequenceError):
+            fasta.fetch_range("read4", 0, 1)
+        for start, end in [(-1, 5), (5, 34), (6, 5)]:
+            with pytest.raises(faidx.CoordinateError, match=f"range {start}-{end}"):
+                fasta.fetch_range("read1", start, end)
+
+
+@pytest.mark.parametrize(
+    "contents,message",
+    [
+        ("ACGT\n>read1\nACGT\n", "sequence before the first header"),
+        (">read1\nACG\nACGT\n", "different lengths"),
+        (">read1\nACGT\nAC\nAC\n", "different lengths"),
+        (">read1\nACGT\n\nACGT\n", "different lengths"),
+        (">read1\nACGT\n>read1\nACGT\n", "duplicate name"),
+        (">\nACGT\n", "no name"),
+    ],
+)
+def test_build_index_bad(tmpdir, contents, message):
+    fasta_path = os.path.join(tmpdir, "bad.fa")
+    with open(fasta_path, "w") as fp:
+        fp.write(contents)
+
+    with pytest.raises(faidx.FaidxError, match=message):
+        faidx.build_index(fasta_path)
+
+
+def test_indexed_fasta_uses_existing_index(fasta_path):
+    # an index that doesn't match the file, to tell that it was used
+    with open(fasta_path + ".fai", "w") as index_file:
+        print("read1", 4, 7, 33, 34, sep="\t", file=index_file)
+
+    with faidx.IndexedFasta(fasta_path) as fasta:
+        assert fasta.fetch("read1").seq == "GGTC"
+        assert "read2" not in fasta
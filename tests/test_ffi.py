@@ -0,0 +1,113 @@
+import os
+import shutil
+import subprocess
+from os.path import dirname, join
+
+import pytest
+
+from trio_binning import kmers, seq
+from trio_binning.classify import Classifier
+
+REPO_DIR = dirname(dirname(__file__))
+DATA_DIR = join(dirname(__file__), "data")
+
+
+@pytest.fixture
+def ffi_classify(tmpdir):
+    """The C test program, compiled against kmers.c and trio_binning.h"""
+    compiler = shutil.which("cc") or shutil.which("gcc")
+    if compiler is None:
+        pytest.skip("no C compiler")
+    program = os.path.join(tmpdir, "ffi_classify")
+    subprocess.run(
+        [
+            compiler,
+            "-O2",
+            "-Wall",
+            "-Werror",
+            "-I",
+            join(REPO_DIR, "c"),
+            join(dirname(__file__), "ffi_classify.c"),
+            join(REPO_DIR, "c", "kmers.c"),
+            "-o",
+            program,
+        ],
+        check=True,
+    )
+    return program
+
+
+def test_ffi_classify(ffi_classify):
+    hap_a_path, hap_b_path = join(DATA_DIR, "hapA.txt"), join(DATA_DIR, "hapB.txt")
+    hap_a = kmers.KmerSet.from_file(hap_a_path)
+    hap_b = kmers.KmerSet.from_file(hap_b_path)
+    with seq.open_text_read(join(DATA_DIR, "test.ccs.fastq.gz")) as fp:
+        seqs = [read.seq for read in seq.readfq(fp)]
+    # a k-mer from each haplotype, with an N, and too short for any k-mers
+    with open(hap_a_path) as fp:
+        hap_a_kmer = fp.readline().strip()
+    with open(hap_b_path) as fp:
+        hap_b_kmer = fp.readline().strip()
+    seqs += [f"G{hap_a_kmer}NT{hap_b_kmer.lower()}{hap_a_kmer}", hap_b_kmer, "ACGT"]
+
+    output = subprocess.run(
+        [ffi_classify, hap_a_path, hap_b_path, *seqs],
+        check=True,
+        capture_output=True,
+        text=True,
+    ).stdout.splitlines()
+    assert output[0] == f"k\t{hap_a.k}\t{len(hap_a)}\t{len(hap_b)}"
+
+    classifier = Classifier(hap_a, hap_b)
+    for line, read_seq in zip(output[1:], seqs):
+        classification = classifier.classify_seq(read_seq)
+        read_bin, *counts, hap_a_score, hap_b_score = line.split("\t")
+        assert read_bin == classification.bin.value
+        assert [int(count) for count in counts] == [
+            classification.hap_a_count,
+            classification.hap_b_count,
+            classification.num_kmers,
+            classification.num_skipped,
+        ]
+        assert float(hap_a_score) == pytest.approx(classification.hap_a_score)
+        assert float(hap_b_score) == pytest.approx(classification.hap_b_score)
+    assert len(output) == len(seqs) + 1
+
+
+@pytest.mark.parametrize("contents", ["", "ACGT\nACG\n", "ACGN\n", "A" * 33 + "\n"])
+def test_ffi_load_invalid(ffi_classify, tmpdir, contents):
+    kmers_path = os.path.join(tmpdir, "kmers.txt")
+    with open(kmers_path, "w") as fp:
+        fp.write(contents)
+    result = subprocess.run(
+        [ffi_classify, kmers_path, join(DATA_DIR, "hapB.txt")],
+        capture_output=True,
+        text=True,
+    )
+    assert result.returncode == 1
+    assert "could not load" in result.stderr
+
+
+def test_ffi_empty_set(ffi_classify):
+    result = subprocess.run(
+        [ffi_classify, "-", join(DATA_DIR, "hapB.txt"), "ACGT"],
+        capture_output=True,
+        text=True,
+    )
+    # an error rather than dividing by the size of the empty set
+    assert result.returncode == 1
+    assert "could not classify ACGT: -4" in result.stderr
+
+
+def test_ffi_load_counts(ffi_classify, tmpdir):
+    kmers_path = os.path.join(tmpdir, "kmers.txt")
+    with open(kmers_path, "w") as fp:
+        fp.write("AAAAC\t3\n\ncccCG\t5\r\n")
+    output = subprocess.run(
+        [ffi_classify, kmers_path, kmers_path, "GAAAACT"],
+        check=True,
+        capture_output=True,
+        text=True,
+    ).stdout.splitlines()
+    # found in haplotype A first, as with a k-mer in both sets in Python
+    assert output == ["k\t5\t2\t2", "A\t1\t0\t3\t0\t1\t0"]
@@ -0,0 +1,421 @@
+import gzip
+import json
+import random
+from os import listdir
+from os.path import dirname, join
+from unittest.mock import patch
+
+import pytest
+
+from trio_binning import classify_by_kmers, seq
+from trio_binning.kmer_tools import main
+
+
+@pytest.fixture
+def kmer_lists(tmpdir):
+    with open(join(tmpdir, "hapA.txt"), "w") as hap_a_file:
+        print("AAAAC\nCCCCG\nGATTA", file=hap_a_file)
+    with open(join(tmpdir, "hapB.txt"), "w") as hap_b_file:
+        print("GTTTT\nTTTTA", file=hap_b_file)
+    return join(tmpdir, "hapA.txt"), join(tmpdir, "hapB.txt")
+
+
+def test_stats(capsys, kmer_lists):
+    with patch("sys.argv", ["kmers", "stats", *kmer_lists]):
+        main()
+
+    out, _ = capsys.readouterr()
+    assert out == (
+        f"{kmer_lists[0]}: 3 5-mers, 46.7% GC\n"
+        f"{kmer_lists[1]}: 2 5-mers, 10.0% GC\n"
+        "Shared k-mers: 1\n"
+        "Jaccard index: 0.25\n"
+    )
+
+
+def test_stats_json(capsys, kmer_lists):
+    with patch("sys.argv", ["kmers", "stats", *kmer_lists, "--json"]):
+        main()
+
+    out, _ = capsys.readouterr()
+    assert json.loads(out) == {
+        "hap_a": {
+            "path": kmer_lists[0],
+            "kmers": 3,
+            "k": 5,
+            "gc_content": pytest.approx(7 / 15),
+        },
+        "hap_b": {"path": kmer_lists[1], "kmers": 2, "k": 5, "gc_content": 0.1},
+        "shared_kmers": 1,
+        "jaccard": 0.25,
+    }
+
+
+def test_stats_mismatched_k(kmer_lists):
+    with patch(
+        "sys.argv",
+        ["kmers", "stats", kmer_lists[0], join(dirname(__file__), "data", "hapB.txt")],
+    ):
+        with pytest.raises(SystemExit, match="are 5-mers, but the haplotype B"):
+            main()
+
+
+def test_histogram(capsys, tmpdir):
+    rng = random.Random(47)
+    genome = "".join(rng.choices("ACGT", k=100))
+    error_read = "".join(rng.choices("ACGT", k=50))
+    reads_path = join(tmpdir, "reads.fa")
+    with open(reads_path, "w") as reads_file:
+        for i in range(6):
+            print(f">read{i}\n{genome}", file=reads_file)
+        print(f">error\n{error_read}", file=reads_file)
+
+    with patch("sys.argv", ["kmers", "histogram", "-k", "11", reads_path]):
+        main()
+    out, err = capsys.readouterr()
+    assert out == "1\t40\n6\t90\n"
+    assert "Suggested --min-count: 2" in err
+
+    with patch(
+        "sys.argv",
+        ["kmers", "histogram", "-k", "11", reads_path, reads_path, "--max-count", "10"],
+    ):
+        main()
+    out, _ = capsys.readouterr()
+    assert out == "2\t40\n10\t90\n"
+
+
+def test_estimate(capsys, tmpdir):
+    rng = random.Random(88)
+    reads_path = join(tmpdir, "reads.fa")
+    with open(reads_path, "w") as reads_file:
+        for i in range(200):
+            print(f">read{i}\n{''.join(rng.choices('ACGT', k=520))}", file=reads_file)
+        # the same k-mers again, reverse complemented, aren't distinct
+        print(f">rc\n{('ACGT' * 10)[::-1]}", file=reads_file)
+
+    outputs = []
+    for args in [[], ["--threads", "3"]]:
+        with patch("sys.argv", ["kmers", "estimate", "-k", "21", reads_path, *args]):
+            main()
+        out, _ = capsys.readouterr()
+        outputs.append(out)
+    assert outputs[0] == outputs[1]
+    estimate_line, error_line = outputs[0].splitlines()
+    # 200 reads of 500 distinct 21-mers, and 4 from the last read
+    num_kmers = int(estimate_line.split(": ")[1])
+    assert estimate_line.startswith("Estimated distinct 21-mers: ")
+    assert num_kmers == pytest.approx(100004, rel=0.03)
+    assert error_line == "Relative standard error: 0.81%"
+
+    with patch(
+        "sys.argv", ["kmers", "estimate", "-k", "21", "-p", "3", reads_path]
+    ), pytest.raises(SystemExit, match="precision must be from 4 to 18, not 3"):
+        main()
+
+
+def test_histogram_on_disk(capsys, tmpdir):
+    rng = random.Random(73)
+    reads_path = join(tmpdir, "reads.fa")
+    with open(reads_path, "w") as reads_file:
+        for i in range(100):
+            print(f">read{i}\n{''.join(rng.choices('ACGT', k=50))}", file=reads_file)
+    outputs = []
+    for args in [[], ["--memory-limit", "1", "--tmpdir", str(tmpdir)]]:
+        with patch("sys.argv", ["kmers", "histogram", "-k", "5", reads_path, *args]):
+            main()
+        out, _ = capsys.readouterr()
+        outputs.append(out)
+    assert outputs[0] == outputs[1]
+    assert listdir(tmpdir) == ["reads.fa"]
+
+    with patch(
+        "sys.argv",
+        ["kmers", "histogram", "-k", "5", reads_path, "--tmpdir", str(tmpdir)],
+    ):
+        with pytest.raises(SystemExit):
+            main()
+    _, err = capsys.readouterr()
+    assert "--tmpdir is only used with --memory-limit" in err
+
+
+@pytest.fixture
+def count_dumps(tmpdir):
+    dumps = {
+        "run1.tsv": "AACGT\t2\nACGTA\t1\nCCCGT\t10\n",
+        "run2.tsv": "AACGT\t3\nACCCA\t4\n",
+        "run3.tsv": "ACCCA\t1\nCCCGT\t5\nTTACG\t1\n",
+    }
+    for name, dump in dumps.items():
+        with open(join(tmpdir, name), "w") as dump_file:
+            dump_file.write(dump)
+    return [join(tmpdir, name) for name in dumps]
+
+
+def test_merge(capsys, tmpdir, count_dumps):
+    with patch("sys.argv", ["kmers", "merge", *count_dumps]):
+        main()
+    out, _ = capsys.readouterr()
+    assert out == "AACGT\t5\nACCCA\t5\nACGTA\t1\nCCCGT\t15\nTTACG\t1\n"
+
+    # merging a merged dump with the rest sums the counts the same way
+    merged_path = join(tmpdir, "merged.tsv.gz")
+    with patch(
+        "sys.argv", ["kmers", "merge", *count_dumps[:2], "-o", merged_path]
+    ):
+        main()
+    with patch("sys.argv", ["kmers", "merge", merged_path, count_dumps[2]]):
+        main()
+    round_trip, _ = capsys.readouterr()
+    assert round_trip == out
+
+
+def test_merge_errors(tmpdir, count_dumps):
+    unsorted_path = join(tmpdir, "unsorted.tsv")
+    with open(unsorted_path, "w") as dump_file:
+        print("CCCGT\t1\nAACGT\t1", file=dump_file)
+    with patch("sys.argv", ["kmers", "merge", count_dumps[0], unsorted_path]):
+        with pytest.raises(SystemExit, match="unsorted.tsv aren't sorted"):
+            main()
+
+    short_path = join(tmpdir, "short.tsv")
+    with open(short_path, "w") as dump_file:
+        print("AAC\t1", file=dump_file)
+    with patch("sys.argv", ["kmers", "merge", count_dumps[0], short_path]):
+        with pytest.raises(SystemExit, match="expected a 5-mer but found 'AAC'"):
+            main()
+
+
+def test_filter(capsys, count_dumps):
+    with patch(
+        "sys.argv", ["kmers", "filter", count_dumps[0], "--min", "2", "--max", "9"]
+    ):
+        main()
+    out, _ = capsys.readouterr()
+    assert out == "AACGT\t2\n"
+
+    with patch("sys.argv", ["kmers", "filter", count_dumps[0]]):
+        with pytest.raises(SystemExit):
+            main()
+    _, err = capsys.readouterr()
+    assert "at least one of --min and --max" in err
+
+
+def test_top(capsys, tmpdir):
+    dump_paths = [join(tmpdir, "hapA_only_kmers.txt"), join(tmpdir, "hapB.tsv.gz")]
+    with open(dump_paths[0], "w") as fp:
+        # CCCCGA's GC content is 5 / 6, and AAAACG has a run of 4
+        fp.write("ACGTAC\t30\nAAAACG\t90\nCCCCGA\t50\nGATTAC\t30\nACGATC\t7\n")
+    with gzip.open(dump_paths[1], "wt") as fp:
+        fp.write("TTGACA\t3\nCAGTCA\t12\n")
+
+    with patch("sys.argv", ["kmers", "top", "-n", "3", *dump_paths]):
+        main()
+    out, _ = capsys.readouterr()
+    assert out == (
+        ">hapA_only_kmers_1 count=90\nAAAACG\n"
+        ">hapA_only_kmers_2 count=50\nCCCCGA\n"
+        ">hapA_only_kmers_3 count=30\nACGTAC\n"
+        ">hapB_1 count=12\nCAGTCA\n"
+        ">hapB_2 count=3\nTTGACA\n"
+    )
+
+    fasta_path = join(tmpdir, "markers.fa")
+    argv = ["kmers", "top", dump_paths[0], "--max-gc", "0.6", "--max-homopolymer", "3"]
+    with patch("sys.argv", [*argv, "-o", fasta_path]):
+        main()
+    with open(fasta_path) as fp:
+        records = [(read.name, read.seq) for read in seq.readfq(fp)]
+    assert records == [
+        ("hapA_only_kmers_1", "ACGTAC"),
+        ("hapA_only_kmers_2", "GATTAC"),
+        ("hapA_only_kmers_3", "ACGATC"),
+    ]
+
+
+@pytest.mark.parametrize(
+    "args, message",
+    [
+        (["-n", "0"], "--n must be at least 1, not 0"),
+        (["--min-gc", "0.6", "--max-gc", "0.4"], "must be from 0 to 1, in order"),
+        (["--max-homopolymer", "0"], "--max-homopolymer must be at least 1, not 0"),
+    ],
+)
+def test_top_invalid(capsys, args, message):
+    with patch("sys.argv", ["kmers", "top", "dump.txt", *args]):
+        with pytest.raises(SystemExit):
+            main()
+    _, err = capsys.readouterr()
+    assert message in err
+
+
+def test_profile(capsys, tmpdir, kmer_lists):
+    reads_path = join(tmpdir, "reads.fa")
+    with open(reads_path, "w") as reads_file:
+        print(">read1\nAAAACNNTTTTA\n>read2\nGATTA\n>read3\nCCCCG", file=reads_file)
+
+    with patch("sys.argv", ["kmers", "profile", reads_path, *kmer_lists]):
+        main()
+    out, _ = capsys.readouterr()
+    # GATTA is in both lists, so it's only written for haplotype A
+    assert out == (
+        "read1\t0\t5\tA\nread1\t7\t12\tB\nread2\t0\t5\tA\nread3\t0\t5\tA\n"
+    )
+
+    bed_path = join(tmpdir, "profile.bed.gz")
+    with patch(
+        "sys.argv",
+        [
+            "kmers",
+            "profile",
+            reads_path,
+            *kmer_lists,
+            "--read-id",
+            "read1",
+            "--read-id",
+            "read4",
+            "-o",
+            bed_path,
+        ],
+    ):
+        main()
+    with gzip.open(bed_path, "rt") as bed_file:
+        assert bed_file.read() == "read1\t0\t5\tA\nread1\t7\t12\tB\n"
+
+
+def test_simulate(tmpdir):
+    prefix = join(tmpdir, "sim")
+    with patch(
+        "sys.argv",
+        [
+            "kmers",
+            "simulate",
+            "-o",
+            prefix,
+            "--genome-length",
+            "5000",
+            "--read-length",
+            "1000",
+            "--coverage",
+            "4",
+            "--het-rate",
+            "0.01",
+            "-k",
+            "15",
+            "--seed",
+            "1",
+        ],
+    ):
+        main()
+
+    for hap in ["hapA", "hapB"]:
+        with open(f"{prefix}.{hap}.fasta") as fasta:
+            lines = fasta.read().splitlines()
+        assert lines[0] == f">{hap}"
+        assert len(lines[1]) == 5000
+        with open(f"{prefix}.{hap}.txt") as kmer_file:
+            assert all(len(line) == 16 for line in kmer_file)
+    with gzip.open(f"{prefix}.reads.fastq.gz", "rt") as reads_file:
+        headers = reads_file.read().splitlines()[::4]
+    with open(f"{prefix}.truth.tsv") as truth_file:
+        truth = [line.rstrip("\n").split("\t") for line in truth_file]
+    assert truth[0] == ["read_id", "haplotype", "start", "strand"]
+    assert len(truth) == len(headers) + 1 == 21
+    for header, (read_id, haplotype, start, strand) in zip(headers, truth[1:]):
+        assert header == f"@{read_id} hap={haplotype} start={start} strand={strand}"
+
+
+def test_evaluate(capsys, tmpdir):
+    report_path = join(tmpdir, "report.tsv")
+    with open(report_path, "w") as report_file:
+        print("read_id\tlength\tbin", file=report_file)
+        print("read1\t100\tA\nread2\t300\tU\nread3\t200\tB", file=report_file)
+    truth_path = join(tmpdir, "truth.tsv")
+    with open(truth_path, "w") as truth_file:
+        print("read1\tA\nread2\tB\nread3\tA\nread4\tB", file=truth_file)
+
+    with patch("sys.argv", ["kmers", "evaluate", report_path, truth_path]):
+        main()
+    out, _ = capsys.readouterr()
+    assert out.startswith(
+        "reads\tbin A\tbin B\tbin U\n"
+        "true A\t1\t1\t0\n"
+        "true B\t0\t0\t1\n"
+        "Haplotype A reads: precision 100.00%, recall 50.00%\n"
+        "Haplotype B reads: precision 0.00%, recall 0.00%\n"
+        "Unknown reads: 33.33%\n"
+    )
+    assert out.endswith(
+        "Reads in the report but not the truth: 0\n"
+        "Reads in the truth but not the report: 1\n"
+    )
+
+    with patch("sys.argv", ["kmers", "evaluate", report_path, truth_path, "--json"]):
+        main()
+    out, _ = capsys.readouterr()
+    evaluation = json.loads(out)
+    assert evaluation["bases"]["recall"] == {"A": pytest.approx(1 / 3), "B": 0.0}
+    assert evaluation["not_in_report"] == 1
+
+    with open(truth_path, "a") as truth_file:
+        print("read5\tC", file=truth_file)
+    with patch("sys.argv", ["kmers", "evaluate", report_path, truth_path]):
+        with pytest.raises(SystemExit, match="haplotype 'C', not A or B"):
+            main()
+
+
+def test_simulate_classify_evaluate(capsys, tmpdir):
+    prefix = join(tmpdir, "sim")
+    with patch(
+        "sys.argv",
+        [
+            "kmers",
+            "simulate",
+            "-o",
+            prefix,
+            "--genome-length",
+            "100000",
+            "--read-length",
+            "5000",
+            "--coverage",
+            "5",
+            "--seed",
+            "70",
+        ],
+    ):
+        main()
+    with patch(
+        "sys.argv",
+        [
+            "classify-by-kmers",
+            f"{prefix}.reads.fastq.gz",
+            f"{prefix}.hapA.txt",
+            f"{prefix}.hapB.txt",
+            "--haplotype-a-out-prefix",
+            join(tmpdir, "hapA"),
+            "--haplotype-b-out-prefix",
+            join(tmpdir, "hapB"),
+            "--unclassified-out-prefix",
+            join(tmpdir, "hapU"),
+            "--report",
+            f"{prefix}.report.tsv",
+            "--min-hits",
+            "5",
+        ],
+    ):
+        classify_by_kmers.main()
+    capsys.readouterr()
+
+    with patch(
+        "sys.argv",
+        ["kmers", "evaluate", f"{prefix}.report.tsv", f"{prefix}.truth.tsv", "--json"],
+    ):
+        main()
+    out, _ = capsys.readouterr()
+    evaluation = json.loads(out)
+    # at 0.1% error, nearly every read is binned, and correctly
+    assert evaluation["not_in_truth"] == evaluation["not_in_report"] == 0
+    assert evaluation["reads"]["unknown_rate"] < 0.05
+    for hap in ["A", "B"]:
+        assert evaluation["reads"]["precision"][hap] > 0.99
+        assert evaluation["bases"]["recall"][hap] > 0.95
@@ -1,4 +1,9 @@
+import math
 import os.path
+import random
+from array import array
+from collections import Counter
+from io import BytesIO, StringIO
 
 import pytest
 
@@ -37,6 +42,15 @@ def test_count_kmers_in_read():
 )
 def test_kmer_to_int(kmer_str, kmer_int):
     assert kmers.kmer_to_int(kmer_str) == kmer_int
+    assert kmers.kmer_to_int(kmer_str.encode()) == kmer_int
+
+
+def test_kmer_to_int_bytes():
+    assert kmers.kmer_to_int(b"acug", rna=True) == kmers.kmer_to_int("ACTG")
+    with pytest.raises(kmers.InvalidBaseError) as exc_info:
+        kmers.kmer_to_int(b"AC\xe9G")
+    # the same as for the string read from a file with the byte in it
+    assert (exc_info.value.base, exc_info.value.position) == ("\udce9", 2)
 
 
 @pytest.mark.parametrize(
@@ -51,3 +65,1444 @@ def test_kmer_to_int(kmer_str, kmer_int):
 )
 def test_reverse_complement(kmer, revcomp_kmer):
     assert kmers.reverse_complement(kmer) == revcomp_kmer
+
+
+@pytest.mark.parametrize(
+    "kmer",
+    [
+        "ATGCTAGCTAGAGAGAGAGGA",
+        "TTTTTTTTTTTTTTTTTTTTTTTTTTTT",
+        "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+        "GGGAGGGAGGGAGGGAGGGAGGGAGGG",
+        "C",
+    ],
+)
+def test_int_to_kmer(kmer):
+    assert kmers.int_to_kmer(kmers.kmer_to_int(kmer), len(kmer)) == kmer
+
+
+@pytest.mark.parametrize(
+    "kmer,canonical_kmer",
+    [
+        ("ATGCTAGCTAGAGAGAGAGGA", "ATGCTAGCTAGAGAGAGAGGA"),
+        ("TCCTCTCTCTCTAGCTAGCAT", "ATGCTAGCTAGAGAGAGAGGA"),
+        ("TTTTTTTTTTTTTTTTTTTTTTTTTTTT", "AAAAAAAAAAAAAAAAAAAAAAAAAAAA"),
+        ("CT", "AG"),
+        ("GA", "GA"),
+    ],
+)
+def test_canonical_int(kmer, canonical_kmer):
+    assert kmers.int_to_kmer(kmers.canonical_int(kmer), len(kmer)) == canonical_kmer
+    assert kmers.canonical_int(kmer) == kmers.canonical_int(
+        kmers.reverse_complement(kmer)
+    )
+
+
+@pytest.mark.parametrize("kmer", ["ACGT", "AT", "GAATTC", "TTTAAA"])
+def test_canonicalize_palindrome(kmer):
+    kmer_int = kmers.kmer_to_int(kmer)
+    assert kmers.reverse_complement(kmer) == kmer
+    assert kmers.canonicalize(kmer_int, len(kmer)) == kmer_int
+
+
+def test_reverse_complement_int():
+    rng = random.Random(42)
+    for _ in range(5000):
+        k = rng.randint(1, 32)
+        kmer = "".join(rng.choice("ACGT") for _ in range(k))
+        assert kmers.reverse_complement_int(
+            kmers.kmer_to_int(kmer), k
+        ) == kmers.kmer_to_int(kmers.reverse_complement(kmer))
+
+
+# non-ASCII bases, including a byte read with surrogateescape, are still
+# reported at their position in the string
+@pytest.mark.parametrize("base", ["N", "n", "X", "!", "\xe9", "\u20ac", "\udce9"])
+@pytest.mark.parametrize("position", [0, 10, 20])
+def test_kmer_to_int_invalid_base(base, position):
+    kmer = list("ATGCTAGCTAGAGAGAGAGGA")
+    kmer[position] = base
+    with pytest.raises(kmers.InvalidBaseError) as excinfo:
+        kmers.kmer_to_int("".join(kmer))
+    assert excinfo.value.base == base
+    assert excinfo.value.position == position
+    assert str(excinfo.value) == f"invalid base {base!r} at position {position}"
+
+
+def test_kmer_to_int_every_character():
+    for code in range(256):
+        kmer = "ACG" + chr(code) + "TA"
+        if chr(code) in "ACGTacgt":
+            assert kmers.kmer_to_int(kmer) == kmers.kmer_to_int(kmer.upper())
+            continue
+        with pytest.raises(kmers.InvalidBaseError) as excinfo:
+            kmers.kmer_to_int(kmer)
+        assert excinfo.value.position == 3
+
+
+def test_kmer_to_int_rna():
+    assert kmers.kmer_to_int("ACUG", rna=True) == kmers.kmer_to_int("ACTG")
+    assert kmers.kmer_to_int("acug", rna=True) == kmers.kmer_to_int("ACTG")
+    # DNA is the same either way
+    assert kmers.kmer_to_int("ACTG", rna=True) == kmers.kmer_to_int("ACTG")
+    with pytest.raises(kmers.InvalidBaseError, match="'U' at position 2"):
+        kmers.kmer_to_int("ACUG")
+    with pytest.raises(kmers.InvalidBaseError, match="'N' at position 0"):
+        kmers.kmer_to_int("NCUG", rna=True)
+    assert kmers.int_to_kmer(kmers.kmer_to_int("ACUG", rna=True), 4) == "ACTG"
+    assert kmers.reverse_complement("AACUG", rna=True) == "CAGTT"
+    assert kmers.reverse_complement("aacug", rna=True) == "cagtt"
+
+
+@pytest.mark.parametrize("k", [1, 21, 31, 32])
+def test_kmer_to_int_unchecked(k):
+    rng = random.Random(k)
+    for _ in range(100):
+        kmer = "".join(rng.choices("ACGTacgt", k=k))
+        assert kmers.kmer_to_int_unchecked(kmer) == kmers.kmer_to_int(kmer)
+    # anything else is read as an A
+    assert kmers.kmer_to_int_unchecked("N" * k) == 0
+    with pytest.raises(kmers.KmerLengthError):
+        kmers.kmer_to_int_unchecked("")
+
+
+@pytest.mark.parametrize("k", [31, 32, 33, 63, 64])
+def test_kmer_to_int128(k):
+    rng = random.Random(k)
+    for _ in range(100):
+        kmer = "".join(rng.choice("ACGT") for _ in range(k))
+        kmer_int = kmers.kmer_to_int128(kmer)
+        assert kmer_int < 1 << (2 * k)
+        assert kmers.int128_to_kmer(kmer_int, k) == kmer
+        if k <= kmers.MAX_K:
+            assert kmer_int == kmers.kmer_to_int(kmer)
+
+
+@pytest.mark.parametrize("base", ["N", "\u20ac"])
+def test_kmer_to_int128_invalid_base(base):
+    with pytest.raises(kmers.InvalidBaseError) as excinfo:
+        kmers.kmer_to_int128("A" * 40 + base + "A" * 10)
+    assert (excinfo.value.base, excinfo.value.position) == (base, 40)
+
+
+@pytest.mark.parametrize(
+    "function,args",
+    [
+        (kmers.kmer_to_int, ["A" * 33]),
+        (kmers.int_to_kmer, [0, 33]),
+        (kmers.reverse_complement_int, [0, 33]),
+        (kmers.canonicalize, [0, 33]),
+        (kmers.kmer_to_int128, ["A" * 65]),
+        (kmers.int128_to_kmer, [0, 65]),
+    ],
+)
+def test_kmer_too_long(function, args):
+    with pytest.raises(kmers.KmerLengthError):
+        function(*args)
+
+
+@pytest.mark.parametrize(
+    "function,args",
+    [
+        (kmers.kmer_to_int, [""]),
+        (kmers.int_to_kmer, [0, 0]),
+        (kmers.reverse_complement_int, [0, 0]),
+        (kmers.canonicalize, [0, 0]),
+        (kmers.kmer_to_int128, [""]),
+        (kmers.int128_to_kmer, [0, 0]),
+    ],
+)
+def test_kmer_empty(function, args):
+    with pytest.raises(kmers.KmerLengthError, match="at least 1 base"):
+        function(*args)
+
+
+@pytest.mark.parametrize("k", range(1, 7))
+def test_kmer_int_round_trip_exhaustive(k):
+    # every k-mer of each short length, and so every integer below 4**k
+    for kmer_int in range(1 << 2 * k):
+        kmer = kmers.int_to_kmer(kmer_int, k)
+        assert len(kmer) == k and set(kmer) <= set("ACGT")
+        assert kmers.kmer_to_int(kmer) == kmer_int
+        assert kmers.kmer_to_int(kmer.lower()) == kmer_int
+
+
+@pytest.mark.parametrize(
+    "kmer_to_int,int_to_kmer,max_k",
+    [
+        (kmers.kmer_to_int, kmers.int_to_kmer, kmers.MAX_K),
+        (kmers.kmer_to_int128, kmers.int128_to_kmer, kmers.MAX_K_128),
+    ],
+)
+def test_kmer_int_round_trip_random(kmer_to_int, int_to_kmer, max_k):
+    rng = random.Random(83)
+    for k in range(1, max_k + 1):
+        for _ in range(20):
+            kmer = "".join(rng.choices("ACGTacgt", k=k))
+            assert int_to_kmer(kmer_to_int(kmer), k) == kmer.upper()
+            kmer_int = rng.randrange(1 << 2 * k)
+            assert kmer_to_int(int_to_kmer(kmer_int, k)) == kmer_int
+
+
+@pytest.mark.parametrize(
+    "int_to_kmer,max_k",
+    [(kmers.int_to_kmer, kmers.MAX_K), (kmers.int128_to_kmer, kmers.MAX_K_128)],
+)
+def test_int_to_kmer_out_of_range(int_to_kmer, max_k):
+    rng = random.Random(83)
+    for k in range(1, max_k + 1):
+        int_to_kmer((1 << 2 * k) - 1, k)
+        for kmer_int in [-1, 1 << 2 * k, rng.randrange(1 << 2 * k, 1 << 2 * k + 64)]:
+            with pytest.raises(kmers.KmerBitsError) as excinfo:
+                int_to_kmer(kmer_int, k)
+            assert (excinfo.value.kmer_int, excinfo.value.k) == (kmer_int, k)
+
+
+def naive_iter_kmers(seq, k):
+    for i in range(len(seq) - k + 1):
+        try:
+            yield kmers.kmer_to_int(seq[i : i + k])
+        except kmers.InvalidBaseError:
+            pass
+
+
+@pytest.mark.parametrize("k", [1, 5, 21, 32])
+def test_iter_kmers(k):
+    rng = random.Random(k)
+    for _ in range(50):
+        seq = "".join(rng.choice("ACGTACGTN") for _ in range(rng.randint(0, 200)))
+        assert list(kmers.iter_kmers(seq, k)) == list(naive_iter_kmers(seq, k))
+        assert list(kmers.iter_kmers(seq.encode(), k)) == list(
+            kmers.iter_kmers(seq, k)
+        )
+
+
+def test_iter_kmers_short_read():
+    assert list(kmers.iter_kmers("ACGT", 5)) == []
+    assert list(kmers.iter_kmers("", 5)) == []
+
+
+@pytest.mark.parametrize(
+    "seq,expected,num_skipped",
+    [
+        ("NACGTA", ["ACGT", "CGTA"], 1),
+        ("ACGTNACGTA", ["ACGT", "ACGT", "CGTA"], 4),
+        ("ACGTAN", ["ACGT", "CGTA"], 1),
+        ("ACGNNNNNNNNTACG", ["TACG"], 11),
+        ("NNNNNNNN", [], 5),
+        ("NNN", [], 0),
+    ],
+)
+def test_iter_kmers_skips_n(seq, expected, num_skipped):
+    assert [kmers.int_to_kmer(i, 4) for i in kmers.iter_kmers(seq, 4)] == expected
+    assert kmers.count_skipped_kmers(seq, 4) == num_skipped
+
+
+@pytest.mark.parametrize("seq", ["acgtacggat", "ACgtACGgat", "aCgTaCgGaT"])
+def test_iter_kmers_lowercase(seq):
+    assert list(kmers.iter_kmers(seq, 4)) == list(kmers.iter_kmers(seq.upper(), 4))
+    assert list(kmers.iter_canonical_kmers(seq, 4)) == list(
+        kmers.iter_canonical_kmers(seq.upper(), 4)
+    )
+    assert list(kmers.iter_canonical_kmers(seq.encode(), 4)) == list(
+        kmers.iter_canonical_kmers(seq.upper(), 4)
+    )
+    assert kmers.kmer_to_int(seq[:4]) == kmers.kmer_to_int(seq[:4].upper())
+
+
+def test_iter_kmers_ignore_softmasked():
+    seq = "ACGTACgtACGTA"
+    kmer_strings = [kmers.int_to_kmer(i, 4) for i in kmers.iter_kmers(seq, 4, True)]
+    assert kmer_strings == ["ACGT", "CGTA", "GTAC", "ACGT", "CGTA"]
+    assert kmers.count_skipped_kmers(seq, 4, True) == 5
+    assert kmers.count_skipped_kmers(seq, 4) == 0
+    assert list(kmers.iter_canonical_kmers("acgtacgt", 4, True)) == []
+
+
+@pytest.mark.parametrize("step", [1, 2, 3, 7])
+def test_iter_kmers_step(step):
+    rng = random.Random(step)
+    for _ in range(50):
+        seq = "".join(rng.choice("ACGTACGTN") for _ in range(rng.randint(0, 200)))
+        expected = [
+            kmer
+            for i in range(0, len(seq) - 4, step)
+            for kmer in naive_iter_kmers(seq[i : i + 5], 5)
+        ]
+        assert list(kmers.iter_kmers(seq, 5, step=step)) == expected
+        assert list(kmers.iter_canonical_kmers(seq, 5, step=step)) == [
+            kmers.canonicalize(kmer, 5) for kmer in expected
+        ]
+
+
+def test_iter_kmers_step_skips_n():
+    # the k-mers start at 0, 2, 4, ... in the read, however many N's fall
+    # before them
+    seq = "ACGTNACGTAC"
+    kmer_strings = [kmers.int_to_kmer(i, 4) for i in kmers.iter_kmers(seq, 4, step=2)]
+    assert kmer_strings == ["ACGT", "CGTA"]
+    with pytest.raises(ValueError, match="at least 1"):
+        list(kmers.iter_kmers(seq, 4, step=0))
+
+
+def test_count_kmers_in_lookups_step():
+    hap_sets = (
+        kmers.KmerSet.from_reader(StringIO("AAAAC\nCCCCG\n")),
+        kmers.KmerSet.from_reader(StringIO("GGGGT\nTTTTA\n")),
+    )
+    # 0         1
+    # 01234567890123456
+    # AAAACNGGGGTACCCCG
+    seq = "AAAAC" + "N" + "GGGGT" + "A" + "CCCCG"
+    # ACCCC at 11 is the reverse complement of GGGGT
+    assert kmers.count_kmers_in_lookups(seq, *hap_sets) == (2, 2, 5)
+    # only the k-mers at 0, 3, 6, 9 and 12 are looked up, and the one at 3
+    # (ACNGG) is the only one of them skipped
+    assert kmers.count_kmers_in_lookups(seq, *hap_sets, step=3) == (2, 1, 1)
+    assert kmers.count_kmers_in_lookups_fuzzy(seq, *hap_sets, step=3) == (
+        kmers.FuzzyCounts(2, 1, 0, 0, 1)
+    )
+    with pytest.raises(ValueError, match="at least 1"):
+        kmers.count_kmers_in_lookups(seq, *hap_sets, step=0)
+
+
+def test_count_kmers_in_read_lowercase():
+    hap_a = kmers.KmerSet.from_reader(StringIO("ACGTA\n"))
+    hap_b = kmers.KmerSet.from_reader(StringIO("GGGGT\n"))
+    for seq, ignore_softmasked, expected in [
+        ("ACGTAGGGGT", False, (1, 1, 0)),
+        ("acgtaggggt", False, (1, 1, 0)),
+        ("acgtaGGGGT", False, (1, 1, 0)),
+        ("acgtaGGGGT", True, (0, 1, 5)),
+        ("acgtaggggt", True, (0, 0, 6)),
+    ]:
+        assert (
+            kmers.count_kmers_in_read_with_skipped(
+                seq, hap_a.hash_set, hap_b.hash_set, ignore_softmasked
+            )
+            == expected
+        )
+
+
+def test_count_kmers_in_read_skips_n():
+    rng = random.Random(27)
+    hap_a = kmers.KmerSet(4)
+    hap_b = kmers.KmerSet(4)
+    for _ in range(40):
+        hap_a.add(rng.getrandbits(8))
+        hap_b.add(rng.getrandbits(8))
+
+    for _ in range(100):
+        seq = "".join(rng.choice("ACGTACGTN") for _ in range(rng.randint(0, 50)))
+        canonical_kmers = list(kmers.iter_canonical_kmers(seq, 4))
+        hap_a_count = sum(1 for i in canonical_kmers if i in hap_a)
+        hap_b_count = sum(1 for i in canonical_kmers if i in hap_b and i not in hap_a)
+        assert kmers.count_kmers_in_read_with_skipped(
+            seq, hap_a.hash_set, hap_b.hash_set
+        ) == (hap_a_count, hap_b_count, kmers.count_skipped_kmers(seq, 4))
+
+
+def test_kmer_class():
+    kmer = kmers.Kmer.from_str("ATGCTAGCTAGAGAGAGAGGA")
+    assert str(kmer) == "ATGCTAGCTAGAGAGAGAGGA"
+    assert str(kmer.revcomp()) == "TCCTCTCTCTCTAGCTAGCAT"
+    assert kmer.canonical() == kmer
+    assert kmer.revcomp().canonical() == kmer
+    assert kmer == kmers.Kmer.from_str("ATGCTAGCTAGAGAGAGAGGA")
+    assert len({kmer, kmer.revcomp(), kmer.revcomp().revcomp()}) == 2
+
+
+def test_kmer_class_different_k():
+    # "A" and "AA" both have an integer format of 0
+    assert kmers.Kmer.from_str("A") != kmers.Kmer.from_str("AA")
+    with pytest.raises(ValueError):
+        kmers.Kmer.from_str("A") < kmers.Kmer.from_str("AA")
+
+
+def test_kmer_class_ordering():
+    rng = random.Random(6)
+    strings = ["".join(rng.choice("ACGT") for _ in range(13)) for _ in range(200)]
+    assert [str(k) for k in sorted(map(kmers.Kmer.from_str, strings))] == sorted(
+        strings
+    )
+
+
+def test_kmer_set_from_file():
+    kmers_path = os.path.join(os.path.dirname(__file__), "data", "hapA.txt")
+    kmer_set = kmers.KmerSet.from_file(kmers_path, 21)
+    assert len(kmer_set) == 4
+    assert kmer_set.k == 21
+    assert kmers.kmer_to_int("CTTATCATGTCTTTGTTTTCA") in kmer_set
+    assert kmers.kmer_to_int("TGAAAACAAAGACATGATAAG") in kmer_set
+    assert kmers.kmer_to_int("CTTATCATGTCTTTGTTTTCC") not in kmer_set
+
+
+def test_kmer_set_from_reader():
+    kmer_set = kmers.KmerSet.from_reader(
+        StringIO("ACGTA\t12\n\nTACGT\t3\nGGGGG\nCCCCC\n\n"), 5
+    )
+    assert len(kmer_set) == 2
+    assert kmers.kmer_to_int("ACGTA") in kmer_set
+    assert kmers.kmer_to_int("CCCCC") in kmer_set
+
+
+@pytest.mark.parametrize(
+    "contents,line_number",
+    [("ACGTA\nACGT\n", 2), ("ACGTAC\n", 1), ("ACGTA\n\nACNTA\n", 3)],
+)
+def test_kmer_set_from_reader_bad_line(contents, line_number):
+    with pytest.raises(kmers.KmerFileError) as excinfo:
+        kmers.KmerSet.from_reader(StringIO(contents), 5)
+    assert excinfo.value.line_number == line_number
+
+
+def test_kmer_set_grows():
+    rng = random.Random(7)
+    kmer_ints = [rng.getrandbits(42) for _ in range(10000)]
+    kmer_set = kmers.KmerSet(21)
+    for kmer_int in kmer_ints:
+        kmer_set.add(kmer_int)
+    assert len(kmer_set) == len({kmers.canonicalize(i, 21) for i in kmer_ints})
+    assert all(kmer_int in kmer_set for kmer_int in kmer_ints)
+
+
+@pytest.mark.parametrize("hasher", list(kmers.Hasher))
+@pytest.mark.parametrize("capacity", [0, 5000])
+def test_kmer_set_hashers(hasher, capacity):
+    rng = random.Random(31)
+    kmer_ints = [rng.getrandbits(42) for _ in range(5000)]
+    kmer_set = kmers.KmerSet(21, capacity, hasher)
+    for kmer_int in kmer_ints:
+        kmer_set.add(kmer_int)
+    canonical = {kmers.canonicalize(i, 21) for i in kmer_ints}
+    assert len(kmer_set) == len(canonical)
+    assert all(kmer_int in kmer_set for kmer_int in kmer_ints)
+    queries = [rng.getrandbits(42) for _ in range(5000)]
+    assert [q in kmer_set for q in queries] == [
+        kmers.canonicalize(q, 21) in canonical for q in queries
+    ]
+    # sets made from this one keep its hash function
+    difference = kmer_set.difference(kmers.KmerSet(21))
+    assert difference.hash_set.contents.hasher == hasher
+    assert sorted(difference) == sorted(kmer_set)
+
+
+def test_kmer_set_with_capacity():
+    kmer_set = kmers.KmerSet.with_capacity(1000, 21)
+    hash_size = kmer_set.hash_set.contents.hash_size
+    assert kmer_set.k == 21
+    assert hash_size > 1000
+    for kmer_int in range(1000):
+        kmer_set.add(kmer_int)
+    assert kmer_set.hash_set.contents.hash_size == hash_size
+
+
+@pytest.mark.parametrize("k", [1, 5, 21, 32])
+def test_iter_canonical_kmers(k):
+    rng = random.Random(k)
+    for _ in range(50):
+        seq = "".join(rng.choice("ACGTACGTN") for _ in range(rng.randint(0, 200)))
+        assert list(kmers.iter_canonical_kmers(seq, k)) == [
+            kmers.canonicalize(kmer_int, k) for kmer_int in kmers.iter_kmers(seq, k)
+        ]
+
+
+def test_kmer_set_to_file(tmpdir):
+    kmers_path = os.path.join(os.path.dirname(__file__), "data", "hapA.txt")
+    kmer_set = kmers.KmerSet.from_file(kmers_path)
+    out_path = os.path.join(tmpdir, "kmers.txt")
+    kmer_set.to_file(out_path)
+
+    assert sorted(kmers.KmerSet.from_file(out_path)) == sorted(kmer_set)
+    assert len(list(kmer_set)) == 4
+
+
+def test_kmer_set_from_reader_min_count():
+    kmer_set = kmers.KmerSet.from_reader(
+        StringIO("ACGTA\t12\nGGGGG\t1\n\nAAAAA\t3\n"), min_count=3
+    )
+    assert kmer_set.k == 5
+    assert sorted(kmer_set) == sorted(
+        kmers.canonical_int(kmer) for kmer in ["ACGTA", "AAAAA"]
+    )
+
+
+@pytest.mark.parametrize("k", [None, 5])
+def test_kmer_set_from_reader_all_filtered(tmpdir, k):
+    contents = "ACGTA\t1\nGGGGG\t2\n"
+    with pytest.raises(kmers.KmerFileError, match="count of at least 3"):
+        kmers.KmerSet.from_reader(StringIO(contents), k, min_count=3)
+    kmer_path = os.path.join(tmpdir, "kmers.txt")
+    with open(kmer_path, "w") as fp:
+        fp.write(contents)
+    with pytest.raises(kmers.KmerFileError, match="count of at least 3"):
+        kmers.KmerBloom.from_file(kmer_path, 0.001, k, min_count=3)
+    # an empty list is still an empty set if k is given
+    empty = kmers.KmerSet.from_reader(
+        StringIO(""), 5, min_count=3, dump_format=kmers.DumpFormat.MERYL
+    )
+    assert len(empty) == 0
+
+
+@pytest.mark.parametrize("contents", ["ACGTA\t12\nGGGGG\n", "ACGTA\tmany\n"])
+def test_kmer_set_from_reader_bad_count(contents):
+    with pytest.raises(kmers.KmerFileError, match="expected a count"):
+        kmers.KmerSet.from_reader(StringIO(contents), min_count=3)
+
+
+def test_kmer_set_from_meryl_database(tmpdir):
+    meryl_db = os.path.join(tmpdir, "hapA.meryl")
+    os.mkdir(meryl_db)
+    open(os.path.join(meryl_db, "0x000000.merylIndex"), "w").close()
+    with pytest.raises(kmers.KmerFileError, match="meryl print"):
+        kmers.KmerSet.from_file(meryl_db)
+
+
+@pytest.mark.parametrize(
+    "filename,dump_format",
+    [
+        ("kmers.plain.txt", kmers.DumpFormat.PLAIN),
+        ("kmers.meryl.txt", kmers.DumpFormat.MERYL),
+        ("kmers.kmc.txt", kmers.DumpFormat.MERYL),
+        ("kmers.jellyfish.fa", kmers.DumpFormat.JELLYFISH),
+    ],
+)
+def test_detect_dump_format(filename, dump_format):
+    with open(os.path.join(os.path.dirname(__file__), "data", filename)) as fp:
+        assert kmers.detect_dump_format(fp.readlines()[:10]) == dump_format
+
+
+@pytest.mark.parametrize(
+    "filename,dump_format",
+    [
+        ("kmers.plain.txt", kmers.DumpFormat.PLAIN),
+        ("kmers.meryl.txt", kmers.DumpFormat.MERYL),
+        ("kmers.kmc.txt", kmers.DumpFormat.KMC),
+        ("kmers.jellyfish.fa", kmers.DumpFormat.JELLYFISH),
+    ],
+)
+@pytest.mark.parametrize("explicit", [True, False])
+def test_kmer_set_dump_formats(filename, dump_format, explicit):
+    path = os.path.join(os.path.dirname(__file__), "data", filename)
+    if not explicit:
+        dump_format = kmers.DumpFormat.AUTO
+
+    all_kmers = ["ACGTACG", "TTTTTTA", "GATTACA", "CCCCGGA"]
+    assert sorted(kmers.KmerSet.from_file(path, dump_format=dump_format)) == sorted(
+        map(kmers.canonical_int, all_kmers)
+    )
+
+    if filename != "kmers.plain.txt":
+        kmer_set = kmers.KmerSet.from_file(path, min_count=4, dump_format=dump_format)
+        assert sorted(kmer_set) == sorted(
+            map(kmers.canonical_int, ["ACGTACG", "GATTACA"])
+        )
+
+
+@pytest.mark.parametrize(
+    "contents,message",
+    [
+        (">3\n>4\nACGTA\n", r"\(jellyfish format\), line 1: expected a k-mer"),
+        (">3\nACGTA\nACGTA\n", r"line 3: expected a >count line"),
+        (">3\nACGTA\n>4\n", r"\(jellyfish format\): file ends with a count"),
+    ],
+)
+def test_kmer_set_bad_jellyfish_dump(contents, message):
+    with pytest.raises(kmers.KmerFileError, match=message):
+        kmers.KmerSet.from_reader(StringIO(contents))
+
+
+def test_kmer_count_map_from_reader():
+    count_map = kmers.KmerCountMap.from_reader(
+        StringIO("ACGTA\t12\nGGGGG\t1\nTACGT\t30\nAAAAA\t3\n"), min_count=2
+    )
+    assert count_map.k == 5
+    assert len(count_map) == 2
+    # TACGT is ACGTA's reverse complement, so the higher count is kept
+    assert count_map.count(kmers.kmer_to_int("ACGTA")) == 30
+    assert count_map.count(kmers.kmer_to_int("TTTTT")) == 3
+    assert count_map.count(kmers.kmer_to_int("GGGGG")) == 0
+
+    with pytest.raises(kmers.KmerFileError, match="weigh k-mers by count"):
+        kmers.KmerCountMap.from_reader(StringIO("ACGTA\n"))
+    for k in [None, 5]:
+        with pytest.raises(kmers.KmerFileError, match="count of at least 13"):
+            kmers.KmerCountMap.from_reader(
+                StringIO("ACGTA\t12\nGGGGG\t1\n"), k, min_count=13
+            )
+
+
+def test_weigh_kmers_in_count_maps():
+    hap_a = kmers.KmerCountMap(5)
+    hap_b = kmers.KmerCountMap(5)
+    hap_a.add(kmers.kmer_to_int("AAAAC"), 1)
+    hap_a.add(kmers.kmer_to_int("CCCCG"), 4)
+    hap_b.add(kmers.kmer_to_int("GGGGT"), 50)
+    seq = "AAAACTTCCCCGATGGGGT"
+    assert kmers.weigh_kmers_in_count_maps(seq, hap_a, hap_b, 10) == (5, 10)
+    assert kmers.weigh_kmers_in_count_maps(seq, hap_a, hap_b, 2) == (3, 2)
+    # the reverse complement weighs the same
+    revcomp = seq[::-1].translate(str.maketrans("ACGT", "TGCA"))
+    assert kmers.weigh_kmers_in_count_maps(revcomp, hap_a, hap_b, 10) == (5, 10)
+    # the low-quality C in CCCCG skips it
+    quals = "I" * 8 + "#" + "I" * 10
+    assert kmers.weigh_kmers_in_count_maps(
+        seq, hap_a, hap_b, 10, quals=quals, min_base_quality=20
+    ) == (1, 10)
+
+    with pytest.raises(ValueError, match="needs k-mer sets with counts"):
+        kmers.weigh_kmers_in_count_maps(seq, hap_a, kmers.KmerSet(5), 10)
+
+
+def test_kmer_set_plain_min_count():
+    with pytest.raises(kmers.KmerFileError, match="no counts"):
+        kmers.KmerSet.from_reader(StringIO("ACGTA\n"), min_count=2)
+
+
+def test_kmer_set_binary_round_trip():
+    rng = random.Random(20)
+    kmer_set = kmers.KmerSet(25)
+    for _ in range(5000):
+        kmer_set.add(rng.getrandbits(50))
+
+    binary = BytesIO()
+    kmer_set.write_binary(binary)
+    assert binary.getvalue().startswith(kmers.BINARY_MAGIC)
+    assert len(binary.getvalue()) == 28 + 8 * len(kmer_set)
+
+    binary.seek(0)
+    read_kmer_set = kmers.KmerSet.read_binary(binary)
+    assert read_kmer_set.k == 25
+    assert sorted(read_kmer_set) == sorted(kmer_set)
+
+
+def test_kmer_set_binary_bad_header():
+    binary = BytesIO()
+    kmers.KmerSet.from_reader(StringIO("ACGTA\nCCCCG\n")).write_binary(binary)
+    contents = binary.getvalue()
+
+    with pytest.raises(kmers.KmerFileError, match="not a binary k-mer set"):
+        kmers.KmerSet.read_binary(BytesIO(b"ACGTA\nCCCCG\n" + contents))
+    with pytest.raises(kmers.KmerFileError, match="not a binary k-mer set"):
+        kmers.KmerSet.read_binary(BytesIO(contents[:20]))
+    with pytest.raises(kmers.KmerFileError, match="version 2"):
+        kmers.KmerSet.read_binary(BytesIO(contents[:8] + b"\x02" + contents[9:]))
+    with pytest.raises(kmers.KmerFileError, match="truncated"):
+        kmers.KmerSet.read_binary(BytesIO(contents[:-8]))
+    with pytest.raises(kmers.KmerFileError, match="k=33"):
+        kmers.KmerSet.read_binary(BytesIO(contents[:12] + b"\x21" + contents[13:]))
+
+
+def test_check_same_k(tmpdir):
+    path_a = os.path.join(tmpdir, "hapA.txt")
+    with open(path_a, "w") as hap_a_file:
+        print("ACGTACGTACGTACGTACGTA", file=hap_a_file)
+    hap_a = kmers.KmerSet.from_file(path_a)
+    assert hap_a.path == path_a
+    kmers.check_same_k(hap_a, kmers.KmerSet(21))
+
+    with pytest.raises(kmers.KmerSizeMismatchError) as excinfo:
+        kmers.check_same_k(hap_a, kmers.KmerSet(25))
+    assert str(excinfo.value) == (
+        f"the haplotype A k-mers in {path_a} are 21-mers, but the haplotype B "
+        "k-mers are 25-mers"
+    )
+    assert (excinfo.value.hap_a_k, excinfo.value.hap_b_k) == (21, 25)
+
+
+def test_kmer_set_difference_intersection():
+    set_a = kmers.KmerSet.from_reader(StringIO("AAAAC\nCCCCG\nGATTA\n"))
+    # GTTTT is the reverse complement of AAAAC
+    set_b = kmers.KmerSet.from_reader(StringIO("GTTTT\nTTTTA\n"))
+
+    difference = set_a.difference(set_b)
+    assert difference.k == 5
+    assert sorted(difference) == sorted(
+        kmers.canonical_int(kmer) for kmer in ["CCCCG", "GATTA"]
+    )
+    assert sorted(set_b.difference(set_a)) == [kmers.canonical_int("TTTTA")]
+    assert list(set_a.intersection(set_b)) == [kmers.canonical_int("AAAAC")]
+    assert len(set_a.intersection(kmers.KmerSet(5))) == 0
+    # the originals are left alone
+    assert len(set_a) == 3
+
+    with pytest.raises(ValueError, match="5-mers and 4-mers"):
+        set_a.difference(kmers.KmerSet(4))
+
+
+def test_kmer_set_jaccard_stats():
+    kmer_set = kmers.KmerSet.from_reader(StringIO("AAAAC\nCCCCG\nGATTA\n"))
+    other = kmers.KmerSet.from_reader(StringIO("GTTTT\nTTTTA\n"))
+    # GTTTT is AAAAC's reverse complement
+    assert kmer_set.intersection_size(other) == other.intersection_size(kmer_set) == 1
+    assert kmer_set.jaccard(other) == pytest.approx(1 / 4)
+    assert kmer_set.jaccard(kmer_set) == 1
+    assert kmers.KmerSet(5).jaccard(kmers.KmerSet(5)) == 0
+
+    # 1 + 5 + 1 G/C bases in 15
+    assert kmer_set.stats() == kmers.KmerSetStats(3, 5, pytest.approx(7 / 15), False)
+    assert kmers.KmerSet(5).stats().gc_content == 0
+
+    with pytest.raises(ValueError, match="5-mers and 7-mers"):
+        kmer_set.jaccard(kmers.KmerSet(7))
+
+
+def test_iter_kmers_with_quality():
+    seq = "ACGTACGTAC"
+    quals = "IIII#IIIII"
+    assert list(kmers.iter_kmers_with_quality(seq, quals, 3, 2)) == list(
+        kmers.iter_kmers(seq, 3)
+    )
+    # the low-quality fifth base is skipped over like an N
+    assert list(kmers.iter_kmers_with_quality(seq, quals, 3, 20)) == list(
+        kmers.iter_kmers("ACGTNCGTAC", 3)
+    )
+
+    hap_a = kmers.KmerSet(3)
+    for kmer_int in kmers.iter_kmers(seq, 3):
+        hap_a.add(kmer_int)
+    assert kmers.count_kmers_in_lookups(
+        seq, hap_a, kmers.KmerSet(3), quals=quals, min_base_quality=20
+    ) == (5, 0, 3)
+
+    with pytest.raises(ValueError, match="quality string"):
+        list(kmers.iter_kmers_with_quality(seq, quals[:-1], 3, 20))
+    with pytest.raises(ValueError, match="quality string"):
+        kmers.count_kmers_in_lookups(seq, hap_a, hap_a, quals=quals[:-1])
+
+
+@pytest.mark.parametrize("ignore_softmasked", [False, True])
+def test_canonical_kmer_array(ignore_softmasked):
+    rng = random.Random(59)
+    for k in [1, 11, 32]:
+        for _ in range(20):
+            seq = "".join(rng.choices("ACGTNacgt", k=rng.randint(0, 100)))
+            assert list(kmers.canonical_kmer_array(seq, k, ignore_softmasked)) == list(
+                kmers.iter_canonical_kmers(seq, k, ignore_softmasked)
+            )
+
+
+def test_iter_canonical_kmers_with_positions():
+    rng = random.Random(83)
+    seq = "".join(rng.choices("ACGTN", weights=[10, 10, 10, 10, 1], k=500))
+    kmers_with_positions = list(kmers.iter_canonical_kmers_with_positions(seq, 7))
+    assert [kmer_int for _, kmer_int in kmers_with_positions] == list(
+        kmers.iter_canonical_kmers(seq, 7)
+    )
+    for start, kmer_int in kmers_with_positions:
+        assert kmer_int == kmers.canonical_int(seq[start : start + 7])
+
+
+def test_compress_homopolymers():
+    assert kmers.compress_homopolymers("AAACGGT") == "ACGT"
+    assert kmers.compress_homopolymers("AACGT") == "ACGT"
+    assert kmers.compress_homopolymers("aAcGgNNt") == "acGNt"
+    assert kmers.compress_homopolymers("") == ""
+    for seq in ["AAACGGT", "AACGT"]:
+        compressed = kmers.iter_homopolymer_compressed(seq)
+        assert list(kmers.iter_kmers(compressed, 3)) == list(
+            kmers.iter_kmers("ACGT", 3)
+        )
+    assert kmers.compress_homopolymers_with_quality("AAACGGT", "III#II5") == (
+        "ACGT",
+        "I#I5",
+    )
+
+
+def test_kmer_set_binary_homopolymer_compressed():
+    kmer_set = kmers.KmerSet.from_reader(StringIO("ACGTA\n"))
+    kmer_set.homopolymer_compressed = True
+    binary = BytesIO()
+    kmer_set.write_binary(binary)
+    for cls in [kmers.KmerSet, kmers.SortedKmerSet]:
+        binary.seek(0)
+        assert cls.read_binary(binary).homopolymer_compressed
+
+    with pytest.raises(ValueError, match="homopolymer-compressed and uncompressed"):
+        kmer_set.difference(kmers.KmerSet(5))
+
+    # flags this version doesn't know about are rejected
+    header = bytearray(binary.getvalue())
+    header[16] |= 0x80
+    with pytest.raises(kmers.KmerFileError, match="unknown flags 0x81"):
+        kmers.KmerSet.read_binary(BytesIO(bytes(header)))
+
+
+def test_sorted_kmer_set():
+    rng = random.Random(32)
+    kmer_ints = [rng.getrandbits(42) for _ in range(5000)]
+    kmer_set = kmers.KmerSet(21)
+    for kmer_int in kmer_ints:
+        kmer_set.add(kmer_int)
+    sorted_set = kmer_set.into_sorted()
+    assert sorted_set.k == 21
+    assert len(sorted_set) == len(kmer_set)
+    assert list(sorted_set) == sorted(kmer_set)
+
+    # duplicates and reverse complements are removed when made directly
+    reverse_complements = [kmers.reverse_complement_int(i, 21) for i in kmer_ints]
+    direct = kmers.SortedKmerSet(21, kmer_ints + reverse_complements)
+    assert list(direct) == list(sorted_set)
+
+    queries = [rng.getrandbits(42) for _ in range(5000)] + kmer_ints[:500]
+    assert [q in sorted_set for q in queries] == [q in kmer_set for q in queries]
+    assert len(kmers.SortedKmerSet(21)) == 0
+    assert kmer_ints[0] not in kmers.SortedKmerSet(21)
+
+
+def test_sorted_kmer_set_binary():
+    kmer_set = kmers.KmerSet.from_reader(StringIO("AAAAC\nCCCCG\nGATTA\n"))
+    binary = BytesIO()
+    kmer_set.write_binary(binary)
+    binary.seek(0)
+    sorted_set = kmers.SortedKmerSet.read_binary(binary)
+    assert list(sorted_set) == sorted(kmer_set)
+
+    binary_again = BytesIO()
+    sorted_set.write_binary(binary_again)
+    assert binary_again.getvalue() == binary.getvalue()
+
+
+def test_mmap_kmer_set(tmpdir):
+    rng = random.Random(77)
+    kmer_ints = [rng.getrandbits(42) for _ in range(5000)]
+    kmer_set = kmers.KmerSet(21)
+    for kmer_int in kmer_ints:
+        kmer_set.add(kmer_int)
+    binary_path = os.path.join(tmpdir, "hap.kmers")
+    with open(binary_path, "wb") as fp:
+        kmer_set.write_binary(fp)
+
+    mapped_set = kmers.MmapKmerSet(binary_path)
+    assert mapped_set.k == 21
+    assert len(mapped_set) == len(kmer_set)
+    assert list(mapped_set) == sorted(kmer_set)
+    assert not mapped_set.homopolymer_compressed
+    queries = [rng.getrandbits(42) for _ in range(5000)] + kmer_ints[:500]
+    assert [q in mapped_set for q in queries] == [q in kmer_set for q in queries]
+    # the C lookup finds the same k-mers as the Python one
+    read = kmers.int_to_kmer(kmer_ints[0], 21) + "ACGTTGCA" * 10
+    assert kmers.count_kmers_in_lookups(
+        read, mapped_set, kmers.SortedKmerSet(21)
+    ) == kmers.count_kmers_in_lookups(read, kmer_set, kmers.SortedKmerSet(21))
+
+    copy = BytesIO()
+    mapped_set.write_binary(copy)
+    with open(binary_path, "rb") as fp:
+        assert copy.getvalue() == fp.read()
+
+
+def test_mmap_kmer_set_empty(tmpdir):
+    binary_path = os.path.join(tmpdir, "empty.kmers")
+    with open(binary_path, "wb") as fp:
+        kmers.KmerSet(21).write_binary(fp)
+    mapped_set = kmers.MmapKmerSet(binary_path)
+    assert len(mapped_set) == 0
+    assert 0 not in mapped_set
+
+
+def test_mmap_kmer_set_bad_file(tmpdir):
+    binary = BytesIO()
+    kmers.KmerSet.from_reader(StringIO("ACGTA\nCCCCG\n")).write_binary(binary)
+    contents = binary.getvalue()
+    binary_path = os.path.join(tmpdir, "hap.kmers")
+    for bad_contents, message in [
+        (b"ACGTA\nCCCCG\n", "not a binary k-mer set"),
+        (contents[:-3], "truncated"),
+        (contents + b"\0", "1 extra bytes"),
+    ]:
+        with open(binary_path, "wb") as fp:
+            fp.write(bad_contents)
+        with pytest.raises(kmers.KmerFileError, match=message):
+            kmers.MmapKmerSet(binary_path)
+
+
+@pytest.mark.parametrize(
+    "pattern, message",
+    [
+        ("", "made of 1's and 0's"),
+        ("11x1", "made of 1's and 0's"),
+        ("0111", "start and end with a 1"),
+        ("1110", "start and end with a 1"),
+        ("1" * 33, "at most 32 long"),
+    ],
+)
+def test_spaced_seed_invalid(pattern, message):
+    with pytest.raises(ValueError, match=message):
+        kmers.SpacedSeed(pattern)
+
+
+def test_spaced_seed():
+    seed = kmers.SpacedSeed("1110110111")
+    assert (seed.span, seed.weight, str(seed)) == (10, 8, "1110110111")
+    assert seed.symmetric
+    assert kmers.SpacedSeed.from_mask(seed.care, seed.span) == seed
+    assert not kmers.SpacedSeed("1101").symmetric
+
+    window = kmers.kmer_to_int("ACGTACGTAC")
+    assert kmers.int_to_kmer(seed.apply(window), 8) == "ACGACTAC"
+    # a symmetric seed's k-mer is the canonical k-mer of the care bases
+    assert seed.canonical(window) == kmers.canonicalize(seed.apply(window), 8)
+    # an all-care seed makes contiguous k-mers
+    contiguous = kmers.SpacedSeed("1" * 21)
+    read = "".join(random.Random(40).choices("ACGT", k=500))
+    assert list(kmers.spaced_kmer_array(read, contiguous)) == list(
+        kmers.canonical_kmer_array(read, 21)
+    )
+
+
+@pytest.mark.parametrize("pattern", ["1110110111", "1101", "11011000111", "1"])
+@pytest.mark.parametrize("ignore_softmasked", [False, True])
+def test_spaced_kmer_array(pattern, ignore_softmasked):
+    seed = kmers.SpacedSeed(pattern)
+    rng = random.Random(41)
+    read = "".join(rng.choices("ACGTacgtN", weights=[6] * 4 + [1] * 5, k=300))
+    expected = []
+    for i in range(len(read) - seed.span + 1):
+        window = read[i : i + seed.span]
+        if "N" in window or (ignore_softmasked and window != window.upper()):
+            continue
+        expected.append(seed.canonical(kmers.kmer_to_int(window.upper())))
+    kmer_ints = kmers.spaced_kmer_array(read, seed, ignore_softmasked)
+    assert list(kmer_ints) == expected
+    assert len(kmers.spaced_kmer_array("ACG", kmers.SpacedSeed("1101"))) == 0
+
+    # either strand gives the same k-mers
+    reverse_complement = read.translate(str.maketrans("ACGTacgt", "TGCAtgca"))[::-1]
+    reverse = kmers.spaced_kmer_array(reverse_complement, seed, ignore_softmasked)
+    assert list(reverse) == expected[::-1]
+
+
+def test_count_spaced_kmers_in_lookups():
+    seed = kmers.SpacedSeed("1110110111")
+    read = "".join(random.Random(42).choices("ACGT", k=200))
+    hap_a, hap_b = kmers.KmerSet(8), kmers.KmerSet(8)
+    hap_a.add(seed.canonical(kmers.kmer_to_int(read[50:60])))
+    hap_b.add(seed.canonical(kmers.kmer_to_int(read[120:130])))
+    assert kmers.count_spaced_kmers_in_lookups(read, hap_a, hap_b, seed) == (1, 1, 0)
+
+    # a mismatch at a don't-care position still matches, but not at a care one
+    mismatch = {"A": "C", "C": "G", "G": "T", "T": "A"}
+    dont_care = read[:53] + mismatch[read[53]] + read[54:]
+    care = read[:52] + mismatch[read[52]] + read[53:]
+    assert kmers.count_spaced_kmers_in_lookups(dont_care, hap_a, hap_b, seed)[0] == 1
+    assert kmers.count_spaced_kmers_in_lookups(care, hap_a, hap_b, seed)[0] == 0
+
+    # so does the reverse complement, and a sorted set
+    assert kmers.count_spaced_kmers_in_lookups(
+        kmers.reverse_complement(read), hap_a.into_sorted(), hap_b, seed
+    ) == (1, 1, 0)
+    # an N anywhere in a window skips it
+    with_n = read[:55] + "N" + read[56:]
+    assert kmers.count_spaced_kmers_in_lookups(with_n, hap_a, hap_b, seed) == (
+        0,
+        1,
+        10,
+    )
+    quals = "I" * 55 + "#" + "I" * 144
+    assert kmers.count_spaced_kmers_in_lookups(
+        read, hap_a, hap_b, seed, quals=quals, min_base_quality=20
+    ) == (0, 1, 10)
+    counts = kmers.count_spaced_kmers_in_lookups(read, hap_a, hap_b, seed, step=3)
+    assert counts[2] == 0
+
+    with pytest.raises(ValueError, match="makes 8-mers, but the k-mers are 9-mers"):
+        kmers.count_spaced_kmers_in_lookups(
+            read, kmers.KmerSet(9), kmers.KmerSet(9), seed
+        )
+
+
+def test_kmer_set_binary_spaced_seed(tmpdir):
+    seed = kmers.SpacedSeed("1101")
+    kmer_set = kmers.KmerSet.from_reader(StringIO("ACT\nGGA\n"))
+    kmer_set.seed = seed
+    binary_path = os.path.join(tmpdir, "hap.kmers")
+    with open(binary_path, "wb") as fp:
+        kmer_set.write_binary(fp)
+    with open(binary_path, "rb") as fp:
+        contents = fp.read()
+    assert len(contents) == 28 + 8 + 8 * len(kmer_set)
+
+    for cls in [kmers.KmerSet, kmers.SortedKmerSet]:
+        read_set = cls.read_binary(BytesIO(contents))
+        assert read_set.seed == seed
+        assert sorted(read_set) == sorted(kmer_set)
+    mapped_set = kmers.MmapKmerSet(binary_path)
+    assert mapped_set.seed == seed
+    assert list(mapped_set) == sorted(kmer_set)
+    copy = BytesIO()
+    mapped_set.write_binary(copy)
+    assert copy.getvalue() == contents
+    assert kmers.KmerSet.read_binary(BytesIO(contents)).into_sorted().seed == seed
+
+    with pytest.raises(ValueError, match="different seed patterns"):
+        kmer_set.difference(kmers.KmerSet(3))
+
+
+def test_kmer_set_binary_bad_spaced_seed():
+    kmer_set = kmers.KmerSet(3)
+    kmer_set.seed = kmers.SpacedSeed("1101")
+    binary = BytesIO()
+    kmer_set.write_binary(binary)
+    contents = binary.getvalue()
+
+    with pytest.raises(kmers.KmerFileError, match="truncated in its seed pattern"):
+        kmers.KmerSet.read_binary(BytesIO(contents[:32]))
+    with pytest.raises(kmers.KmerFileError, match="invalid seed pattern 0x1b"):
+        kmers.KmerSet.read_binary(BytesIO(contents[:32] + b"\x1b" + contents[33:]))
+    with pytest.raises(kmers.KmerFileError, match="start and end with a 1"):
+        kmers.KmerSet.read_binary(BytesIO(contents[:32] + b"\x0a" + contents[33:]))
+    with pytest.raises(kmers.KmerFileError, match="1111 makes 4-mers"):
+        kmers.KmerSet.read_binary(BytesIO(contents[:32] + b"\x0f" + contents[33:]))
+
+
+def test_kmer_bloom_false_positive_rate():
+    rng = random.Random(30)
+    added = {kmers.canonicalize(rng.getrandbits(42), 21) for _ in range(20000)}
+    bloom = kmers.KmerBloom.from_kmer_iter(added, 21, len(added), 0.01)
+    assert all(kmer_int in bloom for kmer_int in added)
+    assert len(bloom) == pytest.approx(len(added), rel=0.01)
+
+    num_queries = 100000
+    num_false_positives = 0
+    for _ in range(num_queries):
+        kmer_int = rng.getrandbits(42)
+        if kmers.canonicalize(kmer_int, 21) not in added and kmer_int in bloom:
+            num_false_positives += 1
+    assert 0.005 < num_false_positives / num_queries < 0.015
+
+
+def test_kmer_bloom_from_file():
+    kmer_path = os.path.join(os.path.dirname(__file__), "data", "kmers.meryl.txt")
+    bloom = kmers.KmerBloom.from_file(kmer_path, 0.001, min_count=3)
+    assert bloom.k == 7
+    assert len(bloom) == 3
+    for kmer in ["ACGTACG", "GATTACA", "CCCCGGA"]:
+        assert kmers.kmer_to_int(kmer) in bloom
+        assert kmers.kmer_to_int(kmers.reverse_complement(kmer)) in bloom
+
+    with pytest.raises(ValueError, match="fpr"):
+        kmers.KmerBloom.for_capacity(7, 10, 1.5)
+
+
+def test_count_kmers_in_lookups():
+    rng = random.Random(31)
+    hap_a = kmers.KmerSet(11)
+    hap_b = kmers.KmerSet(11)
+    for _ in range(2000):
+        hap_a.add(rng.getrandbits(22))
+        hap_b.add(rng.getrandbits(22))
+    # a Bloom filter this big has practically no false positives
+    hap_a_bloom = kmers.KmerBloom.from_kmer_iter(hap_a, 11, len(hap_a), 1e-9)
+    hap_b_bloom = kmers.KmerBloom.from_kmer_iter(hap_b, 11, len(hap_b), 1e-9)
+
+    for _ in range(50):
+        seq = "".join(rng.choices("ACGTN", k=rng.randint(0, 200)))
+        expected = kmers.count_kmers_in_read_with_skipped(
+            seq, hap_a.hash_set, hap_b.hash_set
+        )
+        assert kmers.count_kmers_in_lookups(seq, hap_a, hap_b) == expected
+        assert kmers.count_kmers_in_lookups(seq, hap_a_bloom, hap_b) == expected
+        assert kmers.count_kmers_in_lookups(seq, hap_a_bloom, hap_b_bloom) == expected
+        assert kmers.count_kmers_in_lookups(seq.encode(), hap_a, hap_b) == expected
+
+    with pytest.raises(ValueError, match="11-mers and 12-mers"):
+        kmers.count_kmers_in_lookups("ACGT", hap_a, kmers.KmerSet(12))
+
+
+@pytest.mark.parametrize("base", ["\xe9", "\u20ac", "\U0001f9ec", "\udce9"])
+def test_count_kmers_in_lookups_non_ascii(base):
+    # C sees one byte for each character, so it skips as many k-mers as
+    # Python does, however many bytes the character is in UTF-8
+    hap_a = kmers.KmerSet(5)
+    hap_a.add(kmers.canonicalize(kmers.kmer_to_int("ACGTA"), 5))
+    seq = "ACGTAC" + base + "ACGTACGT"
+    num_skipped = kmers.count_skipped_kmers(seq, 5)
+    assert num_skipped == 5
+    hits = sum(kmer_int in hap_a for kmer_int in kmers.iter_canonical_kmers(seq, 5))
+    hap_b = kmers.KmerSet(5)
+    assert kmers.count_kmers_in_lookups(seq, hap_a, hap_b) == (hits, 0, num_skipped)
+    counts = kmers.count_kmers_in_lookups_fuzzy(seq, hap_a, hap_b)
+    assert counts.num_skipped == num_skipped
+    assert list(kmers.canonical_kmer_array(seq, 5)) == list(
+        kmers.iter_canonical_kmers(seq, 5)
+    )
+
+
+def make_packed_test_seq(rng, invalid_positions, length=140):
+    seq = list(rng.choices("ACGTacgt", k=length))
+    for position in invalid_positions:
+        seq[position] = rng.choice("NnX")
+    return "".join(seq)
+
+
+@pytest.mark.parametrize(
+    "invalid_positions", [[], [31], [32], [33], [31, 32, 33], [63], [64], [0, 139]]
+)
+def test_packed_seq(invalid_positions):
+    rng = random.Random(len(invalid_positions))
+    seq = make_packed_test_seq(rng, invalid_positions)
+    for ignore_softmasked in [False, True]:
+        packed = kmers.PackedSeq.from_str(seq, ignore_softmasked)
+        assert len(packed) == len(seq)
+        valid = "ACGT" if ignore_softmasked else "ACGTacgt"
+        for position, base in enumerate(seq):
+            expected = "ACGT".index(base.upper()) if base in valid else None
+            assert packed.get(position) == expected
+        for k in [1, 5, 21, 31, 32]:
+            for position in range(len(seq) - k + 1):
+                kmer = seq[position : position + k]
+                expected = (
+                    kmers.kmer_to_int(kmer)
+                    if all(base in valid for base in kmer)
+                    else None
+                )
+                assert packed.kmer_at(position, k) == expected
+            assert list(packed.iter_kmers(k)) == [
+                (position, packed.kmer_at(position, k))
+                for position in range(len(seq) - k + 1)
+                if packed.kmer_at(position, k) is not None
+            ]
+
+
+def test_packed_seq_edges():
+    packed = kmers.PackedSeq.from_bytes(b"ACGTN")
+    assert kmers.PackedSeq.from_str("ACGT\u00e9A").get(4) is None
+    assert len(kmers.PackedSeq.from_bytes(b"")) == 0
+    assert list(kmers.PackedSeq.from_bytes(b"").iter_kmers(3)) == []
+    assert list(packed.iter_kmers(6)) == []
+    for position, k in [(-1, 1), (5, 1), (2, 4)]:
+        with pytest.raises(IndexError, match="out of range"):
+            packed.kmer_at(position, k)
+    with pytest.raises(IndexError):
+        packed.get(5)
+    with pytest.raises(kmers.KmerLengthError):
+        packed.kmer_at(0, 0)
+
+
+def test_count_kmers_in_packed_lookups():
+    rng = random.Random(32)
+    hap_a = kmers.KmerSet(11)
+    hap_b = kmers.KmerSet(11)
+    for _ in range(2000):
+        hap_a.add(rng.getrandbits(22))
+        hap_b.add(rng.getrandbits(22))
+
+    for _ in range(50):
+        seq = "".join(rng.choices("ACGTNacgt", k=rng.randint(0, 200)))
+        start = rng.randint(0, 100)
+        end = start + rng.randint(0, 100)
+        step = rng.randint(1, 4)
+        for ignore_softmasked in [False, True]:
+            packed = kmers.PackedSeq.from_str(seq, ignore_softmasked)
+            # the k-mers starting from start up to end
+            expected = kmers.count_kmers_in_lookups(
+                seq[start : end + 10], hap_a, hap_b, ignore_softmasked, step=step
+            )
+            counts = kmers.count_kmers_in_packed_lookups(
+                packed, hap_a, hap_b, start, end, step
+            )
+            assert counts == expected
+        expected = kmers.count_kmers_in_lookups(seq, hap_a, hap_b, True)
+        assert kmers.count_kmers_in_packed_lookups(packed, hap_a, hap_b) == expected
+
+    with pytest.raises(ValueError, match="11-mers and 12-mers"):
+        kmers.count_kmers_in_packed_lookups(packed, hap_a, kmers.KmerSet(12))
+    with pytest.raises(ValueError, match="negative"):
+        kmers.count_kmers_in_packed_lookups(packed, hap_a, hap_b, -1)
+
+
+@pytest.mark.parametrize("k,window", [(15, 1), (15, 10), (21, 5), (32, 256)])
+def test_minimizers(k, window):
+    rng = random.Random(k + window)
+    seq = "".join(rng.choices("ACGT", k=2000))
+    revcomp = seq[::-1].translate(str.maketrans("ACGT", "TGCA"))
+    minimizers = list(kmers.minimizers(seq, k, window))
+    assert list(kmers.minimizers(revcomp, k, window)) == minimizers[::-1]
+    assert len(minimizers) == len(set(minimizers))
+    if window == 1:
+        assert minimizers == [
+            kmers.canonicalize(kmer_int, k) for kmer_int in kmers.iter_kmers(seq, k)
+        ]
+    else:
+        # about 2 / (window + 1) of the k-mers are minimizers
+        assert len(minimizers) < 3 * len(seq) / (window + 1)
+
+
+def test_minimizers_skipped():
+    # no window of 3 k-mers fits on either side of the N
+    assert list(kmers.minimizers("ACGTANCGTAC", 4, 3)) == []
+    # the window of the first 3 k-mers after the N
+    assert list(kmers.minimizers("ACGTANCGTACG", 4, 3)) == [
+        min(
+            (kmers.canonical_int(kmer) for kmer in ["CGTA", "GTAC", "TACG"]),
+            key=lambda kmer_int: kmers.invertible_hash_c(kmer_int, 0xFF),
+        )
+    ]
+    with pytest.raises(ValueError, match="from 1 to 256"):
+        list(kmers.minimizers("ACGT", 4, 0))
+
+
+def test_count_minimizers_in_lookups():
+    rng = random.Random(41)
+    hap_a = kmers.KmerSet(15)
+    hap_b = kmers.KmerSet(15)
+    for _ in range(50):
+        seq = "".join(rng.choices("ACGTN", weights=[10, 10, 10, 10, 1], k=500))
+        minimizers = list(kmers.minimizers(seq, 15, 8))
+        for minimizer in minimizers:
+            random_set = rng.choice([hap_a, hap_b, kmers.KmerSet(15)])
+            random_set.add(minimizer)
+        counts = kmers.count_minimizers_in_lookups(seq, hap_a, hap_b, 8)
+        assert counts.num_minimizers == len(minimizers)
+        assert counts.hap_a == sum(minimizer in hap_a for minimizer in minimizers)
+        assert counts.hap_b == sum(
+            minimizer in hap_b and minimizer not in hap_a for minimizer in minimizers
+        )
+        assert counts.num_skipped == kmers.count_kmers_in_lookups(seq, hap_a, hap_b)[2]
+
+    with pytest.raises(ValueError, match="not 257"):
+        kmers.count_minimizers_in_lookups("ACGT", hap_a, hap_b, 257)
+
+
+@pytest.mark.parametrize("k,s", [(21, 11), (15, 5), (31, 27), (32, 1), (2, 1)])
+def test_syncmers(k, s):
+    rng = random.Random(k + s)
+    seq = "".join(rng.choices("ACGTNacgt", weights=[20] * 4 + [1] * 5, k=5000))
+    revcomp = seq[::-1].translate(str.maketrans("ACGTacgt", "TGCAtgca"))
+    syncmers = list(kmers.syncmers(seq, k, s))
+    # the same k-mers are picked from either strand
+    assert list(kmers.syncmers(revcomp, k, s)) == syncmers[::-1]
+    masked = seq.translate(str.maketrans("acgt", "NNNN"))
+    assert list(kmers.syncmers(seq, k, s, ignore_softmasked=True)) == list(
+        kmers.syncmers(masked, k, s)
+    )
+
+
+@pytest.mark.parametrize(
+    "k,s,fraction", [(21, 11, 0.182), (15, 5, 0.182), (31, 27, 0.4), (21, 20, 1)]
+)
+def test_syncmers_fraction(k, s, fraction):
+    # the fraction of the k-mers of a random sequence that are picked is
+    # about 2 / (k - s + 1), which is 2 / 11 for the first two
+    seq = "".join(random.Random(k * s).choices("ACGT", k=200000))
+    num_syncmers = sum(1 for _ in kmers.syncmers(seq, k, s))
+    assert num_syncmers / (len(seq) - k + 1) == pytest.approx(fraction, abs=0.01)
+
+
+def test_syncmers_invalid():
+    for s in [0, 21]:
+        with pytest.raises(ValueError, match=f"from 1 to 20, not {s}"):
+            list(kmers.syncmers("ACGT", 21, s))
+
+
+def test_count_syncmers_in_lookups():
+    rng = random.Random(42)
+    hap_a = kmers.KmerSet(15)
+    hap_b = kmers.KmerSet(15)
+    for _ in range(50):
+        seq = "".join(rng.choices("ACGTN", weights=[10, 10, 10, 10, 1], k=500))
+        syncmers = list(kmers.syncmers(seq, 15, 7))
+        for syncmer in syncmers:
+            random_set = rng.choice([hap_a, hap_b, kmers.KmerSet(15)])
+            random_set.add(syncmer)
+        counts = kmers.count_syncmers_in_lookups(seq, hap_a, hap_b, 7)
+        assert counts.num_syncmers == len(syncmers)
+        assert counts.hap_a == sum(syncmer in hap_a for syncmer in syncmers)
+        assert counts.hap_b == sum(
+            syncmer in hap_b and syncmer not in hap_a for syncmer in syncmers
+        )
+        assert counts.num_skipped == kmers.count_kmers_in_lookups(seq, hap_a, hap_b)[2]
+        # either strand finds the same syncmers
+        revcomp = seq[::-1].translate(str.maketrans("ACGT", "TGCA"))
+        assert kmers.count_syncmers_in_lookups(revcomp, hap_a, hap_b, 7) == counts
+
+    with pytest.raises(ValueError, match="from 1 to 14, not 15"):
+        kmers.count_syncmers_in_lookups("ACGT", hap_a, hap_b, 15)
+
+
+def test_cardinality_estimator_accuracy():
+    # 10 M random 12-mers, about 7.5 M of them distinct
+    num_kmers = 10_000_000
+    rng = random.Random(88)
+    kmer_ints = array("Q")
+    random_bytes = rng.getrandbits(64 * num_kmers).to_bytes(8 * num_kmers, "little")
+    kmer_ints.frombytes(random_bytes)
+    kmer_ints = array("Q", (kmer_int & 0xFFFFFF for kmer_int in kmer_ints))
+    distinct, _ = kmers.count_kmer_array(array("Q", kmer_ints))
+    exact = len(distinct)
+    assert 7_000_000 < exact < 8_000_000
+
+    for precision in [8, 10, 12, 14, 16, 18]:
+        estimator = kmers.CardinalityEstimator(precision)
+        estimator.insert_array(kmer_ints)
+        relative_error = estimator.estimate() / exact - 1
+        # well within 4 standard errors
+        assert abs(relative_error) < 4 * estimator.relative_error
+    # the relative standard errors of the precisions are 6.5% down to 0.2%
+    assert kmers.CardinalityEstimator(8).relative_error == pytest.approx(0.065)
+    assert kmers.CardinalityEstimator(18).relative_error == pytest.approx(0.002, 0.02)
+
+
+@pytest.mark.parametrize("num_kmers", [0, 1, 10, 1000, 100000])
+def test_cardinality_estimator_small(num_kmers):
+    # accurate even with far fewer k-mers than registers
+    estimator = kmers.CardinalityEstimator()
+    estimator.insert_array(array("Q", range(num_kmers)))
+    estimator.insert_array(array("Q", range(num_kmers)))
+    assert estimator.estimate() == pytest.approx(num_kmers, rel=0.03)
+
+
+def test_cardinality_estimator_merge():
+    rng = random.Random(89)
+    kmer_ints = [rng.getrandbits(42) for _ in range(50000)]
+    whole = kmers.CardinalityEstimator(12)
+    whole.insert_array(array("Q", kmer_ints))
+    first, second = kmers.CardinalityEstimator(12), kmers.CardinalityEstimator(12)
+    first.insert_array(array("Q", kmer_ints[:30000]))
+    for kmer_int in kmer_ints[20000:]:
+        second.insert(kmer_int)
+    first.merge(second)
+    assert first.registers == whole.registers
+    assert first.estimate() == whole.estimate()
+
+    with pytest.raises(ValueError, match="precision 12 and 13"):
+        first.merge(kmers.CardinalityEstimator(13))
+    for precision in [3, 19]:
+        with pytest.raises(ValueError, match=f"from 4 to 18, not {precision}"):
+            kmers.CardinalityEstimator(precision)
+
+
+@pytest.mark.parametrize("num_counters", [100001, 1000])
+def test_kmer_counting_bloom(num_counters):
+    rng = random.Random(89)
+    kmer_ints = array("Q", (rng.getrandbits(42) for _ in range(30000)))
+    counting_bloom = kmers.KmerCountingBloom(num_counters)
+    exact = Counter()
+    for _ in range(30):
+        # about 9500 distinct k-mers, each added about 3 times, some many more
+        batch = array("Q", rng.choices(kmer_ints[:10000], k=1000))
+        counting_bloom.add_array(batch)
+        exact.update(batch)
+    counting_bloom.add_array(array("Q"))
+
+    distinct = array("Q", exact)
+    counts = counting_bloom.counts(distinct)
+    # never too low, except for stopping at 15
+    assert all(n >= min(exact[kmer_int], 15) for kmer_int, n in zip(distinct, counts))
+    assert max(counts) <= kmers.KmerCountingBloom.MAX_COUNT
+    assert counting_bloom.count(distinct[0]) == counts[0]
+    if num_counters > 10 * len(distinct):
+        # 10 counters per k-mer are nearly exact
+        num_exact = sum(n == exact[kmer_int] for kmer_int, n in zip(distinct, counts))
+        assert num_exact > 0.99 * len(distinct)
+        expected_saturation = 1 - math.exp(-3 * len(distinct) / num_counters)
+        assert counting_bloom.saturation == pytest.approx(expected_saturation, 0.02)
+        assert counting_bloom.false_positive_rate < 0.02
+    else:
+        assert counting_bloom.saturation == 1
+        assert counting_bloom.false_positive_rate == 1
+
+    frequent = array("Q", distinct)
+    counting_bloom.keep_frequent(frequent, 4)
+    assert list(frequent) == [k for k, n in zip(distinct, counts) if n >= 4]
+    # counts above 15 are counted as 15
+    counting_bloom.keep_frequent(frequent, 20)
+    assert list(frequent) == [k for k, n in zip(distinct, counts) if n == 15]
+    # never added, so mostly 0 unless the filter is full
+    never_added = array("Q", (rng.getrandbits(42) for _ in range(1000)))
+    num_false_positives = sum(n > 0 for n in counting_bloom.counts(never_added))
+    assert num_false_positives / 1000 == pytest.approx(
+        counting_bloom.false_positive_rate, abs=0.02
+    )
+
+
+def test_kmer_counting_bloom_invalid():
+    with pytest.raises(ValueError, match="num_counters must be at least 1, not 0"):
+        kmers.KmerCountingBloom(0)
+    with pytest.raises(ValueError, match="num_hashes must be from 1 to 255, not 0"):
+        kmers.KmerCountingBloom(10, 0)
+    # an odd number of counters still has room for the last
+    counting_bloom = kmers.KmerCountingBloom(1, 1)
+    counting_bloom.add_array(array("Q", [5] * 20))
+    assert counting_bloom.count(6) == 15
+    assert counting_bloom.counters == bytearray([15])
+
+
+def test_longest_homopolymer_and_gc_content():
+    assert kmers.longest_homopolymer("ACGGGTtt") == 3
+    assert kmers.longest_homopolymer("aAAAc") == 4
+    assert kmers.longest_homopolymer("ACGT") == 1
+    assert kmers.longest_homopolymer("") == 0
+    assert kmers.gc_content("ACGgta") == 0.5
+    assert kmers.gc_content("AT") == 0
+    assert kmers.gc_content("") == 0
+
+
+@pytest.mark.parametrize("k", [1, 5, 32])
+def test_neighbors(k):
+    rng = random.Random(k)
+    kmer = "".join(rng.choices("ACGT", k=k))
+    neighbors = [
+        kmers.int_to_kmer(kmer_int, k)
+        for kmer_int in kmers.neighbors(kmers.kmer_to_int(kmer), k)
+    ]
+    assert len(neighbors) == 3 * k
+    assert len(set(neighbors)) == 3 * k
+    for neighbor in neighbors:
+        assert sum(a != b for a, b in zip(kmer, neighbor)) == 1
+
+
+def test_count_kmers_in_lookups_fuzzy():
+    rng = random.Random(37)
+    hap_a = kmers.KmerSet(11)
+    hap_b = kmers.KmerSet(11)
+    for _ in range(200):
+        hap_a.add(kmers.canonicalize(rng.getrandbits(22), 11))
+        hap_b.add(kmers.canonicalize(rng.getrandbits(22), 11))
+
+    def has_neighbor(kmer_set, kmer_int):
+        return any(
+            kmers.canonicalize(neighbor, 11) in kmer_set
+            for neighbor in kmers.neighbors(kmer_int, 11)
+        )
+
+    for _ in range(50):
+        seq = "".join(rng.choices("ACGTN", k=rng.randint(0, 200)))
+        expected = [0, 0, 0, 0]
+        for kmer_int in kmers.iter_kmers(seq, 11):
+            canonical = kmers.canonicalize(kmer_int, 11)
+            # a k-mer only counts once, for an exact hit first, and otherwise
+            # for neighbors in only one haplotype
+            if canonical in hap_a:
+                expected[0] += 1
+            elif canonical in hap_b:
+                expected[1] += 1
+            else:
+                near_a = has_neighbor(hap_a, kmer_int)
+                near_b = has_neighbor(hap_b, kmer_int)
+                if near_a and not near_b:
+                    expected[2] += 1
+                elif near_b and not near_a:
+                    expected[3] += 1
+        counts = kmers.count_kmers_in_lookups_fuzzy(seq, hap_a, hap_b)
+        assert counts[:4] == tuple(expected)
+        exact = kmers.count_kmers_in_lookups(seq, hap_a, hap_b)
+        assert (counts.hap_a, counts.hap_b, counts.num_skipped) == exact
+
+
+def test_count_kmers_in_lookups_fuzzy_snp():
+    # the haplotypes differ at a SNP in the middle of the k-mer
+    hap_a = kmers.KmerSet(5)
+    hap_a.add(kmers.canonicalize(kmers.kmer_to_int("AACAA"), 5))
+    hap_b = kmers.KmerSet(5)
+    hap_b.add(kmers.canonicalize(kmers.kmer_to_int("AAGAA"), 5))
+
+    # an error at the SNP is a neighbor of both, so it's no hit for either
+    counts = kmers.count_kmers_in_lookups_fuzzy("AATAA", hap_a, hap_b)
+    assert counts == (0, 0, 0, 0, 0)
+    # but an error elsewhere still leaves the SNP to tell them apart
+    counts = kmers.count_kmers_in_lookups_fuzzy("TACAA", hap_a, hap_b)
+    assert counts == (0, 0, 1, 0, 0)
+    counts = kmers.count_kmers_in_lookups_fuzzy("AAGAT", hap_a, hap_b)
+    assert counts == (0, 0, 0, 1, 0)
@@ -0,0 +1,201 @@
+import gzip
+from os.path import join
+from unittest.mock import patch
+
+import pytest
+
+from trio_binning import pair, seq
+from trio_binning.pair import PairIter
+from trio_binning.pair_tools import main
+from trio_binning.seq import Read
+
+
+def mates(names_1, names_2, desc_1=None, desc_2=None):
+    """First and second mates with some names, with qualities"""
+    return (
+        [Read(name, "ACGT", "IIII", desc_1) for name in names_1],
+        [Read(name, "TTGA", "IIII", desc_2) for name in names_2],
+    )
+
+
+@pytest.mark.parametrize(
+    "names_1,names_2,desc_1,desc_2",
+    [
+        (["pair1/1", "pair2/1"], ["pair1/2", "pair2/2"], None, None),
+        (["pair1", "pair2"], ["pair1", "pair2"], "1:N:0:ACGT", "2:N:0:ACGT"),
+        (["pair1", "pair2"], ["pair1", "pair2"], None, "length=4"),
+    ],
+)
+def test_pair_iter(names_1, names_2, desc_1, desc_2):
+    reads_1, reads_2 = mates(names_1, names_2, desc_1, desc_2)
+    pairs = PairIter(reads_1, reads_2)
+    assert list(pairs) == list(zip(reads_1, reads_2))
+    assert pairs.num_pairs == 2
+    assert list(pairs) == []
+
+    interleaved = [reads_1[0], reads_2[0], reads_1[1], reads_2[1]]
+    pairs = PairIter.interleaved(interleaved)
+    assert list(pairs) == list(zip(reads_1, reads_2))
+    assert pairs.num_pairs == 2
+
+
+def test_mate_number():
+    assert pair.mate_number(Read("pair1/1", "A")) == 1
+    assert pair.mate_number(Read("pair1/2", "A", desc="1:N:0:ACGT")) == 2
+    assert pair.mate_number(Read("pair1", "A", desc="2:Y:0:ACGT")) == 2
+    assert pair.mate_number(Read("pair1", "A", desc="3:N:0:ACGT")) is None
+    assert pair.mate_number(Read("pair1", "A", desc="12:N")) is None
+    assert pair.mate_number(Read("pair1", "A")) is None
+    assert pair.pair_name(Read("pair1/2", "A")) == "pair1"
+
+
+def test_pair_iter_out_of_sync():
+    reads_1, reads_2 = mates(["pair1/1", "pair2/1"], ["pair1/2", "pair3/2"])
+    with pytest.raises(
+        seq.SeqError,
+        match=r"read pair 2: mate names 'pair2/1' \(record 2 of its file\) and "
+        r"'pair3/2' \(record 2 of its file\) do not match",
+    ):
+        list(PairIter(reads_1, reads_2))
+    with pytest.raises(
+        seq.SeqError,
+        match=r"read pair 2: the second mates ran out before the other mates, "
+        r"leaving 'pair2/1' \(record 2 of its file\) unpaired",
+    ):
+        list(PairIter(reads_1, reads_2[:1]))
+    with pytest.raises(
+        seq.SeqError, match=r"read pair 2: the first mates ran out .* 'pair3/2'"
+    ):
+        list(PairIter(reads_1[:1], reads_2))
+
+    # a missing second mate puts the rest of the pairs out of step
+    interleaved = [reads_1[0], reads_2[0], reads_1[1], reads_1[0]]
+    with pytest.raises(
+        seq.SeqError,
+        match=r"read pair 2: mate names 'pair2/1' \(record 3\) and 'pair1/1' "
+        r"\(record 4\)",
+    ):
+        list(PairIter.interleaved(interleaved))
+    with pytest.raises(
+        seq.SeqError,
+        match=r"the second mates ran out .* leaving 'pair2/1' \(record 3\) unpaired",
+    ):
+        list(PairIter.interleaved(interleaved[:3]))
+
+
+def test_pair_iter_wrong_mate():
+    reads_1, reads_2 = mates(["pair1/1"], ["pair1/2"])
+    with pytest.raises(
+        seq.SeqError,
+        match=r"read pair 1: 'pair1/2' \(record 1 of its file\) is marked as mate 2 "
+        "but is in the place of mate 1",
+    ):
+        list(PairIter(reads_2, reads_1))
+    reads_1, reads_2 = mates(["pair1"], ["pair1"], "1:N:0:ACGT", "1:N:0:ACGT")
+    with pytest.raises(seq.SeqError, match="is marked as mate 1 but is in the place"):
+        list(PairIter.interleaved(reads_1 + reads_2))
+
+
+def test_pair_iter_streams():
+    """Pairs are read one at a time, however many reads there are"""
+
+    def reads(mate):
+        for i in range(1000000):
+            yield Read(f"pair{i}/{mate}", "ACGT")
+        raise AssertionError("read every read")
+
+    pairs = PairIter(reads(1), reads(2))
+    for _ in range(3):
+        next(pairs)
+    assert pairs.num_pairs == 3
+
+
+@pytest.mark.parametrize("extension", [".fq", ".fq.gz"])
+def test_interleave_and_split(tmpdir, extension):
+    reads_1, reads_2 = mates(["pair1/1", "pair2/1"], ["pair1/2", "pair2/2"])
+    for mate, reads in [(1, reads_1), (2, reads_2)]:
+        with seq.open_text_write(join(tmpdir, f"reads_{mate}{extension}")) as fp:
+            for read in reads:
+                seq.write_record(read, fp)
+
+    interleaved_path = join(tmpdir, f"interleaved{extension}")
+    argv = [
+        "pair",
+        "interleave",
+        "--r1",
+        join(tmpdir, f"reads_1{extension}"),
+        "--r2",
+        join(tmpdir, f"reads_2{extension}"),
+        "-o",
+        interleaved_path,
+    ]
+    with patch("sys.argv", argv):
+        main()
+    interleaved = [reads_1[0], reads_2[0], reads_1[1], reads_2[1]]
+    assert list(seq.open_fastx_read(interleaved_path)) == interleaved
+    with open(interleaved_path, "rb") as fp:
+        assert (fp.read(2) == seq.GZIP_MAGIC) == extension.endswith(".gz")
+
+    out_paths = [join(tmpdir, f"out_{mate}{extension}") for mate in [1, 2]]
+    argv = ["pair", "split", "--interleaved", interleaved_path]
+    with patch("sys.argv", argv + ["--r1", out_paths[0], "--r2", out_paths[1]]):
+        main()
+    assert list(seq.open_fastx_read(out_paths[0])) == reads_1
+    assert list(seq.open_fastx_read(out_paths[1])) == reads_2
+
+
+def test_interleave_stdout(capsys, tmpdir):
+    for mate in [1, 2]:
+        with gzip.open(join(tmpdir, f"reads_{mate}.fa.gz"), "wt") as fp:
+            print(f">pair1 {mate}:N:0:ACGT\nACGT", file=fp)
+    reads_paths = [join(tmpdir, f"reads_{mate}.fa.gz") for mate in [1, 2]]
+    argv = ["pair", "interleave", "--r1", reads_paths[0], "--r2", reads_paths[1]]
+    with patch("sys.argv", argv):
+        main()
+    out, _ = capsys.readouterr()
+    assert out == ">pair1 1:N:0:ACGT\nACGT\n>pair1 2:N:0:ACGT\nACGT\n"
+
+
+def test_interleave_multiline_fastq(capsys, tmpdir):
+    reads_paths = [join(tmpdir, f"reads_{mate}.fq") for mate in [1, 2]]
+    for mate, reads_path in enumerate(reads_paths, 1):
+        with open(reads_path, "w") as fp:
+            print(f"@pair1/{mate}\nAC\nGT\n+\nIIII", file=fp)
+    argv = ["pair", "interleave", "--r1", reads_paths[0], "--r2", reads_paths[1]]
+    with patch("sys.argv", argv + ["--multiline-fastq"]):
+        main()
+    out, _ = capsys.readouterr()
+    assert out == "@pair1/1\nACGT\n+\nIIII\n@pair1/2\nACGT\n+\nIIII\n"
+
+
+def test_split_out_of_sync(tmpdir):
+    interleaved_path = join(tmpdir, "interleaved.fa")
+    with open(interleaved_path, "w") as fp:
+        fp.write(">pair1/1\nACGT\n>pair1/2\nACGT\n>pair2/1\nACGT\n>pair3/2\nACGT\n")
+    out_paths = [join(tmpdir, f"out_{mate}.fa") for mate in [1, 2]]
+    argv = ["pair", "split", "--interleaved", interleaved_path]
+    with patch("sys.argv", argv + ["--r1", out_paths[0], "--r2", out_paths[1]]):
+        with pytest.raises(SystemExit) as excinfo:
+            main()
+    assert str(excinfo.value.code) == (
+        "Error: read pair 2: mate names 'pair2/1' (record 3) and 'pair3/2' "
+        "(record 4) do not match; the mates are out of sync"
+    )
+
+
+@pytest.mark.parametrize(
+    "args,message",
+    [
+        (["interleave", "--r1", "a.fq", "--r2", "-"], "only --r1 can be -"),
+        (["interleave", "--r1", "a.fq", "--r2", "b.fq", "-o", "b.fq"], "--output"),
+        (["split", "--interleaved", "a.fq", "--r1", "-", "--r2", "b.fq"], "stdout"),
+        (["split", "--interleaved", "a.fq", "--r1", "b.fq", "--r2", "b.fq"], "differ"),
+        (["split", "--interleaved", "a.fq", "--r1", "a.fq", "--r2", "b.fq"], "reads"),
+    ],
+)
+def test_pair_tools_invalid(capsys, args, message):
+    with patch("sys.argv", ["pair", *args]):
+        with pytest.raises(SystemExit):
+            main()
+    _, err = capsys.readouterr()
+    assert message in err
@@ -0,0 +1,295 @@
+import gzip
+import io
+import json
+import os
+from os.path import dirname, join
+from unittest.mock import patch
+
+import pytest
+
+from trio_binning import seq
+from trio_binning.read_tools import main
+
+
+def test_split(capsys, tmpdir):
+    reads_path = join(dirname(__file__), "data", "test.ccs.fastq.gz")
+    prefix = join(tmpdir, "shard")
+    with patch("sys.argv", ["reads", "split", reads_path, "-n", "2", "-o", prefix]):
+        main()
+    _, err = capsys.readouterr()
+    assert sorted(os.listdir(tmpdir)) == ["shard.1.fastq.gz", "shard.2.fastq.gz"]
+    reads = list(seq.open_fastx_read(reads_path))
+    shards = [
+        list(seq.open_fastx_read(join(tmpdir, f"shard.{i}.fastq.gz"))) for i in [1, 2]
+    ]
+    assert shards == [reads[::2], reads[1::2]]
+    lines = err.splitlines()
+    assert lines[0] == "file\treads\tbases"
+    assert lines[1] == (
+        f"{prefix}.1.fastq.gz\t{len(shards[0])}\t{sum(map(len, shards[0]))}"
+    )
+
+
+def test_split_help(capsys):
+    with patch("sys.argv", ["reads", "split", "--help"]):
+        with pytest.raises(SystemExit):
+            main()
+    out, _ = capsys.readouterr()
+    assert "--by {reads,bases}" in out
+    assert "(default: reads)" in out
+
+
+def test_split_pairs(capsys, tmpdir):
+    for mate in [1, 2]:
+        with gzip.open(join(tmpdir, f"reads_{mate}.fa.gz"), "wt") as mate_file:
+            for i in range(5):
+                print(f">pair{i}/{mate}\n" + "ACGT" * (i + 1), file=mate_file)
+    with patch(
+        "sys.argv",
+        [
+            "reads",
+            "split",
+            "-1",
+            join(tmpdir, "reads_1.fa.gz"),
+            "-2",
+            join(tmpdir, "reads_2.fa.gz"),
+            "-n",
+            "2",
+            "-o",
+            join(tmpdir, "shard"),
+            "--by",
+            "bases",
+        ],
+    ):
+        main()
+    capsys.readouterr()
+    mates = [
+        [read.name for read in seq.open_fastx_read(mate_path)]
+        for mate_path in [join(tmpdir, f"shard.1_R{mate}.fa.gz") for mate in [1, 2]]
+    ]
+    # each pair is longer than the last, so it goes to the other shard
+    assert mates == [
+        ["pair0/1", "pair2/1", "pair4/1"],
+        ["pair0/2", "pair2/2", "pair4/2"],
+    ]
+
+
+@pytest.mark.parametrize(
+    "args,message",
+    [
+        (["-n", "2", "-o", "x"], "give either reads or --reads-1 and --reads-2"),
+        (["reads.fa", "-n", "0", "-o", "x"], "--num-shards must be at least 1"),
+        (["-1", "r1.fa", "-n", "2", "-o", "x"], "must be given together"),
+    ],
+)
+def test_split_invalid(capsys, args, message):
+    with patch("sys.argv", ["reads", "split", *args]):
+        with pytest.raises(SystemExit):
+            main()
+    _, err = capsys.readouterr()
+    assert message in err
+
+
+@pytest.fixture
+def reads_and_ids(tmpdir):
+    reads_path = join(tmpdir, "reads.fq")
+    with open(reads_path, "w") as reads_file:
+        for i in range(1, 4):
+            print(f"@read{i} length=4\nACGT\n+\nIIII", file=reads_file)
+    ids_path = join(tmpdir, "ids.txt")
+    with open(ids_path, "w") as ids_file:
+        print("read3\nread1\nread5", file=ids_file)
+    return reads_path, ids_path
+
+
+def test_extract(capsys, tmpdir, reads_and_ids):
+    reads_path, ids_path = reads_and_ids
+    with patch("sys.argv", ["reads", "extract", reads_path, "--ids", ids_path]):
+        main()
+    out, _ = capsys.readouterr()
+    assert out == "@read1 length=4\nACGT\n+\nIIII\n@read3 length=4\nACGT\n+\nIIII\n"
+
+    output_path = join(tmpdir, "out.fq.gz")
+    with patch(
+        "sys.argv",
+        ["reads", "extract", reads_path, "--ids", ids_path, "--invert"]
+        + ["-o", output_path],
+    ):
+        main()
+    assert [read.name for read in seq.open_fastx_read(output_path)] == ["read2"]
+
+
+def test_extract_strict(capsys, reads_and_ids):
+    reads_path, ids_path = reads_and_ids
+    with patch(
+        "sys.argv", ["reads", "extract", reads_path, "--ids", ids_path, "--strict"]
+    ):
+        with pytest.raises(SystemExit, match="1 ids weren't found in .*: read5"):
+            main()
+    out, _ = capsys.readouterr()
+    # the reads found are still written
+    assert out.count("@read") == 2
+
+
+def test_stats(capsys, tmpdir):
+    stats_path = join(dirname(__file__), "data", "stats.fa")
+    short_path = join(tmpdir, "short.fq")
+    with open(short_path, "w") as fp:
+        print("@read1\nGGN\n+\nIII", file=fp)
+    with patch("sys.argv", ["reads", "stats", stats_path, short_path]):
+        main()
+    out, _ = capsys.readouterr()
+    header, *rows = [line.split() for line in out.splitlines()]
+    assert header == [
+        "file",
+        "reads",
+        "bases",
+        "min_len",
+        "mean_len",
+        "median_len",
+        "max_len",
+        "N50",
+        "N90",
+        "GC",
+        "Ns",
+    ]
+    assert rows == [
+        [stats_path, "6", "30", "2", "5.0", "4.5", "10", "6", "3", "50.00%", "6"],
+        [short_path, "1", "3", "3", "3.0", "3.0", "3", "3", "3", "100.00%", "1"],
+    ]
+    # the columns line up
+    assert len({len(line) for line in out.splitlines()}) == 1
+
+    with patch("sys.argv", ["reads", "stats", stats_path, "--json"]):
+        main()
+    out, _ = capsys.readouterr()
+    stats = json.loads(out)
+    assert len(stats) == 1
+    assert stats[0]["file"] == stats_path
+    assert stats[0]["n50"] == 6
+    assert stats[0]["median_length"] == 4.5
+
+
+def test_stats_multiline_fastq(capsys, tmpdir):
+    reads_path = join(tmpdir, "wrapped.fq")
+    with open(reads_path, "w") as fp:
+        print("@read1\nACG\nT\n+\nII\nII\n@read2\nGG\n+\nII", file=fp)
+    with patch("sys.argv", ["reads", "stats", reads_path]):
+        with pytest.raises(SystemExit) as excinfo:
+            main()
+    assert "fastq record 0" in str(excinfo.value.code)
+
+    argv = ["reads", "stats", reads_path, "--json"]
+    with patch("sys.argv", argv + ["--multiline-fastq"]):
+        main()
+    out, _ = capsys.readouterr()
+    stats = json.loads(out)
+    assert stats[0]["records"] == 2
+    assert stats[0]["bases"] == 6
+
+
+def test_split_scaffolds(capsys, tmpdir):
+    assembly_path = join(tmpdir, "assembly.fa")
+    with open(assembly_path, "w") as fp:
+        print(f">scaffold1 desc\nACGT{'N' * 10}GG\nCC{'N' * 12}", file=fp)
+        print(f">scaffold2\n{'N' * 10}", file=fp)
+    out_path = join(tmpdir, "contigs.fa.gz")
+    argv = ["reads", "split-scaffolds", assembly_path, "-o", out_path]
+    with patch("sys.argv", argv):
+        main()
+    _, err = capsys.readouterr()
+    assert err == "Split 2 scaffolds into 2 contigs\n"
+    assert list(seq.open_fastx_read(out_path)) == [
+        seq.Read("scaffold1_part1", "ACGT", None, "scaffold=scaffold1 offset=0 desc"),
+        seq.Read("scaffold1_part2", "GGCC", None, "scaffold=scaffold1 offset=14 desc"),
+    ]
+
+    # the gap at the end of scaffold1 is still trimmed
+    with patch("sys.argv", argv[:-2] + ["--min-gap", "11"]):
+        main()
+    out, err = capsys.readouterr()
+    assert [read.seq for read in seq.readfq(io.StringIO(out))] == [
+        f"ACGT{'N' * 10}GGCC",
+        "N" * 10,
+    ]
+    assert err == "Split 2 scaffolds into 2 contigs\n"
+
+
+def test_split_scaffolds_invalid(capsys):
+    with patch("sys.argv", ["reads", "split-scaffolds", "a.fa", "--min-gap", "0"]):
+        with pytest.raises(SystemExit):
+            main()
+    _, err = capsys.readouterr()
+    assert "--min-gap must be at least 1, not 0" in err
+
+
+@pytest.mark.parametrize("exact", [False, True])
+def test_dedup(capsys, tmpdir, exact):
+    reads_path = join(tmpdir, "reads.fq")
+    with open(reads_path, "w") as fp:
+        for name, sequence in [("read1", "ACGG"), ("read2", "ACGG"), ("read3", "CCGT")]:
+            print(f"@{name}\n{sequence}\n+\nIIII", file=fp)
+    argv = ["reads", "dedup", reads_path] + (["--exact"] if exact else [])
+    with patch("sys.argv", argv):
+        main()
+    out, err = capsys.readouterr()
+    assert [read.name for read in seq.readfq(io.StringIO(out))] == ["read1", "read3"]
+    assert err == "Removed 1 duplicates of 3 reads\n"
+    # read3 is the reverse complement of read1
+    out_path = join(tmpdir, "dedup.fq.gz")
+    with patch("sys.argv", argv + ["--consider-revcomp", "-o", out_path]):
+        main()
+    _, err = capsys.readouterr()
+    assert [read.name for read in seq.open_fastx_read(out_path)] == ["read1"]
+    assert err == "Removed 2 duplicates of 3 reads\n"
+
+
+def test_dedup_pairs(capsys, tmpdir):
+    mates = {
+        1: [("pair1/1", "ACGT"), ("pair2/1", "ACGT"), ("pair3/1", "ACG")],
+        2: [("pair1/2", "GGCC"), ("pair2/2", "GGCC"), ("pair3/2", "TGGCC")],
+    }
+    for mate, reads in mates.items():
+        with open(join(tmpdir, f"reads_{mate}.fa"), "w") as fp:
+            for name, sequence in reads:
+                print(f">{name}\n{sequence}", file=fp)
+    out_paths = [join(tmpdir, f"out_{mate}.fa") for mate in [1, 2]]
+    argv = [
+        "reads",
+        "dedup",
+        "-1",
+        join(tmpdir, "reads_1.fa"),
+        "-2",
+        join(tmpdir, "reads_2.fa"),
+        "--output-1",
+        out_paths[0],
+        "--output-2",
+        out_paths[1],
+    ]
+    with patch("sys.argv", argv):
+        main()
+    _, err = capsys.readouterr()
+    assert err == "Removed 1 duplicates of 3 read pairs\n"
+    for mate, out_path in zip([1, 2], out_paths):
+        assert [read.name for read in seq.open_fastx_read(out_path)] == [
+            f"pair1/{mate}",
+            f"pair3/{mate}",
+        ]
+
+
+@pytest.mark.parametrize(
+    "args,message",
+    [
+        ([], "give either reads or --reads-1 and --reads-2"),
+        (["-1", "a.fq"], "must be given together"),
+        (["-1", "a.fq", "-2", "b.fq"], "need --output-1 and --output-2"),
+        (["a.fq", "--output-1", "b.fq"], "are for read pairs"),
+        (["-", "--exact"], "can't be stdin"),
+    ],
+)
+def test_dedup_invalid(capsys, args, message):
+    with patch("sys.argv", ["reads", "dedup", *args]):
+        with pytest.raises(SystemExit):
+            main()
+    _, err = capsys.readouterr()
+    assert message in err
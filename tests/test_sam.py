@@ -0,0 +1,158 @@
+import gzip
+import os
+import sys
+from unittest.mock import patch
+
+import pytest
+
+from trio_binning import sam, seq
+
+# what samtools fasta/fastq make of test.sam and test.bam: the secondary and
+# supplementary records are skipped, and the reverse-strand read is reverse
+# complemented back
+EXPECTED = [
+    seq.Read("read1", "GATTTGGGGTTC", "!!**((++55CC"),
+    seq.Read("read2", "TACCGGTT", "HGFEDCBA"),
+    seq.Read("read3", "ACGTNA", None),
+]
+
+
+def data_path(filename):
+    return os.path.join(os.path.dirname(__file__), "data", filename)
+
+
+def test_read_sam():
+    with open(data_path("test.sam")) as fp:
+        assert list(sam.read_sam(fp)) == EXPECTED
+
+
+@pytest.mark.parametrize("gzipped", [False, True])
+def test_open_records_sam(tmpdir, gzipped):
+    records_path = os.path.join(tmpdir, "reads")
+    with open(data_path("test.sam"), "rb") as infile:
+        contents = infile.read()
+    with (gzip.open if gzipped else open)(records_path, "wb") as outfile:
+        outfile.write(contents)
+
+    assert list(seq.open_records(records_path)) == EXPECTED
+
+
+def test_open_records_headerless_sam(tmpdir):
+    records_path = os.path.join(tmpdir, "reads.sam")
+    with open(data_path("test.sam")) as infile, open(records_path, "w") as outfile:
+        outfile.writelines(line for line in infile if not line.startswith("@"))
+
+    assert list(seq.open_records(records_path)) == EXPECTED
+
+
+def test_open_records_bam():
+    pytest.importorskip("pysam")
+    assert list(seq.open_records(data_path("test.bam"))) == EXPECTED
+
+
+def test_open_records_bam_without_pysam():
+    # None in sys.modules makes importing it raise ImportError
+    with patch.dict(sys.modules, {"pysam": None}):
+        with pytest.raises(seq.SeqError, match=r"trio_binning\[bam\]"):
+            list(seq.open_records(data_path("test.bam")))
+
+
+@pytest.mark.parametrize(
+    "line,message",
+    [
+        ("read1\t4\t*\t0\t0\t*\t*\t0\t0\tACGT\n", "expected at least 11 columns"),
+        ("read1\tx\t*\t0\t0\t*\t*\t0\t0\tACGT\tIIII\n", "flag 'x' is not a number"),
+    ],
+)
+def test_read_sam_bad(line, message):
+    with pytest.raises(sam.SamError, match=message):
+        list(sam.read_sam(["@HD\tVN:1.6\n", line]))
+
+
+def test_program_record():
+    programs = [{"ID": "ccs", "PN": "ccs"}, {"ID": "classify-by-kmers"}]
+    assert sam.program_record("classify-by-kmers", "tb x", programs) == {
+        "ID": "classify-by-kmers.1",
+        "PN": "classify-by-kmers",
+        "PP": "classify-by-kmers",
+        "CL": "tb x",
+    }
+    assert sam.program_record("tb", "tb x", []) == {
+        "ID": "tb",
+        "PN": "tb",
+        "CL": "tb x",
+    }
+
+
+def test_tagger_sam(tmpdir):
+    out_path = os.path.join(tmpdir, "tagged.sam")
+    with sam.open_tagger(data_path("test.sam"), out_path, "tb", "tb x") as tagger:
+        alignments = list(tagger.alignments)
+        reads = [seq.Read(a.name, a.seq, a.qual, a.desc) for a in alignments]
+        assert reads == EXPECTED
+        # the supplementary and secondary records after read2 go with it
+        assert len(alignments[1].others) == 2
+        tagger.write(alignments[0], {"HP": 1, "XY": 3})
+        # read2 is copied unchanged, along with the records after it
+        tagger.write(alignments[2], {"HP": None, "RG": None})
+
+    with open(out_path) as fp:
+        lines = fp.read().splitlines()
+    with open(data_path("test.sam")) as fp:
+        original = fp.read().splitlines()
+    assert lines == original[:3] + [
+        "@PG\tID:tb\tPN:tb\tCL:tb x",
+        original[3] + "\tHP:i:1\tXY:i:3",
+        *original[4:7],
+        original[7].replace("\tRG:Z:m64234e", ""),
+    ]
+
+
+def test_tagger_sam_unwritten(tmpdir):
+    out_path = os.path.join(tmpdir, "tagged.sam")
+    with sam.open_tagger(data_path("test.sam"), out_path, "tb", "tb x") as tagger:
+        alignments = list(tagger.alignments)
+        tagger.write(alignments[1], {"HP": 2})
+
+    with open(out_path) as fp:
+        lines = fp.read().splitlines()
+    with open(data_path("test.sam")) as fp:
+        original = fp.read().splitlines()
+    # the records never written are copied when the tagger is closed
+    assert lines == original[:3] + [
+        "@PG\tID:tb\tPN:tb\tCL:tb x",
+        original[3],
+        original[4] + "\tHP:i:2",
+        *original[5:],
+    ]
+
+
+def test_tagger_bam(tmpdir):
+    pysam = pytest.importorskip("pysam")
+    out_path = os.path.join(tmpdir, "tagged.bam")
+    with sam.open_tagger(data_path("test.bam"), out_path, "tb", "tb x") as tagger:
+        for i, alignment in enumerate(tagger.alignments):
+            tagger.write(alignment, {"HP": i or None, "hA": i})
+
+    with pysam.AlignmentFile(out_path, "rb", check_sq=False) as bam:
+        assert bam.header.to_dict()["PG"] == [{"ID": "tb", "PN": "tb", "CL": "tb x"}]
+        records = list(bam.fetch(until_eof=True))
+    assert [(record.query_name, record.flag) for record in records] == [
+        ("read1", 4),
+        ("read2", 16),
+        ("read2", 2304),
+        ("read1", 256),
+        ("read3", 0),
+    ]
+    assert not records[0].has_tag("HP")
+    assert records[0].get_tag("hA") == 0
+    assert records[1].get_tag("HP") == 1
+    assert not records[2].has_tag("HP")
+    assert records[4].get_tag("HP") == 2
+
+
+def test_read_sam_skip_malformed():
+    errors = []
+    lines = ["@HD\tVN:1.6\n", "read1\tx\n", "read2\t4" + "\t*" * 9 + "\n"]
+    assert [read.name for read in sam.read_sam(lines, errors.append)] == ["read2"]
+    assert [error.line_number for error in errors] == [2]
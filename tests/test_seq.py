@@ -1,5 +1,16 @@
+import gzip
+import io
+import json
 import os
+import random
+import shutil
+from concurrent.futures import ThreadPoolExecutor
+from dataclasses import replace
 from io import StringIO
+from unittest.mock import patch
+
+import pytest
+from helpers import OtherRecord
 
 from trio_binning import seq
 
@@ -24,6 +35,29 @@ def test_read_fastq():
         assert read.qual.startswith("!")
 
 
+@pytest.mark.parametrize(
+    "read",
+    [
+        seq.Read("read1", "ACGT"),
+        seq.Read("read1", "ACGT", "!!#I", "sample=1"),
+        seq.Read("read1", "", ""),
+    ],
+)
+def test_read_round_trip(read):
+    read_dict = json.loads(json.dumps(read.to_dict()))
+    assert seq.Read.from_dict(read_dict) == read
+
+
+def test_read_from_dict_invalid():
+    assert seq.Read("read1", "ACGT").to_dict() == {"name": "read1", "seq": "ACGT"}
+    with pytest.raises(ValueError, match="unknown read fields: sequence"):
+        seq.Read.from_dict({"name": "read1", "sequence": "ACGT"})
+    with pytest.raises(ValueError, match="needs a seq"):
+        seq.Read.from_dict({"name": "read1"})
+    with pytest.raises(ValueError, match="qual of a read must be a string"):
+        seq.Read.from_dict({"name": "read1", "seq": "A", "qual": [30]})
+
+
 def test_write_fasta():
     sio = StringIO()
     seq.Read("read1", "AGATAGAGGACTGA").print(file=sio)
@@ -41,3 +75,967 @@ def test_write_fastq():
         "@read2\nAGGGGATTTTATTA\n+\n++(*))*+%%%))(\n"
     )
     sio.close()
+
+
+def test_read_fastq_strict():
+    fastq_path = os.path.join(os.path.dirname(__file__), "data", "test.fastq")
+
+    with open(fastq_path) as strict_fp, open(fastq_path) as loose_fp:
+        assert list(seq.read_fastq(strict_fp)) == list(seq.readfq(loose_fp))
+
+
+@pytest.mark.parametrize(
+    "contents,record_index,message",
+    [
+        ("@read1\nACGT\n+\nIIII\n@read2\nACGT\n", 1, r"before the \+ line"),
+        ("@read1\nACGT\n+\nIIII\n@read2\nACGT\n+\n", 1, "before the quality line"),
+        ("@read1\nACGT\n+\nIII\n", 0, "quality length 3"),
+        ("@read1\nACGT\n-\nIIII\n", 0, r"does not start with \+"),
+        ("@read1\nACGT\n+\nIIII\n>read2\nACGT\n+\nIIII\n", 1, "does not start with @"),
+    ],
+)
+def test_read_fastq_malformed(contents, record_index, message):
+    with pytest.raises(seq.FastqError, match=message) as excinfo:
+        list(seq.read_fastq(StringIO(contents)))
+    assert excinfo.value.record_index == record_index
+
+
+GOOD_1 = "@good1\nACGT\n+\nIIII\n"
+GOOD_2 = "@good2 desc\nGGCC\n+\n@III\n"
+
+
+@pytest.mark.parametrize(
+    "bad,num_errors",
+    [
+        # cut off partway through, before the next record
+        ("@bad\nACGT\n", 1),
+        ("@bad\nACGT\n+\n", 1),
+        # qualities too short, which start with @ like a header
+        ("@bad\nACGT\n+\n@II\n", 1),
+        ("@bad\nACGT\n-\nIIII\n", 1),
+        # garbage between records, which is one error for the first line and
+        # then skipped over
+        ("garbage\n\x00\x00\nmore garbage\n", 1),
+        # two bad records in a row
+        ("@bad1\nACGT\n+\nIII\n@bad2\nAC\n+\nI\n", 2),
+    ],
+)
+def test_read_fastq_skip_malformed(bad, num_errors):
+    errors = []
+    contents = GOOD_1 + bad + GOOD_2 + GOOD_1
+    reads = list(seq.read_fastq(StringIO(contents), errors.append))
+    assert [read.name for read in reads] == ["good1", "good2", "good1"]
+    assert reads[1] == seq.Read("good2", "GGCC", "@III", "desc")
+    assert len(errors) == num_errors
+    assert all(isinstance(error, seq.FastqError) for error in errors)
+    assert errors[0].record_index == 1
+
+
+def test_read_fastq_skip_malformed_at_end():
+    errors = []
+    reads = list(seq.read_fastq(StringIO(GOOD_1 + "@bad\nACGT\n+"), errors.append))
+    assert [read.name for read in reads] == ["good1"]
+    assert len(errors) == 1
+
+
+def test_read_fastq_multiline():
+    contents = (
+        "@read1 desc\nACGT\nAC\n+\nII\n@III\n"
+        # a quality line that starts with @ and looks like a whole record
+        "@read2\nACGTACGTAC\n+read2\n@read\n+II\nII\n\n"
+        # an empty record, and one in the usual four lines
+        "@read3\n+\n@read4\r\nAC\r\n+\r\n#I\r\n"
+    )
+    assert list(seq.read_fastq(StringIO(contents), multiline=True)) == [
+        seq.Read("read1", "ACGTAC", "II@III", "desc"),
+        seq.Read("read2", "ACGTACGTAC", "@read+IIII"),
+        seq.Read("read3", "", ""),
+        seq.Read("read4", "AC", "#I"),
+    ]
+    # four-line files read the same either way
+    fastq_path = os.path.join(os.path.dirname(__file__), "data", "test.fastq")
+    with open(fastq_path) as strict_fp, open(fastq_path) as multiline_fp:
+        assert list(seq.read_fastq(strict_fp)) == list(
+            seq.read_fastq(multiline_fp, multiline=True)
+        )
+    with pytest.raises(seq.FastqError, match="does not start with"):
+        list(seq.read_fastq(StringIO("@read1\nAC\nGT\n+\nIIII\n")))
+
+
+@pytest.mark.parametrize(
+    "contents,record_index,message",
+    [
+        ("@read1\nACGT\n+\nIIII\n@read2\nACGT\n", 1, r"before the \+ line"),
+        ("@read1\nAC\nGT\n@read2\nACGT\n+\nIIII\n", 0, r"'@read2' before the \+"),
+        ("@read1\nACGT\n+\nII\n", 0, "file ends after 2 of 4 quality characters"),
+        ("@read1\nACGT\n+\nII\nIII\n", 0, "quality length 5"),
+        # a quality cut short, which would take in the next record without the
+        # space in its header
+        ("@read1\nACGT\n+\nII\n@read2 desc\nA\n+\nI\n", 0, "cut short at 2 of 4"),
+        # and one cut short by just the length of the next header, which the
+        # line after it gives away
+        ("@read1\nACGT\n+\nII\n@r\nACGT\n+\nIIII\n", 0, "ran into the next"),
+        (">read1\nACGT\n", 0, "does not start with @"),
+    ],
+)
+def test_read_fastq_multiline_malformed(contents, record_index, message):
+    with pytest.raises(seq.FastqError, match=message) as excinfo:
+        list(seq.read_fastq(StringIO(contents), multiline=True))
+    assert excinfo.value.record_index == record_index
+
+
+@pytest.mark.parametrize(
+    "bad,num_errors",
+    [
+        ("@bad\nACGT\n", 1),
+        ("@bad\nACGT\n+\nIII\nII\n", 1),
+        ("@bad\nAC\nGT\n", 1),
+        ("garbage\n\x00\x00\nmore garbage\n", 1),
+        ("@bad1\nACGT\n+\nII II\n@bad2\nAC\n", 2),
+        ("@bad\nACGT\n+\nII\n@b\nAC\n+\nII\n", 1),
+    ],
+)
+def test_read_fastq_multiline_skip_malformed(bad, num_errors):
+    errors = []
+    contents = GOOD_1 + bad + GOOD_2 + GOOD_1
+    reads = list(seq.read_fastq(StringIO(contents), errors.append, multiline=True))
+    assert [read.name for read in reads] == ["good1", "good2", "good1"]
+    assert reads[1] == seq.Read("good2", "GGCC", "@III", "desc")
+    assert len(errors) == num_errors
+    assert errors[0].record_index == 1
+
+
+def test_open_records_multiline_fastq(tmpdir):
+    records_path = os.path.join(tmpdir, "reads.fq")
+    with open(records_path, "w") as fp:
+        fp.write("@read1\nAC\nGT\n+\nII\nII\n")
+    with pytest.raises(seq.FastqError):
+        list(seq.open_records(records_path))
+    assert list(seq.open_records(records_path, multiline_fastq=True)) == [
+        seq.Read("read1", "ACGT", "IIII")
+    ]
+
+
+@pytest.mark.parametrize("filename", ["reads.fa.gz", "reads.fa"])
+def test_open_fastx_read_gzipped(tmpdir, filename):
+    fasta_path = os.path.join(os.path.dirname(__file__), "data", "test.fa")
+    gzipped_path = os.path.join(tmpdir, filename)
+    with open(fasta_path, "rb") as infile, gzip.open(gzipped_path, "wb") as outfile:
+        outfile.write(infile.read())
+
+    assert list(seq.open_fastx_read(gzipped_path)) == list(
+        seq.readfq(open(fasta_path))
+    )
+
+
+@pytest.mark.parametrize("gzipped", [False, True])
+def test_open_text_read_stdin(gzipped):
+    fasta_path = os.path.join(os.path.dirname(__file__), "data", "test.fa")
+    with open(fasta_path, "rb") as fp:
+        contents = fp.read()
+    if gzipped:
+        contents = gzip.compress(contents)
+    stdin = io.TextIOWrapper(io.BufferedReader(io.BytesIO(contents)))
+
+    with patch("sys.stdin", stdin):
+        reads = list(seq.open_records("-"))
+    assert reads == list(seq.readfq(open(fasta_path)))
+
+
+def test_exit_on_broken_pipe(tmpdir):
+    @seq.exit_on_broken_pipe
+    def main():
+        raise BrokenPipeError
+
+    with open(os.path.join(tmpdir, "out.txt"), "w") as stdout, patch(
+        "sys.stdout", stdout
+    ), patch("os.dup2") as dup2:
+        with pytest.raises(SystemExit):
+            main()
+    dup2.assert_called_once()
+
+
+def test_open_fastx_read_plain_named_gz(tmpdir):
+    fasta_path = os.path.join(os.path.dirname(__file__), "data", "test.fa")
+    misnamed_path = os.path.join(tmpdir, "reads.fa.gz")
+    shutil.copy(fasta_path, misnamed_path)
+
+    assert list(seq.open_fastx_read(misnamed_path)) == list(
+        seq.readfq(open(fasta_path))
+    )
+
+
+@pytest.mark.parametrize(
+    "record,expected,expected_fasta",
+    [
+        (OtherRecord("read1", None, "ACGT", None), ">read1\nACGT\n", ">read1\nACGT\n"),
+        (
+            OtherRecord("read1", "a b", "ACGT", "IIII"),
+            "@read1 a b\nACGT\n+\nIIII\n",
+            ">read1 a b\nACGT\n",
+        ),
+    ],
+)
+def test_write_record(record, expected, expected_fasta):
+    sio = StringIO()
+    seq.write_record(record, sio)
+    assert sio.getvalue() == expected
+    read = seq.Read(record.id, record.seq, record.qual, record.desc)
+    assert str(read) + "\n" == expected
+
+    sio = StringIO()
+    seq.FastaWriter(sio).write(record)
+    assert sio.getvalue() == expected_fasta
+
+
+def test_write_record_output_format():
+    fasta = OtherRecord("read1", "a b", "ACGT", None)
+    fastq = OtherRecord("read2", None, "AC", "#~")
+    cases = [
+        (fasta, seq.OutputFormat.FASTA, ">read1 a b\nACGT\n"),
+        (fasta, seq.OutputFormat.FASTQ, "@read1 a b\nACGT\n+\nIIII\n"),
+        (fastq, seq.OutputFormat.FASTA, ">read2\nAC\n"),
+        (fastq, seq.OutputFormat.FASTQ, "@read2\nAC\n+\n#~\n"),
+        (fastq, seq.OutputFormat.AUTO, "@read2\nAC\n+\n#~\n"),
+    ]
+    for record, output_format, expected in cases:
+        sio = StringIO()
+        seq.write_record(record, sio, output_format)
+        assert sio.getvalue() == expected
+    assert seq.format_record(fasta, seq.OutputFormat.FASTQ, 0) == (
+        "@read1 a b\nACGT\n+\n!!!!"
+    )
+    assert str(seq.OutputFormat.FASTQ) == "fastq"
+
+
+@pytest.mark.parametrize("filename", ["test.fa", "test.fastq"])
+@pytest.mark.parametrize("gzipped", [False, True])
+def test_open_records(tmpdir, filename, gzipped):
+    in_path = os.path.join(os.path.dirname(__file__), "data", filename)
+    # the name says nothing about the format or compression
+    records_path = os.path.join(tmpdir, "reads")
+    with open(in_path, "rb") as infile:
+        contents = infile.read()
+    with (gzip.open if gzipped else open)(records_path, "wb") as outfile:
+        outfile.write(contents)
+
+    assert list(seq.open_records(records_path)) == list(seq.readfq(open(in_path)))
+
+
+@pytest.mark.parametrize(
+    "contents,message",
+    [
+        ("", "is empty"),
+        ("\n>read1\nACGT\n", "starts with whitespace"),
+        (" @read1\nACGT\n+\nIIII\n", "starts with whitespace"),
+        ("ACGT\n", "doesn't look like fasta or fastq"),
+    ],
+)
+def test_open_records_bad(tmpdir, contents, message):
+    records_path = os.path.join(tmpdir, "reads.fq")
+    with open(records_path, "w") as fp:
+        fp.write(contents)
+
+    with pytest.raises(seq.SeqError, match=message) as excinfo:
+        list(seq.open_records(records_path))
+    assert records_path in str(excinfo.value)
+
+
+@pytest.mark.parametrize("filename", ["reads.fa", "reads.fa.gz"])
+def test_non_ascii_round_trip(tmpdir, filename):
+    # a Latin-1 description, which isn't valid UTF-8
+    contents = b">read1 caf\xe9\nACGT\n>read2\nAC\xffGT\n"
+    in_path = os.path.join(tmpdir, "in.fa")
+    with open(in_path, "wb") as fp:
+        fp.write(contents)
+
+    reads = list(seq.open_records(in_path))
+    assert reads[0].desc == "caf\udce9"
+    out_path = os.path.join(tmpdir, filename)
+    with seq.open_text_write(out_path) as fp:
+        for read in reads:
+            read.print(file=fp)
+
+    with seq.open_text_read(out_path) as fp:
+        assert list(seq.readfq(fp)) == reads
+    with (gzip.open if filename.endswith(".gz") else open)(out_path, "rb") as fp:
+        assert fp.read() == contents
+
+
+def test_open_text_write_gzip_deterministic(tmpdir):
+    out_path = os.path.join(tmpdir, "reads.fa.gz")
+    with seq.open_text_write(out_path) as fp:
+        seq.Read("read1", "ACGT").print(file=fp)
+    with open(out_path, "rb") as fp:
+        contents = fp.read()
+    # no modification time in the header
+    assert contents[4:8] == bytes(4)
+    with gzip.open(out_path, "rt") as fp:
+        assert fp.read() == ">read1\nACGT\n"
+
+
+@pytest.mark.parametrize("filename", ["reads.fa", "reads.fa.gz"])
+def test_sync_outfile(tmpdir, filename):
+    out_path = os.path.join(tmpdir, filename)
+    with seq.open_text_write(out_path) as fp:
+        seq.Read("read1", "ACGT").print(file=fp)
+        length = seq.sync_outfile(fp)
+        assert length == os.path.getsize(out_path)
+        seq.Read("read2", "ACGT").print(file=fp)
+
+    # cut back to the sync and added to again, the file is still whole
+    os.truncate(out_path, length)
+    with seq.open_text_write(out_path, append=True) as fp:
+        seq.Read("read3", "ACGT").print(file=fp)
+    with seq.open_text_read(out_path) as fp:
+        assert [read.name for read in seq.readfq(fp)] == ["read1", "read3"]
+
+
+def test_open_records_checks_fastq(tmpdir):
+    records_path = os.path.join(tmpdir, "reads.fq")
+    with open(records_path, "w") as fp:
+        fp.write("@read1\nACGT\n+\nIII\n")
+
+    with pytest.raises(seq.FastqError, match="quality length 3"):
+        list(seq.open_records(records_path))
+
+
+@pytest.mark.parametrize(
+    "line_width,expected",
+    [
+        (60, ">read1\nAGATAGAGGACTGA\n"),
+        (0, ">read1\nAGATAGAGGACTGA\n"),
+        (5, ">read1\nAGATA\nGAGGA\nCTGA\n"),
+        (7, ">read1\nAGATAGA\nGGACTGA\n"),
+    ],
+)
+def test_fasta_writer(line_width, expected):
+    sio = StringIO()
+    with seq.FastaWriter(sio, line_width) as writer:
+        writer.write(seq.Read("read1", "AGATAGAGGACTGA", "%()%%%(%(++***"))
+    assert sio.getvalue() == expected
+
+
+@pytest.mark.parametrize("line_width", [0, 1, 10, 60])
+def test_fasta_writer_round_trip(tmpdir, line_width):
+    fasta_path = os.path.join(os.path.dirname(__file__), "data", "test.fa")
+    out_path = os.path.join(tmpdir, "out.fa.gz")
+    with seq.FastaWriter(gzip.open(out_path, "wt"), line_width) as writer:
+        for read in seq.readfq(open(fasta_path)):
+            writer.write(read)
+    writer.file.close()
+
+    assert list(seq.open_fastx_read(out_path)) == list(seq.readfq(open(fasta_path)))
+
+
+@pytest.mark.parametrize(
+    "defline,name,desc",
+    [
+        ("read1", "read1", None),
+        ("read1 ", "read1", None),
+        ("read1 well=12 barcode=AC", "read1", "well=12 barcode=AC"),
+        ("read1   spaced  out", "read1", "  spaced  out"),
+    ],
+)
+def test_read_description(defline, name, desc):
+    fasta = f">{defline}\nACGT\n"
+    fastq = f"@{defline}\nACGT\n+\nIIII\n"
+    for read in [
+        *seq.readfq(StringIO(fasta)),
+        *seq.readfq(StringIO(fastq)),
+        *seq.read_fastq(StringIO(fastq)),
+    ]:
+        assert read.name == name
+        assert read.desc == desc
+
+    if desc is not None:
+        assert str(next(seq.readfq(StringIO(fasta)))) == fasta.rstrip("\n")
+        assert str(next(seq.readfq(StringIO(fastq)))) == fastq.rstrip("\n")
+        sio = StringIO()
+        seq.FastaWriter(sio).write(next(seq.readfq(StringIO(fasta))))
+        assert sio.getvalue() == fasta
+
+
+@pytest.mark.parametrize(
+    "defline,desc",
+    [
+        ("read1\tBC:Z:ACGT", "BC:Z:ACGT"),
+        ("read1\t", None),
+        ("read1\t\tx y", "\tx y"),
+        ("read1 \tx", "\tx"),
+        ("read1\t x", " x"),
+    ],
+)
+def test_read_description_tab(defline, desc):
+    # e.g., from samtools fastq -T, which puts tags after a tab
+    fastq = f"@{defline}\nACGT\n+\nIIII\n"
+    for read in [*seq.readfq(StringIO(fastq)), *seq.read_fastq(StringIO(fastq))]:
+        assert (read.name, read.desc) == ("read1", desc)
+
+
+def test_multiline_fasta_to_string():
+    fasta = ">read1 desc\nAGATA\nGAGGA\nCTGA\n>read2\nAGG\nGGA\n"
+    reads = list(seq.readfq(StringIO(fasta)))
+    assert [str(read) for read in reads] == [
+        ">read1 desc\nAGATAGAGGACTGA",
+        ">read2\nAGGGGA",
+    ]
+
+    sio = StringIO()
+    writer = seq.FastaWriter(sio, line_width=5)
+    for read in reads:
+        writer.write(read)
+    assert sio.getvalue() == ">read1 desc\nAGATA\nGAGGA\nCTGA\n>read2\nAGGGG\nA\n"
+
+
+def test_read_fasta_strict():
+    fasta_path = os.path.join(os.path.dirname(__file__), "data", "test.fa")
+
+    with open(fasta_path) as strict_fp, open(fasta_path) as loose_fp:
+        assert list(seq.read_fasta(strict_fp)) == list(seq.readfq(loose_fp))
+
+
+@pytest.mark.parametrize(
+    "contents,line_number,message",
+    [
+        (">read1\nACGT\n>\nACGT\n", 3, "line 3: defline contains no ID: '>'"),
+        ("\nACGT\n>read1\nACGT\n", 2, "line 2: sequence before first defline: 'ACGT'"),
+    ],
+)
+def test_read_fasta_malformed(contents, line_number, message):
+    with pytest.raises(seq.FastaError) as excinfo:
+        list(seq.read_fasta(StringIO(contents)))
+    assert excinfo.value.line_number == line_number
+    assert str(excinfo.value) == message
+
+
+def test_read_fasta_skip_malformed():
+    errors = []
+    contents = "ACGT\n>read1\nACGT\n>\nGGGG\nCCCC\n>read2\nTT\n"
+    reads = list(seq.read_fasta(StringIO(contents), errors.append))
+    assert reads == [seq.Read("read1", "ACGT"), seq.Read("read2", "TT")]
+    assert [error.line_number for error in errors] == [1, 4]
+
+
+def test_open_records_skip_malformed(tmpdir):
+    records_path = os.path.join(tmpdir, "reads.fq.gz")
+    with gzip.open(records_path, "wt") as fp:
+        fp.write("@read1\nACGT\n+\nIII\n@read2\nACGT\n+\nIIII\n")
+
+    errors = []
+    reads = list(seq.open_records(records_path, errors.append))
+    assert [read.name for read in reads] == ["read2"]
+    assert len(errors) == 1
+
+
+def test_pair_reads():
+    reads_1 = [seq.Read("pair1/1", "ACGT"), seq.Read("pair2", "GGGG")]
+    reads_2 = [seq.Read("pair1/2", "TTTT"), seq.Read("pair2", "CCCC")]
+    assert list(seq.pair_reads(reads_1, reads_2)) == list(zip(reads_1, reads_2))
+    interleaved = [reads_1[0], reads_2[0], reads_1[1], reads_2[1]]
+    assert list(seq.deinterleave(interleaved)) == list(zip(reads_1, reads_2))
+
+
+def test_pair_reads_out_of_sync():
+    reads_1 = [seq.Read("pair1/1", "ACGT"), seq.Read("pair2/1", "GGGG")]
+    reads_2 = [seq.Read("pair1/2", "TTTT"), seq.Read("pair3/2", "CCCC")]
+    with pytest.raises(seq.SeqError, match="read pair 2: mate names"):
+        list(seq.pair_reads(reads_1, reads_2))
+    with pytest.raises(seq.SeqError, match="read pair 2: the second mates ran out"):
+        list(seq.pair_reads(reads_1, reads_2[:1]))
+    with pytest.raises(seq.SeqError, match="read pair 2: the first mates ran out"):
+        list(seq.pair_reads(reads_1[:1], reads_2))
+    with pytest.raises(seq.SeqError, match="read pair 2: the second mates ran out"):
+        list(seq.deinterleave([reads_1[0], reads_2[0], reads_1[1]]))
+
+
+@pytest.mark.parametrize(
+    "filename,read_strict",
+    [("test.fa", seq.read_fasta), ("test.fastq", seq.read_fastq)],
+)
+def test_read_crlf_and_comments(filename, read_strict):
+    clean_path = os.path.join(os.path.dirname(__file__), "data", filename)
+    # CRLF line endings, with blank lines between records and, for fasta,
+    # comment lines
+    messy_path = clean_path.replace("test.", "test.crlf.")
+    with open(clean_path) as fp:
+        expected = list(seq.readfq(fp))
+
+    # newline="" keeps the \r's that python would otherwise translate away
+    for read_records in [seq.readfq, read_strict]:
+        with open(messy_path, newline="") as fp:
+            assert list(read_records(fp)) == expected
+    assert list(seq.open_records(messy_path)) == expected
+
+
+def test_read_fasta_keeps_case():
+    reads = list(seq.read_fasta(StringIO(">read1\nACGTacgt\nnnNN\n")))
+    assert reads[0].seq == "ACGTacgtnnNN"
+
+
+@pytest.mark.parametrize(
+    "quals",
+    [
+        ["IIII#III", "IIIIIIII"],  # Illumina
+        ["~~~~~~~~", "~~~~~~%~"],  # PacBio HiFi
+        ["~~~~~~~~", "~~~~~~~~"],
+    ],
+)
+def test_check_qualities(quals):
+    reads = [seq.Read(f"read{i}", "ACGTACGT", qual) for i, qual in enumerate(quals)]
+    assert list(seq.check_qualities(reads)) == reads
+
+
+def test_check_qualities_phred64():
+    reads = [seq.Read("read1", "ACGTACGT", "hhhhBhhh")]
+    with pytest.raises(seq.SeqError, match="Phred\\+64"):
+        list(seq.check_qualities(reads))
+
+
+def test_check_qualities_fasta():
+    reads = [seq.Read("read1", "ACGT", "IIII"), seq.Read("read2", "ACGT")]
+    with pytest.raises(seq.SeqError, match="'read2' has no quality scores"):
+        list(seq.check_qualities(reads))
+
+
+@pytest.mark.parametrize(
+    "quals,encoding",
+    [
+        # "?" is below the lowest Phred+64 character, "@"
+        (["?hhhhhhh"], seq.QualityEncoding.PHRED33),
+        (["@hhhhhhh"], seq.QualityEncoding.PHRED64),
+        # as are Solexa's ";" to "?", which aren't told apart from Phred+33
+        ([";hhhhhhh"], seq.QualityEncoding.PHRED33),
+        (["?@@@", "hhhh"], seq.QualityEncoding.PHRED33),
+        # "J" is the highest Illumina Phred+33 character, "K" is above it
+        (["@JJJ", "IIII"], None),
+        (["@JJJ", "IIIK"], seq.QualityEncoding.PHRED64),
+        # "i" is the highest Phred+64 character, "j" is above it
+        (["iiii"], seq.QualityEncoding.PHRED64),
+        (["jiii"], seq.QualityEncoding.PHRED33),
+        (["~~~~"], seq.QualityEncoding.PHRED33),
+        (["IIII#III"], seq.QualityEncoding.PHRED33),
+        ([], None),
+        ([""], None),
+    ],
+)
+def test_detect_quality_encoding(quals, encoding):
+    assert seq.detect_quality_encoding(quals) == encoding
+
+
+def test_convert_qualities():
+    reads = [seq.Read("read1", "ACGT", "@Jhi"), seq.Read("read2", "ACGT")]
+    # "@" is quality 0 and "h" 40
+    expected = [replace(reads[0], qual="!+IJ"), reads[1]]
+    assert list(seq.convert_qualities(reads)) == expected
+    phred64 = seq.QualityEncoding.PHRED64
+    assert list(seq.convert_qualities(reads, phred64)) == expected
+    assert list(seq.convert_qualities(reads, seq.QualityEncoding.PHRED33)) == reads
+    assert reads[0].qual == "@Jhi"
+    # Solexa's ";", quality -5, becomes 0 when the reads are said to be
+    # Phred+64, but makes the qualities look like Phred+33 otherwise
+    solexa = [seq.Read("read1", "ACGT", ";@hi")]
+    assert [read.qual for read in seq.convert_qualities(solexa, phred64)] == ["!!IJ"]
+    assert list(seq.convert_qualities(solexa)) == solexa
+
+
+def test_convert_qualities_detects_from_first_reads(caplog):
+    reads = [seq.Read(f"read{i}", "ACGT", "IIII") for i in range(3)]
+    reads.append(seq.Read("read3", "ACGT", "hhhh"))
+    # the Phred+64 read is past the reads looked at
+    assert list(seq.convert_qualities(reads, num_reads=3)) == reads
+    assert "taking them to be Phred+33" in caplog.text
+    assert "from 'I' to 'I'" in caplog.text
+    caplog.clear()
+    converted = list(seq.convert_qualities(reads, num_reads=4))
+    assert [read.qual for read in converted] == ["****"] * 3 + ["IIII"]
+    assert "taking them" not in caplog.text
+    # no qualities, no warning
+    list(seq.convert_qualities([seq.Read("read1", "ACGT")]))
+    assert "taking them" not in caplog.text
+
+
+def test_open_records_quality_encoding(tmpdir):
+    reads_path = os.path.join(tmpdir, "reads.fq")
+    with open(reads_path, "w") as fp:
+        fp.write("@read1\nACGT\n+\nhhBh\n")
+    assert [read.qual for read in seq.open_records(reads_path)] == ["hhBh"]
+    assert [
+        read.qual
+        for read in seq.open_records(reads_path, None, seq.QualityEncoding.AUTO)
+    ] == ["II#I"]
+    with pytest.raises(seq.SeqError, match="Phred\\+64"):
+        list(seq.check_qualities(seq.open_records(reads_path)))
+
+
+@pytest.mark.parametrize(
+    "sequence,counts,gc",
+    [
+        ("", (0, 0, 0, 0, 0), 0.0),
+        ("NNNN", (0, 0, 0, 0, 4), 0.0),
+        ("ACGT", (1, 1, 1, 1, 0), 0.5),
+        ("acgtNNgg", (1, 1, 3, 1, 2), 4 / 6),
+        ("AAAT", (3, 0, 0, 1, 0), 0.0),
+        ("GCgc-", (0, 2, 2, 0, 1), 1.0),
+    ],
+)
+def test_base_counts(sequence, counts, gc):
+    records = [seq.Read("read1", sequence), OtherRecord("read1", None, sequence, None)]
+    for record in records:
+        assert seq.base_counts(record) == counts
+        assert seq.gc_content(record) == pytest.approx(gc)
+    assert len(seq.Read("read1", sequence)) == len(sequence)
+
+
+def test_stats():
+    stats_path = os.path.join(os.path.dirname(__file__), "data", "stats.fa")
+    stats = seq.Stats.from_records(seq.open_fastx_read(stats_path))
+    assert stats.to_dict() == {
+        "records": 6,
+        "bases": 30,
+        "min_length": 2,
+        "mean_length": 5.0,
+        "median_length": 4.5,
+        "max_length": 10,
+        "n50": 6,
+        "n90": 3,
+        "gc_content": 0.5,
+        "n_bases": 6,
+    }
+    assert stats.nx(1.0) == 2
+    assert stats.nx(1 / 3) == 10
+    assert stats.nx(0.34) == 6
+    stats.add(seq.Read("read7", "ACG"))
+    assert stats.median_length == 4
+    assert stats.length_counts[3] == 2
+    with pytest.raises(ValueError, match="fraction"):
+        stats.nx(0)
+
+
+def test_stats_empty():
+    stats = seq.Stats.from_records([seq.Read("empty", "")])
+    assert (stats.min_length, stats.median_length, stats.n50) == (0, 0, None)
+    assert seq.Stats().to_dict() == {
+        "records": 0,
+        "bases": 0,
+        "min_length": None,
+        "mean_length": None,
+        "median_length": None,
+        "max_length": None,
+        "n50": None,
+        "n90": None,
+        "gc_content": 0.0,
+        "n_bases": 0,
+    }
+
+
+def test_subseq():
+    read = seq.Read("read1", "ACGTNacgtn", "ABCDEFGHIJ", "desc")
+    assert read.subseq(2, 7) == seq.Read("read1:2-7", "GTNac", "CDEFG", "desc")
+    assert read.subseq(0, 10) == seq.Read("read1:0-10", read.seq, read.qual, "desc")
+    assert read.subseq(4, 4) == seq.Read("read1:4-4", "", "", "desc")
+    assert seq.Read("read1", "ACGT").subseq(1, 3) == seq.Read("read1:1-3", "CG")
+
+    for start, end in [(-1, 2), (3, 11), (5, 4)]:
+        with pytest.raises(seq.CoordinateError, match=f"range {start}-{end}"):
+            read.subseq(start, end)
+
+
+@pytest.mark.parametrize(
+    "sequence,expected",
+    [
+        ("", ""),
+        ("ACGT", "ACGT"),
+        ("AAACN", "NGTTT"),
+        ("GATTACAnnnacgt", "acgtnnnTGTAATC"),
+        # anything else is left as it is
+        ("AC-RG", "CR-GT"),
+    ],
+)
+def test_reverse_complement(sequence, expected):
+    read = seq.Read("read1", sequence, "ABCDEFGHIJKLMN"[: len(sequence)])
+    rc = read.reverse_complement()
+    assert rc == seq.Read("read1_rc", expected, read.qual[::-1])
+    assert rc.reverse_complement().seq == sequence
+
+
+def test_rna():
+    read = seq.Read("read1", "ACGUNacgu", "IIIIIIIII", "desc")
+    assert read.is_rna()
+    assert read.to_dna() == seq.Read("read1", "ACGTNacgt", "IIIIIIIII", "desc")
+    assert not read.to_dna().is_rna()
+    # the reverse complement of RNA is DNA
+    assert read.reverse_complement().seq == "acgtNACGT"
+    for sequence in ["", "ACGN", "ACGT", "ACGUT", "acgUt"]:
+        assert not seq.Read("read1", sequence).is_rna()
+    assert seq.Read("read1", "aaau").is_rna()
+    assert seq.rna_to_dna("UuAC") == "TtAC"
+
+
+@pytest.mark.parametrize(
+    "sequence,min_gap,pieces",
+    [
+        ("ACGTNNNGGCC", 3, [(0, "ACGT"), (7, "GGCC")]),
+        ("ACGTNNNGGCC", 4, [(0, "ACGTNNNGGCC")]),
+        # gaps at the ends are trimmed, and soft-masked N's are gaps too
+        ("NNNACnnnnGTNNN", 3, [(3, "AC"), (9, "GT")]),
+        ("NNACNNNNGTNN", 3, [(0, "NNAC"), (8, "GTNN")]),
+        ("ANA", 1, [(0, "A"), (2, "A")]),
+        ("NNNNN", 3, []),
+        ("", 3, []),
+    ],
+)
+def test_split_at_n(sequence, min_gap, pieces):
+    qual = "".join(chr(33 + i) for i in range(len(sequence)))
+    scaffold = seq.Read("scaffold1", sequence, qual)
+    split = seq.split_at_n(scaffold, min_gap)
+    assert [(seq.split_origin(piece), piece.seq) for piece in split] == [
+        (("scaffold1", offset), piece_seq) for offset, piece_seq in pieces
+    ]
+    assert [piece.name for piece in split] == [
+        f"scaffold1_part{i}" for i in range(1, len(pieces) + 1)
+    ]
+    for piece, (offset, piece_seq) in zip(split, pieces):
+        assert piece.qual == qual[offset : offset + len(piece_seq)]
+        assert piece.desc == f"scaffold=scaffold1 offset={offset}"
+
+
+def test_split_at_n_desc():
+    scaffold = seq.Read("scaffold1", "ACNNNNNNNNNNGT", None, "len=14 scaffold=x")
+    pieces = seq.split_at_n(scaffold)
+    assert [(piece.seq, piece.qual) for piece in pieces] == [("AC", None), ("GT", None)]
+    assert pieces[1].desc == "scaffold=scaffold1 offset=12 len=14 scaffold=x"
+    assert seq.split_origin(pieces[1]) == ("scaffold1", 12)
+    assert seq.split_origin(scaffold) is None
+    assert seq.split_origin(seq.Read("contig1", "ACGT")) is None
+    assert seq.split_origin(seq.Read("c", "A", None, "scaffold=s offset=1x")) is None
+    with pytest.raises(ValueError, match="min_gap must be at least 1, not 0"):
+        seq.split_at_n(scaffold, 0)
+
+
+def test_dedup():
+    reads = [
+        seq.Read("read1", "ACGGT", "IIIII"),
+        seq.Read("read2", "ACGGT", "#####"),
+        # the reverse complement of read1
+        seq.Read("read3", "ACCGT"),
+        seq.Read("read1", "ACGGT"),
+        seq.Read("read4", "TTTT"),
+    ]
+    counts = seq.DedupCounts()
+    assert list(seq.dedup(reads, counts=counts)) == [reads[0], reads[2], reads[4]]
+    assert counts == seq.DedupCounts(records=5, removed=2)
+    assert list(seq.dedup(reads, consider_revcomp=True)) == [reads[0], reads[4]]
+    assert list(seq.dedup(reads, same_id=True)) == [*reads[:3], reads[4]]
+    # the ids are kept apart from the sequences
+    swapped = [seq.Read("read1", "A"), seq.Read("read1\0A", "")]
+    assert list(seq.dedup(swapped, same_id=True)) == swapped
+
+
+def test_dedup_pairs():
+    def pair(seq_1, seq_2, name="pair"):
+        return seq.Read(f"{name}/1", seq_1), seq.Read(f"{name}/2", seq_2)
+
+    pairs = [
+        pair("ACGT", "GGCC"),
+        pair("ACGT", "GGCC", "other"),
+        # the same bases split between the mates differently
+        pair("ACG", "TGGCC"),
+        pair("ACGT", "GGCA"),
+        # the first pair with its mates swapped, read from the other strand
+        pair("GGCC", "ACGT"),
+    ]
+    counts = seq.DedupCounts()
+    assert list(seq.dedup(pairs, counts=counts)) == [pairs[0], *pairs[2:]]
+    assert counts.removed == 1
+    assert list(seq.dedup(pairs, consider_revcomp=True)) == [pairs[0], *pairs[2:4]]
+    assert list(seq.dedup(pairs, same_id=True)) == pairs
+
+
+def test_dedup_exact():
+    reads = [
+        seq.Read("read1", "AACG"),
+        seq.Read("read2", "GGCC"),
+        seq.Read("read3", "AACG"),
+        seq.Read("read4", "AACG"),
+        # the reverse complement of read1
+        seq.Read("read5", "CGTT"),
+    ]
+    opened = []
+
+    def open_reads():
+        opened.append(True)
+        return iter(reads)
+
+    counts = seq.DedupCounts()
+    expected = [reads[0], reads[1], reads[4]]
+    assert list(seq.dedup_exact(open_reads, counts=counts)) == expected
+    assert len(opened) == 2
+    assert counts == seq.DedupCounts(records=5, removed=2)
+    assert list(seq.dedup_exact(lambda: reads, consider_revcomp=True)) == [
+        reads[0],
+        reads[1],
+    ]
+
+    # with every hash the same, only true duplicates are removed with --exact
+    with patch("trio_binning.seq._dedup_digest", lambda key: b"\0" * 16):
+        assert list(seq.dedup(reads)) == [reads[0]]
+        counts = seq.DedupCounts()
+        assert list(seq.dedup_exact(lambda: reads, counts=counts)) == expected
+    assert counts == seq.DedupCounts(records=5, removed=2, collisions=2)
+
+
+def test_in_subsample():
+    names = [f"read{i}" for i in range(10000)]
+    subsample = [name for name in names if seq.in_subsample(name, 0.1, 42)]
+    assert 900 < len(subsample) < 1100
+    # the same reads are picked every time, in any order...
+    assert [
+        name for name in reversed(names) if seq.in_subsample(name, 0.1, 42)
+    ] == subsample[::-1]
+    # ...a bigger fraction picks more of them...
+    assert set(subsample) < {name for name in names if seq.in_subsample(name, 0.2, 42)}
+    # ...and another seed picks others
+    assert subsample != [name for name in names if seq.in_subsample(name, 0.1, 43)]
+    assert all(seq.in_subsample(name, 1.0) for name in names)
+
+
+@pytest.mark.parametrize(
+    "qual,expected",
+    [
+        ("", 0.0),
+        ("IIII", 40.0),
+        ("+", 10.0),
+        # the mean of the error probabilities 0.0001 and 0.1, not of 40 and 10
+        ("I+", 13.0060),
+        ("!!", 0.0),
+    ],
+)
+def test_mean_quality(qual, expected):
+    assert seq.mean_quality(qual) == pytest.approx(expected, abs=1e-4)
+
+
+def random_reads(num_reads, seed=0, with_quals=True):
+    rng = random.Random(seed)
+    reads = []
+    for i in range(num_reads):
+        length = rng.randint(1, 500)
+        bases = "".join(rng.choices("ACGT", k=length))
+        reads.append(seq.Read(f"read{i}", bases, "I" * length if with_quals else None))
+    return reads
+
+
+@pytest.mark.parametrize("by", list(seq.SplitBy))
+def test_split(tmpdir, by):
+    reads = random_reads(1000)
+    prefix = os.path.join(tmpdir, "reads")
+    shards = seq.split(reads, 12, prefix, ".fastq", True, by)
+    assert [shard.paths for shard in shards] == [
+        [f"{prefix}.{i:02d}.fastq.gz"] for i in range(1, 13)
+    ]
+    assert sorted(os.listdir(tmpdir)) == [
+        f"reads.{i:02d}.fastq.gz" for i in range(1, 13)
+    ]
+
+    split_reads = [
+        read for shard in shards for read in seq.open_fastx_read(shard.paths[0])
+    ]
+    # no read is lost or duplicated
+    assert sorted(split_reads, key=lambda read: read.name) == sorted(
+        reads, key=lambda read: read.name
+    )
+    assert sum(shard.reads for shard in shards) == 1000
+    assert sum(shard.bases for shard in shards) == sum(len(read) for read in reads)
+    if by == seq.SplitBy.READS:
+        assert {shard.reads for shard in shards} == {83, 84}
+    else:
+        bases = [shard.bases for shard in shards]
+        assert max(bases) - min(bases) <= max(len(read) for read in reads)
+
+
+def test_split_fasta(tmpdir):
+    reads = random_reads(5, with_quals=False)
+    shards = seq.split(reads, 2, os.path.join(tmpdir, "reads"))
+    with open(shards[0].paths[0]) as shard_file:
+        assert shard_file.read().startswith(">read0\n")
+    assert (shards[0].reads, shards[1].reads) == (3, 2)
+    with pytest.raises(ValueError, match="at least 1"):
+        seq.split(reads, 0, os.path.join(tmpdir, "reads"))
+
+
+@pytest.mark.parametrize("interleave", [False, True])
+def test_split_pairs(tmpdir, interleave):
+    reads = random_reads(200)
+    pairs = [
+        (replace(mate_1, name=f"pair{i}/1"), replace(mate_2, name=f"pair{i}/2"))
+        for i, (mate_1, mate_2) in enumerate(zip(reads[::2], reads[1::2]))
+    ]
+    prefix = os.path.join(tmpdir, "pairs")
+    shards = seq.split_pairs(
+        pairs, 3, prefix, ".fq", by=seq.SplitBy.BASES, interleave=interleave
+    )
+    for i, shard in enumerate(shards, 1):
+        if interleave:
+            assert shard.paths == [f"{prefix}.{i}.fq"]
+            shard_pairs = list(seq.deinterleave(seq.open_fastx_read(shard.paths[0])))
+        else:
+            assert shard.paths == [f"{prefix}.{i}_R1.fq", f"{prefix}.{i}_R2.fq"]
+            shard_pairs = list(
+                zip(*(seq.open_fastx_read(mate_path) for mate_path in shard.paths))
+            )
+        assert shard.reads == 2 * len(shard_pairs)
+        assert shard.bases == sum(len(mate) for pair in shard_pairs for mate in pair)
+        # the mates of each pair stay together
+        assert all(pair in pairs for pair in shard_pairs)
+    assert sum(shard.reads for shard in shards) == 200
+
+
+def test_read_ids():
+    assert seq.read_ids(["read1\n", "\n", "read2 length=100\n", "  read3\n"]) == {
+        "read1",
+        "read2",
+        "read3",
+    }
+
+
+def test_filter_by_id():
+    reads = [
+        seq.Read("read1", "ACGT"),
+        seq.Read("read2", "ACGT", desc="length=4"),
+        seq.Read("read3", "ACGT"),
+    ]
+    found = set()
+    kept = seq.filter_by_id(reads, {"read2", "read3", "read4"}, found=found)
+    assert [read.name for read in kept] == ["read2", "read3"]
+    assert found == {"read2", "read3"}
+    dropped = seq.filter_by_id(reads, {"read2"}, invert=True)
+    assert [read.name for read in dropped] == ["read1", "read3"]
+
+
+def test_id_set():
+    ids = seq.IdSet(num_shards=4)
+    assert ids.add("read1")
+    assert ids.add("read2")
+    assert not ids.add("read1")
+    assert "read2" in ids
+    assert "read3" not in ids
+    assert len(ids) == 2
+
+    # names with non-ASCII bytes, as read with surrogateescape
+    assert ids.add("r\udce91")
+    assert "r\udce91" in ids
+    assert "r\udce81" not in ids
+
+
+def test_id_set_threads():
+    ids = seq.IdSet()
+    names = [f"read{i}" for i in range(1000)]
+    with ThreadPoolExecutor(4) as executor:
+        added = list(executor.map(ids.add, names + names))
+    assert sum(added) == 1000
+    assert len(ids) == 1000
+
+
+def test_id_set_invalid():
+    with pytest.raises(ValueError):
+        seq.IdSet(num_shards=0)
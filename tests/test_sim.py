@@ -0,0 +1,94 @@
+import pytest
+
+from trio_binning import kmers
+from trio_binning.classify import Bin, ClassifyOptions, classify_seq
+from trio_binning.seq import Read
+from trio_binning.sim import simulate_trio
+
+
+def test_simulate_trio():
+    trio = simulate_trio(20000, 0.005, 1000, 5, 0.0, seed=1, k=15)
+    assert len(trio.hap_a) == len(trio.hap_b) == 20000
+    num_het_sites = sum(a != b for a, b in zip(trio.hap_a, trio.hap_b))
+    assert 50 < num_het_sites < 150
+    assert len(trio.hap_a_kmers) > 0
+    assert trio.hap_a_kmers.intersection_size(trio.hap_b_kmers) == 0
+    hap_b_kmers = set(kmers.iter_canonical_kmers(trio.hap_b, 15))
+    assert all(
+        kmer in trio.hap_a_kmers
+        for kmer in kmers.iter_canonical_kmers(trio.hap_a, 15)
+        if kmer not in hap_b_kmers
+    )
+
+    assert len(trio.reads) == 100
+    assert {read.truth for read in trio.reads} == {Bin.HAP_A, Bin.HAP_B}
+    assert {read.reverse for read in trio.reads} == {False, True}
+    for simulated_read in trio.reads:
+        read = simulated_read.read
+        haplotype = trio.hap_a if simulated_read.truth == Bin.HAP_A else trio.hap_b
+        expected = Read(
+            read.name,
+            haplotype[simulated_read.start : simulated_read.start + 1000],
+            read.qual,
+            read.desc,
+        )
+        if simulated_read.reverse:
+            expected = expected.reverse_complement()
+        # without errors, the reads are exactly the haplotype
+        assert read.seq == expected.seq
+        assert read.desc == (
+            f"hap={simulated_read.truth.value} start={simulated_read.start} "
+            f"strand={'-' if simulated_read.reverse else '+'}"
+        )
+
+
+def test_simulate_trio_seed():
+    trio_1 = simulate_trio(5000, 0.01, 500, 2, 0.01, seed=2)
+    trio_2 = simulate_trio(5000, 0.01, 500, 2, 0.01, seed=2)
+    assert trio_1.hap_b == trio_2.hap_b
+    assert trio_1.reads == trio_2.reads
+    assert simulate_trio(5000, 0.01, 500, 2, 0.01, seed=3).hap_a != trio_1.hap_a
+
+
+def test_simulate_trio_errors():
+    trio = simulate_trio(10000, 0.0, 10000, 1, 0.01, seed=4)
+    assert len(trio.hap_a_kmers) == len(trio.hap_b_kmers) == 0
+    (simulated_read,) = trio.reads
+    haplotype = trio.hap_a
+    if simulated_read.reverse:
+        haplotype = Read("hap", haplotype).reverse_complement().seq
+    num_errors = sum(a != b for a, b in zip(simulated_read.read.seq, haplotype))
+    assert 50 < num_errors < 150
+    # Phred 20 is 1% errors
+    assert set(simulated_read.read.qual) == {"5"}
+
+
+@pytest.mark.parametrize(
+    "args,message",
+    [
+        ((1000, 1.5, 100, 1, 0.0), "het_rate must be between 0 and 1"),
+        ((1000, 0.01, 100, 1, -0.1), "error_rate must be between 0 and 1"),
+        ((1000, 0.01, 2000, 1, 0.0), "read_len must be from k"),
+        ((1000, 0.01, 10, 1, 0.0), "read_len must be from k"),
+        ((1000, 0.01, 100, -1, 0.0), "coverage must not be negative"),
+    ],
+)
+def test_simulate_trio_invalid(args, message):
+    with pytest.raises(ValueError, match=message):
+        simulate_trio(*args)
+
+
+def test_classification_accuracy():
+    # long reads at 0.1% error, like HiFi
+    trio = simulate_trio(200000, 0.001, 10000, 5, 0.001, seed=69)
+    options = ClassifyOptions(min_hits=5)
+    num_correct = num_classified = 0
+    for simulated_read in trio.reads:
+        classification = classify_seq(
+            simulated_read.read.seq, trio.hap_a_kmers, trio.hap_b_kmers, options=options
+        )
+        if classification.bin != Bin.UNKNOWN:
+            num_classified += 1
+            num_correct += classification.bin == simulated_read.truth
+    assert num_classified > 0.95 * len(trio.reads)
+    assert num_correct > 0.99 * num_classified